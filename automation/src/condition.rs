@@ -0,0 +1,527 @@
+//! 规则条件
+//!
+//! 条件是由读数名称、`time`、数字和运算符组成的表达式，例如
+//! `bme280.temp - thermostat.setpoint > 2 or time < 6:30`：
+//!
+//! - `time` 是本地时间从零点起的分钟数，`HH:MM` 写法的常数同样换算为分钟
+//! - 运算符的优先级从低到高为 `or`、`and`、`not`、比较（`>` `<` `>=` `<=` `==` `!=`）、
+//!   `+` `-`、`*` `/`、负号，括号改变优先级；比较成立为 1，不成立为 0，
+//!   非 0 的值视为成立（NaN 除外）
+//!
+//! 读数不存在或时间未知时对应的值为 NaN，表示“未知”：算术和比较的结果仍是 NaN，
+//! `not` 未知仍是未知；`and` 有一侧不成立时不成立，`or` 有一侧成立时成立，其余情况为未知。
+//! 条件的结果为未知时视为不成立。
+//!
+//! # 字节码
+//!
+//! 条件编译为栈式字节码，求值时不再解析文本。每条指令以一个字节的操作码开始，
+//! `PUSH` 后跟 4 字节的小端 f32 常数，`LOAD` 后跟 1 字节的读数名称下标，其余指令没有操作数。
+//! 求值时检查栈深度和操作数，字节码有误时条件视为不成立。
+
+use alloc::string::String;
+use heapless::Vec;
+
+/// 条件的最大词法单元数
+const MAX_TOKENS: usize = 32;
+
+/// 条件的最大字节码长度
+const MAX_CODE_LEN: usize = 64;
+
+/// 条件最多引用的读数数量
+const MAX_NAMES: usize = 8;
+
+/// 求值栈深度
+const MAX_STACK: usize = 8;
+
+const OP_PUSH: u8 = 0x01;
+const OP_LOAD: u8 = 0x02;
+const OP_TIME: u8 = 0x03;
+const OP_NEG: u8 = 0x10;
+const OP_ADD: u8 = 0x11;
+const OP_SUB: u8 = 0x12;
+const OP_MUL: u8 = 0x13;
+const OP_DIV: u8 = 0x14;
+const OP_GT: u8 = 0x20;
+const OP_LT: u8 = 0x21;
+const OP_GE: u8 = 0x22;
+const OP_LE: u8 = 0x23;
+const OP_EQ: u8 = 0x24;
+const OP_NE: u8 = 0x25;
+const OP_AND: u8 = 0x30;
+const OP_OR: u8 = 0x31;
+const OP_NOT: u8 = 0x32;
+
+/// 比较运算符及其操作码
+const COMPARISONS: [(&str, u8); 6] = [
+    (">", OP_GT),
+    ("<", OP_LT),
+    (">=", OP_GE),
+    ("<=", OP_LE),
+    ("==", OP_EQ),
+    ("!=", OP_NE),
+];
+
+/// 条件编译失败的原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Fault {
+    /// 语法错误，或者名称不是有效的读数名称
+    Syntax,
+    /// 条件太复杂：词法单元、字节码、读数名称或栈深度超出上限
+    TooComplex,
+}
+
+/// 编译好的条件
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Condition {
+    /// 字节码
+    code: Vec<u8, MAX_CODE_LEN>,
+    /// `LOAD` 引用的读数名称
+    names: Vec<String, MAX_NAMES>,
+}
+
+impl Condition {
+    /// 编译条件
+    ///
+    /// # 参数
+    /// * `text` - 条件原文
+    /// * `is_valid_name` - 名称是否是有效的读数名称，`time` 和关键字不经过这里
+    pub fn compile(text: &str, is_valid_name: impl Fn(&str) -> bool) -> Result<Condition, Fault> {
+        let mut compiler = Compiler {
+            tokens: lex(text)?,
+            pos: 0,
+            code: Vec::new(),
+            names: Vec::new(),
+            depth: 0,
+            is_valid_name,
+        };
+        compiler.or()?;
+        if compiler.pos != compiler.tokens.len() {
+            return Err(Fault::Syntax);
+        }
+        Ok(Condition {
+            code: compiler.code,
+            names: compiler.names,
+        })
+    }
+
+    /// 条件是否成立
+    ///
+    /// # 参数
+    /// * `load` - 按名称读取读数，不存在时返回 NaN
+    /// * `time` - 本地时间从零点起的分钟数，未知时为 NaN
+    pub fn is_met(&self, load: impl Fn(&str) -> f32, time: f32) -> bool {
+        let load = |index: usize| self.names.get(index).map_or(f32::NAN, |name| load(name));
+        eval(&self.code, load, time).is_some_and(is_true)
+    }
+}
+
+/// 词法单元
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Token<'a> {
+    /// 数字或 `HH:MM` 时刻（换算为分钟）
+    Number(f32),
+    /// 读数名称或关键字
+    Name(&'a str),
+    /// 运算符或括号
+    Symbol(&'a str),
+}
+
+/// 条件的递归下降编译器，直接生成后缀形式的字节码
+struct Compiler<'a, F> {
+    tokens: Vec<Token<'a>, MAX_TOKENS>,
+    pos: usize,
+    code: Vec<u8, MAX_CODE_LEN>,
+    names: Vec<String, MAX_NAMES>,
+    /// 执行到当前位置时的栈深度
+    depth: usize,
+    is_valid_name: F,
+}
+
+impl<'a, F: Fn(&str) -> bool> Compiler<'a, F> {
+    /// 写入一条指令
+    ///
+    /// # 参数
+    /// * `bytes` - 操作码和操作数
+    /// * `pops` - 指令弹出的值的数量，每条指令都压入一个值
+    fn emit(&mut self, bytes: &[u8], pops: usize) -> Result<(), Fault> {
+        self.code
+            .extend_from_slice(bytes)
+            .map_err(|_| Fault::TooComplex)?;
+        self.depth = self.depth + 1 - pops;
+        if self.depth > MAX_STACK {
+            return Err(Fault::TooComplex);
+        }
+        Ok(())
+    }
+
+    fn peek(&self) -> Option<Token<'a>> {
+        self.tokens.get(self.pos).copied()
+    }
+
+    /// 下一个词法单元是 `token` 时跳过它
+    fn eat(&mut self, token: Token<'_>) -> bool {
+        let matched = self.peek() == Some(token);
+        if matched {
+            self.pos += 1;
+        }
+        matched
+    }
+
+    fn or(&mut self) -> Result<(), Fault> {
+        self.and()?;
+        while self.eat(Token::Name("or")) {
+            self.and()?;
+            self.emit(&[OP_OR], 2)?;
+        }
+        Ok(())
+    }
+
+    fn and(&mut self) -> Result<(), Fault> {
+        self.not()?;
+        while self.eat(Token::Name("and")) {
+            self.not()?;
+            self.emit(&[OP_AND], 2)?;
+        }
+        Ok(())
+    }
+
+    fn not(&mut self) -> Result<(), Fault> {
+        if self.eat(Token::Name("not")) {
+            self.not()?;
+            return self.emit(&[OP_NOT], 1);
+        }
+        self.comparison()
+    }
+
+    fn comparison(&mut self) -> Result<(), Fault> {
+        self.sum()?;
+        let op = COMPARISONS
+            .iter()
+            .find(|&&(symbol, _)| self.peek() == Some(Token::Symbol(symbol)));
+        if let Some(&(_, op)) = op {
+            self.pos += 1;
+            self.sum()?;
+            self.emit(&[op], 2)?;
+        }
+        Ok(())
+    }
+
+    fn sum(&mut self) -> Result<(), Fault> {
+        self.product()?;
+        loop {
+            let op = if self.eat(Token::Symbol("+")) {
+                OP_ADD
+            } else if self.eat(Token::Symbol("-")) {
+                OP_SUB
+            } else {
+                return Ok(());
+            };
+            self.product()?;
+            self.emit(&[op], 2)?;
+        }
+    }
+
+    fn product(&mut self) -> Result<(), Fault> {
+        self.unary()?;
+        loop {
+            let op = if self.eat(Token::Symbol("*")) {
+                OP_MUL
+            } else if self.eat(Token::Symbol("/")) {
+                OP_DIV
+            } else {
+                return Ok(());
+            };
+            self.unary()?;
+            self.emit(&[op], 2)?;
+        }
+    }
+
+    fn unary(&mut self) -> Result<(), Fault> {
+        if self.eat(Token::Symbol("-")) {
+            self.unary()?;
+            return self.emit(&[OP_NEG], 1);
+        }
+        self.atom()
+    }
+
+    fn atom(&mut self) -> Result<(), Fault> {
+        let token = self.peek().ok_or(Fault::Syntax)?;
+        self.pos += 1;
+        match token {
+            Token::Number(value) => {
+                let [a, b, c, d] = value.to_le_bytes();
+                self.emit(&[OP_PUSH, a, b, c, d], 0)
+            }
+            Token::Name("time") => self.emit(&[OP_TIME], 0),
+            Token::Name(name) if (self.is_valid_name)(name) => {
+                let index = match self.names.iter().position(|known| known == name) {
+                    Some(index) => index,
+                    None => {
+                        self.names
+                            .push(String::from(name))
+                            .map_err(|_| Fault::TooComplex)?;
+                        self.names.len() - 1
+                    }
+                };
+                self.emit(&[OP_LOAD, index as u8], 0)
+            }
+            Token::Symbol("(") => {
+                self.or()?;
+                if !self.eat(Token::Symbol(")")) {
+                    return Err(Fault::Syntax);
+                }
+                Ok(())
+            }
+            _ => Err(Fault::Syntax),
+        }
+    }
+}
+
+/// 把条件拆分为词法单元
+fn lex(text: &str) -> Result<Vec<Token<'_>, MAX_TOKENS>, Fault> {
+    let bytes = text.as_bytes();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let start = i;
+        let c = bytes[i];
+        let token = if c.is_ascii_whitespace() {
+            i += 1;
+            continue;
+        } else if c.is_ascii_digit() || c == b'.' {
+            while i < bytes.len() && matches!(bytes[i], b'0'..=b'9' | b'.' | b':') {
+                i += 1;
+            }
+            Token::Number(parse_number(&text[start..i]).ok_or(Fault::Syntax)?)
+        } else if c.is_ascii_alphabetic() {
+            while i < bytes.len()
+                && matches!(bytes[i], b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'_' | b'.')
+            {
+                i += 1;
+            }
+            Token::Name(&text[start..i])
+        } else {
+            let len = match text.get(i..i + 2) {
+                Some(">=" | "<=" | "==" | "!=") => 2,
+                _ if b"<>+-*/()".contains(&c) => 1,
+                _ => return Err(Fault::Syntax),
+            };
+            i += len;
+            Token::Symbol(&text[start..i])
+        };
+        tokens.push(token).map_err(|_| Fault::TooComplex)?;
+    }
+    Ok(tokens)
+}
+
+/// 解析数字，`HH:MM` 换算为从零点起的分钟数
+fn parse_number(text: &str) -> Option<f32> {
+    let Some((hour, minute)) = text.split_once(':') else {
+        return text.parse().ok().filter(|value: &f32| value.is_finite());
+    };
+    if minute.len() != 2 {
+        return None;
+    }
+    let hour = hour.parse::<u8>().ok().filter(|&h| h < 24)?;
+    let minute = minute.parse::<u8>().ok().filter(|&m| m < 60)?;
+    Some((hour as u32 * 60 + minute as u32) as f32)
+}
+
+/// 值是否视为成立：非 0 且不是 NaN
+fn is_true(value: f32) -> bool {
+    value != 0.0 && !value.is_nan()
+}
+
+/// 比较和逻辑运算的结果
+fn truth(value: bool) -> f32 {
+    if value { 1.0 } else { 0.0 }
+}
+
+/// 比较运算：任一侧为 NaN（未知）时结果为 NaN
+///
+/// # 参数
+/// * `a`, `b` - 左右操作数
+/// * `cmp` - 比较函数
+fn compare(a: f32, b: f32, cmp: fn(&f32, &f32) -> bool) -> f32 {
+    if a.is_nan() || b.is_nan() {
+        f32::NAN
+    } else {
+        truth(cmp(&a, &b))
+    }
+}
+
+/// 执行字节码
+///
+/// # 参数
+/// * `code` - 字节码
+/// * `load` - 按下标读取读数，不存在时返回 NaN
+/// * `time` - `TIME` 指令压入的值
+///
+/// # 返回
+/// 栈中最后剩下的值；字节码有误（未知操作码、操作数不完整、栈溢出或结束时栈中不是一个值）
+/// 时返回 None
+fn eval(code: &[u8], load: impl Fn(usize) -> f32, time: f32) -> Option<f32> {
+    let mut stack: Vec<f32, MAX_STACK> = Vec::new();
+    let mut pc = 0;
+    while let Some(&op) = code.get(pc) {
+        pc += 1;
+        let value = match op {
+            OP_PUSH => {
+                let bytes = code.get(pc..pc + 4)?;
+                pc += 4;
+                f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+            }
+            OP_LOAD => {
+                let index = *code.get(pc)?;
+                pc += 1;
+                load(index as usize)
+            }
+            OP_TIME => time,
+            OP_NEG => -stack.pop()?,
+            OP_NOT => match stack.pop()? {
+                v if v.is_nan() => f32::NAN,
+                v => truth(!is_true(v)),
+            },
+            _ => {
+                let b = stack.pop()?;
+                let a = stack.pop()?;
+                match op {
+                    OP_ADD => a + b,
+                    OP_SUB => a - b,
+                    OP_MUL => a * b,
+                    OP_DIV => a / b,
+                    OP_GT => compare(a, b, f32::gt),
+                    OP_LT => compare(a, b, f32::lt),
+                    OP_GE => compare(a, b, f32::ge),
+                    OP_LE => compare(a, b, f32::le),
+                    OP_EQ => compare(a, b, f32::eq),
+                    OP_NE => compare(a, b, f32::ne),
+                    // 一侧已确定时结果与另一侧是否未知无关
+                    OP_AND if a == 0.0 || b == 0.0 => 0.0,
+                    OP_OR if is_true(a) || is_true(b) => 1.0,
+                    OP_AND | OP_OR if a.is_nan() || b.is_nan() => f32::NAN,
+                    OP_AND => 1.0,
+                    OP_OR => 0.0,
+                    _ => return None,
+                }
+            }
+        };
+        stack.push(value).ok()?;
+    }
+    match stack.as_slice() {
+        &[value] => Some(value),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn compile(text: &str) -> Result<Condition, Fault> {
+        Condition::compile(text, |name| name.contains('.'))
+    }
+
+    /// 在给定读数和时间下求值，没有列出的读数为未知
+    fn check(text: &str, readings: &[(&str, f32)], time: f32) -> bool {
+        let load = |name: &str| {
+            readings
+                .iter()
+                .find(|(known, _)| *known == name)
+                .map_or(f32::NAN, |&(_, value)| value)
+        };
+        compile(text).unwrap().is_met(load, time)
+    }
+
+    #[test]
+    fn compares_readings() {
+        let hot = [("bme280.temp", 31.0)];
+        assert!(check("bme280.temp > 30", &hot, 0.0));
+        assert!(!check("bme280.temp > 30", &[("bme280.temp", 29.5)], 0.0));
+        assert!(check("bme280.temp - 1 >= 30", &hot, 0.0));
+    }
+
+    #[test]
+    fn follows_operator_precedence() {
+        assert!(check("1 + 2 * 3 == 7", &[], 0.0));
+        assert!(check("(1 + 2) * 3 == 9", &[], 0.0));
+        assert!(check("-2 * -3 == 6", &[], 0.0));
+        assert!(check("1 or 0 and 0", &[], 0.0));
+        assert!(!check("not 1 or 0", &[], 0.0));
+        assert!(check("8 / 2 - 1 == 3", &[], 0.0));
+    }
+
+    #[test]
+    fn converts_time_of_day_to_minutes() {
+        assert!(check("time >= 20:00", &[], 20.0 * 60.0 + 5.0));
+        assert!(!check("time >= 20:00", &[], 19.0 * 60.0));
+        assert!(!check("time < 6:30", &[], f32::NAN));
+        assert_eq!(compile("time > 24:00"), Err(Fault::Syntax));
+        assert_eq!(compile("time > 6:3"), Err(Fault::Syntax));
+    }
+
+    #[test]
+    fn unknown_values_are_never_met() {
+        assert!(!check("bme280.hum < 70", &[], 0.0));
+        assert!(!check("not (bme280.hum < 70)", &[], 0.0));
+        assert!(!check("bme280.hum < 70 and 1", &[], 0.0));
+        assert!(!check("bme280.hum < 70 or 0", &[], 0.0));
+    }
+
+    #[test]
+    fn known_side_decides_logic() {
+        assert!(check("bme280.hum < 70 or 1", &[], 0.0));
+        assert!(check("not (bme280.hum < 70 and 0)", &[], 0.0));
+    }
+
+    #[test]
+    fn shares_names_between_loads() {
+        let condition = compile("a.x > 1 and a.x < 3 and b.y == 0").unwrap();
+        assert_eq!(condition.names.len(), 2);
+        let load = |name: &str| if name == "a.x" { 2.0 } else { 0.0 };
+        assert!(condition.is_met(load, 0.0));
+    }
+
+    #[test]
+    fn rejects_syntax_errors() {
+        for text in [
+            "",
+            "1 +",
+            "(1",
+            "1 )",
+            "1 2",
+            "a.x >",
+            "a.x = 1",
+            "unknown > 1",
+            "1 $ 2",
+        ] {
+            assert_eq!(compile(text), Err(Fault::Syntax), "{text}");
+        }
+    }
+
+    #[test]
+    fn rejects_overly_complex_conditions() {
+        // 栈深度超过上限
+        assert_eq!(
+            compile("1+(1+(1+(1+(1+(1+(1+(1+1)))))))"),
+            Err(Fault::TooComplex)
+        );
+        // 读数名称超过上限
+        let names = "a.a+a.b+a.c+a.d+a.e+a.f+a.g+a.h+a.i > 0";
+        assert_eq!(compile(names), Err(Fault::TooComplex));
+        // 词法单元超过上限
+        let long = ["1"; 17].join("+");
+        assert_eq!(compile(&long), Err(Fault::TooComplex));
+    }
+
+    #[test]
+    fn malformed_bytecode_is_rejected() {
+        let load = |_: usize| 0.0;
+        assert_eq!(eval(&[OP_PUSH, 0, 0], load, 0.0), None);
+        assert_eq!(eval(&[OP_ADD], load, 0.0), None);
+        assert_eq!(eval(&[OP_TIME, OP_TIME], load, 0.0), None);
+        assert_eq!(eval(&[0xFF], load, 0.0), None);
+        assert_eq!(eval(&[OP_TIME], load, 5.0), Some(5.0));
+    }
+}
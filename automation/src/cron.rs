@@ -0,0 +1,148 @@
+//! 类似 cron 的时间匹配
+//!
+//! 五个时间字段依次为分、时、日、月、星期。每个字段支持 `*`、数字、范围 `a-b`、
+//! 步长 `*/n`、`a/n` 或 `a-b/n`，以及用 `,` 分隔的列表。星期 0 和 7 都表示星期日。
+//! 与 cron 相同，日和星期都不是 `*` 时，满足其一即可。
+
+/// 时间匹配条件
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cron {
+    minutes: u64,
+    hours: u32,
+    days: u32,
+    months: u16,
+    weekdays: u8,
+    /// 日字段是否以 `*` 开头
+    any_day: bool,
+    /// 星期字段是否以 `*` 开头
+    any_weekday: bool,
+}
+
+impl Cron {
+    /// 解析五个时间字段
+    pub fn parse(fields: &[&str]) -> Option<Cron> {
+        let [minute, hour, day, month, weekday] = fields else {
+            return None;
+        };
+        let weekdays = parse_field(weekday, 0, 7)?;
+        Some(Cron {
+            minutes: parse_field(minute, 0, 59)?,
+            hours: parse_field(hour, 0, 23)? as u32,
+            days: parse_field(day, 1, 31)? as u32,
+            months: parse_field(month, 1, 12)? as u16,
+            // 7 和 0 都表示星期日
+            weekdays: ((weekdays | weekdays >> 7) & 0x7F) as u8,
+            any_day: day.starts_with('*'),
+            any_weekday: weekday.starts_with('*'),
+        })
+    }
+
+    /// 是否匹配指定的时刻
+    ///
+    /// # 参数
+    /// * `minute`, `hour`, `day`, `month` - 分、时、日（从 1 开始）、月（从 1 开始）
+    /// * `weekday` - 星期，0 为星期日
+    pub fn matches(&self, minute: u8, hour: u8, day: u8, month: u8, weekday: u8) -> bool {
+        let day = self.days & 1 << day != 0;
+        let weekday = self.weekdays & 1 << weekday != 0;
+        let day = match (self.any_day, self.any_weekday) {
+            (false, false) => day || weekday,
+            _ => day && weekday,
+        };
+        self.minutes & 1 << minute != 0
+            && self.hours & 1 << hour != 0
+            && self.months & 1 << month != 0
+            && day
+    }
+}
+
+/// 解析一个时间字段
+///
+/// # 返回
+/// 匹配值的位图，第 n 位表示值 n
+fn parse_field(text: &str, min: u8, max: u8) -> Option<u64> {
+    let mut bits = 0u64;
+    for part in text.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u8>().ok().filter(|&s| s > 0)?),
+            None => (part, 1),
+        };
+        let (low, high) = if range == "*" {
+            (min, max)
+        } else if let Some((low, high)) = range.split_once('-') {
+            (low.parse().ok()?, high.parse().ok()?)
+        } else {
+            let value = range.parse().ok()?;
+            // `a/n` 表示从 a 开始到最大值
+            (value, if part.contains('/') { max } else { value })
+        };
+        if low < min || high > max || low > high {
+            return None;
+        }
+        for value in (low..=high).step_by(step as usize) {
+            bits |= 1 << value;
+        }
+    }
+    Some(bits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cron(text: &str) -> Option<Cron> {
+        let fields: std::vec::Vec<&str> = text.split_whitespace().collect();
+        Cron::parse(&fields)
+    }
+
+    #[test]
+    fn parses_fields() {
+        assert_eq!(parse_field("*", 0, 3), Some(0b1111));
+        assert_eq!(parse_field("1,3", 0, 5), Some(0b1010));
+        assert_eq!(parse_field("2-4", 0, 5), Some(0b11100));
+        assert_eq!(parse_field("*/20", 0, 59), Some(1 | 1 << 20 | 1 << 40));
+        assert_eq!(
+            parse_field("10-30/10", 0, 59),
+            Some(1 << 10 | 1 << 20 | 1 << 30)
+        );
+        assert_eq!(parse_field("50/5", 0, 59), Some(1 << 50 | 1 << 55));
+    }
+
+    #[test]
+    fn rejects_invalid_fields() {
+        assert_eq!(parse_field("60", 0, 59), None);
+        assert_eq!(parse_field("0", 1, 31), None);
+        assert_eq!(parse_field("5-2", 0, 59), None);
+        assert_eq!(parse_field("*/0", 0, 59), None);
+        assert_eq!(parse_field("", 0, 59), None);
+        assert_eq!(cron("0 22 * *"), None);
+    }
+
+    #[test]
+    fn matches_time_of_day() {
+        let cron = cron("0 22 * * *").unwrap();
+        assert!(cron.matches(0, 22, 15, 6, 3));
+        assert!(!cron.matches(1, 22, 15, 6, 3));
+        assert!(!cron.matches(0, 21, 15, 6, 3));
+    }
+
+    #[test]
+    fn sunday_is_zero_or_seven() {
+        let cron = cron("0 8 * * 7").unwrap();
+        assert!(cron.matches(0, 8, 1, 1, 0));
+        assert!(!cron.matches(0, 8, 1, 1, 6));
+    }
+
+    #[test]
+    fn day_and_weekday_match_either_when_both_restricted() {
+        // 每月 1 日或每个星期一
+        let either = cron("0 9 1 * 1").unwrap();
+        assert!(either.matches(0, 9, 1, 5, 4));
+        assert!(either.matches(0, 9, 12, 5, 1));
+        assert!(!either.matches(0, 9, 12, 5, 4));
+        // 日是 `*` 时只看星期
+        let weekday = cron("0 9 * * 1-5").unwrap();
+        assert!(weekday.matches(0, 9, 12, 5, 1));
+        assert!(!weekday.matches(0, 9, 13, 5, 6));
+    }
+}
@@ -0,0 +1,15 @@
+//! 自动化规则
+//!
+//! 规则引擎的条件编译器和定时任务的时间匹配，与传感器、设置和系统时间无关：
+//! 读数和当前时间由固件的 `rules`、`scheduler` 模块传入。可以在主机上测试：
+//!
+//! ```text
+//! cargo +stable test -p automation --target x86_64-unknown-linux-gnu
+//! ```
+
+#![cfg_attr(not(test), no_std)]
+
+extern crate alloc;
+
+pub mod condition;
+pub mod cron;
@@ -0,0 +1,13 @@
+//! 启动阶段的编排
+//!
+//! 固件的 `app` 模块把启动拆成若干阶段，其中 buses 之后的阶段由 [stages::run] 编排：
+//! 执行顺序、两条分支的并发和跳过规则都在这里，各阶段的具体工作由固件实现
+//! [stages::Stages]。这部分代码与 esp-hal 无关，可以在主机上用模拟的阶段测试启动顺序：
+//!
+//! ```text
+//! cargo +stable test -p boot --target x86_64-unknown-linux-gnu
+//! ```
+
+#![cfg_attr(not(test), no_std)]
+
+pub mod stages;
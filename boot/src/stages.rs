@@ -0,0 +1,427 @@
+//! buses 之后的启动阶段
+//!
+//! ```text
+//! buses → expander → display → sdcard ─┐
+//!   └→ radio ──────────────────────────┴→ services
+//! ```
+//!
+//! - expander、display 和 sdcard 共用 I2C 和 SPI 总线，依次执行；radio 不使用这两条总线，
+//!   与它们在同一个任务中并发执行（[join]），启动时间取决于较长的一条分支
+//! - display 依赖 expander（LCD 的复位和背光由扩展芯片控制），扩展芯片不可用时跳过
+//! - display、sdcard 和 radio 的外设没有装配或在设置中关闭时跳过，原因交给 [Stages::skip]
+//! - 总线上的阶段先完成而 radio 还在启动时调用 [Stages::radio_pending]，
+//!   启动进度的报告器一直保留到 radio 完成
+//!
+//! services 阶段在 [run] 返回之后由固件执行。
+
+use core::future::Future;
+use embassy_futures::join::join;
+use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+use embassy_sync::signal::Signal;
+
+/// 可以跳过的阶段
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    Display,
+    SdCard,
+    Radio,
+}
+
+/// 阶段对应的外设是否可用
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Availability {
+    /// 已装配且已启用
+    Enabled,
+    /// 当前板型没有装配，或固件没有编译对应功能
+    Absent,
+    /// 已装配，但在设置中关闭
+    Disabled,
+}
+
+/// 跳过阶段的原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Skip {
+    /// 外设没有装配
+    Absent,
+    /// 外设在设置中关闭
+    Disabled,
+    /// 依赖的阶段失败
+    DependencyFailed,
+}
+
+/// 各阶段的具体工作，由固件实现
+// 只在固件的单线程执行器上使用，不需要 Send
+#[allow(async_fn_in_trait)]
+pub trait Stages {
+    /// buses 阶段产物：I2C 总线和共享 SPI 总线
+    type Buses;
+    /// expander 阶段产物
+    type Expander;
+    /// display 阶段产物
+    type Display;
+    /// sdcard 阶段产物
+    type SdCard;
+    /// 启动进度的报告器，display 阶段成功时借用它的 LCD 直接绘制
+    type Progress<'a>;
+
+    /// 阶段对应的外设是否可用
+    fn availability(&self, stage: Stage) -> Availability;
+
+    /// 登记跳过的阶段
+    fn skip(&mut self, stage: Stage, reason: Skip);
+
+    /// expander 阶段，失败时返回 None
+    async fn expander(&mut self, buses: &Self::Buses) -> Option<Self::Expander>;
+
+    /// display 阶段，失败时返回 None
+    async fn display(
+        &mut self,
+        buses: &mut Self::Buses,
+        expander: &Self::Expander,
+    ) -> Option<Self::Display>;
+
+    /// 创建启动进度的报告器
+    ///
+    /// # 参数
+    /// * `display` - display 阶段的产物，跳过或失败时为 None
+    fn progress<'a>(&self, display: Option<&'a mut Self::Display>) -> Self::Progress<'a>;
+
+    /// sdcard 阶段，失败时返回 None
+    async fn sdcard(
+        &mut self,
+        buses: &mut Self::Buses,
+        progress: &mut Self::Progress<'_>,
+    ) -> Option<Self::SdCard>;
+
+    /// 总线上的阶段已完成而 radio 还在启动，之后等待 radio 完成
+    fn radio_pending(&mut self, progress: &mut Self::Progress<'_>);
+}
+
+/// 各阶段的产物，跳过或失败的阶段为 None
+pub struct Started<S: Stages, R> {
+    pub buses: S::Buses,
+    pub expander: Option<S::Expander>,
+    pub display: Option<S::Display>,
+    pub sdcard: Option<S::SdCard>,
+    pub radio: Option<R>,
+}
+
+/// 执行 buses 之后的阶段
+///
+/// # 参数
+/// * `stages` - 各阶段的具体工作
+/// * `buses` - buses 阶段产物
+/// * `radio` - radio 阶段，跳过时不会被轮询
+pub async fn run<S, R>(
+    stages: &mut S,
+    mut buses: S::Buses,
+    radio: impl Future<Output = R>,
+) -> Started<S, R>
+where
+    S: Stages,
+{
+    let radio_enabled = match stages.availability(Stage::Radio) {
+        Availability::Enabled => true,
+        availability => {
+            stages.skip(Stage::Radio, skip_reason(availability));
+            false
+        }
+    };
+    let radio_ready = Signal::<NoopRawMutex, ()>::new();
+    let radio_branch = async {
+        let radio = if radio_enabled {
+            Some(radio.await)
+        } else {
+            None
+        };
+        radio_ready.signal(());
+        radio
+    };
+
+    let bus_branch = async {
+        let expander = stages.expander(&buses).await;
+        let mut display = match (stages.availability(Stage::Display), &expander) {
+            (Availability::Enabled, Some(expander)) => stages.display(&mut buses, expander).await,
+            (Availability::Enabled, None) => {
+                stages.skip(Stage::Display, Skip::DependencyFailed);
+                None
+            }
+            (availability, _) => {
+                stages.skip(Stage::Display, skip_reason(availability));
+                None
+            }
+        };
+
+        let mut progress = stages.progress(display.as_mut());
+        let sdcard = match stages.availability(Stage::SdCard) {
+            Availability::Enabled => stages.sdcard(&mut buses, &mut progress).await,
+            availability => {
+                stages.skip(Stage::SdCard, skip_reason(availability));
+                None
+            }
+        };
+
+        if !radio_ready.signaled() {
+            stages.radio_pending(&mut progress);
+            radio_ready.wait().await;
+        }
+        drop(progress);
+        (expander, display, sdcard)
+    };
+
+    let ((expander, display, sdcard), radio) = join(bus_branch, radio_branch).await;
+    Started {
+        buses,
+        expander,
+        display,
+        sdcard,
+        radio,
+    }
+}
+
+/// 不可用的外设对应的跳过原因
+fn skip_reason(availability: Availability) -> Skip {
+    match availability {
+        Availability::Disabled => Skip::Disabled,
+        _ => Skip::Absent,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::pin::pin;
+    use core::task::{Context, Poll, Waker};
+    use std::cell::RefCell;
+
+    /// 运行 future，模拟的阶段只会短暂挂起
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = pin!(future);
+        let mut cx = Context::from_waker(Waker::noop());
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return output;
+            }
+        }
+    }
+
+    /// 挂起 `count` 次后完成，模拟需要等待硬件的阶段
+    async fn yield_times(count: usize) {
+        let mut left = count;
+        core::future::poll_fn(|cx| {
+            if left == 0 {
+                return Poll::Ready(());
+            }
+            left -= 1;
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        })
+        .await
+    }
+
+    /// 记录各阶段开始和结束的顺序
+    struct Mock<'a> {
+        log: &'a RefCell<Vec<String>>,
+        display: Availability,
+        sdcard: Availability,
+        radio: Availability,
+        expander_ok: bool,
+        /// 每个总线阶段挂起的次数
+        stage_yields: usize,
+    }
+
+    impl<'a> Mock<'a> {
+        fn new(log: &'a RefCell<Vec<String>>) -> Self {
+            Mock {
+                log,
+                display: Availability::Enabled,
+                sdcard: Availability::Enabled,
+                radio: Availability::Enabled,
+                expander_ok: true,
+                stage_yields: 1,
+            }
+        }
+
+        fn record(&self, event: &str) {
+            self.log.borrow_mut().push(event.into());
+        }
+    }
+
+    /// 模拟的启动进度，记录是否借到了 LCD
+    struct MockProgress<'a> {
+        log: &'a RefCell<Vec<String>>,
+    }
+
+    impl Drop for MockProgress<'_> {
+        fn drop(&mut self) {
+            self.log.borrow_mut().push("progress done".into());
+        }
+    }
+
+    impl<'a> Stages for Mock<'a> {
+        type Buses = u8;
+        type Expander = ();
+        type Display = &'static str;
+        type SdCard = u64;
+        type Progress<'p> = MockProgress<'a>;
+
+        fn availability(&self, stage: Stage) -> Availability {
+            match stage {
+                Stage::Display => self.display,
+                Stage::SdCard => self.sdcard,
+                Stage::Radio => self.radio,
+            }
+        }
+
+        fn skip(&mut self, stage: Stage, reason: Skip) {
+            self.record(&format!("skip {stage:?} {reason:?}"));
+        }
+
+        async fn expander(&mut self, _buses: &u8) -> Option<()> {
+            self.record("expander");
+            yield_times(self.stage_yields).await;
+            self.record("expander done");
+            self.expander_ok.then_some(())
+        }
+
+        async fn display(&mut self, _buses: &mut u8, _expander: &()) -> Option<&'static str> {
+            self.record("display");
+            yield_times(self.stage_yields).await;
+            self.record("display done");
+            Some("lcd")
+        }
+
+        fn progress<'p>(&self, display: Option<&'p mut &'static str>) -> MockProgress<'a> {
+            self.record(&format!("progress {:?}", display.map(|lcd| *lcd)));
+            MockProgress { log: self.log }
+        }
+
+        async fn sdcard(
+            &mut self,
+            _buses: &mut u8,
+            _progress: &mut Self::Progress<'_>,
+        ) -> Option<u64> {
+            self.record("sdcard");
+            yield_times(self.stage_yields).await;
+            self.record("sdcard done");
+            Some(16)
+        }
+
+        fn radio_pending(&mut self, _progress: &mut Self::Progress<'_>) {
+            self.record("radio pending");
+        }
+    }
+
+    /// 挂起 `yields` 次后完成的 radio 阶段
+    async fn radio(log: &RefCell<Vec<String>>, yields: usize) -> &'static str {
+        log.borrow_mut().push("radio".into());
+        yield_times(yields).await;
+        log.borrow_mut().push("radio done".into());
+        "stack"
+    }
+
+    fn logged(log: &[String], event: &str) -> bool {
+        log.iter().any(|entry| entry == event)
+    }
+
+    fn position(log: &[String], event: &str) -> usize {
+        log.iter()
+            .position(|entry| entry == event)
+            .unwrap_or_else(|| panic!("{event} missing from {log:?}"))
+    }
+
+    #[test]
+    fn bus_stages_run_in_order() {
+        let log = RefCell::new(Vec::new());
+        let mut mock = Mock::new(&log);
+        let started = block_on(run(&mut mock, 7, radio(&log, 0)));
+        let log = log.take();
+        let order = [
+            "expander",
+            "expander done",
+            "display",
+            "display done",
+            "progress Some(\"lcd\")",
+            "sdcard",
+            "sdcard done",
+        ];
+        for pair in order.windows(2) {
+            assert!(position(&log, pair[0]) < position(&log, pair[1]), "{log:?}");
+        }
+        assert_eq!(started.buses, 7);
+        assert_eq!(started.expander, Some(()));
+        assert_eq!(started.display, Some("lcd"));
+        assert_eq!(started.sdcard, Some(16));
+        assert_eq!(started.radio, Some("stack"));
+    }
+
+    #[test]
+    fn radio_runs_alongside_bus_stages() {
+        let log = RefCell::new(Vec::new());
+        let mut mock = Mock::new(&log);
+        block_on(run(&mut mock, 0, radio(&log, 1)));
+        let log = log.take();
+        // expander 挂起时 radio 已经开始，radio 先完成时不等待
+        assert!(position(&log, "radio") < position(&log, "expander done"));
+        assert!(position(&log, "radio done") < position(&log, "sdcard done"));
+        assert!(!logged(&log, "radio pending"));
+    }
+
+    #[test]
+    fn slow_radio_keeps_progress_until_done() {
+        let log = RefCell::new(Vec::new());
+        let mut mock = Mock::new(&log);
+        let started = block_on(run(&mut mock, 0, radio(&log, 20)));
+        let log = log.take();
+        assert!(position(&log, "sdcard done") < position(&log, "radio pending"));
+        assert!(position(&log, "radio pending") < position(&log, "radio done"));
+        assert!(position(&log, "radio done") < position(&log, "progress done"));
+        assert_eq!(started.radio, Some("stack"));
+    }
+
+    #[test]
+    fn failed_expander_skips_display_only() {
+        let log = RefCell::new(Vec::new());
+        let mut mock = Mock::new(&log);
+        mock.expander_ok = false;
+        let started = block_on(run(&mut mock, 0, radio(&log, 0)));
+        let log = log.take();
+        assert!(logged(&log, "skip Display DependencyFailed"));
+        assert!(!logged(&log, "display"));
+        assert!(logged(&log, "progress None"));
+        assert_eq!(started.display, None);
+        assert_eq!(started.sdcard, Some(16));
+    }
+
+    #[test]
+    fn unavailable_stages_are_skipped_with_reason() {
+        let log = RefCell::new(Vec::new());
+        let mut mock = Mock::new(&log);
+        mock.display = Availability::Absent;
+        mock.sdcard = Availability::Disabled;
+        mock.radio = Availability::Disabled;
+        let started = block_on(run(&mut mock, 0, radio(&log, 0)));
+        let log = log.take();
+        assert!(logged(&log, "skip Display Absent"));
+        assert!(logged(&log, "skip SdCard Disabled"));
+        assert!(logged(&log, "skip Radio Disabled"));
+        // 跳过的阶段不执行，expander 照常初始化
+        assert!(!logged(&log, "radio") && !logged(&log, "sdcard"));
+        assert!(logged(&log, "expander done"));
+        assert_eq!(started.expander, Some(()));
+        assert_eq!(started.display, None);
+        assert_eq!(started.sdcard, None);
+        assert_eq!(started.radio, None);
+    }
+
+    #[test]
+    fn disabled_display_is_not_a_failure() {
+        let log = RefCell::new(Vec::new());
+        let mut mock = Mock::new(&log);
+        mock.display = Availability::Disabled;
+        mock.expander_ok = false;
+        block_on(run(&mut mock, 0, radio(&log, 0)));
+        let log = log.take();
+        assert!(logged(&log, "skip Display Disabled"));
+    }
+}
@@ -1,10 +1,50 @@
+use std::path::PathBuf;
+use std::{env, fs};
+
+/// 固件签名公钥文件的默认位置，可以用环境变量 `OTA_PUBLIC_KEY` 指定其他文件
+const OTA_PUBLIC_KEY_DEFAULT: &str = "keys/ota_ed25519.pub";
+
 fn main() {
+    ota_public_key();
     linker_be_nice();
     println!("cargo:rustc-link-arg=-Tdefmt.x");
     // make sure linkall.x is the last linker script (otherwise might cause problems with flip-link)
     println!("cargo:rustc-link-arg=-Tlinkall.x");
 }
 
+/// 把固件签名公钥（32 字节的原始 ed25519 公钥）复制到 `OUT_DIR`，由 ota 模块嵌入固件
+///
+/// 仓库中不提交公钥：每个部署使用自己的密钥对，私钥由发布固件的人保管。
+/// 找不到公钥或长度不对时构建失败，避免编译出无法通过签名校验、永远不能升级的固件
+fn ota_public_key() {
+    println!("cargo:rerun-if-env-changed=OTA_PUBLIC_KEY");
+    let manifest_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
+    let path = match env::var("OTA_PUBLIC_KEY") {
+        Ok(path) => manifest_dir.join(path),
+        Err(_) => manifest_dir.join(OTA_PUBLIC_KEY_DEFAULT),
+    };
+    println!("cargo:rerun-if-changed={}", path.display());
+
+    let key = match fs::read(&path) {
+        Ok(key) => key,
+        Err(err) => panic!(
+            "OTA public key {} not readable ({err}). Generate a key pair as described in \
+             src/ota.rs and put the 32-byte raw public key there, or set OTA_PUBLIC_KEY \
+             to its path.",
+            path.display()
+        ),
+    };
+    if key.len() != 32 {
+        panic!(
+            "OTA public key {} is {} bytes, expected a 32-byte raw ed25519 public key",
+            path.display(),
+            key.len()
+        );
+    }
+    let out = PathBuf::from(env::var("OUT_DIR").unwrap()).join("ota_ed25519.pub");
+    fs::write(out, key).unwrap();
+}
+
 fn linker_be_nice() {
     let args: Vec<String> = std::env::args().collect();
     if args.len() > 1 {
@@ -0,0 +1,154 @@
+//! ChaCha20 流密码（RFC 8439）
+//!
+//! 只实现分组函数和密钥流异或，不含 Poly1305 认证。
+
+/// 密钥长度
+pub const KEY_LEN: usize = 32;
+
+/// nonce 长度（96 位）
+pub const NONCE_LEN: usize = 12;
+
+/// 分组长度
+pub const BLOCK_LEN: usize = 64;
+
+/// 常量 "expand 32-byte k"
+const SIGMA: [u32; 4] = [0x6170_7865, 0x3320_646e, 0x7962_2d32, 0x6b20_6574];
+
+/// 分组函数（RFC 8439 §2.3）
+///
+/// # 参数
+/// * `key` - 密钥
+/// * `counter` - 分组计数器
+/// * `nonce` - nonce
+pub fn block(key: &[u8; KEY_LEN], counter: u32, nonce: &[u8; NONCE_LEN]) -> [u8; BLOCK_LEN] {
+    let mut state = [0u32; 16];
+    state[..4].copy_from_slice(&SIGMA);
+    for (word, bytes) in state[4..12].iter_mut().zip(key.chunks_exact(4)) {
+        *word = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+    }
+    state[12] = counter;
+    for (word, bytes) in state[13..].iter_mut().zip(nonce.chunks_exact(4)) {
+        *word = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+    }
+
+    let mut x = state;
+    for _ in 0..10 {
+        quarter_round(&mut x, 0, 4, 8, 12);
+        quarter_round(&mut x, 1, 5, 9, 13);
+        quarter_round(&mut x, 2, 6, 10, 14);
+        quarter_round(&mut x, 3, 7, 11, 15);
+        quarter_round(&mut x, 0, 5, 10, 15);
+        quarter_round(&mut x, 1, 6, 11, 12);
+        quarter_round(&mut x, 2, 7, 8, 13);
+        quarter_round(&mut x, 3, 4, 9, 14);
+    }
+
+    let mut out = [0u8; BLOCK_LEN];
+    for (bytes, (x, s)) in out.chunks_exact_mut(4).zip(x.iter().zip(state)) {
+        bytes.copy_from_slice(&x.wrapping_add(s).to_le_bytes());
+    }
+    out
+}
+
+/// 用密钥流异或数据，加密和解密相同（RFC 8439 §2.4）
+///
+/// # 参数
+/// * `key` - 密钥
+/// * `counter` - 第一个分组的计数器，之后每 64 字节加 1
+/// * `nonce` - nonce
+/// * `data` - 明文或密文，原地变换
+pub fn apply_keystream(
+    key: &[u8; KEY_LEN],
+    counter: u32,
+    nonce: &[u8; NONCE_LEN],
+    data: &mut [u8],
+) {
+    for (i, chunk) in data.chunks_mut(BLOCK_LEN).enumerate() {
+        let stream = block(key, counter.wrapping_add(i as u32), nonce);
+        for (byte, key) in chunk.iter_mut().zip(stream) {
+            *byte ^= key;
+        }
+    }
+}
+
+fn quarter_round(x: &mut [u32; 16], a: usize, b: usize, c: usize, d: usize) {
+    x[a] = x[a].wrapping_add(x[b]);
+    x[d] = (x[d] ^ x[a]).rotate_left(16);
+    x[c] = x[c].wrapping_add(x[d]);
+    x[b] = (x[b] ^ x[c]).rotate_left(12);
+    x[a] = x[a].wrapping_add(x[b]);
+    x[d] = (x[d] ^ x[a]).rotate_left(8);
+    x[c] = x[c].wrapping_add(x[d]);
+    x[b] = (x[b] ^ x[c]).rotate_left(7);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 测试向量中的密钥 00 01 02 ... 1f
+    fn sequential_key() -> [u8; KEY_LEN] {
+        core::array::from_fn(|i| i as u8)
+    }
+
+    fn hex(text: &str) -> Vec<u8> {
+        let digits: Vec<u8> = text.bytes().filter(u8::is_ascii_hexdigit).collect();
+        digits
+            .chunks(2)
+            .map(|pair| u8::from_str_radix(core::str::from_utf8(pair).unwrap(), 16).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn quarter_round_vector() {
+        // RFC 8439 §2.1.1
+        let mut x = [0u32; 16];
+        x[..4].copy_from_slice(&[0x1111_1111, 0x0102_0304, 0x9b8d_6f43, 0x0123_4567]);
+        quarter_round(&mut x, 0, 1, 2, 3);
+        assert_eq!(x[..4], [0xea2a_92f4, 0xcb1c_f8ce, 0x4581_472e, 0x5881_c4bb]);
+    }
+
+    #[test]
+    fn block_vector() {
+        // RFC 8439 §2.3.2
+        let nonce = [0, 0, 0, 0x09, 0, 0, 0, 0x4a, 0, 0, 0, 0];
+        let expected = hex("10 f1 e7 e4 d1 3b 59 15 50 0f dd 1f a3 20 71 c4
+             c7 d1 f4 c7 33 c0 68 03 04 22 aa 9a c3 d4 6c 4e
+             d2 82 64 46 07 9f aa 09 14 c2 d7 05 d9 8b 02 a2
+             b5 12 9c d1 de 16 4e b9 cb d0 83 e8 a2 50 3c 4e");
+        assert_eq!(block(&sequential_key(), 1, &nonce)[..], expected[..]);
+    }
+
+    #[test]
+    fn encryption_vector() {
+        // RFC 8439 §2.4.2
+        let nonce = [0, 0, 0, 0, 0, 0, 0, 0x4a, 0, 0, 0, 0];
+        let plain = b"Ladies and Gentlemen of the class of '99: If I could offer you \
+                      only one tip for the future, sunscreen would be it.";
+        let expected = hex("6e 2e 35 9a 25 68 f9 80 41 ba 07 28 dd 0d 69 81
+             e9 7e 7a ec 1d 43 60 c2 0a 27 af cc fd 9f ae 0b
+             f9 1b 65 c5 52 47 33 ab 8f 59 3d ab cd 62 b3 57
+             16 39 d6 24 e6 51 52 ab 8f 53 0c 35 9f 08 61 d8
+             07 ca 0d bf 50 0d 6a 61 56 a3 8e 08 8a 22 b6 5e
+             52 bc 51 4d 16 cc f8 06 81 8c e9 1a b7 79 37 36
+             5a f9 0b bf 74 a3 5b e6 b4 0b 8e ed f2 78 5e 42
+             87 4d");
+        let mut data = plain.to_vec();
+        apply_keystream(&sequential_key(), 1, &nonce, &mut data);
+        assert_eq!(data, expected);
+
+        // 再异或一次还原明文
+        apply_keystream(&sequential_key(), 1, &nonce, &mut data);
+        assert_eq!(data, plain);
+    }
+
+    #[test]
+    fn partial_block() {
+        // 不足一个分组的数据使用密钥流的开头
+        let nonce = [7; NONCE_LEN];
+        let stream = block(&sequential_key(), 5, &nonce);
+        let mut data = [0u8; 10];
+        apply_keystream(&sequential_key(), 5, &nonce, &mut data);
+        assert_eq!(data, stream[..10]);
+    }
+}
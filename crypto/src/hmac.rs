@@ -0,0 +1,75 @@
+//! HMAC-SHA256（RFC 2104）
+//!
+//! 用于生成云端 MQTT 代理的 SAS 令牌，见固件的 `sas` 模块。
+
+use sha2::{Digest, Sha256};
+
+/// SHA-256 的分组长度
+const BLOCK_LEN: usize = 64;
+
+/// 输出长度
+pub const MAC_LEN: usize = 32;
+
+/// 计算 HMAC-SHA256
+///
+/// # 参数
+/// * `key` - 密钥，长于分组长度时先做一次 SHA-256
+/// * `message` - 消息
+pub fn sha256(key: &[u8], message: &[u8]) -> [u8; MAC_LEN] {
+    let mut block = [0u8; BLOCK_LEN];
+    if key.len() > BLOCK_LEN {
+        block[..MAC_LEN].copy_from_slice(&Sha256::digest(key));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+    let pad = |byte: u8| block.map(|k| k ^ byte);
+    let inner = Sha256::new()
+        .chain_update(pad(0x36))
+        .chain_update(message)
+        .finalize();
+    Sha256::new()
+        .chain_update(pad(0x5c))
+        .chain_update(inner)
+        .finalize()
+        .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(text: &str) -> Vec<u8> {
+        let digits: Vec<u8> = text.bytes().filter(u8::is_ascii_hexdigit).collect();
+        digits
+            .chunks(2)
+            .map(|pair| u8::from_str_radix(core::str::from_utf8(pair).unwrap(), 16).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn short_key() {
+        // RFC 4231 测试用例 1
+        let mac = sha256(&[0x0b; 20], b"Hi There");
+        let expected = hex("b0344c61d8db38535ca8afceaf0bf12b881dc200c9833da726e9376c2e32cff7");
+        assert_eq!(mac[..], expected[..]);
+    }
+
+    #[test]
+    fn text_key() {
+        // RFC 4231 测试用例 2
+        let mac = sha256(b"Jefe", b"what do ya want for nothing?");
+        let expected = hex("5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843");
+        assert_eq!(mac[..], expected[..]);
+    }
+
+    #[test]
+    fn long_key() {
+        // RFC 4231 测试用例 6，密钥长于分组
+        let mac = sha256(
+            &[0xaa; 131],
+            b"Test Using Larger Than Block-Size Key - Hash Key First",
+        );
+        let expected = hex("60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54");
+        assert_eq!(mac[..], expected[..]);
+    }
+}
@@ -0,0 +1,13 @@
+//! 固件使用的密码学算法
+//!
+//! 这些算法出错时不会有明显的症状（例如加密保存的密码全部无法解密），
+//! 因此与 esp-hal 分开，在主机上用标准中的测试向量验证：
+//!
+//! ```text
+//! cargo +stable test -p crypto --target x86_64-unknown-linux-gnu
+//! ```
+
+#![cfg_attr(not(test), no_std)]
+
+pub mod chacha20;
+pub mod hmac;
@@ -0,0 +1,172 @@
+//! 时间源
+//!
+//! 需要计时的驱动逻辑（按键消抖、超时等）通过 [Clock] 读取时间，而不是直接调用
+//! embassy-time，这样在主机上测试时可以用 [MockClock] 控制时间的流逝，
+//! 需要更高精度的场合也可以换用其他计时器实现。
+//!
+//! 时间以微秒为单位的单调计数表示，起点由实现决定，只有两个时刻之差有意义。
+
+use core::cell::Cell;
+
+/// 单调时间源
+pub trait Clock {
+    /// 当前时刻（微秒）
+    fn now_us(&self) -> u64;
+
+    /// 从 `since` 到现在经过的时间（微秒），`since` 晚于现在时为 0
+    fn elapsed_us(&self, since: u64) -> u64 {
+        self.now_us().saturating_sub(since)
+    }
+}
+
+/// 手动推进的时间源，用于测试
+#[derive(Debug, Default)]
+pub struct MockClock {
+    now_us: Cell<u64>,
+}
+
+impl MockClock {
+    /// 创建时间源，当前时刻为 `start_us`
+    pub const fn new(start_us: u64) -> Self {
+        MockClock {
+            now_us: Cell::new(start_us),
+        }
+    }
+
+    /// 时间前进 `us` 微秒
+    pub fn advance_us(&self, us: u64) {
+        self.now_us.set(self.now_us.get() + us);
+    }
+
+    /// 时间前进 `ms` 毫秒
+    pub fn advance_ms(&self, ms: u64) {
+        self.advance_us(ms * 1000);
+    }
+}
+
+impl Clock for MockClock {
+    fn now_us(&self) -> u64 {
+        self.now_us.get()
+    }
+}
+
+impl<C: Clock + ?Sized> Clock for &C {
+    fn now_us(&self) -> u64 {
+        (**self).now_us()
+    }
+}
+
+/// 开关量消抖
+///
+/// 采用“先响应后锁定”的方式：输入与当前状态不同时立即接受新状态，之后的 `settle_us`
+/// 内忽略变化，锁定期过后输入仍与状态不同再接受。按下不增加延迟，
+/// 松开时的触点抖动也不会被当作又一次按下。
+///
+/// 锁定期应长于一个采样周期，否则相邻两次采样之间的抖动无法滤除。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Debouncer {
+    state: bool,
+    /// 上次接受变化的时刻，从未变化时为 None
+    changed_at: Option<u64>,
+    settle_us: u64,
+}
+
+impl Debouncer {
+    /// 创建消抖器，初始状态为 `false`
+    ///
+    /// # 参数
+    /// * `settle_us` - 接受一次变化后的锁定时长（微秒）
+    pub const fn new(settle_us: u64) -> Self {
+        Debouncer {
+            state: false,
+            changed_at: None,
+            settle_us,
+        }
+    }
+
+    /// 消抖后的状态
+    pub const fn state(&self) -> bool {
+        self.state
+    }
+
+    /// 输入一次采样
+    ///
+    /// # 参数
+    /// * `clock` - 时间源
+    /// * `raw` - 采样到的原始状态
+    ///
+    /// # 返回
+    /// 消抖后的状态发生变化时返回新状态
+    pub fn update(&mut self, clock: &impl Clock, raw: bool) -> Option<bool> {
+        if raw == self.state {
+            return None;
+        }
+        let now = clock.now_us();
+        if self
+            .changed_at
+            .is_some_and(|at| now.saturating_sub(at) < self.settle_us)
+        {
+            return None;
+        }
+        self.state = raw;
+        self.changed_at = Some(now);
+        Some(raw)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mock_clock_advances() {
+        let clock = MockClock::new(5);
+        clock.advance_ms(2);
+        assert_eq!(clock.now_us(), 2005);
+        assert_eq!(clock.elapsed_us(1005), 1000);
+        assert_eq!(clock.elapsed_us(9000), 0);
+    }
+
+    #[test]
+    fn first_change_is_accepted_immediately() {
+        let clock = MockClock::new(0);
+        let mut key = Debouncer::new(60_000);
+        assert_eq!(key.update(&clock, false), None);
+        assert_eq!(key.update(&clock, true), Some(true));
+        assert!(key.state());
+    }
+
+    #[test]
+    fn bounce_within_settle_time_is_ignored() {
+        let clock = MockClock::new(0);
+        let mut key = Debouncer::new(60_000);
+        key.update(&clock, true);
+        clock.advance_ms(200);
+        assert_eq!(key.update(&clock, false), Some(false));
+        // 松开后 50ms 的一次抖动
+        clock.advance_ms(50);
+        assert_eq!(key.update(&clock, true), None);
+        clock.advance_ms(50);
+        assert_eq!(key.update(&clock, false), None);
+        assert!(!key.state());
+    }
+
+    #[test]
+    fn change_held_past_settle_time_is_accepted() {
+        let clock = MockClock::new(0);
+        let mut key = Debouncer::new(60_000);
+        key.update(&clock, true);
+        clock.advance_ms(50);
+        assert_eq!(key.update(&clock, false), None);
+        clock.advance_ms(50);
+        assert_eq!(key.update(&clock, false), Some(false));
+    }
+
+    #[test]
+    fn works_through_a_clock_reference() {
+        let clock = MockClock::new(0);
+        let by_ref: &dyn Clock = &clock;
+        let mut key = Debouncer::new(0);
+        assert_eq!(key.update(&by_ref, true), Some(true));
+    }
+}
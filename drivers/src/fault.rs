@@ -0,0 +1,361 @@
+//! 总线故障注入
+//!
+//! 只用于测试，由 `fault` 特性启用。[FaultyBus] 包装一个 I2C 总线或 SPI 设备，
+//! 按 [Injector] 的设置每隔 N 次传输注入一次故障，用来验证驱动在总线出错后的恢复逻辑：
+//!
+//! - [Fault::Nack]：设备不应答，传输不发生，返回错误
+//! - [Fault::Timeout]：传输超时，传输不发生，返回错误
+//! - [Fault::CorruptRead]：传输正常完成，但读到的每个字节按位取反
+//!
+//! [Injector] 本身不依赖总线类型，固件在 `fault-injection` 特性下也用它在
+//! 共享总线的访问层注入故障。
+
+use embedded_hal::i2c::{self, I2c, NoAcknowledgeSource};
+use embedded_hal::spi::{self, SpiDevice};
+
+/// 注入的故障类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    /// 设备不应答
+    Nack,
+    /// 传输超时
+    Timeout,
+    /// 读到的数据被破坏
+    CorruptRead,
+}
+
+/// 故障注入计数器
+///
+/// 每次传输调用一次 [Injector::on_transfer]，第 N、2N、3N... 次传输返回设置的故障
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Injector {
+    /// 故障类型和间隔，None 表示不注入
+    plan: Option<(Fault, u32)>,
+    /// 设置后经过的传输次数
+    count: u32,
+    /// 已注入的故障次数
+    injected: u32,
+}
+
+impl Injector {
+    /// 创建不注入故障的计数器
+    pub const fn new() -> Self {
+        Injector { plan: None, count: 0, injected: 0 }
+    }
+
+    /// 设置故障，重新开始计数
+    ///
+    /// # 参数
+    /// * `fault` - 故障类型
+    /// * `every` - 每隔多少次传输注入一次，1 表示每次都注入，0 表示不注入
+    pub fn set(&mut self, fault: Fault, every: u32) {
+        self.plan = (every > 0).then_some((fault, every));
+        self.count = 0;
+        self.injected = 0;
+    }
+
+    /// 停止注入
+    pub fn clear(&mut self) {
+        self.plan = None;
+    }
+
+    /// 当前的故障类型和间隔
+    pub fn plan(&self) -> Option<(Fault, u32)> {
+        self.plan
+    }
+
+    /// 已注入的故障次数
+    pub fn injected(&self) -> u32 {
+        self.injected
+    }
+
+    /// 记录一次传输
+    ///
+    /// # 返回
+    /// 本次传输需要注入的故障
+    pub fn on_transfer(&mut self) -> Option<Fault> {
+        let (fault, every) = self.plan?;
+        self.count += 1;
+        if self.count < every {
+            return None;
+        }
+        self.count = 0;
+        self.injected += 1;
+        Some(fault)
+    }
+}
+
+/// 破坏读到的数据（按位取反）
+pub fn corrupt(bytes: &mut [u8]) {
+    for byte in bytes {
+        *byte = !*byte;
+    }
+}
+
+/// 破坏一次 SPI 传输中所有读到的数据
+pub fn corrupt_spi_reads(operations: &mut [spi::Operation<'_, u8>]) {
+    for operation in operations {
+        match operation {
+            spi::Operation::Read(bytes) | spi::Operation::TransferInPlace(bytes) => corrupt(bytes),
+            spi::Operation::Transfer(read, _) => corrupt(read),
+            spi::Operation::Write(_) | spi::Operation::DelayNs(_) => {}
+        }
+    }
+}
+
+/// 带故障注入的总线错误
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FaultError<E> {
+    /// 底层总线返回的错误
+    Bus(E),
+    /// 注入的故障
+    Injected(Fault),
+}
+
+impl<E: i2c::Error> i2c::Error for FaultError<E> {
+    fn kind(&self) -> i2c::ErrorKind {
+        match self {
+            FaultError::Bus(err) => err.kind(),
+            FaultError::Injected(Fault::Nack) => {
+                i2c::ErrorKind::NoAcknowledge(NoAcknowledgeSource::Unknown)
+            }
+            FaultError::Injected(_) => i2c::ErrorKind::Other,
+        }
+    }
+}
+
+impl<E: spi::Error> spi::Error for FaultError<E> {
+    fn kind(&self) -> spi::ErrorKind {
+        match self {
+            FaultError::Bus(err) => err.kind(),
+            FaultError::Injected(_) => spi::ErrorKind::Other,
+        }
+    }
+}
+
+/// 带故障注入的 I2C 总线或 SPI 设备
+///
+/// I2C 的 `read`、`write`、`write_read`、`transaction` 和 SPI 的 `transaction`
+/// 每次调用算一次传输，I2C 的便捷方法直接转发给被包装的总线
+pub struct FaultyBus<B> {
+    bus: B,
+    injector: Injector,
+}
+
+impl<B> FaultyBus<B> {
+    /// 包装总线，初始不注入故障
+    pub fn new(bus: B) -> Self {
+        FaultyBus { bus, injector: Injector::new() }
+    }
+
+    /// 故障注入设置
+    pub fn injector(&mut self) -> &mut Injector {
+        &mut self.injector
+    }
+
+    /// 被包装的总线
+    pub fn inner(&mut self) -> &mut B {
+        &mut self.bus
+    }
+
+    /// 取回被包装的总线
+    pub fn into_inner(self) -> B {
+        self.bus
+    }
+}
+
+impl<B> FaultyBus<B> {
+    /// 记录一次传输
+    ///
+    /// # 返回
+    /// 需要破坏读到的数据时返回 true；注入不应答或超时时返回对应错误，传输不应再进行
+    fn inject<E>(&mut self) -> Result<bool, FaultError<E>> {
+        match self.injector.on_transfer() {
+            Some(Fault::CorruptRead) => Ok(true),
+            Some(fault) => Err(FaultError::Injected(fault)),
+            None => Ok(false),
+        }
+    }
+}
+
+impl<B: i2c::ErrorType> i2c::ErrorType for FaultyBus<B> {
+    type Error = FaultError<B::Error>;
+}
+
+impl<B: I2c> I2c for FaultyBus<B> {
+    fn read(&mut self, address: u8, read: &mut [u8]) -> Result<(), Self::Error> {
+        let corrupt_read = self.inject()?;
+        self.bus.read(address, read).map_err(FaultError::Bus)?;
+        if corrupt_read {
+            corrupt(read);
+        }
+        Ok(())
+    }
+
+    fn write(&mut self, address: u8, write: &[u8]) -> Result<(), Self::Error> {
+        self.inject()?;
+        self.bus.write(address, write).map_err(FaultError::Bus)
+    }
+
+    fn write_read(
+        &mut self,
+        address: u8,
+        write: &[u8],
+        read: &mut [u8],
+    ) -> Result<(), Self::Error> {
+        let corrupt_read = self.inject()?;
+        self.bus.write_read(address, write, read).map_err(FaultError::Bus)?;
+        if corrupt_read {
+            corrupt(read);
+        }
+        Ok(())
+    }
+
+    fn transaction(
+        &mut self,
+        address: u8,
+        operations: &mut [i2c::Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        let corrupt_read = self.inject()?;
+        self.bus.transaction(address, operations).map_err(FaultError::Bus)?;
+        if corrupt_read {
+            for operation in operations {
+                if let i2c::Operation::Read(bytes) = operation {
+                    corrupt(bytes);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<B: spi::ErrorType> spi::ErrorType for FaultyBus<B> {
+    type Error = FaultError<B::Error>;
+}
+
+impl<B: SpiDevice> SpiDevice for FaultyBus<B> {
+    fn transaction(
+        &mut self,
+        operations: &mut [spi::Operation<'_, u8>],
+    ) -> Result<(), Self::Error> {
+        let corrupt_read = self.inject()?;
+        self.bus.transaction(operations).map_err(FaultError::Bus)?;
+        if corrupt_read {
+            corrupt_spi_reads(operations);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::xl9555;
+    use embedded_hal::i2c::ErrorKind;
+    use embedded_hal_mock::eh1::i2c::{Mock, Transaction};
+
+    const KEYS_RELEASED: u8 = 0xF0;
+
+    #[test]
+    fn injector_fires_every_nth_transaction() {
+        let mut injector = Injector::new();
+        assert_eq!(injector.on_transfer(), None);
+
+        injector.set(Fault::Timeout, 3);
+        let faults: Vec<_> = (0..7).map(|_| injector.on_transfer()).collect();
+        let t = Some(Fault::Timeout);
+        assert_eq!(faults, [None, None, t, None, None, t, None]);
+        assert_eq!(injector.injected(), 2);
+
+        injector.clear();
+        assert_eq!(injector.on_transfer(), None);
+        injector.set(Fault::Nack, 0);
+        assert_eq!(injector.plan(), None);
+    }
+
+    #[test]
+    fn nack_maps_to_no_acknowledge() {
+        let err: FaultError<ErrorKind> = FaultError::Injected(Fault::Nack);
+        let kind = i2c::Error::kind(&err);
+        assert_eq!(kind, ErrorKind::NoAcknowledge(NoAcknowledgeSource::Unknown));
+    }
+
+    #[test]
+    fn xl9555_read_fails_then_recovers() {
+        let read = |port: u8, value: u8| {
+            let register = xl9555::registers::INPUT_PORT_0 + port;
+            Transaction::write_read(xl9555::ADDR, vec![register], vec![value])
+        };
+        // 第二次传输被注入故障，不会到达总线
+        let mock = Mock::new(&[read(0, 0xFF), read(0, 0xFF), read(1, KEYS_RELEASED)]);
+        let mut i2c = FaultyBus::new(mock);
+        i2c.injector().set(Fault::Nack, 2);
+
+        let err = xl9555::read_inputs(&mut i2c).unwrap_err();
+        assert_eq!(err, FaultError::Injected(Fault::Nack));
+        i2c.injector().clear();
+        let inputs = xl9555::read_inputs(&mut i2c).unwrap();
+        assert_eq!(xl9555::pressed_keys(inputs), [false; 4]);
+        i2c.into_inner().done();
+    }
+
+    #[test]
+    fn xl9555_corrupt_read_reports_all_keys_pressed() {
+        let register = xl9555::registers::INPUT_PORT_1;
+        let mock = Mock::new(&[
+            Transaction::write_read(xl9555::ADDR, vec![0], vec![0xFF]),
+            Transaction::write_read(xl9555::ADDR, vec![register], vec![KEYS_RELEASED]),
+        ]);
+        let mut i2c = FaultyBus::new(mock);
+        i2c.injector().set(Fault::CorruptRead, 2);
+
+        let inputs = xl9555::read_inputs(&mut i2c).unwrap();
+        assert_eq!(xl9555::pressed_keys(inputs), [true; 4]);
+        i2c.into_inner().done();
+    }
+
+    #[test]
+    fn xl9555_init_aborts_on_timeout() {
+        let mock = Mock::new(&[Transaction::write(
+            xl9555::ADDR,
+            vec![xl9555::registers::CONFIG_PORT_0, 0xFF],
+        )]);
+        let mut i2c = FaultyBus::new(mock);
+        i2c.injector().set(Fault::Timeout, 2);
+
+        assert_eq!(xl9555::init(&mut i2c), Err(FaultError::Injected(Fault::Timeout)));
+        i2c.into_inner().done();
+    }
+
+    #[cfg(feature = "sim")]
+    #[test]
+    fn st7789_redraw_after_spi_error_matches_clean_frame() {
+        use crate::sim;
+        use crate::st7789::{HEIGHT, St7789, WIDTH};
+        use embedded_graphics::pixelcolor::Rgb565;
+        use embedded_graphics::prelude::*;
+
+        fn draw<S: SpiDevice>(lcd: &mut St7789<S, sim::SimDc>) -> Result<(), S::Error> {
+            lcd.fill_screen(Rgb565::BLUE)?;
+            lcd.fill_rectangle(40, 30, 100, 80, Rgb565::RED)
+        }
+        let (mut clean_lcd, clean) = sim::display();
+        draw(&mut clean_lcd).unwrap();
+
+        // 整屏填充中途超时，屏幕上留下一半旧内容
+        let (lcd, panel) = sim::display();
+        let (spi, dc) = lcd.release();
+        let mut spi = FaultyBus::new(spi);
+        spi.injector().set(Fault::Timeout, 40);
+        let mut lcd = St7789::new(spi, dc);
+        assert_eq!(draw(&mut lcd), Err(FaultError::Injected(Fault::Timeout)));
+        assert_eq!(panel.borrow().pixel(WIDTH - 1, HEIGHT - 1), Rgb565::BLACK);
+
+        // 故障消失后重绘，驱动不保留出错时的状态
+        let (mut spi, dc) = lcd.release();
+        spi.injector().clear();
+        let mut lcd = St7789::new(spi, dc);
+        draw(&mut lcd).unwrap();
+        assert_eq!(panel.borrow().checksum(), clean.borrow().checksum());
+    }
+}
@@ -0,0 +1,25 @@
+//! 板载外设驱动
+//!
+//! 只依赖 embedded-hal 接口、与 esp-hal 无关的驱动逻辑，由固件中同名的 `st7789`、
+//! `xl9555` 模块接到具体的总线上；需要计时的逻辑通过 [clock::Clock] 读取时间。这部分代码可以在主机上用 embedded-hal-mock
+//! 模拟总线进行测试：
+//!
+//! ```text
+//! cargo +stable test -p drivers --features sim,fault --target x86_64-unknown-linux-gnu
+//! ```
+//!
+//! 固件的 `.cargo/config.toml` 默认目标为 xtensa，因此需要显式指定主机目标；
+//! stable 工具链会忽略其中只用于固件的 `[unstable] build-std`。
+//!
+//! 启用 `sim` 特性（需要 std）时提供 [sim] 模块，用内存中的模拟面板代替 LCD；
+//! 启用 `fault` 特性时提供 [fault] 模块，在总线上注入故障。
+
+#![cfg_attr(not(any(test, feature = "sim")), no_std)]
+
+pub mod clock;
+#[cfg(feature = "fault")]
+pub mod fault;
+#[cfg(feature = "sim")]
+pub mod sim;
+pub mod st7789;
+pub mod xl9555;
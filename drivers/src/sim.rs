@@ -0,0 +1,336 @@
+//! 模拟 LCD 面板
+//!
+//! 在主机上代替 LCD 所在的 SPI 设备和 DC 引脚：解析 [St7789] 发出的命令，
+//! 把写入显存的像素保存在内存中，界面代码不需要修改就能在桌面上运行和做快照测试。
+//! 画面只由收到的命令决定，与时序无关，每次运行结果相同。
+//!
+//! 只模拟绘制用到的命令（窗口设置、写显存、睡眠和显示开关），其余命令忽略；
+//! 显存按驱动设置的横屏方向排列，坐标与 [St7789] 的逻辑坐标一致。
+//!
+//! ```
+//! use drivers::sim;
+//! use embedded_graphics::pixelcolor::Rgb565;
+//! use embedded_graphics::prelude::*;
+//!
+//! let (mut lcd, panel) = sim::display();
+//! lcd.fill_rectangle(10, 20, 30, 40, Rgb565::RED).unwrap();
+//! assert_eq!(panel.borrow().pixel(10, 20), Rgb565::RED);
+//! // 保存为图片查看：std::fs::write("screen.ppm", panel.borrow().to_ppm())
+//! ```
+
+use crate::st7789::{HEIGHT, St7789, WIDTH, commands};
+use core::cell::RefCell;
+use core::convert::Infallible;
+use embedded_graphics::pixelcolor::Rgb565;
+use embedded_graphics::pixelcolor::raw::RawU16;
+use embedded_graphics::prelude::*;
+use embedded_hal::digital::{self, OutputPin};
+use embedded_hal::spi::{self, Operation, SpiDevice};
+use std::rc::Rc;
+
+/// 接在模拟面板上的驱动
+pub type SimDisplay = St7789<SimSpi, SimDc>;
+
+/// 模拟面板的共享句柄，绘制后通过它检查画面
+pub type PanelRef = Rc<RefCell<Panel>>;
+
+/// 创建接在同一块模拟面板上的驱动
+///
+/// # 返回
+/// 驱动和面板句柄，面板初始为全黑
+pub fn display() -> (SimDisplay, PanelRef) {
+    let panel = Rc::new(RefCell::new(Panel::new()));
+    let lcd = St7789::new(SimSpi(panel.clone()), SimDc(panel.clone()));
+    (lcd, panel)
+}
+
+/// 模拟的面板状态和显存
+pub struct Panel {
+    pixels: Vec<Rgb565>,
+    /// DC 引脚电平，true 表示数据
+    data: bool,
+    /// 最近一条命令
+    command: Option<u8>,
+    /// 最近一条命令已收到的参数
+    params: Vec<u8>,
+    /// 窗口的列范围和行范围（含两端）
+    columns: (u16, u16),
+    rows: (u16, u16),
+    /// 下一个像素写入的位置
+    cursor: (u16, u16),
+    /// 写显存时还没凑成一个像素的高字节
+    pending: Option<u8>,
+    sleeping: bool,
+    display_on: bool,
+}
+
+impl Panel {
+    fn new() -> Self {
+        Panel {
+            pixels: vec![Rgb565::BLACK; WIDTH as usize * HEIGHT as usize],
+            data: true,
+            command: None,
+            params: Vec::new(),
+            columns: (0, WIDTH - 1),
+            rows: (0, HEIGHT - 1),
+            cursor: (0, 0),
+            pending: None,
+            sleeping: true,
+            display_on: false,
+        }
+    }
+
+    /// 读取一个像素，超出屏幕时返回黑色
+    pub fn pixel(&self, x: u16, y: u16) -> Rgb565 {
+        if x < WIDTH && y < HEIGHT {
+            self.pixels[y as usize * WIDTH as usize + x as usize]
+        } else {
+            Rgb565::BLACK
+        }
+    }
+
+    /// 是否处于睡眠模式（复位后和 SLPIN 之后）
+    pub fn is_sleeping(&self) -> bool {
+        self.sleeping
+    }
+
+    /// 是否已打开显示（DISPON）
+    pub fn is_display_on(&self) -> bool {
+        self.display_on
+    }
+
+    /// 显存内容的 FNV-1a 散列，用于快照测试
+    pub fn checksum(&self) -> u64 {
+        self.pixels.iter().fold(0xCBF2_9CE4_8422_2325, |hash, color| {
+            RawU16::from(*color)
+                .into_inner()
+                .to_be_bytes()
+                .iter()
+                .fold(hash, |hash, &byte| {
+                    (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01B3)
+                })
+        })
+    }
+
+    /// 把显存编码为二进制 PPM（P6）图片
+    pub fn to_ppm(&self) -> Vec<u8> {
+        let mut image = format!("P6\n{} {}\n255\n", WIDTH, HEIGHT).into_bytes();
+        for color in &self.pixels {
+            // 5/6 位分量扩展到 8 位
+            image.push(color.r() << 3 | color.r() >> 2);
+            image.push(color.g() << 2 | color.g() >> 4);
+            image.push(color.b() << 3 | color.b() >> 2);
+        }
+        image
+    }
+
+    /// 处理 SPI 写入的字节
+    fn receive(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            if self.data {
+                self.receive_data(byte);
+            } else {
+                self.receive_command(byte);
+            }
+        }
+    }
+
+    fn receive_command(&mut self, command: u8) {
+        self.command = Some(command);
+        self.params.clear();
+        self.pending = None;
+        match command {
+            commands::SLPIN => self.sleeping = true,
+            commands::SLPOUT => self.sleeping = false,
+            commands::DISPOFF => self.display_on = false,
+            commands::DISPON => self.display_on = true,
+            commands::RAMWR => self.cursor = (self.columns.0, self.rows.0),
+            _ => {}
+        }
+    }
+
+    fn receive_data(&mut self, byte: u8) {
+        match self.command {
+            Some(commands::CASET) => {
+                if let Some(range) = self.address_param(byte) {
+                    self.columns = range;
+                }
+            }
+            Some(commands::RASET) => {
+                if let Some(range) = self.address_param(byte) {
+                    self.rows = range;
+                }
+            }
+            Some(commands::RAMWR) => match self.pending.take() {
+                Some(high) => self.write_pixel(u16::from_be_bytes([high, byte])),
+                None => self.pending = Some(byte),
+            },
+            _ => {}
+        }
+    }
+
+    /// 收集 CASET/RASET 的 4 字节参数
+    ///
+    /// # 返回
+    /// 收齐时返回起止地址
+    fn address_param(&mut self, byte: u8) -> Option<(u16, u16)> {
+        self.params.push(byte);
+        match self.params[..] {
+            [s0, s1, e0, e1] => Some((u16::from_be_bytes([s0, s1]), u16::from_be_bytes([e0, e1]))),
+            _ => None,
+        }
+    }
+
+    /// 在光标处写入一个像素，光标在窗口内先行后列移动，到达末尾后回到窗口起点
+    fn write_pixel(&mut self, raw: u16) {
+        let (x, y) = self.cursor;
+        if x < WIDTH && y < HEIGHT {
+            self.pixels[y as usize * WIDTH as usize + x as usize] = RawU16::new(raw).into();
+        }
+        self.cursor = if x < self.columns.1 {
+            (x + 1, y)
+        } else if y < self.rows.1 {
+            (self.columns.0, y + 1)
+        } else {
+            (self.columns.0, self.rows.0)
+        };
+    }
+}
+
+/// 模拟面板的 SPI 设备，读操作返回 0
+pub struct SimSpi(PanelRef);
+
+impl spi::ErrorType for SimSpi {
+    type Error = Infallible;
+}
+
+impl SpiDevice for SimSpi {
+    fn transaction(&mut self, operations: &mut [Operation<'_, u8>]) -> Result<(), Infallible> {
+        let mut panel = self.0.borrow_mut();
+        for operation in operations {
+            match operation {
+                Operation::Write(bytes) => panel.receive(bytes),
+                Operation::Transfer(read, write) => {
+                    panel.receive(write);
+                    read.fill(0);
+                }
+                Operation::TransferInPlace(bytes) => {
+                    panel.receive(bytes);
+                    bytes.fill(0);
+                }
+                Operation::Read(bytes) => bytes.fill(0),
+                Operation::DelayNs(_) => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 模拟面板的 DC 引脚
+pub struct SimDc(PanelRef);
+
+impl digital::ErrorType for SimDc {
+    type Error = Infallible;
+}
+
+impl OutputPin for SimDc {
+    fn set_low(&mut self) -> Result<(), Infallible> {
+        self.0.borrow_mut().data = false;
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Infallible> {
+        self.0.borrow_mut().data = true;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::future::Future;
+    use core::pin::pin;
+    use core::task::{Context, Poll, Waker};
+    use embedded_hal_async::delay::DelayNs;
+
+    /// 立即完成的延时
+    struct NoDelay;
+
+    impl DelayNs for NoDelay {
+        async fn delay_ns(&mut self, _ns: u32) {}
+    }
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = pin!(future);
+        let mut cx = Context::from_waker(Waker::noop());
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return output;
+            }
+        }
+    }
+
+    #[test]
+    fn init_wakes_panel_and_turns_display_on() {
+        let (mut lcd, panel) = display();
+        assert!(panel.borrow().is_sleeping());
+        block_on(lcd.init(&mut NoDelay)).unwrap();
+        assert!(!panel.borrow().is_sleeping());
+        assert!(panel.borrow().is_display_on());
+
+        block_on(lcd.set_sleep(true, &mut NoDelay)).unwrap();
+        assert!(panel.borrow().is_sleeping());
+        assert!(!panel.borrow().is_display_on());
+    }
+
+    #[test]
+    fn fill_rectangle_only_touches_its_window() {
+        let (mut lcd, panel) = display();
+        lcd.fill_rectangle(10, 20, 30, 40, Rgb565::RED).unwrap();
+        let panel = panel.borrow();
+        assert_eq!(panel.pixel(10, 20), Rgb565::RED);
+        assert_eq!(panel.pixel(39, 59), Rgb565::RED);
+        assert_eq!(panel.pixel(9, 20), Rgb565::BLACK);
+        assert_eq!(panel.pixel(40, 59), Rgb565::BLACK);
+        assert_eq!(panel.pixel(39, 60), Rgb565::BLACK);
+    }
+
+    #[test]
+    fn blit_places_pixels_row_by_row() {
+        let (mut lcd, panel) = display();
+        let pixels: Vec<u8> = [Rgb565::RED, Rgb565::GREEN, Rgb565::BLUE, Rgb565::WHITE]
+            .iter()
+            .flat_map(|&color| RawU16::from(color).into_inner().to_be_bytes())
+            .collect();
+        lcd.blit(100, 50, 2, 2, &pixels).unwrap();
+        let panel = panel.borrow();
+        assert_eq!(panel.pixel(100, 50), Rgb565::RED);
+        assert_eq!(panel.pixel(101, 50), Rgb565::GREEN);
+        assert_eq!(panel.pixel(100, 51), Rgb565::BLUE);
+        assert_eq!(panel.pixel(101, 51), Rgb565::WHITE);
+    }
+
+    #[test]
+    fn same_drawing_gives_same_checksum() {
+        let draw = || {
+            let (mut lcd, panel) = display();
+            lcd.fill_screen(Rgb565::BLUE).unwrap();
+            Pixel(Point::new(5, 5), Rgb565::YELLOW).draw(&mut lcd).unwrap();
+            panel.borrow().checksum()
+        };
+        let (_, blank) = display();
+        assert_eq!(draw(), draw());
+        assert_ne!(draw(), blank.borrow().checksum());
+    }
+
+    #[test]
+    fn ppm_has_header_and_rgb888_pixels() {
+        let (mut lcd, panel) = display();
+        lcd.fill_rectangle(0, 0, 1, 1, Rgb565::WHITE).unwrap();
+        let image = panel.borrow().to_ppm();
+        let header = b"P6\n320 240\n255\n";
+        assert_eq!(&image[..header.len()], header);
+        assert_eq!(&image[header.len()..header.len() + 6], &[255, 255, 255, 0, 0, 0]);
+        assert_eq!(image.len(), header.len() + 320 * 240 * 3);
+    }
+}
@@ -0,0 +1,1495 @@
+//! ST7789 LCD 控制器驱动
+//!
+//! 泛型于 embedded-hal 的 [SpiDevice] 和 DC 引脚，错误类型即 SPI 设备的错误类型。
+//! DC 引脚须为不会失败的普通 GPIO。
+//!
+//! 绘制用到的窗口、写显存和睡眠命令是 MIPI DCS 标准命令，ILI9341 与 ST7789 相同，
+//! 因此同一个驱动也能驱动 ILI9341 面板：用 [St7789::detect] 通过 MISO 读回控制器 ID，
+//! 再选择 [St7789::init] 或 [St7789::init_ili9341]。
+//!
+//! [St7789Async] 是基于 embedded-hal-async [AsyncSpiDevice] 的异步版本，提供初始化、
+//! 填充和整块写入等大块传输的接口，传输期间可以让出执行器；读 ID、逐点绘制等
+//! 仍使用 [St7789]，两者可以通过 [St7789::as_async] 共用同一个 SPI 设备和 DC 引脚。
+
+use core::convert::Infallible;
+use embedded_graphics::pixelcolor::Rgb565;
+use embedded_graphics::pixelcolor::raw::RawU16;
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::Rectangle;
+use embedded_hal::digital::OutputPin;
+use embedded_hal::spi::{Operation, SpiDevice};
+use embedded_hal_async::delay::DelayNs;
+use embedded_hal_async::spi::SpiDevice as AsyncSpiDevice;
+
+/// 屏幕宽度（横屏）
+pub const WIDTH: u16 = 320;
+/// 屏幕高度（横屏）
+pub const HEIGHT: u16 = 240;
+
+/// 填充矩形时使用的栈缓冲区大小（字节）
+const FILL_BUF_LEN: usize = 2048;
+
+/// ST7789 命令定义
+#[allow(unused)]
+pub mod commands {
+    pub const NOP: u8 = 0x00;
+    pub const SWRESET: u8 = 0x01;
+    pub const RDDID: u8 = 0x04;
+    pub const RDDST: u8 = 0x09;
+    pub const SLPIN: u8 = 0x10;
+    pub const SLPOUT: u8 = 0x11;
+    pub const INVOFF: u8 = 0x20;
+    pub const INVON: u8 = 0x21;
+    pub const GAMSET: u8 = 0x26;
+    pub const DISPOFF: u8 = 0x28;
+    pub const DISPON: u8 = 0x29;
+    pub const CASET: u8 = 0x2A;
+    pub const RASET: u8 = 0x2B;
+    pub const RAMWR: u8 = 0x2C;
+    pub const MADCTL: u8 = 0x36;
+    pub const COLMOD: u8 = 0x3A;
+    pub const PORCTRL: u8 = 0xB2;
+    pub const GCTRL: u8 = 0xB7;
+    pub const VCOMS: u8 = 0xBB;
+    pub const LCMCTRL: u8 = 0xC0;
+    pub const VDVVRHEN: u8 = 0xC2;
+    pub const VRHS: u8 = 0xC3;
+    pub const VDVS: u8 = 0xC4;
+    pub const FRCTRL2: u8 = 0xC6;
+    pub const PWCTRL1: u8 = 0xD0;
+    pub const PVGAMCTRL: u8 = 0xE0;
+    pub const NVGAMCTRL: u8 = 0xE1;
+}
+
+/// MADCTL 横屏设置：MV | MX，RGB 顺序
+const MADCTL_LANDSCAPE: u8 = 0x60;
+
+/// ST7789V 的 RDDID 返回值（制造商、版本、驱动 ID）
+const ST7789_ID: [u8; 3] = [0x85, 0x85, 0x52];
+
+/// ILI9341 专有的命令和参数（ILI9341 数据手册和常见模块的例程参数）
+pub mod ili9341 {
+    pub const FRMCTR1: u8 = 0xB1;
+    pub const DFUNCTR: u8 = 0xB6;
+    pub const PWCTR1: u8 = 0xC0;
+    pub const PWCTR2: u8 = 0xC1;
+    pub const VMCTR1: u8 = 0xC5;
+    pub const VMCTR2: u8 = 0xC7;
+    /// 读取 ID4，返回一个空字节和 0x00、0x93、0x41
+    pub const RDID4: u8 = 0xD3;
+
+    /// RDID4 返回的芯片型号
+    pub const ID4: [u8; 2] = [0x93, 0x41];
+
+    /// MADCTL 横屏设置：与 ST7789 相同的 MV | MX，BGR 顺序
+    pub const MADCTL_LANDSCAPE: u8 = 0x68;
+
+    /// 正极性 Gamma 校正表
+    pub const PV_GAMMA: [u8; 15] = [
+        0x0F, 0x31, 0x2B, 0x0C, 0x0E, 0x08, 0x4E, 0xF1, 0x37, 0x07, 0x10, 0x03, 0x0E, 0x09, 0x00,
+    ];
+
+    /// 负极性 Gamma 校正表
+    pub const NV_GAMMA: [u8; 15] = [
+        0x00, 0x0E, 0x14, 0x03, 0x11, 0x07, 0x31, 0xC1, 0x48, 0x08, 0x0F, 0x0C, 0x31, 0x36, 0x0F,
+    ];
+}
+
+/// 控制器型号
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Controller {
+    St7789,
+    Ili9341,
+    /// 读回的 ID 无法识别，MISO 未连接时读到全 0 或全 1
+    Unknown,
+}
+
+impl Controller {
+    /// 型号名称，用于日志和诊断输出
+    pub const fn name(self) -> &'static str {
+        match self {
+            Controller::St7789 => "ST7789",
+            Controller::Ili9341 => "ILI9341",
+            Controller::Unknown => "unknown",
+        }
+    }
+}
+
+/// RDDID 读回的显示 ID
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DisplayId {
+    pub manufacturer: u8,
+    pub version: u8,
+    pub driver: u8,
+}
+
+impl DisplayId {
+    /// 三个字节按读回的顺序排列
+    pub const fn bytes(&self) -> [u8; 3] {
+        [self.manufacturer, self.version, self.driver]
+    }
+}
+
+/// RDDST 读回的 32 位显示状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DisplayStatus(pub u32);
+
+impl DisplayStatus {
+    /// 升压电路已开启
+    pub const fn booster_on(self) -> bool {
+        self.0 & 1 << 31 != 0
+    }
+
+    /// 已退出睡眠模式
+    pub const fn is_awake(self) -> bool {
+        self.0 & 1 << 17 != 0
+    }
+
+    /// 已开启颜色反转
+    pub const fn is_inverted(self) -> bool {
+        self.0 & 1 << 13 != 0
+    }
+
+    /// 显示已开启
+    pub const fn is_display_on(self) -> bool {
+        self.0 & 1 << 10 != 0
+    }
+
+    /// 初始化后控制器应处于的状态：已唤醒且显示开启
+    ///
+    /// 全 0（MISO 悬空被下拉或控制器未应答）和全 1 都不满足
+    pub const fn is_running(self) -> bool {
+        self.0 != u32::MAX && self.is_awake() && self.is_display_on()
+    }
+}
+
+/// 去掉读数据前的 1 位空时钟
+///
+/// 串行接口下 RDDID 和 RDDST 在数据之前多一个时钟周期，读到的字节整体错后 1 位，
+/// `raw` 比 `out` 多读一个字节
+fn skip_dummy_bit(raw: &[u8], out: &mut [u8]) {
+    for (i, byte) in out.iter_mut().enumerate() {
+        *byte = raw[i] << 1 | raw[i + 1] >> 7;
+    }
+}
+
+/// 把填充区域裁剪到屏幕内，并在缓冲区开头准备重复的颜色数据
+///
+/// 只准备实际用到的部分：区域比缓冲区小时不写满整个缓冲区
+///
+/// # 返回
+/// 裁剪后的宽度、高度和每次写入的字节数，区域为空或缓冲区不足 2 字节时为 None
+fn prepare_fill(
+    x: u16,
+    y: u16,
+    w: u16,
+    h: u16,
+    color: Rgb565,
+    buf: &mut [u8],
+) -> Option<(u16, u16, usize)> {
+    if x >= WIDTH || y >= HEIGHT || w == 0 || h == 0 || buf.len() < 2 {
+        return None;
+    }
+    let w = w.min(WIDTH - x);
+    let h = h.min(HEIGHT - y);
+    let chunk = (buf.len() & !1).min(w as usize * h as usize * 2);
+    let [hi, lo] = RawU16::from(color).into_inner().to_be_bytes();
+    for pixel in buf[..chunk].chunks_exact_mut(2) {
+        pixel[0] = hi;
+        pixel[1] = lo;
+    }
+    Some((w, h, chunk))
+}
+
+/// 正极性 Gamma 校正表（正点原子例程参数）
+const PV_GAMMA: [u8; 14] = [
+    0xD0, 0x00, 0x05, 0x0E, 0x15, 0x0D, 0x37, 0x43, 0x47, 0x09, 0x15, 0x12, 0x16, 0x19,
+];
+
+/// 负极性 Gamma 校正表（正点原子例程参数）
+const NV_GAMMA: [u8; 14] = [
+    0xD0, 0x00, 0x05, 0x0D, 0x0C, 0x06, 0x2D, 0x44, 0x40, 0x0E, 0x1C, 0x18, 0x16, 0x19,
+];
+
+/// ST7789 初始化序列中 SLPOUT 之后、DISPON 之前的命令及参数
+const ST7789_INIT: [(u8, &[u8]); 14] = [
+    (commands::MADCTL, &[MADCTL_LANDSCAPE]),
+    // 16 位 RGB565 像素格式
+    (commands::COLMOD, &[0x05]),
+    (commands::PORCTRL, &[0x0C, 0x0C, 0x00, 0x33, 0x33]),
+    (commands::GCTRL, &[0x35]),
+    (commands::VCOMS, &[0x32]),
+    (commands::LCMCTRL, &[0x0C]),
+    (commands::VDVVRHEN, &[0x01]),
+    (commands::VRHS, &[Tuning::DEFAULT.contrast]),
+    (commands::VDVS, &[0x20]),
+    (commands::FRCTRL2, &[0x0F]),
+    (commands::PWCTRL1, &[0xA4, 0xA1]),
+    (commands::PVGAMCTRL, &PV_GAMMA),
+    (commands::NVGAMCTRL, &NV_GAMMA),
+    // IPS 面板需要开启颜色反转
+    (commands::INVON, &[]),
+];
+
+/// ILI9341 初始化序列中 SLPOUT 之后、DISPON 之前的命令及参数，TN 面板不需要颜色反转
+const ILI9341_INIT: [(u8, &[u8]); 11] = [
+    (ili9341::PWCTR1, &[0x23]),
+    (ili9341::PWCTR2, &[0x10]),
+    (ili9341::VMCTR1, &[0x3E, 0x28]),
+    (ili9341::VMCTR2, &[0x86]),
+    (commands::MADCTL, &[ili9341::MADCTL_LANDSCAPE]),
+    // 16 位 RGB565 像素格式（DPI 和 DBI 均为 16 位）
+    (commands::COLMOD, &[0x55]),
+    (ili9341::FRMCTR1, &[0x00, 0x18]),
+    (ili9341::DFUNCTR, &[0x08, 0x82, 0x27]),
+    (commands::GAMSET, &[GammaCurve::G2_2.param()]),
+    (commands::PVGAMCTRL, &ili9341::PV_GAMMA),
+    (commands::NVGAMCTRL, &ili9341::NV_GAMMA),
+];
+
+/// VRHS 参数的上限，更大的取值在数据手册中保留
+pub const CONTRAST_MAX: u8 = 0x1B;
+
+/// 预设的 Gamma 曲线（GAMSET 参数）
+///
+/// 在 [Tuning] 的校正表之前生效，校正表再在所选曲线的基础上微调各灰阶电压
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GammaCurve {
+    /// Gamma 2.2，复位后的默认值
+    G2_2,
+    /// Gamma 1.8，暗部更亮
+    G1_8,
+    /// Gamma 2.5，暗部更暗、对比更强
+    G2_5,
+    /// Gamma 1.0，线性
+    G1_0,
+}
+
+impl GammaCurve {
+    /// GAMSET 命令的参数
+    pub const fn param(self) -> u8 {
+        match self {
+            GammaCurve::G2_2 => 0x01,
+            GammaCurve::G1_8 => 0x02,
+            GammaCurve::G2_5 => 0x04,
+            GammaCurve::G1_0 => 0x08,
+        }
+    }
+}
+
+/// 面板调校参数
+///
+/// 不同批次的面板液晶特性有差异，同一组参数在有的面板上暗部发灰、有的偏色，
+/// 用 [St7789::apply_tuning] 在运行时调整，不需要重新初始化
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tuning {
+    /// 正极性 Gamma 校正表（PVGAMCTRL）
+    pub positive_gamma: [u8; 14],
+    /// 负极性 Gamma 校正表（NVGAMCTRL）
+    pub negative_gamma: [u8; 14],
+    /// 预设的 Gamma 曲线
+    pub curve: GammaCurve,
+    /// 对比度，即 VRHS 参数（GVDD 电压），超过 [CONTRAST_MAX] 时按 [CONTRAST_MAX] 发送
+    pub contrast: u8,
+    /// 是否开启颜色反转，IPS 面板需要开启
+    pub inverted: bool,
+}
+
+impl Tuning {
+    /// 与 [St7789::init] 相同的参数
+    pub const DEFAULT: Tuning = Tuning {
+        positive_gamma: PV_GAMMA,
+        negative_gamma: NV_GAMMA,
+        curve: GammaCurve::G2_2,
+        contrast: 0x10,
+        inverted: true,
+    };
+}
+
+/// [St7789::blit_transformed] 的旋转角度（顺时针）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rotation {
+    /// 不旋转
+    Deg0,
+    /// 顺时针 90 度
+    Deg90,
+    /// 180 度
+    Deg180,
+    /// 顺时针 270 度（逆时针 90 度）
+    Deg270,
+}
+
+/// blit 时对像素块做的变换：先旋转，再按整数倍放大
+///
+/// 同一张精灵图可以用于不同的朝向和大小，不需要为每种组合各存一份
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Transform {
+    /// 旋转角度
+    pub rotation: Rotation,
+    /// 放大倍数，0 按 1 处理
+    pub scale: u8,
+}
+
+impl Transform {
+    /// 不做变换
+    pub const IDENTITY: Transform = Transform {
+        rotation: Rotation::Deg0,
+        scale: 1,
+    };
+
+    /// 只旋转
+    pub const fn rotate(rotation: Rotation) -> Transform {
+        Transform { rotation, scale: 1 }
+    }
+
+    /// 在当前变换的基础上按 `scale` 倍放大
+    pub const fn scaled(self, scale: u8) -> Transform {
+        Transform { scale, ..self }
+    }
+
+    const fn factor(&self) -> u16 {
+        if self.scale == 0 {
+            1
+        } else {
+            self.scale as u16
+        }
+    }
+
+    /// 变换后的宽度和高度
+    ///
+    /// # 参数
+    /// * `w`, `h` - 源像素块的宽度和高度
+    pub const fn size(&self, w: u16, h: u16) -> (u16, u16) {
+        let (w, h) = match self.rotation {
+            Rotation::Deg0 | Rotation::Deg180 => (w, h),
+            Rotation::Deg90 | Rotation::Deg270 => (h, w),
+        };
+        (w * self.factor(), h * self.factor())
+    }
+
+    /// 变换后位于 (`x`, `y`) 的像素在源像素块中的位置
+    ///
+    /// # 参数
+    /// * `w`, `h` - 源像素块的宽度和高度
+    /// * `x`, `y` - 变换后的坐标，须在 [Transform::size] 范围内
+    pub const fn source(&self, w: u16, h: u16, x: u16, y: u16) -> (u16, u16) {
+        let (x, y) = (x / self.factor(), y / self.factor());
+        match self.rotation {
+            Rotation::Deg0 => (x, y),
+            // 源图的左边一列（自下而上）转到顶部一行
+            Rotation::Deg90 => (y, h - 1 - x),
+            Rotation::Deg180 => (w - 1 - x, h - 1 - y),
+            Rotation::Deg270 => (w - 1 - y, x),
+        }
+    }
+}
+
+/// ST7789 LCD 控制器驱动
+///
+/// 驱动 ATK-MD0240 模块上的 ST7789 控制器（2.4 英寸，240x320，RGB565）。
+/// 屏幕以横屏方式使用，逻辑分辨率为 320x240。
+///
+/// 控制器的复位与背光引脚由 XL9555 扩展芯片控制（见 [crate::xl9555]），
+/// 本驱动只负责 SPI 命令/数据传输以及 DC 引脚。
+///
+/// 实现了 embedded-graphics 的 [DrawTarget]，可直接用于绘制图形和文字。
+pub struct St7789<SPI, DC> {
+    spi: SPI,
+    dc: DC,
+}
+
+impl<SPI, DC> St7789<SPI, DC>
+where
+    SPI: SpiDevice,
+    DC: OutputPin<Error = Infallible>,
+{
+    /// 创建驱动实例
+    ///
+    /// # 参数
+    /// * `spi` - LCD 所在的 SPI 设备
+    /// * `dc` - 数据/命令选择引脚（低电平为命令，高电平为数据）
+    pub fn new(spi: SPI, dc: DC) -> Self {
+        St7789 { spi, dc }
+    }
+
+    /// 释放 SPI 设备和 DC 引脚
+    pub fn release(self) -> (SPI, DC) {
+        (self.spi, self.dc)
+    }
+
+    /// 设置 DC 引脚
+    ///
+    /// # 参数
+    /// * `data` - true 表示数据（高电平），false 表示命令（低电平）
+    fn set_dc(&mut self, data: bool) {
+        let result = if data {
+            self.dc.set_high()
+        } else {
+            self.dc.set_low()
+        };
+        match result {
+            Ok(()) => {}
+            Err(never) => match never {},
+        }
+    }
+
+    /// 发送命令及其参数
+    ///
+    /// # 参数
+    /// * `cmd` - 命令字节
+    /// * `params` - 参数字节
+    pub fn write_command(&mut self, cmd: u8, params: &[u8]) -> Result<(), SPI::Error> {
+        self.set_dc(false);
+        self.spi.write(&[cmd])?;
+        self.set_dc(true);
+        if !params.is_empty() {
+            self.spi.write(params)?;
+        }
+        Ok(())
+    }
+
+    /// 发送读命令并读取返回的数据
+    ///
+    /// 命令和读取在同一次传输中完成，片选保持有效；DC 保持低电平，
+    /// 控制器只在写入时采样 DC，读取阶段 DC 的电平不影响输出
+    ///
+    /// # 参数
+    /// * `cmd` - 命令字节
+    /// * `buf` - 读到的原始数据，包括数据之前的空时钟或空字节
+    pub fn read_command(&mut self, cmd: u8, buf: &mut [u8]) -> Result<(), SPI::Error> {
+        self.set_dc(false);
+        self.spi
+            .transaction(&mut [Operation::Write(&[cmd]), Operation::Read(buf)])
+    }
+
+    /// 读取显示 ID（RDDID）
+    pub fn read_id(&mut self) -> Result<DisplayId, SPI::Error> {
+        let mut raw = [0u8; 4];
+        self.read_command(commands::RDDID, &mut raw)?;
+        let mut id = [0u8; 3];
+        skip_dummy_bit(&raw, &mut id);
+        let [manufacturer, version, driver] = id;
+        Ok(DisplayId {
+            manufacturer,
+            version,
+            driver,
+        })
+    }
+
+    /// 读取显示状态（RDDST）
+    pub fn read_status(&mut self) -> Result<DisplayStatus, SPI::Error> {
+        let mut raw = [0u8; 5];
+        self.read_command(commands::RDDST, &mut raw)?;
+        let mut status = [0u8; 4];
+        skip_dummy_bit(&raw, &mut status);
+        Ok(DisplayStatus(u32::from_be_bytes(status)))
+    }
+
+    /// 读回 ID 识别控制器型号
+    ///
+    /// 先按 ST7789 读取 RDDID，不匹配时再读 ILI9341 的 RDID4。
+    /// 复位后即可读取，不需要先初始化
+    pub fn detect(&mut self) -> Result<Controller, SPI::Error> {
+        if self.read_id()?.bytes() == ST7789_ID {
+            return Ok(Controller::St7789);
+        }
+        let mut raw = [0u8; 4];
+        self.read_command(ili9341::RDID4, &mut raw)?;
+        if raw[2..] == ili9341::ID4 {
+            Ok(Controller::Ili9341)
+        } else {
+            Ok(Controller::Unknown)
+        }
+    }
+
+    /// 发送像素数据（需先调用 [Self::set_window]）
+    pub fn write_data(&mut self, data: &[u8]) -> Result<(), SPI::Error> {
+        self.set_dc(true);
+        self.spi.write(data)
+    }
+
+    /// 初始化控制器
+    ///
+    /// 调用前需已完成硬件复位
+    ///
+    /// # 参数
+    /// * `delay` - 命令之间的等待
+    pub async fn init(&mut self, delay: &mut impl DelayNs) -> Result<(), SPI::Error> {
+        // 退出睡眠模式，需等待 120 毫秒
+        self.write_command(commands::SLPOUT, &[])?;
+        delay.delay_ms(120).await;
+        for (cmd, params) in ST7789_INIT {
+            self.write_command(cmd, params)?;
+        }
+        self.write_command(commands::DISPON, &[])?;
+        delay.delay_ms(10).await;
+        Ok(())
+    }
+
+    /// 按 ILI9341 初始化控制器
+    ///
+    /// 调用前需已完成硬件复位。初始化后的像素格式和坐标方向与 [Self::init] 相同，
+    /// 绘制接口可以直接使用；[Self::apply_tuning] 的参数只适用于 ST7789
+    ///
+    /// # 参数
+    /// * `delay` - 命令之间的等待
+    pub async fn init_ili9341(&mut self, delay: &mut impl DelayNs) -> Result<(), SPI::Error> {
+        self.write_command(commands::SLPOUT, &[])?;
+        delay.delay_ms(120).await;
+        for (cmd, params) in ILI9341_INIT {
+            self.write_command(cmd, params)?;
+        }
+        self.write_command(commands::DISPON, &[])?;
+        delay.delay_ms(10).await;
+        Ok(())
+    }
+
+    /// 应用面板调校参数，立即生效，屏幕内容不变
+    ///
+    /// # 参数
+    /// * `tuning` - 调校参数
+    pub fn apply_tuning(&mut self, tuning: &Tuning) -> Result<(), SPI::Error> {
+        let contrast = tuning.contrast.min(CONTRAST_MAX);
+        self.write_command(commands::VRHS, &[contrast])?;
+        self.write_command(commands::GAMSET, &[tuning.curve.param()])?;
+        self.write_command(commands::PVGAMCTRL, &tuning.positive_gamma)?;
+        self.write_command(commands::NVGAMCTRL, &tuning.negative_gamma)?;
+        let inversion = if tuning.inverted {
+            commands::INVON
+        } else {
+            commands::INVOFF
+        };
+        self.write_command(inversion, &[])
+    }
+
+    /// 设置绘制窗口（包含端点）
+    ///
+    /// # 参数
+    /// * `x0`, `y0` - 左上角坐标
+    /// * `x1`, `y1` - 右下角坐标
+    pub fn set_window(&mut self, x0: u16, y0: u16, x1: u16, y1: u16) -> Result<(), SPI::Error> {
+        let [x0h, x0l] = x0.to_be_bytes();
+        let [x1h, x1l] = x1.to_be_bytes();
+        let [y0h, y0l] = y0.to_be_bytes();
+        let [y1h, y1l] = y1.to_be_bytes();
+        self.write_command(commands::CASET, &[x0h, x0l, x1h, x1l])?;
+        self.write_command(commands::RASET, &[y0h, y0l, y1h, y1l])?;
+        self.write_command(commands::RAMWR, &[])
+    }
+
+    /// 用单一颜色填充矩形区域
+    ///
+    /// 区域超出屏幕的部分会被裁剪，颜色数据按 [FILL_BUF_LEN] 字节的栈缓冲区分块写入
+    ///
+    /// # 参数
+    /// * `x`, `y` - 左上角坐标
+    /// * `w`, `h` - 宽度和高度
+    /// * `color` - 填充颜色
+    pub fn fill_rectangle(
+        &mut self,
+        x: u16,
+        y: u16,
+        w: u16,
+        h: u16,
+        color: Rgb565,
+    ) -> Result<(), SPI::Error> {
+        let mut buf = [0u8; FILL_BUF_LEN];
+        self.fill_rectangle_with(x, y, w, h, color, &mut buf)
+    }
+
+    /// 用调用者提供的缓冲区填充矩形区域
+    ///
+    /// 与 [St7789::fill_rectangle] 相同，但每次写入的数据量为缓冲区长度（奇数长度舍去最后
+    /// 一个字节）。大块连续写入时缓冲区越大，写入次数和每次写入的固定开销越少。
+    /// 调用后缓冲区的内容不确定
+    ///
+    /// # 参数
+    /// * `x`, `y` - 左上角坐标
+    /// * `w`, `h` - 宽度和高度
+    /// * `color` - 填充颜色
+    /// * `buf` - 存放重复颜色数据的缓冲区，至少 2 字节，否则忽略
+    pub fn fill_rectangle_with(
+        &mut self,
+        x: u16,
+        y: u16,
+        w: u16,
+        h: u16,
+        color: Rgb565,
+        buf: &mut [u8],
+    ) -> Result<(), SPI::Error> {
+        let Some((w, h, chunk)) = prepare_fill(x, y, w, h, color, buf) else {
+            return Ok(());
+        };
+        self.set_window(x, y, x + w - 1, y + h - 1)?;
+
+        let mut remaining = w as usize * h as usize * 2;
+        while remaining > 0 {
+            let len = remaining.min(chunk);
+            self.write_data(&buf[..len])?;
+            remaining -= len;
+        }
+        Ok(())
+    }
+
+    /// 把一块 RGB565 像素数据写入矩形区域
+    ///
+    /// 与 [DrawTarget] 逐点绘制不同，整块数据只设置一次窗口，适合绘制精灵图
+    ///
+    /// # 参数
+    /// * `x`, `y` - 左上角坐标，区域必须完整位于屏幕内，否则忽略
+    /// * `w`, `h` - 宽度和高度
+    /// * `pixels` - 按行排列的像素，每像素 2 字节（大端），长度至少为 `w * h * 2`
+    pub fn blit(
+        &mut self,
+        x: u16,
+        y: u16,
+        w: u16,
+        h: u16,
+        pixels: &[u8],
+    ) -> Result<(), SPI::Error> {
+        let len = w as usize * h as usize * 2;
+        if w == 0 || h == 0 || x + w > WIDTH || y + h > HEIGHT || pixels.len() < len {
+            return Ok(());
+        }
+        self.set_window(x, y, x + w - 1, y + h - 1)?;
+        self.write_data(&pixels[..len])
+    }
+
+    /// 把一块 RGB565 像素数据旋转、放大后写入矩形区域
+    ///
+    /// 按写入顺序逐个取源像素，不需要额外的缓冲区存放变换后的整块图像
+    ///
+    /// # 参数
+    /// * `x`, `y` - 变换后左上角的坐标，变换后的区域必须完整位于屏幕内，否则忽略
+    /// * `w`, `h` - 源像素块的宽度和高度
+    /// * `pixels` - 源像素，格式与 [St7789::blit] 相同
+    /// * `transform` - 旋转角度和放大倍数
+    pub fn blit_transformed(
+        &mut self,
+        x: u16,
+        y: u16,
+        w: u16,
+        h: u16,
+        pixels: &[u8],
+        transform: Transform,
+    ) -> Result<(), SPI::Error> {
+        if transform == Transform::IDENTITY {
+            return self.blit(x, y, w, h, pixels);
+        }
+        let (dw, dh) = transform.size(w, h);
+        let len = w as usize * h as usize * 2;
+        if dw == 0 || dh == 0 || x + dw > WIDTH || y + dh > HEIGHT || pixels.len() < len {
+            return Ok(());
+        }
+        self.set_window(x, y, x + dw - 1, y + dh - 1)?;
+
+        let mut buf = [0u8; FILL_BUF_LEN];
+        let mut pos = 0;
+        for dy in 0..dh {
+            for dx in 0..dw {
+                let (sx, sy) = transform.source(w, h, dx, dy);
+                let i = (sy as usize * w as usize + sx as usize) * 2;
+                buf[pos..pos + 2].copy_from_slice(&pixels[i..i + 2]);
+                pos += 2;
+                if pos == FILL_BUF_LEN {
+                    self.write_data(&buf)?;
+                    pos = 0;
+                }
+            }
+        }
+        if pos > 0 {
+            self.write_data(&buf[..pos])?;
+        }
+        Ok(())
+    }
+
+    /// 用单一颜色填充整个屏幕
+    pub fn fill_screen(&mut self, color: Rgb565) -> Result<(), SPI::Error> {
+        self.fill_rectangle(0, 0, WIDTH, HEIGHT, color)
+    }
+
+    /// 进入或退出睡眠模式
+    ///
+    /// # 参数
+    /// * `sleep` - true 表示进入睡眠，false 表示唤醒
+    /// * `delay` - 命令之间的等待
+    pub async fn set_sleep(
+        &mut self,
+        sleep: bool,
+        delay: &mut impl DelayNs,
+    ) -> Result<(), SPI::Error> {
+        if sleep {
+            self.write_command(commands::DISPOFF, &[])?;
+            self.write_command(commands::SLPIN, &[])?;
+        } else {
+            self.write_command(commands::SLPOUT, &[])?;
+            delay.delay_ms(120).await;
+            self.write_command(commands::DISPON, &[])?;
+        }
+        // SLPIN/SLPOUT 之后需等待 5 毫秒才能发送下一条命令
+        delay.delay_ms(5).await;
+        Ok(())
+    }
+}
+
+impl<SPI, DC> OriginDimensions for St7789<SPI, DC> {
+    fn size(&self) -> Size {
+        Size::new(WIDTH as u32, HEIGHT as u32)
+    }
+}
+
+impl<SPI, DC> DrawTarget for St7789<SPI, DC>
+where
+    SPI: SpiDevice,
+    DC: OutputPin<Error = Infallible>,
+{
+    type Color = Rgb565;
+    type Error = SPI::Error;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for Pixel(point, color) in pixels {
+            if point.x < 0 || point.y < 0 || point.x >= WIDTH as i32 || point.y >= HEIGHT as i32 {
+                continue;
+            }
+            let (x, y) = (point.x as u16, point.y as u16);
+            self.set_window(x, y, x, y)?;
+            self.write_data(&RawU16::from(color).into_inner().to_be_bytes())?;
+        }
+        Ok(())
+    }
+
+    fn fill_solid(&mut self, area: &Rectangle, color: Self::Color) -> Result<(), Self::Error> {
+        let area = area.intersection(&self.bounding_box());
+        if let Some(bottom_right) = area.bottom_right() {
+            let top_left = area.top_left;
+            self.fill_rectangle(
+                top_left.x as u16,
+                top_left.y as u16,
+                (bottom_right.x - top_left.x + 1) as u16,
+                (bottom_right.y - top_left.y + 1) as u16,
+                color,
+            )?;
+        }
+        Ok(())
+    }
+
+    fn clear(&mut self, color: Self::Color) -> Result<(), Self::Error> {
+        self.fill_screen(color)
+    }
+}
+
+impl<SPI, DC> St7789<SPI, DC>
+where
+    SPI: AsyncSpiDevice,
+    DC: OutputPin<Error = Infallible>,
+{
+    /// 借用同一个 SPI 设备和 DC 引脚的异步驱动
+    ///
+    /// SPI 设备同时实现了阻塞和异步接口时，大块传输用异步驱动，其余操作仍用本驱动
+    pub fn as_async(&mut self) -> St7789Async<&mut SPI, &mut DC> {
+        St7789Async::new(&mut self.spi, &mut self.dc)
+    }
+}
+
+/// ST7789 驱动的异步版本
+///
+/// 命令序列和像素格式与 [St7789] 相同，SPI 传输通过 [AsyncSpiDevice] 进行，
+/// 等待传输完成期间执行器可以运行其他任务。只提供大块传输用到的接口，
+/// 读 ID 和状态、调校参数、逐点绘制见 [St7789]
+pub struct St7789Async<SPI, DC> {
+    spi: SPI,
+    dc: DC,
+}
+
+impl<SPI, DC> St7789Async<SPI, DC>
+where
+    SPI: AsyncSpiDevice,
+    DC: OutputPin<Error = Infallible>,
+{
+    /// 创建驱动实例
+    ///
+    /// # 参数
+    /// * `spi` - LCD 所在的 SPI 设备
+    /// * `dc` - 数据/命令选择引脚（低电平为命令，高电平为数据）
+    pub fn new(spi: SPI, dc: DC) -> Self {
+        St7789Async { spi, dc }
+    }
+
+    /// 释放 SPI 设备和 DC 引脚
+    pub fn release(self) -> (SPI, DC) {
+        (self.spi, self.dc)
+    }
+
+    /// 设置 DC 引脚，见 [St7789] 的同名方法
+    fn set_dc(&mut self, data: bool) {
+        let result = if data {
+            self.dc.set_high()
+        } else {
+            self.dc.set_low()
+        };
+        match result {
+            Ok(()) => {}
+            Err(never) => match never {},
+        }
+    }
+
+    /// 发送命令及其参数
+    ///
+    /// # 参数
+    /// * `cmd` - 命令字节
+    /// * `params` - 参数字节
+    pub async fn write_command(&mut self, cmd: u8, params: &[u8]) -> Result<(), SPI::Error> {
+        self.set_dc(false);
+        self.spi.write(&[cmd]).await?;
+        self.set_dc(true);
+        if !params.is_empty() {
+            self.spi.write(params).await?;
+        }
+        Ok(())
+    }
+
+    /// 发送像素数据（需先调用 [Self::set_window]）
+    pub async fn write_data(&mut self, data: &[u8]) -> Result<(), SPI::Error> {
+        self.set_dc(true);
+        self.spi.write(data).await
+    }
+
+    /// 初始化 ST7789 控制器，命令序列与 [St7789::init] 相同
+    ///
+    /// # 参数
+    /// * `delay` - 命令之间的等待
+    pub async fn init(&mut self, delay: &mut impl DelayNs) -> Result<(), SPI::Error> {
+        self.run_init(&ST7789_INIT, delay).await
+    }
+
+    /// 按 ILI9341 初始化控制器，命令序列与 [St7789::init_ili9341] 相同
+    ///
+    /// # 参数
+    /// * `delay` - 命令之间的等待
+    pub async fn init_ili9341(&mut self, delay: &mut impl DelayNs) -> Result<(), SPI::Error> {
+        self.run_init(&ILI9341_INIT, delay).await
+    }
+
+    /// 退出睡眠、发送初始化命令并开启显示
+    async fn run_init(
+        &mut self,
+        sequence: &[(u8, &[u8])],
+        delay: &mut impl DelayNs,
+    ) -> Result<(), SPI::Error> {
+        self.write_command(commands::SLPOUT, &[]).await?;
+        delay.delay_ms(120).await;
+        for &(cmd, params) in sequence {
+            self.write_command(cmd, params).await?;
+        }
+        self.write_command(commands::DISPON, &[]).await?;
+        delay.delay_ms(10).await;
+        Ok(())
+    }
+
+    /// 设置绘制窗口（包含端点）
+    ///
+    /// # 参数
+    /// * `x0`, `y0` - 左上角坐标
+    /// * `x1`, `y1` - 右下角坐标
+    pub async fn set_window(
+        &mut self,
+        x0: u16,
+        y0: u16,
+        x1: u16,
+        y1: u16,
+    ) -> Result<(), SPI::Error> {
+        let [x0h, x0l] = x0.to_be_bytes();
+        let [x1h, x1l] = x1.to_be_bytes();
+        let [y0h, y0l] = y0.to_be_bytes();
+        let [y1h, y1l] = y1.to_be_bytes();
+        self.write_command(commands::CASET, &[x0h, x0l, x1h, x1l])
+            .await?;
+        self.write_command(commands::RASET, &[y0h, y0l, y1h, y1l])
+            .await?;
+        self.write_command(commands::RAMWR, &[]).await
+    }
+
+    /// 用调用者提供的缓冲区填充矩形区域，见 [St7789::fill_rectangle_with]
+    ///
+    /// # 参数
+    /// * `x`, `y` - 左上角坐标
+    /// * `w`, `h` - 宽度和高度
+    /// * `color` - 填充颜色
+    /// * `buf` - 存放重复颜色数据的缓冲区，至少 2 字节，否则忽略
+    pub async fn fill_rectangle_with(
+        &mut self,
+        x: u16,
+        y: u16,
+        w: u16,
+        h: u16,
+        color: Rgb565,
+        buf: &mut [u8],
+    ) -> Result<(), SPI::Error> {
+        let Some((w, h, chunk)) = prepare_fill(x, y, w, h, color, buf) else {
+            return Ok(());
+        };
+        self.set_window(x, y, x + w - 1, y + h - 1).await?;
+
+        let mut remaining = w as usize * h as usize * 2;
+        while remaining > 0 {
+            let len = remaining.min(chunk);
+            self.write_data(&buf[..len]).await?;
+            remaining -= len;
+        }
+        Ok(())
+    }
+
+    /// 用单一颜色填充整个屏幕
+    ///
+    /// # 参数
+    /// * `color` - 填充颜色
+    /// * `buf` - 存放重复颜色数据的缓冲区，每次写入的数据量为缓冲区长度
+    pub async fn fill_screen(&mut self, color: Rgb565, buf: &mut [u8]) -> Result<(), SPI::Error> {
+        self.fill_rectangle_with(0, 0, WIDTH, HEIGHT, color, buf)
+            .await
+    }
+
+    /// 把一块 RGB565 像素数据写入矩形区域，参数和限制与 [St7789::blit] 相同
+    ///
+    /// 整块数据在一次写入中发送，用于把绘制好的条带或帧缓冲区刷新到屏幕上
+    pub async fn flush(
+        &mut self,
+        x: u16,
+        y: u16,
+        w: u16,
+        h: u16,
+        pixels: &[u8],
+    ) -> Result<(), SPI::Error> {
+        let len = w as usize * h as usize * 2;
+        if w == 0 || h == 0 || x + w > WIDTH || y + h > HEIGHT || pixels.len() < len {
+            return Ok(());
+        }
+        self.set_window(x, y, x + w - 1, y + h - 1).await?;
+        self.write_data(&pixels[..len]).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::future::Future;
+    use core::pin::pin;
+    use core::task::{Context, Poll, Waker};
+    use embedded_hal::digital::ErrorType;
+    use embedded_hal_mock::eh1::delay::{CheckedDelay, Transaction as DelayTransaction};
+    use embedded_hal_mock::eh1::digital::{Mock as PinMock, State, Transaction as PinTransaction};
+    use embedded_hal_mock::eh1::spi::{Mock as SpiMock, Transaction as SpiTransaction};
+
+    /// 不会失败的 DC 引脚，包装 embedded-hal-mock 的引脚
+    struct Dc(PinMock);
+
+    impl ErrorType for Dc {
+        type Error = Infallible;
+    }
+
+    impl OutputPin for Dc {
+        fn set_low(&mut self) -> Result<(), Infallible> {
+            self.0.set_low().unwrap();
+            Ok(())
+        }
+
+        fn set_high(&mut self) -> Result<(), Infallible> {
+            self.0.set_high().unwrap();
+            Ok(())
+        }
+    }
+
+    type Lcd = St7789<SpiMock<u8>, Dc>;
+    type AsyncLcd = St7789Async<SpiMock<u8>, Dc>;
+
+    /// 预期的 SPI 传输和 DC 电平变化
+    #[derive(Default)]
+    struct Expect {
+        spi: Vec<SpiTransaction<u8>>,
+        dc: Vec<PinTransaction>,
+    }
+
+    impl Expect {
+        fn write(&mut self, bytes: &[u8]) {
+            self.spi.push(SpiTransaction::transaction_start());
+            self.spi.push(SpiTransaction::write_vec(bytes.to_vec()));
+            self.spi.push(SpiTransaction::transaction_end());
+        }
+
+        fn command(mut self, cmd: u8, params: &[u8]) -> Self {
+            self.dc.push(PinTransaction::set(State::Low));
+            self.write(&[cmd]);
+            self.dc.push(PinTransaction::set(State::High));
+            if !params.is_empty() {
+                self.write(params);
+            }
+            self
+        }
+
+        fn data(mut self, data: &[u8]) -> Self {
+            self.dc.push(PinTransaction::set(State::High));
+            self.write(data);
+            self
+        }
+
+        fn window(self, x0: u16, y0: u16, x1: u16, y1: u16) -> Self {
+            let [x0h, x0l] = x0.to_be_bytes();
+            let [x1h, x1l] = x1.to_be_bytes();
+            let [y0h, y0l] = y0.to_be_bytes();
+            let [y1h, y1l] = y1.to_be_bytes();
+            self.command(commands::CASET, &[x0h, x0l, x1h, x1l])
+                .command(commands::RASET, &[y0h, y0l, y1h, y1l])
+                .command(commands::RAMWR, &[])
+        }
+
+        fn read(mut self, cmd: u8, response: &[u8]) -> Self {
+            self.dc.push(PinTransaction::set(State::Low));
+            self.spi.push(SpiTransaction::transaction_start());
+            self.spi.push(SpiTransaction::write_vec(vec![cmd]));
+            self.spi.push(SpiTransaction::read_vec(response.to_vec()));
+            self.spi.push(SpiTransaction::transaction_end());
+            self
+        }
+
+        fn lcd(&self) -> Lcd {
+            St7789::new(SpiMock::new(&self.spi), Dc(PinMock::new(&self.dc)))
+        }
+
+        fn lcd_async(&self) -> AsyncLcd {
+            St7789Async::new(SpiMock::new(&self.spi), Dc(PinMock::new(&self.dc)))
+        }
+    }
+
+    /// 检查所有预期的操作都已发生
+    fn done(lcd: Lcd) {
+        let St7789 { mut spi, mut dc } = lcd;
+        spi.done();
+        dc.0.done();
+    }
+
+    /// 检查异步驱动的所有预期操作都已发生
+    fn done_async(lcd: AsyncLcd) {
+        let (mut spi, mut dc) = lcd.release();
+        spi.done();
+        dc.0.done();
+    }
+
+    /// 运行不会挂起的 future（模拟的延时立即完成）
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = pin!(future);
+        let mut cx = Context::from_waker(Waker::noop());
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return output;
+            }
+        }
+    }
+
+    /// 红色像素的大端编码
+    const RED: [u8; 2] = [0xF8, 0x00];
+
+    fn red_pixels(count: usize) -> Vec<u8> {
+        RED.repeat(count)
+    }
+
+    #[test]
+    fn init_sends_command_sequence() {
+        let expect = Expect::default()
+            .command(commands::SLPOUT, &[])
+            .command(commands::MADCTL, &[MADCTL_LANDSCAPE])
+            .command(commands::COLMOD, &[0x05])
+            .command(commands::PORCTRL, &[0x0C, 0x0C, 0x00, 0x33, 0x33])
+            .command(commands::GCTRL, &[0x35])
+            .command(commands::VCOMS, &[0x32])
+            .command(commands::LCMCTRL, &[0x0C])
+            .command(commands::VDVVRHEN, &[0x01])
+            .command(commands::VRHS, &[0x10])
+            .command(commands::VDVS, &[0x20])
+            .command(commands::FRCTRL2, &[0x0F])
+            .command(commands::PWCTRL1, &[0xA4, 0xA1])
+            .command(commands::PVGAMCTRL, &PV_GAMMA)
+            .command(commands::NVGAMCTRL, &NV_GAMMA)
+            .command(commands::INVON, &[])
+            .command(commands::DISPON, &[]);
+        let mut delay = CheckedDelay::new(&[
+            DelayTransaction::async_delay_ms(120),
+            DelayTransaction::async_delay_ms(10),
+        ]);
+
+        let mut lcd = expect.lcd();
+        block_on(lcd.init(&mut delay)).unwrap();
+        done(lcd);
+        delay.done();
+    }
+
+    #[test]
+    fn apply_tuning_sends_gamma_and_inversion() {
+        let mut positive_gamma = PV_GAMMA;
+        positive_gamma[0] = 0xF0;
+        let tuning = Tuning {
+            positive_gamma,
+            curve: GammaCurve::G1_8,
+            contrast: 0x30,
+            inverted: false,
+            ..Tuning::DEFAULT
+        };
+        let expect = Expect::default()
+            .command(commands::VRHS, &[CONTRAST_MAX])
+            .command(commands::GAMSET, &[0x02])
+            .command(commands::PVGAMCTRL, &positive_gamma)
+            .command(commands::NVGAMCTRL, &NV_GAMMA)
+            .command(commands::INVOFF, &[]);
+
+        let mut lcd = expect.lcd();
+        lcd.apply_tuning(&tuning).unwrap();
+        done(lcd);
+    }
+
+    #[test]
+    fn default_tuning_matches_init() {
+        let expect = Expect::default()
+            .command(commands::VRHS, &[0x10])
+            .command(commands::GAMSET, &[0x01])
+            .command(commands::PVGAMCTRL, &PV_GAMMA)
+            .command(commands::NVGAMCTRL, &NV_GAMMA)
+            .command(commands::INVON, &[]);
+
+        let mut lcd = expect.lcd();
+        lcd.apply_tuning(&Tuning::DEFAULT).unwrap();
+        done(lcd);
+    }
+
+    #[test]
+    fn read_id_and_status_skip_dummy_bit() {
+        // ST7789V 的 ID 85 85 52 错后 1 位：0 1000 0101 1000 0101 0101 0010 ...
+        let expect = Expect::default()
+            .read(commands::RDDID, &[0x42, 0xC2, 0xA9, 0x00])
+            // 已唤醒（bit 17）、颜色反转（bit 13）、显示开启（bit 10）
+            .read(commands::RDDST, &[0x00, 0x01, 0x12, 0x00, 0x00]);
+
+        let mut lcd = expect.lcd();
+        let id = lcd.read_id().unwrap();
+        assert_eq!(id.bytes(), ST7789_ID);
+        let status = lcd.read_status().unwrap();
+        assert_eq!(status, DisplayStatus(0x0002_2400));
+        assert!(status.is_running() && status.is_inverted());
+        assert!(!status.booster_on());
+        done(lcd);
+
+        assert!(!DisplayStatus(0).is_running());
+        assert!(!DisplayStatus(u32::MAX).is_running());
+    }
+
+    #[test]
+    fn detect_identifies_controllers() {
+        let expect = Expect::default().read(commands::RDDID, &[0x42, 0xC2, 0xA9, 0x00]);
+        let mut lcd = expect.lcd();
+        assert_eq!(lcd.detect().unwrap(), Controller::St7789);
+        done(lcd);
+
+        let expect = Expect::default()
+            .read(commands::RDDID, &[0x00, 0x00, 0x00, 0x00])
+            .read(ili9341::RDID4, &[0x00, 0x00, 0x93, 0x41]);
+        let mut lcd = expect.lcd();
+        assert_eq!(lcd.detect().unwrap(), Controller::Ili9341);
+        done(lcd);
+
+        let expect = Expect::default()
+            .read(commands::RDDID, &[0xFF; 4])
+            .read(ili9341::RDID4, &[0xFF; 4]);
+        let mut lcd = expect.lcd();
+        assert_eq!(lcd.detect().unwrap(), Controller::Unknown);
+        done(lcd);
+    }
+
+    #[test]
+    fn sleep_and_wake_sequences() {
+        let expect = Expect::default()
+            .command(commands::DISPOFF, &[])
+            .command(commands::SLPIN, &[])
+            .command(commands::SLPOUT, &[])
+            .command(commands::DISPON, &[]);
+        let mut delay = CheckedDelay::new(&[
+            DelayTransaction::async_delay_ms(5),
+            DelayTransaction::async_delay_ms(120),
+            DelayTransaction::async_delay_ms(5),
+        ]);
+
+        let mut lcd = expect.lcd();
+        block_on(lcd.set_sleep(true, &mut delay)).unwrap();
+        block_on(lcd.set_sleep(false, &mut delay)).unwrap();
+        done(lcd);
+        delay.done();
+    }
+
+    #[test]
+    fn set_window_encodes_big_endian_coordinates() {
+        let expect = Expect::default()
+            .command(commands::CASET, &[0x01, 0x02, 0x01, 0x3F])
+            .command(commands::RASET, &[0x00, 0x03, 0x00, 0xEF])
+            .command(commands::RAMWR, &[]);
+
+        let mut lcd = expect.lcd();
+        lcd.set_window(0x0102, 3, 0x013F, 0xEF).unwrap();
+        done(lcd);
+    }
+
+    #[test]
+    fn fill_rectangle_clips_to_screen() {
+        let expect = Expect::default()
+            .window(310, 235, 319, 239)
+            .data(&red_pixels(10 * 5));
+
+        let mut lcd = expect.lcd();
+        lcd.fill_rectangle(310, 235, 50, 50, Rgb565::RED).unwrap();
+        done(lcd);
+    }
+
+    #[test]
+    fn fill_rectangle_outside_screen_or_empty_is_ignored() {
+        let mut lcd = Expect::default().lcd();
+        lcd.fill_rectangle(WIDTH, 0, 10, 10, Rgb565::RED).unwrap();
+        lcd.fill_rectangle(0, HEIGHT, 10, 10, Rgb565::RED).unwrap();
+        lcd.fill_rectangle(0, 0, 0, 10, Rgb565::RED).unwrap();
+        lcd.fill_rectangle(0, 0, 10, 0, Rgb565::RED).unwrap();
+        done(lcd);
+    }
+
+    #[test]
+    fn fill_screen_is_sent_in_buffer_sized_chunks() {
+        let total = WIDTH as usize * HEIGHT as usize * 2;
+        let chunk = red_pixels(FILL_BUF_LEN / 2);
+        let mut expect = Expect::default().window(0, 0, WIDTH - 1, HEIGHT - 1);
+        for _ in 0..total / FILL_BUF_LEN {
+            expect = expect.data(&chunk);
+        }
+        let rest = total % FILL_BUF_LEN;
+        if rest > 0 {
+            expect = expect.data(&chunk[..rest]);
+        }
+
+        let mut lcd = expect.lcd();
+        lcd.fill_screen(Rgb565::RED).unwrap();
+        done(lcd);
+    }
+
+    #[test]
+    fn fill_rectangle_with_uses_caller_buffer_size() {
+        let total = WIDTH as usize * 10 * 2;
+        let chunk = red_pixels(1500);
+        let mut expect = Expect::default().window(0, 0, WIDTH - 1, 9);
+        for _ in 0..total / 3000 {
+            expect = expect.data(&chunk);
+        }
+        expect = expect.data(&chunk[..total % 3000]);
+
+        let mut lcd = expect.lcd();
+        // 奇数长度舍去最后一个字节
+        let mut buf = [0u8; 3001];
+        lcd.fill_rectangle_with(0, 0, WIDTH, 10, Rgb565::RED, &mut buf)
+            .unwrap();
+        done(lcd);
+    }
+
+    #[test]
+    fn fill_rectangle_with_small_area_writes_once() {
+        let expect = Expect::default()
+            .window(1, 2, 4, 3)
+            .data(&red_pixels(4 * 2));
+
+        let mut lcd = expect.lcd();
+        let mut buf = [0u8; 4096];
+        lcd.fill_rectangle_with(1, 2, 4, 2, Rgb565::RED, &mut buf)
+            .unwrap();
+        let mut tiny = [0u8; 1];
+        lcd.fill_rectangle_with(1, 2, 4, 2, Rgb565::RED, &mut tiny)
+            .unwrap();
+        done(lcd);
+    }
+
+    #[test]
+    fn fill_solid_clips_negative_origin() {
+        let expect = Expect::default()
+            .window(0, 0, 4, 2)
+            .data(&red_pixels(5 * 3));
+
+        let mut lcd = expect.lcd();
+        let area = Rectangle::new(Point::new(-5, -7), Size::new(10, 10));
+        lcd.fill_solid(&area, Rgb565::RED).unwrap();
+        done(lcd);
+    }
+
+    #[test]
+    fn fill_solid_outside_screen_draws_nothing() {
+        let mut lcd = Expect::default().lcd();
+        let area = Rectangle::new(Point::new(WIDTH as i32, 0), Size::new(10, 10));
+        lcd.fill_solid(&area, Rgb565::RED).unwrap();
+        let area = Rectangle::new(Point::new(-20, -20), Size::new(10, 10));
+        lcd.fill_solid(&area, Rgb565::RED).unwrap();
+        done(lcd);
+    }
+
+    #[test]
+    fn draw_iter_skips_offscreen_pixels() {
+        let expect = Expect::default().window(5, 6, 5, 6).data(&RED);
+
+        let mut lcd = expect.lcd();
+        let pixels = [
+            Pixel(Point::new(-1, 0), Rgb565::RED),
+            Pixel(Point::new(5, 6), Rgb565::RED),
+            Pixel(Point::new(WIDTH as i32, 0), Rgb565::RED),
+            Pixel(Point::new(0, HEIGHT as i32), Rgb565::RED),
+        ];
+        lcd.draw_iter(pixels).unwrap();
+        done(lcd);
+    }
+
+    #[test]
+    fn blit_writes_whole_block_once() {
+        let pixels = [1, 2, 3, 4, 5, 6, 7, 8, 9];
+        let expect = Expect::default()
+            .window(318, 239, 319, 239)
+            .data(&pixels[..4]);
+
+        let mut lcd = expect.lcd();
+        lcd.blit(318, 239, 2, 1, &pixels).unwrap();
+        done(lcd);
+    }
+
+    #[test]
+    fn blit_rejects_partial_or_short_blocks() {
+        let pixels = [0u8; 64];
+        let mut lcd = Expect::default().lcd();
+        lcd.blit(319, 0, 2, 1, &pixels).unwrap();
+        lcd.blit(0, 239, 1, 2, &pixels).unwrap();
+        lcd.blit(0, 0, 0, 1, &pixels).unwrap();
+        lcd.blit(0, 0, 8, 8, &pixels).unwrap();
+        done(lcd);
+    }
+
+    /// 3x2 的源像素块，每个像素的两个字节都是它的编号
+    ///
+    /// ```text
+    /// 1 2 3
+    /// 4 5 6
+    /// ```
+    const SOURCE: [u8; 12] = [1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6];
+
+    /// 按变换后的顺序排列的像素编号
+    fn transformed(transform: Transform) -> Vec<u8> {
+        let (w, h) = transform.size(3, 2);
+        let mut out = Vec::new();
+        for y in 0..h {
+            for x in 0..w {
+                let (sx, sy) = transform.source(3, 2, x, y);
+                out.push(SOURCE[(sy as usize * 3 + sx as usize) * 2]);
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn transform_rotates_clockwise() {
+        assert_eq!(transformed(Transform::IDENTITY), [1, 2, 3, 4, 5, 6]);
+        let r90 = Transform::rotate(Rotation::Deg90);
+        assert_eq!(r90.size(3, 2), (2, 3));
+        assert_eq!(transformed(r90), [4, 1, 5, 2, 6, 3]);
+        let r180 = Transform::rotate(Rotation::Deg180);
+        assert_eq!(transformed(r180), [6, 5, 4, 3, 2, 1]);
+        let r270 = Transform::rotate(Rotation::Deg270);
+        assert_eq!(transformed(r270), [3, 6, 2, 5, 1, 4]);
+    }
+
+    #[test]
+    fn transform_scales_after_rotating() {
+        let scaled = Transform::rotate(Rotation::Deg90).scaled(2);
+        assert_eq!(scaled.size(3, 2), (4, 6));
+        assert_eq!(
+            transformed(scaled),
+            [
+                4, 4, 1, 1, 4, 4, 1, 1, 5, 5, 2, 2, 5, 5, 2, 2, 6, 6, 3, 3, 6, 6, 3, 3
+            ]
+        );
+        assert_eq!(Transform::IDENTITY.scaled(0).size(3, 2), (3, 2));
+    }
+
+    #[test]
+    fn blit_transformed_streams_rotated_pixels() {
+        let rotated = [4, 4, 1, 1, 5, 5, 2, 2, 6, 6, 3, 3];
+        let expect = Expect::default().window(10, 20, 11, 22).data(&rotated);
+        let mut lcd = expect.lcd();
+        let r90 = Transform::rotate(Rotation::Deg90);
+        lcd.blit_transformed(10, 20, 3, 2, &SOURCE, r90).unwrap();
+        // 放大后超出屏幕时忽略
+        let r90x2 = r90.scaled(2);
+        lcd.blit_transformed(317, 0, 3, 2, &SOURCE, r90x2).unwrap();
+        done(lcd);
+    }
+
+    #[test]
+    fn async_init_matches_blocking_sequence() {
+        let mut expect = Expect::default().command(commands::SLPOUT, &[]);
+        for (cmd, params) in ST7789_INIT {
+            expect = expect.command(cmd, params);
+        }
+        expect = expect.command(commands::DISPON, &[]);
+        let mut delay = CheckedDelay::new(&[
+            DelayTransaction::async_delay_ms(120),
+            DelayTransaction::async_delay_ms(10),
+            DelayTransaction::async_delay_ms(120),
+            DelayTransaction::async_delay_ms(10),
+        ]);
+
+        let mut lcd = expect.lcd();
+        block_on(lcd.init(&mut delay)).unwrap();
+        done(lcd);
+        let mut lcd = expect.lcd_async();
+        block_on(lcd.init(&mut delay)).unwrap();
+        done_async(lcd);
+        delay.done();
+    }
+
+    #[test]
+    fn async_fill_screen_uses_caller_buffer_size() {
+        let total = WIDTH as usize * HEIGHT as usize * 2;
+        let chunk = red_pixels(5000);
+        let mut expect = Expect::default().window(0, 0, WIDTH - 1, HEIGHT - 1);
+        for _ in 0..total / 10000 {
+            expect = expect.data(&chunk);
+        }
+        expect = expect.data(&chunk[..total % 10000]);
+
+        let mut lcd = expect.lcd_async();
+        let mut buf = [0u8; 10000];
+        block_on(lcd.fill_screen(Rgb565::RED, &mut buf)).unwrap();
+        done_async(lcd);
+    }
+
+    #[test]
+    fn async_flush_writes_whole_block_once() {
+        let pixels = red_pixels(4 * 2);
+        let expect = Expect::default().window(2, 3, 5, 4).data(&pixels);
+
+        let mut lcd = expect.lcd_async();
+        block_on(lcd.flush(2, 3, 4, 2, &pixels)).unwrap();
+        block_on(lcd.flush(WIDTH - 1, 0, 2, 1, &pixels)).unwrap();
+        done_async(lcd);
+    }
+
+    #[test]
+    fn as_async_borrows_the_same_bus() {
+        let expect = Expect::default()
+            .window(0, 0, 0, 0)
+            .data(&RED)
+            .window(1, 0, 1, 0)
+            .data(&RED);
+
+        let mut lcd = expect.lcd();
+        block_on(lcd.as_async().flush(0, 0, 1, 1, &RED)).unwrap();
+        lcd.fill_rectangle(1, 0, 1, 1, Rgb565::RED).unwrap();
+        done(lcd);
+    }
+}
@@ -0,0 +1,537 @@
+//! XL9555 I2C GPIO 扩展芯片
+//!
+//! 寄存器级的读写逻辑，泛型于 embedded-hal 的 [I2c]。
+//!
+//! XL9555 具有 16 个 GPIO 引脚，分为两个 8 位端口：
+//! - P0 端口：P0.0-P0.7（摄像头、蜂鸣器等控制信号）
+//! - P1 端口：P1.0-P1.7（低 4 位为 LCD 控制信号，高 4 位为按键）
+//!
+//! 引脚在本模块中统一用 16 位掩码表示（见 [io_bits]），低 8 位为 P0，高 8 位为 P1。
+
+use embedded_hal::i2c::I2c;
+
+/// 7 位 I2C 地址
+pub const ADDR: u8 = 0x20;
+
+/// 寄存器地址定义
+///
+/// XL9555 芯片包含以下寄存器：
+/// - 输入端口寄存器：用于读取 GPIO 引脚状态
+/// - 输出端口寄存器：用于设置 GPIO 引脚输出状态
+/// - 极性反转寄存器：用于设置 GPIO 引脚极性
+/// - 配置寄存器：用于设置 GPIO 引脚方向（输入/输出）
+///
+/// 寄存器地址说明：
+/// - INPUT_PORT_0: 0x00 - P0 端口输入寄存器
+/// - INPUT_PORT_1: 0x01 - P1 端口输入寄存器
+/// - OUTPUT_PORT_0: 0x02 - P0 端口输出寄存器
+/// - OUTPUT_PORT_1: 0x03 - P1 端口输出寄存器
+/// - INVERSION_PORT_0: 0x04 - P0 端口极性反转寄存器
+/// - INVERSION_PORT_1: 0x05 - P1 端口极性反转寄存器
+/// - CONFIG_PORT_0: 0x06 - P0 端口方向配置寄存器
+/// - CONFIG_PORT_1: 0x07 - P1 端口方向配置寄存器
+#[allow(unused)]
+pub mod registers {
+    pub const INPUT_PORT_0: u8 = 0;
+    pub const INPUT_PORT_1: u8 = 1;
+    pub const OUTPUT_PORT_0: u8 = 2;
+    pub const OUTPUT_PORT_1: u8 = 3;
+    pub const INVERSION_PORT_0: u8 = 4;
+    pub const INVERSION_PORT_1: u8 = 5;
+    pub const CONFIG_PORT_0: u8 = 6;
+    pub const CONFIG_PORT_1: u8 = 7;
+}
+
+/// IO 位定义
+///
+/// 定义 XL9555 各个 IO 引脚的功能分配
+/// IO 引脚分为两组：
+/// - P0 端口（P0.0-P0.7）：摄像头、蜂鸣器等控制信号
+/// - P1 端口（P1.0-P1.7）：LCD 控制信号和按键输入
+///
+/// 引脚分配说明：
+/// - LCD_BL_IO: P1.0 - LCD 背光控制（备用）
+/// - SLCD_RST_IO: P1.2 - SPI LCD 复位信号
+/// - SLCD_PWR_IO: P1.3 - SPI LCD 电源/背光控制
+/// - KEY0_IO: P1.7 - 按键 0 输入
+/// - KEY1_IO: P1.6 - 按键 1 输入
+/// - KEY2_IO: P1.5 - 按键 2 输入
+/// - KEY3_IO: P1.4 - 按键 3 输入
+#[allow(unused)]
+pub mod io_bits {
+    pub const AP_INT_IO: u16 = 0x0001; // P0.0
+    pub const QMA_INT_IO: u16 = 0x0002; // P0.1
+    pub const SPK_EN_IO: u16 = 0x0004; // P0.2
+    pub const BEEP_IO: u16 = 0x0008; // P0.3
+    pub const OV_PWDN_IO: u16 = 0x0010; // P0.4
+    pub const OV_RESET_IO: u16 = 0x0020; // P0.5
+    pub const GBC_LED_IO: u16 = 0x0040; // P0.6
+    pub const GBC_KEY_IO: u16 = 0x0080; // P0.7
+    pub const LCD_BL_IO: u16 = 0x0100; // P1.0
+    pub const CT_RST_IO: u16 = 0x0200; // P1.1
+    pub const SLCD_RST_IO: u16 = 0x0400; // P1.2
+    pub const SLCD_PWR_IO: u16 = 0x0800; // P1.3
+    pub const KEY3_IO: u16 = 0x1000; // P1.4
+    pub const KEY2_IO: u16 = 0x2000; // P1.5
+    pub const KEY1_IO: u16 = 0x4000; // P1.6
+    pub const KEY0_IO: u16 = 0x8000; // P1.7
+}
+
+/// KEY0-KEY3 对应的引脚，下标即按键编号
+const KEY_BITS: [u16; 4] = [
+    io_bits::KEY0_IO,
+    io_bits::KEY1_IO,
+    io_bits::KEY2_IO,
+    io_bits::KEY3_IO,
+];
+
+/// 初始化引脚方向和输出状态
+///
+/// - P0 端口全部配置为输入
+/// - P1 端口低 4 位为输出（LCD 控制），高 4 位为输入（按键）
+/// - 两个端口的输出寄存器清零
+///
+/// # 参数
+/// * `i2c` - I2C 总线
+pub fn init<I: I2c>(i2c: &mut I) -> Result<(), I::Error> {
+    // 配置寄存器中 0 表示输出，1 表示输入
+    i2c.write(ADDR, &[registers::CONFIG_PORT_0, 0xFF])?;
+    i2c.write(ADDR, &[registers::CONFIG_PORT_1, 0xF0])?;
+    i2c.write(ADDR, &[registers::OUTPUT_PORT_0, 0x00])?;
+    i2c.write(ADDR, &[registers::OUTPUT_PORT_1, 0x00])
+}
+
+/// 按引脚掩码选择端口
+///
+/// # 返回
+/// 端口 0 或 1 的寄存器偏移，以及引脚在该端口内的位
+fn port_of(pins: u16) -> (u8, u8) {
+    if pins & 0x00FF != 0 {
+        (0, pins as u8)
+    } else {
+        (1, (pins >> 8) as u8)
+    }
+}
+
+/// 读取一个寄存器，修改若干位后写回
+fn update_register<I: I2c>(i2c: &mut I, register: u8, bits: u8, set: bool) -> Result<(), I::Error> {
+    let value = read_register(i2c, register)?;
+    i2c.write(ADDR, &[register, with_bits(value, bits, set)])
+}
+
+/// 设置输出引脚的电平
+///
+/// 读取所在端口的输出寄存器，修改对应位后写回，不改变引脚方向。
+/// 不校验写入结果，长期运行的应用应通过 [Shadow] 写入
+///
+/// # 参数
+/// * `i2c` - I2C 总线
+/// * `pins` - [io_bits] 中的引脚，可以是同一端口的多个引脚
+/// * `high` - true 表示高电平
+pub fn write_output<I: I2c>(i2c: &mut I, pins: u16, high: bool) -> Result<(), I::Error> {
+    let (port, bits) = port_of(pins);
+    update_register(i2c, registers::OUTPUT_PORT_0 + port, bits, high)
+}
+
+/// 将引脚配置为输出并设置电平
+///
+/// 先设置输出电平，再切换方向，避免切换瞬间输出错误电平
+///
+/// # 参数
+/// * `i2c` - I2C 总线
+/// * `pins` - [io_bits] 中的引脚，可以是同一端口的多个引脚
+/// * `high` - true 表示高电平
+pub fn set_as_output<I: I2c>(i2c: &mut I, pins: u16, high: bool) -> Result<(), I::Error> {
+    write_output(i2c, pins, high)?;
+    let (port, bits) = port_of(pins);
+    update_register(i2c, registers::CONFIG_PORT_0 + port, bits, false)
+}
+
+/// 校验结果，见 [Shadow]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verify {
+    /// 芯片上的寄存器与期望值一致
+    Ok,
+    /// 不一致（芯片复位过），已重写全部寄存器并校验通过
+    Restored,
+    /// 重写后仍不一致
+    Failed,
+}
+
+/// 输出和配置寄存器的期望值（影子寄存器）
+///
+/// XL9555 欠压或受静电干扰复位后，寄存器恢复为上电默认值（全部为输入，输出寄存器全 1），
+/// 输出悄悄失效，之后的读-改-写还会在默认值上继续修改。通过本类型写入时以软件保存的
+/// 期望值为准，不读取芯片上的旧值；每次写入后读回校验，不一致时说明芯片复位过，
+/// 重写全部输出和配置寄存器。还应定期调用 [Shadow::verify]，发现没有写操作期间的复位。
+///
+/// 写入出错时期望值不变。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Shadow {
+    /// OUTPUT_PORT_0、OUTPUT_PORT_1
+    output: [u8; 2],
+    /// CONFIG_PORT_0、CONFIG_PORT_1
+    config: [u8; 2],
+}
+
+impl Shadow {
+    /// [init] 写入后的寄存器值
+    pub const INIT: Shadow = Shadow {
+        output: [0x00, 0x00],
+        config: [0xFF, 0xF0],
+    };
+
+    /// 设置输出引脚的电平，不改变引脚方向
+    ///
+    /// # 参数
+    /// * `i2c` - I2C 总线
+    /// * `pins` - [io_bits] 中的引脚，可以是同一端口的多个引脚
+    /// * `high` - true 表示高电平
+    pub fn write_output<I: I2c>(
+        &mut self,
+        i2c: &mut I,
+        pins: u16,
+        high: bool,
+    ) -> Result<Verify, I::Error> {
+        let mut next = *self;
+        let (port, bits) = port_of(pins);
+        let index = port as usize;
+        let output = with_bits(self.output[index], bits, high);
+        next.output[index] = output;
+        let verify = next.commit(i2c, registers::OUTPUT_PORT_0 + port, output)?;
+        *self = next;
+        Ok(verify)
+    }
+
+    /// 将引脚配置为输出并设置电平
+    ///
+    /// 先设置输出电平，再切换方向，避免切换瞬间输出错误电平；方向不变时不写配置寄存器
+    ///
+    /// # 参数
+    /// * `i2c` - I2C 总线
+    /// * `pins` - [io_bits] 中的引脚，可以是同一端口的多个引脚
+    /// * `high` - true 表示高电平
+    pub fn set_as_output<I: I2c>(
+        &mut self,
+        i2c: &mut I,
+        pins: u16,
+        high: bool,
+    ) -> Result<Verify, I::Error> {
+        let mut next = *self;
+        let (port, bits) = port_of(pins);
+        let index = port as usize;
+        let output = with_bits(self.output[index], bits, high);
+        let config = with_bits(self.config[index], bits, false);
+        next.output[index] = output;
+        next.config[index] = config;
+        let mut verify = next.commit(i2c, registers::OUTPUT_PORT_0 + port, output)?;
+        // 重写全部寄存器时配置已一并写入
+        if verify == Verify::Ok && config != self.config[index] {
+            verify = next.commit(i2c, registers::CONFIG_PORT_0 + port, config)?;
+        }
+        *self = next;
+        Ok(verify)
+    }
+
+    /// 读取芯片上的输出和配置寄存器与期望值比较，不一致时重写全部寄存器
+    ///
+    /// # 参数
+    /// * `i2c` - I2C 总线
+    pub fn verify<I: I2c>(&self, i2c: &mut I) -> Result<Verify, I::Error> {
+        if Shadow::read(i2c)? == *self {
+            return Ok(Verify::Ok);
+        }
+        // 先写电平，再写方向
+        let writes = [
+            (registers::OUTPUT_PORT_0, self.output[0]),
+            (registers::OUTPUT_PORT_1, self.output[1]),
+            (registers::CONFIG_PORT_0, self.config[0]),
+            (registers::CONFIG_PORT_1, self.config[1]),
+        ];
+        for (register, value) in writes {
+            i2c.write(ADDR, &[register, value])?;
+        }
+        if Shadow::read(i2c)? == *self {
+            Ok(Verify::Restored)
+        } else {
+            Ok(Verify::Failed)
+        }
+    }
+
+    /// 写入一个寄存器并读回，不一致时按 [Shadow::verify] 处理
+    ///
+    /// `value` 应为期望值中该寄存器的值
+    fn commit<I: I2c>(&self, i2c: &mut I, register: u8, value: u8) -> Result<Verify, I::Error> {
+        i2c.write(ADDR, &[register, value])?;
+        if read_register(i2c, register)? == value {
+            Ok(Verify::Ok)
+        } else {
+            self.verify(i2c)
+        }
+    }
+
+    /// 读取芯片上的输出和配置寄存器
+    fn read<I: I2c>(i2c: &mut I) -> Result<Shadow, I::Error> {
+        Ok(Shadow {
+            output: [
+                read_register(i2c, registers::OUTPUT_PORT_0)?,
+                read_register(i2c, registers::OUTPUT_PORT_1)?,
+            ],
+            config: [
+                read_register(i2c, registers::CONFIG_PORT_0)?,
+                read_register(i2c, registers::CONFIG_PORT_1)?,
+            ],
+        })
+    }
+}
+
+/// 读取一个寄存器
+fn read_register<I: I2c>(i2c: &mut I, register: u8) -> Result<u8, I::Error> {
+    let mut value = [0u8];
+    i2c.write_read(ADDR, &[register], &mut value)?;
+    Ok(value[0])
+}
+
+/// 置位或清除若干位
+fn with_bits(value: u8, bits: u8, set: bool) -> u8 {
+    if set { value | bits } else { value & !bits }
+}
+
+/// 读取两个端口的输入状态
+///
+/// # 返回
+/// 16 位引脚电平，高 8 位来自 P1 端口，低 8 位来自 P0 端口
+pub fn read_inputs<I: I2c>(i2c: &mut I) -> Result<u16, I::Error> {
+    let mut port0 = [0u8];
+    let mut port1 = [0u8];
+    i2c.write_read(ADDR, &[registers::INPUT_PORT_0], &mut port0)?;
+    i2c.write_read(ADDR, &[registers::INPUT_PORT_1], &mut port1)?;
+    Ok(u16::from_le_bytes([port0[0], port1[0]]))
+}
+
+/// 从输入状态中解码 KEY0-KEY3
+///
+/// 按键按下时引脚为低电平
+///
+/// # 参数
+/// * `inputs` - [read_inputs] 读到的引脚电平
+///
+/// # 返回
+/// 各按键是否按下，下标即按键编号
+pub fn pressed_keys(inputs: u16) -> [bool; 4] {
+    KEY_BITS.map(|bit| inputs & bit == 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use embedded_hal::i2c::ErrorKind;
+    use embedded_hal_mock::eh1::i2c::{Mock, Transaction};
+
+    #[test]
+    fn init_configures_directions_then_clears_outputs() {
+        let mut i2c = Mock::new(&[
+            Transaction::write(ADDR, vec![registers::CONFIG_PORT_0, 0xFF]),
+            Transaction::write(ADDR, vec![registers::CONFIG_PORT_1, 0xF0]),
+            Transaction::write(ADDR, vec![registers::OUTPUT_PORT_0, 0x00]),
+            Transaction::write(ADDR, vec![registers::OUTPUT_PORT_1, 0x00]),
+        ]);
+        init(&mut i2c).unwrap();
+        i2c.done();
+    }
+
+    #[test]
+    fn init_stops_at_first_error() {
+        let mut i2c = Mock::new(&[
+            Transaction::write(ADDR, vec![registers::CONFIG_PORT_0, 0xFF])
+                .with_error(ErrorKind::Other),
+        ]);
+        assert_eq!(init(&mut i2c), Err(ErrorKind::Other));
+        i2c.done();
+    }
+
+    #[test]
+    fn write_output_sets_and_clears_port1_bits() {
+        let mut i2c = Mock::new(&[
+            Transaction::write_read(ADDR, vec![registers::OUTPUT_PORT_1], vec![0x04]),
+            Transaction::write(ADDR, vec![registers::OUTPUT_PORT_1, 0x0C]),
+            Transaction::write_read(ADDR, vec![registers::OUTPUT_PORT_1], vec![0x0C]),
+            Transaction::write(ADDR, vec![registers::OUTPUT_PORT_1, 0x08]),
+        ]);
+        write_output(&mut i2c, io_bits::SLCD_PWR_IO, true).unwrap();
+        write_output(&mut i2c, io_bits::SLCD_RST_IO, false).unwrap();
+        i2c.done();
+    }
+
+    #[test]
+    fn write_output_skips_write_when_read_fails() {
+        let mut i2c =
+            Mock::new(&[
+                Transaction::write_read(ADDR, vec![registers::OUTPUT_PORT_1], vec![0x00])
+                    .with_error(ErrorKind::Other),
+            ]);
+        assert!(write_output(&mut i2c, io_bits::SLCD_PWR_IO, true).is_err());
+        i2c.done();
+    }
+
+    #[test]
+    fn set_as_output_writes_level_before_direction() {
+        let mut i2c = Mock::new(&[
+            Transaction::write_read(ADDR, vec![registers::OUTPUT_PORT_0], vec![0x00]),
+            Transaction::write(ADDR, vec![registers::OUTPUT_PORT_0, 0x08]),
+            Transaction::write_read(ADDR, vec![registers::CONFIG_PORT_0], vec![0xFF]),
+            Transaction::write(ADDR, vec![registers::CONFIG_PORT_0, 0xF7]),
+        ]);
+        set_as_output(&mut i2c, io_bits::BEEP_IO, true).unwrap();
+        i2c.done();
+    }
+
+    #[test]
+    fn set_as_output_keeps_input_when_level_write_fails() {
+        let mut i2c = Mock::new(&[
+            Transaction::write_read(ADDR, vec![registers::OUTPUT_PORT_0], vec![0xFF]),
+            Transaction::write(ADDR, vec![registers::OUTPUT_PORT_0, 0xEF])
+                .with_error(ErrorKind::Other),
+        ]);
+        assert!(set_as_output(&mut i2c, io_bits::OV_PWDN_IO, false).is_err());
+        i2c.done();
+    }
+
+    /// 读回四个寄存器
+    fn read_back(values: [u8; 4]) -> Vec<Transaction> {
+        let registers = [
+            registers::OUTPUT_PORT_0,
+            registers::OUTPUT_PORT_1,
+            registers::CONFIG_PORT_0,
+            registers::CONFIG_PORT_1,
+        ];
+        registers
+            .into_iter()
+            .zip(values)
+            .map(|(register, value)| Transaction::write_read(ADDR, vec![register], vec![value]))
+            .collect()
+    }
+
+    #[test]
+    fn shadow_writes_expected_value_and_reads_it_back() {
+        let mut i2c = Mock::new(&[
+            Transaction::write(ADDR, vec![registers::OUTPUT_PORT_1, 0x08]),
+            Transaction::write_read(ADDR, vec![registers::OUTPUT_PORT_1], vec![0x08]),
+        ]);
+        let mut shadow = Shadow::INIT;
+        let verify = shadow.write_output(&mut i2c, io_bits::SLCD_PWR_IO, true);
+        assert_eq!(verify, Ok(Verify::Ok));
+        i2c.done();
+    }
+
+    #[test]
+    fn shadow_writes_direction_only_when_it_changes() {
+        let mut i2c = Mock::new(&[
+            Transaction::write(ADDR, vec![registers::OUTPUT_PORT_0, 0x08]),
+            Transaction::write_read(ADDR, vec![registers::OUTPUT_PORT_0], vec![0x08]),
+            Transaction::write(ADDR, vec![registers::CONFIG_PORT_0, 0xF7]),
+            Transaction::write_read(ADDR, vec![registers::CONFIG_PORT_0], vec![0xF7]),
+            Transaction::write(ADDR, vec![registers::OUTPUT_PORT_0, 0x00]),
+            Transaction::write_read(ADDR, vec![registers::OUTPUT_PORT_0], vec![0x00]),
+        ]);
+        let mut shadow = Shadow::INIT;
+        let first = shadow.set_as_output(&mut i2c, io_bits::BEEP_IO, true);
+        let second = shadow.set_as_output(&mut i2c, io_bits::BEEP_IO, false);
+        assert_eq!((first, second), (Ok(Verify::Ok), Ok(Verify::Ok)));
+        i2c.done();
+    }
+
+    #[test]
+    fn shadow_restores_all_registers_after_chip_reset() {
+        // 背光已打开，芯片复位后寄存器回到上电默认值
+        let mut shadow = Shadow::INIT;
+        let mut i2c = Mock::new(&[
+            Transaction::write(ADDR, vec![registers::OUTPUT_PORT_1, 0x08]),
+            Transaction::write_read(ADDR, vec![registers::OUTPUT_PORT_1], vec![0x08]),
+        ]);
+        shadow
+            .write_output(&mut i2c, io_bits::SLCD_PWR_IO, true)
+            .unwrap();
+        i2c.done();
+
+        let mut transactions = vec![
+            Transaction::write(ADDR, vec![registers::OUTPUT_PORT_0, 0x08]),
+            Transaction::write_read(ADDR, vec![registers::OUTPUT_PORT_0], vec![0xFF]),
+        ];
+        transactions.extend(read_back([0xFF, 0xFF, 0xFF, 0xFF]));
+        transactions.extend([
+            Transaction::write(ADDR, vec![registers::OUTPUT_PORT_0, 0x08]),
+            Transaction::write(ADDR, vec![registers::OUTPUT_PORT_1, 0x08]),
+            Transaction::write(ADDR, vec![registers::CONFIG_PORT_0, 0xF7]),
+            Transaction::write(ADDR, vec![registers::CONFIG_PORT_1, 0xF0]),
+        ]);
+        transactions.extend(read_back([0x08, 0x08, 0xF7, 0xF0]));
+        let mut i2c = Mock::new(&transactions);
+        let verify = shadow.set_as_output(&mut i2c, io_bits::BEEP_IO, true);
+        assert_eq!(verify, Ok(Verify::Restored));
+        i2c.done();
+    }
+
+    #[test]
+    fn shadow_verify_only_reads_when_registers_match() {
+        let mut i2c = Mock::new(&read_back([0x00, 0x00, 0xFF, 0xF0]));
+        assert_eq!(Shadow::INIT.verify(&mut i2c), Ok(Verify::Ok));
+        i2c.done();
+    }
+
+    #[test]
+    fn shadow_reports_registers_that_do_not_stick() {
+        let mut transactions = read_back([0x00, 0x00, 0xFF, 0xFF]);
+        transactions.extend([
+            Transaction::write(ADDR, vec![registers::OUTPUT_PORT_0, 0x00]),
+            Transaction::write(ADDR, vec![registers::OUTPUT_PORT_1, 0x00]),
+            Transaction::write(ADDR, vec![registers::CONFIG_PORT_0, 0xFF]),
+            Transaction::write(ADDR, vec![registers::CONFIG_PORT_1, 0xF0]),
+        ]);
+        transactions.extend(read_back([0x00, 0x00, 0xFF, 0xFF]));
+        let mut i2c = Mock::new(&transactions);
+        assert_eq!(Shadow::INIT.verify(&mut i2c), Ok(Verify::Failed));
+        i2c.done();
+    }
+
+    #[test]
+    fn shadow_is_unchanged_when_write_fails() {
+        let mut i2c = Mock::new(&[
+            Transaction::write(ADDR, vec![registers::OUTPUT_PORT_1, 0x08])
+                .with_error(ErrorKind::Other),
+        ]);
+        let mut shadow = Shadow::INIT;
+        assert!(
+            shadow
+                .write_output(&mut i2c, io_bits::SLCD_PWR_IO, true)
+                .is_err()
+        );
+        assert_eq!(shadow, Shadow::INIT);
+        i2c.done();
+    }
+
+    #[test]
+    fn read_inputs_combines_both_ports() {
+        let mut i2c = Mock::new(&[
+            Transaction::write_read(ADDR, vec![registers::INPUT_PORT_0], vec![0x12]),
+            Transaction::write_read(ADDR, vec![registers::INPUT_PORT_1], vec![0xAB]),
+        ]);
+        assert_eq!(read_inputs(&mut i2c), Ok(0xAB12));
+        i2c.done();
+    }
+
+    #[test]
+    fn pressed_keys_are_active_low() {
+        assert_eq!(pressed_keys(0xFFFF), [false; 4]);
+        assert_eq!(pressed_keys(0x0000), [true; 4]);
+        assert_eq!(pressed_keys(!io_bits::KEY0_IO), [true, false, false, false]);
+        assert_eq!(pressed_keys(!io_bits::KEY3_IO), [false, false, false, true]);
+    }
+
+    #[test]
+    fn pressed_keys_ignore_other_pins() {
+        // P0 和 P1 低 4 位的电平不影响按键
+        assert_eq!(pressed_keys(0xF000), [false; 4]);
+        assert_eq!(pressed_keys(0xA000 | 0x0FFF), [false, true, false, true]);
+    }
+}
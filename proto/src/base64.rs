@@ -0,0 +1,58 @@
+//! 标准 Base64（RFC 4648）编解码
+//!
+//! 固件中 syslog 用它编码 defmt 日志帧，HTTP 基本认证和 MQTT 的 SAS 令牌用它解码和编码。
+
+use core::fmt::{self, Write};
+
+/// 字符表
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// 编码数据，末尾按需加 `=` 填充
+///
+/// # 参数
+/// * `data` - 原始数据
+/// * `out` - 输出，写满时返回错误，已写入的部分保留
+pub fn encode(data: &[u8], out: &mut impl Write) -> fmt::Result {
+    for chunk in data.chunks(3) {
+        let bits = chunk.iter().enumerate().fold(0u32, |bits, (i, &byte)| {
+            bits | (byte as u32) << (16 - 8 * i)
+        });
+        for i in 0..4 {
+            let c = if i <= chunk.len() {
+                ALPHABET[(bits >> (18 - 6 * i) & 0x3F) as usize]
+            } else {
+                b'='
+            };
+            out.write_char(c as char)?;
+        }
+    }
+    Ok(())
+}
+
+/// 解码（可带 `=` 填充）
+///
+/// # 返回
+/// 解码后的长度，含有非法字符或超出 `out` 时返回 None
+pub fn decode(text: &str, out: &mut [u8]) -> Option<usize> {
+    let mut bits = 0u32;
+    let mut count = 0;
+    let mut len = 0;
+    for c in text.trim_end_matches('=').bytes() {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => return None,
+        };
+        bits = bits << 6 | value as u32;
+        count += 6;
+        if count >= 8 {
+            count -= 8;
+            *out.get_mut(len)? = (bits >> count) as u8;
+            len += 1;
+        }
+    }
+    Some(len)
+}
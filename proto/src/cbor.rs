@@ -0,0 +1,117 @@
+//! CBOR 编码
+//!
+//! 遥测数据的紧凑编码（RFC 8949），在带宽有限的链路（例如按流量计费的手机热点）上代替 JSON。
+//! 数据结构只实现一种表示（[crate::json::ToJson]），需要 CBOR 时用 [from_json]
+//! 把编码好的 JSON 转换过来：
+//!
+//! - 对象和数组编码为定长的 map 和 array
+//! - 整数编码为最短的整数；其他数字能无损表示为单精度时用 4 字节浮点，否则用 8 字节
+//! - 字符串解码转义序列后编码为 UTF-8 文本
+//!
+//! 省掉的是引号、分隔符和数字的文本形式，键名和字符串照原样保留：告警通知的请求体
+//! 约减少 20%，以数字为主的数据减少得更多。
+//!
+//! 按端点选择编码：固件中告警 webhook 由设置决定，HTTP 接口在请求头带有
+//! `Accept: application/cbor` 时返回 CBOR。
+
+use crate::json::{self, JsonError, Value};
+use alloc::vec::Vec;
+
+/// HTTP Content-Type
+pub const CONTENT_TYPE: &str = "application/cbor";
+
+/// 主类型
+const UNSIGNED: u8 = 0;
+const NEGATIVE: u8 = 1;
+const TEXT: u8 = 3;
+const ARRAY: u8 = 4;
+const MAP: u8 = 5;
+const SIMPLE: u8 = 7;
+
+/// 主类型 7 的附加信息：简单值和浮点数
+const FALSE: u8 = 20;
+const TRUE: u8 = 21;
+const NULL: u8 = 22;
+const FLOAT32: u8 = 26;
+const FLOAT64: u8 = 27;
+
+/// 把 JSON 文档转换为 CBOR
+///
+/// # 参数
+/// * `text` - JSON 文档
+///
+/// # 返回
+/// CBOR 编码，文档不是合法的 JSON 时返回错误
+pub fn from_json(text: &str) -> Result<Vec<u8>, JsonError> {
+    let value = json::parse(text)?;
+    let mut out = Vec::with_capacity(text.len());
+    write_value(&mut out, &value)?;
+    Ok(out)
+}
+
+/// 写入一个值，嵌套深度已由 [json::parse] 限制
+fn write_value(out: &mut Vec<u8>, value: &Value<'_>) -> Result<(), JsonError> {
+    match *value {
+        Value::Null => out.push(SIMPLE << 5 | NULL),
+        Value::Bool(value) => out.push(SIMPLE << 5 | if value { TRUE } else { FALSE }),
+        Value::Number(number) => write_number(out, number),
+        Value::String(raw) => {
+            let text = json::unescape(raw)?;
+            write_head(out, TEXT, text.len() as u64);
+            out.extend_from_slice(text.as_bytes());
+        }
+        Value::Array(_) => {
+            write_head(out, ARRAY, value.items().count() as u64);
+            for item in value.items() {
+                write_value(out, &item)?;
+            }
+        }
+        Value::Object(_) => {
+            write_head(out, MAP, value.members().count() as u64);
+            for (key, member) in value.members() {
+                write_value(out, &Value::String(key))?;
+                write_value(out, &member)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// 写入数字，整数优先，其次是无损的单精度浮点
+fn write_number(out: &mut Vec<u8>, number: f64) {
+    // 超出 i64 范围时转换饱和，比较不相等，按浮点编码
+    let integer = number as i64;
+    if integer as f64 == number {
+        match u64::try_from(integer) {
+            Ok(value) => write_head(out, UNSIGNED, value),
+            Err(_) => write_head(out, NEGATIVE, !integer as u64),
+        }
+    } else if number as f32 as f64 == number {
+        out.push(SIMPLE << 5 | FLOAT32);
+        out.extend_from_slice(&(number as f32).to_be_bytes());
+    } else {
+        out.push(SIMPLE << 5 | FLOAT64);
+        out.extend_from_slice(&number.to_be_bytes());
+    }
+}
+
+/// 写入数据项的头部：主类型和长度（整数时为数值），使用最短的形式
+fn write_head(out: &mut Vec<u8>, major: u8, value: u64) {
+    let major = major << 5;
+    match value {
+        0..24 => out.push(major | value as u8),
+        24..=0xFF => out.extend_from_slice(&[major | 24, value as u8]),
+        0x100..=0xFFFF => {
+            out.push(major | 25);
+            out.extend_from_slice(&(value as u16).to_be_bytes());
+        }
+        0x1_0000..=0xFFFF_FFFF => {
+            out.push(major | 26);
+            out.extend_from_slice(&(value as u32).to_be_bytes());
+        }
+        _ => {
+            out.push(major | 27);
+            out.extend_from_slice(&value.to_be_bytes());
+        }
+    }
+}
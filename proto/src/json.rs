@@ -0,0 +1,511 @@
+//! 最小的 JSON 解析器和写入器
+//!
+//! 解析不分配内存，也不构建文档树：[parse] 校验整个文档并返回顶层的 [Value]，
+//! 对象和数组以原始文本切片保存，通过 [Value::get]、[Value::items] 按需向下查找。
+//! 适合从较大的响应中取出少量字段（例如固件中的天气预报）。
+//!
+//! 写入用 [Object] 和 [Array] 按顺序追加成员，负责逗号、转义和括号。设备的数据结构
+//! 实现 [ToJson]（例如设置、传感器读数和系统状态报告），HTTP 接口和告警通知共用同一种表示，
+//! 不在各模块中拼接字符串。
+//!
+//! 限制：
+//!
+//! - 字符串保留原始转义（`\"`、`\u4e2d` 等不解码），只适合比较 ASCII 键和取值，
+//!   需要原文时用 [unescape] 解码
+//! - 嵌套深度不超过 [MAX_DEPTH]
+
+use alloc::string::String;
+use core::fmt::Write;
+
+/// 最大嵌套深度，避免恶意输入耗尽栈空间
+const MAX_DEPTH: usize = 16;
+
+/// 解析错误
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum JsonError {
+    /// 语法错误，附带出错位置（字节偏移）
+    Syntax(usize),
+    /// 嵌套过深
+    TooDeep,
+    /// 字符串中有无效的转义序列
+    Escape,
+}
+
+/// JSON 值
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Value<'a> {
+    Null,
+    Bool(bool),
+    Number(f64),
+    /// 不含引号的原始字符串，转义序列未解码
+    String(&'a str),
+    /// 包含方括号的数组原文
+    Array(&'a str),
+    /// 包含花括号的对象原文
+    Object(&'a str),
+}
+
+impl<'a> Value<'a> {
+    /// 查找对象成员，不是对象或键不存在时返回 None
+    pub fn get(&self, key: &str) -> Option<Value<'a>> {
+        self.members()
+            .find(|(name, _)| *name == key)
+            .map(|(_, value)| value)
+    }
+
+    /// 遍历对象成员，键为原始字符串，不是对象时为空
+    pub fn members(&self) -> Members<'a> {
+        let mut parser = Parser::new(match *self {
+            Value::Object(text) => text,
+            _ => "",
+        });
+        let done = parser.expect(b'{').is_err() || parser.peek_after_ws() == Some(b'}');
+        Members { parser, done }
+    }
+
+    /// 按路径逐层查找对象成员
+    pub fn path(&self, keys: &[&str]) -> Option<Value<'a>> {
+        keys.iter().try_fold(*self, |value, key| value.get(key))
+    }
+
+    /// 遍历数组元素，不是数组时为空
+    pub fn items(&self) -> Items<'a> {
+        let mut parser = Parser::new(match *self {
+            Value::Array(text) => text,
+            _ => "",
+        });
+        let done = parser.expect(b'[').is_err() || parser.peek_after_ws() == Some(b']');
+        Items { parser, done }
+    }
+
+    /// 数组的第 `index` 个元素
+    pub fn index(&self, index: usize) -> Option<Value<'a>> {
+        self.items().nth(index)
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match *self {
+            Value::Number(n) => Some(n),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&'a str> {
+        match *self {
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+}
+
+/// 数组元素迭代器，遇到错误时结束
+pub struct Items<'a> {
+    parser: Parser<'a>,
+    done: bool,
+}
+
+impl<'a> Iterator for Items<'a> {
+    type Item = Value<'a>;
+
+    fn next(&mut self) -> Option<Value<'a>> {
+        if self.done {
+            return None;
+        }
+        let Ok(value) = self.parser.value(1) else {
+            self.done = true;
+            return None;
+        };
+        self.parser.skip_ws();
+        self.done = self.parser.bump() != Some(b',');
+        Some(value)
+    }
+}
+
+/// 对象成员迭代器，遇到错误时结束
+pub struct Members<'a> {
+    parser: Parser<'a>,
+    done: bool,
+}
+
+impl<'a> Iterator for Members<'a> {
+    type Item = (&'a str, Value<'a>);
+
+    fn next(&mut self) -> Option<(&'a str, Value<'a>)> {
+        if self.done {
+            return None;
+        }
+        self.parser.skip_ws();
+        let member = self.parser.string().and_then(|name| {
+            self.parser.expect(b':')?;
+            Ok((name, self.parser.value(1)?))
+        });
+        let Ok(member) = member else {
+            self.done = true;
+            return None;
+        };
+        self.parser.skip_ws();
+        self.done = self.parser.bump() != Some(b',');
+        Some(member)
+    }
+}
+
+/// 解码字符串中的转义序列
+///
+/// # 参数
+/// * `raw` - [Value::String] 中的原文
+///
+/// # 返回
+/// 解码后的字符串，转义序列无效时返回错误
+pub fn unescape(raw: &str) -> Result<String, JsonError> {
+    let mut text = String::with_capacity(raw.len());
+    let mut chars = raw.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            text.push(c);
+            continue;
+        }
+        let decoded = match chars.next() {
+            Some('"') => '"',
+            Some('\\') => '\\',
+            Some('/') => '/',
+            Some('b') => '\u{8}',
+            Some('f') => '\u{c}',
+            Some('n') => '\n',
+            Some('r') => '\r',
+            Some('t') => '\t',
+            Some('u') => unicode_escape(&mut chars).ok_or(JsonError::Escape)?,
+            _ => return Err(JsonError::Escape),
+        };
+        text.push(decoded);
+    }
+    Ok(text)
+}
+
+/// 解码 `\u` 之后的 4 位十六进制数，UTF-16 代理对需要紧跟第二个 `\u`
+fn unicode_escape(chars: &mut core::str::Chars<'_>) -> Option<char> {
+    let high = hex4(chars)?;
+    if !(0xD800..0xDC00).contains(&high) {
+        return char::from_u32(high);
+    }
+    let low = match (chars.next(), chars.next()) {
+        (Some('\\'), Some('u')) => hex4(chars)?,
+        _ => return None,
+    };
+    if !(0xDC00..0xE000).contains(&low) {
+        return None;
+    }
+    char::from_u32(0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00))
+}
+
+fn hex4(chars: &mut core::str::Chars<'_>) -> Option<u32> {
+    (0..4).try_fold(0, |code, _| Some(code << 4 | chars.next()?.to_digit(16)?))
+}
+
+/// 有统一 JSON 表示的数据结构
+pub trait ToJson {
+    /// 把成员写入对象
+    fn write_members(&self, object: &mut Object<'_>);
+
+    /// 编码为 JSON 对象
+    fn to_json(&self) -> String {
+        let mut text = String::new();
+        self.write_members(&mut Object::new(&mut text));
+        text
+    }
+}
+
+/// JSON 对象的写入器
+///
+/// 成员按调用顺序追加，离开作用域时写出右花括号
+pub struct Object<'a> {
+    out: &'a mut String,
+    empty: bool,
+}
+
+impl<'a> Object<'a> {
+    /// 在 `out` 末尾开始一个对象
+    pub fn new(out: &'a mut String) -> Self {
+        out.push('{');
+        Object { out, empty: true }
+    }
+
+    fn key(&mut self, key: &str) {
+        if !self.empty {
+            self.out.push(',');
+        }
+        self.empty = false;
+        push_string(self.out, key);
+        self.out.push(':');
+    }
+
+    pub fn str(&mut self, key: &str, value: &str) -> &mut Self {
+        self.key(key);
+        push_string(self.out, value);
+        self
+    }
+
+    /// 写入数字，NaN 和无穷大写为 `null`
+    pub fn number(&mut self, key: &str, value: f64) -> &mut Self {
+        self.key(key);
+        push_number(self.out, value);
+        self
+    }
+
+    pub fn int(&mut self, key: &str, value: i64) -> &mut Self {
+        self.key(key);
+        write!(self.out, "{}", value).ok();
+        self
+    }
+
+    pub fn bool(&mut self, key: &str, value: bool) -> &mut Self {
+        self.key(key);
+        self.out.push_str(if value { "true" } else { "false" });
+        self
+    }
+
+    /// 开始一个对象成员
+    pub fn object(&mut self, key: &str) -> Object<'_> {
+        self.key(key);
+        Object::new(self.out)
+    }
+
+    /// 开始一个数组成员
+    pub fn array(&mut self, key: &str) -> Array<'_> {
+        self.key(key);
+        Array::new(self.out)
+    }
+}
+
+impl Drop for Object<'_> {
+    fn drop(&mut self) {
+        self.out.push('}');
+    }
+}
+
+/// JSON 数组的写入器
+///
+/// 元素按调用顺序追加，离开作用域时写出右方括号
+pub struct Array<'a> {
+    out: &'a mut String,
+    empty: bool,
+}
+
+impl<'a> Array<'a> {
+    /// 在 `out` 末尾开始一个数组
+    pub fn new(out: &'a mut String) -> Self {
+        out.push('[');
+        Array { out, empty: true }
+    }
+
+    fn next(&mut self) {
+        if !self.empty {
+            self.out.push(',');
+        }
+        self.empty = false;
+    }
+
+    pub fn int(&mut self, value: i64) -> &mut Self {
+        self.next();
+        write!(self.out, "{}", value).ok();
+        self
+    }
+
+    pub fn str(&mut self, value: &str) -> &mut Self {
+        self.next();
+        push_string(self.out, value);
+        self
+    }
+
+    /// 开始一个对象元素
+    pub fn object(&mut self) -> Object<'_> {
+        self.next();
+        Object::new(self.out)
+    }
+}
+
+impl Drop for Array<'_> {
+    fn drop(&mut self) {
+        self.out.push(']');
+    }
+}
+
+/// 写入带引号的字符串，按 JSON 规则转义
+fn push_string(out: &mut String, text: &str) {
+    out.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if (c as u32) < 0x20 => {
+                write!(out, "\\u{:04x}", c as u32).ok();
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+fn push_number(out: &mut String, value: f64) {
+    if value.is_finite() {
+        write!(out, "{}", value).ok();
+    } else {
+        out.push_str("null");
+    }
+}
+
+/// 解析并校验一个完整的 JSON 文档
+///
+/// # 参数
+/// * `text` - 文档文本，前后允许空白
+///
+/// # 返回
+/// 顶层值
+pub fn parse(text: &str) -> Result<Value<'_>, JsonError> {
+    let mut parser = Parser::new(text);
+    let value = parser.value(0)?;
+    parser.skip_ws();
+    if parser.pos != text.len() {
+        return Err(JsonError::Syntax(parser.pos));
+    }
+    Ok(value)
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(text: &'a str) -> Self {
+        Parser { text, pos: 0 }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.text.as_bytes().get(self.pos).copied()
+    }
+
+    fn peek_after_ws(&mut self) -> Option<u8> {
+        self.skip_ws();
+        self.peek()
+    }
+
+    fn bump(&mut self) -> Option<u8> {
+        let byte = self.peek()?;
+        self.pos += 1;
+        Some(byte)
+    }
+
+    fn skip_ws(&mut self) {
+        while matches!(self.peek(), Some(b' ' | b'\t' | b'\r' | b'\n')) {
+            self.pos += 1;
+        }
+    }
+
+    fn error(&self) -> JsonError {
+        JsonError::Syntax(self.pos)
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<(), JsonError> {
+        self.skip_ws();
+        match self.bump() {
+            Some(b) if b == byte => Ok(()),
+            _ => Err(self.error()),
+        }
+    }
+
+    /// 解析一个值，`depth` 为当前嵌套深度
+    fn value(&mut self, depth: usize) -> Result<Value<'a>, JsonError> {
+        if depth > MAX_DEPTH {
+            return Err(JsonError::TooDeep);
+        }
+        self.skip_ws();
+        let start = self.pos;
+        match self.peek().ok_or(self.error())? {
+            b'{' => {
+                self.container(b'{', b'}', depth, true)?;
+                Ok(Value::Object(&self.text[start..self.pos]))
+            }
+            b'[' => {
+                self.container(b'[', b']', depth, false)?;
+                Ok(Value::Array(&self.text[start..self.pos]))
+            }
+            b'"' => self.string().map(Value::String),
+            b't' => self.literal("true", Value::Bool(true)),
+            b'f' => self.literal("false", Value::Bool(false)),
+            b'n' => self.literal("null", Value::Null),
+            b'-' | b'0'..=b'9' => self.number(),
+            _ => Err(self.error()),
+        }
+    }
+
+    /// 跳过一个对象或数组，同时校验其中的成员
+    fn container(
+        &mut self,
+        open: u8,
+        close: u8,
+        depth: usize,
+        keyed: bool,
+    ) -> Result<(), JsonError> {
+        self.expect(open)?;
+        if self.peek_after_ws() == Some(close) {
+            self.pos += 1;
+            return Ok(());
+        }
+        loop {
+            if keyed {
+                self.skip_ws();
+                self.string()?;
+                self.expect(b':')?;
+            }
+            self.value(depth + 1)?;
+            self.skip_ws();
+            match self.bump() {
+                Some(b',') => continue,
+                Some(b) if b == close => return Ok(()),
+                _ => return Err(self.error()),
+            }
+        }
+    }
+
+    /// 解析字符串，返回引号之间的原文
+    fn string(&mut self) -> Result<&'a str, JsonError> {
+        if self.bump() != Some(b'"') {
+            return Err(self.error());
+        }
+        let start = self.pos;
+        loop {
+            match self.bump().ok_or(self.error())? {
+                b'"' => return Ok(&self.text[start..self.pos - 1]),
+                b'\\' => {
+                    self.bump().ok_or(self.error())?;
+                }
+                0x00..=0x1F => return Err(self.error()),
+                _ => {}
+            }
+        }
+    }
+
+    fn literal(&mut self, word: &str, value: Value<'a>) -> Result<Value<'a>, JsonError> {
+        if self.text[self.pos..].starts_with(word) {
+            self.pos += word.len();
+            Ok(value)
+        } else {
+            Err(self.error())
+        }
+    }
+
+    fn number(&mut self) -> Result<Value<'a>, JsonError> {
+        let start = self.pos;
+        while matches!(
+            self.peek(),
+            Some(b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9')
+        ) {
+            self.pos += 1;
+        }
+        self.text[start..self.pos]
+            .parse()
+            .map(Value::Number)
+            .map_err(|_| JsonError::Syntax(start))
+    }
+}
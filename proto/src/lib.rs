@@ -0,0 +1,23 @@
+//! 通信协议和数据格式
+//!
+//! 固件中各协议模块的报文解析和编码，与网络协议栈、UART 和 esp-hal 无关：
+//! 固件模块负责收发，把收到的字节交给这里解析，再把这里编码好的报文发出去。
+//! 这部分代码处理的都是外部输入，可以在主机上测试：
+//!
+//! ```text
+//! cargo +stable test -p proto --target x86_64-unknown-linux-gnu
+//! ```
+
+#![cfg_attr(not(test), no_std)]
+
+extern crate alloc;
+
+pub mod base64;
+pub mod cbor;
+pub mod json;
+pub mod lin;
+pub mod modbus;
+pub mod mqtt;
+pub mod nmea;
+pub mod rc;
+pub mod snmp;
@@ -0,0 +1,105 @@
+//! LIN 2.x 帧头和校验和
+//!
+//! 帧头由 break、同步字节 [SYNC] 和受保护 ID（[protected_id]）组成，
+//! 数据之后的校验和分经典和增强两种，见 [Checksum]。
+
+/// 同步字节
+pub const SYNC: u8 = 0x55;
+
+/// 主节点请求诊断帧 ID，诊断帧总是使用经典校验和
+pub const MASTER_REQUEST_ID: u8 = 0x3C;
+
+/// 从节点应答诊断帧 ID
+pub const SLAVE_RESPONSE_ID: u8 = 0x3D;
+
+/// 校验和类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Checksum {
+    /// 只对数据求和（LIN 1.x）
+    Classic,
+    /// 对 PID 和数据求和（LIN 2.x）
+    Enhanced,
+}
+
+impl Checksum {
+    /// 计算一帧数据的校验和
+    ///
+    /// 经典校验和的初值为 0，增强校验和的初值为 PID；诊断帧总是使用经典校验和
+    ///
+    /// # 参数
+    /// * `id` - 帧 ID（0 到 0x3F）
+    /// * `data` - 数据
+    pub fn compute(self, id: u8, data: &[u8]) -> u8 {
+        let diagnostic = id == MASTER_REQUEST_ID || id == SLAVE_RESPONSE_ID;
+        let seed = match self {
+            Checksum::Enhanced if !diagnostic => protected_id(id),
+            _ => 0,
+        };
+        checksum(seed, data)
+    }
+}
+
+/// 计算受保护 ID：P0 = ID0^ID1^ID2^ID4，P1 = !(ID1^ID3^ID4^ID5)
+pub fn protected_id(id: u8) -> u8 {
+    let bit = |n: u8| (id >> n) & 1;
+    let p0 = bit(0) ^ bit(1) ^ bit(2) ^ bit(4);
+    let p1 = !(bit(1) ^ bit(3) ^ bit(4) ^ bit(5)) & 1;
+    (id & 0x3F) | (p0 << 6) | (p1 << 7)
+}
+
+/// 带进位回卷的求和再取反
+fn checksum(seed: u8, data: &[u8]) -> u8 {
+    let sum = data.iter().fold(seed as u16, |sum, &byte| {
+        let sum = sum + byte as u16;
+        if sum > 0xFF { sum - 0xFF } else { sum }
+    });
+    !(sum as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn protected_ids_match_specification_table() {
+        assert_eq!(protected_id(0x00), 0x80);
+        assert_eq!(protected_id(0x01), 0xC1);
+        assert_eq!(protected_id(0x10), 0x50);
+        assert_eq!(protected_id(0x3C), 0x3C);
+        assert_eq!(protected_id(0x3D), 0x7D);
+        assert_eq!(protected_id(0x3F), 0xBF);
+    }
+
+    #[test]
+    fn classic_checksum_wraps_carry() {
+        // 0x4A + 0x55 + 0x93 + 0xE5 两次进位回卷后为 0x19
+        assert_eq!(checksum(0, &[0x4A, 0x55, 0x93, 0xE5]), 0xE6);
+        assert_eq!(Checksum::Classic.compute(0x10, &[0xFF, 0x01]), 0xFE);
+    }
+
+    #[test]
+    fn enhanced_checksum_includes_pid() {
+        let data = [0x01, 0x02];
+        let pid = protected_id(0x10);
+        assert_eq!(
+            Checksum::Enhanced.compute(0x10, &data),
+            checksum(pid, &data)
+        );
+        assert_ne!(
+            Checksum::Enhanced.compute(0x10, &data),
+            Checksum::Classic.compute(0x10, &data)
+        );
+    }
+
+    #[test]
+    fn diagnostic_frames_use_classic_checksum() {
+        let data = [0x7F, 0x06, 0xB2, 0x00, 0xFF, 0x7F, 0xFF, 0xFF];
+        for id in [MASTER_REQUEST_ID, SLAVE_RESPONSE_ID] {
+            assert_eq!(
+                Checksum::Enhanced.compute(id, &data),
+                Checksum::Classic.compute(id, &data)
+            );
+        }
+    }
+}
@@ -0,0 +1,291 @@
+//! Modbus 报文
+//!
+//! Modbus TCP（MBAP 报文头 + PDU）和 Modbus RTU（从站地址 + PDU + CRC）的帧格式，
+//! 以及从站处理请求 PDU 时共用的检查和编码。地址表和对请求的执行由固件负责。
+
+/// MBAP 报文头长度
+pub const MBAP_LEN: usize = 7;
+
+/// PDU 最大长度
+pub const MAX_PDU_LEN: usize = 253;
+
+/// RTU 帧最大长度（从站地址 + PDU + CRC）
+pub const RTU_FRAME_LEN: usize = 1 + MAX_PDU_LEN + 2;
+
+/// RTU 广播地址
+pub const BROADCAST: u8 = 0;
+
+/// 单次请求最多读取的线圈/离散输入数量（协议上限）
+pub const MAX_READ_BITS: u16 = 2000;
+
+/// 单次请求最多读取的寄存器数量（协议上限）
+pub const MAX_READ_REGISTERS: u16 = 125;
+
+/// 功能码
+pub mod function {
+    pub const READ_COILS: u8 = 0x01;
+    pub const READ_DISCRETE_INPUTS: u8 = 0x02;
+    pub const READ_HOLDING_REGISTERS: u8 = 0x03;
+    pub const READ_INPUT_REGISTERS: u8 = 0x04;
+    pub const WRITE_SINGLE_COIL: u8 = 0x05;
+    pub const WRITE_SINGLE_REGISTER: u8 = 0x06;
+    pub const WRITE_MULTIPLE_COILS: u8 = 0x0F;
+    pub const WRITE_MULTIPLE_REGISTERS: u8 = 0x10;
+}
+
+/// 异常码
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u8)]
+pub enum Exception {
+    IllegalFunction = 0x01,
+    IllegalDataAddress = 0x02,
+    IllegalDataValue = 0x03,
+    ServerDeviceBusy = 0x06,
+}
+
+/// 请求 PDU 的公共部分
+///
+/// 支持的功能码都以起始地址和数量（或写入的数值）开头
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Request {
+    pub code: u8,
+    pub address: u16,
+    pub value: u16,
+}
+
+impl Request {
+    /// 解析请求 PDU（功能码 + 数据），PDU 不能为空
+    ///
+    /// # 返回
+    /// PDU 过短时，支持的功能码返回 [Exception::IllegalDataValue]，
+    /// 其他返回 [Exception::IllegalFunction]
+    pub fn parse(pdu: &[u8]) -> Result<Request, Exception> {
+        let code = pdu[0];
+        if pdu.len() < 5 {
+            return Err(match code {
+                function::READ_COILS..=function::WRITE_SINGLE_REGISTER
+                | function::WRITE_MULTIPLE_COILS
+                | function::WRITE_MULTIPLE_REGISTERS => Exception::IllegalDataValue,
+                _ => Exception::IllegalFunction,
+            });
+        }
+        Ok(Request {
+            code,
+            address: u16::from_be_bytes([pdu[1], pdu[2]]),
+            value: u16::from_be_bytes([pdu[3], pdu[4]]),
+        })
+    }
+}
+
+/// 检查 MBAP 报文头
+///
+/// # 返回
+/// 其后的 PDU 长度；协议标识符不为 0 或长度字段无效时返回 None
+pub fn mbap_pdu_len(header: &[u8; MBAP_LEN]) -> Option<usize> {
+    let protocol = u16::from_be_bytes([header[2], header[3]]);
+    let length = u16::from_be_bytes([header[4], header[5]]) as usize;
+    // 长度字段包含单元标识符，PDU 至少有功能码
+    let pdu_len = length.checked_sub(1)?;
+    (protocol == 0 && (1..=MAX_PDU_LEN).contains(&pdu_len)).then_some(pdu_len)
+}
+
+/// 写入应答的 MBAP 报文头，事务标识符、协议标识符和单元标识符与请求相同
+///
+/// # 参数
+/// * `request` - 请求的报文头
+/// * `pdu_len` - 应答 PDU 长度
+/// * `out` - 应答报文头
+pub fn mbap_response(request: &[u8; MBAP_LEN], pdu_len: usize, out: &mut [u8]) {
+    out[..4].copy_from_slice(&request[..4]);
+    out[4..6].copy_from_slice(&(pdu_len as u16 + 1).to_be_bytes());
+    out[6] = request[6];
+}
+
+/// 检查 RTU 帧
+///
+/// # 返回
+/// 从站地址和 PDU；帧过短或 CRC 错误时返回 None
+pub fn rtu_pdu(frame: &[u8]) -> Option<(u8, &[u8])> {
+    // 至少有地址、功能码和 CRC
+    let len = frame.len();
+    if len < 4 || crc16(&frame[..len - 2]).to_le_bytes() != frame[len - 2..] {
+        return None;
+    }
+    Some((frame[0], &frame[1..len - 2]))
+}
+
+/// 在 `frame[1..]` 中的应答 PDU 前后加上从站地址和 CRC
+///
+/// # 返回
+/// 整帧长度
+pub fn seal_rtu(unit: u8, pdu_len: usize, frame: &mut [u8]) -> usize {
+    frame[0] = unit;
+    let end = 1 + pdu_len;
+    let crc = crc16(&frame[..end]);
+    frame[end..end + 2].copy_from_slice(&crc.to_le_bytes());
+    end + 2
+}
+
+/// 写入异常应答 PDU
+///
+/// # 返回
+/// 应答 PDU 长度
+pub fn exception_pdu(code: u8, exception: Exception, out: &mut [u8]) -> usize {
+    out[0] = code | 0x80;
+    out[1] = exception as u8;
+    2
+}
+
+/// Modbus RTU 的 CRC-16（多项式 0xA001，初值 0xFFFF），低字节在前发送
+pub fn crc16(data: &[u8]) -> u16 {
+    let mut crc = 0xFFFF_u16;
+    for &byte in data {
+        crc ^= byte as u16;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xA001
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    crc
+}
+
+/// 检查读取范围
+///
+/// # 参数
+/// * `address` - 起始地址
+/// * `count` - 数量
+/// * `size` - 该类数据的总数
+/// * `max` - 单次请求允许的最大数量
+pub fn check_range(address: u16, count: u16, size: u16, max: u16) -> Result<(), Exception> {
+    if count == 0 || count > max {
+        return Err(Exception::IllegalDataValue);
+    }
+    if address as u32 + count as u32 > size as u32 {
+        return Err(Exception::IllegalDataAddress);
+    }
+    Ok(())
+}
+
+/// 取出写多个线圈/寄存器请求中的数据部分
+///
+/// # 参数
+/// * `pdu` - 请求 PDU：功能码、地址、数量、字节数、数据
+/// * `expected` - 按数量计算应有的字节数
+pub fn write_data(pdu: &[u8], expected: usize) -> Result<&[u8], Exception> {
+    let byte_count = *pdu.get(5).ok_or(Exception::IllegalDataValue)? as usize;
+    if byte_count != expected || pdu.len() < 6 + byte_count {
+        return Err(Exception::IllegalDataValue);
+    }
+    Ok(&pdu[6..6 + byte_count])
+}
+
+/// 将位打包为响应数据（字节数 + 数据，低位在前）
+///
+/// # 返回
+/// 响应 PDU 长度（含功能码）
+pub fn pack_bits(bits: &[bool], out: &mut [u8]) -> usize {
+    let byte_count = bits.len().div_ceil(8);
+    out[1] = byte_count as u8;
+    out[2..2 + byte_count].fill(0);
+    for (index, &bit) in bits.iter().enumerate() {
+        if bit {
+            out[2 + index / 8] |= 1 << (index % 8);
+        }
+    }
+    2 + byte_count
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crc16_matches_reference_frame() {
+        // 读从站 1 的保持寄存器 0-1：01 03 00 00 00 02 C4 0B
+        assert_eq!(crc16(&[0x01, 0x03, 0x00, 0x00, 0x00, 0x02]), 0x0BC4);
+    }
+
+    #[test]
+    fn checks_rtu_frames() {
+        let frame = [0x01, 0x03, 0x00, 0x00, 0x00, 0x02, 0xC4, 0x0B];
+        assert_eq!(rtu_pdu(&frame), Some((1, &frame[1..6])));
+        let mut corrupted = frame;
+        corrupted[3] ^= 1;
+        assert_eq!(rtu_pdu(&corrupted), None);
+        assert_eq!(rtu_pdu(&frame[..3]), None);
+    }
+
+    #[test]
+    fn sealed_rtu_frame_passes_check() {
+        let mut frame = [0u8; RTU_FRAME_LEN];
+        let pdu_len = exception_pdu(0x03, Exception::IllegalDataAddress, &mut frame[1..]);
+        let len = seal_rtu(0x11, pdu_len, &mut frame);
+        assert_eq!(len, 5);
+        assert_eq!(rtu_pdu(&frame[..len]), Some((0x11, &[0x83, 0x02][..])));
+    }
+
+    #[test]
+    fn validates_mbap_header() {
+        assert_eq!(mbap_pdu_len(&[0, 1, 0, 0, 0, 6, 1]), Some(5));
+        // 协议标识符不为 0
+        assert_eq!(mbap_pdu_len(&[0, 1, 0, 1, 0, 6, 1]), None);
+        // 没有 PDU 或 PDU 过长
+        assert_eq!(mbap_pdu_len(&[0, 1, 0, 0, 0, 1, 1]), None);
+        assert_eq!(mbap_pdu_len(&[0, 1, 0, 0, 0, 255, 1]), None);
+    }
+
+    #[test]
+    fn response_header_echoes_request() {
+        let mut out = [0u8; MBAP_LEN];
+        mbap_response(&[0x12, 0x34, 0, 0, 0, 6, 9], 4, &mut out);
+        assert_eq!(out, [0x12, 0x34, 0, 0, 0, 5, 9]);
+    }
+
+    #[test]
+    fn short_requests_are_rejected() {
+        assert_eq!(
+            Request::parse(&[function::READ_COILS, 0, 0]),
+            Err(Exception::IllegalDataValue)
+        );
+        assert_eq!(
+            Request::parse(&[0x2B, 0x0E]),
+            Err(Exception::IllegalFunction)
+        );
+        let request = Request::parse(&[function::WRITE_SINGLE_COIL, 0, 1, 0xFF, 0]).unwrap();
+        assert_eq!((request.address, request.value), (1, 0xFF00));
+    }
+
+    #[test]
+    fn checks_read_ranges() {
+        assert_eq!(check_range(0, 2, 2, 125), Ok(()));
+        assert_eq!(
+            check_range(1, 2, 2, 125),
+            Err(Exception::IllegalDataAddress)
+        );
+        assert_eq!(check_range(0, 0, 2, 125), Err(Exception::IllegalDataValue));
+        assert_eq!(
+            check_range(0, 126, 200, 125),
+            Err(Exception::IllegalDataValue)
+        );
+    }
+
+    #[test]
+    fn extracts_write_data() {
+        let pdu = [function::WRITE_MULTIPLE_COILS, 0, 0, 0, 2, 1, 0b10];
+        assert_eq!(write_data(&pdu, 1), Ok(&[0b10][..]));
+        assert_eq!(write_data(&pdu, 2), Err(Exception::IllegalDataValue));
+        assert_eq!(write_data(&pdu[..6], 1), Err(Exception::IllegalDataValue));
+    }
+
+    #[test]
+    fn packs_bits_low_first() {
+        let mut out = [0xFFu8; 5];
+        let bits = [true, false, true, false, false, false, false, false, true];
+        assert_eq!(pack_bits(&bits, &mut out), 4);
+        assert_eq!(out[1..4], [2, 0b101, 0b1]);
+    }
+}
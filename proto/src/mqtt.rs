@@ -0,0 +1,305 @@
+//! MQTT 3.1.1 报文
+//!
+//! 只使用 QoS 0 的最小客户端需要的报文：CONNECT、SUBSCRIBE、PUBLISH 的编码，
+//! 固定报头、CONNACK、SUBACK 和 PUBLISH 的解析，以及订阅过滤器的匹配。
+//! 连接、收发和重连由固件负责。
+
+use alloc::vec::Vec;
+
+/// 报文类型（固定报头第一个字节的高 4 位）
+const CONNECT: u8 = 0x10;
+pub const CONNACK: u8 = 0x20;
+pub const PUBLISH: u8 = 0x30;
+const SUBSCRIBE: u8 = 0x82;
+pub const SUBACK: u8 = 0x90;
+pub const PINGREQ: u8 = 0xC0;
+pub const PINGRESP: u8 = 0xD0;
+pub const DISCONNECT: u8 = 0xE0;
+
+/// PUBLISH 的保留标志
+const RETAIN: u8 = 0x01;
+
+/// 报文格式错误
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Malformed;
+
+/// 主题是否与过滤器匹配
+pub fn topic_matches(filter: &str, topic: &str) -> bool {
+    let mut levels = topic.split('/');
+    for pattern in filter.split('/') {
+        if pattern == "#" {
+            return true;
+        }
+        match levels.next() {
+            Some(level) if pattern == "+" || pattern == level => {}
+            _ => return false,
+        }
+    }
+    levels.next().is_none()
+}
+
+/// 解析固定报头
+///
+/// # 返回
+/// 报文类型、报头长度和报文体长度；数据不足一个报头时返回 None
+pub fn parse_header(data: &[u8]) -> Result<Option<(u8, usize, usize)>, Malformed> {
+    let Some(&kind) = data.first() else {
+        return Ok(None);
+    };
+    // 剩余长度：每字节 7 位，低位在前，最多 4 字节
+    let mut body_len = 0usize;
+    for (i, &byte) in data[1..].iter().enumerate().take(4) {
+        body_len |= ((byte & 0x7F) as usize) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Ok(Some((kind, i + 2, body_len)));
+        }
+    }
+    if data.len() > 4 {
+        Err(Malformed)
+    } else {
+        Ok(None)
+    }
+}
+
+/// 读取带 2 字节长度前缀的字符串
+///
+/// # 返回
+/// 字符串和剩余的数据
+fn read_str(data: &[u8]) -> Option<(&str, &[u8])> {
+    let (len, rest) = data.split_first_chunk::<2>()?;
+    let len = u16::from_be_bytes(*len) as usize;
+    let text = core::str::from_utf8(rest.get(..len)?).ok()?;
+    Some((text, &rest[len..]))
+}
+
+/// 解析 CONNACK 报文体
+///
+/// # 返回
+/// 返回码，0 表示接受连接
+pub fn parse_connack(kind: u8, body: &[u8]) -> Result<u8, Malformed> {
+    match body {
+        [_, code] if kind == CONNACK => Ok(*code),
+        _ => Err(Malformed),
+    }
+}
+
+/// 解析 PUBLISH 报文体
+///
+/// # 参数
+/// * `kind` - 固定报头的第一个字节
+/// * `body` - 报文体
+///
+/// # 返回
+/// 主题和消息内容
+pub fn parse_publish(kind: u8, body: &[u8]) -> Result<(&str, &[u8]), Malformed> {
+    let (topic, rest) = read_str(body).ok_or(Malformed)?;
+    // QoS 大于 0 时主题之后是报文标识符；订阅的是 QoS 0，代理不会发来，这里只是跳过
+    let payload = if kind & 0x06 != 0 {
+        rest.get(2..).ok_or(Malformed)?
+    } else {
+        rest
+    };
+    Ok((topic, payload))
+}
+
+/// SUBACK 中被代理拒绝的订阅
+///
+/// # 返回
+/// 有过滤器被拒绝时返回 SUBSCRIBE 的报文标识符
+pub fn rejected_subscription(body: &[u8]) -> Option<u16> {
+    let (id, codes) = body.split_first_chunk::<2>()?;
+    codes.contains(&0x80).then_some(u16::from_be_bytes(*id))
+}
+
+/// 报文编码
+struct Packet(Vec<u8>);
+
+impl Packet {
+    fn new() -> Self {
+        Packet(Vec::new())
+    }
+
+    fn u8(&mut self, value: u8) -> &mut Self {
+        self.0.push(value);
+        self
+    }
+
+    fn u16(&mut self, value: u16) -> &mut Self {
+        self.0.extend_from_slice(&value.to_be_bytes());
+        self
+    }
+
+    /// 带 2 字节长度前缀的数据
+    fn bytes(&mut self, value: &[u8]) -> &mut Self {
+        self.u16(value.len() as u16);
+        self.0.extend_from_slice(value);
+        self
+    }
+
+    /// 不带长度前缀的数据
+    fn raw(&mut self, value: &[u8]) -> &mut Self {
+        self.0.extend_from_slice(value);
+        self
+    }
+
+    /// 加上固定报头
+    fn finish(&self, kind: u8) -> Vec<u8> {
+        let mut packet = Vec::with_capacity(self.0.len() + 5);
+        packet.push(kind);
+        let mut len = self.0.len();
+        loop {
+            let byte = (len & 0x7F) as u8;
+            len >>= 7;
+            if len == 0 {
+                packet.push(byte);
+                break;
+            }
+            packet.push(byte | 0x80);
+        }
+        packet.extend_from_slice(&self.0);
+        packet
+    }
+}
+
+/// CONNECT 报文：清除会话，遗嘱为保留的 `offline`
+///
+/// # 参数
+/// * `client_id` - 客户端标识
+/// * `keep_alive_secs` - 保活间隔（秒）
+/// * `will_topic` - 遗嘱主题
+/// * `credentials` - 用户名和密码
+pub fn connect_packet(
+    client_id: &str,
+    keep_alive_secs: u16,
+    will_topic: &str,
+    credentials: Option<(&str, &str)>,
+) -> Vec<u8> {
+    // 清除会话、遗嘱、遗嘱保留
+    let mut flags = 0x02 | 0x04 | 0x20;
+    if let Some((_, password)) = credentials {
+        flags |= 0x80;
+        if !password.is_empty() {
+            flags |= 0x40;
+        }
+    }
+    let mut packet = Packet::new();
+    packet
+        .bytes(b"MQTT")
+        .u8(4)
+        .u8(flags)
+        .u16(keep_alive_secs)
+        .bytes(client_id.as_bytes())
+        .bytes(will_topic.as_bytes())
+        .bytes(b"offline");
+    if let Some((user, password)) = credentials {
+        packet.bytes(user.as_bytes());
+        if !password.is_empty() {
+            packet.bytes(password.as_bytes());
+        }
+    }
+    packet.finish(CONNECT)
+}
+
+/// SUBSCRIBE 报文，每个过滤器都请求 QoS 0
+pub fn subscribe_packet(id: u16, filters: &[&str]) -> Vec<u8> {
+    let mut packet = Packet::new();
+    packet.u16(id);
+    for filter in filters {
+        packet.bytes(filter.as_bytes()).u8(0);
+    }
+    packet.finish(SUBSCRIBE)
+}
+
+/// QoS 0 的 PUBLISH 报文
+pub fn publish_packet(topic: &str, payload: &[u8], retain: bool) -> Vec<u8> {
+    let kind = if retain { PUBLISH | RETAIN } else { PUBLISH };
+    Packet::new()
+        .bytes(topic.as_bytes())
+        .raw(payload)
+        .finish(kind)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_topic_filters() {
+        assert!(topic_matches("home/+/temp", "home/kitchen/temp"));
+        assert!(topic_matches("home/#", "home/kitchen/temp"));
+        assert!(topic_matches("home/#", "home"));
+        assert!(!topic_matches("home/+/temp", "home/kitchen/hum"));
+        assert!(!topic_matches("home/+", "home/kitchen/temp"));
+        assert!(!topic_matches("home/kitchen/temp", "home/kitchen"));
+    }
+
+    #[test]
+    fn encodes_connect() {
+        let packet = connect_packet("dev", 60, "s", Some(("u", "p")));
+        assert_eq!(
+            packet,
+            [
+                CONNECT, 33, 0, 4, b'M', b'Q', b'T', b'T', 4, 0xE6, 0, 60, 0, 3, b'd', b'e', b'v',
+                0, 1, b's', 0, 7, b'o', b'f', b'f', b'l', b'i', b'n', b'e', 0, 1, b'u', 0, 1, b'p',
+            ]
+        );
+        // 没有密码时不设置密码标志
+        let packet = connect_packet("dev", 60, "s", Some(("u", "")));
+        assert_eq!(packet[9], 0xA6);
+        let packet = connect_packet("dev", 60, "s", None);
+        assert_eq!(packet[9], 0x26);
+    }
+
+    #[test]
+    fn encodes_subscribe_and_publish() {
+        assert_eq!(
+            subscribe_packet(1, &["a/+", "b"]),
+            [
+                SUBSCRIBE, 12, 0, 1, 0, 3, b'a', b'/', b'+', 0, 0, 1, b'b', 0
+            ]
+        );
+        assert_eq!(
+            publish_packet("t", b"on", true),
+            [PUBLISH | RETAIN, 5, 0, 1, b't', b'o', b'n']
+        );
+    }
+
+    #[test]
+    fn long_packets_use_multibyte_length() {
+        let payload = [0u8; 200];
+        let packet = publish_packet("t", &payload, false);
+        assert_eq!(packet[..3], [PUBLISH, 0xCB, 0x01]);
+        assert_eq!(parse_header(&packet), Ok(Some((PUBLISH, 3, 203))));
+    }
+
+    #[test]
+    fn parses_fixed_header() {
+        assert_eq!(parse_header(&[]), Ok(None));
+        assert_eq!(parse_header(&[PUBLISH, 0x80]), Ok(None));
+        assert_eq!(parse_header(&[PINGRESP, 0]), Ok(Some((PINGRESP, 2, 0))));
+        assert_eq!(
+            parse_header(&[PUBLISH, 0xFF, 0xFF, 0xFF, 0xFF]),
+            Err(Malformed)
+        );
+    }
+
+    #[test]
+    fn parses_publish() {
+        let body = [0, 3, b'a', b'/', b'b', b'h', b'i'];
+        assert_eq!(parse_publish(PUBLISH, &body), Ok(("a/b", &b"hi"[..])));
+        // QoS 1 的报文标识符被跳过
+        let body = [0, 1, b'a', 0, 7, b'h', b'i'];
+        assert_eq!(parse_publish(PUBLISH | 0x02, &body), Ok(("a", &b"hi"[..])));
+        assert_eq!(parse_publish(PUBLISH, &[0, 5, b'a']), Err(Malformed));
+    }
+
+    #[test]
+    fn parses_acknowledgements() {
+        assert_eq!(parse_connack(CONNACK, &[0, 0]), Ok(0));
+        assert_eq!(parse_connack(CONNACK, &[0, 5]), Ok(5));
+        assert_eq!(parse_connack(SUBACK, &[0, 0]), Err(Malformed));
+        assert_eq!(rejected_subscription(&[0, 2, 0, 0x80]), Some(2));
+        assert_eq!(rejected_subscription(&[0, 2, 0]), None);
+    }
+}
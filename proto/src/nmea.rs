@@ -0,0 +1,205 @@
+//! NMEA 0183 语句解析
+//!
+//! 只解析 GPS 接收机的两种语句：
+//!
+//! - GGA：定位质量、卫星数、经纬度和海拔
+//! - RMC：UTC 日期和时间
+//!
+//! 支持所有卫星系统的语句前缀（GP、GN、GL、BD 等），校验和错误的语句被丢弃。
+
+/// GGA 语句
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Gga {
+    /// 定位质量：0 无效，1 GPS，2 差分，4/5 RTK，6 推算
+    pub quality: u8,
+    /// 参与定位的卫星数
+    pub satellites: u8,
+    /// 纬度（度，北纬为正），字段为空时为 None
+    pub latitude: Option<f64>,
+    /// 经度（度，东经为正），字段为空时为 None
+    pub longitude: Option<f64>,
+    /// 海拔（米）
+    pub altitude: Option<f32>,
+}
+
+/// RMC 语句
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rmc {
+    /// 状态为 A（有效）
+    pub valid: bool,
+    /// UTC 日期（年, 月, 日），字段为空时为 None
+    pub date: Option<(u16, u8, u8)>,
+    /// UTC 时间（时, 分, 秒）
+    pub time: (u8, u8, u8),
+    /// 秒的小数部分（毫秒）
+    pub millis: u32,
+}
+
+/// 支持的语句
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Sentence {
+    Gga(Gga),
+    Rmc(Rmc),
+}
+
+/// 解析一行 NMEA 语句
+///
+/// # 返回
+/// 校验和错误、不支持的语句或字段不全时返回 None
+pub fn parse(line: &str) -> Option<Sentence> {
+    let body = verify_checksum(line.trim())?;
+    let mut fields = body.split(',');
+    // 前两个字符是卫星系统前缀
+    let kind = fields.next()?.get(2..)?;
+    match kind {
+        "GGA" => parse_gga(fields).map(Sentence::Gga),
+        "RMC" => parse_rmc(fields).map(Sentence::Rmc),
+        _ => None,
+    }
+}
+
+/// 校验 `$<body>*<hh>` 格式的语句
+///
+/// # 返回
+/// 校验通过时返回 body
+fn verify_checksum(line: &str) -> Option<&str> {
+    let (body, checksum) = line.strip_prefix('$')?.split_once('*')?;
+    let expected = u8::from_str_radix(checksum.get(..2)?, 16).ok()?;
+    let actual = body.bytes().fold(0u8, |acc, byte| acc ^ byte);
+    (actual == expected).then_some(body)
+}
+
+/// 解析 GGA：时间,纬度,N/S,经度,E/W,质量,卫星数,HDOP,海拔,M,...
+fn parse_gga<'a>(mut fields: impl Iterator<Item = &'a str>) -> Option<Gga> {
+    let _time = fields.next()?;
+    let latitude = parse_coordinate(fields.next()?, fields.next()?);
+    let longitude = parse_coordinate(fields.next()?, fields.next()?);
+    let quality = fields.next()?.parse().unwrap_or(0);
+    let satellites = fields.next()?.parse().unwrap_or(0);
+    let _hdop = fields.next()?;
+    let altitude = fields.next().and_then(|value| value.parse().ok());
+    Some(Gga {
+        quality,
+        satellites,
+        latitude,
+        longitude,
+        altitude,
+    })
+}
+
+/// 解析 RMC：时间,状态,纬度,N/S,经度,E/W,速度,航向,日期,...
+fn parse_rmc<'a>(mut fields: impl Iterator<Item = &'a str>) -> Option<Rmc> {
+    let time = fields.next()?;
+    let valid = fields.next()? == "A";
+    // 跳过纬度、N/S、经度、E/W、速度、航向
+    let date = fields.nth(6)?;
+
+    let (hour, minute, second, millis) = parse_time(time)?;
+    Some(Rmc {
+        valid,
+        date: parse_date(date),
+        time: (hour, minute, second),
+        millis,
+    })
+}
+
+/// 解析 `ddmm.mmmm` / `dddmm.mmmm` 格式的坐标
+///
+/// # 返回
+/// 度数，南纬和西经为负；字段为空时返回 None
+fn parse_coordinate(value: &str, hemisphere: &str) -> Option<f64> {
+    let dot = value.find('.').unwrap_or(value.len());
+    let split = dot.checked_sub(2)?;
+    let degrees: f64 = value[..split].parse().ok()?;
+    let minutes: f64 = value[split..].parse().ok()?;
+    let coordinate = degrees + minutes / 60.0;
+    match hemisphere {
+        "N" | "E" => Some(coordinate),
+        "S" | "W" => Some(-coordinate),
+        _ => None,
+    }
+}
+
+/// 解析 `hhmmss.sss` 格式的时间
+fn parse_time(value: &str) -> Option<(u8, u8, u8, u32)> {
+    let hour = value.get(0..2)?.parse().ok()?;
+    let minute = value.get(2..4)?.parse().ok()?;
+    let second = value.get(4..6)?.parse().ok()?;
+    let millis = match value.get(6..) {
+        Some(fraction) if fraction.len() > 1 => {
+            let fraction: f32 = fraction.parse().ok()?;
+            (fraction * 1000.0) as u32
+        }
+        _ => 0,
+    };
+    Some((hour, minute, second, millis))
+}
+
+/// 解析 `ddmmyy` 格式的日期
+fn parse_date(value: &str) -> Option<(u16, u8, u8)> {
+    let day = value.get(0..2)?.parse().ok()?;
+    let month = value.get(2..4)?.parse().ok()?;
+    let year: u16 = value.get(4..6)?.parse().ok()?;
+    Some((2000 + year, month, day))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_gga() {
+        let line = "$GPGGA,123519,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,*47\r\n";
+        let Some(Sentence::Gga(gga)) = parse(line) else {
+            panic!("not a GGA sentence");
+        };
+        assert_eq!(gga.quality, 1);
+        assert_eq!(gga.satellites, 8);
+        assert!((gga.latitude.unwrap() - 48.1173).abs() < 1e-4);
+        assert!((gga.longitude.unwrap() - 11.516_667).abs() < 1e-4);
+        assert_eq!(gga.altitude, Some(545.4));
+    }
+
+    #[test]
+    fn gga_without_fix_has_no_position() {
+        let Some(Sentence::Gga(gga)) = parse("$GNGGA,,,,,,0,00,,,,,,,*78") else {
+            panic!("not a GGA sentence");
+        };
+        assert_eq!(gga.quality, 0);
+        assert_eq!(gga.latitude, None);
+        assert_eq!(gga.longitude, None);
+    }
+
+    #[test]
+    fn parses_rmc() {
+        let line = "$GPRMC,123519.25,A,4807.038,N,01131.000,E,022.4,084.4,230394,003.1,W*43";
+        let Some(Sentence::Rmc(rmc)) = parse(line) else {
+            panic!("not an RMC sentence");
+        };
+        assert!(rmc.valid);
+        assert_eq!(rmc.date, Some((2094, 3, 23)));
+        assert_eq!(rmc.time, (12, 35, 19));
+        assert_eq!(rmc.millis, 250);
+    }
+
+    #[test]
+    fn southern_and_western_coordinates_are_negative() {
+        assert_eq!(parse_coordinate("3000.000", "S"), Some(-30.0));
+        assert_eq!(parse_coordinate("12030.000", "W"), Some(-120.5));
+        assert_eq!(parse_coordinate("", "N"), None);
+    }
+
+    #[test]
+    fn rejects_bad_checksum() {
+        assert_eq!(
+            parse("$GPGGA,123519,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,*48"),
+            None
+        );
+        assert_eq!(parse("GPGGA,123519*47"), None);
+    }
+
+    #[test]
+    fn ignores_other_sentences() {
+        assert_eq!(parse("$GPGSA,A,3,,,,,,,,,,,,,,,*1C"), None);
+    }
+}
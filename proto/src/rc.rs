@@ -0,0 +1,318 @@
+//! 航模遥控接收机协议
+//!
+//! 逐字节解码接收机串口输出的通道帧，支持两种协议：
+//!
+//! - [Protocol::Sbus]：25 字节定长帧，标志字节报告丢帧和失控
+//! - [Protocol::Crsf]：变长帧，带 CRC8 校验，只解码通道帧（0x16）
+//!
+//! 两种协议都是 16 个 11 位通道，解码后换算为常见的舵机脉宽（微秒，中位 1500）。
+//! 串口的波特率、校验和信号反相由固件按协议设置。
+
+/// 通道数量
+pub const CHANNELS: usize = 16;
+
+/// SBUS 帧长度
+const SBUS_FRAME_LEN: usize = 25;
+
+/// SBUS 帧头
+const SBUS_HEADER: u8 = 0x0F;
+
+/// SBUS 标志字节：接收机丢帧
+const SBUS_FRAME_LOST: u8 = 1 << 2;
+
+/// SBUS 标志字节：接收机失控
+const SBUS_FAILSAFE: u8 = 1 << 3;
+
+/// CRSF 最大帧长度（同步字节 + 长度字节 + 最多 62 字节）
+const CRSF_FRAME_LEN: usize = 64;
+
+/// CRSF 通道帧类型
+const CRSF_RC_CHANNELS_PACKED: u8 = 0x16;
+
+/// 打包的 16 个 11 位通道占用的字节数
+const PACKED_CHANNELS_LEN: usize = 22;
+
+/// 接收机协议
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Protocol {
+    Sbus,
+    Crsf,
+}
+
+impl Protocol {
+    /// 协议的波特率
+    pub const fn baudrate(self) -> u32 {
+        match self {
+            Protocol::Sbus => 100_000,
+            Protocol::Crsf => 420_000,
+        }
+    }
+
+    /// 按波特率选择协议，扩展排针上的接线用波特率区分协议
+    pub fn from_baudrate(baudrate: u32) -> Option<Protocol> {
+        [Protocol::Sbus, Protocol::Crsf]
+            .into_iter()
+            .find(|protocol| protocol.baudrate() == baudrate)
+    }
+}
+
+/// 一帧通道数据
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct RcFrame {
+    /// 通道脉宽（微秒），通常在 988 到 2012 之间
+    pub channels: [u16; CHANNELS],
+    /// 是否处于失控状态
+    pub failsafe: bool,
+}
+
+/// 帧解析器，逐字节输入，允许从任意位置开始同步
+pub struct Parser {
+    protocol: Protocol,
+    buf: [u8; CRSF_FRAME_LEN],
+    len: usize,
+}
+
+impl Parser {
+    pub fn new(protocol: Protocol) -> Self {
+        Parser {
+            protocol,
+            buf: [0; CRSF_FRAME_LEN],
+            len: 0,
+        }
+    }
+
+    /// 输入一个字节
+    ///
+    /// # 返回
+    /// 收齐一个有效的通道帧时返回解码结果
+    pub fn push(&mut self, byte: u8) -> Option<RcFrame> {
+        // 帧头不对时丢弃，等待下一个帧头
+        if self.len == 0 && !self.is_header(byte) {
+            return None;
+        }
+        self.buf[self.len] = byte;
+        self.len += 1;
+
+        let frame = match self.protocol {
+            Protocol::Sbus => self.check_sbus(),
+            Protocol::Crsf => self.check_crsf(),
+        };
+        match frame {
+            Check::Incomplete => None,
+            Check::Invalid => {
+                self.resync();
+                None
+            }
+            Check::Done(frame) => {
+                self.len = 0;
+                frame
+            }
+        }
+    }
+
+    fn is_header(&self, byte: u8) -> bool {
+        match self.protocol {
+            Protocol::Sbus => byte == SBUS_HEADER,
+            // 接收机发给飞控的帧通常用 0xC8，部分固件用设备地址 0xEE/0xEA
+            Protocol::Crsf => matches!(byte, 0xC8 | 0xEE | 0xEA),
+        }
+    }
+
+    fn check_sbus(&self) -> Check {
+        if self.len < SBUS_FRAME_LEN {
+            return Check::Incomplete;
+        }
+        // 结束字节为 0x00，部分接收机在低 4 位为 0x04 时附带遥测槽位号
+        let end = self.buf[SBUS_FRAME_LEN - 1];
+        if end != 0x00 && end & 0x0F != 0x04 {
+            return Check::Invalid;
+        }
+        let flags = self.buf[23];
+        if flags & SBUS_FRAME_LOST != 0 {
+            return Check::Done(None);
+        }
+        Check::Done(Some(RcFrame {
+            channels: unpack_channels(&self.buf[1..23]),
+            failsafe: flags & SBUS_FAILSAFE != 0,
+        }))
+    }
+
+    fn check_crsf(&self) -> Check {
+        if self.len < 2 {
+            return Check::Incomplete;
+        }
+        // 长度字节包括类型、负载和 CRC
+        let frame_len = self.buf[1] as usize;
+        if !(2..=CRSF_FRAME_LEN - 2).contains(&frame_len) {
+            return Check::Invalid;
+        }
+        if self.len < frame_len + 2 {
+            return Check::Incomplete;
+        }
+        let body = &self.buf[2..frame_len + 1];
+        if crc8_dvb_s2(body) != self.buf[frame_len + 1] {
+            return Check::Invalid;
+        }
+        let (kind, payload) = (body[0], &body[1..]);
+        if kind != CRSF_RC_CHANNELS_PACKED || payload.len() != PACKED_CHANNELS_LEN {
+            // 链路统计等其他帧不关心
+            return Check::Done(None);
+        }
+        Check::Done(Some(RcFrame {
+            channels: unpack_channels(payload),
+            failsafe: false,
+        }))
+    }
+
+    /// 帧无效时从第二个字节起重新寻找帧头
+    fn resync(&mut self) {
+        let start = (1..self.len)
+            .find(|&i| self.is_header(self.buf[i]))
+            .unwrap_or(self.len);
+        self.buf.copy_within(start..self.len, 0);
+        self.len -= start;
+    }
+}
+
+/// 解析进度
+enum Check {
+    Incomplete,
+    Invalid,
+    /// 收齐一帧；不是通道帧或接收机丢帧时为 None
+    Done(Option<RcFrame>),
+}
+
+/// 解包 16 个 11 位通道（低位在前），并换算为脉宽
+fn unpack_channels(packed: &[u8]) -> [u16; CHANNELS] {
+    let mut channels = [0u16; CHANNELS];
+    for (i, channel) in channels.iter_mut().enumerate() {
+        let bit = i * 11;
+        let byte = bit / 8;
+        let word = packed[byte] as u32
+            | (packed[byte + 1] as u32) << 8
+            | (packed.get(byte + 2).copied().unwrap_or(0) as u32) << 16;
+        *channel = to_micros((word >> (bit % 8)) as u16 & 0x7FF);
+    }
+    channels
+}
+
+/// 原始值换算为脉宽：172 对应 988 µs，992 对应 1500 µs，1811 对应 2012 µs
+fn to_micros(raw: u16) -> u16 {
+    ((raw as i32 - 992) * 5 / 8 + 1500) as u16
+}
+
+/// CRSF 使用的 CRC8（多项式 0xD5）
+fn crc8_dvb_s2(data: &[u8]) -> u8 {
+    data.iter().fold(0u8, |mut crc, &byte| {
+        crc ^= byte;
+        for _ in 0..8 {
+            crc = if crc & 0x80 != 0 {
+                (crc << 1) ^ 0xD5
+            } else {
+                crc << 1
+            };
+        }
+        crc
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 把 16 个原始通道值打包为 22 字节
+    fn pack(raw: [u16; CHANNELS]) -> [u8; PACKED_CHANNELS_LEN] {
+        let mut packed = [0u8; PACKED_CHANNELS_LEN];
+        for (i, &value) in raw.iter().enumerate() {
+            for bit in 0..11 {
+                if value >> bit & 1 != 0 {
+                    let n = i * 11 + bit;
+                    packed[n / 8] |= 1 << (n % 8);
+                }
+            }
+        }
+        packed
+    }
+
+    fn sbus_frame(raw: [u16; CHANNELS], flags: u8) -> [u8; SBUS_FRAME_LEN] {
+        let mut frame = [0u8; SBUS_FRAME_LEN];
+        frame[0] = SBUS_HEADER;
+        frame[1..23].copy_from_slice(&pack(raw));
+        frame[23] = flags;
+        frame
+    }
+
+    fn feed(parser: &mut Parser, bytes: &[u8]) -> Option<RcFrame> {
+        bytes.iter().filter_map(|&byte| parser.push(byte)).last()
+    }
+
+    fn ramp() -> [u16; CHANNELS] {
+        core::array::from_fn(|i| 172 + i as u16 * 100)
+    }
+
+    #[test]
+    fn crc8_matches_check_value() {
+        assert_eq!(crc8_dvb_s2(b"123456789"), 0xBC);
+    }
+
+    #[test]
+    fn converts_raw_values_to_micros() {
+        assert_eq!(to_micros(172), 988);
+        assert_eq!(to_micros(992), 1500);
+        assert_eq!(to_micros(1811), 2011);
+    }
+
+    #[test]
+    fn decodes_sbus_frame() {
+        let mut parser = Parser::new(Protocol::Sbus);
+        let frame = feed(&mut parser, &sbus_frame(ramp(), 0)).unwrap();
+        let expected: [u16; CHANNELS] = core::array::from_fn(|i| to_micros(ramp()[i]));
+        assert_eq!(frame.channels, expected);
+        assert!(!frame.failsafe);
+    }
+
+    #[test]
+    fn reports_sbus_failsafe_and_skips_lost_frames() {
+        let mut parser = Parser::new(Protocol::Sbus);
+        let frame = feed(&mut parser, &sbus_frame(ramp(), SBUS_FAILSAFE)).unwrap();
+        assert!(frame.failsafe);
+        assert_eq!(
+            feed(&mut parser, &sbus_frame(ramp(), SBUS_FRAME_LOST)),
+            None
+        );
+    }
+
+    #[test]
+    fn resyncs_after_garbage() {
+        let mut parser = Parser::new(Protocol::Sbus);
+        let mut bytes = [0x55, SBUS_HEADER, 0x12].to_vec();
+        bytes.extend_from_slice(&sbus_frame(ramp(), 0));
+        assert!(feed(&mut parser, &bytes).is_some());
+    }
+
+    #[test]
+    fn decodes_crsf_channels() {
+        let mut body = [CRSF_RC_CHANNELS_PACKED].to_vec();
+        body.extend_from_slice(&pack([992; CHANNELS]));
+        let mut bytes = [0xC8, body.len() as u8 + 1].to_vec();
+        bytes.extend_from_slice(&body);
+        bytes.push(crc8_dvb_s2(&body));
+
+        let mut parser = Parser::new(Protocol::Crsf);
+        let frame = feed(&mut parser, &bytes).unwrap();
+        assert_eq!(frame.channels, [1500; CHANNELS]);
+
+        // CRC 错误的帧被丢弃
+        *bytes.last_mut().unwrap() ^= 1;
+        assert_eq!(feed(&mut parser, &bytes), None);
+    }
+
+    #[test]
+    fn selects_protocol_by_baudrate() {
+        assert_eq!(Protocol::from_baudrate(100_000), Some(Protocol::Sbus));
+        assert_eq!(Protocol::from_baudrate(420_000), Some(Protocol::Crsf));
+        assert_eq!(Protocol::from_baudrate(115_200), None);
+    }
+}
@@ -0,0 +1,613 @@
+//! SNMP v2c 报文
+//!
+//! 只读代理需要的 BER 编解码和请求处理：支持 GetRequest、GetNextRequest 和 GetBulkRequest，
+//! 其他请求（包括 SetRequest）返回 [SnmpError::Unsupported]，调用者直接丢弃。
+//! 对象表由调用者以 [Object] 的切片提供，按 OID 升序排列。
+
+use heapless::{String, Vec};
+
+/// 字符串值的最大长度
+pub const OCTET_STRING_LEN: usize = 32;
+
+/// OID 最多包含的子标识符数量
+const MAX_OID_LEN: usize = 24;
+
+/// 单个请求或响应最多包含的变量绑定数量
+const MAX_VARBINDS: usize = 16;
+
+/// 版本字段：SNMP v2c
+const VERSION_2C: i64 = 1;
+
+/// 错误状态：响应超出报文长度
+const ERROR_TOO_BIG: i64 = 1;
+
+/// BER 标签
+mod tag {
+    pub const INTEGER: u8 = 0x02;
+    pub const OCTET_STRING: u8 = 0x04;
+    pub const OBJECT_ID: u8 = 0x06;
+    pub const SEQUENCE: u8 = 0x30;
+    pub const TIME_TICKS: u8 = 0x43;
+    pub const NO_SUCH_OBJECT: u8 = 0x80;
+    pub const NO_SUCH_INSTANCE: u8 = 0x81;
+    pub const END_OF_MIB_VIEW: u8 = 0x82;
+    pub const GET_REQUEST: u8 = 0xA0;
+    pub const GET_NEXT_REQUEST: u8 = 0xA1;
+    pub const RESPONSE: u8 = 0xA2;
+    pub const GET_BULK_REQUEST: u8 = 0xA5;
+}
+
+/// 请求处理错误，出错的请求不回复
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SnmpError {
+    /// 报文格式错误
+    Malformed,
+    /// 不支持的版本或 PDU 类型
+    Unsupported,
+    /// 团体名错误
+    BadCommunity,
+    /// 响应超出缓冲区
+    TooBig,
+}
+
+/// 对象标识符
+pub type Oid = Vec<u32, MAX_OID_LEN>;
+
+/// 变量值
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    Integer(i32),
+    OctetString(String<OCTET_STRING_LEN>),
+    ObjectId(&'static [u32]),
+    TimeTicks(u32),
+    NoSuchObject,
+    NoSuchInstance,
+    EndOfMibView,
+}
+
+/// 对象表中的一项
+pub struct Object {
+    pub oid: &'static [u32],
+    /// 读取当前值，暂无数据时返回 None
+    pub get: fn() -> Option<Value>,
+}
+
+/// 按 OID 精确查找
+fn get(mib: &[Object], oid: &[u32]) -> Value {
+    match mib.iter().find(|object| object.oid == oid) {
+        Some(object) => (object.get)().unwrap_or(Value::NoSuchInstance),
+        None => Value::NoSuchObject,
+    }
+}
+
+/// 查找字典序在 `oid` 之后的第一个有值的对象
+fn get_next(mib: &[Object], oid: &Oid) -> (Oid, Value) {
+    mib.iter()
+        .filter(|object| object.oid > oid.as_slice())
+        .find_map(|object| Some((object.oid, (object.get)()?)))
+        .and_then(|(next, value)| Some((Oid::from_slice(next).ok()?, value)))
+        .unwrap_or_else(|| (oid.clone(), Value::EndOfMibView))
+}
+
+/// BER 解码
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    /// 读取一个 TLV
+    ///
+    /// # 返回
+    /// 标签和内容
+    fn tlv(&mut self) -> Result<(u8, &'a [u8]), SnmpError> {
+        let data = self.data;
+        let [tag, first, rest @ ..] = data else {
+            return Err(SnmpError::Malformed);
+        };
+        let (len, rest) = if first & 0x80 == 0 {
+            (*first as usize, rest)
+        } else {
+            // 长格式，报文不超过 64 KiB，最多两个长度字节
+            let count = (first & 0x7F) as usize;
+            if count == 0 || count > 2 || rest.len() < count {
+                return Err(SnmpError::Malformed);
+            }
+            let len = rest[..count]
+                .iter()
+                .fold(0, |len, &byte| len << 8 | byte as usize);
+            (len, &rest[count..])
+        };
+        if rest.len() < len {
+            return Err(SnmpError::Malformed);
+        }
+        let (content, rest) = rest.split_at(len);
+        self.data = rest;
+        Ok((*tag, content))
+    }
+
+    /// 读取指定标签的 TLV
+    fn expect(&mut self, tag: u8) -> Result<&'a [u8], SnmpError> {
+        match self.tlv()? {
+            (found, content) if found == tag => Ok(content),
+            _ => Err(SnmpError::Malformed),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+}
+
+/// 解码有符号整数
+fn decode_integer(content: &[u8]) -> Result<i64, SnmpError> {
+    if content.is_empty() || content.len() > 8 {
+        return Err(SnmpError::Malformed);
+    }
+    let sign = if content[0] & 0x80 != 0 { -1 } else { 0 };
+    Ok(content
+        .iter()
+        .fold(sign, |value, &byte| value << 8 | byte as i64))
+}
+
+/// 解码 OID
+fn decode_oid(content: &[u8]) -> Result<Oid, SnmpError> {
+    let (&first, rest) = content.split_first().ok_or(SnmpError::Malformed)?;
+    if first & 0x80 != 0 {
+        return Err(SnmpError::Malformed);
+    }
+    let mut oid = Oid::new();
+    let (arc1, arc2) = if first < 80 {
+        (first / 40, first % 40)
+    } else {
+        (2, first - 80)
+    };
+    oid.extend_from_slice(&[arc1 as u32, arc2 as u32])
+        .map_err(|_| SnmpError::Malformed)?;
+
+    let mut value: u32 = 0;
+    for &byte in rest {
+        value = value.checked_mul(128).ok_or(SnmpError::Malformed)? | (byte & 0x7F) as u32;
+        if byte & 0x80 == 0 {
+            oid.push(value).map_err(|_| SnmpError::Malformed)?;
+            value = 0;
+        }
+    }
+    if rest.last().is_some_and(|byte| byte & 0x80 != 0) {
+        return Err(SnmpError::Malformed);
+    }
+    Ok(oid)
+}
+
+/// BER 编码，从缓冲区末尾向前写入，先写内容再写长度和标签
+struct Encoder<'a> {
+    buf: &'a mut [u8],
+    start: usize,
+}
+
+impl<'a> Encoder<'a> {
+    fn new(buf: &'a mut [u8]) -> Self {
+        let start = buf.len();
+        Encoder { buf, start }
+    }
+
+    /// 已写入的长度
+    fn len(&self) -> usize {
+        self.buf.len() - self.start
+    }
+
+    fn push(&mut self, bytes: &[u8]) -> Result<(), SnmpError> {
+        let start = self
+            .start
+            .checked_sub(bytes.len())
+            .ok_or(SnmpError::TooBig)?;
+        self.buf[start..self.start].copy_from_slice(bytes);
+        self.start = start;
+        Ok(())
+    }
+
+    /// 写入标签和长度
+    ///
+    /// # 参数
+    /// * `tag` - 标签
+    /// * `end` - 写入内容之前的 [Self::len]
+    fn header(&mut self, tag: u8, end: usize) -> Result<(), SnmpError> {
+        let len = self.len() - end;
+        match len {
+            0..=0x7F => self.push(&[len as u8])?,
+            0x80..=0xFF => self.push(&[0x81, len as u8])?,
+            _ => {
+                let [high, low] = (len as u16).to_be_bytes();
+                self.push(&[0x82, high, low])?;
+            }
+        }
+        self.push(&[tag])
+    }
+
+    /// 写入最短的补码整数
+    fn integer(&mut self, tag: u8, value: i64) -> Result<(), SnmpError> {
+        let end = self.len();
+        let bytes = value.to_be_bytes();
+        let redundant = |i: usize| {
+            (bytes[i] == 0x00 && bytes[i + 1] & 0x80 == 0)
+                || (bytes[i] == 0xFF && bytes[i + 1] & 0x80 != 0)
+        };
+        let skip = (0..7).take_while(|&i| redundant(i)).count();
+        self.push(&bytes[skip..])?;
+        self.header(tag, end)
+    }
+
+    fn oid(&mut self, oid: &[u32]) -> Result<(), SnmpError> {
+        let end = self.len();
+        for &sub in oid.get(2..).unwrap_or_default().iter().rev() {
+            self.sub_identifier(sub)?;
+        }
+        let arc = |i: usize| oid.get(i).copied().unwrap_or(0);
+        self.sub_identifier(arc(0) * 40 + arc(1))?;
+        self.header(tag::OBJECT_ID, end)
+    }
+
+    /// 写入一个 base-128 子标识符，除最后一个字节外最高位为 1
+    fn sub_identifier(&mut self, mut value: u32) -> Result<(), SnmpError> {
+        self.push(&[value as u8 & 0x7F])?;
+        value >>= 7;
+        while value != 0 {
+            self.push(&[value as u8 | 0x80])?;
+            value >>= 7;
+        }
+        Ok(())
+    }
+
+    fn value(&mut self, value: &Value) -> Result<(), SnmpError> {
+        let end = self.len();
+        match *value {
+            Value::Integer(value) => self.integer(tag::INTEGER, value as i64),
+            Value::TimeTicks(ticks) => self.integer(tag::TIME_TICKS, ticks as i64),
+            Value::ObjectId(oid) => self.oid(oid),
+            Value::OctetString(ref text) => {
+                self.push(text.as_bytes())?;
+                self.header(tag::OCTET_STRING, end)
+            }
+            Value::NoSuchObject => self.header(tag::NO_SUCH_OBJECT, end),
+            Value::NoSuchInstance => self.header(tag::NO_SUCH_INSTANCE, end),
+            Value::EndOfMibView => self.header(tag::END_OF_MIB_VIEW, end),
+        }
+    }
+}
+
+/// 编码 Response 报文
+///
+/// # 返回
+/// 报文在 `buf` 末尾的起始位置
+fn encode_response(
+    buf: &mut [u8],
+    community: &[u8],
+    request_id: i64,
+    error_status: i64,
+    varbinds: &[(Oid, Value)],
+) -> Result<usize, SnmpError> {
+    let mut out = Encoder::new(buf);
+    for (oid, value) in varbinds.iter().rev() {
+        let end = out.len();
+        out.value(value)?;
+        out.oid(oid)?;
+        out.header(tag::SEQUENCE, end)?;
+    }
+    out.header(tag::SEQUENCE, 0)?;
+    // 错误索引从 1 开始，tooBig 不指向具体变量
+    out.integer(tag::INTEGER, 0)?;
+    out.integer(tag::INTEGER, error_status)?;
+    out.integer(tag::INTEGER, request_id)?;
+    out.header(tag::RESPONSE, 0)?;
+    let end = out.len();
+    out.push(community)?;
+    out.header(tag::OCTET_STRING, end)?;
+    out.integer(tag::INTEGER, VERSION_2C)?;
+    out.header(tag::SEQUENCE, 0)?;
+    Ok(out.start)
+}
+
+/// 处理一个请求
+///
+/// # 参数
+/// * `request` - 请求报文
+/// * `community` - 只读团体名
+/// * `mib` - 对象表，按 OID 升序排列
+/// * `buf` - 响应缓冲区
+///
+/// # 返回
+/// 响应报文，位于 `buf` 中
+pub fn handle<'b>(
+    request: &[u8],
+    community: &[u8],
+    mib: &[Object],
+    buf: &'b mut [u8],
+) -> Result<&'b [u8], SnmpError> {
+    let mut message = Reader {
+        data: Reader { data: request }.expect(tag::SEQUENCE)?,
+    };
+    if decode_integer(message.expect(tag::INTEGER)?)? != VERSION_2C {
+        return Err(SnmpError::Unsupported);
+    }
+    if message.expect(tag::OCTET_STRING)? != community {
+        return Err(SnmpError::BadCommunity);
+    }
+    let (pdu_type, pdu) = message.tlv()?;
+    let mut pdu = Reader { data: pdu };
+    let request_id = decode_integer(pdu.expect(tag::INTEGER)?)?;
+    // GetBulkRequest 中这两个字段是 non-repeaters 和 max-repetitions
+    let limit = |value: i64| value.clamp(0, MAX_VARBINDS as i64) as usize;
+    let non_repeaters = limit(decode_integer(pdu.expect(tag::INTEGER)?)?);
+    let max_repetitions = limit(decode_integer(pdu.expect(tag::INTEGER)?)?);
+
+    let mut list = Reader {
+        data: pdu.expect(tag::SEQUENCE)?,
+    };
+    let mut oids: Vec<Oid, MAX_VARBINDS> = Vec::new();
+    while !list.is_empty() {
+        let mut varbind = Reader {
+            data: list.expect(tag::SEQUENCE)?,
+        };
+        let oid = decode_oid(varbind.expect(tag::OBJECT_ID)?)?;
+        oids.push(oid).map_err(|_| SnmpError::TooBig)?;
+    }
+
+    let mut varbinds: Vec<(Oid, Value), MAX_VARBINDS> = Vec::new();
+    match pdu_type {
+        tag::GET_REQUEST => {
+            for oid in oids {
+                let value = get(mib, &oid);
+                varbinds.push((oid, value)).ok();
+            }
+        }
+        tag::GET_NEXT_REQUEST => {
+            for oid in &oids {
+                varbinds.push(get_next(mib, oid)).ok();
+            }
+        }
+        tag::GET_BULK_REQUEST => {
+            let (singles, repeaters) = oids.split_at(non_repeaters.min(oids.len()));
+            for oid in singles {
+                varbinds.push(get_next(mib, oid)).ok();
+            }
+            let mut cursors = Vec::<Oid, MAX_VARBINDS>::from_slice(repeaters).unwrap_or_default();
+            // 响应变量数量达到上限或所有变量都已走完时提前结束
+            'repeat: for _ in 0..max_repetitions {
+                let mut finished = true;
+                for cursor in cursors.iter_mut() {
+                    let (oid, value) = get_next(mib, cursor);
+                    finished &= value == Value::EndOfMibView;
+                    if varbinds.push((oid.clone(), value)).is_err() {
+                        break 'repeat;
+                    }
+                    *cursor = oid;
+                }
+                if finished {
+                    break;
+                }
+            }
+        }
+        _ => return Err(SnmpError::Unsupported),
+    }
+
+    let start = match encode_response(buf, community, request_id, 0, &varbinds) {
+        Err(SnmpError::TooBig) => encode_response(buf, community, request_id, ERROR_TOO_BIG, &[])?,
+        result => result?,
+    };
+    Ok(&buf[start..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ENTERPRISE: &[u32] = &[1, 3, 6, 1, 4, 1, 99999];
+
+    const MIB: [Object; 4] = [
+        Object {
+            oid: &[1, 3, 6, 1, 2, 1, 1, 1, 0],
+            get: || Some(Value::OctetString(String::try_from("board").unwrap())),
+        },
+        Object {
+            oid: &[1, 3, 6, 1, 2, 1, 1, 2, 0],
+            get: || Some(Value::ObjectId(ENTERPRISE)),
+        },
+        Object {
+            oid: &[1, 3, 6, 1, 4, 1, 99999, 1, 1, 0],
+            get: || None,
+        },
+        Object {
+            oid: &[1, 3, 6, 1, 4, 1, 99999, 1, 2, 0],
+            get: || Some(Value::Integer(-215)),
+        },
+    ];
+
+    /// 用编码器构造请求报文：GetNext/GetBulk 的两个整数字段分别为 `a` 和 `b`
+    fn request(pdu_type: u8, community: &[u8], a: i64, b: i64, oids: &[&[u32]]) -> Vec<u8, 256> {
+        let mut buf = [0u8; 256];
+        let mut out = Encoder::new(&mut buf);
+        for oid in oids.iter().rev() {
+            let end = out.len();
+            out.header(0x05, end).unwrap();
+            out.oid(oid).unwrap();
+            out.header(tag::SEQUENCE, end).unwrap();
+        }
+        out.header(tag::SEQUENCE, 0).unwrap();
+        out.integer(tag::INTEGER, b).unwrap();
+        out.integer(tag::INTEGER, a).unwrap();
+        out.integer(tag::INTEGER, 0x1234).unwrap();
+        out.header(pdu_type, 0).unwrap();
+        let end = out.len();
+        out.push(community).unwrap();
+        out.header(tag::OCTET_STRING, end).unwrap();
+        out.integer(tag::INTEGER, VERSION_2C).unwrap();
+        out.header(tag::SEQUENCE, 0).unwrap();
+        let start = out.start;
+        Vec::from_slice(&buf[start..]).unwrap()
+    }
+
+    /// 解码响应，返回错误状态和变量绑定（值为标签和内容）
+    fn response(data: &[u8]) -> (i64, std::vec::Vec<(Oid, u8, std::vec::Vec<u8>)>) {
+        let mut message = Reader {
+            data: Reader { data }.expect(tag::SEQUENCE).unwrap(),
+        };
+        assert_eq!(
+            decode_integer(message.expect(tag::INTEGER).unwrap()),
+            Ok(VERSION_2C)
+        );
+        assert_eq!(message.expect(tag::OCTET_STRING).unwrap(), b"public");
+        let mut pdu = Reader {
+            data: message.expect(tag::RESPONSE).unwrap(),
+        };
+        assert_eq!(
+            decode_integer(pdu.expect(tag::INTEGER).unwrap()),
+            Ok(0x1234)
+        );
+        let status = decode_integer(pdu.expect(tag::INTEGER).unwrap()).unwrap();
+        pdu.expect(tag::INTEGER).unwrap();
+        let mut list = Reader {
+            data: pdu.expect(tag::SEQUENCE).unwrap(),
+        };
+        let mut varbinds = std::vec::Vec::new();
+        while !list.is_empty() {
+            let mut varbind = Reader {
+                data: list.expect(tag::SEQUENCE).unwrap(),
+            };
+            let oid = decode_oid(varbind.expect(tag::OBJECT_ID).unwrap()).unwrap();
+            let (tag, content) = varbind.tlv().unwrap();
+            varbinds.push((oid, tag, content.to_vec()));
+        }
+        (status, varbinds)
+    }
+
+    #[test]
+    fn handles_snmpget_request() {
+        // snmpget -v2c -c public <host> sysDescr.0
+        let packet = [
+            0x30, 0x29, 0x02, 0x01, 0x01, 0x04, 0x06, b'p', b'u', b'b', b'l', b'i', b'c', 0xA0,
+            0x1C, 0x02, 0x04, 0x00, 0x00, 0x12, 0x34, 0x02, 0x01, 0x00, 0x02, 0x01, 0x00, 0x30,
+            0x0E, 0x30, 0x0C, 0x06, 0x08, 0x2B, 0x06, 0x01, 0x02, 0x01, 0x01, 0x01, 0x00, 0x05,
+            0x00,
+        ];
+        let mut buf = [0u8; 256];
+        let reply = handle(&packet, b"public", &MIB, &mut buf).unwrap();
+        let (status, varbinds) = response(reply);
+        assert_eq!(status, 0);
+        assert_eq!(varbinds.len(), 1);
+        assert_eq!(varbinds[0].0.as_slice(), MIB[0].oid);
+        assert_eq!(
+            (varbinds[0].1, varbinds[0].2.as_slice()),
+            (tag::OCTET_STRING, &b"board"[..])
+        );
+    }
+
+    #[test]
+    fn get_reports_missing_objects_and_instances() {
+        let packet = request(
+            tag::GET_REQUEST,
+            b"public",
+            0,
+            0,
+            &[MIB[2].oid, &[1, 3, 6, 1, 9]],
+        );
+        let mut buf = [0u8; 256];
+        let (_, varbinds) = response(handle(&packet, b"public", &MIB, &mut buf).unwrap());
+        assert_eq!(varbinds[0].1, tag::NO_SUCH_INSTANCE);
+        assert_eq!(varbinds[1].1, tag::NO_SUCH_OBJECT);
+    }
+
+    #[test]
+    fn get_next_skips_objects_without_value() {
+        let packet = request(
+            tag::GET_NEXT_REQUEST,
+            b"public",
+            0,
+            0,
+            &[MIB[1].oid, MIB[3].oid],
+        );
+        let mut buf = [0u8; 256];
+        let (_, varbinds) = response(handle(&packet, b"public", &MIB, &mut buf).unwrap());
+        assert_eq!(varbinds[0].0.as_slice(), MIB[3].oid);
+        assert_eq!(
+            (varbinds[0].1, varbinds[0].2.as_slice()),
+            (tag::INTEGER, &[0xFF, 0x29][..])
+        );
+        assert_eq!(varbinds[1].1, tag::END_OF_MIB_VIEW);
+    }
+
+    #[test]
+    fn get_bulk_walks_until_end_of_mib() {
+        let packet = request(tag::GET_BULK_REQUEST, b"public", 0, 10, &[&[1, 3, 6, 1]]);
+        let mut buf = [0u8; 512];
+        let (_, varbinds) = response(handle(&packet, b"public", &MIB, &mut buf).unwrap());
+        let oids: std::vec::Vec<&[u32]> = varbinds.iter().map(|v| v.0.as_slice()).collect();
+        assert_eq!(oids, [MIB[0].oid, MIB[1].oid, MIB[3].oid, MIB[3].oid]);
+        assert_eq!(varbinds[3].1, tag::END_OF_MIB_VIEW);
+    }
+
+    #[test]
+    fn rejects_wrong_community_and_set_request() {
+        let mut buf = [0u8; 256];
+        let packet = request(tag::GET_REQUEST, b"private", 0, 0, &[MIB[0].oid]);
+        assert_eq!(
+            handle(&packet, b"public", &MIB, &mut buf),
+            Err(SnmpError::BadCommunity)
+        );
+        let packet = request(0xA3, b"public", 0, 0, &[MIB[0].oid]);
+        assert_eq!(
+            handle(&packet, b"public", &MIB, &mut buf),
+            Err(SnmpError::Unsupported)
+        );
+        assert_eq!(
+            handle(&packet[..10], b"public", &MIB, &mut buf),
+            Err(SnmpError::Malformed)
+        );
+    }
+
+    #[test]
+    fn oversized_response_reports_too_big() {
+        let packet = request(tag::GET_REQUEST, b"public", 0, 0, &[MIB[0].oid; 4]);
+        let mut buf = [0u8; 64];
+        let (status, varbinds) = response(handle(&packet, b"public", &MIB, &mut buf).unwrap());
+        assert_eq!(status, ERROR_TOO_BIG);
+        assert!(varbinds.is_empty());
+    }
+
+    #[test]
+    fn encodes_shortest_integers() {
+        let mut buf = [0u8; 16];
+        for (value, expected) in [
+            (0, &[0x02, 0x01, 0x00][..]),
+            (127, &[0x02, 0x01, 0x7F]),
+            (128, &[0x02, 0x02, 0x00, 0x80]),
+            (-1, &[0x02, 0x01, 0xFF]),
+            (-129, &[0x02, 0x02, 0xFF, 0x7F]),
+        ] {
+            let mut out = Encoder::new(&mut buf);
+            out.integer(tag::INTEGER, value).unwrap();
+            let start = out.start;
+            assert_eq!(&buf[start..], expected);
+            assert_eq!(decode_integer(&expected[2..]), Ok(value));
+        }
+    }
+
+    #[test]
+    fn oid_round_trips() {
+        let mut buf = [0u8; 32];
+        let mut out = Encoder::new(&mut buf);
+        out.oid(&[1, 3, 6, 1, 4, 1, 99999, 1, 3, 0]).unwrap();
+        let start = out.start;
+        let content = Reader {
+            data: &buf[start..],
+        }
+        .expect(tag::OBJECT_ID)
+        .unwrap();
+        assert_eq!(content, [0x2B, 6, 1, 4, 1, 0x86, 0x8D, 0x1F, 1, 3, 0]);
+        assert_eq!(
+            decode_oid(content).unwrap().as_slice(),
+            [1, 3, 6, 1, 4, 1, 99999, 1, 3, 0]
+        );
+        // 最后一个子标识符没有结束
+        assert_eq!(decode_oid(&[0x2B, 0x86]), Err(SnmpError::Malformed));
+    }
+}
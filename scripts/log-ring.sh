@@ -0,0 +1,35 @@
+#!/bin/sh
+# 取回设备的日志环形缓冲区并解码
+#
+# 缓冲区中是 defmt 帧，需要用设备上运行的固件 ELF 解码，见 src/logbuf.rs。
+#
+# 用法：
+#   scripts/log-ring.sh [-e <elf>] <设备地址>      经 HTTP GET /logs/ring 取回
+#   scripts/log-ring.sh [-e <elf>] -f <ring.txt>   解码命令行 `log dump` 的十六进制输出
+#
+# 依赖 defmt-print（cargo install defmt-print）、curl 和 xxd
+
+set -eu
+
+elf=target/xtensa-esp32s3-none-elf/release/esp-app-4
+file=
+
+while getopts e:f: opt; do
+    case $opt in
+    e) elf=$OPTARG ;;
+    f) file=$OPTARG ;;
+    *) exit 2 ;;
+    esac
+done
+shift $((OPTIND - 1))
+
+if [ -z "$file" ] && [ $# -ne 1 ]; then
+    echo "usage: $0 [-e <elf>] <host> | [-e <elf>] -f <ring.txt>" >&2
+    exit 2
+fi
+
+if [ -n "$file" ]; then
+    tr -d '\r' < "$file" | xxd -r -p | defmt-print -e "$elf"
+else
+    curl -sSf "http://$1/logs/ring" | defmt-print -e "$elf"
+fi
@@ -0,0 +1,178 @@
+//! 访问控制
+//!
+//! 默认不做任何限制；在设置中保存了令牌或 PIN 后分别启用：
+//!
+//! - HTTP：修改状态的请求（`GET` 以外的方法）需要在 `Authorization` 请求头中带上令牌，
+//!   `Bearer <令牌>` 或 `Basic`（用户名任意，密码为令牌）均可，见 [http_authorized]。
+//!   只读的 `GET` 接口不需要认证
+//! - 命令行：只读的命令（查看状态和设置）不受限制，其余修改设置、输出或设备状态的命令
+//!   （重启、WiFi、规则、继电器、定时任务、同步等）需要先用 `unlock <PIN>` 解锁，
+//!   解锁在 [UNLOCK_TIMEOUT] 后或执行 `lock` 时失效，见 [is_unlocked]
+//! - MQTT：不检查 PIN 和令牌。代理发来的命令（重启、定时任务、PID、恒温设定值、DMX、服务）和
+//!   影子的期望状态（见 [crate::mqtt]、[crate::shadow]）都直接执行，谁能发送只由代理的认证和
+//!   主题权限决定。PIN 只适合交互输入，令牌放在消息里又会随保留消息留在代理上，
+//!   因此设置了 PIN 或令牌时应同时设置代理的用户名和密码，并限制命令主题的发布权限；
+//!   没有设置时连接代理会给出警告，见 [is_restricted]
+//!
+//! PIN 输错后等待 [FAILURE_DELAY] 才返回；连续输错 [FREE_ATTEMPTS] 次后锁定，
+//! 锁定期间拒绝所有解锁尝试，锁定时长从 [LOCKOUT_MIN] 起每次加倍，最长 [LOCKOUT_MAX]，
+//! 输对一次后清零。
+//!
+//! 限制：HTTP 服务没有 TLS，令牌在局域网中以明文传输，只能防止误操作和随手的访问。
+//! 按住 BOOT 按键恢复出厂设置不受 PIN 限制，忘记 PIN 时以此清除；
+//! 离线升级从 TF 卡在启动时进行，同样需要接触设备，也不受限制。
+
+use crate::settings;
+use core::cell::Cell;
+use critical_section::Mutex;
+use defmt::{info, warn};
+use embassy_time::{Duration, Instant, Timer};
+use proto::base64;
+
+/// 命令行解锁的有效时间
+pub const UNLOCK_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+/// PIN 输错后的等待时间
+const FAILURE_DELAY: Duration = Duration::from_secs(2);
+
+/// 不触发锁定的连续输错次数
+pub const FREE_ATTEMPTS: u32 = 3;
+
+/// 第一次锁定的时长
+pub const LOCKOUT_MIN: Duration = Duration::from_secs(30);
+
+/// 最长的锁定时长
+pub const LOCKOUT_MAX: Duration = Duration::from_secs(60 * 60);
+
+/// 命令行解锁的截止时间，None 表示未解锁
+static UNLOCKED_UNTIL: Mutex<Cell<Option<Instant>>> = Mutex::new(Cell::new(None));
+
+/// 连续输错 PIN 的次数
+static FAILURES: Mutex<Cell<u32>> = Mutex::new(Cell::new(0));
+
+/// 锁定的截止时间，None 表示未锁定
+static LOCKED_OUT_UNTIL: Mutex<Cell<Option<Instant>>> = Mutex::new(Cell::new(None));
+
+/// 解锁失败的原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnlockError {
+    /// PIN 错误
+    WrongPin,
+    /// 输错次数过多，仍需等待的秒数
+    LockedOut(u64),
+}
+
+/// PIN 是否合法：4 到 8 位数字
+pub fn is_valid_pin(pin: &str) -> bool {
+    (4..=8).contains(&pin.len()) && pin.bytes().all(|b| b.is_ascii_digit())
+}
+
+/// 令牌是否合法：8 个以上可打印 ASCII 字符，不含空格
+pub fn is_valid_token(token: &str) -> bool {
+    token.len() >= 8 && token.bytes().all(|b| b.is_ascii_graphic())
+}
+
+/// 命令行是否已解锁，没有设置 PIN 时总是解锁的
+pub fn is_unlocked() -> bool {
+    if settings::get().pin.is_empty() {
+        return true;
+    }
+    let until = critical_section::with(|cs| UNLOCKED_UNTIL.borrow(cs).get());
+    until.is_some_and(|until| Instant::now() < until)
+}
+
+/// 是否设置了 PIN 或令牌
+pub fn is_restricted() -> bool {
+    let s = settings::get();
+    !s.pin.is_empty() || !s.http_token.is_empty()
+}
+
+/// 用 PIN 解锁命令行
+///
+/// # 返回
+/// 没有设置 PIN 时总是成功；锁定期间不检查 PIN，直接返回 [UnlockError::LockedOut]
+pub async fn unlock(pin: &str) -> Result<(), UnlockError> {
+    let expected = settings::get().pin;
+    if expected.is_empty() {
+        return Ok(());
+    }
+    let now = Instant::now();
+    let locked_out = critical_section::with(|cs| LOCKED_OUT_UNTIL.borrow(cs).get());
+    if let Some(until) = locked_out.filter(|&until| now < until) {
+        return Err(UnlockError::LockedOut((until - now).as_secs() + 1));
+    }
+    if !equal(pin.as_bytes(), expected.as_bytes()) {
+        let failures = critical_section::with(|cs| {
+            let failures = FAILURES.borrow(cs).get().saturating_add(1);
+            FAILURES.borrow(cs).set(failures);
+            failures
+        });
+        warn!("Wrong PIN ({} consecutive failures)", failures);
+        if let Some(lockout) = lockout(failures) {
+            warn!("Console locked out for {} s", lockout.as_secs());
+            let until = Instant::now() + lockout;
+            critical_section::with(|cs| LOCKED_OUT_UNTIL.borrow(cs).set(Some(until)));
+        }
+        Timer::after(FAILURE_DELAY).await;
+        return Err(UnlockError::WrongPin);
+    }
+    critical_section::with(|cs| {
+        FAILURES.borrow(cs).set(0);
+        LOCKED_OUT_UNTIL.borrow(cs).set(None);
+    });
+    info!("Console unlocked");
+    let until = Instant::now() + UNLOCK_TIMEOUT;
+    critical_section::with(|cs| UNLOCKED_UNTIL.borrow(cs).set(Some(until)));
+    Ok(())
+}
+
+/// 连续输错若干次后的锁定时长
+///
+/// # 返回
+/// 不超过 [FREE_ATTEMPTS] 次时返回 None，之后从 [LOCKOUT_MIN] 起每次加倍，最长 [LOCKOUT_MAX]
+fn lockout(failures: u32) -> Option<Duration> {
+    let doublings = failures.checked_sub(FREE_ATTEMPTS + 1)?;
+    let secs = LOCKOUT_MIN.as_secs().saturating_mul(1 << doublings.min(16));
+    Some(Duration::from_secs(secs.min(LOCKOUT_MAX.as_secs())))
+}
+
+/// 锁定命令行
+pub fn lock() {
+    critical_section::with(|cs| UNLOCKED_UNTIL.borrow(cs).set(None));
+}
+
+/// HTTP 请求是否通过认证，没有设置令牌时总是通过
+///
+/// # 参数
+/// * `authorization` - `Authorization` 请求头的值
+pub fn http_authorized(authorization: Option<&str>) -> bool {
+    let token = settings::get().http_token;
+    if token.is_empty() {
+        return true;
+    }
+    let Some((scheme, credentials)) = authorization.and_then(|value| value.split_once(' ')) else {
+        return false;
+    };
+    let credentials = credentials.trim();
+    if scheme.eq_ignore_ascii_case("bearer") {
+        return equal(credentials.as_bytes(), token.as_bytes());
+    }
+    if !scheme.eq_ignore_ascii_case("basic") {
+        return false;
+    }
+    // 用户名:密码，令牌最长 32 字节，用户名再留出 32 字节
+    let mut decoded = [0u8; 64];
+    let Some(len) = base64::decode(credentials, &mut decoded) else {
+        return false;
+    };
+    let decoded = &decoded[..len];
+    match decoded.iter().position(|&b| b == b':') {
+        Some(colon) => equal(&decoded[colon + 1..], token.as_bytes()),
+        None => false,
+    }
+}
+
+/// 比较两段数据，耗时与内容无关
+fn equal(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
@@ -0,0 +1,818 @@
+use crate::assets;
+use crate::board::{self, Interface, Signal};
+use crate::bridge::{self, Port};
+use crate::can::{self, CanBus, Filter};
+use crate::capability::{self, Capability};
+use crate::console::{self, ConsolePins, ConsoleRx};
+use crate::dmx::{self, Dmx};
+use crate::i18n::{self, Msg};
+#[cfg(feature = "ui")]
+use crate::lcd::{self, Lcd};
+use crate::lin::{self, Lin};
+use crate::mqtt;
+use crate::multicore;
+#[cfg(feature = "ui")]
+use crate::multicore::Core;
+use crate::net::NetRunner;
+#[cfg(all(feature = "sd", feature = "ui"))]
+use crate::photo;
+use crate::pid::{self, PwmOutput};
+#[cfg(feature = "sd")]
+use crate::power::{self, Load};
+use crate::profile::{self, Profile};
+use crate::progress::Progress;
+use crate::rc::{self, Protocol, RcReceiver};
+use crate::registry::{self, Peripheral};
+use crate::rs485::{Rs485, Rs485Config};
+use crate::rules;
+use crate::secret;
+use crate::serial::{Serial, SerialConfig};
+use crate::spi::SharedSpiBus;
+use crate::system::RebootReason;
+#[cfg(feature = "ui")]
+use crate::{bench, clock, pairing, pomodoro, remote, render, snake, stopwatch, weather, wizard};
+use crate::{
+    bme280, button, buzzer, crash, espnow, forecast, gps, http, i2c, jitter, led, linktest, logbuf,
+    modbus, net, notifier, ota, peersync, relay, scheduler, settings, snmp, sntp, spi, storage,
+    syslog, system, theme, thermostat, thingspeak, wifi, xl9555,
+};
+#[cfg(feature = "sd")]
+use crate::{sdcard, sdlog};
+use boot::stages::{self, Availability, Skip, Stage, Stages};
+use defmt::{info, warn};
+use embassy_executor::Spawner;
+use embassy_net::Stack;
+use esp_hal::gpio::{Level, Output, OutputConfig};
+use esp_hal::interrupt::software::SoftwareInterruptControl;
+use esp_hal::peripherals::{LEDC, Peripherals, TWAI0};
+use esp_hal::timer::timg::TimerGroup;
+use esp_hal::twai::BaudRate;
+use esp_hal::uart::AnyUart;
+use esp_radio::esp_now::EspNow;
+
+/// 应用程序框架
+///
+/// 将系统启动拆分为以下几个明确的阶段：
+///
+/// 1. board    - 分配堆内存、启动 RTOS 调度器、APP_CPU 执行器和高优先级执行器、加载设置并选定开发板型号
+///    （见 [crate::board]）、板载 LED 和 BOOT 按键
+/// 2. console  - 按设置初始化控制台（USB Serial/JTAG 或 UART0）
+/// 3. buses    - 初始化 I2C 总线和共享 SPI 总线
+/// 4. expander - 初始化 XL9555 GPIO 扩展芯片
+/// 5. display  - 初始化 ST7789 LCD（依赖扩展芯片控制复位/背光）
+/// 6. sdcard   - 挂载 TF 卡，存在升级文件时执行离线固件更新
+/// 7. radio    - 初始化 WiFi 和网络协议栈，与 4-6 并发执行
+/// 8. services - 启动所有后台任务（渲染任务运行在 APP_CPU，LCD 的 DMA 传输和蜂鸣器运行在 PRO_CPU 的
+///    高优先级执行器，其余在 PRO_CPU）；
+///    首次启动时以设置向导代替渲染任务；其他应用模式（见 [crate::profile]）
+///    以各自的屏幕代替渲染任务，气象站模式还会启动 BME280 测量和天气预报下载任务；
+///    扩展排针上分配了引脚的外接接口（见 [crate::board::Interface]）也在这一阶段启动
+///
+/// 每个阶段返回一个类型化的句柄，后续阶段通过参数声明依赖，
+/// 从而在编译期保证初始化顺序。所有句柄最终汇总到 [App] 中。
+///
+/// 阶段之间的依赖关系如下，没有依赖关系的两条分支在同一个任务中并发执行，
+/// 启动时间取决于较长的一条，而不是所有延时之和：
+///
+/// ```text
+/// board → console → buses → expander → display → sdcard ─┐
+///                     └→ radio ──────────────────────────┴→ services
+/// ```
+///
+/// expander、display 和 sdcard 共用 I2C 和 SPI 总线，依次执行；LCD 的复位和上电依赖扩展芯片，
+/// radio 不使用这两条总线。buses 之后的顺序、并发和跳过规则由 boot crate 的 [stages::run]
+/// 编排，[BootStages] 提供各阶段的具体工作，启动顺序可以在主机上测试。
+///
+/// 各阶段的初始化结果登记到外设注册表（见 [crate::registry]）。并发的分支中，射频、背光和
+/// TF 卡按 [crate::power] 的规则错开上电，避免冲击电流叠加。
+///
+/// LCD 就绪后，之后的阶段（包括离线固件更新）在屏幕上显示启动进度（见 [crate::progress]）。
+///
+/// 关闭 `ui` feature 时跳过 display 阶段和屏幕任务，关闭 `sd` feature 时跳过 sdcard 阶段，
+/// 对应外设在注册表中标记为不存在。
+pub struct App {
+    pub board: Board,
+    /// 控制台接收端，由 services 阶段交给命令行任务
+    pub console: Option<ConsoleRx>,
+    pub expander: Option<Expander>,
+    #[cfg(feature = "ui")]
+    pub display: Option<Display>,
+    pub radio: Option<Radio>,
+    /// 外接接口用到的片上外设，由 services 阶段交给各接口
+    ports: Ports,
+}
+
+/// 扩展排针上外接接口用到的片上外设
+struct Ports {
+    twai: TWAI0<'static>,
+    ledc: LEDC<'static>,
+    uarts: Uarts,
+}
+
+/// UART1 和 UART2，按接口顺序分给用到 UART 的接口
+struct Uarts([Option<AnyUart<'static>>; 2]);
+
+impl Uarts {
+    /// 取一个空闲的 UART，都已分配时记录日志并返回 None
+    fn take(&mut self, interface: Interface) -> Option<AnyUart<'static>> {
+        let uart = self.0.iter_mut().find_map(Option::take);
+        if uart.is_none() {
+            warn!("No UART left for {}", interface.name());
+        }
+        uart
+    }
+}
+
+/// board 阶段产物
+pub struct Board {
+    /// 设置是否从 Flash 成功读取（false 表示首次启动）
+    pub settings_found: bool,
+    /// 是否通过长按 BOOT 按键请求进入配网模式
+    pub provisioning: bool,
+}
+
+/// buses 阶段产物：I2C 总线和共享 SPI 总线已就绪
+pub struct Buses {
+    /// 共享 SPI2 总线
+    #[cfg_attr(
+        not(any(feature = "sd", feature = "ui")),
+        expect(dead_code, reason = "no device shares the bus without `sd` or `ui`")
+    )]
+    pub spi: &'static SharedSpiBus,
+    /// LCD 片选，由 display 阶段取走
+    #[cfg_attr(
+        not(feature = "ui"),
+        expect(dead_code, reason = "kept driven high so the LCD stays off the bus")
+    )]
+    lcd_cs: Option<Output<'static>>,
+    /// TF 卡片选，由 sdcard 阶段取走
+    #[cfg_attr(
+        not(feature = "sd"),
+        expect(dead_code, reason = "kept driven high so the card stays off the bus")
+    )]
+    sd_cs: Option<Output<'static>>,
+}
+
+/// expander 阶段产物：XL9555 已完成配置
+pub struct Expander {
+    _private: (),
+}
+
+/// display 阶段产物：LCD 已完成复位和初始化
+#[cfg(feature = "ui")]
+pub struct Display {
+    pub lcd: Lcd,
+}
+
+/// sdcard 阶段产物：TF 卡已挂载
+pub struct SdCard {
+    _private: (),
+}
+
+/// radio 阶段产物：WiFi 已启动，网络协议栈已创建
+pub struct Radio {
+    /// 网络协议栈句柄
+    pub stack: Stack<'static>,
+    /// 协议栈后台运行器，由 services 阶段交给后台任务
+    runner: NetRunner,
+    /// ESP-NOW 接口，链路测试模式下由 services 阶段交给收发任务
+    esp_now: EspNow<'static>,
+}
+
+impl App {
+    /// 按阶段初始化所有子系统
+    ///
+    /// # 参数
+    /// * `peripherals` - esp-hal 初始化后得到的外设集合
+    pub async fn init(peripherals: Peripherals) -> App {
+        let board = init_board(
+            peripherals.TIMG0,
+            peripherals.FLASH,
+            peripherals.PSRAM,
+            peripherals.SW_INTERRUPT,
+            peripherals.CPU_CTRL,
+            peripherals.HMAC,
+            peripherals.LPWR,
+        );
+        led::led0_init(board::pin(Signal::Led)).await;
+        button::boot_button_init(peripherals.GPIO0).await;
+        registry::set_online(Peripheral::Led);
+        registry::set_online(Peripheral::BootButton);
+
+        let console = console::init(ConsolePins {
+            usb_device: peripherals.USB_DEVICE,
+            uart: peripherals.UART0,
+            tx: peripherals.GPIO43,
+            rx: peripherals.GPIO44,
+        });
+
+        let buses = init_buses(peripherals.I2C0, peripherals.SPI2, peripherals.DMA_CH0).await;
+
+        // radio 只依赖 board 阶段，与总线上的 expander → display → sdcard 并发执行，
+        // WiFi 启动与 LCD 复位、TF 卡挂载的等待时间互相重叠
+        let radio = init_radio(peripherals.WIFI);
+        let started = stages::run(&mut BootStages, buses, radio).await;
+
+        App {
+            board,
+            console,
+            expander: started.expander,
+            #[cfg(feature = "ui")]
+            display: started.display,
+            radio: started.radio,
+            ports: Ports {
+                twai: peripherals.TWAI0,
+                ledc: peripherals.LEDC,
+                uarts: Uarts([
+                    Some(peripherals.UART1.into()),
+                    Some(peripherals.UART2.into()),
+                ]),
+            },
+        }
+    }
+
+    /// services 阶段：根据已初始化的子系统启动后台任务
+    ///
+    /// # 参数
+    /// * `spawner` - 任务生成器
+    pub fn start(self, spawner: Spawner) {
+        multicore::register_pro(spawner);
+
+        // 所有阶段初始化完成，确认当前固件可用，避免引导程序回滚
+        ota::mark_running_image_valid();
+
+        spawner
+            .spawn(button::boot_button_task())
+            .expect("failed to spawn boot button task");
+        if let Some(rx) = self.console {
+            spawner
+                .spawn(console::console_task(rx))
+                .expect("failed to spawn console task");
+        }
+
+        spawner
+            .spawn(jitter::report_task())
+            .expect("failed to spawn jitter report task");
+        spawner
+            .spawn(scheduler::scheduler_task())
+            .expect("failed to spawn scheduler task");
+        rules::load();
+        spawner
+            .spawn(rules::rules_task())
+            .expect("failed to spawn rules task");
+        spawner
+            .spawn(relay::relay_task())
+            .expect("failed to spawn relay task");
+        spawner
+            .spawn(theme::ambient_task())
+            .expect("failed to spawn ambient light task");
+        #[cfg(feature = "sd")]
+        if board::current().has(Peripheral::SdCard) && capability::is_enabled(Capability::Sd) {
+            spawner
+                .spawn(sdcard::watch_task())
+                .expect("failed to spawn SD card watch task");
+            spawner
+                .spawn(sdlog::writer_task())
+                .expect("failed to spawn data log task");
+        }
+
+        let profile = profile::current();
+        info!("Application profile: {}", profile);
+        if !board::current().has(Peripheral::Bme280) {
+            registry::set_absent(Peripheral::Bme280);
+        } else if profile == Profile::WeatherStation {
+            spawner
+                .spawn(bme280::bme280_task())
+                .expect("failed to spawn bme280 task");
+        } else {
+            registry::set_disabled(Peripheral::Bme280);
+        }
+
+        // 首次启动或请求配网，且有屏幕和 WiFi 时运行设置向导，由向导负责扫描和连接
+        let wants_wizard = !self.board.settings_found || self.board.provisioning;
+        #[cfg(feature = "ui")]
+        let wizard_stack = match &self.radio {
+            Some(radio) if self.display.is_some() && self.expander.is_some() => Some(radio.stack),
+            _ => None,
+        }
+        .filter(|_| wants_wizard);
+        #[cfg(not(feature = "ui"))]
+        let wizard_stack: Option<Stack<'static>> = None;
+        if wants_wizard && wizard_stack.is_none() {
+            warn!("Setup wizard unavailable, configure Wi-Fi with the 'wifi' console command");
+        }
+
+        let stack = self.radio.as_ref().map(|radio| radio.stack);
+        if let Some(radio) = self.radio {
+            if wizard_stack.is_none() {
+                spawner
+                    .spawn(wifi::connection())
+                    .expect("failed to spawn wifi connection task");
+            }
+            spawner
+                .spawn(net::net_task(radio.runner))
+                .expect("failed to spawn network task");
+            spawner
+                .spawn(net::report_address(radio.stack))
+                .expect("failed to spawn address report task");
+            spawner
+                .spawn(http::server(radio.stack))
+                .expect("failed to spawn http server task");
+            spawner
+                .spawn(modbus::server(radio.stack))
+                .expect("failed to spawn modbus server task");
+            spawner
+                .spawn(snmp::server(radio.stack))
+                .expect("failed to spawn snmp agent task");
+            spawner
+                .spawn(sntp::sntp_task(radio.stack))
+                .expect("failed to spawn sntp task");
+            spawner
+                .spawn(notifier::notifier_task(radio.stack))
+                .expect("failed to spawn notifier task");
+            spawner
+                .spawn(ota::http_task(radio.stack))
+                .expect("failed to spawn firmware download task");
+            spawner
+                .spawn(syslog::syslog_task(radio.stack))
+                .expect("failed to spawn syslog task");
+            spawner
+                .spawn(peersync::peersync_task(radio.stack))
+                .expect("failed to spawn settings sync task");
+            spawner
+                .spawn(mqtt::mqtt_task(radio.stack))
+                .expect("failed to spawn mqtt task");
+            if profile == Profile::WeatherStation {
+                spawner
+                    .spawn(forecast::forecast_task(radio.stack))
+                    .expect("failed to spawn forecast task");
+                spawner
+                    .spawn(thingspeak::upload_task(radio.stack))
+                    .expect("failed to spawn weather upload task");
+            }
+            if profile == Profile::LinkTest {
+                spawner
+                    .spawn(espnow::espnow_task(radio.esp_now))
+                    .expect("failed to spawn esp-now task");
+                spawner
+                    .spawn(linktest::linktest_task(radio.stack))
+                    .expect("failed to spawn link test task");
+                #[cfg(feature = "ui")]
+                spawner
+                    .spawn(pairing::pairing_task())
+                    .expect("failed to spawn esp-now pairing task");
+            }
+            #[cfg(feature = "ui")]
+            if profile == Profile::Remote {
+                spawner
+                    .spawn(remote::server(radio.stack))
+                    .expect("failed to spawn remote display server task");
+            }
+        }
+
+        start_ports(spawner, self.ports, profile, stack);
+
+        if self.expander.is_some() {
+            // 按键检测在临界区内做阻塞的 I2C 读取，留在普通执行器上；蜂鸣器节奏对延迟敏感，
+            // 运行在高优先级执行器上
+            spawner
+                .spawn(xl9555::read_keys())
+                .expect("failed to spawn xl9555 task");
+            spawner
+                .spawn(xl9555::watchdog_task())
+                .expect("failed to spawn xl9555 watchdog task");
+            multicore::spawn_realtime(buzzer::buzzer_task()).expect("failed to spawn buzzer task");
+            let boot_sound = assets::boot_sound();
+            if !boot_sound.is_empty() {
+                buzzer::pattern(&boot_sound);
+            }
+            // 继电器接在 XL9555 上
+            spawner
+                .spawn(thermostat::thermostat_task())
+                .expect("failed to spawn thermostat task");
+        }
+
+        #[cfg(feature = "ui")]
+        if let Some(display) = self.display {
+            // LCD 的 DMA 传输在高优先级执行器上完成，见 [crate::lcd]
+            multicore::spawn_realtime(lcd::transfer_task())
+                .expect("failed to spawn LCD transfer task");
+            if let Some(stack) = wizard_stack {
+                // 向导需要与 WiFi 控制器交互，留在 PRO_CPU 上
+                spawner
+                    .spawn(wizard::wizard_task(display.lcd, stack))
+                    .expect("failed to spawn setup wizard task");
+            } else {
+                // 屏幕任务按应用模式选择，独占 LCD，运行在 APP_CPU 上
+                let lcd = display.lcd;
+                match profile {
+                    Profile::Status => multicore::spawn_on(Core::App, render::render_task(lcd)),
+                    Profile::WeatherStation => {
+                        multicore::spawn_on(Core::App, weather::weather_task(lcd))
+                    }
+                    Profile::Timer => multicore::spawn_on(Core::App, pomodoro::pomodoro_task(lcd)),
+                    Profile::Stopwatch => {
+                        multicore::spawn_on(Core::App, stopwatch::stopwatch_task(lcd))
+                    }
+                    Profile::Game => multicore::spawn_on(Core::App, snake::game_task(lcd)),
+                    #[cfg(feature = "sd")]
+                    Profile::PhotoFrame => multicore::spawn_on(Core::App, photo::photo_task(lcd)),
+                    // 没有 TF 卡支持时相框没有照片来源，显示状态屏幕
+                    #[cfg(not(feature = "sd"))]
+                    Profile::PhotoFrame => multicore::spawn_on(Core::App, render::render_task(lcd)),
+                    Profile::Clock => multicore::spawn_on(Core::App, clock::clock_task(lcd)),
+                    Profile::LinkTest => {
+                        multicore::spawn_on(Core::App, linktest::display_task(lcd))
+                    }
+                    Profile::Bench => multicore::spawn_on(Core::App, bench::bench_task(lcd)),
+                    Profile::Thermostat => {
+                        multicore::spawn_on(Core::App, thermostat::screen_task(lcd))
+                    }
+                    Profile::Pid => multicore::spawn_on(Core::App, pid::screen_task(lcd)),
+                    Profile::Relay => multicore::spawn_on(Core::App, relay::screen_task(lcd)),
+                    Profile::Remote => multicore::spawn_on(Core::App, remote::display_task(lcd)),
+                }
+                .expect("failed to spawn display task");
+            }
+        }
+
+        info!("Application started");
+    }
+}
+
+/// buses 之后各阶段的具体工作，顺序和跳过规则见 [stages::run]
+struct BootStages;
+
+impl Stages for BootStages {
+    type Buses = Buses;
+    type Expander = Expander;
+    #[cfg(feature = "ui")]
+    type Display = Display;
+    // 没有屏幕的构建不会执行 display 阶段
+    #[cfg(not(feature = "ui"))]
+    type Display = core::convert::Infallible;
+    type SdCard = SdCard;
+    type Progress<'a> = Progress<'a>;
+
+    fn availability(&self, stage: Stage) -> Availability {
+        let board = board::current();
+        let (fitted, capability) = match stage {
+            Stage::Display => (
+                cfg!(feature = "ui") && board.has(Peripheral::Lcd),
+                Capability::Display,
+            ),
+            Stage::SdCard => (
+                cfg!(feature = "sd") && board.has(Peripheral::SdCard),
+                Capability::Sd,
+            ),
+            Stage::Radio => (true, Capability::Wifi),
+        };
+        if !fitted {
+            Availability::Absent
+        } else if capability::is_enabled(capability) {
+            Availability::Enabled
+        } else {
+            Availability::Disabled
+        }
+    }
+
+    fn skip(&mut self, stage: Stage, reason: Skip) {
+        let peripheral = match stage {
+            Stage::Display => Peripheral::Lcd,
+            Stage::SdCard => Peripheral::SdCard,
+            Stage::Radio => Peripheral::Wifi,
+        };
+        match reason {
+            Skip::Absent => registry::set_absent(peripheral),
+            Skip::Disabled => {
+                info!("{} disabled, skipping initialization", peripheral);
+                registry::set_disabled(peripheral);
+            }
+            // 只有 display 依赖其他阶段
+            Skip::DependencyFailed => {
+                warn!("XL9555 unavailable, LCD cannot be initialized");
+                registry::set_failed(peripheral, "LCD reset");
+            }
+        }
+    }
+
+    async fn expander(&mut self, buses: &Buses) -> Option<Expander> {
+        init_expander(buses).await
+    }
+
+    #[cfg(feature = "ui")]
+    async fn display(&mut self, buses: &mut Buses, expander: &Expander) -> Option<Display> {
+        init_display(buses, expander).await
+    }
+
+    #[cfg(not(feature = "ui"))]
+    async fn display(&mut self, _buses: &mut Buses, _expander: &Expander) -> Option<Self::Display> {
+        None
+    }
+
+    fn progress<'a>(&self, display: Option<&'a mut Self::Display>) -> Progress<'a> {
+        // 渲染任务还没有启动，由报告器直接绘制到 LCD
+        #[cfg(feature = "ui")]
+        {
+            let mut boot = Progress::new("boot");
+            if let Some(display) = display {
+                boot.attach(&mut display.lcd);
+            }
+            boot
+        }
+        #[cfg(not(feature = "ui"))]
+        {
+            let _ = display;
+            Progress::new("boot")
+        }
+    }
+
+    #[cfg(feature = "sd")]
+    async fn sdcard(&mut self, buses: &mut Buses, progress: &mut Self::Progress<'_>) -> Option<SdCard> {
+        progress.update(40, i18n::lcd(Msg::BootSdCard));
+        init_sdcard(buses, progress).await
+    }
+
+    #[cfg(not(feature = "sd"))]
+    async fn sdcard(&mut self, _buses: &mut Buses, _progress: &mut Self::Progress<'_>) -> Option<SdCard> {
+        None
+    }
+
+    fn radio_pending(&mut self, progress: &mut Progress<'_>) {
+        // 总线上的外设已就绪，WiFi 还在启动时继续显示进度
+        progress.update(70, i18n::lcd(Msg::BootWifi));
+    }
+}
+
+/// board 阶段：分配堆内存（内部 SRAM 64KB，加上模组的 PSRAM），启动 RTOS 调度器、
+/// APP_CPU 执行器和高优先级执行器，并加载持久化设置
+fn init_board(
+    timg0: esp_hal::peripherals::TIMG0<'static>,
+    flash: esp_hal::peripherals::FLASH<'static>,
+    psram: esp_hal::peripherals::PSRAM<'static>,
+    sw_interrupt: esp_hal::peripherals::SW_INTERRUPT<'static>,
+    cpu_ctrl: esp_hal::peripherals::CPU_CTRL<'static>,
+    hmac: esp_hal::peripherals::HMAC<'static>,
+    lpwr: esp_hal::peripherals::LPWR<'static>,
+) -> Board {
+    esp_alloc::heap_allocator!( size : 64 * 1024 );
+    // 内部堆在前，放不下的大块分配（例如整帧帧缓冲区，见 framebuffer 模块）落在 PSRAM 中
+    esp_alloc::psram_allocator!(psram, esp_hal::psram);
+    logbuf::init();
+
+    let time_g0 = TimerGroup::new(timg0);
+    esp_rtos::start(time_g0.timer0);
+    info!("Embassy initialized!");
+
+    let sw_int = SoftwareInterruptControl::new(sw_interrupt);
+    multicore::start_app_core(cpu_ctrl, sw_int.software_interrupt0, sw_int.software_interrupt1);
+    multicore::start_realtime(sw_int.software_interrupt2);
+    let provisioning = system::log_reset_reason() == Some(RebootReason::Provisioning);
+    system::init(lpwr);
+
+    // 加载持久化设置，决定需要初始化哪些子系统
+    storage::init(flash);
+    secret::init(hmac);
+    if crash::persist_pending() {
+        crash::log_last();
+    }
+    let settings_found = settings::load();
+    board::init();
+    capability::log_summary();
+
+    Board { settings_found, provisioning }
+}
+
+/// buses 阶段：初始化 I2C 总线和共享 SPI 总线
+///
+/// 引脚取自当前开发板的引脚表（见 [crate::board]）。板上有的 LCD 和 TF 卡的片选
+/// 均以高电平（无效）初始化，避免初始化其中一个设备时另一个设备误响应
+async fn init_buses(
+    i2c: esp_hal::peripherals::I2C0<'static>,
+    spi: esp_hal::peripherals::SPI2<'static>,
+    dma_channel: esp_hal::peripherals::DMA_CH0<'static>,
+) -> Buses {
+    i2c::init(i2c, board::pin(Signal::Sda), board::pin(Signal::Scl)).await;
+
+    let spi = spi::init_with_dma(
+        spi,
+        board::pin(Signal::Sck),
+        board::pin(Signal::Mosi),
+        board::pin(Signal::Miso),
+        dma_channel,
+    );
+    let board = board::current();
+    let chip_select = |signal| {
+        let cs = board::pin(signal);
+        Output::new(cs, Level::High, OutputConfig::default())
+    };
+    let lcd_cs = board.has(Peripheral::Lcd).then(|| chip_select(Signal::LcdCs));
+    let sd_cs = board.has(Peripheral::SdCard).then(|| chip_select(Signal::SdCs));
+
+    Buses { spi, lcd_cs, sd_cs }
+}
+
+/// expander 阶段：初始化 XL9555 GPIO 扩展芯片
+///
+/// 开发板上没有扩展芯片或初始化失败时返回 None，依赖扩展芯片的 LCD 和按键将不会启动
+async fn init_expander(_buses: &Buses) -> Option<Expander> {
+    if !board::current().has(Peripheral::Expander) {
+        registry::set_absent(Peripheral::Expander);
+        return None;
+    }
+    match xl9555::init().await {
+        Ok(()) => Some(Expander { _private: () }),
+        Err(err) => {
+            warn!("Failed to initialize XL9555 GPIO expander: {}", err);
+            None
+        }
+    }
+}
+
+/// display 阶段：初始化 ATK-MD0240 LCD 模块
+///
+/// # 参数
+/// * `buses` - 总线句柄，LCD 片选从中取走
+/// * `expander` - XL9555 句柄，用于复位和背光控制
+#[cfg(feature = "ui")]
+async fn init_display(buses: &mut Buses, _expander: &Expander) -> Option<Display> {
+    let cs = buses.lcd_cs.take()?;
+    let spi = spi::device(buses.spi, cs);
+    let dc = Output::new(board::pin(Signal::LcdDc), Level::High, OutputConfig::default());
+
+    // 复位、初始化并清屏后才打开背光，见 [Lcd::init]
+    let result = Lcd::init(spi, dc).await;
+    registry::report(Peripheral::Lcd, &result);
+    match result {
+        Ok(lcd) => Some(Display { lcd }),
+        Err(err) => {
+            warn!("Failed to initialize LCD: {}", err);
+            None
+        }
+    }
+}
+
+/// sdcard 阶段：挂载 TF 卡，检查离线升级文件并加载资源包
+///
+/// 未插卡或挂载失败时返回 None，之后插入的卡由 [sdcard::watch_task] 挂载。
+/// 发现有效的升级文件时会写入固件并重启，不会返回。
+///
+/// # 参数
+/// * `buses` - 总线句柄，TF 卡片选从中取走
+/// * `progress` - 启动进度，固件更新的进度也显示在这里
+#[cfg(feature = "sd")]
+async fn init_sdcard(buses: &mut Buses, progress: &mut Progress<'_>) -> Option<SdCard> {
+    let cs = buses.sd_cs.take()?;
+    power::power_up(Load::SdCard).await;
+    if let Err(err) = sdcard::init(buses.spi, cs).await {
+        warn!("Failed to initialize SD card: {}", defmt::Debug2Format(&err));
+        return None;
+    }
+
+    if let Err(err) = ota::apply_from_sd(progress).await {
+        warn!("Offline firmware update failed: {}", err);
+    }
+    assets::load_from_sd().await;
+
+    Some(SdCard { _private: () })
+}
+
+/// 启动扩展排针上分配了引脚的外接接口，创建失败的接口记录日志后跳过
+///
+/// PWM 输出只在 PID 模式下启动；串口透传打开时（见 [bridge::Setup]）用它指定的接口，
+/// RS485 接口不用于透传时作为 Modbus RTU 从站
+fn start_ports(
+    spawner: Spawner,
+    mut ports: Ports,
+    profile: Profile,
+    stack: Option<Stack<'static>>,
+) {
+    let board = board::current();
+    let bridge = match (bridge::Setup::current(), stack) {
+        (Some(setup), _) if board.port(setup.interface).is_none() => {
+            warn!("Bridge port {} not wired", setup.interface.name());
+            None
+        }
+        (Some(setup), Some(stack)) => Some((setup, stack)),
+        (Some(_), None) => {
+            warn!("Serial bridge needs Wi-Fi, not started");
+            None
+        }
+        (None, _) => None,
+    };
+    if let Some(wiring) = board.port(Interface::Can) {
+        let baudrate = can::baudrate(wiring.param).unwrap_or(BaudRate::B500K);
+        let filter = Filter::from_bytes(&settings::get().can_filter);
+        match CanBus::new(ports.twai, wiring.pin(0), wiring.pin(1), baudrate, filter) {
+            Ok(bus) => spawner
+                .spawn(can::can_task(bus))
+                .expect("failed to spawn CAN task"),
+            Err(err) => warn!("Failed to start CAN: {}", err),
+        }
+    }
+    if let Some(wiring) = board.port(Interface::Dmx)
+        && let Some(uart) = ports.uarts.take(Interface::Dmx)
+    {
+        let de = Output::new(wiring.pin(1), Level::High, OutputConfig::default());
+        match Dmx::new(uart, wiring.pin(0), de) {
+            Ok(dmx) => spawner
+                .spawn(dmx::dmx_task(dmx))
+                .expect("failed to spawn DMX task"),
+            Err(err) => warn!("Failed to start DMX: {}", err),
+        }
+    }
+    if let Some(wiring) = board.port(Interface::Gps)
+        && let Some(uart) = ports.uarts.take(Interface::Gps)
+    {
+        let config = SerialConfig {
+            baudrate: wiring.param,
+            ..SerialConfig::DEFAULT
+        };
+        match Serial::new("gps", uart, wiring.pin(0), wiring.pin(1), config) {
+            Ok(serial) => spawner
+                .spawn(gps::gps_task(serial))
+                .expect("failed to spawn GPS task"),
+            Err(err) => warn!("Failed to start GPS: {}", err),
+        }
+    }
+    if let Some(wiring) = board.port(Interface::Rs485)
+        && let Some(uart) = ports.uarts.take(Interface::Rs485)
+    {
+        let de = Output::new(wiring.pin(2), Level::Low, OutputConfig::default());
+        let config = Rs485Config {
+            baudrate: wiring.param,
+            ..Rs485Config::DEFAULT
+        };
+        match Rs485::new(uart, wiring.pin(0), wiring.pin(1), de, config) {
+            Ok(bus) => match bridge {
+                Some((setup, stack)) if setup.interface == Interface::Rs485 => spawner
+                    .spawn(bridge::bridge_task(stack, Port::Rs485(bus), setup.mode))
+                    .expect("failed to spawn serial bridge task"),
+                _ => spawner
+                    .spawn(modbus::rtu_task(bus))
+                    .expect("failed to spawn Modbus RTU task"),
+            },
+            Err(err) => warn!("Failed to start RS485: {}", err),
+        }
+    }
+    if let Some((setup, stack)) = bridge.filter(|(setup, _)| setup.interface == Interface::Serial)
+        && let Some(wiring) = board.port(Interface::Serial)
+        && let Some(uart) = ports.uarts.take(Interface::Serial)
+    {
+        let config = SerialConfig {
+            baudrate: wiring.param,
+            ..SerialConfig::DEFAULT
+        };
+        match Serial::new("bridge", uart, wiring.pin(0), wiring.pin(1), config) {
+            Ok(serial) => spawner
+                .spawn(bridge::bridge_task(stack, Port::Serial(serial), setup.mode))
+                .expect("failed to spawn serial bridge task"),
+            Err(err) => warn!("Failed to start serial port: {}", err),
+        }
+    }
+    if let Some(wiring) = board.port(Interface::Rc)
+        && let Some(uart) = ports.uarts.take(Interface::Rc)
+    {
+        let protocol = Protocol::from_baudrate(wiring.param).unwrap_or(Protocol::Sbus);
+        match RcReceiver::new(uart, wiring.pin(0), protocol) {
+            Ok(receiver) => spawner
+                .spawn(rc::rc_task(receiver))
+                .expect("failed to spawn RC receiver task"),
+            Err(err) => warn!("Failed to start RC receiver: {}", err),
+        }
+    }
+    if let Some(wiring) = board.port(Interface::Lin)
+        && let Some(uart) = ports.uarts.take(Interface::Lin)
+    {
+        let checksum = lin::saved_checksum();
+        match Lin::new(uart, wiring.pin(0), wiring.pin(1), wiring.param, checksum) {
+            Ok(lin) => spawner
+                .spawn(lin::lin_task(lin, lin::load_schedule()))
+                .expect("failed to spawn LIN task"),
+            Err(err) => warn!("Failed to start LIN: {}", err),
+        }
+    }
+    if profile == Profile::Pid {
+        match board.port(Interface::Pwm) {
+            Some(wiring) => match PwmOutput::new(ports.ledc, wiring.pin(0)) {
+                Ok(output) => spawner
+                    .spawn(pid::pid_task(output))
+                    .expect("failed to spawn PID task"),
+                Err(err) => warn!("Failed to start PWM output: {}", err),
+            },
+            None => warn!("PWM output not wired, PID loop not started"),
+        }
+    }
+}
+
+/// radio 阶段：初始化 WiFi 和网络协议栈
+async fn init_radio(wifi_peripheral: esp_hal::peripherals::WIFI<'static>) -> Radio {
+    let (device, esp_now) = wifi::init(wifi_peripheral).await;
+    registry::set_online(Peripheral::Wifi);
+    let (stack, runner) = net::init(device);
+    Radio {
+        stack,
+        runner,
+        esp_now,
+    }
+}
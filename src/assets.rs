@@ -0,0 +1,620 @@
+//! TF 卡资源包
+//!
+//! 屏幕的字体、状态栏图标、开机提示音和界面译文可以放在 TF 卡根目录的资源包
+//! [SD_ASSETS_FILE] 中，启动时加载，替换编译进固件的内置资源，不需要重新编译。
+//! 资源包不存在、文件头或索引损坏时整体使用内置资源；单项资源校验失败或内容不合格时
+//! 跳过该项并记录警告，该项使用内置资源。命令行 `assets` 查看加载结果。
+//!
+//! # 格式
+//!
+//! 整数均为小端序：
+//!
+//! ```text
+//! 文件头（16 字节）
+//!   0  魔数 "EPAK"
+//!   4  格式版本 u16，目前为 1
+//!   6  资源数 u16，最多 32
+//!   8  索引的 CRC32 u32
+//!   12 保留，填 0
+//! 索引（紧接文件头，每项 32 字节）
+//!   0  资源名称，ASCII，不足 20 字节补 0
+//!   20 数据在文件中的偏移 u32
+//!   24 数据长度 u32
+//!   28 数据的 CRC32 u32
+//! 数据（任意顺序）
+//! ```
+//!
+//! CRC32 与命令行 `crc32` 的算法相同（IEEE 802.3），只用于发现损坏，不能防止篡改。
+//!
+//! # 资源
+//!
+//! | 名称 | 内容 |
+//! |------|------|
+//! | `font.main` | 屏幕的大字体：10x20 的 ASCII 字体，位图布局与内置的 `FONT_10X20` 相同（每行 16 个字符，从空格开始，1 位/像素，高位在左），2400 字节 |
+//! | `font.cjk` | 含全角字形的 10x20 字体，见下文；加载后所有屏幕的大字体文本都使用它，替代 `font.main` |
+//! | `icon.<名称>` | 16x16 单色图标，16 个 u16，每个一行，高位在左；名称为 `sd`、`signal0` 至 `signal4` |
+//! | `sound.boot` | 开机提示音：交替的鸣响和静音时长（毫秒），u16 数组，最多 16 步；内置为空（不鸣响） |
+//! | `text.<语言代码>` | 译文，UTF-8，每行 `英文原文<Tab>译文`，`#` 开头的行为注释；语言代码为 `en` 或 `zh` |
+//!
+//! 译文按英文原文匹配，固件升级后原文没有改变的条目继续有效；原文不存在的条目被忽略。
+//! 包含换行的文本（例如命令行帮助）不能替换。
+//!
+//! # 全角字体
+//!
+//! 内置字体只有 ASCII 字形，中文界面需要资源包提供 `font.cjk`，只需包含界面译文用到的字：
+//!
+//! ```text
+//! 0  字形数 N u16，最多 [MAX_CJK_GLYPHS]
+//! 2  保留，填 0
+//! 4  N 个 Unicode 码位 u32，递增，不含 ASCII
+//! 4 + 4N 位图：布局与 font.main 相同，前 96 格是从空格开始的 ASCII 字形，
+//!    之后第 i 个全角字形（20x20）分成左右两格，依次占第 96 + 2i 和 97 + 2i 格
+//! ```
+//!
+//! 单色字体的每个字符宽度相同，全角字形在文本中以两个私用区字符表示左右两半（见 [cjk_text]），
+//! 因此按字符数排版的屏幕不需要修改。小字体（`FONT_6X10`）没有全角字形，
+//! 用 [crate::i18n::lcd_small] 取得的文本始终为英文。
+//!
+//! 加载的资源常驻堆内存，总大小不超过 [MAX_TOTAL_LEN]；更换资源包后需要重启。
+
+use crate::buzzer;
+use crate::i18n::{Language, Msg};
+#[cfg(feature = "sd")]
+use crate::sdcard::{self, SdFile};
+#[cfg(feature = "sd")]
+use crate::storage;
+#[cfg(feature = "sd")]
+use alloc::boxed::Box;
+use alloc::string::String;
+#[cfg(feature = "sd")]
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cell::{Cell, RefCell};
+use critical_section::Mutex;
+#[cfg(feature = "sd")]
+use defmt::{info, warn};
+#[cfg(feature = "sd")]
+use embedded_graphics::image::ImageRaw;
+use embedded_graphics::mono_font::MonoFont;
+#[cfg(any(feature = "sd", feature = "ui"))]
+use embedded_graphics::mono_font::ascii::FONT_10X20;
+#[cfg(feature = "sd")]
+use embedded_graphics::mono_font::mapping::GlyphMapping;
+#[cfg(feature = "sd")]
+use embedded_sdmmc::Mode;
+#[cfg(any(feature = "sd", feature = "ui"))]
+use ui::icon;
+use ui::icon::Icon;
+
+/// TF 卡上的资源包文件名
+#[cfg(feature = "sd")]
+pub const SD_ASSETS_FILE: &str = "ASSETS.PAK";
+
+/// 文件头魔数
+#[cfg(feature = "sd")]
+const MAGIC: [u8; 4] = *b"EPAK";
+
+/// 支持的格式版本
+#[cfg(feature = "sd")]
+const VERSION: u16 = 1;
+
+/// 文件头长度
+#[cfg(feature = "sd")]
+const HEADER_LEN: usize = 16;
+
+/// 索引项长度
+#[cfg(feature = "sd")]
+const ENTRY_LEN: usize = 32;
+
+/// 资源名称最大长度
+#[cfg(feature = "sd")]
+const NAME_LEN: usize = 20;
+
+/// 资源数上限
+#[cfg(feature = "sd")]
+const MAX_ENTRIES: usize = 32;
+
+/// 加载的资源数据总大小上限（字节），堆内存只有 64KB
+#[cfg(feature = "sd")]
+pub const MAX_TOTAL_LEN: usize = 16 * 1024;
+
+/// 字体位图宽度（像素）：每行 16 个 10 像素宽的字符
+#[cfg(feature = "sd")]
+const FONT_IMAGE_WIDTH: u32 = 16 * 10;
+
+/// 字体位图中一行字符（20 像素高）的大小
+#[cfg(feature = "sd")]
+const FONT_ROW_LEN: usize = FONT_IMAGE_WIDTH as usize / 8 * 20;
+
+/// 字体位图大小：95 个 ASCII 字符排成 6 行
+#[cfg(feature = "sd")]
+const FONT_LEN: usize = FONT_ROW_LEN * 6;
+
+/// 字体位图中 ASCII 字形的格数（95 个字符，最后一格不用）
+#[cfg(feature = "sd")]
+const ASCII_CELLS: usize = 96;
+
+/// 全角字形数上限，左右两半共占用私用区的 2 倍码位
+#[cfg(feature = "sd")]
+const MAX_CJK_GLYPHS: usize = 1024;
+
+/// 全角字形左右两半在文本中使用的第一个私用区码位
+const CJK_HALF_BASE: u32 = 0xE000;
+
+/// 可以替换的图标名称
+#[cfg(any(feature = "sd", feature = "ui"))]
+const ICON_NAMES: [&str; 6] = ["sd", "signal0", "signal1", "signal2", "signal3", "signal4"];
+
+/// 资源包错误，资源包整体不可用
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+#[cfg(feature = "sd")]
+pub enum AssetError {
+    /// 读取资源包失败
+    Read,
+    /// 文件头不正确或资源数超出上限
+    BadHeader,
+    /// 不支持的格式版本
+    Version(u16),
+    /// 索引校验失败
+    IndexCrc,
+}
+
+/// 单项资源被跳过的原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+#[cfg(feature = "sd")]
+enum Rejected {
+    /// 读取数据失败
+    Read,
+    /// 数据超出文件范围
+    OutOfBounds,
+    /// 超出 [MAX_TOTAL_LEN]
+    TooLarge,
+    /// 数据校验失败
+    Crc,
+    /// 长度或内容不符合该类资源的格式
+    Invalid,
+    /// 未知的资源名称
+    Unknown,
+}
+
+/// 一条译文
+struct Text {
+    language: Language,
+    /// 英文原文
+    source: &'static str,
+    text: &'static str,
+}
+
+/// 加载结果，用于命令行显示
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Summary {
+    /// 是否替换了字体
+    pub font: bool,
+    /// 全角字体的字形数，0 表示没有全角字体
+    pub cjk_glyphs: usize,
+    /// 替换的图标数量
+    pub icons: usize,
+    /// 开机提示音的步数，0 表示不鸣响
+    pub boot_sound: usize,
+    /// 译文条数
+    pub texts: usize,
+}
+
+static FONT: Mutex<Cell<Option<&'static MonoFont<'static>>>> = Mutex::new(Cell::new(None));
+
+/// 全角字体和其中的全角字符（递增）
+type CjkFont = (&'static MonoFont<'static>, &'static [char]);
+
+static CJK_FONT: Mutex<Cell<Option<CjkFont>>> = Mutex::new(Cell::new(None));
+
+/// [cjk_text] 转换过的文本，按原文的地址查找
+static CJK_TEXTS: Mutex<RefCell<Vec<(usize, &'static str)>>> = Mutex::new(RefCell::new(Vec::new()));
+
+static ICONS: Mutex<RefCell<Vec<(&'static str, Icon)>>> = Mutex::new(RefCell::new(Vec::new()));
+
+static BOOT_SOUND: Mutex<RefCell<heapless::Vec<u16, { buzzer::MAX_PATTERN_LEN }>>> =
+    Mutex::new(RefCell::new(heapless::Vec::new()));
+
+/// 按语言和原文排序
+static TEXTS: Mutex<RefCell<Vec<Text>>> = Mutex::new(RefCell::new(Vec::new()));
+
+/// 屏幕的 10x20 字体：有全角字体时为全角字体，否则为替换的字体或内置的 `FONT_10X20`
+///
+/// 显示 [crate::i18n::lcd] 文本的屏幕必须使用此字体
+#[cfg(feature = "ui")]
+pub fn font() -> &'static MonoFont<'static> {
+    critical_section::with(|cs| match CJK_FONT.borrow(cs).get() {
+        Some((font, _)) => Some(font),
+        None => FONT.borrow(cs).get(),
+    })
+    .unwrap_or(&FONT_10X20)
+}
+
+/// 是否加载了全角字体
+pub fn has_cjk_font() -> bool {
+    critical_section::with(|cs| CJK_FONT.borrow(cs).get().is_some())
+}
+
+/// 把文本转换为用 [font] 显示的形式
+///
+/// 全角字体中有的字符换成表示左右两半的两个私用区字符，没有的换成 `?`；
+/// ASCII 字符不变。没有全角字体或文本只有 ASCII 时原样返回。
+/// 转换结果常驻内存，同一文本只转换一次。
+///
+/// # 参数
+/// * `text` - 界面文本
+pub fn cjk_text(text: &'static str) -> &'static str {
+    if text.is_ascii() {
+        return text;
+    }
+    let Some((_, chars)) = critical_section::with(|cs| CJK_FONT.borrow(cs).get()) else {
+        return text;
+    };
+    let key = text.as_ptr() as usize;
+    let cached = critical_section::with(|cs| {
+        let texts = CJK_TEXTS.borrow_ref(cs);
+        texts
+            .iter()
+            .find(|(source, _)| *source == key)
+            .map(|&(_, encoded)| encoded)
+    });
+    if let Some(encoded) = cached {
+        return encoded;
+    }
+
+    let mut encoded = String::with_capacity(text.len() * 2);
+    for c in text.chars() {
+        if c.is_ascii() {
+            encoded.push(c);
+            continue;
+        }
+        let half = chars
+            .binary_search(&c)
+            .ok()
+            .and_then(|i| char::from_u32(CJK_HALF_BASE + i as u32 * 2));
+        match half {
+            Some(left) => {
+                encoded.push(left);
+                encoded.push(char::from_u32(left as u32 + 1).unwrap_or('?'));
+            }
+            None => encoded.push('?'),
+        }
+    }
+    let encoded: &'static str = encoded.leak();
+    critical_section::with(|cs| CJK_TEXTS.borrow_ref_mut(cs).push((key, encoded)));
+    encoded
+}
+
+/// 按名称取得图标，没有替换时返回内置图标
+///
+/// # 参数
+/// * `name` - 图标名称，见模块文档
+/// * `builtin` - 内置图标
+#[cfg(feature = "ui")]
+pub fn icon(name: &str, builtin: Icon) -> Icon {
+    critical_section::with(|cs| {
+        ICONS
+            .borrow_ref(cs)
+            .iter()
+            .find(|(icon_name, _)| *icon_name == name)
+            .map_or(builtin, |&(_, icon)| icon)
+    })
+}
+
+/// 信号强度图标
+///
+/// # 参数
+/// * `level` - 等级，0 到 [icon::LEVELS]
+#[cfg(feature = "ui")]
+pub fn signal_icon(level: u8) -> Icon {
+    let level = level.min(icon::LEVELS);
+    icon(ICON_NAMES[1 + level as usize], icon::signal(level))
+}
+
+/// 开机提示音，内置为空
+pub fn boot_sound() -> heapless::Vec<u16, { buzzer::MAX_PATTERN_LEN }> {
+    critical_section::with(|cs| BOOT_SOUND.borrow_ref(cs).clone())
+}
+
+/// 资源包中的译文，没有时为 None
+///
+/// # 参数
+/// * `language` - 语言
+/// * `msg` - 文本标识
+pub fn text(language: Language, msg: Msg) -> Option<&'static str> {
+    let source = msg.in_language(Language::English);
+    critical_section::with(|cs| {
+        let texts = TEXTS.borrow_ref(cs);
+        let key = (language.to_u8(), source);
+        texts
+            .binary_search_by(|text| (text.language.to_u8(), text.source).cmp(&key))
+            .ok()
+            .map(|i| texts[i].text)
+    })
+}
+
+/// 加载结果
+pub fn summary() -> Summary {
+    critical_section::with(|cs| Summary {
+        font: FONT.borrow(cs).get().is_some(),
+        cjk_glyphs: CJK_FONT
+            .borrow(cs)
+            .get()
+            .map_or(0, |(_, chars)| chars.len()),
+        icons: ICONS.borrow_ref(cs).len(),
+        boot_sound: BOOT_SOUND.borrow_ref(cs).len(),
+        texts: TEXTS.borrow_ref(cs).len(),
+    })
+}
+
+/// 从 TF 卡加载资源包，在启动时调用一次
+///
+/// 卡未挂载或没有资源包时什么也不做，结果写入日志
+#[cfg(feature = "sd")]
+pub async fn load_from_sd() {
+    if !sdcard::is_mounted() {
+        return;
+    }
+    let result = sdcard::with_root_dir(|dir| {
+        if !sdcard::file_exists(dir, SD_ASSETS_FILE)? {
+            return Ok(None);
+        }
+        let mut file = dir.open_file_in_dir(SD_ASSETS_FILE, Mode::ReadOnly)?;
+        let loaded = load(&mut file);
+        file.close()?;
+        Ok(Some(loaded))
+    })
+    .await;
+    match result {
+        Ok(None) => info!("No asset bundle on SD card, using built-in assets"),
+        Ok(Some(Ok(count))) => info!("Loaded {} assets from {}", count, SD_ASSETS_FILE),
+        Ok(Some(Err(err))) => warn!("Asset bundle rejected, using built-in assets: {}", err),
+        Err(err) => warn!(
+            "SD card error while loading assets: {}",
+            defmt::Debug2Format(&err)
+        ),
+    }
+}
+
+/// 校验文件头和索引，逐项加载资源
+///
+/// # 返回
+/// 成功加载的资源数
+#[cfg(feature = "sd")]
+fn load(file: &mut SdFile<'_>) -> Result<usize, AssetError> {
+    let file_len = file.length() as usize;
+    let mut header = [0u8; HEADER_LEN];
+    read_at(file, 0, &mut header).map_err(|_| AssetError::Read)?;
+    if header[..4] != MAGIC {
+        return Err(AssetError::BadHeader);
+    }
+    let version = u16::from_le_bytes([header[4], header[5]]);
+    if version != VERSION {
+        return Err(AssetError::Version(version));
+    }
+    let count = u16::from_le_bytes([header[6], header[7]]) as usize;
+    if count > MAX_ENTRIES {
+        return Err(AssetError::BadHeader);
+    }
+    let index_crc = u32::from_le_bytes([header[8], header[9], header[10], header[11]]);
+
+    let mut index = vec![0u8; count * ENTRY_LEN];
+    read_at(file, HEADER_LEN as u32, &mut index).map_err(|_| AssetError::Read)?;
+    if storage::crc32(&index) != index_crc {
+        return Err(AssetError::IndexCrc);
+    }
+
+    let mut total = 0;
+    let mut loaded = 0;
+    for entry in index.chunks_exact(ENTRY_LEN) {
+        let Some(name) = entry_name(entry) else {
+            warn!("Asset with an invalid name skipped");
+            continue;
+        };
+        let word = |at: usize| {
+            u32::from_le_bytes([entry[at], entry[at + 1], entry[at + 2], entry[at + 3]])
+        };
+        let (offset, len, crc) = (word(20), word(24) as usize, word(28));
+        let result = if (offset as usize)
+            .checked_add(len)
+            .is_none_or(|end| end > file_len)
+        {
+            Err(Rejected::OutOfBounds)
+        } else if len > MAX_TOTAL_LEN - total {
+            Err(Rejected::TooLarge)
+        } else {
+            load_entry(file, name, offset, len, crc)
+        };
+        match result {
+            Ok(()) => {
+                total += len;
+                loaded += 1;
+            }
+            Err(reason) => warn!("Asset {} skipped: {}", name, reason),
+        }
+    }
+    Ok(loaded)
+}
+
+/// 索引项中的资源名称，不是可打印 ASCII 时为 None
+#[cfg(feature = "sd")]
+fn entry_name(entry: &[u8]) -> Option<&str> {
+    let raw = &entry[..NAME_LEN];
+    let len = raw.iter().position(|&b| b == 0).unwrap_or(NAME_LEN);
+    let name = core::str::from_utf8(&raw[..len]).ok()?;
+    (!name.is_empty() && name.bytes().all(|b| b.is_ascii_graphic())).then_some(name)
+}
+
+/// 从指定偏移读满缓冲区
+#[cfg(feature = "sd")]
+fn read_at(file: &mut SdFile<'_>, offset: u32, buf: &mut [u8]) -> Result<(), Rejected> {
+    file.seek_from_start(offset).map_err(|_| Rejected::Read)?;
+    let mut total = 0;
+    while total < buf.len() {
+        match file.read(&mut buf[total..]) {
+            Ok(0) | Err(_) => return Err(Rejected::Read),
+            Ok(len) => total += len,
+        }
+    }
+    Ok(())
+}
+
+/// 读取、校验并安装一项资源
+#[cfg(feature = "sd")]
+fn load_entry(
+    file: &mut SdFile<'_>,
+    name: &str,
+    offset: u32,
+    len: usize,
+    crc: u32,
+) -> Result<(), Rejected> {
+    let mut data = vec![0u8; len];
+    read_at(file, offset, &mut data)?;
+    if storage::crc32(&data) != crc {
+        return Err(Rejected::Crc);
+    }
+    match name.split_once('.') {
+        Some(("font", "main")) => install_font(data),
+        Some(("font", "cjk")) => install_cjk_font(data),
+        Some(("icon", icon_name)) => install_icon(icon_name, &data),
+        Some(("sound", "boot")) => install_boot_sound(&data),
+        Some(("text", code)) => install_texts(code, data),
+        _ => Err(Rejected::Unknown),
+    }
+}
+
+/// 替换状态屏幕的字体，位图常驻内存
+#[cfg(feature = "sd")]
+fn install_font(data: Vec<u8>) -> Result<(), Rejected> {
+    if data.len() != FONT_LEN {
+        return Err(Rejected::Invalid);
+    }
+    let image: &'static [u8] = Box::leak(data.into_boxed_slice());
+    let font = Box::leak(Box::new(MonoFont {
+        image: ImageRaw::new(image, FONT_IMAGE_WIDTH),
+        ..FONT_10X20
+    }));
+    critical_section::with(|cs| FONT.borrow(cs).set(Some(font)));
+    Ok(())
+}
+
+/// 全角字体的字形映射：ASCII 字符之后是各全角字形的左右两半，其他字符显示为 `?`
+#[cfg(feature = "sd")]
+struct CjkMapping {
+    /// 全角字形数
+    glyphs: usize,
+}
+
+#[cfg(feature = "sd")]
+impl GlyphMapping for CjkMapping {
+    fn index(&self, c: char) -> usize {
+        let replacement = '?' as usize - ' ' as usize;
+        match c {
+            ' '..='~' => c as usize - ' ' as usize,
+            _ => (c as u32)
+                .checked_sub(CJK_HALF_BASE)
+                .map(|half| half as usize)
+                .filter(|&half| half < self.glyphs * 2)
+                .map_or(replacement, |half| ASCII_CELLS + half),
+        }
+    }
+}
+
+/// 加载全角字体，码位表和位图常驻内存
+#[cfg(feature = "sd")]
+fn install_cjk_font(data: Vec<u8>) -> Result<(), Rejected> {
+    let count = match data.get(..2) {
+        Some(&[low, high]) => u16::from_le_bytes([low, high]) as usize,
+        _ => return Err(Rejected::Invalid),
+    };
+    let cells = ASCII_CELLS + count * 2;
+    let image_start = 4 + count * 4;
+    let image_len = cells.div_ceil(16) * FONT_ROW_LEN;
+    if count == 0 || count > MAX_CJK_GLYPHS || data.len() != image_start + image_len {
+        return Err(Rejected::Invalid);
+    }
+    let mut chars = Vec::with_capacity(count);
+    for bytes in data[4..image_start].chunks_exact(4) {
+        let code = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        let c = char::from_u32(code).filter(|c| !c.is_ascii());
+        match c {
+            Some(c) if chars.last().is_none_or(|&last| last < c) => chars.push(c),
+            _ => return Err(Rejected::Invalid),
+        }
+    }
+    let chars: &'static [char] = chars.leak();
+    let data: &'static [u8] = Box::leak(data.into_boxed_slice());
+    let mapping = Box::leak(Box::new(CjkMapping { glyphs: count }));
+    let font = Box::leak(Box::new(MonoFont {
+        image: ImageRaw::new(&data[image_start..], FONT_IMAGE_WIDTH),
+        glyph_mapping: mapping,
+        ..FONT_10X20
+    }));
+    critical_section::with(|cs| CJK_FONT.borrow(cs).set(Some((font, chars))));
+    Ok(())
+}
+
+/// 替换一个图标
+#[cfg(feature = "sd")]
+fn install_icon(name: &str, data: &[u8]) -> Result<(), Rejected> {
+    let Some(&name) = ICON_NAMES.iter().find(|&&known| known == name) else {
+        return Err(Rejected::Unknown);
+    };
+    if data.len() != icon::SIZE as usize * 2 {
+        return Err(Rejected::Invalid);
+    }
+    let mut rows = [0u16; icon::SIZE as usize];
+    for (row, bytes) in rows.iter_mut().zip(data.chunks_exact(2)) {
+        *row = u16::from_le_bytes([bytes[0], bytes[1]]);
+    }
+    critical_section::with(|cs| {
+        let mut icons = ICONS.borrow_ref_mut(cs);
+        icons.retain(|(icon_name, _)| *icon_name != name);
+        icons.push((name, Icon::from_rows(rows)));
+    });
+    Ok(())
+}
+
+/// 替换开机提示音
+#[cfg(feature = "sd")]
+fn install_boot_sound(data: &[u8]) -> Result<(), Rejected> {
+    let len = data.len();
+    if len == 0 || !len.is_multiple_of(2) || len > buzzer::MAX_PATTERN_LEN * 2 {
+        return Err(Rejected::Invalid);
+    }
+    let steps = data
+        .chunks_exact(2)
+        .map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]))
+        .collect();
+    critical_section::with(|cs| *BOOT_SOUND.borrow_ref_mut(cs) = steps);
+    Ok(())
+}
+
+/// 加入一种语言的译文，文本常驻内存
+#[cfg(feature = "sd")]
+fn install_texts(code: &str, data: Vec<u8>) -> Result<(), Rejected> {
+    let Some(language) = Language::ALL.into_iter().find(|l| l.code() == code) else {
+        return Err(Rejected::Unknown);
+    };
+    let text: &'static str = String::from_utf8(data)
+        .map_err(|_| Rejected::Invalid)?
+        .leak();
+    let entries = text
+        .lines()
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| line.split_once('\t'))
+        .map(|(source, text)| Text {
+            language,
+            source,
+            text,
+        });
+    critical_section::with(|cs| {
+        let mut texts = TEXTS.borrow_ref_mut(cs);
+        texts.retain(|text| text.language != language);
+        texts.extend(entries);
+        texts.sort_unstable_by(|a, b| {
+            (a.language.to_u8(), a.source).cmp(&(b.language.to_u8(), b.source))
+        });
+    });
+    Ok(())
+}
@@ -0,0 +1,416 @@
+//! 显示性能测试
+//!
+//! [Profile::Bench](crate::profile::Profile::Bench) 模式下，屏幕任务测量 LCD 的绘制速度，
+//! 每 [REPEAT_PERIOD] 重复一次，结果以对比表打印到日志，命令行 `bench` 查看最近一次结果。
+//! 修改 [crate::st7789] 前后分别运行，对比两次的表格即可。
+//!
+//! 测量项目：
+//! - fill：整屏单色填充（[Lcd::fill_screen]），千像素/秒
+//! - text：`FONT_10X20` 带背景色的文字，字符/秒
+//! - pixel：随机位置的单个像素，千像素/秒
+//! - flush：以 [FLUSH_ROWS] 行为一块刷新（[Lcd::flush]）一整帧所需的时间（微秒）
+//! - scene：以同样的行数分条渲染（见 [Lcd::render_strips]）一整帧合成画面所需的时间（微秒），
+//!   包括绘制和传输，与 flush 之差就是绘制画面的开销
+//!
+//! 表中每种绘制路径占一行：
+//! - spi-dma：直接在 LCD 上绘制，经共享 SPI 总线 DMA 缓冲区的阻塞传输（见 [crate::spi]）
+//! - fb-psram：在 PSRAM 中的整帧帧缓冲区（见 [crate::framebuffer]）上绘制，每项绘制后用
+//!   [Lcd::flush_framebuffer] 发送改变过的区域；flush 为整帧重新发送。
+//!   模组没有 PSRAM 时没有这一行
+//!
+//! fill、flush 和帧缓冲区的发送经异步驱动在传输之间让出执行器，
+//! 测得的时间包括其他任务运行的时间。
+
+use crate::assets;
+use crate::framebuffer;
+use crate::i18n::{self, Msg};
+use crate::lcd::Lcd;
+use crate::st7789::{self, St7789};
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cell::RefCell;
+use core::fmt::Write;
+use critical_section::Mutex;
+use defmt::{info, warn};
+use embassy_time::{Duration, Instant, Timer};
+use embedded_graphics::mono_font::ascii::FONT_10X20;
+use embedded_graphics::mono_font::{MonoTextStyle, MonoTextStyleBuilder};
+use embedded_graphics::pixelcolor::Rgb565;
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::{Circle, PrimitiveStyle, Rectangle, RoundedRectangle};
+use embedded_graphics::text::Text;
+use esp_hal::spi::Error as SpiError;
+use ui::framebuffer::Framebuffer;
+
+/// 两次测量之间的间隔
+const REPEAT_PERIOD: Duration = Duration::from_secs(30);
+
+/// 整屏填充的次数
+const FILL_ROUNDS: u32 = 20;
+
+/// 文字测试使用的一行文本
+const TEXT_LINE: &str = "The quick brown fox jumps over";
+
+/// 绘制文字的行数
+const TEXT_ROUNDS: u32 = 40;
+
+/// 随机像素的数量
+const PIXEL_COUNT: usize = 4000;
+
+/// blit 整帧时每块的行数
+const FLUSH_ROWS: u16 = 16;
+
+/// blit 整帧的次数
+const FLUSH_ROUNDS: u32 = 5;
+
+/// 分条渲染合成画面的次数
+const SCENE_ROUNDS: u32 = 5;
+
+/// 直接在 LCD 上绘制
+const PATH_DMA: &str = "spi-dma";
+
+/// 在帧缓冲区上绘制
+const PATH_FRAMEBUFFER: &str = "fb-psram";
+
+/// 绘制路径的数量
+const PATHS: usize = 2;
+
+/// 最近一次的测量结果
+static LATEST: Mutex<RefCell<heapless::Vec<Row, PATHS>>> =
+    Mutex::new(RefCell::new(heapless::Vec::new()));
+
+/// 一种绘制路径的测量结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct Row {
+    /// 绘制路径名称
+    pub path: &'static str,
+    /// 整屏填充速度（千像素/秒）
+    pub fill_kpps: u32,
+    /// 文字绘制速度（字符/秒）
+    pub text_cps: u32,
+    /// 随机像素绘制速度（千像素/秒）
+    pub pixel_kpps: u32,
+    /// blit 一整帧的时间（微秒）
+    pub flush_us: u32,
+    /// 分条渲染一整帧合成画面的时间（微秒）
+    pub scene_us: u32,
+}
+
+/// 结果表的一行：指标名、取值方法和单位
+type Line = (&'static str, fn(&Row) -> u32, &'static str);
+
+/// 最近一次的测量结果，每种绘制路径一项，还没有测量过时为空
+pub fn latest() -> heapless::Vec<Row, PATHS> {
+    critical_section::with(|cs| LATEST.borrow_ref(cs).clone())
+}
+
+/// 格式化对比表，每种绘制路径一行
+///
+/// 还没有测量结果时返回空字符串
+pub fn format_report() -> String {
+    let mut text = String::new();
+    let rows = latest();
+    if rows.is_empty() {
+        return text;
+    }
+    writeln!(
+        text,
+        "{:<8} {:>10} {:>9} {:>11} {:>9} {:>9}",
+        "path", "fill_kpx/s", "text_ch/s", "pixel_kpx/s", "flush_us", "scene_us"
+    )
+    .ok();
+    for row in &rows {
+        writeln!(
+            text,
+            "{:<8} {:>10} {:>9} {:>11} {:>9} {:>9}",
+            row.path, row.fill_kpps, row.text_cps, row.pixel_kpps, row.flush_us, row.scene_us
+        )
+        .ok();
+    }
+    text
+}
+
+/// 按耗时计算每秒的数量
+fn per_second(count: u64, elapsed: Duration) -> u32 {
+    (count * 1_000_000 / elapsed.as_micros().max(1)) as u32
+}
+
+/// 整屏填充的颜色，红蓝交替
+fn fill_color(round: u32) -> Rgb565 {
+    if round.is_multiple_of(2) {
+        Rgb565::RED
+    } else {
+        Rgb565::BLUE
+    }
+}
+
+/// 整屏填充
+async fn measure_fill(lcd: &mut Lcd) -> Result<u32, SpiError> {
+    let start = Instant::now();
+    for round in 0..FILL_ROUNDS {
+        lcd.fill_screen(fill_color(round)).await?;
+    }
+    Ok(fill_kpps(start.elapsed()))
+}
+
+/// 按耗时计算整屏填充的速度（千像素/秒）
+fn fill_kpps(elapsed: Duration) -> u32 {
+    let pixels = st7789::WIDTH as u64 * st7789::HEIGHT as u64 * FILL_ROUNDS as u64;
+    per_second(pixels, elapsed) / 1000
+}
+
+/// 绘制第 `round` 行文字，写满屏幕后从顶部重新开始
+fn draw_text_line<D>(target: &mut D, round: u32) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = Rgb565>,
+{
+    let style: MonoTextStyle<'_, Rgb565> = MonoTextStyleBuilder::new()
+        .font(&FONT_10X20)
+        .text_color(Rgb565::WHITE)
+        .background_color(Rgb565::BLACK)
+        .build();
+    let lines = (st7789::HEIGHT / 20) as u32;
+    let y = 15 + (round % lines) as i32 * 20;
+    Text::new(TEXT_LINE, Point::new(10, y), style).draw(target)?;
+    Ok(())
+}
+
+/// 逐行绘制文字
+fn measure_text(lcd: &mut St7789) -> Result<u32, SpiError> {
+    let start = Instant::now();
+    for round in 0..TEXT_ROUNDS {
+        draw_text_line(lcd, round)?;
+    }
+    Ok(text_cps(start.elapsed()))
+}
+
+/// 按耗时计算文字的绘制速度（字符/秒）
+fn text_cps(elapsed: Duration) -> u32 {
+    per_second(TEXT_LINE.len() as u64 * TEXT_ROUNDS as u64, elapsed)
+}
+
+/// 在伪随机位置绘制随机颜色的单个像素
+fn measure_pixels(lcd: &mut St7789) -> Result<u32, SpiError> {
+    let start = Instant::now();
+    lcd.draw_iter(random_pixels())?;
+    Ok(per_second(PIXEL_COUNT as u64, start.elapsed()) / 1000)
+}
+
+/// [PIXEL_COUNT] 个伪随机位置和颜色的像素，每次调用的序列相同
+fn random_pixels() -> impl Iterator<Item = Pixel<Rgb565>> {
+    let mut seed: u32 = 0x2545_F491;
+    core::iter::repeat_with(move || {
+        // xorshift32
+        seed ^= seed << 13;
+        seed ^= seed >> 17;
+        seed ^= seed << 5;
+        seed
+    })
+    .take(PIXEL_COUNT)
+    .map(|r| {
+        let x = r % st7789::WIDTH as u32;
+        let y = (r >> 16) % st7789::HEIGHT as u32;
+        let color = Rgb565::new((r >> 3) as u8 & 0x1F, (r >> 8) as u8 & 0x3F, r as u8 & 0x1F);
+        Pixel(Point::new(x as i32, y as i32), color)
+    })
+}
+
+/// 分块 blit 整帧
+///
+/// # 参数
+/// * `strip` - [FLUSH_ROWS] 行的像素数据
+async fn measure_flush(lcd: &mut Lcd, strip: &[u8]) -> Result<u32, SpiError> {
+    let start = Instant::now();
+    for _ in 0..FLUSH_ROUNDS {
+        for y in (0..st7789::HEIGHT).step_by(FLUSH_ROWS as usize) {
+            let rows = FLUSH_ROWS.min(st7789::HEIGHT - y);
+            lcd.flush(0, y, st7789::WIDTH, rows, strip).await?;
+        }
+    }
+    Ok((start.elapsed().as_micros() / FLUSH_ROUNDS as u64) as u32)
+}
+
+/// 分条渲染合成画面
+///
+/// # 参数
+/// * `buffer` - [FLUSH_ROWS] 行的条带缓冲区
+fn measure_scene(lcd: &mut Lcd, buffer: &mut [u8]) -> Result<u32, SpiError> {
+    let start = Instant::now();
+    for _ in 0..SCENE_ROUNDS {
+        lcd.render_strips(buffer, |strip| {
+            // 只绘制落在当前条带内的背景行
+            let rows = strip.area();
+            scene(strip, rows)
+        })?;
+    }
+    Ok((start.elapsed().as_micros() / SCENE_ROUNDS as u64) as u32)
+}
+
+/// 测试用的合成画面：渐变背景上叠加圆角面板、圆环和文字
+///
+/// # 参数
+/// * `rows` - 需要绘制背景的行
+fn scene<D>(target: &mut D, rows: Rectangle) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = Rgb565>,
+{
+    for y in rows.rows() {
+        let band = Rectangle::new(Point::new(0, y), Size::new(st7789::WIDTH as u32, 1));
+        target.fill_solid(&band, Rgb565::new(0, (y / 4) as u8, 31 - (y / 8) as u8))?;
+    }
+    let panel = Rectangle::new(Point::new(30, 40), Size::new(200, 120));
+    RoundedRectangle::with_equal_corners(panel, Size::new(12, 12))
+        .into_styled(PrimitiveStyle::with_fill(Rgb565::CSS_DARK_SLATE_GRAY))
+        .draw(target)?;
+    Circle::new(Point::new(230, 130), 70)
+        .into_styled(PrimitiveStyle::with_stroke(Rgb565::YELLOW, 4))
+        .draw(target)?;
+    let style = MonoTextStyle::new(&FONT_10X20, Rgb565::WHITE);
+    Text::new(TEXT_LINE, Point::new(10, 200), style).draw(target)?;
+    Ok(())
+}
+
+/// 生成 blit 用的横向渐变像素块（RGB565，大端）
+fn flush_strip() -> Vec<u8> {
+    let width = st7789::WIDTH as usize;
+    (0..width * FLUSH_ROWS as usize)
+        .flat_map(|i| ((i % width * 0xFFFF / width) as u16).to_be_bytes())
+        .collect()
+}
+
+/// 依次测量所有项目
+///
+/// # 参数
+/// * `strip` - blit 用的像素块，同样大小的缓冲区用于分条渲染
+async fn measure(lcd: &mut Lcd, path: &'static str, strip: &[u8]) -> Result<Row, SpiError> {
+    let mut buffer = vec![0; strip.len()];
+    Ok(Row {
+        path,
+        fill_kpps: measure_fill(lcd).await?,
+        text_cps: measure_text(lcd)?,
+        pixel_kpps: measure_pixels(lcd)?,
+        flush_us: measure_flush(lcd, strip).await?,
+        scene_us: measure_scene(lcd, &mut buffer)?,
+    })
+}
+
+/// 在帧缓冲区上依次测量所有项目，每项绘制后发送改变过的区域
+///
+/// 绘制到帧缓冲区不会失败，错误都来自发送
+async fn measure_framebuffer(lcd: &mut Lcd, fb: &mut Framebuffer<'_>) -> Result<Row, SpiError> {
+    let start = Instant::now();
+    for round in 0..FILL_ROUNDS {
+        fb.clear(fill_color(round)).ok();
+        lcd.flush_framebuffer(fb).await?;
+    }
+    let fill_kpps = fill_kpps(start.elapsed());
+
+    let start = Instant::now();
+    for round in 0..TEXT_ROUNDS {
+        draw_text_line(fb, round).ok();
+        lcd.flush_framebuffer(fb).await?;
+    }
+    let text_cps = text_cps(start.elapsed());
+
+    let start = Instant::now();
+    fb.draw_iter(random_pixels()).ok();
+    lcd.flush_framebuffer(fb).await?;
+    let pixel_kpps = per_second(PIXEL_COUNT as u64, start.elapsed()) / 1000;
+
+    let start = Instant::now();
+    for _ in 0..FLUSH_ROUNDS {
+        fb.invalidate();
+        lcd.flush_framebuffer(fb).await?;
+    }
+    let flush_us = (start.elapsed().as_micros() / FLUSH_ROUNDS as u64) as u32;
+
+    let start = Instant::now();
+    for _ in 0..SCENE_ROUNDS {
+        let all = fb.bounding_box();
+        scene(fb, all).ok();
+        lcd.flush_framebuffer(fb).await?;
+    }
+    let scene_us = (start.elapsed().as_micros() / SCENE_ROUNDS as u64) as u32;
+
+    Ok(Row {
+        path: PATH_FRAMEBUFFER,
+        fill_kpps,
+        text_cps,
+        pixel_kpps,
+        flush_us,
+        scene_us,
+    })
+}
+
+/// 在屏幕上显示测量结果，每种绘制路径一列
+fn show(lcd: &mut St7789, rows: &[Row]) -> Result<(), SpiError> {
+    let style = MonoTextStyle::new(assets::font(), Rgb565::WHITE);
+    lcd.fill_screen(Rgb565::BLACK)?;
+    Text::new(i18n::lcd(Msg::BenchTitle), Point::new(10, 30), style).draw(lcd)?;
+
+    let mut text: heapless::String<32> = heapless::String::new();
+    write!(text, "{:<6}", "").ok();
+    for row in rows {
+        write!(text, "{:>9}", row.path).ok();
+    }
+    Text::new(&text, Point::new(10, 66), style).draw(lcd)?;
+
+    let lines: [Line; 5] = [
+        ("fill", |row| row.fill_kpps, "kpx/s"),
+        ("text", |row| row.text_cps, "ch/s"),
+        ("pixel", |row| row.pixel_kpps, "kpx/s"),
+        ("flush", |row| row.flush_us, "us"),
+        ("scene", |row| row.scene_us, "us"),
+    ];
+    for (i, (name, value, unit)) in lines.into_iter().enumerate() {
+        text.clear();
+        write!(text, "{name:<6}").ok();
+        for row in rows {
+            write!(text, "{:>9}", value(row)).ok();
+        }
+        write!(text, " {unit}").ok();
+        Text::new(&text, Point::new(10, 100 + i as i32 * 26), style).draw(lcd)?;
+    }
+    Ok(())
+}
+
+/// 显示性能测试屏幕任务
+///
+/// 测量期间 LCD 显示测试图案，测量完成后显示结果。帧缓冲区在任务开始时分配一次，
+/// 分配失败时只测量直接绘制
+///
+/// # 参数
+/// * `lcd` - 已完成初始化的 LCD
+#[embassy_executor::task]
+pub async fn bench_task(mut lcd: Lcd) {
+    let strip = flush_strip();
+    let mut fb = framebuffer::alloc();
+    loop {
+        let mut rows: heapless::Vec<Row, PATHS> = heapless::Vec::new();
+        match measure(&mut lcd, PATH_DMA, &strip).await {
+            Ok(row) => {
+                rows.push(row).ok();
+            }
+            Err(err) => warn!("Display benchmark failed: {}", err),
+        }
+        if let Some(fb) = fb.as_mut() {
+            match measure_framebuffer(&mut lcd, fb).await {
+                Ok(row) => {
+                    rows.push(row).ok();
+                }
+                Err(err) => warn!("Framebuffer benchmark failed: {}", err),
+            }
+        }
+        if !rows.is_empty() {
+            critical_section::with(|cs| *LATEST.borrow_ref_mut(cs) = rows.clone());
+            for line in format_report().lines() {
+                info!("{}", line);
+            }
+            if let Err(err) = show(&mut lcd, &rows) {
+                warn!("Failed to show benchmark results: {}", err);
+            }
+        }
+        Timer::after(REPEAT_PERIOD).await;
+    }
+}
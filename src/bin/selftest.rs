@@ -0,0 +1,526 @@
+//! # 装配自检程序
+//!
+//! 用于生产线上检查组装好的开发板：依次检查 I2C 器件应答、LCD 显示、KEY0-KEY3、
+//! WiFi 扫描和 TF 卡挂载，结果以文本行输出到控制台，供测试工装解析。
+//!
+//! 自检程序独立于主固件，不读取 Flash 中的设置；LCD 和 XL9555 的寄存器逻辑来自
+//! [drivers]，总线直接在这里创建。
+//!
+//! ## 使用方法
+//!
+//! ```text
+//! cargo run --release --bin selftest
+//! ```
+//!
+//! 按键检查开始后，需在 [KEY_TIMEOUT] 内依次按下 KEY0-KEY3（LCD 上同时有提示）；
+//! LCD 只能检查 SPI 传输是否成功，彩条画面需由操作员目视确认。
+//!
+//! ## 报告格式
+//!
+//! 每项检查输出一行 `SELFTEST <项目> <PASS|FAIL|SKIP> <详情>`，
+//! 开始和结束各有一行，结束行给出总体结果：
+//!
+//! ```text
+//! SELFTEST BEGIN esp-app-4 0.1.0
+//! SELFTEST i2c.xl9555 PASS addr=0x20
+//! SELFTEST i2c.bme280 SKIP not-fitted
+//! SELFTEST lcd PASS pattern=bars
+//! SELFTEST keys WAIT timeout=15s
+//! SELFTEST keys FAIL missing=KEY2
+//! SELFTEST wifi PASS aps=6 best=-48dBm
+//! SELFTEST sd PASS size=15193MB
+//! SELFTEST END FAIL passed=6 failed=1
+//! ```
+//!
+//! 同一输出中还夹杂着 defmt 日志帧，工装只需匹配以 `SELFTEST ` 开头的行。
+
+#![no_std]
+#![no_main]
+#![deny(
+    clippy::mem_forget,
+    reason = "mem::forget is generally not safe to do with esp_hal types, especially those \
+    holding buffers for the duration of a data transfer."
+)]
+
+use core::cell::RefCell;
+use core::fmt::{self, Write};
+use core::panic::PanicInfo;
+use core::sync::atomic::{AtomicBool, Ordering};
+use critical_section::RestoreState;
+use defmt::warn;
+use drivers::st7789::{self, St7789};
+use drivers::xl9555::{self, io_bits};
+use embassy_executor::Spawner;
+use embassy_time::{Duration, Instant, Timer};
+use embedded_graphics::mono_font::MonoTextStyle;
+use embedded_graphics::mono_font::ascii::FONT_10X20;
+use embedded_graphics::pixelcolor::Rgb565;
+use embedded_graphics::prelude::*;
+use embedded_graphics::text::Text;
+use embedded_hal_bus::spi::RefCellDevice;
+use embedded_sdmmc::{SdCard, TimeSource, Timestamp, VolumeIdx, VolumeManager};
+use esp_hal::Blocking;
+use esp_hal::clock::CpuClock;
+use esp_hal::delay::Delay;
+use esp_hal::gpio::{Level, Output, OutputConfig};
+use esp_hal::i2c::master::{Config as I2cConfig, I2c};
+use esp_hal::spi::Mode;
+use esp_hal::spi::master::{Config as SpiConfig, Spi};
+use esp_hal::time::Rate;
+use esp_hal::timer::timg::TimerGroup;
+use esp_println::println;
+use esp_radio::wifi::ModeConfig::Client;
+use esp_radio::wifi::{ClientConfig, Config as WifiConfig, ScanConfig};
+
+esp_bootloader_esp_idf::esp_app_desc!();
+
+/// 需要检查应答的 I2C 器件：名称、可能的地址、是否为板载器件
+///
+/// 外接器件不在时记为 SKIP，板载器件不应答记为 FAIL
+const I2C_DEVICES: [(&str, &[u8], bool); 5] = [
+    ("xl9555", &[xl9555::ADDR], true),
+    ("es8388", &[0x10], true),
+    ("qma6100p", &[0x12], true),
+    ("ap3216c", &[0x1E], true),
+    ("bme280", &[0x76, 0x77], false),
+];
+
+/// 等待按键的最长时间
+const KEY_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// 按键轮询周期
+const KEY_POLL: Duration = Duration::from_millis(20);
+
+/// 扫描时最多记录的网络数量
+const SCAN_MAX: usize = 16;
+
+/// SPI 总线默认时钟，与主固件相同
+const SPI_FREQUENCY: Rate = Rate::from_mhz(10);
+
+/// 测试图案的彩条颜色，从左到右
+const BARS: [Rgb565; 8] = [
+    Rgb565::WHITE,
+    Rgb565::YELLOW,
+    Rgb565::CYAN,
+    Rgb565::GREEN,
+    Rgb565::MAGENTA,
+    Rgb565::RED,
+    Rgb565::BLUE,
+    Rgb565::BLACK,
+];
+
+/// LCD 与 TF 卡共享的 SPI 总线
+type SpiBus = RefCell<Spi<'static, Blocking>>;
+
+/// 共享总线上的单个设备
+type SpiDevice<'a> = RefCellDevice<'a, Spi<'static, Blocking>, Output<'static>, Delay>;
+
+/// 检查结果
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Outcome {
+    Pass,
+    Fail,
+    Skip,
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Outcome::Pass => "PASS",
+            Outcome::Fail => "FAIL",
+            Outcome::Skip => "SKIP",
+        })
+    }
+}
+
+/// 自检报告，逐项输出并统计结果
+struct Report {
+    passed: u32,
+    failed: u32,
+}
+
+impl Report {
+    fn begin() -> Self {
+        println!(
+            "SELFTEST BEGIN {} {}",
+            env!("CARGO_PKG_NAME"),
+            env!("CARGO_PKG_VERSION")
+        );
+        Report {
+            passed: 0,
+            failed: 0,
+        }
+    }
+
+    /// 输出一项检查结果
+    ///
+    /// # 参数
+    /// * `item` - 项目名称
+    /// * `outcome` - 检查结果
+    /// * `detail` - 详情，不含空格以便解析
+    fn record(&mut self, item: &str, outcome: Outcome, detail: fmt::Arguments<'_>) {
+        match outcome {
+            Outcome::Pass => self.passed += 1,
+            Outcome::Fail => self.failed += 1,
+            Outcome::Skip => {}
+        }
+        println!("SELFTEST {} {} {}", item, outcome, detail);
+    }
+
+    fn end(self) {
+        let result = if self.failed == 0 { "PASS" } else { "FAIL" };
+        println!(
+            "SELFTEST END {} passed={} failed={}",
+            result, self.passed, self.failed
+        );
+    }
+}
+
+/// 文件系统时间戳来源，自检不写文件，使用固定时间
+struct FixedTime;
+
+impl TimeSource for FixedTime {
+    fn get_timestamp(&self) -> Timestamp {
+        Timestamp {
+            year_since_1970: 55,
+            zero_indexed_month: 0,
+            zero_indexed_day: 0,
+            hours: 0,
+            minutes: 0,
+            seconds: 0,
+        }
+    }
+}
+
+#[esp_rtos::main]
+async fn main(_spawner: Spawner) {
+    let config = esp_hal::Config::default().with_cpu_clock(CpuClock::max());
+    let peripherals = esp_hal::init(config);
+
+    esp_alloc::heap_allocator!( size : 64 * 1024 );
+    let time_g0 = TimerGroup::new(peripherals.TIMG0);
+    esp_rtos::start(time_g0.timer0);
+
+    let mut report = Report::begin();
+
+    let mut i2c = I2c::new(peripherals.I2C0, I2cConfig::default())
+        .expect("Failed to initialize I2C")
+        .with_sda(peripherals.GPIO41)
+        .with_scl(peripherals.GPIO42);
+    check_i2c(&mut report, &mut i2c);
+
+    // 扩展芯片控制 LCD 复位/背光并连接按键，配置失败时跳过相关检查
+    let expander = match xl9555::init(&mut i2c) {
+        Ok(()) => true,
+        Err(err) => {
+            warn!("Failed to configure XL9555: {}", err);
+            false
+        }
+    };
+
+    let spi = Spi::new(
+        peripherals.SPI2,
+        SpiConfig::default()
+            .with_frequency(SPI_FREQUENCY)
+            .with_mode(Mode::_0),
+    )
+    .expect("Failed to initialize SPI")
+    .with_sck(peripherals.GPIO12)
+    .with_mosi(peripherals.GPIO11)
+    .with_miso(peripherals.GPIO13);
+    let bus: SpiBus = RefCell::new(spi);
+    // 两个片选都先拉高，避免检查其中一个设备时另一个设备误响应
+    let lcd_cs = Output::new(peripherals.GPIO21, Level::High, OutputConfig::default());
+    let sd_cs = Output::new(peripherals.GPIO2, Level::High, OutputConfig::default());
+    let dc = Output::new(peripherals.GPIO40, Level::High, OutputConfig::default());
+
+    if expander {
+        let mut lcd = St7789::new(device(&bus, lcd_cs), dc);
+        check_lcd(&mut report, &mut i2c, &mut lcd).await;
+        check_keys(&mut report, &mut i2c, &mut lcd).await;
+    } else {
+        report.record("lcd", Outcome::Fail, format_args!("no-expander"));
+        report.record("keys", Outcome::Fail, format_args!("no-expander"));
+    }
+
+    check_wifi(&mut report, peripherals.WIFI).await;
+    check_sd(&mut report, &bus, sd_cs);
+
+    report.end();
+
+    loop {
+        Timer::after_secs(1).await;
+    }
+}
+
+/// 在共享总线上创建一个设备
+fn device<'a>(bus: &'a SpiBus, cs: Output<'static>) -> SpiDevice<'a> {
+    match RefCellDevice::new(bus, cs, Delay::new()) {
+        Ok(device) => device,
+        Err(never) => match never {},
+    }
+}
+
+/// 检查各 I2C 器件是否应答
+///
+/// 以单字节读取探测地址，所列器件读取一个字节都没有副作用
+fn check_i2c(report: &mut Report, i2c: &mut I2c<'static, Blocking>) {
+    for (name, addresses, fitted) in I2C_DEVICES {
+        let mut item = heapless::String::<16>::new();
+        write!(item, "i2c.{}", name).ok();
+
+        let found = addresses
+            .iter()
+            .copied()
+            .find(|&address| i2c.read(address, &mut [0u8]).is_ok());
+        match (found, fitted) {
+            (Some(address), _) => {
+                report.record(&item, Outcome::Pass, format_args!("addr={:#04x}", address))
+            }
+            (None, true) => report.record(&item, Outcome::Fail, format_args!("no-ack")),
+            (None, false) => report.record(&item, Outcome::Skip, format_args!("not-fitted")),
+        }
+    }
+}
+
+/// 复位并初始化 LCD，显示彩条后打开背光
+async fn check_lcd(
+    report: &mut Report,
+    i2c: &mut I2c<'static, Blocking>,
+    lcd: &mut St7789<SpiDevice<'_>, Output<'static>>,
+) {
+    // 硬件复位：RST 拉低至少 10 微秒，拉高后等待 120 毫秒
+    let reset = xl9555::write_output(i2c, io_bits::SLCD_RST_IO, false).and_then(|()| {
+        Delay::new().delay_micros(10);
+        xl9555::write_output(i2c, io_bits::SLCD_RST_IO, true)
+    });
+    if let Err(err) = reset {
+        warn!("Failed to reset LCD: {}", err);
+        report.record("lcd", Outcome::Fail, format_args!("reset"));
+        return;
+    }
+    Timer::after_millis(120).await;
+
+    if let Err(err) = lcd.init(&mut embassy_time::Delay).await {
+        warn!("Failed to initialize ST7789: {}", defmt::Debug2Format(&err));
+        report.record("lcd", Outcome::Fail, format_args!("init"));
+        return;
+    }
+
+    let width = st7789::WIDTH / BARS.len() as u16;
+    let drawn = BARS.iter().enumerate().try_for_each(|(i, &color)| {
+        lcd.fill_rectangle(i as u16 * width, 0, width, st7789::HEIGHT, color)
+    });
+    if let Err(err) = drawn {
+        warn!("Failed to draw test pattern: {}", defmt::Debug2Format(&err));
+        report.record("lcd", Outcome::Fail, format_args!("draw"));
+        return;
+    }
+
+    if let Err(err) = xl9555::write_output(i2c, io_bits::SLCD_PWR_IO, true) {
+        warn!("Failed to turn on LCD backlight: {}", err);
+        report.record("lcd", Outcome::Fail, format_args!("backlight"));
+        return;
+    }
+    report.record("lcd", Outcome::Pass, format_args!("pattern=bars"));
+}
+
+/// 等待 KEY0-KEY3 全部被按下过一次
+async fn check_keys(
+    report: &mut Report,
+    i2c: &mut I2c<'static, Blocking>,
+    lcd: &mut St7789<SpiDevice<'_>, Output<'static>>,
+) {
+    println!("SELFTEST keys WAIT timeout={}s", KEY_TIMEOUT.as_secs());
+    // 提示绘制失败不影响按键检查
+    lcd.fill_rectangle(0, 100, st7789::WIDTH, 40, Rgb565::BLACK)
+        .ok();
+    let style = MonoTextStyle::new(&FONT_10X20, Rgb565::WHITE);
+    Text::new("Press KEY0-KEY3", Point::new(85, 125), style)
+        .draw(lcd)
+        .ok();
+
+    let mut seen = [false; 4];
+    let deadline = Instant::now() + KEY_TIMEOUT;
+    while !seen.iter().all(|&key| key) && Instant::now() < deadline {
+        // 读取失败时跳过本次轮询，避免误判为按下
+        if let Ok(inputs) = xl9555::read_inputs(i2c) {
+            for (seen, pressed) in seen.iter_mut().zip(xl9555::pressed_keys(inputs)) {
+                *seen |= pressed;
+            }
+        }
+        Timer::after(KEY_POLL).await;
+    }
+
+    if seen.iter().all(|&key| key) {
+        report.record("keys", Outcome::Pass, format_args!("all"));
+        return;
+    }
+    let mut missing = heapless::String::<24>::new();
+    for (i, _) in seen.iter().enumerate().filter(|&(_, &key)| !key) {
+        let separator = if missing.is_empty() { "" } else { "," };
+        write!(missing, "{}KEY{}", separator, i).ok();
+    }
+    report.record("keys", Outcome::Fail, format_args!("missing={}", missing));
+}
+
+/// 以客户端模式启动 WiFi 并扫描周围的网络
+async fn check_wifi(report: &mut Report, wifi: esp_hal::peripherals::WIFI<'static>) {
+    let radio = match esp_radio::init() {
+        Ok(radio) => radio,
+        Err(err) => {
+            warn!("Failed to initialize radio: {}", defmt::Debug2Format(&err));
+            report.record("wifi", Outcome::Fail, format_args!("init"));
+            return;
+        }
+    };
+    let mut controller = match esp_radio::wifi::new(&radio, wifi, WifiConfig::default()) {
+        Ok((controller, _interfaces)) => controller,
+        Err(err) => {
+            warn!("Failed to initialize Wi-Fi controller: {}", err);
+            report.record("wifi", Outcome::Fail, format_args!("init"));
+            return;
+        }
+    };
+    let started = controller.set_config(&Client(ClientConfig::default()));
+    let started = match started {
+        Ok(()) => controller.start_async().await,
+        Err(err) => Err(err),
+    };
+    if let Err(err) = started {
+        warn!("Wi-Fi start failed: {}", err);
+        report.record("wifi", Outcome::Fail, format_args!("start"));
+        return;
+    }
+
+    match controller
+        .scan_with_config_async(ScanConfig::default().with_max(SCAN_MAX))
+        .await
+    {
+        Ok(networks) => match networks.iter().map(|network| network.signal_strength).max() {
+            Some(best) => report.record(
+                "wifi",
+                Outcome::Pass,
+                format_args!("aps={} best={}dBm", networks.len(), best),
+            ),
+            None => report.record("wifi", Outcome::Fail, format_args!("aps=0")),
+        },
+        Err(err) => {
+            warn!("Wi-Fi scan failed: {}", err);
+            report.record("wifi", Outcome::Fail, format_args!("scan"));
+        }
+    }
+}
+
+/// 初始化 TF 卡并打开第一个分区的根目录
+///
+/// 初始化阶段以 400kHz 时钟与 TF 卡通信，完成后恢复总线默认时钟
+fn check_sd(report: &mut Report, bus: &SpiBus, cs: Output<'static>) {
+    set_frequency(bus, Rate::from_khz(400));
+    // 上电后需在片选无效时发送至少 74 个时钟
+    bus.borrow_mut().write(&[0xFF; 10]).ok();
+
+    let card = SdCard::new(device(bus, cs), Delay::new());
+    let size = card.num_bytes();
+    set_frequency(bus, SPI_FREQUENCY);
+    let size = match size {
+        Ok(size) => size,
+        Err(err) => {
+            warn!(
+                "Failed to initialize SD card: {}",
+                defmt::Debug2Format(&err)
+            );
+            report.record("sd", Outcome::Fail, format_args!("no-card"));
+            return;
+        }
+    };
+
+    let mut volumes = VolumeManager::new(card, FixedTime);
+    let mounted = volumes
+        .open_volume(VolumeIdx(0))
+        .and_then(|mut volume| volume.open_root_dir().map(drop));
+    match mounted {
+        Ok(()) => report.record(
+            "sd",
+            Outcome::Pass,
+            format_args!("size={}MB", size / (1024 * 1024)),
+        ),
+        Err(err) => {
+            warn!("Failed to mount SD card: {}", defmt::Debug2Format(&err));
+            report.record("sd", Outcome::Fail, format_args!("mount"));
+        }
+    }
+}
+
+/// 修改 SPI 总线时钟频率
+fn set_frequency(bus: &SpiBus, frequency: Rate) {
+    bus.borrow_mut()
+        .apply_config(
+            &SpiConfig::default()
+                .with_frequency(frequency)
+                .with_mode(Mode::_0),
+        )
+        .ok();
+}
+
+/// 自检过程中 panic 时输出失败结果，停在原地等待工装读取
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    println!("SELFTEST END FAIL panic={}", info.message());
+    loop {
+        core::hint::spin_loop();
+    }
+}
+
+// 自检程序只把 defmt 日志帧直接输出到控制台，格式与主固件相同
+
+defmt::timestamp!("{=u64:us}", embassy_time::Instant::now().as_micros());
+
+/// espflash 用于区分 defmt 帧与普通文本的帧起始标记
+const FRAME_START: [u8; 2] = [0xFF, 0x00];
+
+#[defmt::global_logger]
+struct Logger;
+
+/// logger 是否已被获取，用于检测重入
+static TAKEN: AtomicBool = AtomicBool::new(false);
+static mut CS_RESTORE: RestoreState = RestoreState::invalid();
+static mut ENCODER: defmt::Encoder = defmt::Encoder::new();
+
+unsafe impl defmt::Logger for Logger {
+    fn acquire() {
+        // SAFETY: 在 release 中配对释放
+        let restore = unsafe { critical_section::acquire() };
+        if TAKEN.load(Ordering::Relaxed) {
+            panic!("defmt logger taken reentrantly");
+        }
+        TAKEN.store(true, Ordering::Relaxed);
+        // SAFETY: 处于临界区内，且已检查没有重入
+        unsafe {
+            CS_RESTORE = restore;
+            esp_println::Printer::write_bytes(&FRAME_START);
+            let encoder = &raw mut ENCODER;
+            (*encoder).start_frame(esp_println::Printer::write_bytes);
+        }
+    }
+
+    unsafe fn flush() {}
+
+    unsafe fn release() {
+        // SAFETY: 调用者保证已通过 acquire 进入临界区
+        unsafe {
+            let encoder = &raw mut ENCODER;
+            (*encoder).end_frame(esp_println::Printer::write_bytes);
+            TAKEN.store(false, Ordering::Relaxed);
+            critical_section::release(CS_RESTORE);
+        }
+    }
+
+    unsafe fn write(bytes: &[u8]) {
+        // SAFETY: 调用者保证已通过 acquire 进入临界区
+        unsafe {
+            let encoder = &raw mut ENCODER;
+            (*encoder).write(bytes, esp_println::Printer::write_bytes);
+        }
+    }
+}
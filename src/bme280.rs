@@ -0,0 +1,283 @@
+//! BME280 温湿度气压传感器
+//!
+//! 接在板载 I2C 总线上（与 XL9555 共用，见 [crate::i2c]），地址 0x76 或 0x77
+//! （取决于 SDO 引脚）。[bme280_task] 以强制模式周期测量，读数按 Bosch 数据手册的
+//! 整数补偿公式换算后以 `bme280.*` 为名登记到 [crate::sensor]：
+//!
+//! - `bme280.temp`：温度（°C）
+//! - `bme280.hum`：相对湿度（%）
+//! - `bme280.press`：气压（hPa）
+//!
+//! 每次测量同时追加一行到 TF 卡数据记录（见 [crate::sdlog]），字段为
+//! `UNIX 时间（未校准时为空）,运行秒数,温度,湿度,气压`。
+//!
+//! 传感器可以热插拔：拔出后撤下读数，插上后重新读取校准参数继续测量（见 [crate::registry]）。
+
+use crate::error::Error;
+use crate::registry::{self, PROBE_PERIOD, Peripheral, State};
+#[cfg(feature = "sd")]
+use crate::sdlog;
+use crate::sensor::{self, SensorError};
+use crate::{i2c, wallclock};
+use core::fmt::Write;
+use defmt::{info, warn};
+use embassy_time::{Duration, Instant, Ticker, Timer};
+use heapless::String;
+
+/// 可能的 I2C 地址
+const ADDRESSES: [u8; 2] = [0x76, 0x77];
+
+/// BME280 的芯片 ID（BMP280 为 0x58，没有湿度）
+const CHIP_ID: u8 = 0x60;
+
+/// 测量周期
+const MEASURE_PERIOD: Duration = Duration::from_secs(10);
+
+/// 强制模式下 1 倍过采样的最长测量时间
+const MEASURE_TIME: Duration = Duration::from_millis(10);
+
+/// 寄存器地址
+mod registers {
+    pub const CALIB_00: u8 = 0x88;
+    pub const CHIP_ID: u8 = 0xD0;
+    pub const CALIB_26: u8 = 0xE1;
+    pub const CTRL_HUM: u8 = 0xF2;
+    pub const CTRL_MEAS: u8 = 0xF4;
+    pub const DATA: u8 = 0xF7;
+}
+
+/// 一次测量结果
+#[derive(Debug, Clone, Copy, PartialEq, defmt::Format)]
+pub struct Measurement {
+    /// 温度（°C）
+    pub temperature: f32,
+    /// 相对湿度（%）
+    pub humidity: f32,
+    /// 气压（hPa）
+    pub pressure: f32,
+}
+
+/// 出厂校准参数
+struct Calibration {
+    t1: u16,
+    t2: i16,
+    t3: i16,
+    p1: u16,
+    p2: i16,
+    p3: i16,
+    p4: i16,
+    p5: i16,
+    p6: i16,
+    p7: i16,
+    p8: i16,
+    p9: i16,
+    h1: u8,
+    h2: i16,
+    h3: u8,
+    h4: i16,
+    h5: i16,
+    h6: i8,
+}
+
+/// BME280 驱动
+pub struct Bme280 {
+    address: u8,
+    calibration: Calibration,
+}
+
+impl Bme280 {
+    /// 在两个可能的地址上查找传感器并读取校准参数
+    ///
+    /// 两个地址上都没有 BME280 时返回 [SensorError::NotFound]
+    pub fn probe() -> Result<Self, Error> {
+        for address in ADDRESSES {
+            let mut id = [0u8];
+            let found = i2c::with_i2c("BME280 probe", |i2c| {
+                i2c.write_read(address, &[registers::CHIP_ID], &mut id)
+            });
+            if found.is_ok() && id[0] == CHIP_ID {
+                let calibration = read_calibration(address)?;
+                info!("BME280 found at {:#04x}", address);
+                return Ok(Bme280 {
+                    address,
+                    calibration,
+                });
+            }
+        }
+        Err(Error::Sensor { op: "BME280 probe", source: SensorError::NotFound })
+    }
+
+    /// 触发一次强制模式测量并读取结果
+    pub async fn measure(&mut self) -> Result<Measurement, Error> {
+        // 湿度过采样需在 ctrl_meas 之前写入才生效；温度、气压、湿度均 1 倍过采样，
+        // ctrl_meas = osrs_t 001 | osrs_p 001 | mode 01（强制模式）
+        i2c::with_i2c("BME280 start measurement", |i2c| {
+            i2c.write(self.address, &[registers::CTRL_HUM, 0x01])?;
+            i2c.write(self.address, &[registers::CTRL_MEAS, 0x25])
+        })?;
+        Timer::after(MEASURE_TIME).await;
+
+        let mut data = [0u8; 8];
+        i2c::with_i2c("BME280 read data", |i2c| {
+            i2c.write_read(self.address, &[registers::DATA], &mut data)
+        })?;
+        let adc_p = (data[0] as i32) << 12 | (data[1] as i32) << 4 | (data[2] as i32) >> 4;
+        let adc_t = (data[3] as i32) << 12 | (data[4] as i32) << 4 | (data[5] as i32) >> 4;
+        let adc_h = (data[6] as i32) << 8 | data[7] as i32;
+
+        let (temperature, t_fine) = self.compensate_temperature(adc_t);
+        Ok(Measurement {
+            temperature: temperature as f32 / 100.0,
+            pressure: self.compensate_pressure(adc_p, t_fine) as f32 / 256.0 / 100.0,
+            humidity: self.compensate_humidity(adc_h, t_fine) as f32 / 1024.0,
+        })
+    }
+
+    /// 温度补偿
+    ///
+    /// # 返回
+    /// 温度（0.01 °C）和供气压、湿度补偿使用的 t_fine
+    fn compensate_temperature(&self, adc: i32) -> (i32, i32) {
+        let c = &self.calibration;
+        let var1 = (((adc >> 3) - ((c.t1 as i32) << 1)) * c.t2 as i32) >> 11;
+        let var2 = (((((adc >> 4) - c.t1 as i32) * ((adc >> 4) - c.t1 as i32)) >> 12)
+            * c.t3 as i32)
+            >> 14;
+        let t_fine = var1 + var2;
+        ((t_fine * 5 + 128) >> 8, t_fine)
+    }
+
+    /// 气压补偿
+    ///
+    /// # 返回
+    /// 气压（Pa，Q24.8 定点数）
+    fn compensate_pressure(&self, adc: i32, t_fine: i32) -> u32 {
+        let c = &self.calibration;
+        let mut var1 = t_fine as i64 - 128000;
+        let mut var2 = var1 * var1 * c.p6 as i64;
+        var2 += (var1 * c.p5 as i64) << 17;
+        var2 += (c.p4 as i64) << 35;
+        var1 = ((var1 * var1 * c.p3 as i64) >> 8) + ((var1 * c.p2 as i64) << 12);
+        var1 = (((1i64 << 47) + var1) * c.p1 as i64) >> 33;
+        if var1 == 0 {
+            // 避免除零
+            return 0;
+        }
+        let mut p = 1048576 - adc as i64;
+        p = (((p << 31) - var2) * 3125) / var1;
+        let var1 = ((c.p9 as i64) * (p >> 13) * (p >> 13)) >> 25;
+        let var2 = ((c.p8 as i64) * p) >> 19;
+        (((p + var1 + var2) >> 8) + ((c.p7 as i64) << 4)) as u32
+    }
+
+    /// 湿度补偿
+    ///
+    /// # 返回
+    /// 相对湿度（%，Q22.10 定点数）
+    fn compensate_humidity(&self, adc: i32, t_fine: i32) -> u32 {
+        let c = &self.calibration;
+        let mut v = t_fine - 76800;
+        v = ((((adc << 14) - ((c.h4 as i32) << 20) - (c.h5 as i32 * v)) + 16384) >> 15)
+            * (((((((v * c.h6 as i32) >> 10) * (((v * c.h3 as i32) >> 11) + 32768)) >> 10)
+                + 2097152)
+                * c.h2 as i32
+                + 8192)
+                >> 14);
+        v -= ((((v >> 15) * (v >> 15)) >> 7) * c.h1 as i32) >> 4;
+        (v.clamp(0, 419430400) >> 12) as u32
+    }
+}
+
+/// 读取出厂校准参数
+fn read_calibration(address: u8) -> Result<Calibration, Error> {
+    let mut tp = [0u8; 26];
+    let mut h = [0u8; 7];
+    i2c::with_i2c("BME280 read calibration", |i2c| {
+        i2c.write_read(address, &[registers::CALIB_00], &mut tp)?;
+        i2c.write_read(address, &[registers::CALIB_26], &mut h)
+    })?;
+
+    let u16_at = |i: usize| u16::from_le_bytes([tp[i], tp[i + 1]]);
+    let i16_at = |i: usize| i16::from_le_bytes([tp[i], tp[i + 1]]);
+    Ok(Calibration {
+        t1: u16_at(0),
+        t2: i16_at(2),
+        t3: i16_at(4),
+        p1: u16_at(6),
+        p2: i16_at(8),
+        p3: i16_at(10),
+        p4: i16_at(12),
+        p5: i16_at(14),
+        p6: i16_at(16),
+        p7: i16_at(18),
+        p8: i16_at(20),
+        p9: i16_at(22),
+        h1: tp[25],
+        h2: i16::from_le_bytes([h[0], h[1]]),
+        h3: h[2],
+        // H4、H5 为 12 位有符号数，共用 0xE5 的高低半字节
+        h4: ((h[3] as i8 as i16) << 4) | (h[4] & 0x0F) as i16,
+        h5: ((h[5] as i8 as i16) << 4) | (h[4] >> 4) as i16,
+        h6: h[6] as i8,
+    })
+}
+
+/// 周期测量任务
+///
+/// 未找到传感器或测量失败时每 [PROBE_PERIOD] 重新探测，支持热插拔（见 [crate::registry]）
+#[embassy_executor::task]
+pub async fn bme280_task() {
+    loop {
+        let probed = Bme280::probe();
+        if let Err(err) = &probed
+            && registry::state(Peripheral::Bme280) != State::Offline
+        {
+            info!("BME280 unavailable: {}", err);
+        }
+        registry::report(Peripheral::Bme280, &probed);
+        let Ok(mut sensor) = probed else {
+            // 撤下拔出前的读数，界面显示 `--`
+            sensor::withdraw("bme280.");
+            Timer::after(PROBE_PERIOD).await;
+            continue;
+        };
+
+        // 测量失败后重新探测，仍然找不到时才视为拔出
+        let mut ticker = Ticker::every(MEASURE_PERIOD);
+        loop {
+            match sensor.measure().await {
+                Ok(m) => {
+                    sensor::publish("bme280.temp", m.temperature as f64, "C");
+                    sensor::publish("bme280.hum", m.humidity as f64, "%");
+                    sensor::publish("bme280.press", m.pressure as f64, "hPa");
+                    log_measurement(&m);
+                }
+                Err(err) => {
+                    warn!("BME280 measurement failed: {}", err);
+                    Timer::after(PROBE_PERIOD).await;
+                    break;
+                }
+            }
+            ticker.next().await;
+        }
+    }
+}
+
+/// 追加一行测量记录到 TF 卡
+fn log_measurement(m: &Measurement) {
+    let mut line: String<64> = String::new();
+    if let Some(unix) = wallclock::now() {
+        write!(line, "{}", unix).ok();
+    }
+    write!(
+        line,
+        ",{},{:.2},{:.2},{:.2}",
+        Instant::now().as_secs(),
+        m.temperature,
+        m.humidity,
+        m.pressure
+    )
+    .ok();
+    #[cfg(feature = "sd")]
+    sdlog::append(&line);
+}
@@ -0,0 +1,432 @@
+//! 开发板型号
+//!
+//! 描述各型号的引脚分配（[PinMap]）和板上装有的外设，app 按当前型号初始化总线和外设，
+//! 不再写死 GPIO 编号；外设注册表（[crate::registry]）显示的引脚也来自这里。
+//!
+//! - [Variant::Dnesp32s3]：正点原子 DNESP32S3 开发板，引脚见 `main.rs` 的模块文档
+//! - [Variant::Custom]：自定义底板，引脚和外设从设置读取，用命令行 `board pin`、`board has` 修改
+//!
+//! 型号保存在设置中，启动时由 [init] 读取一次，修改后重启生效。BOOT 按键（GPIO0）和
+//! UART0 控制台（GPIO43/44）是 ESP32-S3 的固定引脚，不在引脚表中。
+//!
+//! 自定义引脚只能使用 [USABLE_PINS]：不含 BOOT、USB、控制台以及模组内部连接 Flash/PSRAM 的
+//! 引脚，且互不相同。继电器（[crate::relay]）不能使用引脚表中的引脚。
+//!
+//! 扩展排针上的外接接口（[Interface]，例如 CAN 收发器、DMX512 用的 RS485 收发器）所接的 GPIO 也属于开发板描述，
+//! 所有型号都一样从设置读取，用命令行 `board port` 修改。没有分配引脚的接口不启动；
+//! 分配的引脚同样只能使用 [USABLE_PINS]，不能与引脚表或其他接口重复，否则启动时忽略该接口。
+//! 继电器也不能使用已分配给接口的引脚。
+
+use crate::registry::Peripheral;
+use crate::settings;
+use core::cell::Cell;
+use critical_section::Mutex;
+use defmt::{info, warn};
+use esp_hal::gpio::AnyPin;
+
+/// 开发板型号
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Variant {
+    /// 正点原子 DNESP32S3
+    Dnesp32s3,
+    /// 自定义底板
+    Custom,
+}
+
+impl Variant {
+    /// 所有型号
+    pub const ALL: [Variant; 2] = [Variant::Dnesp32s3, Variant::Custom];
+
+    /// 编码为设置中的值
+    pub const fn to_u8(self) -> u8 {
+        self as u8
+    }
+
+    /// 从设置中的编码解析，未知编码视为 DNESP32S3
+    pub const fn from_u8(value: u8) -> Variant {
+        match value {
+            1 => Variant::Custom,
+            _ => Variant::Dnesp32s3,
+        }
+    }
+
+    /// 型号名称，用于命令行
+    pub const fn name(self) -> &'static str {
+        match self {
+            Variant::Dnesp32s3 => "dnesp32s3",
+            Variant::Custom => "custom",
+        }
+    }
+}
+
+/// 引脚表中的信号
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Signal {
+    /// I2C0 数据
+    Sda,
+    /// I2C0 时钟
+    Scl,
+    /// SPI2 时钟
+    Sck,
+    Mosi,
+    Miso,
+    /// LCD 片选
+    LcdCs,
+    /// LCD 数据/命令选择
+    LcdDc,
+    /// TF 卡片选
+    SdCs,
+    /// 板载 LED
+    Led,
+}
+
+impl Signal {
+    /// 所有信号，顺序与设置中的引脚表一致
+    pub const ALL: [Signal; 9] = [
+        Signal::Sda,
+        Signal::Scl,
+        Signal::Sck,
+        Signal::Mosi,
+        Signal::Miso,
+        Signal::LcdCs,
+        Signal::LcdDc,
+        Signal::SdCs,
+        Signal::Led,
+    ];
+
+    /// 信号名称，用于命令行
+    pub const fn name(self) -> &'static str {
+        match self {
+            Signal::Sda => "sda",
+            Signal::Scl => "scl",
+            Signal::Sck => "sck",
+            Signal::Mosi => "mosi",
+            Signal::Miso => "miso",
+            Signal::LcdCs => "lcd_cs",
+            Signal::LcdDc => "lcd_dc",
+            Signal::SdCs => "sd_cs",
+            Signal::Led => "led",
+        }
+    }
+}
+
+/// 可以分配给引脚表的 GPIO
+pub const USABLE_PINS: [u8; 26] = [
+    1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 21, 38, 39, 40, 41, 42, 47, 48,
+];
+
+/// 引脚表，下标与 [Signal::ALL] 一致
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct PinMap(pub [u8; Signal::ALL.len()]);
+
+impl PinMap {
+    /// DNESP32S3 的引脚
+    pub const DNESP32S3: PinMap = PinMap([41, 42, 12, 11, 13, 21, 40, 2, 1]);
+
+    /// 信号所接的 GPIO
+    pub const fn get(&self, signal: Signal) -> u8 {
+        self.0[signal as usize]
+    }
+
+    /// 是否使用了某个 GPIO
+    pub fn uses(&self, gpio: u8) -> bool {
+        self.0.contains(&gpio)
+    }
+
+    /// 所有引脚都可用且互不相同
+    pub fn is_valid(&self) -> bool {
+        self.0
+            .iter()
+            .enumerate()
+            .all(|(i, pin)| USABLE_PINS.contains(pin) && !self.0[i + 1..].contains(pin))
+    }
+}
+
+/// 板上可能没有的外设，其余外设所有型号都有
+pub const OPTIONAL: [Peripheral; 4] = [
+    Peripheral::Expander,
+    Peripheral::Lcd,
+    Peripheral::SdCard,
+    Peripheral::Bme280,
+];
+
+/// 外设在“装有的外设”位图中对应的位
+pub const fn present_bit(peripheral: Peripheral) -> u8 {
+    1 << peripheral as u8
+}
+
+/// 所有可选外设都装上时的位图
+pub const ALL_PRESENT: u8 = present_bit(Peripheral::Expander)
+    | present_bit(Peripheral::Lcd)
+    | present_bit(Peripheral::SdCard)
+    | present_bit(Peripheral::Bme280);
+
+/// 扩展排针上的外接接口
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Interface {
+    /// CAN 收发器，见 [crate::can]
+    Can,
+    /// DMX512 输出，经 RS485 收发器，见 [crate::dmx]
+    Dmx,
+    /// PID 回路的 PWM 输出，只在 [Profile::Pid](crate::profile::Profile::Pid) 模式启动，见 [crate::pid]
+    Pwm,
+    /// GPS 接收机的串口，见 [crate::gps]
+    Gps,
+    /// RS485 收发器，用作 Modbus RTU 从站（见 [crate::modbus]）或串口透传（见 [crate::bridge]）
+    Rs485,
+    /// RS232/TTL 串口，用于串口透传，见 [crate::bridge]
+    Serial,
+    /// 航模遥控接收机，见 [crate::rc]
+    Rc,
+    /// LIN 收发器，作为主节点，见 [crate::lin]
+    Lin,
+}
+
+impl Interface {
+    /// 所有接口，顺序与设置中的接线表一致
+    pub const ALL: [Interface; 8] = [
+        Interface::Can,
+        Interface::Dmx,
+        Interface::Pwm,
+        Interface::Gps,
+        Interface::Rs485,
+        Interface::Serial,
+        Interface::Rc,
+        Interface::Lin,
+    ];
+
+    /// 接口名称，用于命令行
+    pub const fn name(self) -> &'static str {
+        match self {
+            Interface::Can => "can",
+            Interface::Dmx => "dmx",
+            Interface::Pwm => "pwm",
+            Interface::Gps => "gps",
+            Interface::Rs485 => "rs485",
+            Interface::Serial => "serial",
+            Interface::Rc => "rc",
+            Interface::Lin => "lin",
+        }
+    }
+
+    /// 接口的信号，依次对应 [Wiring::pins]
+    pub const fn signals(self) -> &'static [&'static str] {
+        match self {
+            Interface::Can => &["rx", "tx"],
+            Interface::Dmx => &["tx", "de"],
+            Interface::Pwm => &["out"],
+            Interface::Rc => &["rx"],
+            Interface::Gps | Interface::Serial | Interface::Lin => &["tx", "rx"],
+            Interface::Rs485 => &["tx", "rx", "de"],
+        }
+    }
+
+    /// 参数的含义，用于命令行；没有参数的接口为空，参数固定为 0
+    pub const fn param_name(self) -> &'static str {
+        match self {
+            Interface::Can => "kbit/s",
+            Interface::Gps
+            | Interface::Rs485
+            | Interface::Serial
+            | Interface::Rc
+            | Interface::Lin => "baud",
+            Interface::Dmx | Interface::Pwm => "",
+        }
+    }
+
+    /// 参数的默认值
+    pub const fn default_param(self) -> u32 {
+        match self {
+            Interface::Can => 500,
+            Interface::Gps | Interface::Rs485 => 9600,
+            Interface::Serial => 115_200,
+            Interface::Rc => crate::rc::Protocol::Sbus.baudrate(),
+            Interface::Lin => 19_200,
+            Interface::Dmx | Interface::Pwm => 0,
+        }
+    }
+
+    /// 参数是否有效
+    pub fn accepts(self, param: u32) -> bool {
+        match self {
+            Interface::Can => crate::can::baudrate(param).is_some(),
+            Interface::Gps | Interface::Rs485 | Interface::Serial => {
+                crate::serial::BAUDRATES.contains(&param)
+            }
+            Interface::Rc => crate::rc::Protocol::from_baudrate(param).is_some(),
+            Interface::Lin => crate::lin::BAUDRATES.contains(&param),
+            Interface::Dmx | Interface::Pwm => param == 0,
+        }
+    }
+}
+
+/// 一个接口最多使用的引脚数
+pub const MAX_INTERFACE_PINS: usize = 3;
+
+/// 外接接口的接线
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct Wiring {
+    /// 各信号所接的 GPIO，0 表示未分配
+    pub pins: [u8; MAX_INTERFACE_PINS],
+    /// 接口参数，含义见 [Interface::param_name]
+    pub param: u32,
+}
+
+impl Wiring {
+    /// 未分配引脚
+    pub const NONE: Wiring = Wiring {
+        pins: [0; MAX_INTERFACE_PINS],
+        param: 0,
+    };
+
+    /// 是否分配了引脚
+    pub const fn is_assigned(&self) -> bool {
+        self.pins[0] != 0
+    }
+
+    /// 接口用到的引脚
+    pub fn used(&self, interface: Interface) -> &[u8] {
+        &self.pins[..interface.signals().len()]
+    }
+
+    /// 取得第 `index` 个信号所接的引脚
+    ///
+    /// 每个信号只能在启动接口时取一次
+    pub fn pin(&self, index: usize) -> AnyPin<'static> {
+        // SAFETY: 当前开发板的接线已通过 [Board::from_settings] 的检查，接口的引脚互不相同，
+        // 也不在引脚表中；这些 GPIO 的类型化外设对象不再使用，继电器也不能分配它们
+        unsafe { AnyPin::steal(self.pins[index]) }
+    }
+}
+
+/// 开发板描述
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct Board {
+    pub variant: Variant,
+    pub pins: PinMap,
+    /// 装有的可选外设，见 [OPTIONAL]
+    present: u8,
+    /// 外接接口的接线，下标与 [Interface::ALL] 一致
+    ports: [Wiring; Interface::ALL.len()],
+}
+
+impl Board {
+    /// DNESP32S3：扩展芯片、LCD 和 TF 卡都在板上，BME280 接在 I2C 排针上
+    pub const DNESP32S3: Board = Board {
+        variant: Variant::Dnesp32s3,
+        pins: PinMap::DNESP32S3,
+        present: ALL_PRESENT,
+        ports: [Wiring::NONE; Interface::ALL.len()],
+    };
+
+    /// 按设置生成开发板描述
+    ///
+    /// # 参数
+    /// * `variant` - 型号
+    /// * `pins` - 自定义型号的引脚表
+    /// * `present` - 自定义型号装有的外设位图
+    /// * `ports` - 外接接口的接线，无效的接口视为未分配
+    ///
+    /// # 返回
+    /// 自定义引脚无效时返回 None
+    pub fn from_settings(
+        variant: Variant,
+        pins: [u8; 9],
+        present: u8,
+        ports: [Wiring; Interface::ALL.len()],
+    ) -> Option<Board> {
+        let mut board = match variant {
+            Variant::Dnesp32s3 => Board::DNESP32S3,
+            Variant::Custom => Board {
+                variant,
+                pins: Some(PinMap(pins)).filter(PinMap::is_valid)?,
+                present: present & ALL_PRESENT,
+                ports: [Wiring::NONE; Interface::ALL.len()],
+            },
+        };
+        for (interface, wiring) in Interface::ALL.into_iter().zip(ports) {
+            if !wiring.is_assigned() {
+                continue;
+            }
+            if board.accepts(interface, &wiring) {
+                board.ports[interface as usize] = wiring;
+            } else {
+                warn!("Invalid {} wiring, leaving it unused", interface.name());
+            }
+        }
+        Some(board)
+    }
+
+    /// 接线能否加入开发板：引脚可用、互不相同，且没有被占用
+    fn accepts(&self, interface: Interface, wiring: &Wiring) -> bool {
+        let used = wiring.used(interface);
+        interface.accepts(wiring.param)
+            && used.iter().enumerate().all(|(i, pin)| {
+                USABLE_PINS.contains(pin) && !used[i + 1..].contains(pin) && !self.uses(*pin)
+            })
+    }
+
+    /// 是否使用了某个 GPIO，包括引脚表和外接接口
+    pub fn uses(&self, gpio: u8) -> bool {
+        self.pins.uses(gpio)
+            || Interface::ALL.into_iter().any(|interface| {
+                self.ports[interface as usize]
+                    .used(interface)
+                    .contains(&gpio)
+            })
+    }
+
+    /// 外接接口的接线，未分配时返回 None
+    pub fn port(&self, interface: Interface) -> Option<Wiring> {
+        Some(self.ports[interface as usize]).filter(Wiring::is_assigned)
+    }
+
+    /// 板上是否有该外设
+    ///
+    /// 编译时关闭了 `ui` 或 `sd` feature 的固件视为没有 LCD 或 TF 卡
+    pub const fn has(&self, peripheral: Peripheral) -> bool {
+        match peripheral {
+            Peripheral::Lcd if !cfg!(feature = "ui") => false,
+            Peripheral::SdCard if !cfg!(feature = "sd") => false,
+            Peripheral::Expander | Peripheral::Lcd | Peripheral::SdCard | Peripheral::Bme280 => {
+                self.present & present_bit(peripheral) != 0
+            }
+            _ => true,
+        }
+    }
+}
+
+/// 启动时选定的开发板
+static BOARD: Mutex<Cell<Board>> = Mutex::new(Cell::new(Board::DNESP32S3));
+
+/// 按设置选定开发板，需在加载设置之后、初始化外设之前调用
+pub fn init() {
+    let s = settings::get();
+    let variant = Variant::from_u8(s.board);
+    let ports = s.board_ports;
+    let board = Board::from_settings(variant, s.board_pins, s.board_present, ports);
+    let board = board.unwrap_or_else(|| {
+        warn!("Invalid custom board pins, using the DNESP32S3 pin map");
+        Board::from_settings(Variant::Dnesp32s3, s.board_pins, s.board_present, ports)
+            .unwrap_or(Board::DNESP32S3)
+    });
+    info!("Board: {}", board.variant);
+    critical_section::with(|cs| BOARD.borrow(cs).set(board));
+}
+
+/// 当前开发板
+pub fn current() -> Board {
+    critical_section::with(|cs| BOARD.borrow(cs).get())
+}
+
+/// 取得信号所接的引脚
+///
+/// 每个信号只能在初始化时取一次
+///
+/// # 参数
+/// * `signal` - 信号
+pub fn pin(signal: Signal) -> AnyPin<'static> {
+    // SAFETY: 引脚表中的引脚互不相同且都存在（见 [PinMap::is_valid]），app 的各初始化阶段
+    // 每个信号只取一次；这些 GPIO 的类型化外设对象不再使用，继电器也不能分配它们
+    unsafe { AnyPin::steal(current().pins.get(signal)) }
+}
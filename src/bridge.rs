@@ -0,0 +1,285 @@
+//! 串口透传
+//!
+//! 在串口和 TCP 连接之间原样转发字节，用于把没有网络接口的 RS232/RS485 设备接入网络。
+//! 设备侧可以是 [Serial]（RS232/TTL，波特率和校验见 [crate::serial::SerialConfig]），
+//! 也可以是 [Rs485]（半双工，按帧转发，帧间隔见 [crate::rs485::Rs485Config]）。
+//! 网络侧按 [Mode] 作为服务端等待连接，或作为客户端主动连接，同一时刻只有一个连接，
+//! 连接断开后重新等待或重连。
+//!
+//! 透传默认关闭，用命令行 `bridge serial|rs485 server <端口>` 或
+//! `bridge serial|rs485 client <IPv4 地址>:<端口>` 打开（重启后生效，见 [Setup]）。
+//! 设备侧接在扩展排针的 `serial` 或 `rs485` 接口上（见 [crate::board]）；
+//! RS485 接口用于透传时不再作为 Modbus RTU 从站。
+//!
+//! ```ignore
+//! let config = SerialConfig {
+//!     baudrate: 9600,
+//!     ..SerialConfig::DEFAULT
+//! };
+//! let serial = Serial::new("bridge", peripherals.UART1, tx, rx, config)?;
+//! let mode = Mode::Server { port: 4001 };
+//! spawner.spawn(bridge::bridge_task(stack, Port::Serial(serial), mode))?;
+//! ```
+
+use crate::board::Interface;
+use crate::net::{SocketOptions, TcpBuffers};
+use crate::netstats::{self, Link};
+use crate::rs485::{Rs485, Rs485Error};
+use crate::serial::Serial;
+use crate::settings;
+use defmt::{info, warn};
+use embassy_futures::select::{Either, select};
+use embassy_net::tcp::{Error as TcpError, TcpSocket};
+use embassy_net::{IpAddress, IpEndpoint, Ipv4Address, Stack};
+use embassy_time::{Duration, Timer};
+use embedded_io_async::Write;
+
+/// 单次转发的最大字节数
+const CHUNK_LEN: usize = 256;
+
+/// 连接失败后重试的间隔
+const RETRY_DELAY: Duration = Duration::from_secs(5);
+
+/// TCP 保活间隔；透传连接可能长时间没有数据，靠保活发现对端掉线
+const KEEP_ALIVE: Duration = Duration::from_secs(30);
+
+/// 连接参数：连续三个保活间隔没有应答时断开
+const SOCKET: SocketOptions = SocketOptions {
+    keep_alive: Some(KEEP_ALIVE),
+    timeout: Some(Duration::from_secs(3 * 30)),
+    ..SocketOptions::DEFAULT
+};
+
+/// RS485 模式下轮询网络侧数据的间隔
+const RS485_POLL: Duration = Duration::from_millis(10);
+
+/// 网络侧工作方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Mode {
+    /// 在指定端口等待连接
+    Server { port: u16 },
+    /// 连接到指定地址
+    Client { remote: IpEndpoint },
+}
+
+/// 透传设置
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct Setup {
+    /// 设备侧接口，[Interface::Serial] 或 [Interface::Rs485]
+    pub interface: Interface,
+    /// 网络侧工作方式
+    pub mode: Mode,
+}
+
+impl Setup {
+    /// 透传关闭时的编码
+    pub const OFF: [u8; 8] = [0; 8];
+
+    /// 编码为设置中保存的 8 字节：接口、方式、端口（小端）和客户端模式的 IPv4 地址
+    pub fn to_bytes(self) -> [u8; 8] {
+        let mut bytes = [0u8; 8];
+        bytes[0] = match self.interface {
+            Interface::Rs485 => 2,
+            _ => 1,
+        };
+        let port = match self.mode {
+            Mode::Server { port } => port,
+            Mode::Client { remote } => {
+                let IpAddress::Ipv4(address) = remote.addr;
+                bytes[1] = 1;
+                bytes[4..].copy_from_slice(&address.octets());
+                remote.port
+            }
+        };
+        bytes[2..4].copy_from_slice(&port.to_le_bytes());
+        bytes
+    }
+
+    /// 从设置中保存的编码恢复，透传关闭或编码无效时返回 None
+    pub fn from_bytes(bytes: &[u8; 8]) -> Option<Setup> {
+        let interface = match bytes[0] {
+            1 => Interface::Serial,
+            2 => Interface::Rs485,
+            _ => return None,
+        };
+        let port = u16::from_le_bytes([bytes[2], bytes[3]]);
+        let mode = match bytes[1] {
+            0 => Mode::Server { port },
+            1 => {
+                let [a, b, c, d] = [bytes[4], bytes[5], bytes[6], bytes[7]];
+                let address = IpAddress::Ipv4(Ipv4Address::new(a, b, c, d));
+                Mode::Client {
+                    remote: IpEndpoint::new(address, port),
+                }
+            }
+            _ => return None,
+        };
+        Some(Setup { interface, mode })
+    }
+
+    /// 当前设置
+    pub fn current() -> Option<Setup> {
+        Setup::from_bytes(&settings::get().bridge)
+    }
+}
+
+/// 设备侧串口
+#[expect(
+    clippy::large_enum_variant,
+    reason = "created once and moved into the bridge task, never copied around"
+)]
+pub enum Port {
+    /// 全双工串口，字节流透传
+    Serial(Serial),
+    /// RS485 半双工总线，按帧透传
+    Rs485(Rs485),
+}
+
+/// 连接结束的原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+enum PumpError {
+    /// 对端关闭连接
+    Closed,
+    Tcp(TcpError),
+    /// 串口收发出错
+    Serial,
+}
+
+impl From<TcpError> for PumpError {
+    fn from(err: TcpError) -> Self {
+        PumpError::Tcp(err)
+    }
+}
+
+/// 转发统计
+#[derive(Default)]
+struct Counters {
+    /// 串口到网络
+    uplink: usize,
+    /// 网络到串口
+    downlink: usize,
+}
+
+/// 透传任务
+///
+/// # 参数
+/// * `stack` - 网络栈
+/// * `port` - 设备侧串口
+/// * `mode` - 网络侧工作方式
+#[embassy_executor::task]
+pub async fn bridge_task(stack: Stack<'static>, mut port: Port, mode: Mode) {
+    let mut buffers = TcpBuffers::new(SOCKET);
+
+    stack.wait_config_up().await;
+    info!("Serial bridge started: {}", mode);
+
+    loop {
+        let mut socket = buffers.socket(stack);
+
+        let connected = match mode {
+            Mode::Server { port } => socket.accept(port).await.map_err(|err| {
+                warn!("Bridge accept failed: {}", err);
+            }),
+            Mode::Client { remote } => socket.connect(remote).await.map_err(|err| {
+                warn!("Bridge connect to {} failed: {}", remote, err);
+            }),
+        };
+        if connected.is_err() {
+            Timer::after(RETRY_DELAY).await;
+            continue;
+        }
+        info!("Bridge connected: {}", socket.remote_endpoint());
+        netstats::opened(Link::Bridge);
+
+        let mut counters = Counters::default();
+        let result = match &mut port {
+            Port::Serial(serial) => pump_serial(&mut socket, serial, &mut counters).await,
+            Port::Rs485(bus) => pump_rs485(&mut socket, bus, &mut counters).await,
+        };
+        match result {
+            Err(PumpError::Closed) | Ok(()) => info!(
+                "Bridge disconnected, {} bytes up, {} bytes down",
+                counters.uplink, counters.downlink
+            ),
+            Err(err) => warn!(
+                "Bridge connection error: {}, {} bytes up, {} bytes down",
+                err, counters.uplink, counters.downlink
+            ),
+        }
+        socket.close();
+        socket.flush().await.ok();
+
+        if matches!(mode, Mode::Client { .. }) {
+            Timer::after(RETRY_DELAY).await;
+        }
+    }
+}
+
+/// 全双工转发，直到连接断开
+///
+/// 两个方向的读取都可以安全取消，同时等待，先到的数据先转发
+async fn pump_serial(
+    socket: &mut TcpSocket<'_>,
+    serial: &mut Serial,
+    counters: &mut Counters,
+) -> Result<(), PumpError> {
+    let mut uart_buf = [0u8; CHUNK_LEN];
+    let mut net_buf = [0u8; CHUNK_LEN];
+
+    loop {
+        match select(serial.read(&mut uart_buf), socket.read(&mut net_buf)).await {
+            Either::First(read) => {
+                let len = read.map_err(|_| PumpError::Serial)?;
+                socket.write_all(&uart_buf[..len]).await?;
+                netstats::sent(Link::Bridge, len);
+                counters.uplink += len;
+            }
+            Either::Second(read) => {
+                let len = read?;
+                if len == 0 {
+                    return Err(PumpError::Closed);
+                }
+                netstats::received(Link::Bridge, len);
+                serial.write(&net_buf[..len]).await.map_err(|_| PumpError::Serial)?;
+                counters.downlink += len;
+            }
+        }
+    }
+}
+
+/// 半双工按帧转发，直到连接断开
+///
+/// 接收中途取消会丢失已收到的半帧，所以不与网络读取同时等待，而是交替轮询：
+/// 总线上等待一帧最多 [RS485_POLL]，期间没有数据再检查网络侧
+async fn pump_rs485(
+    socket: &mut TcpSocket<'_>,
+    bus: &mut Rs485,
+    counters: &mut Counters,
+) -> Result<(), PumpError> {
+    let mut frame = [0u8; CHUNK_LEN];
+
+    loop {
+        match bus.receive(&mut frame, RS485_POLL).await {
+            Ok(len) => {
+                socket.write_all(&frame[..len]).await?;
+                netstats::sent(Link::Bridge, len);
+                counters.uplink += len;
+            }
+            Err(Rs485Error::Timeout) => {}
+            Err(err) => warn!("Bridge RS485 receive failed: {}", err),
+        }
+
+        if !socket.may_recv() {
+            return Err(PumpError::Closed);
+        }
+        if socket.can_recv() {
+            let len = socket.read(&mut frame).await?;
+            if len == 0 {
+                return Err(PumpError::Closed);
+            }
+            netstats::received(Link::Bridge, len);
+            bus.send(&frame[..len]).await.map_err(|_| PumpError::Serial)?;
+            counters.downlink += len;
+        }
+    }
+}
@@ -1,7 +1,43 @@
-use defmt::info;
+//! BOOT 按键
+//!
+//! 按住时间决定动作：
+//!
+//! - 短按：开关 LCD 背光
+//! - 按住 5 秒后松开：重启进入配网模式，运行设置向导
+//! - 按住 10 秒：擦除设置，恢复出厂设置并重启
+//!
+//! 按住超过 1 秒后，状态屏幕底部显示倒计时，顶部进度条显示距离恢复出厂设置的进度
+//! （通过 [crate::progress]，其他应用模式下不显示）。
+
+use crate::i18n::{self, Msg};
+use crate::progress::Progress;
+use crate::system::{self, RebootReason};
+use crate::{settings, xl9555};
+use core::fmt::Write;
+use defmt::{info, warn};
 use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
 use embassy_sync::mutex::Mutex as EmbassyMutex;
+use embassy_time::{Duration, Instant, Timer};
 use esp_hal::gpio::{Event, Input, InputConfig, InputPin};
+use heapless::String;
+
+/// 短按的最短按住时间
+///
+/// GPIO0 同时连接 XL9555 的中断输出，扩展芯片的中断在 [xl9555::read_keys]
+/// 读取输入后即释放，持续时间不超过一个轮询周期（50ms），不会被当作短按
+const SHORT_PRESS_MIN: Duration = Duration::from_millis(100);
+
+/// 按住超过该时间后开始显示倒计时
+const COUNTDOWN_DELAY: Duration = Duration::from_secs(1);
+
+/// 松开后进入配网模式所需的按住时间
+const LONG_PRESS_PROVISIONING: Duration = Duration::from_secs(5);
+
+/// 恢复出厂设置所需的按住时间
+const LONG_PRESS_FACTORY_RESET: Duration = Duration::from_secs(10);
+
+/// 按住期间的采样周期
+const HOLD_POLL_INTERVAL: Duration = Duration::from_millis(50);
 
 pub static BOOT_BUTTON_ASYNC: EmbassyMutex<CriticalSectionRawMutex, Option<Input<'static>>> =
     EmbassyMutex::new(None);
@@ -11,3 +47,77 @@ pub async fn boot_button_init(button: impl InputPin + 'static) {
     BOOT_BUTTON_ASYNC.lock().await.replace(boot_button);
     info!("Boot button initialized")
 }
+
+/// BOOT 按键处理任务
+///
+/// 等待 BOOT 按键按下（下降沿），并测量按住的时间，动作见模块文档
+#[embassy_executor::task]
+pub async fn boot_button_task() {
+    let mut guard = BOOT_BUTTON_ASYNC.lock().await;
+    let Some(button) = guard.as_mut() else {
+        return;
+    };
+
+    loop {
+        button.wait_for_falling_edge().await;
+        let pressed_at = Instant::now();
+        // 屏幕上正在显示的剩余秒数
+        let mut shown: Option<u64> = None;
+        let mut countdown = Progress::new("button");
+
+        while button.is_low() {
+            let held = pressed_at.elapsed();
+            if held >= LONG_PRESS_FACTORY_RESET {
+                factory_reset().await;
+            }
+            if held >= COUNTDOWN_DELAY {
+                let (msg, target) = if held < LONG_PRESS_PROVISIONING {
+                    (Msg::ButtonSetupIn, LONG_PRESS_PROVISIONING)
+                } else {
+                    (Msg::ButtonResetIn, LONG_PRESS_FACTORY_RESET)
+                };
+                let remaining = (target - held).as_millis().div_ceil(1000);
+                if shown != Some(remaining) {
+                    let mut text: String<32> = String::new();
+                    write!(text, "{} {}s", i18n::lcd(msg), remaining).ok();
+                    let percent = held.as_millis() * 100 / LONG_PRESS_FACTORY_RESET.as_millis();
+                    countdown.update(percent as u8, &text);
+                    shown = Some(remaining);
+                }
+            }
+            Timer::after(HOLD_POLL_INTERVAL).await;
+        }
+
+        // 隐藏倒计时和进度条
+        drop(countdown);
+        let held = pressed_at.elapsed();
+        if held >= LONG_PRESS_PROVISIONING {
+            info!("BOOT button long press, entering provisioning mode");
+            system::reboot(RebootReason::Provisioning).await;
+        } else if held >= SHORT_PRESS_MIN {
+            toggle_screen().await;
+        }
+    }
+}
+
+/// 短按：开关 LCD 背光
+async fn toggle_screen() {
+    let on = !xl9555::lcd_backlight();
+    info!(
+        "BOOT button pressed, turning LCD backlight {}",
+        if on { "on" } else { "off" }
+    );
+    if let Err(err) = xl9555::set_lcd_backlight(on).await {
+        warn!("Failed to toggle LCD backlight: {}", err);
+    }
+}
+
+/// 擦除设置并重启
+async fn factory_reset() -> ! {
+    info!("BOOT button held, restoring factory settings");
+    // 擦除失败时仍然重启，设置保持不变
+    if let Err(err) = settings::erase() {
+        warn!("Factory reset failed: {}", err);
+    }
+    system::reboot(RebootReason::FactoryReset).await
+}
@@ -0,0 +1,74 @@
+//! 蜂鸣器
+//!
+//! 板载有源蜂鸣器接在 XL9555 的 P0.3（BEEP）上，低电平鸣响。本模块隐藏极性，
+//! 调用者只需给出鸣响和静音的时长：
+//!
+//! - [chirp]：短鸣一声，例如按键音
+//! - [pattern]：按 `[鸣响, 静音, 鸣响, ...]`（毫秒）的节奏鸣响，例如定时器到时提示
+//!
+//! 节奏由 [buzzer_task] 按顺序播放，每段播放完后总会关闭蜂鸣器，
+//! 调用者不需要也无法让蜂鸣器一直响着。
+
+use crate::xl9555;
+use defmt::warn;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::channel::Channel;
+use embassy_time::Timer;
+use heapless::Vec;
+
+/// 一段节奏最多包含的步数，超出部分被截断
+pub const MAX_PATTERN_LEN: usize = 16;
+
+/// 按键音时长（毫秒）
+pub const KEY_CLICK_MS: u16 = 15;
+
+/// 节奏队列长度
+const QUEUE_LEN: usize = 4;
+
+/// 节奏：交替的鸣响和静音时长（毫秒），从鸣响开始
+type Pattern = Vec<u16, MAX_PATTERN_LEN>;
+
+static PATTERNS: Channel<CriticalSectionRawMutex, Pattern, QUEUE_LEN> = Channel::new();
+
+/// 短鸣一声
+///
+/// # 参数
+/// * `ms` - 鸣响时长（毫秒）
+pub fn chirp(ms: u16) {
+    pattern(&[ms]);
+}
+
+/// 按节奏鸣响
+///
+/// 在当前节奏播放完后开始；蜂鸣器任务未运行或队列已满时丢弃
+///
+/// # 参数
+/// * `steps` - 交替的鸣响和静音时长（毫秒），从鸣响开始，最多 [MAX_PATTERN_LEN] 步
+pub fn pattern(steps: &[u16]) {
+    let len = steps.len().min(MAX_PATTERN_LEN);
+    // 长度已截断，不会失败
+    let steps = Vec::from_slice(&steps[..len]).unwrap_or_default();
+    PATTERNS.try_send(steps).ok();
+}
+
+/// 蜂鸣器任务
+///
+/// 依次播放 [chirp] 和 [pattern] 提交的节奏
+#[embassy_executor::task]
+pub async fn buzzer_task() {
+    loop {
+        let steps = PATTERNS.receive().await;
+        for (i, &ms) in steps.iter().enumerate() {
+            set(i % 2 == 0).await;
+            Timer::after_millis(ms as u64).await;
+        }
+        set(false).await;
+    }
+}
+
+/// 打开或关闭蜂鸣器
+async fn set(on: bool) {
+    if let Err(err) = xl9555::set_beep(on).await {
+        warn!("Failed to set buzzer: {}", err);
+    }
+}
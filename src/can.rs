@@ -0,0 +1,388 @@
+//! CAN 总线（TWAI 控制器）
+//!
+//! ESP32-S3 的 TWAI 控制器兼容 ISO 11898-1（CAN 2.0），需要外接收发器（例如 TJA1050、
+//! SN65HVD230），RX/TX 接扩展排针上的任意 GPIO。支持 11 位标准帧和 29 位扩展帧，
+//! 以及一组硬件验收滤波器（见 [Filter]）。
+//!
+//! 引脚和波特率是开发板描述中的 [Interface::Can](crate::board::Interface::Can) 接口，
+//! 例如 `board port can gpio4 gpio5 500`，重启后 app 按此创建控制器并启动 [can_task]；
+//! 没有分配引脚时不启动。验收滤波器保存在设置中，用命令行 `can filter` 修改，同样重启后生效。
+//!
+//! 控制器由 [can_task] 独占：
+//!
+//! - 发送：[send] 把帧放入发送队列，由任务依次发出
+//! - 接收：收到的帧通过 [subscribe] 发布，命令行的 `can sniff` 即订阅者之一
+//! - 错误：发送或接收错误过多时控制器进入 bus-off 状态，任务等待 [RECOVERY_DELAY]
+//!   后重启控制器恢复通信，恢复次数见 [status]
+//!
+//! ```ignore
+//! let bus = CanBus::new(peripherals.TWAI0, rx, tx, BaudRate::B500K, Filter::AcceptAll)?;
+//! spawner.spawn(can::can_task(bus))?;
+//! ```
+
+use core::cell::RefCell;
+use core::sync::atomic::{AtomicBool, Ordering};
+use critical_section::Mutex;
+use defmt::{info, warn};
+use embassy_futures::select::{Either, select};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::channel::Channel;
+use embassy_sync::pubsub::{PubSubChannel, Subscriber};
+use embassy_time::{Duration, Timer};
+use embedded_can::Frame as _;
+use esp_hal::Async;
+use esp_hal::gpio::interconnect::{PeripheralInput, PeripheralOutput};
+use esp_hal::twai::filter::{SingleExtendedFilter, SingleStandardFilter};
+use esp_hal::twai::{
+    self, BaudRate, EspTwaiError, EspTwaiFrame, ExtendedId, StandardId, Twai, TwaiConfiguration,
+    TwaiMode,
+};
+
+pub use esp_hal::twai::Id;
+
+/// 发送队列长度
+const TX_QUEUE_LEN: usize = 8;
+
+/// 接收事件队列长度
+const RX_QUEUE_LEN: usize = 16;
+
+/// 最多同时存在的接收订阅者数量
+const MAX_SUBSCRIBERS: usize = 2;
+
+/// 进入 bus-off 后等待多久再重启控制器
+pub const RECOVERY_DELAY: Duration = Duration::from_millis(100);
+
+/// CAN 错误
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum CanError {
+    /// 总线未启动
+    NotStarted,
+    /// ID 超出范围或数据超过 8 字节
+    InvalidFrame,
+    /// 发送队列已满
+    QueueFull,
+}
+
+/// 硬件验收滤波器
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Filter {
+    /// 接收所有帧
+    AcceptAll,
+    /// 只接收 `id & mask == code & mask` 的标准帧
+    Standard { code: u16, mask: u16 },
+    /// 只接收 `id & mask == code & mask` 的扩展帧
+    Extended { code: u32, mask: u32 },
+}
+
+impl Filter {
+    /// 编码为设置中的值：类型（0 接收所有，1 标准帧，2 扩展帧）、code 和 mask（小端）
+    pub const fn to_bytes(self) -> [u8; 9] {
+        let (kind, code, mask) = match self {
+            Filter::AcceptAll => (0, 0, 0),
+            Filter::Standard { code, mask } => (1, code as u32, mask as u32),
+            Filter::Extended { code, mask } => (2, code, mask),
+        };
+        let [c0, c1, c2, c3] = code.to_le_bytes();
+        let [m0, m1, m2, m3] = mask.to_le_bytes();
+        [kind, c0, c1, c2, c3, m0, m1, m2, m3]
+    }
+
+    /// 从设置中的编码解析，ID 超出范围或类型未知时接收所有帧
+    pub fn from_bytes(bytes: &[u8; 9]) -> Filter {
+        let code = u32::from_le_bytes([bytes[1], bytes[2], bytes[3], bytes[4]]);
+        let mask = u32::from_le_bytes([bytes[5], bytes[6], bytes[7], bytes[8]]);
+        Filter::new(bytes[0] == 2, code, mask)
+            .filter(|_| bytes[0] != 0)
+            .unwrap_or(Filter::AcceptAll)
+    }
+
+    /// 按帧类型创建滤波器
+    ///
+    /// # 返回
+    /// code 或 mask 超出 ID 范围时返回 None
+    pub fn new(extended: bool, code: u32, mask: u32) -> Option<Filter> {
+        if extended {
+            let valid = ExtendedId::new(code).is_some() && ExtendedId::new(mask).is_some();
+            valid.then_some(Filter::Extended { code, mask })
+        } else {
+            let code = u16::try_from(code).ok()?;
+            let mask = u16::try_from(mask).ok()?;
+            let valid = StandardId::new(code).is_some() && StandardId::new(mask).is_some();
+            valid.then_some(Filter::Standard { code, mask })
+        }
+    }
+}
+
+/// 一帧数据
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct Frame {
+    pub id: Id,
+    /// 远程帧（只有 DLC，没有数据）
+    pub remote: bool,
+    /// 数据长度（DLC）
+    pub len: u8,
+    data: [u8; 8],
+}
+
+impl Frame {
+    /// 创建数据帧
+    ///
+    /// # 参数
+    /// * `id` - 标准帧（不超过 0x7FF）或扩展帧 ID
+    /// * `extended` - 是否为扩展帧
+    /// * `data` - 数据，最多 8 字节
+    pub fn new(id: u32, extended: bool, data: &[u8]) -> Result<Frame, CanError> {
+        if data.len() > 8 {
+            return Err(CanError::InvalidFrame);
+        }
+        let mut frame = Frame {
+            id: make_id(id, extended)?,
+            remote: false,
+            len: data.len() as u8,
+            data: [0; 8],
+        };
+        frame.data[..data.len()].copy_from_slice(data);
+        Ok(frame)
+    }
+
+    /// 数据
+    pub fn data(&self) -> &[u8] {
+        if self.remote { &[] } else { &self.data[..self.len as usize] }
+    }
+
+    /// 原始 ID 和是否为扩展帧
+    pub fn raw_id(&self) -> (u32, bool) {
+        match self.id {
+            Id::Standard(id) => (id.as_raw() as u32, false),
+            Id::Extended(id) => (id.as_raw(), true),
+        }
+    }
+
+    fn from_twai(frame: &EspTwaiFrame) -> Frame {
+        let mut data = [0; 8];
+        let payload = frame.data();
+        data[..payload.len()].copy_from_slice(payload);
+        Frame {
+            id: frame.id().into(),
+            remote: frame.is_remote_frame(),
+            len: frame.dlc() as u8,
+            data,
+        }
+    }
+
+    fn to_twai(self) -> Option<EspTwaiFrame> {
+        if self.remote {
+            EspTwaiFrame::new_remote(self.id, self.len as usize)
+        } else {
+            EspTwaiFrame::new(self.id, self.data())
+        }
+    }
+}
+
+/// 按 kbit/s 选择波特率，只支持 125、250、500 和 1000
+pub fn baudrate(kbps: u32) -> Option<BaudRate> {
+    match kbps {
+        125 => Some(BaudRate::B125K),
+        250 => Some(BaudRate::B250K),
+        500 => Some(BaudRate::B500K),
+        1000 => Some(BaudRate::B1000K),
+        _ => None,
+    }
+}
+
+fn make_id(raw: u32, extended: bool) -> Result<Id, CanError> {
+    let id = if extended {
+        ExtendedId::new(raw).map(Id::Extended)
+    } else {
+        u16::try_from(raw).ok().and_then(StandardId::new).map(Id::Standard)
+    };
+    id.ok_or(CanError::InvalidFrame)
+}
+
+/// 总线统计
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, defmt::Format)]
+pub struct Status {
+    pub rx_frames: u32,
+    pub tx_frames: u32,
+    /// 发送失败（bus-off 时被丢弃）的帧数
+    pub tx_failed: u32,
+    /// 接收 FIFO 溢出次数
+    pub overruns: u32,
+    /// bus-off 恢复次数
+    pub recoveries: u32,
+    /// 控制器的接收错误计数
+    pub rx_error_count: u8,
+    /// 控制器的发送错误计数
+    pub tx_error_count: u8,
+}
+
+/// 接收帧订阅者
+pub type FrameSubscriber =
+    Subscriber<'static, CriticalSectionRawMutex, Frame, RX_QUEUE_LEN, MAX_SUBSCRIBERS, 1>;
+
+static RX_FRAMES: PubSubChannel<CriticalSectionRawMutex, Frame, RX_QUEUE_LEN, MAX_SUBSCRIBERS, 1> =
+    PubSubChannel::new();
+
+static TX_QUEUE: Channel<CriticalSectionRawMutex, Frame, TX_QUEUE_LEN> = Channel::new();
+
+static RUNNING: AtomicBool = AtomicBool::new(false);
+
+static STATUS: Mutex<RefCell<Status>> = Mutex::new(RefCell::new(Status {
+    rx_frames: 0,
+    tx_frames: 0,
+    tx_failed: 0,
+    overruns: 0,
+    recoveries: 0,
+    rx_error_count: 0,
+    tx_error_count: 0,
+}));
+
+/// 总线是否已启动
+pub fn is_running() -> bool {
+    RUNNING.load(Ordering::Relaxed)
+}
+
+/// 当前统计
+pub fn status() -> Status {
+    critical_section::with(|cs| *STATUS.borrow_ref(cs))
+}
+
+fn update_status(f: impl FnOnce(&mut Status)) {
+    critical_section::with(|cs| f(&mut STATUS.borrow_ref_mut(cs)));
+}
+
+/// 发送一帧（放入发送队列，不等待发出）
+pub fn send(frame: Frame) -> Result<(), CanError> {
+    if !is_running() {
+        return Err(CanError::NotStarted);
+    }
+    TX_QUEUE.try_send(frame).map_err(|_| CanError::QueueFull)
+}
+
+/// 订阅接收的帧
+///
+/// # 返回
+/// 订阅者数量已满时返回 None
+pub fn subscribe() -> Option<FrameSubscriber> {
+    RX_FRAMES.subscriber().ok()
+}
+
+/// 已配置的 CAN 控制器
+pub struct CanBus {
+    twai: Twai<'static, Async>,
+}
+
+impl CanBus {
+    /// 配置并启动控制器
+    ///
+    /// # 参数
+    /// * `twai` - TWAI 外设
+    /// * `rx`, `tx` - 接收发器的引脚
+    /// * `baudrate` - 波特率
+    /// * `filter` - 验收滤波器
+    pub fn new(
+        twai: impl twai::Instance + 'static,
+        rx: impl PeripheralInput<'static>,
+        tx: impl PeripheralOutput<'static>,
+        baudrate: BaudRate,
+        filter: Filter,
+    ) -> Result<Self, CanError> {
+        let mut config = TwaiConfiguration::new(twai, rx, tx, baudrate, TwaiMode::Normal);
+        match filter {
+            Filter::AcceptAll => {}
+            Filter::Standard { code, mask } => {
+                let (Some(code), Some(mask)) = (StandardId::new(code), StandardId::new(mask)) else {
+                    return Err(CanError::InvalidFrame);
+                };
+                config.set_filter(SingleStandardFilter::new_from_code_mask(
+                    code,
+                    mask,
+                    false,
+                    false,
+                    [0; 2],
+                    [0; 2],
+                ));
+            }
+            Filter::Extended { code, mask } => {
+                let (Some(code), Some(mask)) = (ExtendedId::new(code), ExtendedId::new(mask)) else {
+                    return Err(CanError::InvalidFrame);
+                };
+                let filter = SingleExtendedFilter::new_from_code_mask(code, mask, false, false);
+                config.set_filter(filter);
+            }
+        }
+        info!("CAN started at {}, filter {}", baudrate, filter);
+        Ok(CanBus {
+            twai: config.into_async().start(),
+        })
+    }
+}
+
+/// CAN 任务
+///
+/// # 参数
+/// * `bus` - 已启动的控制器
+#[embassy_executor::task]
+pub async fn can_task(bus: CanBus) {
+    let mut twai = bus.twai;
+    RUNNING.store(true, Ordering::Relaxed);
+
+    loop {
+        let bus_off = match select(TX_QUEUE.receive(), twai.receive_async()).await {
+            Either::First(frame) => transmit(&mut twai, frame).await,
+            Either::Second(Ok(frame)) => {
+                let frame = Frame::from_twai(&frame);
+                update_status(|status| status.rx_frames += 1);
+                RX_FRAMES.immediate_publisher().publish_immediate(frame);
+                false
+            }
+            Either::Second(Err(EspTwaiError::BusOff)) => true,
+            Either::Second(Err(EspTwaiError::EmbeddedHAL(twai::ErrorKind::Overrun))) => {
+                update_status(|status| status.overruns += 1);
+                false
+            }
+            Either::Second(Err(err)) => {
+                warn!("CAN receive failed: {}", err);
+                false
+            }
+        };
+
+        let (rx_errors, tx_errors) = (twai.receive_error_count(), twai.transmit_error_count());
+        update_status(|status| {
+            status.rx_error_count = rx_errors;
+            status.tx_error_count = tx_errors;
+        });
+
+        if bus_off || twai.is_bus_off() {
+            warn!("CAN bus-off, recovering in {} ms", RECOVERY_DELAY.as_millis());
+            Timer::after(RECOVERY_DELAY).await;
+            // 退出再进入工作模式即触发 bus-off 恢复流程，错误计数清零
+            twai = twai.stop().start();
+            update_status(|status| status.recoveries += 1);
+        }
+    }
+}
+
+/// 发送一帧
+///
+/// # 返回
+/// 是否因 bus-off 而失败
+async fn transmit(twai: &mut Twai<'static, Async>, frame: Frame) -> bool {
+    let Some(frame) = frame.to_twai() else {
+        update_status(|status| status.tx_failed += 1);
+        return false;
+    };
+    match twai.transmit_async(&frame).await {
+        Ok(()) => {
+            update_status(|status| status.tx_frames += 1);
+            false
+        }
+        Err(err) => {
+            update_status(|status| status.tx_failed += 1);
+            if err != EspTwaiError::BusOff {
+                warn!("CAN transmit failed: {}", err);
+            }
+            err == EspTwaiError::BusOff
+        }
+    }
+}
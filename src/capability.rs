@@ -0,0 +1,145 @@
+use crate::settings;
+use defmt::{info, warn};
+
+/// 子系统能力管理
+///
+/// 记录各个子系统（WiFi、BLE、摄像头、音频、SD 卡、显示）是否启用，
+/// 启用状态保存在持久化设置中，主程序根据该配置决定初始化哪些子系统。
+///
+/// 运行时修改的状态会立即写入 Flash，在下一次启动时生效。
+///
+/// # 使用方法
+///
+/// 1. 启动时调用 [crate::settings::load] 加载设置
+/// 2. 调用 [is_enabled] 判断子系统是否需要初始化
+/// 3. 调用 [set_enabled] 在运行时开关子系统
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Capability {
+    Wifi,
+    Ble,
+    Camera,
+    Audio,
+    Sd,
+    Display,
+}
+
+impl Capability {
+    /// 所有子系统
+    pub const ALL: [Capability; 6] = [
+        Capability::Wifi,
+        Capability::Ble,
+        Capability::Camera,
+        Capability::Audio,
+        Capability::Sd,
+        Capability::Display,
+    ];
+
+    /// 子系统在位图中对应的位
+    const fn bit(self) -> u8 {
+        1 << self as u8
+    }
+
+    /// 子系统名称
+    pub const fn name(self) -> &'static str {
+        match self {
+            Capability::Wifi => "wifi",
+            Capability::Ble => "ble",
+            Capability::Camera => "camera",
+            Capability::Audio => "audio",
+            Capability::Sd => "sd",
+            Capability::Display => "display",
+        }
+    }
+
+    /// 当前固件是否包含该子系统的驱动，SD 卡和显示取决于 `sd`、`ui` feature
+    pub const fn is_supported(self) -> bool {
+        matches!(self, Capability::Wifi)
+            || (matches!(self, Capability::Sd) && cfg!(feature = "sd"))
+            || (matches!(self, Capability::Display) && cfg!(feature = "ui"))
+    }
+}
+
+/// 子系统启用位图
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct Capabilities(u8);
+
+impl Capabilities {
+    /// 默认启用所有子系统
+    pub const DEFAULT: Capabilities = Capabilities(0x3F);
+
+    /// 所有已定义子系统的位，旧固件中 Matter 使用的第 6 位被忽略
+    const MASK: u8 = 0x3F;
+
+    /// 从位图创建
+    pub const fn from_bits(bits: u8) -> Self {
+        Capabilities(bits & Self::MASK)
+    }
+
+    /// 获取位图
+    pub const fn bits(self) -> u8 {
+        self.0
+    }
+
+    /// 判断子系统是否启用
+    pub const fn contains(self, capability: Capability) -> bool {
+        self.0 & capability.bit() != 0
+    }
+
+    /// 设置子系统启用状态
+    pub fn set(&mut self, capability: Capability, enabled: bool) {
+        if enabled {
+            self.0 |= capability.bit();
+        } else {
+            self.0 &= !capability.bit();
+        }
+    }
+}
+
+/// 获取当前的子系统启用位图
+pub fn current() -> Capabilities {
+    Capabilities::from_bits(settings::get().capabilities)
+}
+
+/// 判断子系统是否启用
+///
+/// # 参数
+/// * `capability` - 子系统
+pub fn is_enabled(capability: Capability) -> bool {
+    current().contains(capability)
+}
+
+/// 设置子系统启用状态并保存到 Flash
+///
+/// 修改在下一次启动时生效
+///
+/// # 参数
+/// * `capability` - 子系统
+/// * `enabled` - true 表示启用，false 表示禁用
+pub fn set_enabled(capability: Capability, enabled: bool) {
+    settings::update(|s| {
+        let mut caps = Capabilities::from_bits(s.capabilities);
+        caps.set(capability, enabled);
+        s.capabilities = caps.bits();
+    });
+    match settings::save() {
+        Ok(()) => info!(
+            "Capability {} {}, takes effect after reboot",
+            capability.name(),
+            if enabled { "enabled" } else { "disabled" }
+        ),
+        Err(err) => warn!("Failed to persist capability {}: {}", capability.name(), err),
+    }
+}
+
+/// 打印所有子系统的启用状态
+pub fn log_summary() {
+    let caps = current();
+    for capability in Capability::ALL {
+        let state = match (caps.contains(capability), capability.is_supported()) {
+            (true, true) => "enabled",
+            (true, false) => "enabled (no driver in this build)",
+            (false, _) => "disabled",
+        };
+        info!("Capability {}: {}", capability.name(), state);
+    }
+}
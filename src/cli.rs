@@ -0,0 +1,2050 @@
+//! 命令行
+//!
+//! 控制台输入的一行文本按空格拆分为命令和参数，由 [execute] 分发执行。
+//! 输入 `help` 查看所有命令，命令输出按设置中的语言显示（见 [crate::i18n]）。
+
+use crate::access::UnlockError;
+use crate::assets;
+use crate::board::{self, Board, Interface, PinMap, Signal, Variant, Wiring};
+use crate::capability::{self, Capability};
+#[cfg(feature = "ui")]
+use crate::clock::Face;
+use crate::console::{self, Backend, Writer};
+use crate::forecast::{self, Provider};
+use crate::i18n::{self, Language, Msg};
+use crate::keymap::{self, Action};
+use crate::notifier::{self, Format};
+#[cfg(feature = "sd")]
+use crate::outbox;
+#[cfg(all(feature = "sd", feature = "ui"))]
+use crate::photo::{self, Transition};
+use crate::power::{self, Load};
+use crate::profile::{self, Profile};
+use crate::registry::{self, Peripheral};
+#[cfg(feature = "ui")]
+use crate::render::{Command, Screen};
+use crate::rules::{self, RulesError};
+use crate::service::{self, Service};
+#[cfg(feature = "ui")]
+use crate::st7789::{self, PanelInfo};
+use crate::system::{self, RebootReason};
+use crate::theme::{self, Mode};
+#[cfg(feature = "ui")]
+use crate::tuning::{self, CONTRAST_MAX, Curve};
+use crate::wallclock::{self, DateTime, TimeSource};
+#[cfg(feature = "fault-injection")]
+use crate::fault;
+use crate::{
+    access, bridge, can, crash, device, dmx, espnow, http_client, jitter, lin, logbuf, modbus, mqtt,
+    net, ota, pid, presence, rc, relay, sas, scheduler, sensor, serial, settings, syslog, thermostat,
+    wifi,
+};
+#[cfg(feature = "ui")]
+use crate::{bench, render};
+use core::fmt::Write;
+use core::net::SocketAddrV4;
+use embassy_futures::select::select;
+use embassy_net::{IpAddress, IpEndpoint};
+use embassy_time::{Duration, Instant, with_deadline};
+#[cfg(feature = "ui")]
+use ui::frame;
+
+/// `can sniff` 默认的监听时长（秒）
+const CAN_SNIFF_SECS: u64 = 10;
+
+/// `can sniff` 最长的监听时长（秒）
+const CAN_SNIFF_MAX_SECS: u64 = 600;
+
+/// `rc watch` 默认的监听时长（秒）
+const RC_WATCH_SECS: u64 = 10;
+
+/// `rc watch` 最长的监听时长（秒）
+const RC_WATCH_MAX_SECS: u64 = 600;
+
+/// `rc watch` 输出的最小间隔，接收机每秒上百帧，逐帧输出会刷屏
+const RC_WATCH_INTERVAL: Duration = Duration::from_millis(200);
+
+/// 早于此时刻（2020-01-01）的同步修改时间是未校时时的计数，不按日期显示
+const SYNC_STAMP_EPOCH: u32 = 1_577_836_800;
+
+/// `log dump` 每行输出的字节数
+const LOG_DUMP_LINE: usize = 32;
+
+/// 命令提示符
+pub const PROMPT: &str = "esp> ";
+
+/// 执行一行命令
+///
+/// # 参数
+/// * `line` - 去掉首尾空白的命令行
+/// * `out` - 命令输出
+pub async fn execute(line: &str, out: &mut Writer) {
+    let mut args = line.split_whitespace();
+    let Some(command) = args.next() else {
+        return;
+    };
+    if is_protected(command, args.clone().next()) && !access::is_unlocked() {
+        writeln!(out, "{}\r", i18n::tr(Msg::CliLocked)).ok();
+        return;
+    }
+
+    match (command, args.next()) {
+        ("help", _) => {
+            out.write_str(i18n::tr(Msg::CliHelp)).ok();
+        }
+        ("uptime", _) => {
+            let secs = Instant::now().as_secs();
+            let (days, hours) = (secs / 86400, secs / 3600 % 24);
+            let (minutes, seconds) = (secs / 60 % 60, secs % 60);
+            writeln!(out, "{}d {:02}:{:02}:{:02}\r", days, hours, minutes, seconds).ok();
+        }
+        ("status", _) => print_status(out),
+        ("reboot", _) => system::reboot(RebootReason::UserRequest).await,
+        ("sleep", Some(seconds)) => match seconds.parse::<u32>() {
+            Ok(seconds @ 1..) => system::deep_sleep(Duration::from_secs(seconds.into())).await,
+            _ => {
+                writeln!(out, "{}\r", i18n::tr(Msg::CliSleepUsage)).ok();
+            }
+        },
+        ("unlock", pin) => {
+            match access::unlock(pin.unwrap_or("")).await {
+                Ok(()) => writeln!(out, "{}\r", i18n::tr(Msg::CliUnlocked)),
+                Err(UnlockError::WrongPin) => writeln!(out, "{}\r", i18n::tr(Msg::CliWrongPin)),
+                Err(UnlockError::LockedOut(secs)) => {
+                    writeln!(out, "{} ({} s)\r", i18n::tr(Msg::CliLockedOut), secs)
+                }
+            }
+            .ok();
+        }
+        ("lock", _) => access::lock(),
+        ("auth", None) => {
+            let s = settings::get();
+            let state = |set: bool| if set { "set" } else { "off" };
+            writeln!(out, "token: {}\r", state(!s.http_token.is_empty())).ok();
+            writeln!(out, "pin: {}\r", state(!s.pin.is_empty())).ok();
+        }
+        ("auth", Some(kind)) => {
+            let value = match args.next() {
+                Some("off") => Some(""),
+                value => value,
+            };
+            let stored = match (kind, value) {
+                ("token", Some(token)) if token.is_empty() || access::is_valid_token(token) => {
+                    token
+                        .try_into()
+                        .map(|token| settings::update(|s| s.http_token = token))
+                        .is_ok()
+                }
+                ("pin", Some(pin)) if pin.is_empty() || access::is_valid_pin(pin) => pin
+                    .try_into()
+                    .map(|pin| settings::update(|s| s.pin = pin))
+                    .is_ok(),
+                _ => false,
+            };
+            if !stored {
+                writeln!(out, "{}\r", i18n::tr(Msg::CliAuthUsage)).ok();
+                return;
+            }
+            save_settings(out);
+        }
+        // 原始的 defmt 帧会扰乱终端，以十六进制输出，在主机上用 `scripts/log-ring.sh -f` 解码
+        ("log", Some("dump")) => {
+            for line in logbuf::snapshot().chunks(LOG_DUMP_LINE) {
+                for byte in line {
+                    write!(out, "{:02x}", byte).ok();
+                }
+                writeln!(out, "\r").ok();
+            }
+        }
+        ("log", Some("clear")) => logbuf::clear(),
+        ("crash", None) => match crash::last() {
+            Some(record) => {
+                writeln!(out, "uptime_ms: {}\r", record.uptime_ms).ok();
+                writeln!(out, "message: {}\r", record.message()).ok();
+                let regs = record.registers;
+                if regs.is_exception() {
+                    writeln!(
+                        out,
+                        "exccause: {} pc: {:#010x} ps: {:#010x}\r",
+                        regs.cause, regs.pc, regs.ps
+                    )
+                    .ok();
+                    writeln!(
+                        out,
+                        "a0: {:#010x} sp: {:#010x} excvaddr: {:#010x}\r",
+                        regs.a0, regs.sp, regs.excvaddr
+                    )
+                    .ok();
+                }
+                write!(out, "backtrace:").ok();
+                for pc in record.backtrace() {
+                    write!(out, " {:#010x}", pc).ok();
+                }
+                writeln!(out, "\r").ok();
+            }
+            None => {
+                writeln!(out, "{}\r", i18n::tr(Msg::CliNoCrashRecord)).ok();
+            }
+        },
+        ("crash", Some("clear")) => {
+            match crash::clear() {
+                Ok(()) => writeln!(out, "{}\r", i18n::tr(Msg::CliCrashCleared)),
+                Err(err) => {
+                    writeln!(out, "{}: {:?}\r", i18n::tr(Msg::CliCrashClearFailed), err)
+                }
+            }
+            .ok();
+        }
+        ("jitter", _) => {
+            for line in jitter::format_report().lines() {
+                writeln!(out, "{}\r", line).ok();
+            }
+        }
+        #[cfg(feature = "ui")]
+        ("bench", _) => {
+            let report = bench::format_report();
+            if report.is_empty() {
+                writeln!(out, "{}\r", i18n::tr(Msg::CliBenchNoResults)).ok();
+            }
+            for line in report.lines() {
+                writeln!(out, "{}\r", line).ok();
+            }
+        }
+        #[cfg(feature = "fault-injection")]
+        ("fault", None) => {
+            for bus in fault::Bus::ALL {
+                match fault::status(bus) {
+                    (Some((kind, every)), injected) => writeln!(
+                        out,
+                        "{}: {} every {} (injected {})\r",
+                        bus.name(),
+                        fault::fault_name(kind),
+                        every,
+                        injected
+                    ),
+                    (None, injected) => {
+                        writeln!(out, "{}: off (injected {})\r", bus.name(), injected)
+                    }
+                }
+                .ok();
+            }
+        }
+        #[cfg(feature = "fault-injection")]
+        ("fault", Some("off")) => fault::clear(),
+        #[cfg(feature = "fault-injection")]
+        ("fault", Some(name)) => {
+            let bus = fault::Bus::ALL.into_iter().find(|bus| bus.name() == name);
+            let kind = args.next().and_then(|name| {
+                [fault::Fault::Nack, fault::Fault::Timeout, fault::Fault::CorruptRead]
+                    .into_iter()
+                    .find(|&kind| fault::fault_name(kind) == name)
+            });
+            let every = args.next().and_then(|n| n.parse().ok());
+            let applied = match (bus, kind, every) {
+                (Some(bus), Some(kind), Some(every)) => fault::set(bus, kind, every),
+                _ => false,
+            };
+            if !applied {
+                writeln!(out, "{}\r", i18n::tr(Msg::CliFaultUsage)).ok();
+            }
+        }
+        ("cap", None) => {
+            for capability in Capability::ALL {
+                let state = if capability::is_enabled(capability) { "on" } else { "off" };
+                writeln!(out, "{}: {}\r", capability.name(), state).ok();
+            }
+        }
+        ("peripherals", None | Some("list")) => {
+            for peripheral in Peripheral::ALL {
+                let entry = registry::entry(peripheral);
+                writeln!(
+                    out,
+                    "{:<8}{:<6}{:<11}{:<9}{}\r",
+                    peripheral.name(),
+                    peripheral.bus().name(),
+                    peripheral.location(),
+                    entry.state.name(),
+                    entry.last_error.unwrap_or("-")
+                )
+                .ok();
+            }
+        }
+        ("board", None) => {
+            let board = board::current();
+            writeln!(out, "board: {}\r", board.variant.name()).ok();
+            for signal in Signal::ALL {
+                writeln!(out, "  {}: gpio{}\r", signal.name(), board.pins.get(signal)).ok();
+            }
+            write!(out, "  fitted:").ok();
+            for peripheral in board::OPTIONAL.into_iter().filter(|&p| board.has(p)) {
+                write!(out, " {}", peripheral.name()).ok();
+            }
+            writeln!(out, "\r").ok();
+            for interface in Interface::ALL {
+                let Some(wiring) = board.port(interface) else {
+                    continue;
+                };
+                write!(out, "  port {}:", interface.name()).ok();
+                for (signal, gpio) in interface.signals().iter().zip(wiring.pins) {
+                    write!(out, " {}=gpio{}", signal, gpio).ok();
+                }
+                if !interface.param_name().is_empty() {
+                    write!(out, " {} {}", wiring.param, interface.param_name()).ok();
+                }
+                writeln!(out, "\r").ok();
+            }
+            let configured = Variant::from_u8(settings::get().board);
+            if configured != board.variant {
+                writeln!(out, "after reboot: {}\r", configured.name()).ok();
+            }
+        }
+        ("board", Some("pin")) => {
+            let signal = args
+                .next()
+                .and_then(|name| Signal::ALL.into_iter().find(|s| s.name() == name));
+            let gpio = args
+                .next()
+                .and_then(|value| value.trim_start_matches("gpio").parse::<u8>().ok());
+            let mut pins = PinMap(settings::get().board_pins);
+            if let (Some(signal), Some(gpio)) = (signal, gpio) {
+                pins.0[signal as usize] = gpio;
+            }
+            if signal.is_none() || gpio.is_none() || !pins.is_valid() {
+                writeln!(out, "{}\r", i18n::tr(Msg::CliBoardUsage)).ok();
+                return;
+            }
+            settings::update(|s| s.board_pins = pins.0);
+            save_settings(out);
+        }
+        ("board", Some("has")) => {
+            let peripheral = args
+                .next()
+                .and_then(|name| board::OPTIONAL.into_iter().find(|p| p.name() == name));
+            let fitted = args.next().filter(|&v| v == "on" || v == "off");
+            let (Some(peripheral), Some(fitted)) = (peripheral, fitted) else {
+                writeln!(out, "{}\r", i18n::tr(Msg::CliBoardUsage)).ok();
+                return;
+            };
+            let bit = board::present_bit(peripheral);
+            settings::update(|s| {
+                if fitted == "on" {
+                    s.board_present |= bit;
+                } else {
+                    s.board_present &= !bit;
+                }
+            });
+            save_settings(out);
+        }
+        ("board", Some("port")) => {
+            let interface = args
+                .next()
+                .and_then(|name| Interface::ALL.into_iter().find(|i| i.name() == name));
+            let Some(interface) = interface else {
+                writeln!(out, "{}\r", i18n::tr(Msg::CliBoardPortUsage)).ok();
+                return;
+            };
+            let Some(wiring) = parse_port(interface, &mut args) else {
+                writeln!(out, "{}\r", i18n::tr(Msg::CliBoardPortUsage)).ok();
+                return;
+            };
+            let mut s = settings::get();
+            s.board_ports[interface as usize] = wiring;
+            let board = Board::from_settings(
+                Variant::from_u8(s.board),
+                s.board_pins,
+                s.board_present,
+                s.board_ports,
+            );
+            if wiring.is_assigned() && board.and_then(|b| b.port(interface)).is_none() {
+                writeln!(out, "{}\r", i18n::tr(Msg::CliBoardPortUsage)).ok();
+                return;
+            }
+            settings::update(|s| s.board_ports[interface as usize] = wiring);
+            save_settings(out);
+        }
+        ("board", Some(name)) => {
+            let Some(variant) = Variant::ALL.into_iter().find(|v| v.name() == name) else {
+                writeln!(out, "{}\r", i18n::tr(Msg::CliBoardUsage)).ok();
+                return;
+            };
+            settings::update(|s| s.board = variant.to_u8());
+            save_settings(out);
+        }
+        ("power", None) => {
+            let config = power::Config::current();
+            writeln!(out, "stagger: {} ms\r", config.stagger.as_millis()).ok();
+            writeln!(out, "budget: {} mA\r", config.budget).ok();
+            for load in Load::ALL {
+                write!(out, "  {:<10}{:>4} mA", load.name(), load.inrush_ma()).ok();
+                match power::started(load) {
+                    Some(at) => writeln!(out, "  on at {} ms\r", at.as_millis()),
+                    None => writeln!(out, "\r"),
+                }
+                .ok();
+            }
+        }
+        ("power", Some("stagger")) => {
+            let stagger = args.next().and_then(|value| value.parse::<u16>().ok());
+            let Some(stagger) = stagger.filter(|&ms| ms <= power::MAX_STAGGER_MS) else {
+                writeln!(out, "{}\r", i18n::tr(Msg::CliPowerUsage)).ok();
+                return;
+            };
+            settings::update(|s| s.power_stagger = stagger);
+            save_settings(out);
+        }
+        ("power", Some("budget")) => {
+            let budget = args.next().and_then(|value| value.parse::<u16>().ok());
+            let range = power::MIN_BUDGET_MA..=power::MAX_BUDGET_MA;
+            let Some(budget) = budget.filter(|ma| range.contains(ma)) else {
+                writeln!(out, "{}\r", i18n::tr(Msg::CliPowerUsage)).ok();
+                return;
+            };
+            settings::update(|s| s.power_budget = budget);
+            save_settings(out);
+        }
+        ("power", Some(_)) => {
+            writeln!(out, "{}\r", i18n::tr(Msg::CliPowerUsage)).ok();
+        }
+        ("modbus", None) => {
+            writeln!(out, "unit: {}\r", settings::get().modbus_unit).ok();
+        }
+        ("modbus", Some("unit")) => {
+            let unit = args.next().and_then(|value| value.parse::<u8>().ok());
+            let Some(unit) = unit.filter(|unit| modbus::UNITS.contains(unit)) else {
+                writeln!(out, "{}\r", i18n::tr(Msg::CliModbusUsage)).ok();
+                return;
+            };
+            settings::update(|s| s.modbus_unit = unit);
+            save_settings(out);
+        }
+        ("modbus", Some(_)) => {
+            writeln!(out, "{}\r", i18n::tr(Msg::CliModbusUsage)).ok();
+        }
+        ("service", None) => {
+            for service in Service::ALL.into_iter().filter(|s| s.is_available()) {
+                let state = if service::is_running(service) {
+                    "running"
+                } else {
+                    "stopped"
+                };
+                writeln!(out, "{:<10}{}\r", service.name(), state).ok();
+            }
+        }
+        ("service", Some(name)) => {
+            let target = Service::from_name(name).filter(|s| s.is_available());
+            match (target, args.next()) {
+                (Some(service), Some("start")) if !service::start(service) => {
+                    writeln!(out, "already running\r").ok();
+                }
+                (Some(service), Some("stop")) if !service::stop(service) => {
+                    writeln!(out, "already stopped\r").ok();
+                }
+                (Some(_), Some("start" | "stop")) => {}
+                _ => {
+                    writeln!(out, "{}\r", i18n::tr(Msg::CliServiceUsage)).ok();
+                }
+            }
+        }
+        ("presence", None) => {
+            let enabled = if presence::is_enabled() { "on" } else { "off" };
+            let threshold = presence::threshold();
+            writeln!(out, "sensing: {enabled}, threshold {threshold:.1} dB^2\r").ok();
+            if let Some(variance) = presence::variance() {
+                let state = if presence::motion() {
+                    "motion"
+                } else {
+                    "quiet"
+                };
+                writeln!(out, "variance: {variance:.1} dB^2, {state}\r").ok();
+            }
+        }
+        ("presence", Some("on")) => presence::set_enabled(true),
+        ("presence", Some("off")) => presence::set_enabled(false),
+        ("presence", Some("threshold")) => {
+            let threshold = args.next().and_then(|value| value.parse::<f32>().ok());
+            let range = presence::MIN_THRESHOLD..=presence::MAX_THRESHOLD;
+            match threshold.filter(|threshold| range.contains(threshold)) {
+                Some(threshold) => presence::set_threshold(threshold),
+                None => {
+                    writeln!(out, "{}\r", i18n::tr(Msg::CliPresenceUsage)).ok();
+                }
+            }
+        }
+        ("presence", Some(_)) => {
+            writeln!(out, "{}\r", i18n::tr(Msg::CliPresenceUsage)).ok();
+        }
+        ("serial", None) => {
+            let tap = if serial::tap() { "on" } else { "off" };
+            writeln!(out, "tap: {tap}\r").ok();
+        }
+        ("serial", Some("tap")) => match args.next() {
+            Some("on") => serial::set_tap(true),
+            Some("off") => serial::set_tap(false),
+            _ => {
+                writeln!(out, "{}\r", i18n::tr(Msg::CliSerialUsage)).ok();
+            }
+        },
+        ("serial", Some(_)) => {
+            writeln!(out, "{}\r", i18n::tr(Msg::CliSerialUsage)).ok();
+        }
+        ("bridge", None) => match bridge::Setup::current() {
+            Some(setup) => {
+                write!(out, "bridge: {} ", setup.interface.name()).ok();
+                match setup.mode {
+                    bridge::Mode::Server { port } => writeln!(out, "server port {}\r", port),
+                    bridge::Mode::Client { remote } => writeln!(out, "client {}\r", remote),
+                }
+                .ok();
+            }
+            None => {
+                writeln!(out, "bridge: off\r").ok();
+            }
+        },
+        ("bridge", Some(interface)) => {
+            let setup = match interface {
+                "off" => Some(bridge::Setup::OFF),
+                _ => parse_bridge(interface, args.next(), args.next()).map(|s| s.to_bytes()),
+            };
+            let Some(setup) = setup.filter(|_| args.next().is_none()) else {
+                writeln!(out, "{}\r", i18n::tr(Msg::CliBridgeUsage)).ok();
+                return;
+            };
+            settings::update(|s| s.bridge = setup);
+            save_settings(out);
+        }
+        ("assets", None) => {
+            let summary = assets::summary();
+            let font = if summary.font { "bundle" } else { "built-in" };
+            writeln!(out, "font: {font}\r").ok();
+            writeln!(out, "cjk font: {} glyphs\r", summary.cjk_glyphs).ok();
+            writeln!(out, "icons: {}\r", summary.icons).ok();
+            writeln!(out, "boot sound: {} steps\r", summary.boot_sound).ok();
+            writeln!(out, "texts: {}\r", summary.texts).ok();
+        }
+        ("cap", Some(name)) => {
+            let target = Capability::ALL.into_iter().find(|c| c.name() == name);
+            match (target, args.next()) {
+                (Some(capability), Some("on")) => capability::set_enabled(capability, true),
+                (Some(capability), Some("off")) => capability::set_enabled(capability, false),
+                _ => {
+                    writeln!(out, "{}\r", i18n::tr(Msg::CliCapUsage)).ok();
+                }
+            }
+        }
+        ("wifi", None) => {
+            let profiles = wifi::profiles();
+            if profiles.is_empty() {
+                writeln!(out, "{}\r", i18n::tr(Msg::CliWifiNone)).ok();
+            }
+            for profile in profiles {
+                writeln!(out, "{:>3} {}\r", profile.priority, profile.ssid).ok();
+            }
+            if let Some(best) = wifi::best_known_network() {
+                let (ssid, rssi) = (best.ssid, best.signal_strength);
+                writeln!(out, "in range: {} ({} dBm)\r", ssid, rssi).ok();
+            }
+        }
+        ("wifi", Some("forget")) => {
+            let Some(ssid) = args.next() else {
+                writeln!(out, "{}\r", i18n::tr(Msg::CliWifiUsage)).ok();
+                return;
+            };
+            let mut found = false;
+            settings::update(|s| {
+                let before = s.wifi_profiles.len();
+                s.wifi_profiles.retain(|p| p.ssid != ssid);
+                found = s.wifi_profiles.len() != before;
+            });
+            if !found {
+                writeln!(out, "{}\r", i18n::tr(Msg::CliWifiNotSaved)).ok();
+                return;
+            }
+            save_settings(out);
+        }
+        ("wifi", Some("priority")) => {
+            let (ssid, priority) = (args.next(), args.next().and_then(|p| p.parse::<u8>().ok()));
+            let (Some(ssid), Some(priority)) = (ssid, priority) else {
+                writeln!(out, "{}\r", i18n::tr(Msg::CliWifiUsage)).ok();
+                return;
+            };
+            let mut found = false;
+            settings::update(|s| {
+                if let Some(profile) = s.wifi_profiles.iter_mut().find(|p| p.ssid == ssid) {
+                    profile.priority = priority;
+                    found = true;
+                }
+            });
+            if !found {
+                writeln!(out, "{}\r", i18n::tr(Msg::CliWifiNotSaved)).ok();
+                return;
+            }
+            save_settings(out);
+        }
+        ("wifi", Some(ssid)) => {
+            let password = args.next().unwrap_or("");
+            let (Ok(ssid), Ok(password)) = (ssid.try_into(), password.try_into()) else {
+                writeln!(out, "{}\r", i18n::tr(Msg::CliWifiTooLong)).ok();
+                return;
+            };
+            wifi::save_profile(ssid, password);
+            save_settings(out);
+        }
+        ("scan", _) => {
+            let networks = wifi::cached_networks();
+            if networks.is_empty() {
+                writeln!(out, "{}\r", i18n::tr(Msg::CliScanEmpty)).ok();
+            }
+            for network in networks {
+                for (i, byte) in network.bssid.iter().enumerate() {
+                    let separator = if i == 0 { "" } else { ":" };
+                    write!(out, "{}{:02x}", separator, byte).ok();
+                }
+                let (channel, rssi) = (network.channel, network.signal_strength);
+                write!(out, " ch{:<2} {:>4}dBm", channel, rssi).ok();
+                let age = network.seen.elapsed().as_secs();
+                writeln!(out, " {:>3}s {}\r", age, network.ssid).ok();
+            }
+        }
+        ("country", None) => {
+            let plan = wifi::channel_plan();
+            writeln!(out, "country: {}\r", plan.country_code()).ok();
+            writeln!(out, "channels: {}-{}\r", plan.first, plan.last).ok();
+        }
+        ("country", Some(code)) => {
+            let Some(plan) = parse_channel_plan(code, args.next()) else {
+                writeln!(out, "{}\r", i18n::tr(Msg::CliCountryUsage)).ok();
+                return;
+            };
+            settings::update(|s| {
+                s.wifi_country = plan.country;
+                s.wifi_channels = [plan.first, plan.last];
+            });
+            save_settings(out);
+        }
+        ("hostname", None) => {
+            writeln!(out, "hostname: {}\r", net::hostname()).ok();
+        }
+        ("hostname", Some(name)) => {
+            let name = if name == "default" { "" } else { name };
+            let valid = name.is_empty() || net::is_valid_hostname(name);
+            let Some(name) = name.try_into().ok().filter(|_| valid) else {
+                writeln!(out, "{}\r", i18n::tr(Msg::CliHostnameUsage)).ok();
+                return;
+            };
+            settings::update(|s| s.hostname = name);
+            save_settings(out);
+        }
+        ("device", None) => {
+            let identity = device::identity();
+            writeln!(out, "id: {}\r", identity.id).ok();
+            writeln!(out, "name: {}\r", identity.name).ok();
+            writeln!(out, "location: {}\r", identity.location).ok();
+            writeln!(out, "tags: {}\r", identity.tags).ok();
+        }
+        ("device", Some(field @ ("name" | "location" | "tags"))) => {
+            // 名称和位置中可以有空格，取字段名之后的整行
+            let value = line["device".len()..].trim_start()[field.len()..].trim();
+            let value = if value == "off" { "" } else { value };
+            let valid = match field {
+                "tags" => value.is_empty() || device::is_valid_tags(value),
+                _ => device::is_valid_text(value),
+            };
+            let mut stored = false;
+            if valid {
+                settings::update(|s| {
+                    stored = match field {
+                        "name" => value.try_into().map(|name| s.device_name = name),
+                        "location" => value.try_into().map(|place| s.device_location = place),
+                        _ => value.try_into().map(|tags| s.device_tags = tags),
+                    }
+                    .is_ok();
+                });
+            }
+            if !stored {
+                writeln!(out, "{}\r", i18n::tr(Msg::CliDeviceUsage)).ok();
+                return;
+            }
+            save_settings(out);
+        }
+        ("device", Some(_)) => {
+            writeln!(out, "{}\r", i18n::tr(Msg::CliDeviceUsage)).ok();
+        }
+        ("console", None) => {
+            writeln!(out, "console: {}\r", console::selected_backend().name()).ok();
+        }
+        ("console", Some(name)) => {
+            let backend = match name {
+                "usb" => Backend::UsbSerialJtag,
+                "uart" => Backend::Uart,
+                _ => {
+                    writeln!(out, "{}\r", i18n::tr(Msg::CliConsoleUsage)).ok();
+                    return;
+                }
+            };
+            settings::update(|s| s.console = backend.to_u8());
+            save_settings(out);
+        }
+        ("lang", None) => {
+            writeln!(out, "lang: {}\r", i18n::current().code()).ok();
+        }
+        ("lang", Some(code)) => {
+            let Some(language) = Language::ALL.into_iter().find(|l| l.code() == code) else {
+                writeln!(out, "{}\r", i18n::tr(Msg::CliLangUsage)).ok();
+                return;
+            };
+            settings::update(|s| s.language = language.to_u8());
+            match settings::save() {
+                Ok(()) => writeln!(out, "{}\r", i18n::tr(Msg::CliLangSaved)),
+                Err(err) => writeln!(out, "{}: {:?}\r", i18n::tr(Msg::CliSaveFailed), err),
+            }
+            .ok();
+        }
+        ("profile", None) => {
+            let current = profile::current();
+            for profile in Profile::ALL {
+                let marker = if profile == current { "*" } else { " " };
+                writeln!(out, "{} {}\r", marker, profile.name()).ok();
+            }
+        }
+        ("profile", Some(name)) => {
+            let Some(profile) = Profile::ALL.into_iter().find(|p| p.name() == name) else {
+                writeln!(out, "{}\r", i18n::tr(Msg::CliProfileUsage)).ok();
+                return;
+            };
+            settings::update(|s| s.profile = profile.to_u8());
+            save_settings(out);
+        }
+        ("keymap", None) => {
+            for action in Action::ALL {
+                let key = keymap::key_name(keymap::key_for(action));
+                writeln!(out, "{}: {}\r", action.name(), key).ok();
+            }
+        }
+        ("keymap", Some(name)) => {
+            let action = Action::ALL.into_iter().find(|a| a.name() == name);
+            let key = args
+                .next()
+                .and_then(|k| keymap::KEYS.into_iter().find(|&key| keymap::key_name(key) == k));
+            let (Some(action), Some(key)) = (action, key) else {
+                writeln!(out, "{}\r", i18n::tr(Msg::CliKeymapUsage)).ok();
+                return;
+            };
+            keymap::assign(action, key);
+            match settings::save() {
+                Ok(()) => writeln!(out, "{}: {}\r", action.name(), keymap::key_name(key)),
+                Err(err) => writeln!(out, "{}: {:?}\r", i18n::tr(Msg::CliSaveFailed), err),
+            }
+            .ok();
+        }
+        ("forecast", None) => match forecast::latest() {
+            Some(latest) => {
+                let age = Instant::now().duration_since(latest.updated).as_secs();
+                writeln!(out, "updated {} s ago\r", age).ok();
+                for day in &latest.days {
+                    writeln!(
+                        out,
+                        "{:02}-{:02} {:?} {:.1}..{:.1} C\r",
+                        day.month, day.day, day.condition, day.min, day.max
+                    )
+                    .ok();
+                }
+            }
+            None => {
+                writeln!(out, "{}\r", i18n::tr(Msg::CliForecastNone)).ok();
+            }
+        },
+        ("forecast", Some("location")) => {
+            let location = args.next().filter(|l| forecast::valid_location(l));
+            let Some(Ok(location)) = location.map(|l| l.try_into()) else {
+                writeln!(out, "{}\r", i18n::tr(Msg::CliForecastUsage)).ok();
+                return;
+            };
+            settings::update(|s| s.forecast_location = location);
+            save_forecast_settings(out);
+        }
+        ("forecast", Some("provider")) => {
+            let name = args.next().unwrap_or("");
+            let Some(provider) = Provider::ALL.into_iter().find(|p| p.name() == name) else {
+                writeln!(out, "{}\r", i18n::tr(Msg::CliForecastUsage)).ok();
+                return;
+            };
+            let Ok(key) = args.next().unwrap_or("").try_into() else {
+                writeln!(out, "{}\r", i18n::tr(Msg::CliForecastUsage)).ok();
+                return;
+            };
+            settings::update(|s| {
+                s.forecast_provider = provider.to_u8();
+                s.forecast_key = key;
+            });
+            save_forecast_settings(out);
+        }
+        ("forecast", Some("upload")) => {
+            let key = match args.next() {
+                Some("off") => Some(""),
+                key => key,
+            };
+            let Some(Ok(key)) = key.map(|k| k.try_into()) else {
+                writeln!(out, "{}\r", i18n::tr(Msg::CliForecastUsage)).ok();
+                return;
+            };
+            settings::update(|s| s.thingspeak_key = key);
+            save_settings(out);
+        }
+        #[cfg(all(feature = "sd", feature = "ui"))]
+        ("photo", None) => {
+            let s = settings::get();
+            writeln!(out, "interval: {} s\r", s.photo_interval).ok();
+            let transition = Transition::from_u8(s.photo_transition);
+            writeln!(out, "transition: {}\r", transition.name()).ok();
+        }
+        #[cfg(all(feature = "sd", feature = "ui"))]
+        ("photo", Some("interval")) => {
+            let interval = args.next().and_then(|s| s.parse::<u16>().ok());
+            let Some(interval) =
+                interval.filter(|i| (photo::MIN_INTERVAL..=photo::MAX_INTERVAL).contains(i))
+            else {
+                writeln!(out, "{}\r", i18n::tr(Msg::CliPhotoUsage)).ok();
+                return;
+            };
+            settings::update(|s| s.photo_interval = interval);
+            save_photo_settings(out);
+        }
+        #[cfg(all(feature = "sd", feature = "ui"))]
+        ("photo", Some("transition")) => {
+            let name = args.next().unwrap_or("");
+            let Some(transition) = Transition::ALL.into_iter().find(|t| t.name() == name) else {
+                writeln!(out, "{}\r", i18n::tr(Msg::CliPhotoUsage)).ok();
+                return;
+            };
+            settings::update(|s| s.photo_transition = transition.to_u8());
+            save_photo_settings(out);
+        }
+        #[cfg(all(feature = "sd", feature = "ui"))]
+        ("photo", Some(command)) => {
+            let Some(command) = photo::Command::from_name(command) else {
+                writeln!(out, "{}\r", i18n::tr(Msg::CliPhotoUsage)).ok();
+                return;
+            };
+            photo::command(command);
+        }
+        ("clock", None) => {
+            let s = settings::get();
+            #[cfg(feature = "ui")]
+            writeln!(out, "face: {}\r", Face::from_u8(s.clock_face).name()).ok();
+            let offset = s.utc_offset.unsigned_abs();
+            let sign = if s.utc_offset < 0 { '-' } else { '+' };
+            writeln!(out, "tz: UTC{}{:02}:{:02}\r", sign, offset / 60, offset % 60).ok();
+            let [from, to] = s.night_hours;
+            if from == to {
+                writeln!(out, "night: off\r").ok();
+            } else {
+                writeln!(out, "night: {:02}:00-{:02}:00\r", from, to).ok();
+            }
+        }
+        #[cfg(feature = "ui")]
+        ("clock", Some("face")) => {
+            let name = args.next().unwrap_or("");
+            let Some(face) = Face::ALL.into_iter().find(|f| f.name() == name) else {
+                writeln!(out, "{}\r", i18n::tr(Msg::CliClockUsage)).ok();
+                return;
+            };
+            settings::update(|s| s.clock_face = face.to_u8());
+            save_clock_settings(out);
+        }
+        ("clock", Some("tz")) => {
+            let Some(offset) = args.next().and_then(parse_utc_offset) else {
+                writeln!(out, "{}\r", i18n::tr(Msg::CliClockUsage)).ok();
+                return;
+            };
+            settings::update(|s| s.utc_offset = offset);
+            save_clock_settings(out);
+        }
+        ("clock", Some("night")) => {
+            let Some(hours) = args.next().and_then(parse_night_hours) else {
+                writeln!(out, "{}\r", i18n::tr(Msg::CliClockUsage)).ok();
+                return;
+            };
+            settings::update(|s| s.night_hours = hours);
+            save_clock_settings(out);
+        }
+        ("theme", None) => {
+            let s = settings::get();
+            writeln!(out, "theme: {}\r", Mode::from_u8(s.theme).name()).ok();
+            match s.accent {
+                0 => writeln!(out, "accent: default\r"),
+                accent => {
+                    let [r, g, b] = rgb888(accent);
+                    writeln!(out, "accent: {:02x}{:02x}{:02x}\r", r, g, b)
+                }
+            }
+            .ok();
+            if let Some(lux) = theme::ambient_lux() {
+                writeln!(out, "ambient: {:.0} lx\r", lux).ok();
+            }
+        }
+        ("theme", Some("accent")) => {
+            let accent = match args.next() {
+                Some("default") => Some(0),
+                Some(text) => parse_color(text),
+                None => None,
+            };
+            let Some(accent) = accent else {
+                writeln!(out, "{}\r", i18n::tr(Msg::CliThemeUsage)).ok();
+                return;
+            };
+            settings::update(|s| s.accent = accent);
+            save_theme_settings(out);
+        }
+        ("theme", Some(name)) => {
+            let Some(mode) = Mode::ALL.into_iter().find(|m| m.name() == name) else {
+                writeln!(out, "{}\r", i18n::tr(Msg::CliThemeUsage)).ok();
+                return;
+            };
+            settings::update(|s| s.theme = mode.to_u8());
+            save_theme_settings(out);
+        }
+        #[cfg(feature = "ui")]
+        ("lcd", None) => {
+            match st7789::panel() {
+                Some(panel) => print_panel(out, &panel),
+                None => {
+                    writeln!(out, "controller: -\r").ok();
+                }
+            }
+            let s = settings::get();
+            writeln!(out, "gamma: {}\r", Curve::from_u8(s.lcd_curve).name()).ok();
+            writeln!(out, "contrast: {}\r", s.lcd_contrast).ok();
+            let invert = if s.lcd_inverted { "on" } else { "off" };
+            writeln!(out, "invert: {}\r", invert).ok();
+            print_gamma_table(out, "pos", &s.lcd_positive_gamma);
+            print_gamma_table(out, "neg", &s.lcd_negative_gamma);
+        }
+        #[cfg(feature = "ui")]
+        ("lcd", Some("gamma")) => {
+            let name = args.next().unwrap_or("");
+            let Some(curve) = Curve::ALL.into_iter().find(|c| c.name() == name) else {
+                writeln!(out, "{}\r", i18n::tr(Msg::CliLcdUsage)).ok();
+                return;
+            };
+            settings::update(|s| s.lcd_curve = curve.to_u8());
+            save_lcd_settings(out);
+        }
+        #[cfg(feature = "ui")]
+        ("lcd", Some("contrast")) => {
+            let contrast = args.next().and_then(|text| text.parse::<u8>().ok());
+            let Some(contrast) = contrast.filter(|&c| c <= CONTRAST_MAX) else {
+                writeln!(out, "{}\r", i18n::tr(Msg::CliLcdUsage)).ok();
+                return;
+            };
+            settings::update(|s| s.lcd_contrast = contrast);
+            save_lcd_settings(out);
+        }
+        #[cfg(feature = "ui")]
+        ("lcd", Some("invert")) => {
+            let inverted = match args.next() {
+                Some("on") => true,
+                Some("off") => false,
+                _ => {
+                    writeln!(out, "{}\r", i18n::tr(Msg::CliLcdUsage)).ok();
+                    return;
+                }
+            };
+            settings::update(|s| s.lcd_inverted = inverted);
+            save_lcd_settings(out);
+        }
+        #[cfg(feature = "ui")]
+        ("lcd", Some("table")) => {
+            let (polarity, value) = (args.next(), args.next());
+            let defaults = settings::Settings::DEFAULT;
+            let table = match (polarity, value) {
+                (Some("pos"), Some("default")) => Some(defaults.lcd_positive_gamma),
+                (Some("neg"), Some("default")) => Some(defaults.lcd_negative_gamma),
+                (Some("pos" | "neg"), Some(hex)) => parse_gamma_table(hex),
+                _ => None,
+            };
+            let Some(table) = table else {
+                writeln!(out, "{}\r", i18n::tr(Msg::CliLcdUsage)).ok();
+                return;
+            };
+            if polarity == Some("pos") {
+                settings::update(|s| s.lcd_positive_gamma = table);
+            } else {
+                settings::update(|s| s.lcd_negative_gamma = table);
+            }
+            save_lcd_settings(out);
+        }
+        #[cfg(feature = "ui")]
+        ("lcd", Some("reset")) => {
+            tuning::reset();
+            save_lcd_settings(out);
+        }
+        #[cfg(feature = "ui")]
+        ("lcd", Some(_)) => {
+            writeln!(out, "{}\r", i18n::tr(Msg::CliLcdUsage)).ok();
+        }
+        #[cfg(feature = "ui")]
+        ("screen", Some("status")) => render::command(Command::ShowScreen(Screen::Status)),
+        #[cfg(feature = "ui")]
+        ("screen", Some("blank")) => render::command(Command::ShowScreen(Screen::Blank)),
+        #[cfg(feature = "ui")]
+        ("fps", None) => {
+            let stats = render::frame_stats();
+            writeln!(out, "target: {} fps\r", settings::get().render_fps).ok();
+            writeln!(out, "actual: {} fps\r", stats.fps).ok();
+            let (average, max) = (stats.average_ms, stats.max_ms);
+            writeln!(out, "frame: {} ms avg, {} ms max\r", average, max).ok();
+            writeln!(out, "skipped: {}\r", stats.skipped).ok();
+            let overlay = if render::fps_overlay() { "on" } else { "off" };
+            writeln!(out, "overlay: {}\r", overlay).ok();
+        }
+        #[cfg(feature = "ui")]
+        ("fps", Some(state @ ("on" | "off"))) => render::set_fps_overlay(state == "on"),
+        #[cfg(feature = "ui")]
+        ("fps", Some(value)) => {
+            let fps = value.parse::<u32>().ok();
+            let Some(fps) = fps.filter(|f| (frame::MIN_FPS..=frame::MAX_FPS).contains(f)) else {
+                writeln!(out, "{}\r", i18n::tr(Msg::CliFpsUsage)).ok();
+                return;
+            };
+            settings::update(|s| s.render_fps = fps as u8);
+            save_render_settings(out);
+        }
+        ("webhook", None) => {
+            let settings = settings::get();
+            if settings.webhook_url.is_empty() {
+                writeln!(out, "{}\r", i18n::tr(Msg::CliWebhookNone)).ok();
+            } else {
+                writeln!(out, "webhook: {}\r", settings.webhook_url).ok();
+            }
+            let format = Format::from_u8(settings.webhook_format);
+            writeln!(out, "format: {}\r", format.name()).ok();
+            // 离线时暂存在 TF 卡上、尚未发送的通知
+            #[cfg(feature = "sd")]
+            {
+                let pending = outbox::WEBHOOK.pending().await;
+                if pending > 0 {
+                    writeln!(out, "outbox: {} bytes\r", pending).ok();
+                }
+            }
+        }
+        ("webhook", Some("test")) => notifier::notify("Test notification"),
+        ("webhook", Some("format")) => {
+            let name = args.next();
+            let Some(format) = Format::ALL.into_iter().find(|f| Some(f.name()) == name) else {
+                writeln!(out, "{}\r", i18n::tr(Msg::CliWebhookUsage)).ok();
+                return;
+            };
+            settings::update(|s| s.webhook_format = format.to_u8());
+            match settings::save() {
+                Ok(()) => writeln!(out, "{}\r", i18n::tr(Msg::CliWebhookSaved)),
+                Err(err) => writeln!(out, "{}: {:?}\r", i18n::tr(Msg::CliSaveFailed), err),
+            }
+            .ok();
+        }
+        ("webhook", Some(url)) => {
+            let url = if url == "off" { "" } else { url };
+            let valid = url.is_empty() || http_client::parse_url(url).is_ok();
+            let Some(url) = url.try_into().ok().filter(|_| valid) else {
+                writeln!(out, "{}\r", i18n::tr(Msg::CliWebhookUsage)).ok();
+                return;
+            };
+            settings::update(|s| s.webhook_url = url);
+            match settings::save() {
+                Ok(()) => writeln!(out, "{}\r", i18n::tr(Msg::CliWebhookSaved)),
+                Err(err) => writeln!(out, "{}: {:?}\r", i18n::tr(Msg::CliSaveFailed), err),
+            }
+            .ok();
+        }
+        ("ota", Some(url)) => {
+            let msg = if ota::request_http(url) {
+                Msg::CliOtaStarted
+            } else {
+                Msg::CliOtaUsage
+            };
+            writeln!(out, "{}\r", i18n::tr(msg)).ok();
+        }
+        ("ota", None) => {
+            writeln!(out, "{}\r", i18n::tr(Msg::CliOtaUsage)).ok();
+        }
+        ("syslog", None) => {
+            let server = settings::get().syslog_server;
+            if server.is_empty() {
+                writeln!(out, "{}\r", i18n::tr(Msg::CliSyslogNone)).ok();
+            } else {
+                writeln!(out, "syslog: {}\r", server).ok();
+            }
+        }
+        ("syslog", Some(server)) => {
+            let server = if server == "off" { "" } else { server };
+            let valid = server.is_empty() || syslog::parse_server(server).is_some();
+            let Some(server) = server.try_into().ok().filter(|_| valid) else {
+                writeln!(out, "{}\r", i18n::tr(Msg::CliSyslogUsage)).ok();
+                return;
+            };
+            settings::update(|s| s.syslog_server = server);
+            save_settings(out);
+        }
+        ("mqtt", None) => {
+            let s = settings::get();
+            let state = match mqtt::is_connected() {
+                true => "connected",
+                false => "disconnected",
+            };
+            if s.mqtt_broker.is_empty() {
+                writeln!(out, "{} ({})\r", i18n::tr(Msg::CliMqttNone), state).ok();
+            } else {
+                writeln!(out, "broker: {} ({})\r", s.mqtt_broker, state).ok();
+            }
+            if !s.mqtt_sas_key.is_empty() {
+                writeln!(out, "auth: SAS token\r").ok();
+            } else if !s.mqtt_user.is_empty() {
+                writeln!(out, "user: {}\r", s.mqtt_user).ok();
+            }
+            writeln!(out, "topic: {}\r", mqtt::base_topic()).ok();
+        }
+        ("mqtt", Some(option)) => {
+            let value = match args.next() {
+                Some("off" | "default") => Some(""),
+                value => value,
+            };
+            let stored = match (option, value) {
+                ("broker", Some(broker))
+                    if broker.is_empty() || mqtt::parse_broker(broker).is_some() =>
+                {
+                    broker
+                        .try_into()
+                        .map(|broker| settings::update(|s| s.mqtt_broker = broker))
+                        .is_ok()
+                }
+                ("user", Some(user)) => {
+                    let password = args.next().unwrap_or("");
+                    match (user.try_into(), password.try_into()) {
+                        (Ok(user), Ok(password)) => {
+                            settings::update(|s| {
+                                s.mqtt_user = user;
+                                s.mqtt_password = password;
+                            });
+                            true
+                        }
+                        _ => false,
+                    }
+                }
+                ("sas", Some(key)) if key.is_empty() || sas::is_valid_key(key) => key
+                    .try_into()
+                    .map(|key| settings::update(|s| s.mqtt_sas_key = key))
+                    .is_ok(),
+                ("topic", Some(topic)) if topic.is_empty() || mqtt::is_valid_base_topic(topic) => {
+                    topic
+                        .try_into()
+                        .map(|topic| settings::update(|s| s.mqtt_topic = topic))
+                        .is_ok()
+                }
+                _ => false,
+            };
+            if !stored {
+                writeln!(out, "{}\r", i18n::tr(Msg::CliMqttUsage)).ok();
+                return;
+            }
+            save_settings(out);
+        }
+        ("sync", None) => {
+            let s = settings::get();
+            if s.sync_group.is_empty() {
+                writeln!(out, "{}\r", i18n::tr(Msg::CliSyncNone)).ok();
+                return;
+            }
+            writeln!(out, "group: {}\r", s.sync_group).ok();
+            // 未校时时修改时间只是计数
+            if s.sync_stamp >= SYNC_STAMP_EPOCH {
+                let t = DateTime::from_unix(s.sync_stamp as u64);
+                writeln!(
+                    out,
+                    "changed: {:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC\r",
+                    t.year, t.month, t.day, t.hour, t.minute, t.second
+                )
+                .ok();
+            } else {
+                writeln!(out, "changed: #{}\r", s.sync_stamp).ok();
+            }
+        }
+        ("sync", Some(group)) => {
+            let group = if group == "off" { "" } else { group };
+            let valid = group
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_');
+            let Some(group) = group.try_into().ok().filter(|_| valid) else {
+                writeln!(out, "{}\r", i18n::tr(Msg::CliSyncUsage)).ok();
+                return;
+            };
+            settings::update(|s| s.sync_group = group);
+            save_settings(out);
+        }
+        ("thermostat", None) => {
+            let s = settings::get();
+            if s.thermostat_source.is_empty() {
+                writeln!(out, "{}\r", i18n::tr(Msg::CliThermostatNone)).ok();
+                return;
+            }
+            let mode = thermostat::Mode::from_u8(s.thermostat_mode);
+            writeln!(out, "source: {}\r", s.thermostat_source).ok();
+            writeln!(out, "mode: {}\r", mode.name()).ok();
+            let (setpoint, band) = (s.thermostat_setpoint, s.thermostat_hysteresis);
+            let (setpoint, band) = (setpoint as f32 / 10.0, band as f32 / 10.0);
+            writeln!(out, "setpoint: {:.1} C, band: {:.1} C\r", setpoint, band).ok();
+            let (min_on, min_off) = (s.thermostat_min_on, s.thermostat_min_off);
+            writeln!(out, "min on/off: {}/{} s\r", min_on, min_off).ok();
+            let status = thermostat::status();
+            match status.temperature {
+                Some(t) => writeln!(out, "temperature: {:.1} C\r", t),
+                None => writeln!(out, "temperature: --\r"),
+            }
+            .ok();
+            let output = if status.output { "on" } else { "off" };
+            writeln!(out, "output: {}\r", output).ok();
+        }
+        ("thermostat", Some("source")) => {
+            let name = args.next().unwrap_or("");
+            let name = if name == "off" { "" } else { name };
+            let valid = name.is_empty() || sensor::is_valid_name(name);
+            let Some(name) = name.try_into().ok().filter(|_| valid) else {
+                writeln!(out, "{}\r", i18n::tr(Msg::CliThermostatUsage)).ok();
+                return;
+            };
+            settings::update(|s| s.thermostat_source = name);
+            save_settings(out);
+        }
+        ("thermostat", Some("set")) => {
+            let tenths = args.next().and_then(thermostat::parse_tenths);
+            let Some(tenths) = tenths.filter(|t| thermostat::SETPOINT_RANGE.contains(t)) else {
+                writeln!(out, "{}\r", i18n::tr(Msg::CliThermostatUsage)).ok();
+                return;
+            };
+            thermostat::set_setpoint(tenths);
+            save_settings(out);
+        }
+        ("thermostat", Some("mode")) => {
+            let name = args.next();
+            let mode = thermostat::Mode::ALL
+                .into_iter()
+                .find(|m| Some(m.name()) == name);
+            let Some(mode) = mode else {
+                writeln!(out, "{}\r", i18n::tr(Msg::CliThermostatUsage)).ok();
+                return;
+            };
+            settings::update(|s| s.thermostat_mode = mode.to_u8());
+            save_settings(out);
+        }
+        ("thermostat", Some("band")) => {
+            let tenths = args.next().and_then(thermostat::parse_tenths);
+            let Some(tenths) = tenths.filter(|t| (1..=100).contains(t)) else {
+                writeln!(out, "{}\r", i18n::tr(Msg::CliThermostatUsage)).ok();
+                return;
+            };
+            settings::update(|s| s.thermostat_hysteresis = tenths as u8);
+            save_settings(out);
+        }
+        ("thermostat", Some("min")) => {
+            let min_on = args.next().and_then(|v| v.parse::<u16>().ok());
+            let min_off = args.next().and_then(|v| v.parse::<u16>().ok());
+            let (Some(min_on), Some(min_off)) = (min_on, min_off) else {
+                writeln!(out, "{}\r", i18n::tr(Msg::CliThermostatUsage)).ok();
+                return;
+            };
+            settings::update(|s| {
+                s.thermostat_min_on = min_on;
+                s.thermostat_min_off = min_off;
+            });
+            save_settings(out);
+        }
+        ("thermostat", Some(_)) => {
+            writeln!(out, "{}\r", i18n::tr(Msg::CliThermostatUsage)).ok();
+        }
+        ("pid", None) => {
+            let s = settings::get();
+            if s.pid_source.is_empty() {
+                writeln!(out, "{}\r", i18n::tr(Msg::CliPidNone)).ok();
+                return;
+            }
+            let milli = |value: i32| value as f32 / pid::MILLI as f32;
+            writeln!(out, "source: {}\r", s.pid_source).ok();
+            writeln!(out, "setpoint: {:.3}\r", milli(s.pid_setpoint)).ok();
+            let (kp, ki, kd) = (milli(s.pid_kp), milli(s.pid_ki), milli(s.pid_kd));
+            writeln!(out, "kp: {:.3} ki: {:.3} kd: {:.3}\r", kp, ki, kd).ok();
+            writeln!(out, "period: {} ms\r", s.pid_period).ok();
+            let status = pid::status();
+            if !status.running {
+                writeln!(out, "{}\r", i18n::tr(Msg::CliPidNotRunning)).ok();
+                return;
+            }
+            match status.input {
+                Some(input) => writeln!(out, "input: {:.3}\r", input),
+                None => writeln!(out, "input: --\r"),
+            }
+            .ok();
+            writeln!(out, "output: {:.1}%\r", status.output).ok();
+        }
+        ("pid", Some("source")) => {
+            let name = args.next().unwrap_or("");
+            let name = if name == "off" { "" } else { name };
+            let valid = name.is_empty() || sensor::is_valid_name(name);
+            let Some(name) = name.try_into().ok().filter(|_| valid) else {
+                writeln!(out, "{}\r", i18n::tr(Msg::CliPidUsage)).ok();
+                return;
+            };
+            settings::update(|s| s.pid_source = name);
+            save_settings(out);
+        }
+        ("pid", Some(name)) => {
+            if !pid::tune(name, args.next().unwrap_or("")) {
+                writeln!(out, "{}\r", i18n::tr(Msg::CliPidUsage)).ok();
+                return;
+            }
+            save_settings(out);
+        }
+        ("relay", None) => {
+            for (index, status) in relay::status().iter().enumerate() {
+                let Some(pin) = status.pin else {
+                    writeln!(out, "{}: -\r", index + 1).ok();
+                    continue;
+                };
+                let state = if status.is_on() { "on" } else { "off" };
+                write!(out, "{}: {} {}", index + 1, pin.name(), state).ok();
+                if let Some(remaining) = status.remaining() {
+                    write!(out, ", {} s left", remaining.as_secs()).ok();
+                }
+                let runtime = status.total_runtime().as_secs();
+                writeln!(out, ", runtime {} s\r", runtime).ok();
+            }
+        }
+        ("relay", Some("pin")) => {
+            let output = args.next().and_then(|v| v.parse::<usize>().ok());
+            let output = output.filter(|n| (1..=relay::OUTPUTS).contains(n));
+            let code = match (args.next(), args.next()) {
+                (Some("off"), None) => Some(0),
+                (Some(name), low @ (None | Some("low"))) => {
+                    relay::Pin::parse(name, low.is_some()).map(relay::Pin::to_u8)
+                }
+                _ => None,
+            };
+            let (Some(output), Some(code)) = (output, code) else {
+                writeln!(out, "{}\r", i18n::tr(Msg::CliRelayUsage)).ok();
+                return;
+            };
+            settings::update(|s| s.relay_pins[output - 1] = code);
+            save_settings(out);
+        }
+        ("relay", Some(output)) => {
+            let output = output.parse::<usize>().ok();
+            let switch = args.next().and_then(relay::Switch::parse);
+            let (Some(output), Some(switch)) = (output, switch) else {
+                writeln!(out, "{}\r", i18n::tr(Msg::CliRelayUsage)).ok();
+                return;
+            };
+            if !relay::command(output, switch) {
+                writeln!(out, "{}\r", i18n::tr(Msg::CliRelayUsage)).ok();
+            }
+        }
+        ("espnow", None) => {
+            let peers = espnow::paired();
+            if peers.is_empty() {
+                writeln!(out, "{}\r", i18n::tr(Msg::CliEspNowNone)).ok();
+            }
+            for mac in peers {
+                writeln!(out, "{}\r", device::format_id(&mac)).ok();
+            }
+        }
+        ("espnow", Some("forget")) => {
+            let mac = match args.next() {
+                Some("all") => None,
+                Some(id) => match device::parse_id(id) {
+                    Some(mac) => Some(mac),
+                    None => {
+                        writeln!(out, "{}\r", i18n::tr(Msg::CliEspNowUsage)).ok();
+                        return;
+                    }
+                },
+                None => {
+                    writeln!(out, "{}\r", i18n::tr(Msg::CliEspNowUsage)).ok();
+                    return;
+                }
+            };
+            match espnow::forget(mac) {
+                Ok(0) => writeln!(out, "{}\r", i18n::tr(Msg::CliEspNowNotPaired)),
+                Ok(_) => writeln!(out, "{}\r", i18n::tr(Msg::CliSaved)),
+                Err(err) => writeln!(out, "{}: {:?}\r", i18n::tr(Msg::CliSaveFailed), err),
+            }
+            .ok();
+        }
+        ("espnow", Some(_)) => {
+            writeln!(out, "{}\r", i18n::tr(Msg::CliEspNowUsage)).ok();
+        }
+        ("schedule", None) => {
+            let schedule = settings::get().schedule;
+            if schedule.is_empty() {
+                writeln!(out, "{}\r", i18n::tr(Msg::CliScheduleNone)).ok();
+            }
+            for rule in schedule
+                .split(';')
+                .map(str::trim)
+                .filter(|rule| !rule.is_empty())
+            {
+                writeln!(out, "{}\r", rule).ok();
+            }
+        }
+        ("schedule", Some(first)) => {
+            // 规则中含有空格，取命令名之后的整行
+            let rules = if first == "off" {
+                ""
+            } else {
+                line["schedule".len()..].trim()
+            };
+            let valid = scheduler::parse(rules).is_ok();
+            let Some(rules) = rules.try_into().ok().filter(|_| valid) else {
+                writeln!(out, "{}\r", i18n::tr(Msg::CliScheduleUsage)).ok();
+                return;
+            };
+            settings::update(|s| s.schedule = rules);
+            save_schedule_settings(out);
+        }
+        ("rules", None) => {
+            let report = rules::format_report();
+            if report.is_empty() {
+                writeln!(out, "{}\r", i18n::tr(Msg::CliRulesNone)).ok();
+            }
+            for line in report.lines() {
+                writeln!(out, "{}\r", line).ok();
+            }
+        }
+        ("rules", Some("add")) => {
+            // 规则中含有空格，取 add 之后的整行
+            let rule = line.split_once("add").map_or("", |(_, rule)| rule.trim());
+            if rule.is_empty() {
+                writeln!(out, "{}\r", i18n::tr(Msg::CliRulesUsage)).ok();
+                return;
+            }
+            report_rules(out, rules::add(rule).map(|_| ()));
+        }
+        ("rules", Some("del")) => {
+            let Some(number) = args.next().and_then(|n| n.parse().ok()) else {
+                writeln!(out, "{}\r", i18n::tr(Msg::CliRulesUsage)).ok();
+                return;
+            };
+            match rules::remove(number) {
+                Ok(false) => {
+                    writeln!(out, "{}\r", i18n::tr(Msg::CliRulesUsage)).ok();
+                }
+                result => report_rules(out, result.map(|_| ())),
+            }
+        }
+        ("rules", Some("clear")) => report_rules(out, rules::set("").map(|_| ())),
+        ("rules", Some(_)) => {
+            writeln!(out, "{}\r", i18n::tr(Msg::CliRulesUsage)).ok();
+        }
+        ("date", None) => match (wallclock::now(), wallclock::source()) {
+            (Some(secs), Some(source)) => {
+                let t = DateTime::from_unix(secs);
+                writeln!(
+                    out,
+                    "{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC ({:?})\r",
+                    t.year, t.month, t.day, t.hour, t.minute, t.second, source
+                )
+                .ok();
+                if let Some(drift) = wallclock::drift_ppb() {
+                    writeln!(out, "drift: {:+.3} ppm\r", drift as f32 / 1000.0).ok();
+                }
+            }
+            _ => {
+                writeln!(out, "{}\r", i18n::tr(Msg::CliDateNotSet)).ok();
+            }
+        },
+        ("date", Some(value)) => {
+            // 校准值按 i64 微秒保存，超出范围的秒数按用法错误处理
+            let unix_us = value
+                .parse::<u64>()
+                .ok()
+                .and_then(|secs| secs.checked_mul(1_000_000))
+                .filter(|&us| i64::try_from(us).is_ok());
+            match unix_us {
+                Some(unix_us) => {
+                    if !wallclock::set(unix_us, TimeSource::Manual) {
+                        writeln!(out, "{}\r", i18n::tr(Msg::CliDateRejected)).ok();
+                    }
+                }
+                None => {
+                    writeln!(out, "{}\r", i18n::tr(Msg::CliDateUsage)).ok();
+                }
+            }
+        }
+        ("can", None) => {
+            if !can::is_running() {
+                writeln!(out, "{}\r", i18n::tr(Msg::CliCanNotStarted)).ok();
+                return;
+            }
+            let status = can::status();
+            writeln!(
+                out,
+                "rx: {} tx: {} failed: {}\r",
+                status.rx_frames, status.tx_frames, status.tx_failed
+            )
+            .ok();
+            writeln!(out, "overruns: {} recoveries: {}\r", status.overruns, status.recoveries).ok();
+            writeln!(
+                out,
+                "error count rx/tx: {}/{}\r",
+                status.rx_error_count, status.tx_error_count
+            )
+            .ok();
+        }
+        ("can", Some("send")) => {
+            let id = args.next().unwrap_or("");
+            let frame = parse_can_frame(id, args.next().unwrap_or(""));
+            let Some(frame) = frame else {
+                writeln!(out, "{}\r", i18n::tr(Msg::CliCanSendUsage)).ok();
+                return;
+            };
+            if let Err(err) = can::send(frame) {
+                writeln!(out, "{}: {:?}\r", i18n::tr(Msg::CliCanSendFailed), err).ok();
+            }
+        }
+        ("can", Some("filter")) => {
+            let kind = args.next();
+            let code = args.next().and_then(|v| u32::from_str_radix(v, 16).ok());
+            let mask = args.next().and_then(|v| u32::from_str_radix(v, 16).ok());
+            let filter = match (kind, code, mask) {
+                (Some("all"), None, None) => Some(can::Filter::AcceptAll),
+                (Some("std"), Some(code), Some(mask)) => can::Filter::new(false, code, mask),
+                (Some("ext"), Some(code), Some(mask)) => can::Filter::new(true, code, mask),
+                _ => None,
+            };
+            let Some(filter) = filter else {
+                writeln!(out, "{}\r", i18n::tr(Msg::CliCanFilterUsage)).ok();
+                return;
+            };
+            settings::update(|s| s.can_filter = filter.to_bytes());
+            save_settings(out);
+        }
+        ("dmx", Some("get")) => {
+            let value = args.next().and_then(|v| v.parse().ok()).and_then(dmx::get);
+            match value {
+                Some(value) => writeln!(out, "{}\r", value),
+                None => writeln!(out, "{}\r", i18n::tr(Msg::CliDmxUsage)),
+            }
+            .ok();
+        }
+        ("dmx", Some("set")) => {
+            if args.next().and_then(dmx::set_list).is_none() {
+                writeln!(out, "{}\r", i18n::tr(Msg::CliDmxUsage)).ok();
+            }
+        }
+        ("dmx", Some("blackout")) => dmx::blackout(),
+        ("can", Some("sniff")) => {
+            let secs = args.next().map_or(Ok(CAN_SNIFF_SECS), str::parse);
+            let Some(secs) = secs.ok().filter(|&s| s <= CAN_SNIFF_MAX_SECS) else {
+                writeln!(out, "{}\r", i18n::tr(Msg::CliCanSniffUsage)).ok();
+                return;
+            };
+            if !can::is_running() {
+                writeln!(out, "{}\r", i18n::tr(Msg::CliCanNotStarted)).ok();
+                return;
+            }
+            let Some(mut frames) = can::subscribe() else {
+                writeln!(out, "{}\r", i18n::tr(Msg::CliCanSniffBusy)).ok();
+                return;
+            };
+            // 到时或按任意键结束
+            let deadline = Instant::now() + Duration::from_secs(secs);
+            let sniff = async {
+                while let Ok(frame) = with_deadline(deadline, frames.next_message_pure()).await {
+                    print_can_frame(out, &frame);
+                }
+            };
+            select(sniff, console::wait_interrupt()).await;
+        }
+        ("rc", None) => match rc::latest() {
+            Some(frame) => print_rc_frame(out, &frame),
+            None => {
+                writeln!(out, "{}\r", i18n::tr(Msg::CliRcNone)).ok();
+            }
+        },
+        ("lin", None) => {
+            let checksum = if settings::get().lin_classic {
+                "classic"
+            } else {
+                "enhanced"
+            };
+            writeln!(out, "checksum: {checksum}\r").ok();
+            let schedule = lin::saved_schedule();
+            if schedule.is_empty() {
+                writeln!(out, "{}\r", i18n::tr(Msg::CliLinNone)).ok();
+            }
+            for entry in &schedule {
+                let direction = match entry.direction {
+                    lin::Direction::Publish => "pub",
+                    lin::Direction::Subscribe => "sub",
+                };
+                let (id, len, slot) = (entry.id, entry.len, entry.slot.as_millis());
+                write!(out, "{id:02x} {direction} {len} {slot:>5} ms:").ok();
+                if let Some(frame) = lin::frame_data(entry.id)
+                    && let Some(updated) = frame.updated
+                {
+                    for byte in frame.bytes() {
+                        write!(out, " {:02X}", byte).ok();
+                    }
+                    write!(out, " ({} ms ago)", updated.elapsed().as_millis()).ok();
+                }
+                writeln!(out, "\r").ok();
+            }
+        }
+        ("lin", Some("add")) => {
+            let entry = parse_lin_entry(args.next(), args.next(), args.next(), args.next());
+            let Some(entry) = entry.filter(|_| args.next().is_none()) else {
+                writeln!(out, "{}\r", i18n::tr(Msg::CliLinUsage)).ok();
+                return;
+            };
+            let mut added = false;
+            settings::update(|s| added = s.lin_schedule.push(entry.to_bytes()).is_ok());
+            if !added {
+                writeln!(out, "{}\r", i18n::tr(Msg::CliLinUsage)).ok();
+                return;
+            }
+            save_settings(out);
+        }
+        ("lin", Some("checksum")) => {
+            let classic = match args.next() {
+                Some("classic") => true,
+                Some("enhanced") => false,
+                _ => {
+                    writeln!(out, "{}\r", i18n::tr(Msg::CliLinUsage)).ok();
+                    return;
+                }
+            };
+            settings::update(|s| s.lin_classic = classic);
+            save_settings(out);
+        }
+        ("lin", Some("clear")) => {
+            settings::update(|s| s.lin_schedule.clear());
+            save_settings(out);
+        }
+        ("lin", Some("set")) => {
+            let id = args.next().and_then(|id| u8::from_str_radix(id, 16).ok());
+            let data = args.next().and_then(parse_hex::<{ lin::MAX_DATA_LEN }>);
+            let result = match (id, data) {
+                (Some(id), Some(data)) => lin::set_frame_data(id, &data).ok(),
+                _ => None,
+            };
+            if result.is_none() {
+                writeln!(out, "{}\r", i18n::tr(Msg::CliLinUsage)).ok();
+            }
+        }
+        ("lin", Some(_)) => {
+            writeln!(out, "{}\r", i18n::tr(Msg::CliLinUsage)).ok();
+        }
+        ("rc", Some("watch")) => {
+            let secs = args.next().map_or(Ok(RC_WATCH_SECS), str::parse);
+            let Some(secs) = secs.ok().filter(|&s| s <= RC_WATCH_MAX_SECS) else {
+                writeln!(out, "{}\r", i18n::tr(Msg::CliRcWatchUsage)).ok();
+                return;
+            };
+            let Some(mut frames) = rc::subscribe() else {
+                writeln!(out, "{}\r", i18n::tr(Msg::CliRcWatchBusy)).ok();
+                return;
+            };
+            // 到时或按任意键结束
+            let deadline = Instant::now() + Duration::from_secs(secs);
+            let watch = async {
+                let mut printed: Option<Instant> = None;
+                while let Ok(frame) = with_deadline(deadline, frames.next_message_pure()).await {
+                    if printed.is_none_or(|at| at.elapsed() >= RC_WATCH_INTERVAL) {
+                        print_rc_frame(out, &frame);
+                        printed = Some(Instant::now());
+                    }
+                }
+            };
+            select(watch, console::wait_interrupt()).await;
+        }
+        _ => {
+            writeln!(out, "{}: {}\r", i18n::tr(Msg::CliUnknownCommand), line).ok();
+        }
+    }
+}
+
+/// 设置了 PIN 时需要先解锁的命令，见 [access]
+///
+/// 除了下面列出的只读命令，带参数的命令（修改设置、输出或设备状态）和重启都需要解锁；
+/// 新增只读的子命令时需要加到这里
+fn is_protected(command: &str, arg: Option<&str>) -> bool {
+    let read_only = matches!(
+        (command, arg),
+        ("help" | "uptime" | "status" | "unlock" | "lock", _)
+            | ("jitter" | "bench" | "scan", _)
+            | ("log", Some("dump"))
+            | ("peripherals", Some("list"))
+            | ("can", Some("sniff"))
+            | ("dmx", Some("get"))
+            | ("rc", Some("watch"))
+    );
+    let reboot = command == "reboot";
+    !read_only && (arg.is_some() || reboot)
+}
+
+/// 保存设置并输出结果
+fn save_settings(out: &mut Writer) {
+    match settings::save() {
+        Ok(()) => writeln!(out, "{}\r", i18n::tr(Msg::CliSaved)),
+        Err(err) => writeln!(out, "{}: {:?}\r", i18n::tr(Msg::CliSaveFailed), err),
+    }
+    .ok();
+}
+
+/// 保存天气预报设置，下次下载时生效
+fn save_forecast_settings(out: &mut Writer) {
+    match settings::save() {
+        Ok(()) => writeln!(out, "{}\r", i18n::tr(Msg::CliForecastSaved)),
+        Err(err) => writeln!(out, "{}: {:?}\r", i18n::tr(Msg::CliSaveFailed), err),
+    }
+    .ok();
+}
+
+/// 保存数码相框设置，显示下一张图片时生效
+#[cfg(all(feature = "sd", feature = "ui"))]
+fn save_photo_settings(out: &mut Writer) {
+    match settings::save() {
+        Ok(()) => writeln!(out, "{}\r", i18n::tr(Msg::CliPhotoSaved)),
+        Err(err) => writeln!(out, "{}: {:?}\r", i18n::tr(Msg::CliSaveFailed), err),
+    }
+    .ok();
+}
+
+/// 保存时钟设置，立即生效
+fn save_clock_settings(out: &mut Writer) {
+    match settings::save() {
+        Ok(()) => writeln!(out, "{}\r", i18n::tr(Msg::CliClockSaved)),
+        Err(err) => writeln!(out, "{}: {:?}\r", i18n::tr(Msg::CliSaveFailed), err),
+    }
+    .ok();
+}
+
+/// 保存配色设置，各屏幕下次刷新时生效
+fn save_theme_settings(out: &mut Writer) {
+    match settings::save() {
+        Ok(()) => writeln!(out, "{}\r", i18n::tr(Msg::CliThemeSaved)),
+        Err(err) => writeln!(out, "{}: {:?}\r", i18n::tr(Msg::CliSaveFailed), err),
+    }
+    .ok();
+}
+
+/// 保存屏幕调校参数，状态屏幕立即生效，其他应用模式下次启动时生效
+#[cfg(feature = "ui")]
+fn save_lcd_settings(out: &mut Writer) {
+    match settings::save() {
+        Ok(()) => writeln!(out, "{}\r", i18n::tr(Msg::CliLcdSaved)),
+        Err(err) => writeln!(out, "{}: {:?}\r", i18n::tr(Msg::CliSaveFailed), err),
+    }
+    .ok();
+}
+
+/// 保存渲染设置，渲染任务下次刷新时生效
+#[cfg(feature = "ui")]
+fn save_render_settings(out: &mut Writer) {
+    match settings::save() {
+        Ok(()) => writeln!(out, "{}\r", i18n::tr(Msg::CliFpsSaved)),
+        Err(err) => writeln!(out, "{}: {:?}\r", i18n::tr(Msg::CliSaveFailed), err),
+    }
+    .ok();
+}
+
+/// 保存定时任务，下一分钟起生效
+fn save_schedule_settings(out: &mut Writer) {
+    match settings::save() {
+        Ok(()) => writeln!(out, "{}\r", i18n::tr(Msg::CliScheduleSaved)),
+        Err(err) => writeln!(out, "{}: {:?}\r", i18n::tr(Msg::CliSaveFailed), err),
+    }
+    .ok();
+}
+
+/// 报告修改自动化规则的结果，规则立即生效
+fn report_rules(out: &mut Writer, result: Result<(), RulesError>) {
+    match result {
+        Ok(()) => writeln!(out, "{}\r", i18n::tr(Msg::CliRulesSaved)),
+        Err(RulesError::Compile(err)) => {
+            writeln!(out, "{}: {:?}\r", i18n::tr(Msg::CliRulesInvalid), err)
+        }
+        Err(RulesError::Storage(err)) => {
+            writeln!(out, "{}: {:?}\r", i18n::tr(Msg::CliRulesSaveFailed), err)
+        }
+    }
+    .ok();
+}
+
+/// 解析时区偏移 `[+|-]hh[:mm]`
+///
+/// # 返回
+/// 与 UTC 之差（分钟），范围 -14:00 到 +14:00
+fn parse_utc_offset(text: &str) -> Option<i16> {
+    let (sign, rest) = match text.strip_prefix('-') {
+        Some(rest) => (-1, rest),
+        None => (1, text.strip_prefix('+').unwrap_or(text)),
+    };
+    let (hours, minutes) = rest.split_once(':').unwrap_or((rest, "0"));
+    let (hours, minutes) = (hours.parse::<i16>().ok()?, minutes.parse::<i16>().ok()?);
+    let valid = (0..=14).contains(&hours) && (0..60).contains(&minutes);
+    valid.then_some(sign * (hours * 60 + minutes))
+}
+
+/// 解析夜间时段 `<开始小时>-<结束小时>`，`off` 表示不启用
+fn parse_night_hours(text: &str) -> Option<[u8; 2]> {
+    if text == "off" {
+        return Some([0, 0]);
+    }
+    let (from, to) = text.split_once('-')?;
+    let (from, to) = (from.parse::<u8>().ok()?, to.parse::<u8>().ok()?);
+    (from < 24 && to < 24).then_some([from, to])
+}
+
+/// 解析国家代码和可选的信道范围 `<first>-<last>`
+///
+/// # 返回
+/// 国家代码（大写）不受支持或信道超出该国允许的范围时返回 None，
+/// 没有给出信道范围时使用该国允许的全部信道
+fn parse_channel_plan(code: &str, channels: Option<&str>) -> Option<wifi::ChannelPlan> {
+    let country: [u8; 2] = code.as_bytes().try_into().ok()?;
+    let country = country.map(|c| c.to_ascii_uppercase());
+    let max = wifi::max_channel(core::str::from_utf8(&country).ok()?)?;
+    let (first, last) = match channels {
+        Some(range) => {
+            let (first, last) = range.split_once('-')?;
+            (first.parse::<u8>().ok()?, last.parse::<u8>().ok()?)
+        }
+        None => (1, max),
+    };
+    let valid = 1 <= first && first <= last && last <= max;
+    valid.then_some(wifi::ChannelPlan {
+        country,
+        first,
+        last,
+    })
+}
+
+/// 解析颜色 `[#]rrggbb`
+///
+/// # 返回
+/// RGB565 编码；黑色编码为 0，与“使用默认强调色”相同
+fn parse_color(text: &str) -> Option<u16> {
+    let hex = text.strip_prefix('#').unwrap_or(text);
+    if hex.len() != 6 {
+        return None;
+    }
+    let rgb = u32::from_str_radix(hex, 16).ok()?;
+    let (r, g, b) = (rgb >> 16, rgb >> 8 & 0xFF, rgb & 0xFF);
+    Some(((r >> 3) << 11 | (g >> 2) << 5 | b >> 3) as u16)
+}
+
+/// RGB565 编码展开为 8 位的 R、G、B
+fn rgb888(color: u16) -> [u8; 3] {
+    let (r, g, b) = (color >> 11, color >> 5 & 0x3F, color & 0x1F);
+    [
+        (r << 3 | r >> 2) as u8,
+        (g << 2 | g >> 4) as u8,
+        (b << 3 | b >> 2) as u8,
+    ]
+}
+
+/// 解析 28 位十六进制的 Gamma 校正表
+#[cfg(feature = "ui")]
+fn parse_gamma_table(hex: &str) -> Option<[u8; 14]> {
+    if hex.len() != 28 {
+        return None;
+    }
+    let mut table = [0u8; 14];
+    for (i, byte) in table.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(hex.get(i * 2..i * 2 + 2)?, 16).ok()?;
+    }
+    Some(table)
+}
+
+/// 输出初始化时读回的控制器型号、ID 和状态
+#[cfg(feature = "ui")]
+fn print_panel(out: &mut Writer, panel: &PanelInfo) {
+    writeln!(out, "controller: {}\r", panel.controller.name()).ok();
+    match panel.id {
+        Some(id) => {
+            let [manufacturer, version, driver] = id.bytes();
+            writeln!(out, "id: {manufacturer:02x} {version:02x} {driver:02x}\r")
+        }
+        None => writeln!(out, "id: -\r"),
+    }
+    .ok();
+    match panel.status {
+        Some(status) if panel.is_responding() => writeln!(out, "status: {:#010x}\r", status.0),
+        Some(status) => writeln!(out, "status: {:#010x} (not running)\r", status.0),
+        None => writeln!(out, "status: -\r"),
+    }
+    .ok();
+}
+
+/// 输出状态报告（见 [system::status]），每个子系统一行，传感器读数逐行缩进列出
+fn print_status(out: &mut Writer) {
+    let report = system::status();
+    let device = &report.device;
+    writeln!(out, "device: {} ({})\r", device.name, device.id).ok();
+    let secs = report.uptime.as_secs();
+    let (d, h, m, s) = (secs / 86400, secs / 3600 % 24, secs / 60 % 60, secs % 60);
+    writeln!(out, "uptime: {d}d {h:02}:{m:02}:{s:02}\r").ok();
+    let (used, free) = (report.heap_used, report.heap_free);
+    writeln!(out, "heap: {used} used, {free} free\r").ok();
+    match (report.wifi.connected, report.wifi.rssi) {
+        (false, _) => writeln!(out, "wifi: disconnected\r"),
+        (true, Some(rssi)) => writeln!(out, "wifi: connected, {rssi} dBm\r"),
+        (true, None) => writeln!(out, "wifi: connected\r"),
+    }
+    .ok();
+    writeln!(out, "sensors: {}\r", report.sensors.len()).ok();
+    for reading in &report.sensors {
+        let (name, value, unit) = (reading.name, reading.value, reading.unit);
+        writeln!(out, "  {name}: {value} {unit}\r").ok();
+    }
+    let state = |on: bool, yes: &'static str, no: &'static str| if on { yes } else { no };
+    let datalog = state(report.storage.datalog, "running", "stopped");
+    let sd_card = report.storage.sd_card.name();
+    writeln!(out, "storage: sd card {sd_card}, datalog {datalog}\r").ok();
+    let backlight = state(report.power.backlight, "on", "off");
+    let reset = report.power.reset_name();
+    writeln!(out, "power: backlight {backlight}, last reset {reset}\r").ok();
+}
+
+/// 按 `lcd table` 的参数格式输出 Gamma 校正表
+#[cfg(feature = "ui")]
+fn print_gamma_table(out: &mut Writer, polarity: &str, table: &[u8; 14]) {
+    write!(out, "table {}: ", polarity).ok();
+    for byte in table {
+        write!(out, "{:02x}", byte).ok();
+    }
+    writeln!(out, "\r").ok();
+}
+
+/// 解析 `can send` 的参数
+///
+/// # 参数
+/// * `id` - 十六进制 ID，3 位为标准帧，8 位为扩展帧（与 cansend 一致）
+/// * `data` - 十六进制数据，最多 8 字节
+fn parse_can_frame(id: &str, data: &str) -> Option<can::Frame> {
+    let extended = match id.len() {
+        1..=3 => false,
+        8 => true,
+        _ => return None,
+    };
+    let id = u32::from_str_radix(id, 16).ok()?;
+    if !data.len().is_multiple_of(2) || data.len() > 16 {
+        return None;
+    }
+    let mut bytes = [0u8; 8];
+    for (i, byte) in bytes.iter_mut().take(data.len() / 2).enumerate() {
+        *byte = u8::from_str_radix(data.get(i * 2..i * 2 + 2)?, 16).ok()?;
+    }
+    can::Frame::new(id, extended, &bytes[..data.len() / 2]).ok()
+}
+
+/// 解析 `lin add` 的调度表项：十六进制帧 ID、方向、数据长度和时间槽毫秒数
+fn parse_lin_entry(
+    id: Option<&str>,
+    direction: Option<&str>,
+    len: Option<&str>,
+    slot_ms: Option<&str>,
+) -> Option<lin::ScheduleEntry> {
+    let id = u8::from_str_radix(id?, 16).ok()?;
+    let direction = match direction? {
+        "pub" => lin::Direction::Publish,
+        "sub" => lin::Direction::Subscribe,
+        _ => return None,
+    };
+    lin::ScheduleEntry::new(id, direction, len?.parse().ok()?, slot_ms?.parse().ok()?)
+}
+
+/// 解析十六进制字节串，最多 N 字节
+fn parse_hex<const N: usize>(data: &str) -> Option<heapless::Vec<u8, N>> {
+    if !data.len().is_multiple_of(2) || data.len() > N * 2 {
+        return None;
+    }
+    (0..data.len() / 2)
+        .map(|i| u8::from_str_radix(data.get(i * 2..i * 2 + 2)?, 16).ok())
+        .collect()
+}
+
+/// 解析 `board port` 的接线：`off`，或各信号的引脚加可选的参数
+///
+/// 只检查格式和参数，引脚是否可用由 [Board::from_settings] 检查
+fn parse_port<'a>(
+    interface: Interface,
+    args: &mut impl Iterator<Item = &'a str>,
+) -> Option<Wiring> {
+    let mut wiring = Wiring::NONE;
+    let first = args.next()?;
+    if first == "off" {
+        return args.next().is_none().then_some(wiring);
+    }
+    let mut values = core::iter::once(first).chain(args);
+    for pin in wiring.pins.iter_mut().take(interface.signals().len()) {
+        let gpio = values.next()?.trim_start_matches("gpio").parse().ok();
+        *pin = gpio.filter(|&gpio| gpio != 0)?;
+    }
+    wiring.param = match values.next() {
+        Some(value) => value.parse().ok()?,
+        None => interface.default_param(),
+    };
+    (values.next().is_none() && interface.accepts(wiring.param)).then_some(wiring)
+}
+
+/// 解析透传设置 `serial|rs485 server <端口>` 或 `serial|rs485 client <IPv4 地址>:<端口>`
+fn parse_bridge(
+    interface: &str,
+    mode: Option<&str>,
+    target: Option<&str>,
+) -> Option<bridge::Setup> {
+    let interface = match interface {
+        "serial" => Interface::Serial,
+        "rs485" => Interface::Rs485,
+        _ => return None,
+    };
+    let mode = match (mode?, target?) {
+        ("server", port) => bridge::Mode::Server {
+            port: port.parse().ok().filter(|&port| port != 0)?,
+        },
+        ("client", remote) => {
+            let remote: SocketAddrV4 = remote.parse().ok()?;
+            if remote.port() == 0 {
+                return None;
+            }
+            bridge::Mode::Client {
+                remote: IpEndpoint::new(IpAddress::Ipv4(*remote.ip()), remote.port()),
+            }
+        }
+        _ => return None,
+    };
+    Some(bridge::Setup { interface, mode })
+}
+
+/// 输出一帧遥控通道数据（微秒）
+fn print_rc_frame(out: &mut Writer, frame: &rc::RcFrame) {
+    for pulse in frame.channels {
+        write!(out, "{:5}", pulse).ok();
+    }
+    let failsafe = if frame.failsafe { " failsafe" } else { "" };
+    writeln!(out, "{failsafe}\r").ok();
+}
+
+/// 按 candump 的格式输出一帧
+fn print_can_frame(out: &mut Writer, frame: &can::Frame) {
+    match frame.raw_id() {
+        (id, true) => write!(out, "{:08X}", id),
+        (id, false) => write!(out, "     {:03X}", id),
+    }
+    .ok();
+    write!(out, "   [{}] ", frame.len).ok();
+    if frame.remote {
+        write!(out, " remote request").ok();
+    }
+    for byte in frame.data() {
+        write!(out, " {:02X}", byte).ok();
+    }
+    writeln!(out, "\r").ok();
+}
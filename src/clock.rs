@@ -0,0 +1,422 @@
+//! 桌面时钟
+//!
+//! [Profile::Clock](crate::profile::Profile::Clock) 模式下代替渲染任务占用 LCD，
+//! 以大号数字或指针表盘显示本地时间，下方显示星期和日期，右上角显示 WiFi 连接状态。
+//! 时间来自 [crate::wallclock]（联网后由 [crate::sntp] 同步），
+//! 按设置中的 `utc_offset` 换算为本地时间（命令行 `clock tz`）。
+//!
+//! 白天使用 [crate::theme] 的配色，设置中的夜间时段（命令行 `clock night`）改用黑底暗红色显示，
+//! 降低夜间的屏幕亮度。
+//! 板上没有环境光传感器，背光也只能开关，因此按时段而不是按环境亮度切换。
+//!
+//! 按键：KEY2 切换数字/指针表盘（保存到设置）；按键不独占，KEY1 仍用于开关背光。
+
+use crate::i18n::{self, Msg};
+use crate::input::{self, Key};
+use crate::lcd::Lcd;
+use crate::st7789::{self, St7789};
+use crate::wallclock::{self, DateTime};
+use crate::{assets, settings, theme, wifi};
+use core::fmt::Write;
+use defmt::warn;
+use embassy_time::{Duration, with_timeout};
+use embedded_graphics::mono_font::ascii::FONT_6X10;
+use embedded_graphics::mono_font::{MonoFont, MonoTextStyleBuilder};
+use embedded_graphics::pixelcolor::Rgb565;
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::{Circle, Line, PrimitiveStyle};
+use embedded_graphics::text::Text;
+use heapless::String;
+use ui::segment::SegmentDisplay;
+use ui::theme::Theme;
+
+/// 大号时分数字的上边界和尺寸
+const DIGITS_Y: i32 = 40;
+const DIGIT_WIDTH: u32 = 48;
+const DIGIT_HEIGHT: u32 = 96;
+
+/// 秒数字的上边界和尺寸
+const SECONDS_Y: i32 = 150;
+const SECONDS_WIDTH: u32 = 20;
+const SECONDS_HEIGHT: u32 = 40;
+
+/// 表盘圆心和半径
+const DIAL_CENTER: Point = Point::new(160, 116);
+const DIAL_RADIUS: i32 = 96;
+
+/// 刻度的内侧半径：整点刻度和分钟刻度
+const HOUR_TICK_RADIUS: i32 = 82;
+const MINUTE_TICK_RADIUS: i32 = 90;
+
+/// 时针、分针、秒针的长度和粗细
+const HANDS: [(i32, u32); 3] = [(48, 5), (70, 3), (78, 1)];
+
+/// 日期行基线位置
+const DATE_Y: i32 = 232;
+
+/// WiFi 状态基线位置
+const STATUS_Y: i32 = 10;
+
+/// sin(n × 6°) × 1000，n = 0..=15，用于计算指针端点
+const SIN_TABLE: [i32; 16] = [
+    0, 105, 208, 309, 407, 500, 588, 669, 743, 809, 866, 914, 951, 978, 995, 1000,
+];
+
+/// 星期名称，从星期日开始
+const WEEKDAYS: [Msg; 7] = [
+    Msg::Sunday,
+    Msg::Monday,
+    Msg::Tuesday,
+    Msg::Wednesday,
+    Msg::Thursday,
+    Msg::Friday,
+    Msg::Saturday,
+];
+
+/// 表盘
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Face {
+    /// 大号数字
+    Digital,
+    /// 指针表盘
+    Analog,
+}
+
+impl Face {
+    /// 所有表盘，下标与设置中保存的编码一致
+    pub const ALL: [Face; 2] = [Face::Digital, Face::Analog];
+
+    /// 设置中保存的编码
+    pub const fn to_u8(self) -> u8 {
+        self as u8
+    }
+
+    /// 从设置中的编码解析，未知编码视为 [Face::Digital]
+    pub const fn from_u8(value: u8) -> Face {
+        match value {
+            1 => Face::Analog,
+            _ => Face::Digital,
+        }
+    }
+
+    /// 表盘名称，用于命令行参数
+    pub const fn name(self) -> &'static str {
+        match self {
+            Face::Digital => "digital",
+            Face::Analog => "analog",
+        }
+    }
+}
+
+/// 配色
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Palette {
+    /// 背景
+    background: Rgb565,
+    /// 数字、指针
+    foreground: Rgb565,
+    /// 表盘刻度、秒数字
+    accent: Rgb565,
+    /// 秒针
+    second_hand: Rgb565,
+    /// 次要文字
+    muted: Rgb565,
+}
+
+/// 白天配色：取自界面配色，秒针固定为红色
+const fn day(colors: Theme) -> Palette {
+    Palette {
+        background: colors.background,
+        foreground: colors.foreground,
+        accent: colors.accent,
+        second_hand: Rgb565::RED,
+        muted: colors.muted,
+    }
+}
+
+/// 夜间配色：只用低亮度的红色
+const NIGHT: Palette = Palette {
+    background: Rgb565::BLACK,
+    foreground: Rgb565::new(16, 0, 0),
+    accent: Rgb565::new(10, 0, 0),
+    second_hand: Rgb565::new(10, 0, 0),
+    muted: Rgb565::new(8, 0, 0),
+};
+
+/// 距离下一整秒的时长，未校准时为 1 秒
+fn until_next_second() -> Duration {
+    let micros = wallclock::now_micros().map_or(0, |us| us % 1_000_000);
+    Duration::from_micros(1_000_000 - micros)
+}
+
+/// `hour` 是否在夜间时段 `[start, end)` 内，时段可以跨越午夜
+fn is_night(hour: u8, [start, end]: [u8; 2]) -> bool {
+    if start <= end {
+        (start..end).contains(&hour)
+    } else {
+        hour >= start || hour < end
+    }
+}
+
+/// 表盘位置（0-59，12 点方向为 0）对应的 (sin, cos) × 1000
+fn direction(position: u32) -> (i32, i32) {
+    let sin = |p: u32| {
+        let (quadrant, step) = (p % 60 / 15, (p % 15) as usize);
+        match quadrant {
+            0 => SIN_TABLE[step],
+            1 => SIN_TABLE[15 - step],
+            2 => -SIN_TABLE[step],
+            _ => -SIN_TABLE[15 - step],
+        }
+    };
+    (sin(position), sin(position + 15))
+}
+
+/// 从圆心出发、指向 `position`、距离圆心 `radius` 的点
+fn dial_point(position: u32, radius: i32) -> Point {
+    let (sin, cos) = direction(position);
+    DIAL_CENTER + Point::new(radius * sin / 1000, -radius * cos / 1000)
+}
+
+/// 屏幕上已显示的内容，用于只重绘变化的部分
+struct Screen {
+    face: Face,
+    palette: Palette,
+    digits: SegmentDisplay,
+    seconds: SegmentDisplay,
+    /// 上次绘制的时针、分针、秒针位置
+    hands: Option<[u32; 3]>,
+    date: Option<String<32>>,
+    wifi: Option<bool>,
+}
+
+impl Screen {
+    /// 清屏后按表盘和配色重新开始绘制
+    fn new(lcd: &mut St7789, face: Face, palette: Palette) -> Screen {
+        if let Err(err) = lcd.fill_screen(palette.background) {
+            warn!("Failed to clear LCD: {}", err);
+        }
+        let digits = SegmentDisplay::new(
+            Point::new(0, DIGITS_Y),
+            DIGIT_WIDTH,
+            DIGIT_HEIGHT,
+            palette.foreground,
+        )
+        .with_background(palette.background)
+        .centered("00:00");
+        let seconds = SegmentDisplay::new(
+            Point::new(0, SECONDS_Y),
+            SECONDS_WIDTH,
+            SECONDS_HEIGHT,
+            palette.accent,
+        )
+        .with_background(palette.background)
+        .centered("00");
+        if face == Face::Analog {
+            draw_dial(lcd, palette);
+        }
+        Screen {
+            face,
+            palette,
+            digits,
+            seconds,
+            hands: None,
+            date: None,
+            wifi: None,
+        }
+    }
+
+    fn show_time(&mut self, lcd: &mut St7789, time: Option<&DateTime>) {
+        match self.face {
+            Face::Digital => {
+                let mut text: String<8> = String::new();
+                match time {
+                    Some(t) => write!(text, "{:02}:{:02}", t.hour, t.minute),
+                    None => write!(text, "--:--"),
+                }
+                .ok();
+                if let Err(err) = self.digits.show(lcd, &text) {
+                    warn!("Failed to draw clock digits: {}", err);
+                }
+                text.clear();
+                match time {
+                    Some(t) => write!(text, "{:02}", t.second),
+                    None => write!(text, "--"),
+                }
+                .ok();
+                self.seconds.show(lcd, &text).ok();
+            }
+            Face::Analog => {
+                let Some(t) = time else {
+                    return;
+                };
+                let minute = t.minute as u32;
+                let hands = [
+                    (t.hour as u32 % 12) * 5 + minute / 12,
+                    minute,
+                    t.second as u32,
+                ];
+                if self.hands == Some(hands) {
+                    return;
+                }
+                // 擦除全部旧指针后重绘，避免指针重叠处留下缺口
+                if let Some(old) = self.hands {
+                    for (position, (length, width)) in old.into_iter().zip(HANDS) {
+                        draw_hand(lcd, position, length, width, self.palette.background);
+                    }
+                }
+                let colors = [
+                    self.palette.foreground,
+                    self.palette.foreground,
+                    self.palette.second_hand,
+                ];
+                for ((position, (length, width)), color) in hands.into_iter().zip(HANDS).zip(colors)
+                {
+                    draw_hand(lcd, position, length, width, color);
+                }
+                Circle::with_center(DIAL_CENTER, 9)
+                    .into_styled(PrimitiveStyle::with_fill(self.palette.foreground))
+                    .draw(lcd)
+                    .ok();
+                self.hands = Some(hands);
+            }
+        }
+    }
+
+    fn show_date(&mut self, lcd: &mut St7789, text: &str) {
+        if self.date.as_deref() == Some(text) {
+            return;
+        }
+        let (foreground, background) = (self.palette.foreground, self.palette.background);
+        lcd.fill_rectangle(0, (DATE_Y - 16) as u16, st7789::WIDTH, 22, background)
+            .ok();
+        let x = (st7789::WIDTH as i32 - text.len() as i32 * 10).max(0) / 2;
+        draw_text(lcd, text, assets::font(), x, DATE_Y, foreground, background);
+        self.date = String::try_from(text).ok();
+    }
+
+    fn show_wifi(&mut self, lcd: &mut St7789, connected: bool) {
+        if self.wifi == Some(connected) {
+            return;
+        }
+        let (msg, color) = if connected {
+            (Msg::ClockOnline, self.palette.accent)
+        } else {
+            (Msg::ClockOffline, self.palette.muted)
+        };
+        let background = self.palette.background;
+        lcd.fill_rectangle(st7789::WIDTH - 60, 0, 60, 14, background)
+            .ok();
+        let text = i18n::lcd_small(msg);
+        let x = st7789::WIDTH as i32 - 4 - text.len() as i32 * 6;
+        draw_text(lcd, text, &FONT_6X10, x, STATUS_Y, color, background);
+        self.wifi = Some(connected);
+    }
+}
+
+/// 绘制表盘外圈和刻度
+fn draw_dial(lcd: &mut St7789, palette: Palette) {
+    Circle::with_center(DIAL_CENTER, DIAL_RADIUS as u32 * 2 + 1)
+        .into_styled(PrimitiveStyle::with_stroke(palette.muted, 2))
+        .draw(lcd)
+        .ok();
+    for position in 0..60 {
+        let (inner, width) = if position % 5 == 0 {
+            (HOUR_TICK_RADIUS, 3)
+        } else {
+            (MINUTE_TICK_RADIUS, 1)
+        };
+        Line::new(
+            dial_point(position, inner),
+            dial_point(position, DIAL_RADIUS - 3),
+        )
+        .into_styled(PrimitiveStyle::with_stroke(palette.accent, width))
+        .draw(lcd)
+        .ok();
+    }
+}
+
+fn draw_hand(lcd: &mut St7789, position: u32, length: i32, width: u32, color: Rgb565) {
+    Line::new(DIAL_CENTER, dial_point(position, length))
+        .into_styled(PrimitiveStyle::with_stroke(color, width))
+        .draw(lcd)
+        .ok();
+}
+
+fn draw_text(
+    lcd: &mut St7789,
+    text: &str,
+    font: &MonoFont<'_>,
+    x: i32,
+    y: i32,
+    color: Rgb565,
+    background: Rgb565,
+) {
+    let style = MonoTextStyleBuilder::new()
+        .font(font)
+        .text_color(color)
+        .background_color(background)
+        .build();
+    if let Err(err) = Text::new(text, Point::new(x, y), style).draw(lcd) {
+        warn!("Failed to draw clock text: {}", err);
+    }
+}
+
+/// 桌面时钟任务
+///
+/// # 参数
+/// * `lcd` - 已完成初始化的 LCD
+#[embassy_executor::task]
+pub async fn clock_task(mut lcd: Lcd) {
+    let Some(mut keys) = input::subscribe() else {
+        warn!("No key subscriber available for clock");
+        return;
+    };
+
+    let mut shown: Option<Screen> = None;
+    let mut date: String<32> = String::new();
+    loop {
+        let now = wallclock::local_now();
+        let settings = settings::get();
+        let face = Face::from_u8(settings.clock_face);
+        let night = now.is_some_and(|(t, _)| is_night(t.hour, settings.night_hours));
+        let palette = if night { NIGHT } else { day(theme::current()) };
+
+        // 表盘或配色改变时完整重绘
+        if shown
+            .as_ref()
+            .is_some_and(|s| s.face != face || s.palette != palette)
+        {
+            shown = None;
+        }
+        let screen = shown.get_or_insert_with(|| Screen::new(&mut lcd, face, palette));
+        screen.show_time(&mut lcd, now.as_ref().map(|(t, _)| t));
+
+        date.clear();
+        match now {
+            Some((t, weekday)) => {
+                let name = i18n::lcd(WEEKDAYS[weekday]);
+                write!(date, "{} {:04}-{:02}-{:02}", name, t.year, t.month, t.day).ok();
+            }
+            None => {
+                date.push_str(i18n::lcd(Msg::ClockWaiting)).ok();
+            }
+        }
+        screen.show_date(&mut lcd, &date);
+        screen.show_wifi(&mut lcd, wifi::is_connected());
+
+        let Ok(key) = with_timeout(until_next_second(), keys.next_message_pure()).await else {
+            continue;
+        };
+        if key == Key::Key2 {
+            let face = match face {
+                Face::Digital => Face::Analog,
+                Face::Analog => Face::Digital,
+            };
+            settings::update(|s| s.clock_face = face.to_u8());
+            if let Err(err) = settings::save() {
+                warn!("Failed to save clock face: {}", err);
+            }
+        }
+    }
+}
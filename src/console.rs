@@ -0,0 +1,278 @@
+//! 控制台
+//!
+//! 控制台同时承载 defmt 日志输出（见 [crate::logbuf]）和命令行（见 [crate::cli]），
+//! 后端可选：
+//!
+//! - [Backend::UsbSerialJtag]：ESP32-S3 内置的 USB Serial/JTAG，只需一根 USB 线
+//! - [Backend::Uart]：UART0（TX: GPIO43，RX: GPIO44），经板载 CH340 转 USB
+//!
+//! 默认后端在编译时通过 `console-uart` feature 选择（未启用时为 USB Serial/JTAG），
+//! 运行时可通过设置覆盖（`console usb|uart` 命令，重启后生效）。
+//! 控制台初始化之前的日志仍由 esp-println 输出。
+
+use crate::{cli, settings};
+use core::cell::RefCell;
+use core::pin::pin;
+use critical_section::Mutex;
+use defmt::{info, warn};
+use embassy_futures::select::{Either, select};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::signal::Signal;
+use esp_hal::uart::{Config as UartConfig, Uart, UartRx, UartTx};
+use esp_hal::usb_serial_jtag::{UsbSerialJtag, UsbSerialJtagRx, UsbSerialJtagTx};
+use esp_hal::{Async, Blocking};
+
+/// 命令行最大长度
+const LINE_LEN: usize = 128;
+
+/// 命令执行期间收到输入时发出，长时间运行的命令据此提前结束
+static INTERRUPT: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+
+/// USB 主机未读取数据时，单个字节最多重试的次数，超过后丢弃剩余输出
+const USB_WRITE_RETRIES: u32 = 1000;
+
+/// 控制台后端
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Backend {
+    /// 内置 USB Serial/JTAG
+    UsbSerialJtag,
+    /// UART0 (CH340)
+    Uart,
+}
+
+impl Backend {
+    /// 编译时选择的默认后端
+    pub const DEFAULT: Backend = if cfg!(feature = "console-uart") {
+        Backend::Uart
+    } else {
+        Backend::UsbSerialJtag
+    };
+
+    /// 设置中保存的编码，0 表示使用编译时默认值
+    pub const fn to_u8(self) -> u8 {
+        match self {
+            Backend::UsbSerialJtag => 1,
+            Backend::Uart => 2,
+        }
+    }
+
+    /// 从设置中的编码解析
+    pub const fn from_u8(value: u8) -> Backend {
+        match value {
+            1 => Backend::UsbSerialJtag,
+            2 => Backend::Uart,
+            _ => Backend::DEFAULT,
+        }
+    }
+
+    /// 后端名称
+    pub const fn name(self) -> &'static str {
+        match self {
+            Backend::UsbSerialJtag => "usb",
+            Backend::Uart => "uart",
+        }
+    }
+}
+
+/// 控制台发送端
+enum ConsoleTx {
+    Usb(UsbSerialJtagTx<'static, Async>),
+    Uart(UartTx<'static, Blocking>),
+}
+
+// SAFETY: 异步模式的驱动不是 Send，因为它的中断处理绑定在初始化时的 CPU 核上。
+// 发送端只在 CONSOLE_TX 的临界区内使用，且只调用不依赖中断的非阻塞写方法，
+// 在哪个核上调用都一样
+unsafe impl Send for ConsoleTx {}
+
+/// 控制台接收端，交给 [console_task]
+pub enum ConsoleRx {
+    Usb(UsbSerialJtagRx<'static, Async>),
+    Uart(UartRx<'static, Async>),
+}
+
+static CONSOLE_TX: Mutex<RefCell<Option<ConsoleTx>>> = Mutex::new(RefCell::new(None));
+
+/// 控制台所需的外设
+pub struct ConsolePins {
+    pub usb_device: esp_hal::peripherals::USB_DEVICE<'static>,
+    pub uart: esp_hal::peripherals::UART0<'static>,
+    pub tx: esp_hal::peripherals::GPIO43<'static>,
+    pub rx: esp_hal::peripherals::GPIO44<'static>,
+}
+
+/// 当前设置选择的后端
+pub fn selected_backend() -> Backend {
+    Backend::from_u8(settings::get().console)
+}
+
+/// 初始化控制台
+///
+/// 根据设置选择后端，之后的日志都从该后端输出
+///
+/// # 返回
+/// 接收端，需交给 [console_task] 运行命令行；初始化失败时返回 None
+pub fn init(pins: ConsolePins) -> Option<ConsoleRx> {
+    let backend = selected_backend();
+    let (rx, tx) = match backend {
+        Backend::UsbSerialJtag => {
+            let (rx, tx) = UsbSerialJtag::new(pins.usb_device).into_async().split();
+            (ConsoleRx::Usb(rx), ConsoleTx::Usb(tx))
+        }
+        Backend::Uart => {
+            let uart = match Uart::new(pins.uart, UartConfig::default()) {
+                Ok(uart) => uart,
+                Err(err) => {
+                    warn!("Failed to initialize console UART: {}", err);
+                    return None;
+                }
+            };
+            // 只有接收端需要异步，发送端保持阻塞模式以便放进 CONSOLE_TX
+            let (rx, tx) = uart.with_rx(pins.rx).with_tx(pins.tx).split();
+            (ConsoleRx::Uart(rx.into_async()), ConsoleTx::Uart(tx))
+        }
+    };
+
+    critical_section::with(|cs| {
+        CONSOLE_TX.borrow_ref_mut(cs).replace(tx);
+    });
+    info!("Console on {}", backend.name());
+    Some(rx)
+}
+
+/// 向控制台输出原始字节
+///
+/// # 返回
+/// 控制台尚未初始化时返回 false，由调用者自行选择其他输出方式
+pub fn write_bytes(bytes: &[u8]) -> bool {
+    critical_section::with(|cs| {
+        let mut tx = CONSOLE_TX.borrow_ref_mut(cs);
+        match tx.as_mut() {
+            Some(ConsoleTx::Usb(tx)) => {
+                usb_write(tx, bytes);
+                true
+            }
+            Some(ConsoleTx::Uart(tx)) => {
+                let mut data = bytes;
+                while !data.is_empty() {
+                    match tx.write(data) {
+                        Ok(written) => data = &data[written..],
+                        Err(_) => break,
+                    }
+                }
+                true
+            }
+            None => false,
+        }
+    })
+}
+
+/// 向 USB Serial/JTAG 写入数据
+///
+/// 没有主机读取时 FIFO 会一直是满的，这里限制重试次数，避免日志输出卡死系统
+fn usb_write(tx: &mut UsbSerialJtagTx<'static, Async>, bytes: &[u8]) {
+    for &byte in bytes {
+        let mut retries = 0;
+        while tx.write_byte_nb(byte).is_err() {
+            retries += 1;
+            if retries >= USB_WRITE_RETRIES {
+                return;
+            }
+        }
+    }
+    tx.flush_tx_nb().ok();
+}
+
+/// 控制台文本输出，用于命令行回显和命令输出
+pub struct Writer;
+
+impl core::fmt::Write for Writer {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        if !write_bytes(s.as_bytes()) {
+            esp_println::Printer::write_bytes(s.as_bytes());
+        }
+        Ok(())
+    }
+}
+
+/// 读取一个字节
+async fn read_byte(rx: &mut ConsoleRx) -> Option<u8> {
+    let mut byte = [0u8];
+    let read = match rx {
+        ConsoleRx::Usb(rx) => embedded_io_async::Read::read(rx, &mut byte).await.ok(),
+        ConsoleRx::Uart(rx) => rx.read_async(&mut byte).await.ok(),
+    };
+    match read {
+        Some(1) => Some(byte[0]),
+        _ => None,
+    }
+}
+
+/// 等待控制台输入，用于长时间运行的命令在用户按任意键时提前结束
+///
+/// 只响应调用之后的输入
+pub async fn wait_interrupt() {
+    INTERRUPT.reset();
+    INTERRUPT.wait().await;
+}
+
+/// 执行一行命令，执行期间的输入被丢弃并发出 [wait_interrupt] 通知
+///
+/// # 参数
+/// * `rx` - 控制台输入
+/// * `line` - 命令行
+/// * `out` - 命令输出
+async fn run_command(rx: &mut ConsoleRx, line: &str, out: &mut Writer) {
+    let mut command = pin!(cli::execute(line, out));
+    loop {
+        match select(&mut command, read_byte(rx)).await {
+            Either::First(()) => return,
+            // 回车后跟随的换行不算
+            Either::Second(Some(b'\n')) | Either::Second(None) => {}
+            Either::Second(Some(_)) => INTERRUPT.signal(()),
+        }
+    }
+}
+
+/// 命令行任务
+///
+/// 逐字节读取输入，支持退格编辑，回车后交给 [cli::execute] 执行
+#[embassy_executor::task]
+pub async fn console_task(mut rx: ConsoleRx) {
+    use core::fmt::Write;
+
+    let mut line: heapless::String<LINE_LEN> = heapless::String::new();
+    let mut out = Writer;
+    write!(out, "\r\n{}", cli::PROMPT).ok();
+    // 终端以 CRLF 结束一行时，CR 之后的 LF 不再输出一次提示符
+    let mut after_cr = false;
+
+    loop {
+        let Some(byte) = read_byte(&mut rx).await else {
+            continue;
+        };
+        let skip = byte == b'\n' && after_cr;
+        after_cr = byte == b'\r';
+        match byte {
+            b'\n' if skip => {}
+            b'\r' | b'\n' => {
+                write!(out, "\r\n").ok();
+                if !line.is_empty() {
+                    run_command(&mut rx, line.trim(), &mut out).await;
+                    line.clear();
+                }
+                write!(out, "{}", cli::PROMPT).ok();
+            }
+            // 退格或 DEL
+            0x08 | 0x7F => {
+                if line.pop().is_some() {
+                    write!(out, "\x08 \x08").ok();
+                }
+            }
+            0x20..=0x7E if line.push(byte as char).is_ok() => {
+                write!(out, "{}", byte as char).ok();
+            }
+            _ => {}
+        }
+    }
+}
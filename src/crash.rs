@@ -0,0 +1,356 @@
+//! 崩溃记录
+//!
+//! 发生 panic（包括 CPU 异常，esp-hal 的默认异常处理会转为 panic）时，
+//! 由本模块的 panic 处理函数记录一份精简的崩溃信息，然后软件复位：
+//!
+//! - panic 位置和消息（截断到 [MESSAGE_LEN] 字节）
+//! - 回溯 PC 地址（最多 [BACKTRACE_LEN] 个）
+//! - 运行时间
+//! - CPU 异常时异常现场的寄存器（EXCCAUSE、PC、PS、A0、SP、EXCVADDR，见 [Registers]）
+//!
+//! panic 时 Flash 可能正被占用，因此记录先写入 RTC 快速内存，
+//! 下次启动时由 [persist_pending] 转存到 Flash 的崩溃扇区（0xA000），
+//! 之后可通过 [last] 读取、[clear] 清除。
+//!
+//! 回溯地址可使用 `xtensa-esp32s3-elf-addr2line -e <elf> <pc>...` 还原为源码位置。
+
+use crate::storage::{self, StorageError};
+use core::fmt::Write;
+use core::panic::PanicInfo;
+use defmt::{error, info, warn};
+use esp_hal::xtensa_lx_rt::exception::{Context, ExceptionCause};
+
+/// 崩溃记录所在扇区
+pub const CRASH_OFFSET: u32 = storage::SETTINGS_OFFSET + storage::SECTOR_SIZE;
+
+/// 回溯地址最大数量
+pub const BACKTRACE_LEN: usize = 10;
+
+/// panic 消息最大长度
+pub const MESSAGE_LEN: usize = 96;
+
+/// RTC 内存中崩溃记录的魔数
+const CRASH_MAGIC: u32 = 0x4352_5348;
+
+/// [Registers::cause] 的取值，表示普通 panic 而不是 CPU 异常
+pub const NO_EXCEPTION: u32 = u32::MAX;
+
+/// 编码后的记录长度：运行时间(8) + 寄存器(6 * 4)
+/// + 回溯数量(1) + 消息长度(1) + 回溯 + 消息
+const ENCODED_LEN: usize = 34 + BACKTRACE_LEN * 4 + MESSAGE_LEN;
+
+/// 回溯数量在编码中的位置
+const COUNTS_AT: usize = 32;
+
+/// CPU 异常时的寄存器，取自异常处理保存的现场
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(C)]
+pub struct Registers {
+    /// 异常原因（EXCCAUSE），普通 panic 时为 [NO_EXCEPTION]
+    pub cause: u32,
+    /// 出错指令的地址
+    pub pc: u32,
+    /// 处理器状态（PS）
+    pub ps: u32,
+    /// 返回地址（A0）
+    pub a0: u32,
+    /// 栈指针（A1）
+    pub sp: u32,
+    /// 出错的访存地址（EXCVADDR）
+    pub excvaddr: u32,
+}
+
+impl Registers {
+    const NONE: Registers = Registers {
+        cause: NO_EXCEPTION,
+        pc: 0,
+        ps: 0,
+        a0: 0,
+        sp: 0,
+        excvaddr: 0,
+    };
+
+    /// 是否由 CPU 异常引起
+    pub fn is_exception(&self) -> bool {
+        self.cause != NO_EXCEPTION
+    }
+
+    fn words(&self) -> [u32; 6] {
+        [
+            self.cause,
+            self.pc,
+            self.ps,
+            self.a0,
+            self.sp,
+            self.excvaddr,
+        ]
+    }
+
+    fn from_words(words: [u32; 6]) -> Registers {
+        let [cause, pc, ps, a0, sp, excvaddr] = words;
+        Registers {
+            cause,
+            pc,
+            ps,
+            a0,
+            sp,
+            excvaddr,
+        }
+    }
+}
+
+/// 崩溃记录
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(C)]
+pub struct CrashRecord {
+    /// 崩溃时的运行时间（毫秒）
+    pub uptime_ms: u64,
+    /// CPU 异常时的寄存器
+    pub registers: Registers,
+    /// 有效的回溯地址数量
+    pub backtrace_len: u8,
+    /// 有效的消息长度
+    pub message_len: u8,
+    /// 回溯 PC 地址
+    pub backtrace: [u32; BACKTRACE_LEN],
+    /// panic 位置和消息
+    pub message: [u8; MESSAGE_LEN],
+}
+
+// SAFETY: 两个结构体都只包含整数和整数数组，任意位模式都是合法值；
+// 长度字段在使用前都会截断到数组长度，消息按 UTF-8 解码失败时只取合法前缀
+unsafe impl esp_hal::Persistable for Registers {}
+unsafe impl esp_hal::Persistable for CrashRecord {}
+
+impl CrashRecord {
+    const EMPTY: CrashRecord = CrashRecord {
+        uptime_ms: 0,
+        registers: Registers::NONE,
+        backtrace_len: 0,
+        message_len: 0,
+        backtrace: [0; BACKTRACE_LEN],
+        message: [0; MESSAGE_LEN],
+    };
+
+    /// 有效的回溯地址
+    pub fn backtrace(&self) -> &[u32] {
+        &self.backtrace[..(self.backtrace_len as usize).min(BACKTRACE_LEN)]
+    }
+
+    /// panic 位置和消息
+    pub fn message(&self) -> &str {
+        let bytes = &self.message[..(self.message_len as usize).min(MESSAGE_LEN)];
+        match core::str::from_utf8(bytes) {
+            Ok(text) => text,
+            // 截断可能切在多字节字符中间，只保留合法前缀
+            Err(err) => core::str::from_utf8(&bytes[..err.valid_up_to()]).unwrap_or(""),
+        }
+    }
+
+    fn encode(&self, buf: &mut [u8; ENCODED_LEN]) {
+        buf[0..8].copy_from_slice(&self.uptime_ms.to_le_bytes());
+        for (i, word) in self.registers.words().iter().enumerate() {
+            buf[8 + i * 4..12 + i * 4].copy_from_slice(&word.to_le_bytes());
+        }
+        buf[COUNTS_AT] = self.backtrace_len;
+        buf[COUNTS_AT + 1] = self.message_len;
+        for (i, pc) in self.backtrace.iter().enumerate() {
+            let at = COUNTS_AT + 2 + i * 4;
+            buf[at..at + 4].copy_from_slice(&pc.to_le_bytes());
+        }
+        buf[COUNTS_AT + 2 + BACKTRACE_LEN * 4..].copy_from_slice(&self.message);
+    }
+
+    fn decode(buf: &[u8; ENCODED_LEN]) -> CrashRecord {
+        let word = |at: usize| u32::from_le_bytes([buf[at], buf[at + 1], buf[at + 2], buf[at + 3]]);
+        let mut record = CrashRecord::EMPTY;
+        let mut uptime = [0u8; 8];
+        uptime.copy_from_slice(&buf[0..8]);
+        record.uptime_ms = u64::from_le_bytes(uptime);
+        record.registers = Registers::from_words(core::array::from_fn(|i| word(8 + i * 4)));
+        record.backtrace_len = buf[COUNTS_AT];
+        record.message_len = buf[COUNTS_AT + 1];
+        for (i, pc) in record.backtrace.iter_mut().enumerate() {
+            *pc = word(COUNTS_AT + 2 + i * 4);
+        }
+        record
+            .message
+            .copy_from_slice(&buf[COUNTS_AT + 2 + BACKTRACE_LEN * 4..]);
+        record
+    }
+}
+
+/// 等待转存的崩溃记录是否有效，值为 [CRASH_MAGIC] 时有效
+#[esp_hal::ram(unstable(rtc_fast, persistent))]
+static mut PENDING_MAGIC: u32 = 0;
+
+/// 等待转存的崩溃记录
+///
+/// 位于 RTC 快速内存，软件复位后内容保持不变
+#[esp_hal::ram(unstable(rtc_fast, persistent))]
+static mut PENDING_RECORD: CrashRecord = CrashRecord::EMPTY;
+
+/// CPU 异常时由 [exception] 保存的寄存器，之后的 panic 处理把它写入记录
+static mut FAULT: Registers = Registers::NONE;
+
+/// 是否已经进入 panic 处理，处理过程中再次 panic 时直接复位
+static mut PANICKING: bool = false;
+
+/// 将格式化输出写入固定长度缓冲区，超出部分丢弃
+struct MessageWriter<'a> {
+    buf: &'a mut [u8; MESSAGE_LEN],
+    len: usize,
+}
+
+impl Write for MessageWriter<'_> {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        let take = s.len().min(MESSAGE_LEN - self.len);
+        self.buf[self.len..self.len + take].copy_from_slice(&s.as_bytes()[..take]);
+        self.len += take;
+        Ok(())
+    }
+}
+
+/// CPU 异常处理：保存异常现场的寄存器，再交给 panic 处理记录并复位
+///
+/// 替换 xtensa-lx-rt 的默认处理（`__user_exception`），后者只把现场格式化进 panic 消息，
+/// 记录里留不下寄存器。没有用 `#[exception]` 属性，它生成的 `export_name` 在 2024 版本中
+/// 必须写成 unsafe 属性
+#[unsafe(export_name = "__user_exception")]
+fn exception(cause: ExceptionCause, frame: &Context) -> ! {
+    let registers = Registers {
+        cause: frame.EXCCAUSE,
+        pc: frame.PC,
+        ps: frame.PS,
+        a0: frame.A0,
+        sp: frame.A1,
+        excvaddr: frame.EXCVADDR,
+    };
+    // SAFETY: 只有异常处理写、panic 处理读，两者在同一个核上先后执行
+    unsafe { core::ptr::write_volatile(&raw mut FAULT, registers) };
+    panic!("Exception: {:?}", cause)
+}
+
+/// panic 处理：先把记录写入 RTC 内存，再输出日志，最后软件复位
+///
+/// 记录不经过堆（分配器的锁可能正被出错的代码持有）也不依赖日志，
+/// 日志输出本身再次出错时记录已经保存好了
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    // SAFETY: panic 时不再调度其他任务，这里只在本核上读写
+    let nested = unsafe { core::ptr::replace(&raw mut PANICKING, true) };
+    if nested {
+        esp_hal::system::software_reset()
+    }
+
+    let mut record = CrashRecord::EMPTY;
+    record.uptime_ms = esp_hal::time::Instant::now()
+        .duration_since_epoch()
+        .as_millis();
+    // SAFETY: 见 [exception]
+    record.registers = unsafe { core::ptr::read_volatile(&raw const FAULT) };
+
+    let backtrace = esp_backtrace::Backtrace::capture();
+    for (slot, frame) in record.backtrace.iter_mut().zip(backtrace.frames()) {
+        *slot = frame.program_counter() as u32;
+        record.backtrace_len += 1;
+    }
+
+    let mut writer = MessageWriter {
+        buf: &mut record.message,
+        len: 0,
+    };
+    if let Some(location) = info.location() {
+        write!(writer, "{}:{}: ", location.file(), location.line()).ok();
+    }
+    write!(writer, "{}", info.message()).ok();
+    record.message_len = writer.len as u8;
+
+    // SAFETY: 复位前没有其他代码访问该记录；先写记录再写魔数，
+    // 写到一半复位时下次启动不会把半条记录当成有效记录
+    unsafe {
+        core::ptr::write_volatile(&raw mut PENDING_RECORD, record);
+        core::ptr::write_volatile(&raw mut PENDING_MAGIC, CRASH_MAGIC);
+    }
+
+    error!("{}", defmt::Display2Format(info));
+    log_registers(&record.registers);
+    error!("Backtrace: {:#x}", record.backtrace());
+
+    esp_hal::system::software_reset()
+}
+
+/// 输出 CPU 异常时的寄存器，普通 panic 时不输出
+fn log_registers(registers: &Registers) {
+    if registers.is_exception() {
+        error!(
+            "EXCCAUSE {} PC {:#010x} PS {:#010x} A0 {:#010x} SP {:#010x} EXCVADDR {:#010x}",
+            registers.cause,
+            registers.pc,
+            registers.ps,
+            registers.a0,
+            registers.sp,
+            registers.excvaddr
+        );
+    }
+}
+
+/// 将上次崩溃留在 RTC 内存中的记录转存到 Flash
+///
+/// 需在 [crate::storage::init] 之后、启动阶段调用
+///
+/// # 返回
+/// 上次复位是否由崩溃引起
+pub fn persist_pending() -> bool {
+    // SAFETY: 只在启动阶段单线程调用
+    let record = unsafe {
+        let magic = &raw mut PENDING_MAGIC;
+        if *magic != CRASH_MAGIC {
+            return false;
+        }
+        *magic = 0;
+        (&raw const PENDING_RECORD).read()
+    };
+
+    warn!("Previous run crashed: {}", record.message());
+    let mut buf = [0u8; ENCODED_LEN];
+    record.encode(&mut buf);
+    if let Err(err) = storage::write_blob(CRASH_OFFSET, &buf) {
+        warn!("Failed to save crash record: {}", err);
+    }
+    true
+}
+
+/// 读取 Flash 中保存的崩溃记录
+///
+/// # 返回
+/// 没有记录时返回 None
+pub fn last() -> Option<CrashRecord> {
+    let mut buf = [0u8; ENCODED_LEN];
+    match storage::read_blob(CRASH_OFFSET, &mut buf) {
+        Ok(ENCODED_LEN) => Some(CrashRecord::decode(&buf)),
+        Ok(_) | Err(StorageError::Corrupted) => None,
+        Err(err) => {
+            warn!("Failed to read crash record: {}", err);
+            None
+        }
+    }
+}
+
+/// 清除 Flash 中保存的崩溃记录
+pub fn clear() -> Result<(), StorageError> {
+    storage::erase_blob(CRASH_OFFSET)
+}
+
+/// 打印 Flash 中保存的崩溃记录
+pub fn log_last() {
+    match last() {
+        Some(record) => {
+            info!("Crash record: uptime {} ms", record.uptime_ms);
+            info!("  {}", record.message());
+            log_registers(&record.registers);
+            info!("  backtrace: {:#x}", record.backtrace());
+        }
+        None => info!("No crash record"),
+    }
+}
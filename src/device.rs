@@ -0,0 +1,115 @@
+//! 设备标识
+//!
+//! 每块板子有一个固定的 ID（[id]），由出厂时烧写在 eFuse 中的基准 MAC 地址生成，
+//! 重刷固件、恢复出厂设置后都不变；另有在设置中保存的名称、安装位置和标签，
+//! 在部署了多块板子时区分设备。名称未设置时使用主机名（见 [crate::net::hostname]）。
+//!
+//! 标识（[Identity]）出现在：
+//!
+//! - 告警通知的请求体（[crate::notifier]）
+//! - HTTP `GET /api/device`（[crate::http]）
+//! - 状态报告（[crate::system::status]）
+//! - SNMP 的 sysName 和 sysLocation（[crate::snmp]）
+//! - 状态屏幕的设置页面（[crate::screens]）
+//!
+//! 名称、位置和标签通过命令行 `device` 修改。
+
+use crate::net;
+use crate::settings::{self, DEVICE_LOCATION_LEN, DEVICE_NAME_LEN, DEVICE_TAGS_LEN};
+use core::fmt::Write;
+use esp_hal::efuse::Efuse;
+use heapless::String;
+use proto::json::{Object, ToJson};
+
+/// ID 的长度：MAC 地址的 12 个十六进制数字
+pub const ID_LEN: usize = 12;
+
+/// 设备标识
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Identity {
+    /// 由 MAC 地址生成的 ID，例如 `f412fa1b2c3d`
+    pub id: String<ID_LEN>,
+    /// 名称，未设置时为主机名
+    pub name: String<DEVICE_NAME_LEN>,
+    /// 安装位置，可以为空
+    pub location: String<DEVICE_LOCATION_LEN>,
+    /// 逗号分隔的标签，可以为空
+    pub tags: String<DEVICE_TAGS_LEN>,
+}
+
+impl Identity {
+    /// 逐个取出标签，去掉首尾空白
+    pub fn tags(&self) -> impl Iterator<Item = &str> {
+        self.tags
+            .split(',')
+            .map(str::trim)
+            .filter(|tag| !tag.is_empty())
+    }
+}
+
+impl ToJson for Identity {
+    /// 标签写为字符串数组，空的位置写为空字符串
+    fn write_members(&self, object: &mut Object<'_>) {
+        object
+            .str("id", &self.id)
+            .str("name", &self.name)
+            .str("location", &self.location);
+        let mut tags = object.array("tags");
+        for tag in self.tags() {
+            tags.str(tag);
+        }
+    }
+}
+
+/// 设备 ID，MAC 地址的小写十六进制形式
+pub fn id() -> String<ID_LEN> {
+    format_id(&Efuse::mac_address())
+}
+
+/// 把 MAC 地址写成设备 ID 的形式，其他板子的 MAC 地址（例如 ESP-NOW 对端）也这样显示
+pub fn format_id(mac: &[u8; 6]) -> String<ID_LEN> {
+    let mut id = String::new();
+    for byte in mac {
+        write!(id, "{:02x}", byte).ok();
+    }
+    id
+}
+
+/// 解析设备 ID 形式的 MAC 地址，大小写均可
+pub fn parse_id(id: &str) -> Option<[u8; 6]> {
+    if id.len() != ID_LEN {
+        return None;
+    }
+    let mut mac = [0u8; 6];
+    for (i, byte) in mac.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(id.get(i * 2..i * 2 + 2)?, 16).ok()?;
+    }
+    Some(mac)
+}
+
+/// 当前的设备标识
+pub fn identity() -> Identity {
+    let s = settings::get();
+    let name = if s.device_name.is_empty() {
+        // 主机名与名称的长度上限相同
+        String::try_from(net::hostname().as_str()).unwrap_or_default()
+    } else {
+        s.device_name
+    };
+    Identity {
+        id: id(),
+        name,
+        location: s.device_location,
+        tags: s.device_tags,
+    }
+}
+
+/// 名称或位置是否合法：不含控制字符
+pub fn is_valid_text(text: &str) -> bool {
+    !text.chars().any(char::is_control)
+}
+
+/// 标签列表是否合法：逗号分隔，每个标签去掉首尾空白后不为空
+pub fn is_valid_tags(tags: &str) -> bool {
+    is_valid_text(tags) && tags.split(',').all(|tag| !tag.trim().is_empty())
+}
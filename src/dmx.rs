@@ -0,0 +1,198 @@
+//! DMX512 输出
+//!
+//! 通过 UART 和 RS485 收发器驱动舞台灯光。DMX512 帧由以下部分组成：
+//!
+//! - break：线路保持低电平至少 88 µs
+//! - mark-after-break（MAB）：高电平至少 8 µs
+//! - 起始码 0x00 和最多 512 个通道值，250000 波特率 8N2
+//!
+//! UART 没有直接产生 break 的接口，这里临时切换到 [BREAK_BAUDRATE] 发送一个 0x00：
+//! 起始位加 8 个数据位共 100 µs 低电平即为 break，随后的停止位即为 MAB。
+//!
+//! 通道值保存在一个 512 字节的全局 universe 中，通过 [set]、[set_range] 修改，
+//! HTTP 的 `POST /dmx` 和 MQTT 的 `cmd/dmx` 主题用 [set_list] 按 `<通道>=<值>&...` 修改；
+//! [dmx_task] 以 [FRAME_INTERVAL] 周期持续发送。RS485 收发器的 DE 引脚由 [Dmx] 一直保持高电平
+//! （只发送）。接线用命令行 `board port dmx <tx> <de>` 设置，见 [crate::board]。
+//!
+//! ```ignore
+//! let de = Output::new(peripherals.GPIO16, Level::High, OutputConfig::default());
+//! let dmx = Dmx::new(peripherals.UART2, peripherals.GPIO17, de)?;
+//! spawner.spawn(dmx::dmx_task(dmx))?;
+//! dmx::set(1, 255)?;
+//! ```
+
+use core::cell::RefCell;
+use critical_section::Mutex;
+use defmt::{info, warn};
+use embassy_time::{Duration, Ticker};
+use esp_hal::Async;
+use esp_hal::gpio::Output;
+use esp_hal::gpio::interconnect::PeripheralOutput;
+use esp_hal::uart::{self, Config as UartConfig, StopBits, UartTx};
+
+/// 通道数量
+pub const UNIVERSE_LEN: usize = 512;
+
+/// 帧周期，512 个通道的帧约需 23 ms，约 40 Hz 刷新
+pub const FRAME_INTERVAL: Duration = Duration::from_millis(25);
+
+/// 数据波特率
+const DMX_BAUDRATE: u32 = 250_000;
+
+/// 产生 break 时使用的波特率：9 个低电平位 100 µs，停止位 11 µs
+const BREAK_BAUDRATE: u32 = 90_000;
+
+/// 起始码（0x00 为调光数据）
+const START_CODE: u8 = 0x00;
+
+/// DMX 错误
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum DmxError {
+    /// UART 配置无效
+    Config,
+    /// 通道号不在 1..=512 范围内
+    InvalidChannel,
+    /// 发送出错
+    Transmit,
+}
+
+static UNIVERSE: Mutex<RefCell<[u8; UNIVERSE_LEN]>> =
+    Mutex::new(RefCell::new([0; UNIVERSE_LEN]));
+
+/// 设置一个通道
+///
+/// # 参数
+/// * `channel` - 通道号，从 1 开始
+/// * `value` - 通道值
+pub fn set(channel: u16, value: u8) -> Result<(), DmxError> {
+    set_range(channel, &[value])
+}
+
+/// 从 `start` 开始连续设置多个通道
+///
+/// # 参数
+/// * `start` - 起始通道号，从 1 开始
+/// * `values` - 通道值，不能超出第 512 通道
+pub fn set_range(start: u16, values: &[u8]) -> Result<(), DmxError> {
+    let offset = (start as usize).checked_sub(1).ok_or(DmxError::InvalidChannel)?;
+    if offset + values.len() > UNIVERSE_LEN {
+        return Err(DmxError::InvalidChannel);
+    }
+    critical_section::with(|cs| {
+        UNIVERSE.borrow_ref_mut(cs)[offset..offset + values.len()].copy_from_slice(values);
+    });
+    Ok(())
+}
+
+/// 按 `<通道>=<值>&...` 设置多个通道
+///
+/// # 返回
+/// 格式错误或通道无效时返回 None，此时已解析的通道仍会生效
+pub fn set_list(text: &str) -> Option<()> {
+    for pair in text.trim().split('&').filter(|pair| !pair.is_empty()) {
+        let (channel, value) = pair.split_once('=')?;
+        set(channel.parse().ok()?, value.parse().ok()?).ok()?;
+    }
+    Some(())
+}
+
+/// 读取一个通道，通道号无效时返回 None
+pub fn get(channel: u16) -> Option<u8> {
+    let offset = (channel as usize).checked_sub(1)?;
+    critical_section::with(|cs| UNIVERSE.borrow_ref(cs).get(offset).copied())
+}
+
+/// 所有通道归零
+pub fn blackout() {
+    critical_section::with(|cs| UNIVERSE.borrow_ref_mut(cs).fill(0));
+}
+
+/// DMX512 发送器
+pub struct Dmx {
+    tx: UartTx<'static, Async>,
+    /// 收发器的 DE 引脚，持有期间一直为高电平
+    _de: Output<'static>,
+    data_config: UartConfig,
+    break_config: UartConfig,
+}
+
+impl Dmx {
+    /// 创建发送器
+    ///
+    /// # 参数
+    /// * `uart` - UART 外设
+    /// * `tx` - 发送引脚，接 RS485 收发器的 DI
+    /// * `de` - 收发器 DE/RE 的输出引脚，创建时置为高电平（发送）
+    pub fn new(
+        uart: impl uart::Instance + 'static,
+        tx: impl PeripheralOutput<'static>,
+        mut de: Output<'static>,
+    ) -> Result<Self, DmxError> {
+        let data_config = UartConfig::default()
+            .with_baudrate(DMX_BAUDRATE)
+            .with_stop_bits(StopBits::_2);
+        let break_config = UartConfig::default().with_baudrate(BREAK_BAUDRATE);
+        let tx = UartTx::new(uart, data_config)
+            .map_err(|_| DmxError::Config)?
+            .with_tx(tx)
+            .into_async();
+        de.set_high();
+        Ok(Dmx {
+            tx,
+            _de: de,
+            data_config,
+            break_config,
+        })
+    }
+
+    /// 发送一帧
+    async fn send_frame(&mut self, universe: &[u8; UNIVERSE_LEN]) -> Result<(), DmxError> {
+        // break + MAB
+        self.tx.apply_config(&self.break_config).map_err(|_| DmxError::Config)?;
+        self.write_all(&[0x00]).await?;
+        self.tx.flush_async().await.map_err(|_| DmxError::Transmit)?;
+
+        self.tx.apply_config(&self.data_config).map_err(|_| DmxError::Config)?;
+        self.write_all(&[START_CODE]).await?;
+        self.write_all(universe).await?;
+        self.tx.flush_async().await.map_err(|_| DmxError::Transmit)
+    }
+
+    async fn write_all(&mut self, mut data: &[u8]) -> Result<(), DmxError> {
+        while !data.is_empty() {
+            let written = self
+                .tx
+                .write_async(data)
+                .await
+                .map_err(|_| DmxError::Transmit)?;
+            data = &data[written..];
+        }
+        Ok(())
+    }
+}
+
+/// DMX 发送任务
+///
+/// # 参数
+/// * `dmx` - 发送器
+#[embassy_executor::task]
+pub async fn dmx_task(mut dmx: Dmx) {
+    let mut ticker = Ticker::every(FRAME_INTERVAL);
+    let mut failing = false;
+
+    info!("DMX512 output started");
+    loop {
+        let universe = critical_section::with(|cs| *UNIVERSE.borrow_ref(cs));
+        match dmx.send_frame(&universe).await {
+            Ok(()) => failing = false,
+            Err(err) => {
+                // 每次出错都记录会刷屏，只记录第一次
+                if !failing {
+                    warn!("DMX frame failed: {}", err);
+                }
+                failing = true;
+            }
+        }
+        ticker.next().await;
+    }
+}
@@ -0,0 +1,107 @@
+//! 全局错误类型
+//!
+//! 总线、Wi-Fi、Flash 存储和传感器的底层错误统一包装为 [Error]，同时记录出错时
+//! 正在执行的操作，日志中可以直接看出是哪一步失败，例如：
+//!
+//! ```text
+//! BME280 measurement failed: I2c { op: "BME280 read data", source: Timeout }
+//! ```
+//!
+//! 底层错误通过 [Context::context] 加上操作说明：
+//!
+//! ```ignore
+//! i2c.write_read(address, &[register], &mut buf).context("BME280 read data")?;
+//! ```
+//!
+//! 只在本模块内部有意义的错误（例如协议解析错误）仍使用各模块自己的错误类型。
+
+use crate::sensor::SensorError;
+use crate::storage::StorageError;
+use esp_hal::i2c::master::Error as I2cError;
+use esp_hal::spi::Error as SpiError;
+use esp_radio::wifi::WifiError;
+
+/// 全局错误
+#[derive(Debug, Clone, Copy, defmt::Format)]
+pub enum Error {
+    /// 所需的总线或外设尚未初始化
+    NotInitialized { op: &'static str },
+    /// I2C 总线错误
+    I2c { op: &'static str, source: I2cError },
+    /// SPI 总线错误
+    Spi { op: &'static str, source: SpiError },
+    /// Wi-Fi 驱动错误
+    Wifi { op: &'static str, source: WifiError },
+    /// Flash 存储错误
+    Storage {
+        op: &'static str,
+        source: StorageError,
+    },
+    /// 传感器错误
+    Sensor {
+        op: &'static str,
+        source: SensorError,
+    },
+}
+
+impl Error {
+    /// 出错时正在执行的操作
+    pub fn op(&self) -> &'static str {
+        match *self {
+            Error::NotInitialized { op }
+            | Error::I2c { op, .. }
+            | Error::Spi { op, .. }
+            | Error::Wifi { op, .. }
+            | Error::Storage { op, .. }
+            | Error::Sensor { op, .. } => op,
+        }
+    }
+}
+
+/// 可以包装为 [Error] 的底层错误
+pub trait Source {
+    /// 加上操作说明，包装为 [Error]
+    fn with_op(self, op: &'static str) -> Error;
+}
+
+impl Source for I2cError {
+    fn with_op(self, op: &'static str) -> Error {
+        Error::I2c { op, source: self }
+    }
+}
+
+impl Source for SpiError {
+    fn with_op(self, op: &'static str) -> Error {
+        Error::Spi { op, source: self }
+    }
+}
+
+impl Source for WifiError {
+    fn with_op(self, op: &'static str) -> Error {
+        Error::Wifi { op, source: self }
+    }
+}
+
+impl Source for StorageError {
+    fn with_op(self, op: &'static str) -> Error {
+        Error::Storage { op, source: self }
+    }
+}
+
+impl Source for SensorError {
+    fn with_op(self, op: &'static str) -> Error {
+        Error::Sensor { op, source: self }
+    }
+}
+
+/// 给底层错误加上操作说明
+pub trait Context<T> {
+    /// 出错时包装为带有操作说明 `op` 的 [Error]
+    fn context(self, op: &'static str) -> Result<T, Error>;
+}
+
+impl<T, E: Source> Context<T> for Result<T, E> {
+    fn context(self, op: &'static str) -> Result<T, Error> {
+        self.map_err(|err| err.with_op(op))
+    }
+}
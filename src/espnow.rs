@@ -0,0 +1,255 @@
+//! ESP-NOW 收发
+//!
+//! ESP-NOW 是乐鑫的无连接数据帧协议，直接在 WiFi 射频上收发最多 [MAX_DATA_LEN] 字节的帧，
+//! 不需要接入点和 IP 协议栈，时延比 UDP 低。[espnow_task] 独占驱动的收发端：
+//!
+//! - [send] 把帧放入发送队列，目标是对端的 MAC 地址或广播地址 [BROADCAST]；
+//!   第一次发给某个对端时自动加入驱动的对端表
+//! - [receive] 等待下一个收到的帧
+//!
+//! 帧使用 WiFi 客户端接口当前的信道。两块板连接同一个接入点时信道自然相同；
+//! 客户端正在扫描或重连时信道会变化，期间的帧可能丢失。
+//!
+//! # 加密
+//!
+//! 配对过的对端（见 [crate::pairing]）以设置中保存的密钥作为本地主密钥（LMK）加入驱动的
+//! 对端表，发给它们的单播帧由驱动用 AES-CCM（CCMP）加密和认证。双方都要用 [pair] 保存
+//! 同一个密钥；主主密钥（PMK）使用驱动的默认值，两块板相同。
+//! 广播帧不能加密，需要保密的数据只能单播发给配对过的对端。驱动最多支持 6 个加密对端，
+//! 配对数量上限 [ESPNOW_PEERS_MAX] 在此之内。
+//!
+//! 目前只有链路测试（见 [crate::linktest]）和配对使用 ESP-NOW。收到的帧没有人读取时，
+//! 接收队列满后丢弃新帧。
+
+use crate::error::Error;
+#[cfg(feature = "ui")]
+use crate::pairing;
+#[cfg(feature = "ui")]
+use crate::settings::ESPNOW_KEY_LEN;
+use crate::settings::{self, ESPNOW_PEERS_MAX, EspNowPeer};
+use alloc::vec::Vec;
+use defmt::{info, warn};
+use embassy_futures::select::{Either3, select3};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::channel::Channel;
+use embassy_sync::signal::Signal;
+use esp_radio::esp_now::{EspNow, EspNowManager, EspNowWifiInterface, PeerInfo};
+
+/// 广播地址
+pub const BROADCAST: [u8; 6] = esp_radio::esp_now::BROADCAST_ADDRESS;
+
+/// 一帧数据的最大长度
+pub const MAX_DATA_LEN: usize = 250;
+
+/// 收发队列容量
+const QUEUE_LEN: usize = 8;
+
+/// 驱动的对端表中未配对对端的数量（不含广播地址），满时移除最早加入的对端
+const MAX_PEERS: usize = 8;
+
+/// 一帧数据
+pub struct Frame {
+    /// 对端的 MAC 地址：发送时为目标，接收时为来源
+    pub peer: [u8; 6],
+    pub data: Vec<u8>,
+}
+
+/// 待发送的帧
+static OUTGOING: Channel<CriticalSectionRawMutex, Frame, QUEUE_LEN> = Channel::new();
+
+/// 收到的帧
+static INCOMING: Channel<CriticalSectionRawMutex, Frame, QUEUE_LEN> = Channel::new();
+
+/// 配对的对端有变化，需要重新加入驱动的对端表
+static PEERS_CHANGED: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+
+/// 发送一帧
+///
+/// # 参数
+/// * `peer` - 对端的 MAC 地址或 [BROADCAST]
+/// * `data` - 数据，最长 [MAX_DATA_LEN] 字节
+///
+/// # 返回
+/// 数据过长或发送队列已满时丢弃并返回 false；返回 true 不代表对端已收到
+pub fn send(peer: [u8; 6], data: &[u8]) -> bool {
+    if data.len() > MAX_DATA_LEN {
+        return false;
+    }
+    let frame = Frame {
+        peer,
+        data: data.to_vec(),
+    };
+    OUTGOING.try_send(frame).is_ok()
+}
+
+/// 等待下一个收到的帧
+pub async fn receive() -> Frame {
+    INCOMING.receive().await
+}
+
+/// 保存与对端配对的密钥，之后发给它的单播帧加密
+///
+/// 已配对的对端更新密钥；配对数量已达 [ESPNOW_PEERS_MAX] 时替换最早配对的对端
+///
+/// # 参数
+/// * `mac` - 对端的 MAC 地址
+/// * `key` - 配对时协商的密钥
+#[cfg(feature = "ui")]
+pub fn pair(mac: [u8; 6], key: [u8; ESPNOW_KEY_LEN]) -> Result<(), Error> {
+    settings::update(|s| {
+        s.espnow_peers.retain(|peer| peer.mac != mac);
+        if s.espnow_peers.is_full() {
+            s.espnow_peers.remove(0);
+        }
+        s.espnow_peers.push(EspNowPeer { mac, key }).ok();
+    });
+    PEERS_CHANGED.signal(());
+    settings::save()
+}
+
+/// 删除配对，之后与对端的帧不再加密
+///
+/// # 参数
+/// * `mac` - 对端的 MAC 地址，None 时删除全部配对
+///
+/// # 返回
+/// 删除的配对数量
+pub fn forget(mac: Option<[u8; 6]>) -> Result<usize, Error> {
+    let mut removed = 0;
+    settings::update(|s| {
+        let before = s.espnow_peers.len();
+        s.espnow_peers
+            .retain(|peer| mac.is_some_and(|mac| peer.mac != mac));
+        removed = before - s.espnow_peers.len();
+    });
+    if removed == 0 {
+        return Ok(0);
+    }
+    PEERS_CHANGED.signal(());
+    settings::save().map(|()| removed)
+}
+
+/// 配对过的对端的 MAC 地址
+pub fn paired() -> heapless::Vec<[u8; 6], ESPNOW_PEERS_MAX> {
+    settings::get()
+        .espnow_peers
+        .iter()
+        .map(|peer| peer.mac)
+        .collect()
+}
+
+/// ESP-NOW 收发任务
+///
+/// # 参数
+/// * `esp_now` - WiFi 初始化时得到的 ESP-NOW 接口（见 [crate::wifi::init]）
+#[embassy_executor::task]
+pub async fn espnow_task(esp_now: EspNow<'static>) {
+    match esp_now.version() {
+        Ok(version) => info!("ESP-NOW version {}", version),
+        Err(err) => warn!("ESP-NOW unavailable: {}", defmt::Debug2Format(&err)),
+    }
+    let (manager, mut sender, mut receiver) = esp_now.split();
+    // 未配对、以明文加入对端表的对端，按加入的先后排列
+    let mut peers: heapless::Deque<[u8; 6], MAX_PEERS> = heapless::Deque::new();
+    // 配对的对端，以配对密钥加入对端表
+    let mut paired = heapless::Vec::new();
+    add_paired(&manager, &mut peers, &mut paired);
+
+    loop {
+        let event = select3(
+            receiver.receive_async(),
+            OUTGOING.receive(),
+            PEERS_CHANGED.wait(),
+        )
+        .await;
+        match event {
+            Either3::First(received) => {
+                let frame = Frame {
+                    peer: received.info.src_address,
+                    data: received.data().to_vec(),
+                };
+                // 配对报文交给配对任务
+                #[cfg(feature = "ui")]
+                if pairing::is_pairing_frame(&frame.data) {
+                    pairing::deliver(frame);
+                    continue;
+                }
+                // 没有人读取时丢弃，不阻塞接收
+                INCOMING.try_send(frame).ok();
+            }
+            Either3::Second(frame) => {
+                if frame.peer != BROADCAST && !manager.peer_exists(&frame.peer) {
+                    // 配对的对端加入失败时不退回明文
+                    if paired.contains(&frame.peer) {
+                        continue;
+                    }
+                    if peers.is_full()
+                        && let Some(oldest) = peers.pop_front()
+                    {
+                        manager.remove_peer(&oldest).ok();
+                    }
+                    let peer = PeerInfo {
+                        interface: EspNowWifiInterface::Sta,
+                        peer_address: frame.peer,
+                        lmk: None,
+                        channel: None,
+                        encrypt: false,
+                    };
+                    if let Err(err) = manager.add_peer(peer) {
+                        warn!("Failed to add ESP-NOW peer: {}", defmt::Debug2Format(&err));
+                        continue;
+                    }
+                    peers.push_back(frame.peer).ok();
+                }
+                // 发送失败（对端未确认）由上层按丢包处理，不逐个打印
+                sender.send_async(&frame.peer, &frame.data).await.ok();
+            }
+            Either3::Third(()) => {
+                for mac in &paired {
+                    manager.remove_peer(mac).ok();
+                }
+                paired.clear();
+                add_paired(&manager, &mut peers, &mut paired);
+            }
+        }
+    }
+}
+
+/// 把配对的对端以配对密钥加入驱动的对端表
+///
+/// # 参数
+/// * `peers` - 以明文加入的对端，其中配对过的先移除
+/// * `paired` - 配对的对端，包括加入失败的
+fn add_paired(
+    manager: &EspNowManager<'_>,
+    peers: &mut heapless::Deque<[u8; 6], MAX_PEERS>,
+    paired: &mut heapless::Vec<[u8; 6], ESPNOW_PEERS_MAX>,
+) {
+    for EspNowPeer { mac, key } in settings::get().espnow_peers {
+        if manager.peer_exists(&mac) {
+            manager.remove_peer(&mac).ok();
+        }
+        for _ in 0..peers.len() {
+            if let Some(other) = peers.pop_front().filter(|&other| other != mac) {
+                peers.push_back(other).ok();
+            }
+        }
+        paired.push(mac).ok();
+        let peer = PeerInfo {
+            interface: EspNowWifiInterface::Sta,
+            peer_address: mac,
+            lmk: Some(key),
+            channel: None,
+            encrypt: true,
+        };
+        if let Err(err) = manager.add_peer(peer) {
+            warn!(
+                "Failed to add paired ESP-NOW peer: {}",
+                defmt::Debug2Format(&err)
+            );
+        }
+    }
+    if !paired.is_empty() {
+        info!("ESP-NOW: {} paired peers", paired.len());
+    }
+}
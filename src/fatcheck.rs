@@ -0,0 +1,278 @@
+//! FAT 簇链检查
+//!
+//! embedded-sdmmc 不带文件系统检查。[check_file] 直接读 FAT 表，沿文件的簇链检查它与
+//! 目录项中的长度是否一致，用于未正常关机后检查数据记录文件（见 [crate::sdlog]）：
+//!
+//! - 簇链比长度需要的长：掉电时簇已分配，目录项中的长度还没有更新。多出的簇在之后追加时
+//!   继续使用，不需要修复
+//! - 簇链中途断开（空闲簇、坏簇、超出范围的簇号或提前结束）：在最后一个有效簇处写入
+//!   结束标记（所有 FAT 副本），目录项中的长度截短到簇链容纳的长度，断开之后的数据丢失
+//!
+//! 只检查一个文件，不扫描整个卷的交叉链接和丢失簇，这些仍需在电脑上用 chkdsk/fsck 处理。
+//! 与 [crate::sdcard] 相同，只支持 MBR 第一个分区上的 FAT16/FAT32，扇区大小 512 字节。
+
+use crate::sdcard::{self, Card, SdError};
+use embedded_sdmmc::fat::Bpb;
+use embedded_sdmmc::{Block, BlockDevice, BlockIdx};
+
+/// 块大小
+const BLOCK_LEN: u32 = 512;
+
+/// MBR 中第一个分区表项的偏移和其中起始扇区的偏移
+const PARTITION_ENTRY: usize = 446;
+const PARTITION_LBA: usize = 8;
+
+/// 引导扇区末尾的签名
+const BOOT_SIGNATURE: [u8; 2] = [0x55, 0xAA];
+
+/// 簇数少于此值的卷为 FAT16，否则为 FAT32
+const FAT32_MIN_CLUSTERS: u32 = 65525;
+
+/// 第一个数据簇的编号
+const FIRST_CLUSTER: u32 = 2;
+
+/// FAT32 表项中有效的位
+const FAT32_MASK: u32 = 0x0FFF_FFFF;
+
+/// 簇链结束标记（不小于此值的表项表示结束）
+const FAT16_EOC: u32 = 0xFFF8;
+const FAT32_EOC: u32 = 0x0FFF_FFF8;
+
+/// 目录项中起始簇号高 16 位、低 16 位和文件长度的偏移
+const ENTRY_CLUSTER_HIGH: usize = 20;
+const ENTRY_CLUSTER_LOW: usize = 26;
+const ENTRY_SIZE: usize = 28;
+
+/// 检查结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum ChainState {
+    /// 簇链与长度一致
+    Consistent,
+    /// 簇链比长度需要的长，多出的簇之后追加时继续使用
+    Overlong,
+    /// 簇链断开或比长度需要的短，已截短文件
+    Truncated {
+        /// 原来目录项中的长度
+        from: u32,
+        /// 截短后的长度
+        to: u32,
+    },
+}
+
+/// 卷的布局，从引导扇区中读出
+struct Geometry {
+    /// 第一个 FAT 副本的起始块
+    fat_start: u32,
+    /// 每个 FAT 副本的块数
+    fat_blocks: u32,
+    /// FAT 副本数
+    fats: u32,
+    /// 每簇字节数
+    cluster_len: u32,
+    /// 数据簇数
+    clusters: u32,
+    fat32: bool,
+}
+
+impl Geometry {
+    /// 每个 FAT 表项的字节数
+    fn entry_len(&self) -> u32 {
+        if self.fat32 { 4 } else { 2 }
+    }
+
+    /// 簇号是否指向一个数据簇；空闲、坏簇、保留值和结束标记都不是
+    fn is_data(&self, cluster: u32) -> bool {
+        (FIRST_CLUSTER..self.clusters + FIRST_CLUSTER).contains(&cluster)
+    }
+
+    /// 表项是否为簇链结束标记
+    fn is_end(&self, entry: u32) -> bool {
+        if self.fat32 {
+            entry >= FAT32_EOC
+        } else {
+            entry >= FAT16_EOC
+        }
+    }
+
+    /// 簇的表项所在的块（相对 FAT 副本起始）和块内偏移
+    fn entry_pos(&self, cluster: u32) -> (u32, usize) {
+        let offset = cluster * self.entry_len();
+        (offset / BLOCK_LEN, (offset % BLOCK_LEN) as usize)
+    }
+}
+
+/// 检查根目录中文件的簇链，簇链断开时截短文件
+///
+/// 执行期间独占卡槽（见 [sdcard::with_device]），调用方不能持有打开的文件或目录
+///
+/// # 参数
+/// * `name` - 根目录中的文件名
+///
+/// # 返回
+/// 检查结果；文件不存在时返回 `NotFound`
+pub async fn check_file(name: &str) -> Result<ChainState, SdError> {
+    let entry = sdcard::with_root_dir(|dir| dir.find_directory_entry(name)).await?;
+    sdcard::with_device(|card| {
+        let geometry = read_geometry(card)?;
+        let mut dir_block = read_block(card, entry.entry_block.0)?;
+        let offset = entry.entry_offset as usize;
+        let raw = &dir_block.contents[offset..offset + 32];
+        let mut first = u32::from(u16::from_le_bytes([
+            raw[ENTRY_CLUSTER_LOW],
+            raw[ENTRY_CLUSTER_LOW + 1],
+        ]));
+        if geometry.fat32 {
+            first |= u32::from(u16::from_le_bytes([
+                raw[ENTRY_CLUSTER_HIGH],
+                raw[ENTRY_CLUSTER_HIGH + 1],
+            ])) << 16;
+        }
+        let size = entry.size;
+        let expected = size.div_ceil(geometry.cluster_len);
+        if first == 0 && size == 0 {
+            return Ok(ChainState::Consistent);
+        }
+
+        // 沿簇链最多走到长度需要的簇数，簇链有环时也不会多走
+        let mut fat = FatReader::new(&geometry);
+        let mut length = 0;
+        let mut last = None;
+        let mut cluster = first;
+        let broken = loop {
+            if !geometry.is_data(cluster) {
+                break true;
+            }
+            length += 1;
+            last = Some(cluster);
+            let next = fat.entry(card, cluster)?;
+            if geometry.is_end(next) {
+                break false;
+            }
+            if length >= expected {
+                return Ok(ChainState::Overlong);
+            }
+            cluster = next;
+        };
+        if !broken && length == expected {
+            return Ok(ChainState::Consistent);
+        }
+        if !broken && length > expected {
+            // 只在长度为 0 而已分配一个簇时出现
+            return Ok(ChainState::Overlong);
+        }
+
+        if broken {
+            match last {
+                Some(last) => write_end(card, &geometry, last)?,
+                // 起始簇就无效，文件变为空文件
+                None => {
+                    let raw = &mut dir_block.contents[offset..offset + 32];
+                    raw[ENTRY_CLUSTER_HIGH..ENTRY_CLUSTER_HIGH + 2].fill(0);
+                    raw[ENTRY_CLUSTER_LOW..ENTRY_CLUSTER_LOW + 2].fill(0);
+                }
+            }
+        }
+        let truncated = size.min(length * geometry.cluster_len);
+        dir_block.contents[offset + ENTRY_SIZE..offset + ENTRY_SIZE + 4]
+            .copy_from_slice(&truncated.to_le_bytes());
+        write_block(card, entry.entry_block.0, &dir_block)?;
+        Ok(ChainState::Truncated {
+            from: size,
+            to: truncated,
+        })
+    })
+    .await
+}
+
+/// 从 MBR 和第一个分区的引导扇区读出卷的布局
+fn read_geometry(card: &Card) -> Result<Geometry, SdError> {
+    let mbr = read_block(card, 0)?;
+    if mbr.contents[510..] != BOOT_SIGNATURE {
+        return Err(SdError::FormatError("Invalid MBR signature"));
+    }
+    let lba = PARTITION_ENTRY + PARTITION_LBA;
+    let start = u32::from_le_bytes([
+        mbr.contents[lba],
+        mbr.contents[lba + 1],
+        mbr.contents[lba + 2],
+        mbr.contents[lba + 3],
+    ]);
+    let boot = read_block(card, start)?;
+    let bpb = Bpb::create_from_bytes(&boot.contents).map_err(SdError::FormatError)?;
+    if u32::from(bpb.bytes_per_block()) != BLOCK_LEN {
+        return Err(SdError::BadBlockSize(bpb.bytes_per_block()));
+    }
+    let clusters = bpb.total_clusters();
+    Ok(Geometry {
+        fat_start: start + u32::from(bpb.reserved_block_count()),
+        fat_blocks: bpb.fat_size(),
+        fats: u32::from(bpb.num_fats()),
+        cluster_len: u32::from(bpb.blocks_per_cluster()) * BLOCK_LEN,
+        clusters,
+        fat32: clusters >= FAT32_MIN_CLUSTERS,
+    })
+}
+
+/// 读 FAT 表项，缓存最近读过的一块
+struct FatReader<'a> {
+    geometry: &'a Geometry,
+    cached: Option<(u32, Block)>,
+}
+
+impl<'a> FatReader<'a> {
+    fn new(geometry: &'a Geometry) -> Self {
+        Self {
+            geometry,
+            cached: None,
+        }
+    }
+
+    /// 读第一个 FAT 副本中簇的表项
+    fn entry(&mut self, card: &Card, cluster: u32) -> Result<u32, SdError> {
+        let (block_idx, offset) = self.geometry.entry_pos(cluster);
+        let block_idx = self.geometry.fat_start + block_idx;
+        let block = match &mut self.cached {
+            Some((idx, block)) if *idx == block_idx => block,
+            cached => &mut cached.insert((block_idx, read_block(card, block_idx)?)).1,
+        };
+        let bytes = &block.contents[offset..];
+        Ok(if self.geometry.fat32 {
+            u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) & FAT32_MASK
+        } else {
+            u32::from(u16::from_le_bytes([bytes[0], bytes[1]]))
+        })
+    }
+}
+
+/// 在所有 FAT 副本中把簇的表项改为结束标记
+fn write_end(card: &Card, geometry: &Geometry, cluster: u32) -> Result<(), SdError> {
+    let (block_idx, offset) = geometry.entry_pos(cluster);
+    for copy in 0..geometry.fats {
+        let idx = geometry.fat_start + copy * geometry.fat_blocks + block_idx;
+        let mut block = read_block(card, idx)?;
+        let bytes = &mut block.contents[offset..];
+        if geometry.fat32 {
+            // 高 4 位保留，保持原值
+            let old = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+            let new = (old & !FAT32_MASK) | FAT32_MASK;
+            bytes[..4].copy_from_slice(&new.to_le_bytes());
+        } else {
+            bytes[..2].copy_from_slice(&0xFFFFu16.to_le_bytes());
+        }
+        write_block(card, idx, &block)?;
+    }
+    Ok(())
+}
+
+fn read_block(card: &Card, idx: u32) -> Result<Block, SdError> {
+    let mut blocks = [Block::new()];
+    card.read(&mut blocks, BlockIdx(idx), "fatcheck")
+        .map_err(SdError::DeviceError)?;
+    let [block] = blocks;
+    Ok(block)
+}
+
+fn write_block(card: &Card, idx: u32, block: &Block) -> Result<(), SdError> {
+    card.write(core::slice::from_ref(block), BlockIdx(idx))
+        .map_err(SdError::DeviceError)
+}
@@ -0,0 +1,150 @@
+//! 总线故障注入（仅测试用）
+//!
+//! 启用 `fault-injection` feature 时编译，在共享总线的访问层按
+//! [drivers::fault::Injector] 的设置注入故障，用来在板上验证 XL9555、传感器和 LCD
+//! 在总线出错后的恢复逻辑。命令行 `fault` 查看和设置：
+//!
+//! ```text
+//! fault                              查看设置和已注入的次数
+//! fault i2c nack|timeout <n>         每 n 次 I2C 访问注入一次不应答或超时
+//! fault lcd nack|timeout|corrupt <n> 每 n 次 LCD 的 SPI 传输注入一次故障
+//! fault off                          停止注入
+//! ```
+//!
+//! - I2C：一次 [crate::i2c::with_i2c] 调用算一次访问，故障在闭包执行前注入，
+//!   闭包不会执行。闭包直接使用 esp-hal 的总线，无法破坏读到的数据，
+//!   读数据损坏只能在主机上用 [drivers::fault::FaultyBus] 测试
+//! - LCD：[crate::st7789::LcdSpi] 的每次传输算一次，不应答和超时都上报为
+//!   `spi::Error::Unknown`
+//!
+//! TF 卡虽与 LCD 共用 SPI 总线，但不经过 [crate::st7789::LcdSpi]，不受影响。
+
+use core::cell::RefCell;
+use critical_section::Mutex;
+use defmt::{info, warn};
+use drivers::fault::Injector;
+use esp_hal::i2c::master::{AcknowledgeCheckFailedReason, Error as I2cError};
+#[cfg(feature = "ui")]
+use esp_hal::spi::Error as SpiError;
+
+pub use drivers::fault::Fault;
+
+/// 注入故障的总线
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Bus {
+    /// 板载 I2C 总线
+    I2c,
+    /// LCD 所在的 SPI 设备
+    Lcd,
+}
+
+impl Bus {
+    /// 所有总线
+    pub const ALL: [Bus; 2] = [Bus::I2c, Bus::Lcd];
+
+    /// 命令行中使用的名称
+    pub fn name(self) -> &'static str {
+        match self {
+            Bus::I2c => "i2c",
+            Bus::Lcd => "lcd",
+        }
+    }
+}
+
+/// 命令行中使用的故障名称
+pub fn fault_name(fault: Fault) -> &'static str {
+    match fault {
+        Fault::Nack => "nack",
+        Fault::Timeout => "timeout",
+        Fault::CorruptRead => "corrupt",
+    }
+}
+
+static I2C: Mutex<RefCell<Injector>> = Mutex::new(RefCell::new(Injector::new()));
+static LCD: Mutex<RefCell<Injector>> = Mutex::new(RefCell::new(Injector::new()));
+
+fn injector(bus: Bus) -> &'static Mutex<RefCell<Injector>> {
+    match bus {
+        Bus::I2c => &I2C,
+        Bus::Lcd => &LCD,
+    }
+}
+
+/// 设置故障
+///
+/// # 参数
+/// * `bus` - 总线
+/// * `fault` - 故障类型，I2C 不支持 [Fault::CorruptRead]
+/// * `every` - 每隔多少次访问注入一次，0 表示不注入
+///
+/// # 返回
+/// 总线不支持该故障类型时返回 false
+pub fn set(bus: Bus, fault: Fault, every: u32) -> bool {
+    if bus == Bus::I2c && fault == Fault::CorruptRead {
+        return false;
+    }
+    critical_section::with(|cs| injector(bus).borrow_ref_mut(cs).set(fault, every));
+    info!(
+        "Fault injection on {}: {} every {}",
+        bus.name(),
+        fault_name(fault),
+        every
+    );
+    true
+}
+
+/// 停止所有总线上的注入
+pub fn clear() {
+    critical_section::with(|cs| {
+        for bus in Bus::ALL {
+            injector(bus).borrow_ref_mut(cs).clear();
+        }
+    });
+}
+
+/// 当前设置
+///
+/// # 返回
+/// 故障类型和间隔（未注入时为 None），以及已注入的次数
+pub fn status(bus: Bus) -> (Option<(Fault, u32)>, u32) {
+    critical_section::with(|cs| {
+        let injector = injector(bus).borrow_ref(cs);
+        (injector.plan(), injector.injected())
+    })
+}
+
+/// 记录总线上的一次访问
+fn on_transfer(bus: Bus) -> Option<Fault> {
+    let fault = critical_section::with(|cs| injector(bus).borrow_ref_mut(cs).on_transfer());
+    if let Some(fault) = fault {
+        warn!("Injected {} on {}", fault_name(fault), bus.name());
+    }
+    fault
+}
+
+/// 记录一次 I2C 访问，由 [crate::i2c::with_i2c] 在访问总线前调用
+///
+/// # 返回
+/// 需要注入故障时返回对应的 I2C 错误
+pub fn inject_i2c() -> Result<(), I2cError> {
+    match on_transfer(Bus::I2c) {
+        Some(Fault::Nack) => Err(I2cError::AcknowledgeCheckFailed(
+            AcknowledgeCheckFailedReason::Unknown,
+        )),
+        Some(_) => Err(I2cError::Timeout),
+        None => Ok(()),
+    }
+}
+
+/// 记录一次 LCD 的 SPI 传输，由 [crate::st7789::LcdSpi] 在传输前调用
+///
+/// # 返回
+/// 需要破坏读到的数据时返回 true；注入不应答或超时时返回 SPI 错误
+#[cfg(feature = "ui")]
+pub fn inject_lcd() -> Result<bool, SpiError> {
+    match on_transfer(Bus::Lcd) {
+        Some(Fault::CorruptRead) => Ok(true),
+        Some(_) => Err(SpiError::Unknown),
+        None => Ok(false),
+    }
+}
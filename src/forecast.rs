@@ -0,0 +1,304 @@
+//! 网络天气预报
+//!
+//! [forecast_task] 每 [FETCH_INTERVAL] 通过 [crate::http_client] 从设置中选择的服务商
+//! 下载逐日预报，用 [proto::json] 取出每天的天气类型和最高/最低气温，
+//! 结果通过 [latest] 读取（气象站屏幕见 [crate::weather]）。
+//!
+//! 位置和服务商在命令行中设置（`forecast location`、`forecast provider`）：
+//!
+//! - Open-Meteo：不需要 API Key；设置了 Key 时改用商业版接口
+//! - OpenWeatherMap：One Call 3.0 接口，需要 API Key
+//!
+//! 两者都通过明文 HTTP 访问（[crate::http_client] 不支持 TLS），设置了 API Key 时 Key 以明文
+//! 出现在请求的 URL 中，同一网络中的其他设备可以截获。
+
+use crate::http_client;
+use crate::net::SocketOptions;
+use crate::settings;
+use crate::wallclock::DateTime;
+use alloc::vec;
+use core::cell::RefCell;
+use core::fmt::Write;
+use critical_section::Mutex;
+use defmt::{info, warn};
+use embassy_net::Stack;
+use embassy_time::{Duration, Instant, Timer};
+use heapless::{String, Vec};
+use proto::json::{self, Value};
+
+/// 预报天数
+pub const FORECAST_DAYS: usize = 4;
+
+/// 正常的更新周期
+const FETCH_INTERVAL: Duration = Duration::from_secs(30 * 60);
+
+/// 下载或解析失败、未设置位置时的重试间隔
+const RETRY_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// 响应缓冲区大小，OpenWeatherMap 的 8 天逐日预报约 6 KB
+const RESPONSE_BUF_LEN: usize = 8 * 1024;
+
+/// 连接参数：接收窗口加大到 4KB，几 KB 的响应只需一两个往返
+const SOCKET: SocketOptions = SocketOptions {
+    rx_buffer: 4 * 1024,
+    ..http_client::OPTIONS
+};
+
+/// 天气预报服务商
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Provider {
+    OpenMeteo,
+    OpenWeatherMap,
+}
+
+impl Provider {
+    /// 所有服务商，下标与设置中保存的编码一致
+    pub const ALL: [Provider; 2] = [Provider::OpenMeteo, Provider::OpenWeatherMap];
+
+    /// 设置中保存的编码
+    pub const fn to_u8(self) -> u8 {
+        self as u8
+    }
+
+    /// 从设置中的编码解析，未知编码视为 Open-Meteo
+    pub const fn from_u8(value: u8) -> Provider {
+        match value {
+            1 => Provider::OpenWeatherMap,
+            _ => Provider::OpenMeteo,
+        }
+    }
+
+    /// 服务商名称，用于命令行参数
+    pub const fn name(self) -> &'static str {
+        match self {
+            Provider::OpenMeteo => "open-meteo",
+            Provider::OpenWeatherMap => "openweathermap",
+        }
+    }
+}
+
+/// 天气类型，决定屏幕上的图标
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Condition {
+    Clear,
+    Cloudy,
+    Fog,
+    Rain,
+    Snow,
+    Storm,
+    Unknown,
+}
+
+impl Condition {
+    /// 从 WMO 天气代码转换（Open-Meteo）
+    fn from_wmo(code: u32) -> Condition {
+        match code {
+            0 | 1 => Condition::Clear,
+            2 | 3 => Condition::Cloudy,
+            45 | 48 => Condition::Fog,
+            51..=67 | 80..=82 => Condition::Rain,
+            71..=77 | 85 | 86 => Condition::Snow,
+            95..=99 => Condition::Storm,
+            _ => Condition::Unknown,
+        }
+    }
+
+    /// 从天气状况 ID 转换（OpenWeatherMap）
+    fn from_owm(id: u32) -> Condition {
+        match id {
+            200..=299 => Condition::Storm,
+            300..=599 => Condition::Rain,
+            600..=699 => Condition::Snow,
+            700..=799 => Condition::Fog,
+            800 => Condition::Clear,
+            801..=899 => Condition::Cloudy,
+            _ => Condition::Unknown,
+        }
+    }
+}
+
+/// 一天的预报
+#[derive(Debug, Clone, Copy, PartialEq, defmt::Format)]
+pub struct Day {
+    pub month: u8,
+    pub day: u8,
+    pub condition: Condition,
+    /// 最低气温（°C）
+    pub min: f32,
+    /// 最高气温（°C）
+    pub max: f32,
+}
+
+/// 最近一次成功下载的预报
+#[derive(Debug, Clone, PartialEq)]
+pub struct Forecast {
+    pub days: Vec<Day, FORECAST_DAYS>,
+    /// 下载时间
+    pub updated: Instant,
+}
+
+/// 预报错误
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum ForecastError {
+    /// 未设置位置
+    NoLocation,
+    /// 服务商需要 API Key 但未设置
+    NoApiKey,
+    /// 位置和 API Key 过长，请求路径超出缓冲区
+    PathTooLong,
+    /// 下载失败
+    Http(http_client::HttpClientError),
+    /// 响应不是有效的 JSON
+    Json(json::JsonError),
+    /// 响应中缺少需要的字段
+    MissingField,
+}
+
+static LATEST: Mutex<RefCell<Option<Forecast>>> = Mutex::new(RefCell::new(None));
+
+/// 最近一次成功下载的预报
+pub fn latest() -> Option<Forecast> {
+    critical_section::with(|cs| LATEST.borrow_ref(cs).clone())
+}
+
+/// 检查位置格式 `<纬度>,<经度>`（十进制度数）
+pub fn valid_location(location: &str) -> bool {
+    parse_location(location).is_some()
+}
+
+fn parse_location(location: &str) -> Option<(&str, &str)> {
+    let (lat, lon) = location.split_once(',')?;
+    let (lat, lon) = (lat.trim(), lon.trim());
+    let in_range = |text: &str, limit: f32| text.parse::<f32>().is_ok_and(|v| v.abs() <= limit);
+    (in_range(lat, 90.0) && in_range(lon, 180.0)).then_some((lat, lon))
+}
+
+/// 按当前设置下载一次预报
+///
+/// # 参数
+/// * `stack` - 网络协议栈
+pub async fn fetch(stack: Stack<'_>) -> Result<Forecast, ForecastError> {
+    let settings = settings::get();
+    let (lat, lon) =
+        parse_location(&settings.forecast_location).ok_or(ForecastError::NoLocation)?;
+    let provider = Provider::from_u8(settings.forecast_provider);
+    let key = settings.forecast_key.as_str();
+
+    let mut path: String<256> = String::new();
+    let host = match provider {
+        Provider::OpenMeteo => {
+            write!(
+                path,
+                "/v1/forecast?latitude={}&longitude={}&timezone=UTC&forecast_days={}\
+                 &daily=weather_code,temperature_2m_max,temperature_2m_min",
+                lat, lon, FORECAST_DAYS
+            )
+            .map_err(|_| ForecastError::PathTooLong)?;
+            if key.is_empty() {
+                "api.open-meteo.com"
+            } else {
+                write!(path, "&apikey={}", key).map_err(|_| ForecastError::PathTooLong)?;
+                "customer-api.open-meteo.com"
+            }
+        }
+        Provider::OpenWeatherMap => {
+            if key.is_empty() {
+                return Err(ForecastError::NoApiKey);
+            }
+            write!(
+                path,
+                "/data/3.0/onecall?lat={}&lon={}&units=metric\
+                 &exclude=current,minutely,hourly,alerts&appid={}",
+                lat, lon, key
+            )
+            .map_err(|_| ForecastError::PathTooLong)?;
+            "api.openweathermap.org"
+        }
+    };
+
+    let mut buf = vec![0u8; RESPONSE_BUF_LEN];
+    let body = http_client::get(stack, &SOCKET, host, 80, &path, &mut buf)
+        .await
+        .map_err(ForecastError::Http)?;
+    let text = core::str::from_utf8(body).map_err(|_| ForecastError::MissingField)?;
+    let doc = json::parse(text).map_err(ForecastError::Json)?;
+    let days = match provider {
+        Provider::OpenMeteo => parse_open_meteo(doc),
+        Provider::OpenWeatherMap => parse_owm(doc),
+    }
+    .ok_or(ForecastError::MissingField)?;
+
+    Ok(Forecast {
+        days,
+        updated: Instant::now(),
+    })
+}
+
+/// 解析 Open-Meteo 响应，`daily` 中每个字段是一个按天排列的数组
+fn parse_open_meteo(doc: Value<'_>) -> Option<Vec<Day, FORECAST_DAYS>> {
+    let daily = doc.get("daily")?;
+    let dates = daily.get("time")?;
+    let codes = daily.get("weather_code")?;
+    let maxima = daily.get("temperature_2m_max")?;
+    let minima = daily.get("temperature_2m_min")?;
+
+    let mut days = Vec::new();
+    for (i, date) in dates.items().take(FORECAST_DAYS).enumerate() {
+        // 日期格式为 YYYY-MM-DD
+        let date = date.as_str()?;
+        let month = date.get(5..7)?.parse().ok()?;
+        let day = date.get(8..10)?.parse().ok()?;
+        let code = codes.index(i)?.as_f64()? as u32;
+        days.push(Day {
+            month,
+            day,
+            condition: Condition::from_wmo(code),
+            min: minima.index(i)?.as_f64()? as f32,
+            max: maxima.index(i)?.as_f64()? as f32,
+        })
+        .ok();
+    }
+    (!days.is_empty()).then_some(days)
+}
+
+/// 解析 OpenWeatherMap 响应，`daily` 是每天一个对象的数组
+fn parse_owm(doc: Value<'_>) -> Option<Vec<Day, FORECAST_DAYS>> {
+    let mut days = Vec::new();
+    for item in doc.get("daily")?.items().take(FORECAST_DAYS) {
+        let date = DateTime::from_unix(item.get("dt")?.as_f64()? as u64);
+        let id = item.get("weather")?.index(0)?.get("id")?.as_f64()? as u32;
+        days.push(Day {
+            month: date.month,
+            day: date.day,
+            condition: Condition::from_owm(id),
+            min: item.path(&["temp", "min"])?.as_f64()? as f32,
+            max: item.path(&["temp", "max"])?.as_f64()? as f32,
+        })
+        .ok();
+    }
+    (!days.is_empty()).then_some(days)
+}
+
+/// 周期下载任务
+///
+/// # 参数
+/// * `stack` - 网络协议栈
+#[embassy_executor::task]
+pub async fn forecast_task(stack: Stack<'static>) {
+    loop {
+        stack.wait_config_up().await;
+        let delay = match fetch(stack).await {
+            Ok(forecast) => {
+                info!("Forecast updated: {} days", forecast.days.len());
+                critical_section::with(|cs| *LATEST.borrow_ref_mut(cs) = Some(forecast));
+                FETCH_INTERVAL
+            }
+            Err(ForecastError::NoLocation) => RETRY_INTERVAL,
+            Err(err) => {
+                warn!("Forecast fetch failed: {}", err);
+                RETRY_INTERVAL
+            }
+        };
+        Timer::after(delay).await;
+    }
+}
@@ -0,0 +1,40 @@
+//! 整帧帧缓冲区
+//!
+//! 320x240 的 RGB565 帧缓冲区需要 150KB，内部堆（64KB）放不下。board 阶段（见 [crate::app]）
+//! 把模组的 PSRAM 加入堆，内部堆放不下的大块分配由 PSRAM 满足，[alloc] 分配的帧缓冲区
+//! 因此总在 PSRAM 中。模组没有 PSRAM 时分配失败，调用方继续直接在 LCD 上绘制。
+//!
+//! 在帧缓冲区上绘制只是内存写入，逐像素绘制也不会对每个像素单独设置窗口、发起 SPI 传输；
+//! 改变过的区域由 [ui::framebuffer] 记录，画完后用 [crate::lcd::Lcd::flush_framebuffer]
+//! 只发送这些区域。
+//! PSRAM 经 cache 访问，比内部 SRAM 慢，但远快于 SPI。发送时像素由共享总线复制到内部 SRAM 的
+//! DMA 缓冲区（见 [crate::spi]），不要求帧缓冲区可以直接用于 DMA。
+//!
+//! 目前只有显示性能测试（[crate::bench]）使用帧缓冲区，状态屏幕仍直接绘制到 LCD。
+
+use crate::st7789;
+use alloc::vec::Vec;
+use defmt::{info, warn};
+use embedded_graphics::prelude::*;
+use ui::framebuffer::Framebuffer;
+
+/// 帧缓冲区大小（字节）
+pub const LEN: usize = st7789::WIDTH as usize * st7789::HEIGHT as usize * 2;
+
+/// 分配一个整屏大小的帧缓冲区，初始为黑色
+///
+/// 缓冲区常驻内存，每次调用都会分配新的缓冲区，应在任务开始时调用一次
+///
+/// # 返回
+/// 堆中没有 [LEN] 字节的连续空间（通常是模组没有 PSRAM）时返回 None
+pub fn alloc() -> Option<Framebuffer<'static>> {
+    let mut pixels = Vec::new();
+    if pixels.try_reserve_exact(LEN).is_err() {
+        warn!("No memory for a {} byte framebuffer, is PSRAM fitted?", LEN);
+        return None;
+    }
+    pixels.resize(LEN, 0);
+    info!("Framebuffer allocated at {:#x}", pixels.as_ptr() as usize);
+    let size = Size::new(st7789::WIDTH as u32, st7789::HEIGHT as u32);
+    Framebuffer::new(pixels.leak(), size)
+}
@@ -0,0 +1,156 @@
+//! GPS 接收机
+//!
+//! 从串口（见 [crate::serial]）逐行读取 NMEA 0183 语句，用 [proto::nmea] 解析：
+//!
+//! - GGA：定位质量、卫星数、经纬度和海拔。最新定位通过 [fix] 读取，
+//!   同时以 `gps.*` 为名登记到 [crate::sensor]
+//! - RMC：UTC 日期和时间。定位有效时用于校准系统时间（[TimeSource::Gps]），
+//!   NTP 可用时 GPS 只作为后备，见 [crate::wallclock]
+//!
+//! 接收机的接线和波特率用命令行 `board port gps <tx> <rx> [<波特率>]` 设置（见 [crate::board]），
+//! 常见接收机默认 9600 波特率：
+//!
+//! ```ignore
+//! let config = SerialConfig { baudrate: 9600, ..SerialConfig::DEFAULT };
+//! let serial = Serial::new("gps", peripherals.UART1, tx, rx, config)?;
+//! spawner.spawn(gps::gps_task(serial))?;
+//! ```
+
+use crate::sensor;
+use crate::serial::Serial;
+use crate::wallclock::{self, DateTime, TimeSource};
+use core::cell::RefCell;
+use critical_section::Mutex;
+use defmt::{info, warn};
+use embassy_time::{Duration, Instant};
+use proto::nmea::{self, Gga, Rmc, Sentence};
+
+/// NMEA 语句最大长度（规范为 82 字节）
+const SENTENCE_LEN: usize = 128;
+
+/// 校准系统时间的最小间隔
+const TIME_SYNC_INTERVAL: Duration = Duration::from_secs(60);
+
+/// 定位结果
+#[derive(Debug, Clone, Copy, PartialEq, defmt::Format)]
+pub struct Fix {
+    /// GGA 定位质量：0 无效，1 GPS，2 差分，4/5 RTK，6 推算
+    pub quality: u8,
+    /// 参与定位的卫星数
+    pub satellites: u8,
+    /// 纬度（度，北纬为正）
+    pub latitude: f64,
+    /// 经度（度，东经为正）
+    pub longitude: f64,
+    /// 海拔（米）
+    pub altitude: f32,
+    /// 收到该定位的时间
+    pub updated: Instant,
+}
+
+impl Fix {
+    /// 定位是否有效
+    pub fn is_valid(&self) -> bool {
+        self.quality != 0
+    }
+}
+
+static FIX: Mutex<RefCell<Option<Fix>>> = Mutex::new(RefCell::new(None));
+
+/// 最新定位，尚未收到 GGA 语句时返回 None
+pub fn fix() -> Option<Fix> {
+    critical_section::with(|cs| *FIX.borrow_ref(cs))
+}
+
+/// GPS 任务
+///
+/// # 参数
+/// * `serial` - 连接接收机的串口
+#[embassy_executor::task]
+pub async fn gps_task(mut serial: Serial) {
+    let mut line = [0u8; SENTENCE_LEN];
+    let mut last_sync: Option<Instant> = None;
+    let mut had_fix = false;
+
+    loop {
+        let len = match serial.read_line(&mut line, b'\n').await {
+            Ok(len) => len,
+            Err(err) => {
+                warn!("GPS read failed: {}", err);
+                continue;
+            }
+        };
+        let Some(sentence) = core::str::from_utf8(&line[..len])
+            .ok()
+            .and_then(nmea::parse)
+        else {
+            continue;
+        };
+
+        match sentence {
+            Sentence::Gga(gga) => {
+                let fix = update_fix(&gga);
+                if fix.is_valid() != had_fix {
+                    had_fix = fix.is_valid();
+                    if had_fix {
+                        info!("GPS fix acquired: {} satellites", fix.satellites);
+                    } else {
+                        warn!("GPS fix lost");
+                    }
+                }
+            }
+            Sentence::Rmc(rmc) => {
+                let due = last_sync.is_none_or(|at| at.elapsed() >= TIME_SYNC_INTERVAL);
+                if rmc.valid
+                    && due
+                    && let Some(secs) = datetime(&rmc).and_then(|dt| dt.to_unix())
+                {
+                    wallclock::set(secs * 1_000_000 + rmc.millis as u64 * 1000, TimeSource::Gps);
+                    last_sync = Some(Instant::now());
+                }
+            }
+        }
+    }
+}
+
+/// 用 GGA 语句更新定位，并登记到传感器表
+fn update_fix(gga: &Gga) -> Fix {
+    let previous = fix();
+    let mut fix = Fix {
+        quality: gga.quality,
+        satellites: gga.satellites,
+        latitude: previous.map_or(0.0, |f| f.latitude),
+        longitude: previous.map_or(0.0, |f| f.longitude),
+        altitude: previous.map_or(0.0, |f| f.altitude),
+        updated: Instant::now(),
+    };
+
+    sensor::publish("gps.satellites", gga.satellites as f64, "");
+    if gga.quality != 0
+        && let (Some(latitude), Some(longitude)) = (gga.latitude, gga.longitude)
+    {
+        fix.latitude = latitude;
+        fix.longitude = longitude;
+        fix.altitude = gga.altitude.unwrap_or(fix.altitude);
+        sensor::publish("gps.lat", latitude, "deg");
+        sensor::publish("gps.lon", longitude, "deg");
+        sensor::publish("gps.alt", fix.altitude as f64, "m");
+    }
+
+    critical_section::with(|cs| *FIX.borrow_ref_mut(cs) = Some(fix));
+    fix
+}
+
+/// RMC 语句中的 UTC 日期时间，日期字段为空时返回 None
+fn datetime(rmc: &Rmc) -> Option<DateTime> {
+    let (year, month, day) = rmc.date?;
+    let (hour, minute, second) = rmc.time;
+    Some(DateTime {
+        year,
+        month,
+        day,
+        hour,
+        minute,
+        second,
+    })
+}
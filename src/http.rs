@@ -0,0 +1,490 @@
+//! HTTP 服务
+//!
+//! 在 80 端口提供一个最小的 HTTP/1.0 服务，每次处理一个连接，
+//! 响应后即关闭连接。路由见 [route]：
+//!
+//! - `GET /logs/ring`：下载日志环形缓冲区（defmt 帧，见 [crate::logbuf]）
+//! - `GET /crash`：查看上次的崩溃记录
+//! - `DELETE /crash`：清除崩溃记录
+//! - `GET /stats/jitter`：查看周期任务调度延迟（见 [crate::jitter]）
+//! - `GET /sensors`：查看传感器读数（见 [crate::sensor]）
+//! - `GET /api/sensors`：传感器读数，JSON 数组（见 [proto::json]）
+//! - `GET /api/settings`：当前设置，JSON 对象，不含密码
+//! - `GET /api/device`：设备标识，JSON 对象（见 [crate::device]）
+//! - `GET /api/status`：各子系统的状态汇总，JSON 对象（见 [crate::system::status]）
+//! - `GET /metrics`：按连接统计的网络流量，Prometheus 文本格式（见 [crate::netstats]）
+//! - `POST /dmx`：设置 DMX512 通道，请求体为 `<通道>=<值>&...`（见 [crate::dmx]）
+//! - `GET /api/thermostat`：恒温控制器状态，JSON 对象（见 [crate::thermostat]）
+//! - `POST /thermostat`：修改恒温设定值并保存，请求体为 `setpoint=<°C>`
+//! - `GET /api/relays`：各路继电器输出的状态，JSON 数组（见 [crate::relay]）
+//! - `POST /relay`：开关继电器输出，请求体为 `<n>=on|off|<分钟>`
+//! - `GET /rules`：查看自动化规则，每行一条（见 [crate::rules]）
+//! - `PUT /rules`：替换全部自动化规则并保存，请求体为规则原文
+//! - `DELETE /rules`：清除全部自动化规则
+//!
+//! `/api` 下的接口在请求头带有 `Accept: application/cbor` 时改为返回 CBOR（见 [proto::cbor]）。
+//!
+//! 设置了访问令牌时，`GET` 以外的请求需要认证，否则返回 401（见 [crate::access]）。
+//!
+//! 每个客户端每秒最多 [RATE] 个请求，超出时返回 429（见 [crate::ratelimit]）；
+//! 请求需在 [REQUEST_TIMEOUT] 内发送完整，防止慢速客户端长时间占用唯一的连接。
+
+use crate::net::{SocketOptions, TcpBuffers};
+use crate::netstats::{self, Link};
+use crate::ratelimit::RateLimiter;
+use crate::relay::{self, Switch};
+use crate::rules::{self, RulesError};
+use crate::{access, crash, device, dmx, jitter, logbuf, sensor, settings, system, thermostat};
+use alloc::string::String;
+use core::fmt::Write as _;
+use defmt::{info, warn};
+use embassy_net::tcp::{Error as TcpError, TcpSocket};
+use embassy_net::Stack;
+use embassy_time::{Duration, Instant, with_timeout};
+use embedded_io_async::Write;
+use proto::cbor;
+use proto::json::{Array, ToJson};
+
+/// 监听端口
+const PORT: u16 = 80;
+
+/// 每个客户端每秒允许的请求数
+const RATE: u32 = 5;
+
+/// 每个客户端短时间内最多允许的连续请求数，打开网页时会同时请求多个资源
+const BURST: u32 = 20;
+
+/// 限速时记录的客户端数量
+const RATE_CLIENTS: usize = 8;
+
+/// 从建立连接到收完请求的时间上限
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// 请求缓冲区大小，包括请求头和请求体
+const RX_BUF_LEN: usize = 1024;
+
+/// 连接参数：发送缓冲区比接收的大，下载日志等大响应时吞吐量更高
+const SOCKET: SocketOptions = SocketOptions {
+    timeout: Some(Duration::from_secs(10)),
+    rx_buffer: RX_BUF_LEN,
+    tx_buffer: 2048,
+    ..SocketOptions::DEFAULT
+};
+
+/// 响应状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Status {
+    Ok,
+    NoContent,
+    BadRequest,
+    Unauthorized,
+    NotFound,
+    RequestTimeout,
+    TooManyRequests,
+    InternalError,
+}
+
+impl Status {
+    fn line(self) -> &'static str {
+        match self {
+            Status::Ok => "200 OK",
+            Status::NoContent => "204 No Content",
+            Status::BadRequest => "400 Bad Request",
+            Status::Unauthorized => "401 Unauthorized",
+            Status::NotFound => "404 Not Found",
+            Status::RequestTimeout => "408 Request Timeout",
+            Status::TooManyRequests => "429 Too Many Requests",
+            Status::InternalError => "500 Internal Server Error",
+        }
+    }
+
+    /// 状态需要的额外响应头，每行以 CRLF 结尾
+    fn headers(self) -> &'static str {
+        match self {
+            // 浏览器据此弹出登录框
+            Status::Unauthorized => "WWW-Authenticate: Basic realm=\"esp-app-4\"\r\n",
+            Status::TooManyRequests => "Retry-After: 1\r\n",
+            _ => "",
+        }
+    }
+}
+
+/// 解析后的请求
+pub struct Request<'a> {
+    pub method: &'a str,
+    pub path: &'a str,
+    /// 请求体（按 Content-Length 读取）
+    pub body: &'a [u8],
+    /// 请求头 Accept 中有 `application/cbor`
+    pub cbor: bool,
+    /// 请求头 Authorization 的值
+    pub authorization: Option<&'a str>,
+}
+
+/// HTTP 服务任务
+#[embassy_executor::task]
+pub async fn server(stack: Stack<'static>) {
+    let mut buffers = TcpBuffers::new(SOCKET);
+    let mut request_buf = [0u8; RX_BUF_LEN];
+    let mut limiter = RateLimiter::<RATE_CLIENTS>::new(RATE, BURST);
+
+    stack.wait_config_up().await;
+    info!("HTTP server listening on port {}", PORT);
+
+    loop {
+        let mut socket = buffers.socket(stack);
+
+        if let Err(err) = socket.accept(PORT).await {
+            warn!("HTTP accept failed: {}", err);
+            continue;
+        }
+        netstats::opened(Link::HttpServer);
+
+        let result = match socket.remote_endpoint() {
+            Some(peer) if !limiter.allow(peer.addr) => {
+                warn!("HTTP client {} over rate limit", peer.addr);
+                let body = b"too many requests\n";
+                respond(&mut socket, Status::TooManyRequests, "text/plain", body).await
+            }
+            _ => handle(&mut socket, &mut request_buf).await,
+        };
+        if let Err(err) = result {
+            warn!("HTTP connection error: {}", err);
+        }
+        socket.close();
+        socket.flush().await.ok();
+    }
+}
+
+/// 读取并处理一个请求
+async fn handle(socket: &mut TcpSocket<'_>, buf: &mut [u8]) -> Result<(), TcpError> {
+    let Ok(read) = with_timeout(REQUEST_TIMEOUT, read_request(socket, buf)).await else {
+        warn!("HTTP request not complete in time");
+        let body = b"request timeout\n";
+        return respond(socket, Status::RequestTimeout, "text/plain", body).await;
+    };
+    let Some(len) = read? else {
+        return respond(socket, Status::BadRequest, "text/plain", b"bad request\n").await;
+    };
+    let Some(request) = parse(&buf[..len]) else {
+        return respond(socket, Status::BadRequest, "text/plain", b"bad request\n").await;
+    };
+    info!("HTTP {} {}", request.method, request.path);
+    route(socket, &request).await
+}
+
+/// 读取请求头，以及 Content-Length 指定的请求体
+///
+/// # 返回
+/// 读取的总长度，请求不完整或超出缓冲区时返回 None
+async fn read_request(
+    socket: &mut TcpSocket<'_>,
+    buf: &mut [u8],
+) -> Result<Option<usize>, TcpError> {
+    let mut len = 0;
+    loop {
+        if len == buf.len() {
+            return Ok(None);
+        }
+        let read = socket.read(&mut buf[len..]).await?;
+        netstats::received(Link::HttpServer, read);
+        if read == 0 {
+            return Ok(None);
+        }
+        len += read;
+
+        if let Some(header_end) = find_header_end(&buf[..len]) {
+            let total = header_end + content_length(&buf[..header_end]);
+            if total > buf.len() {
+                return Ok(None);
+            }
+            if len >= total {
+                return Ok(Some(total));
+            }
+        }
+    }
+}
+
+/// 查找请求头结束位置（空行之后）
+fn find_header_end(data: &[u8]) -> Option<usize> {
+    data.windows(4)
+        .position(|window| window == b"\r\n\r\n")
+        .map(|pos| pos + 4)
+}
+
+/// 从请求头中读取 Content-Length，缺失时为 0
+fn content_length(header: &[u8]) -> usize {
+    let Ok(text) = core::str::from_utf8(header) else {
+        return 0;
+    };
+    header_value(text, "content-length")
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(0)
+}
+
+/// 按名称查找请求头（不区分大小写），返回去掉首尾空白的值
+fn header_value<'a>(header: &'a str, name: &str) -> Option<&'a str> {
+    header
+        .split("\r\n")
+        .skip(1)
+        .filter_map(|line| line.split_once(':'))
+        .find(|(key, _)| key.trim().eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.trim())
+}
+
+/// 解析请求行
+fn parse(data: &[u8]) -> Option<Request<'_>> {
+    let header_end = find_header_end(data)?;
+    let header = core::str::from_utf8(&data[..header_end]).ok()?;
+    let mut parts = header.lines().next()?.split(' ');
+    let method = parts.next()?;
+    let path = parts.next()?;
+    // 忽略查询参数
+    let path = path.split('?').next().unwrap_or(path);
+    let accept = header_value(header, "accept").unwrap_or("");
+    let cbor = accept.contains(cbor::CONTENT_TYPE);
+    Some(Request {
+        method,
+        path,
+        body: &data[header_end..],
+        cbor,
+        authorization: header_value(header, "authorization"),
+    })
+}
+
+/// 发送完整响应
+///
+/// # 参数
+/// * `socket` - 连接
+/// * `status` - 响应状态
+/// * `content_type` - 响应体类型
+/// * `body` - 响应体
+pub async fn respond(
+    socket: &mut TcpSocket<'_>,
+    status: Status,
+    content_type: &str,
+    body: &[u8],
+) -> Result<(), TcpError> {
+    let mut header = String::new();
+    write!(
+        header,
+        "HTTP/1.0 {}\r\n{}Content-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        status.line(),
+        status.headers(),
+        content_type,
+        body.len()
+    )
+    .ok();
+    socket.write_all(header.as_bytes()).await?;
+    netstats::sent(Link::HttpServer, header.len());
+    socket.write_all(body).await?;
+    netstats::sent(Link::HttpServer, body.len());
+    Ok(())
+}
+
+/// 发送 JSON 数据，请求接受 CBOR 时转换为 CBOR
+async fn respond_data(
+    socket: &mut TcpSocket<'_>,
+    request: &Request<'_>,
+    json: &str,
+) -> Result<(), TcpError> {
+    if !request.cbor {
+        return respond(socket, Status::Ok, "application/json", json.as_bytes()).await;
+    }
+    match cbor::from_json(json) {
+        Ok(data) => respond(socket, Status::Ok, cbor::CONTENT_TYPE, &data).await,
+        Err(err) => {
+            warn!("Failed to encode response as CBOR: {}", err);
+            respond(
+                socket,
+                Status::InternalError,
+                "text/plain",
+                b"encoding error\n",
+            )
+            .await
+        }
+    }
+}
+
+/// 请求路由
+async fn route(socket: &mut TcpSocket<'_>, request: &Request<'_>) -> Result<(), TcpError> {
+    if request.method != "GET" && !access::http_authorized(request.authorization) {
+        warn!("HTTP {} rejected: not authorized", request.path);
+        let body = b"unauthorized\n";
+        return respond(socket, Status::Unauthorized, "text/plain", body).await;
+    }
+    match (request.method, request.path) {
+        ("GET", "/logs/ring") => {
+            let data = logbuf::snapshot();
+            respond(socket, Status::Ok, "application/octet-stream", &data).await
+        }
+        ("GET", "/crash") => match crash::last() {
+            Some(record) => {
+                let text = format_crash(&record);
+                respond(socket, Status::Ok, "text/plain", text.as_bytes()).await
+            }
+            None => respond(socket, Status::NoContent, "text/plain", b"").await,
+        },
+        ("DELETE", "/crash") => match crash::clear() {
+            Ok(()) => respond(socket, Status::NoContent, "text/plain", b"").await,
+            Err(err) => {
+                warn!("Failed to clear crash record: {}", err);
+                respond(socket, Status::InternalError, "text/plain", b"flash error\n").await
+            }
+        },
+        ("GET", "/stats/jitter") => {
+            let text = jitter::format_report();
+            respond(socket, Status::Ok, "text/plain", text.as_bytes()).await
+        }
+        ("GET", "/sensors") => {
+            let text = format_sensors();
+            respond(socket, Status::Ok, "text/plain", text.as_bytes()).await
+        }
+        ("GET", "/api/sensors") => respond_data(socket, request, &sensors_json()).await,
+        ("GET", "/api/settings") => respond_data(socket, request, &settings::get().to_json()).await,
+        ("GET", "/api/device") => {
+            respond_data(socket, request, &device::identity().to_json()).await
+        }
+        ("GET", "/api/status") => respond_data(socket, request, &system::status().to_json()).await,
+        ("GET", "/metrics") => {
+            let text = netstats::format_metrics();
+            let content_type = "text/plain; version=0.0.4";
+            respond(socket, Status::Ok, content_type, text.as_bytes()).await
+        }
+        ("POST", "/dmx") => match core::str::from_utf8(request.body).ok().and_then(dmx::set_list) {
+            Some(()) => respond(socket, Status::NoContent, "text/plain", b"").await,
+            None => respond(socket, Status::BadRequest, "text/plain", b"bad channel list\n").await,
+        },
+        ("GET", "/api/thermostat") => {
+            respond_data(socket, request, &thermostat::status().to_json()).await
+        }
+        ("POST", "/thermostat") => {
+            let Some(tenths) = parse_setpoint(request.body) else {
+                return respond(socket, Status::BadRequest, "text/plain", b"bad setpoint\n").await;
+            };
+            thermostat::set_setpoint(tenths);
+            if let Err(err) = settings::save() {
+                warn!("Failed to save settings: {}", err);
+                let body = b"flash error\n";
+                return respond(socket, Status::InternalError, "text/plain", body).await;
+            }
+            respond(socket, Status::NoContent, "text/plain", b"").await
+        }
+        ("GET", "/api/relays") => respond_data(socket, request, &relays_json()).await,
+        ("POST", "/relay") => {
+            let Some((output, switch)) = parse_relay_command(request.body) else {
+                return respond(socket, Status::BadRequest, "text/plain", b"bad command\n").await;
+            };
+            relay::command(output, switch);
+            respond(socket, Status::NoContent, "text/plain", b"").await
+        }
+        ("GET", "/rules") => {
+            let text = rules::source();
+            respond(socket, Status::Ok, "text/plain", text.as_bytes()).await
+        }
+        ("PUT", "/rules") => {
+            let Ok(source) = core::str::from_utf8(request.body) else {
+                return respond(socket, Status::BadRequest, "text/plain", b"bad encoding\n").await;
+            };
+            set_rules(socket, source).await
+        }
+        ("DELETE", "/rules") => set_rules(socket, "").await,
+        _ => respond(socket, Status::NotFound, "text/plain", b"not found\n").await,
+    }
+}
+
+/// 替换自动化规则并返回结果
+async fn set_rules(socket: &mut TcpSocket<'_>, source: &str) -> Result<(), TcpError> {
+    match rules::set(source) {
+        Ok(_) => respond(socket, Status::NoContent, "text/plain", b"").await,
+        Err(RulesError::Compile(err)) => {
+            let mut text = String::new();
+            writeln!(text, "invalid rule: {:?}", err).ok();
+            respond(socket, Status::BadRequest, "text/plain", text.as_bytes()).await
+        }
+        Err(RulesError::Storage(err)) => {
+            warn!("Failed to save automation rules: {}", err);
+            let body = b"flash error\n";
+            respond(socket, Status::InternalError, "text/plain", body).await
+        }
+    }
+}
+
+/// 将崩溃记录格式化为文本
+fn format_crash(record: &crash::CrashRecord) -> String {
+    let mut text = String::new();
+    writeln!(text, "uptime_ms: {}", record.uptime_ms).ok();
+    writeln!(text, "message: {}", record.message()).ok();
+    let regs = record.registers;
+    if regs.is_exception() {
+        writeln!(text, "exccause: {}", regs.cause).ok();
+        writeln!(text, "pc: {:#010x}", regs.pc).ok();
+        writeln!(text, "ps: {:#010x}", regs.ps).ok();
+        writeln!(text, "a0: {:#010x}", regs.a0).ok();
+        writeln!(text, "sp: {:#010x}", regs.sp).ok();
+        writeln!(text, "excvaddr: {:#010x}", regs.excvaddr).ok();
+    }
+    text.push_str("backtrace:");
+    for pc in record.backtrace() {
+        write!(text, " {:#010x}", pc).ok();
+    }
+    text.push('\n');
+    text
+}
+
+/// 将传感器读数格式化为文本，每行一个读数
+fn format_sensors() -> String {
+    let mut text = String::new();
+    let now = Instant::now();
+    for reading in sensor::all() {
+        let age = now.duration_since(reading.updated).as_secs();
+        writeln!(text, "{}: {} {} ({} s ago)", reading.name, reading.value, reading.unit, age).ok();
+    }
+    text
+}
+
+/// 将传感器读数编码为 JSON 数组
+fn sensors_json() -> String {
+    let mut text = String::new();
+    {
+        let mut array = Array::new(&mut text);
+        for reading in sensor::all() {
+            reading.write_members(&mut array.object());
+        }
+    }
+    text
+}
+
+/// 将各路继电器输出的状态编码为 JSON 数组，下标加 1 为输出编号
+fn relays_json() -> String {
+    let mut text = String::new();
+    {
+        let mut array = Array::new(&mut text);
+        for status in relay::status() {
+            status.write_members(&mut array.object());
+        }
+    }
+    text
+}
+
+/// 解析 `setpoint=<°C>`
+///
+/// # 返回
+/// 设定值（0.1 °C），格式错误或超出范围时返回 None
+fn parse_setpoint(body: &[u8]) -> Option<i16> {
+    let body = core::str::from_utf8(body).ok()?;
+    let value = body.trim().strip_prefix("setpoint=")?;
+    thermostat::parse_tenths(value).filter(|tenths| thermostat::SETPOINT_RANGE.contains(tenths))
+}
+
+/// 解析 `<n>=on|off|<分钟>`
+///
+/// # 返回
+/// 输出编号和开关命令，格式错误或编号无效时返回 None
+fn parse_relay_command(body: &[u8]) -> Option<(usize, Switch)> {
+    let body = core::str::from_utf8(body).ok()?;
+    let (output, switch) = body.trim().split_once('=')?;
+    let output = output.parse().ok();
+    let output = output.filter(|n| (1..=relay::OUTPUTS).contains(n))?;
+    Some((output, Switch::parse(switch)?))
+}
@@ -0,0 +1,358 @@
+//! 最小的 HTTP 客户端
+//!
+//! 发送 HTTP/1.0 `GET` 或 `POST` 请求并读取完整响应，服务器在响应后关闭连接，
+//! 因此不需要处理分块传输编码。主机名通过协议栈的 DNS 解析（IP 地址字面量直接使用）。
+//! 放不进内存的下载（例如固件）用 [get_streamed] 边接收边处理。
+//!
+//! 限制：
+//!
+//! - 只支持明文 HTTP，不支持 TLS
+//! - 响应（包括响应头）必须能放入调用者提供的缓冲区，[get_streamed] 只要求响应头放得下
+//! - 不跟随重定向
+//!
+//! 连接参数默认为 [OPTIONS]，[get] 下载较大的响应时可以加大接收缓冲区。
+
+use crate::net::{SocketOptions, TcpBuffers};
+use crate::netstats::{self, Link};
+use core::fmt::Write as _;
+use embassy_net::Stack;
+use embassy_net::dns::DnsQueryType;
+use embassy_net::tcp::TcpSocket;
+use embedded_io_async::Write;
+use heapless::String;
+
+/// 默认的连接参数：15 秒超时，请求通常很短，发送缓冲区只需 512 字节
+pub const OPTIONS: SocketOptions = SocketOptions {
+    tx_buffer: 512,
+    ..SocketOptions::DEFAULT
+};
+
+/// [get_streamed] 的响应头缓冲区大小
+const HEADER_BUF_LEN: usize = 1024;
+
+/// 地址错误
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct InvalidUrl;
+
+/// 解析地址 `http://<host>[:<port>][/<path>]`
+///
+/// # 返回
+/// 主机、端口和路径
+pub fn parse_url(url: &str) -> Result<(&str, u16, &str), InvalidUrl> {
+    let rest = url.strip_prefix("http://").ok_or(InvalidUrl)?;
+    let (authority, path) = match rest.find('/') {
+        Some(index) => rest.split_at(index),
+        None => (rest, "/"),
+    };
+    let (host, port) = match authority.split_once(':') {
+        Some((host, port)) => (host, port.parse().map_err(|_| InvalidUrl)?),
+        None => (authority, 80),
+    };
+    if host.is_empty() {
+        return Err(InvalidUrl);
+    }
+    Ok((host, port, path))
+}
+
+/// 请求错误
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum HttpClientError {
+    /// 主机名解析失败
+    Dns,
+    /// 无法建立连接
+    Connect,
+    /// 发送或接收失败（包括超时）
+    Io,
+    /// 响应超出缓冲区
+    TooLarge,
+    /// 响应格式错误
+    Malformed,
+    /// 服务器返回非 2xx 状态码
+    Status(u16),
+    /// 调用者中止了下载
+    Aborted,
+}
+
+/// 发送 `GET` 请求
+///
+/// # 参数
+/// * `stack` - 网络协议栈
+/// * `options` - 连接参数，通常为 [OPTIONS]
+/// * `host` - 主机名或 IPv4 地址
+/// * `port` - 端口，通常为 80
+/// * `path` - 请求路径，包括查询参数
+/// * `buf` - 响应缓冲区
+///
+/// # 返回
+/// 响应体，位于 `buf` 中
+pub async fn get<'b>(
+    stack: Stack<'_>,
+    options: &SocketOptions,
+    host: &str,
+    port: u16,
+    path: &str,
+    buf: &'b mut [u8],
+) -> Result<&'b [u8], HttpClientError> {
+    request(stack, options, host, port, "GET", path, None, buf).await
+}
+
+/// 发送 `POST` 请求
+///
+/// # 参数
+/// * `stack` - 网络协议栈
+/// * `host` - 主机名或 IPv4 地址
+/// * `port` - 端口，通常为 80
+/// * `path` - 请求路径
+/// * `content_type` - 请求体类型，例如 `application/json`
+/// * `body` - 请求体
+/// * `buf` - 响应缓冲区
+///
+/// # 返回
+/// 响应体，位于 `buf` 中
+pub async fn post<'b>(
+    stack: Stack<'_>,
+    host: &str,
+    port: u16,
+    path: &str,
+    content_type: &str,
+    body: &[u8],
+    buf: &'b mut [u8],
+) -> Result<&'b [u8], HttpClientError> {
+    request(
+        stack,
+        &OPTIONS,
+        host,
+        port,
+        "POST",
+        path,
+        Some((content_type, body)),
+        buf,
+    )
+    .await
+}
+
+/// 发送 `GET` 请求，边接收边把响应体交给 `sink`
+///
+/// # 参数
+/// * `stack` - 网络协议栈
+/// * `options` - 连接参数，下载时通常加大接收缓冲区
+/// * `host` - 主机名或 IPv4 地址
+/// * `port` - 端口，通常为 80
+/// * `path` - 请求路径，包括查询参数
+/// * `sink` - 依次收到响应体的每一块和响应头中的 `Content-Length`（没有时为 None），
+///   返回 false 时中止下载
+///
+/// # 返回
+/// 响应体长度；响应体比 `Content-Length` 短（连接中断）时返回 [HttpClientError::Io]
+pub async fn get_streamed(
+    stack: Stack<'_>,
+    options: &SocketOptions,
+    host: &str,
+    port: u16,
+    path: &str,
+    mut sink: impl FnMut(&[u8], Option<u32>) -> bool,
+) -> Result<u32, HttpClientError> {
+    let mut buffers = TcpBuffers::new(*options);
+    let mut socket = connect(stack, &mut buffers, host, port).await?;
+    let result = async {
+        send_request(&mut socket, host, "GET", path, None).await?;
+
+        // 读到响应头结束，同一次读到的响应体开头先交给 sink
+        let mut buf = [0u8; HEADER_BUF_LEN];
+        let mut len = 0;
+        let header_end = loop {
+            if let Some(end) = buf[..len]
+                .windows(4)
+                .position(|window| window == b"\r\n\r\n")
+            {
+                break end + 4;
+            }
+            if len == buf.len() {
+                return Err(HttpClientError::TooLarge);
+            }
+            match read_some(&mut socket, &mut buf[len..]).await? {
+                0 => return Err(HttpClientError::Malformed),
+                read => len += read,
+            }
+        };
+        parse_response(&buf[..len])?;
+        let content_length = content_length(&buf[..header_end]);
+        let mut received = 0u32;
+        let mut data = &buf[header_end..len];
+        let mut chunk = [0u8; HEADER_BUF_LEN];
+        loop {
+            if !data.is_empty() {
+                if !sink(data, content_length) {
+                    return Err(HttpClientError::Aborted);
+                }
+                received += data.len() as u32;
+            }
+            let read = read_some(&mut socket, &mut chunk).await?;
+            if read == 0 {
+                break;
+            }
+            data = &chunk[..read];
+        }
+        match content_length {
+            Some(expected) if expected != received => Err(HttpClientError::Io),
+            _ => Ok(received),
+        }
+    }
+    .await;
+    socket.close();
+    socket.flush().await.ok();
+    result
+}
+
+/// 解析主机名并建立连接
+async fn connect<'a>(
+    stack: Stack<'a>,
+    buffers: &'a mut TcpBuffers,
+    host: &str,
+    port: u16,
+) -> Result<TcpSocket<'a>, HttpClientError> {
+    let address = *stack
+        .dns_query(host, DnsQueryType::A)
+        .await
+        .map_err(|_| HttpClientError::Dns)?
+        .first()
+        .ok_or(HttpClientError::Dns)?;
+
+    let mut socket = buffers.socket(stack);
+    socket
+        .connect((address, port))
+        .await
+        .map_err(|_| HttpClientError::Connect)?;
+    netstats::opened(Link::HttpClient);
+    Ok(socket)
+}
+
+/// 建立连接、发送请求并解析响应
+#[allow(clippy::too_many_arguments)]
+async fn request<'b>(
+    stack: Stack<'_>,
+    options: &SocketOptions,
+    host: &str,
+    port: u16,
+    method: &str,
+    path: &str,
+    body: Option<(&str, &[u8])>,
+    buf: &'b mut [u8],
+) -> Result<&'b [u8], HttpClientError> {
+    let mut buffers = TcpBuffers::new(*options);
+    let mut socket = connect(stack, &mut buffers, host, port).await?;
+    let result = exchange(&mut socket, host, method, path, body, buf).await;
+    socket.close();
+    socket.flush().await.ok();
+    let len = result?;
+
+    parse_response(&buf[..len])
+}
+
+/// 发送请求并读取到连接关闭
+///
+/// # 返回
+/// 读取的响应长度
+async fn exchange(
+    socket: &mut TcpSocket<'_>,
+    host: &str,
+    method: &str,
+    path: &str,
+    body: Option<(&str, &[u8])>,
+    buf: &mut [u8],
+) -> Result<usize, HttpClientError> {
+    send_request(socket, host, method, path, body).await?;
+    let mut len = 0;
+    loop {
+        if len == buf.len() {
+            return Err(HttpClientError::TooLarge);
+        }
+        match read_some(socket, &mut buf[len..]).await? {
+            0 => return Ok(len),
+            read => len += read,
+        }
+    }
+}
+
+/// 发送请求行、请求头和请求体
+async fn send_request(
+    socket: &mut TcpSocket<'_>,
+    host: &str,
+    method: &str,
+    path: &str,
+    body: Option<(&str, &[u8])>,
+) -> Result<(), HttpClientError> {
+    let mut body_headers: String<96> = String::new();
+    if let Some((content_type, body)) = body {
+        write!(
+            body_headers,
+            "Content-Type: {}\r\nContent-Length: {}\r\n",
+            content_type,
+            body.len()
+        )
+        .map_err(|_| HttpClientError::TooLarge)?;
+    }
+    let mut header_len = 0;
+    for part in [
+        method,
+        " ",
+        path,
+        " HTTP/1.0\r\nHost: ",
+        host,
+        "\r\nUser-Agent: esp-app-4\r\nConnection: close\r\n",
+        &body_headers,
+        "\r\n",
+    ] {
+        socket
+            .write_all(part.as_bytes())
+            .await
+            .map_err(|_| HttpClientError::Io)?;
+        header_len += part.len();
+    }
+    netstats::sent(Link::HttpClient, header_len);
+    if let Some((_, body)) = body {
+        socket
+            .write_all(body)
+            .await
+            .map_err(|_| HttpClientError::Io)?;
+        netstats::sent(Link::HttpClient, body.len());
+    }
+    Ok(())
+}
+
+/// 读取一次，0 表示连接已关闭
+async fn read_some(socket: &mut TcpSocket<'_>, buf: &mut [u8]) -> Result<usize, HttpClientError> {
+    let read = socket.read(buf).await.map_err(|_| HttpClientError::Io)?;
+    netstats::received(Link::HttpClient, read);
+    Ok(read)
+}
+
+/// 响应头中的 `Content-Length`
+fn content_length(header: &[u8]) -> Option<u32> {
+    let header = core::str::from_utf8(header).ok()?;
+    header.lines().find_map(|line| {
+        let (name, value) = line.split_once(':')?;
+        name.eq_ignore_ascii_case("content-length")
+            .then(|| value.trim().parse().ok())?
+    })
+}
+
+/// 检查状态行并取出响应体
+fn parse_response(response: &[u8]) -> Result<&[u8], HttpClientError> {
+    let header_end = response
+        .windows(4)
+        .position(|window| window == b"\r\n\r\n")
+        .ok_or(HttpClientError::Malformed)?;
+    let header =
+        core::str::from_utf8(&response[..header_end]).map_err(|_| HttpClientError::Malformed)?;
+    let status: u16 = header
+        .lines()
+        .next()
+        .and_then(|line| line.split(' ').nth(1))
+        .and_then(|code| code.parse().ok())
+        .ok_or(HttpClientError::Malformed)?;
+    if !(200..300).contains(&status) {
+        return Err(HttpClientError::Status(status));
+    }
+    Ok(&response[header_end + 4..])
+}
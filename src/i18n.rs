@@ -0,0 +1,915 @@
+//! 界面文本多语言支持
+//!
+//! 所有显示给用户的界面、菜单和诊断文本都以 [Msg] 标识，通过 [tr] 按设置中的语言
+//! 取得对应译文。新增文本时在 [Msg] 中添加一项，并在 [Msg::translations] 中
+//! 同时给出英文和中文。TF 卡资源包中的译文可以替换内置译文（见 [crate::assets]）。
+//!
+//! defmt 日志的格式字符串在编译时被编码进 ELF，不经过本模块，始终为英文。
+//!
+//! 内置 LCD 字体（`FONT_10X20`）只包含 ASCII 字符，中文需要 TF 卡资源包提供全角字体
+//! （见 [crate::assets] 的 `font.cjk`）。屏幕上的文本应通过 [lcd] 获取并用 [assets::font]
+//! 显示，当前语言在 LCD 上没有可用字体时自动回退为英文；小字体的文本通过 [lcd_small] 获取。
+//! 控制台终端支持 UTF-8，直接使用 [tr]。
+
+use crate::{assets, settings};
+
+/// 界面语言
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Language {
+    English,
+    Chinese,
+}
+
+impl Language {
+    /// 所有语言，下标与设置中保存的编码一致
+    pub const ALL: [Language; 2] = [Language::English, Language::Chinese];
+
+    /// 设置中保存的编码
+    pub const fn to_u8(self) -> u8 {
+        self as u8
+    }
+
+    /// 从设置中的编码解析，未知编码视为英文
+    pub const fn from_u8(value: u8) -> Language {
+        match value {
+            1 => Language::Chinese,
+            _ => Language::English,
+        }
+    }
+
+    /// 语言代码，用于命令行参数
+    pub const fn code(self) -> &'static str {
+        match self {
+            Language::English => "en",
+            Language::Chinese => "zh",
+        }
+    }
+
+    /// 语言名称（ASCII，可在 LCD 上显示）
+    #[cfg(feature = "ui")]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Language::English => "English",
+            Language::Chinese => "Chinese",
+        }
+    }
+
+    /// LCD 字体是否能显示该语言：中文需要资源包中的全角字体
+    pub fn has_lcd_font(self) -> bool {
+        match self {
+            Language::English => true,
+            Language::Chinese => assets::has_cjk_font(),
+        }
+    }
+}
+
+/// 界面文本标识
+#[cfg_attr(
+    not(all(feature = "ui", feature = "sd")),
+    expect(dead_code, reason = "the table holds the texts of every feature")
+)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Msg {
+    // 设置向导
+    WizardLanguage,
+    WizardSelectWifi,
+    WizardScanning,
+    WizardRescan,
+    WizardSkip,
+    WizardPassword,
+    WizardListHint,
+    WizardKeyboardHint,
+    WizardConnecting,
+    WizardPleaseWait,
+    WizardConnectFailed,
+    WizardAssociateFailed,
+    WizardNoAddress,
+    WizardAnyKey,
+    WizardSetup,
+    WizardSaveFailed,
+    WizardComplete,
+    WizardRebooting,
+    // 状态屏幕
+    Uptime,
+    // BOOT 按键长按倒计时
+    ButtonSetupIn,
+    ButtonResetIn,
+    // 启动和固件更新进度
+    BootSdCard,
+    BootWifi,
+    OtaVerifying,
+    OtaWriting,
+    // 气象站屏幕
+    WeatherTemperature,
+    WeatherHumidity,
+    WeatherPressure,
+    WeatherMin,
+    WeatherMax,
+    WeatherNoForecast,
+    // 定时器
+    TimerSetup,
+    TimerRunning,
+    TimerPaused,
+    TimerExpired,
+    TimerSetupHint,
+    TimerRunningHint,
+    TimerPausedHint,
+    TimerExpiredHint,
+    // 秒表
+    StopwatchTitle,
+    StopwatchStart,
+    StopwatchStop,
+    StopwatchLap,
+    StopwatchReset,
+    StopwatchScroll,
+    GameScore,
+    GameStartHint,
+    GamePaused,
+    GameOver,
+    PhotoNoCard,
+    PhotoNoPhotos,
+    PhotoPaused,
+    ClockOnline,
+    ClockOffline,
+    ClockWaiting,
+    Sunday,
+    Monday,
+    Tuesday,
+    Wednesday,
+    Thursday,
+    Friday,
+    Saturday,
+    LinkTestTitle,
+    LinkTestOffline,
+    LinkTestSearching,
+    LinkTestLoss,
+    LinkTestPair,
+    PairingTitle,
+    PairingSearching,
+    PairingCompare,
+    PairingConfirm,
+    PairingCancel,
+    PairingDone,
+    PairingFailed,
+    RemoteTitle,
+    BenchTitle,
+    ThermostatTitle,
+    ThermostatSetpoint,
+    ThermostatNotConfigured,
+    ThermostatOutputOn,
+    ThermostatOutputOff,
+    ThermostatAdjust,
+    PidTitle,
+    PidNotRunning,
+    RelayTitle,
+    RelayUnassigned,
+    RelaySelect,
+    RelayToggle,
+    // 状态屏幕的其他页面
+    PagesHint,
+    PageSettings,
+    PageFiles,
+    PageNetwork,
+    PagePeripherals,
+    PageServices,
+    SettingsProfile,
+    SettingsLanguage,
+    SettingsTheme,
+    SettingsWifi,
+    SettingsDevice,
+    SettingsId,
+    FilesNoCard,
+    FilesEmpty,
+    NetworkIdle,
+    ServicesRunning,
+    ServicesStopped,
+    ServicesHint,
+    SettingsCalibrate,
+    PageCalibration,
+    CalibrationGamma,
+    CalibrationContrast,
+    CalibrationInvert,
+    CalibrationHint,
+    // 命令行
+    CliHelp,
+    CliUnknownCommand,
+    CliNoCrashRecord,
+    CliBenchNoResults,
+    CliCrashCleared,
+    CliCrashClearFailed,
+    CliCapUsage,
+    #[cfg(feature = "fault-injection")]
+    CliFaultUsage,
+    CliWifiTooLong,
+    CliWifiUsage,
+    CliWifiNone,
+    CliWifiNotSaved,
+    CliConsoleUsage,
+    CliCountryUsage,
+    CliScanEmpty,
+    CliHostnameUsage,
+    CliDeviceUsage,
+    CliAuthUsage,
+    CliLocked,
+    CliUnlocked,
+    CliWrongPin,
+    CliLockedOut,
+    CliDateNotSet,
+    CliDateUsage,
+    CliDateRejected,
+    CliLangUsage,
+    CliLangSaved,
+    CliCanNotStarted,
+    CliCanSendUsage,
+    CliCanSendFailed,
+    CliCanSniffUsage,
+    CliCanSniffBusy,
+    CliRcNone,
+    CliRcWatchUsage,
+    CliRcWatchBusy,
+    CliLinUsage,
+    CliLinNone,
+    CliCanFilterUsage,
+    CliDmxUsage,
+    CliProfileUsage,
+    CliKeymapUsage,
+    CliForecastUsage,
+    CliForecastNone,
+    CliForecastSaved,
+    CliPhotoUsage,
+    CliPhotoSaved,
+    CliClockUsage,
+    CliClockSaved,
+    CliThemeUsage,
+    CliThemeSaved,
+    CliLcdUsage,
+    CliLcdSaved,
+    CliFpsUsage,
+    CliFpsSaved,
+    CliSleepUsage,
+    CliWebhookUsage,
+    CliWebhookNone,
+    CliWebhookSaved,
+    CliOtaUsage,
+    CliOtaStarted,
+    CliSyslogUsage,
+    CliSyslogNone,
+    CliMqttUsage,
+    CliMqttNone,
+    CliSyncUsage,
+    CliSyncNone,
+    CliThermostatUsage,
+    CliThermostatNone,
+    CliPidUsage,
+    CliPidNone,
+    CliPidNotRunning,
+    CliRelayUsage,
+    CliEspNowUsage,
+    CliEspNowNone,
+    CliEspNowNotPaired,
+    CliBoardUsage,
+    CliBoardPortUsage,
+    CliPowerUsage,
+    CliModbusUsage,
+    CliServiceUsage,
+    CliPresenceUsage,
+    CliSerialUsage,
+    CliBridgeUsage,
+    CliScheduleUsage,
+    CliScheduleNone,
+    CliScheduleSaved,
+    CliRulesUsage,
+    CliRulesNone,
+    CliRulesInvalid,
+    CliRulesSaveFailed,
+    CliRulesSaved,
+    CliSaved,
+    CliSaveFailed,
+}
+
+impl Msg {
+    /// 英文和中文译文
+    const fn translations(self) -> [&'static str; 2] {
+        match self {
+            Msg::WizardLanguage => ["Language", "语言"],
+            Msg::WizardSelectWifi => ["Select Wi-Fi", "选择 Wi-Fi"],
+            Msg::WizardScanning => ["Scanning...", "正在扫描..."],
+            Msg::WizardRescan => ["Rescan", "重新扫描"],
+            Msg::WizardSkip => ["Skip (offline)", "跳过（离线）"],
+            Msg::WizardPassword => ["Wi-Fi password", "Wi-Fi 密码"],
+            Msg::WizardListHint => ["K0/K1 move  K2 ok  K3 back", "K0/K1 移动 K2 确认 K3 返回"],
+            Msg::WizardKeyboardHint => [
+                "K0 right K1 down K2 ok K3 back",
+                "K0 右移 K1 下移 K2 输入 K3 返回",
+            ],
+            Msg::WizardConnecting => ["Connecting", "正在连接"],
+            Msg::WizardPleaseWait => ["Please wait...", "请稍候..."],
+            Msg::WizardConnectFailed => ["Connection failed", "连接失败"],
+            Msg::WizardAssociateFailed => ["Wi-Fi connect failed", "无法连接 Wi-Fi"],
+            Msg::WizardNoAddress => ["No IP address (DHCP)", "未获取到 IP 地址（DHCP）"],
+            Msg::WizardAnyKey => ["Press any key", "按任意键继续"],
+            Msg::WizardSetup => ["Setup", "设置"],
+            Msg::WizardSaveFailed => ["Failed to save settings", "保存设置失败"],
+            Msg::WizardComplete => ["Setup complete", "设置完成"],
+            Msg::WizardRebooting => ["Rebooting...", "正在重启..."],
+            Msg::Uptime => ["Uptime", "运行时间"],
+            Msg::ButtonSetupIn => ["Setup in", "进入配网"],
+            Msg::ButtonResetIn => ["Factory reset in", "恢复出厂设置"],
+            Msg::BootSdCard => ["Mounting SD card", "正在挂载 TF 卡"],
+            Msg::BootWifi => ["Starting Wi-Fi", "正在启动 Wi-Fi"],
+            Msg::OtaVerifying => ["Verifying firmware", "正在校验固件"],
+            Msg::OtaWriting => ["Writing firmware", "正在写入固件"],
+            Msg::WeatherTemperature => ["Temp", "温度"],
+            Msg::WeatherHumidity => ["Humidity", "湿度"],
+            Msg::WeatherPressure => ["Pressure", "气压"],
+            Msg::WeatherMin => ["24h min", "24 小时最低"],
+            Msg::WeatherMax => ["max", "最高"],
+            Msg::WeatherNoForecast => ["No forecast", "无天气预报"],
+            Msg::TimerSetup => ["Set timer", "设置定时"],
+            Msg::TimerRunning => ["Running", "计时中"],
+            Msg::TimerPaused => ["Paused", "已暂停"],
+            Msg::TimerExpired => ["Time's up!", "时间到！"],
+            Msg::TimerSetupHint => ["K0/K1 -/+  K2 start  K3 preset", "K0/K1 -/+ K2 开始 K3 预设"],
+            Msg::TimerRunningHint => ["K2 pause  K3 reset", "K2 暂停 K3 取消"],
+            Msg::TimerPausedHint => ["K2 resume  K3 reset", "K2 继续 K3 取消"],
+            Msg::TimerExpiredHint => ["Press any key", "按任意键停止"],
+            Msg::StopwatchTitle => ["Stopwatch", "秒表"],
+            Msg::StopwatchStart => ["Start", "开始"],
+            Msg::StopwatchStop => ["Stop", "停止"],
+            Msg::StopwatchLap => ["Lap", "计圈"],
+            Msg::StopwatchReset => ["Reset", "清零"],
+            Msg::StopwatchScroll => ["List", "列表"],
+            Msg::GameScore => ["Score", "得分"],
+            Msg::GameStartHint => ["K2 start  K0/K1 turn", "K2 开始 K0/K1 转向"],
+            Msg::GamePaused => ["Paused  K2 resume", "已暂停 K2 继续"],
+            Msg::GameOver => ["Game over  K2 retry", "游戏结束 K2 重来"],
+            Msg::PhotoNoCard => ["No SD card", "未插入 TF 卡"],
+            Msg::PhotoNoPhotos => ["No BMP files in /PHOTOS", "/PHOTOS 中没有 BMP 图片"],
+            Msg::PhotoPaused => ["Paused", "已暂停"],
+            Msg::ClockOnline => ["Wi-Fi", "Wi-Fi"],
+            Msg::ClockOffline => ["Offline", "离线"],
+            Msg::ClockWaiting => ["Waiting for time sync", "等待时间同步"],
+            Msg::Sunday => ["Sun", "周日"],
+            Msg::Monday => ["Mon", "周一"],
+            Msg::Tuesday => ["Tue", "周二"],
+            Msg::Wednesday => ["Wed", "周三"],
+            Msg::Thursday => ["Thu", "周四"],
+            Msg::Friday => ["Fri", "周五"],
+            Msg::Saturday => ["Sat", "周六"],
+            Msg::LinkTestTitle => ["Link test", "链路测试"],
+            Msg::LinkTestOffline => ["offline", "未连接"],
+            Msg::LinkTestSearching => ["searching...", "正在寻找对端…"],
+            Msg::LinkTestLoss => ["Loss", "丢包"],
+            Msg::LinkTestPair => ["pair", "配对"],
+            Msg::PairingTitle => ["ESP-NOW pairing", "ESP-NOW 配对"],
+            Msg::PairingSearching => ["press on the other board too", "请在另一块板上也按确认"],
+            Msg::PairingCompare => ["same code on both boards?", "两块板的配对码相同吗？"],
+            Msg::PairingConfirm => ["yes", "相同"],
+            Msg::PairingCancel => ["cancel", "取消"],
+            Msg::PairingDone => ["paired with", "已配对"],
+            Msg::PairingFailed => ["pairing failed", "配对失败"],
+            Msg::RemoteTitle => ["Remote display", "远程显示"],
+            Msg::BenchTitle => ["Display benchmark", "显示性能测试"],
+            Msg::ThermostatTitle => ["Thermostat", "恒温控制"],
+            Msg::ThermostatSetpoint => ["Setpoint", "设定"],
+            Msg::ThermostatNotConfigured => ["No sensor selected", "未选择温度读数"],
+            Msg::ThermostatOutputOn => ["Output on", "输出开"],
+            Msg::ThermostatOutputOff => ["Output off", "输出关"],
+            Msg::ThermostatAdjust => ["+/- setpoint", "调整设定值"],
+            Msg::PidTitle => ["PID loop", "PID 控制"],
+            Msg::PidNotRunning => ["Not running", "未运行"],
+            Msg::RelayTitle => ["Relays", "继电器"],
+            Msg::RelayUnassigned => ["no pin", "未分配引脚"],
+            Msg::RelaySelect => ["select", "选择"],
+            Msg::RelayToggle => ["on/off", "开关"],
+            Msg::PagesHint => ["K0 next page  K1/K2 scroll  K3 home", "K0 下一页 K1/K2 滚动 K3 返回"],
+            Msg::PageSettings => ["Settings", "设置"],
+            Msg::PageFiles => ["Files", "文件"],
+            Msg::PageNetwork => ["Network traffic", "网络流量"],
+            Msg::PagePeripherals => ["Peripherals", "外设"],
+            Msg::PageServices => ["Services", "服务"],
+            Msg::SettingsProfile => ["Mode", "模式"],
+            Msg::SettingsLanguage => ["Language", "语言"],
+            Msg::SettingsTheme => ["Theme", "配色"],
+            Msg::SettingsWifi => ["Wi-Fi", "Wi-Fi"],
+            Msg::SettingsDevice => ["Device", "设备"],
+            Msg::SettingsId => ["ID", "ID"],
+            Msg::FilesNoCard => ["No SD card", "未插入 TF 卡"],
+            Msg::FilesEmpty => ["No files", "没有文件"],
+            Msg::NetworkIdle => ["No traffic yet", "暂无流量"],
+            Msg::ServicesRunning => ["running", "运行中"],
+            Msg::ServicesStopped => ["stopped", "已停止"],
+            Msg::ServicesHint => ["K1 select  K2 start/stop", "K1 选择 K2 启停"],
+            Msg::SettingsCalibrate => ["K2: calibrate display", "K2：屏幕校准"],
+            Msg::PageCalibration => ["Display calibration", "屏幕校准"],
+            Msg::CalibrationGamma => ["Gamma", "Gamma"],
+            Msg::CalibrationContrast => ["Contrast", "对比度"],
+            Msg::CalibrationInvert => ["Invert", "反转"],
+            Msg::CalibrationHint => ["K1 select  K2 change  K3 save", "K1 选择 K2 调整 K3 保存"],
+            Msg::CliHelp => [
+                "\
+help                      show this help\r
+uptime                    show time since boot\r
+status                    show a summary of all subsystems\r
+reboot                    restart the device\r
+sleep <seconds>           shut down and deep sleep, then restart\r
+log dump                  print the log ring buffer as hex (defmt frames)\r
+log clear                 clear the log ring buffer\r
+crash [clear]             show or clear the last crash record\r
+jitter                    show periodic task scheduling delays\r
+bench                     show the last display benchmark results\r
+cap [<name> on|off]       show or toggle subsystems (after reboot)\r
+peripherals list          show each peripheral's bus, state and last error\r
+board                     show the board variant, pin map and fitted peripherals\r
+board dnesp32s3|custom    select the board variant (after reboot)\r
+board pin <signal> <gpio> set a pin of the custom board (after reboot)\r
+board has <name> on|off   mark a peripheral as fitted on the custom board (after reboot)\r
+board port <name> <gpio>... [<param>]|off wire an expansion header port (after reboot)\r
+power                     show the power-up stagger, current budget and loads\r
+power stagger <ms>        set the power-up interval of high-inrush loads (after reboot)\r
+power budget <mA>         set the inrush current budget for simultaneous loads (after reboot)\r
+modbus                    show the Modbus RTU slave address\r
+modbus unit <address>     set the Modbus RTU slave address (1-247)\r
+service                   list services that can be started and stopped at runtime\r
+service <name> start|stop start or stop a service (until reboot)\r
+presence                  show the Wi-Fi signal variance and presence state (experimental)\r
+presence on|off           turn Wi-Fi signal presence sensing on or off (until reboot)\r
+serial tap on|off         log raw bytes of expansion serial ports (until reboot)\r
+bridge                    show the serial-to-TCP bridge setup\r
+bridge off|<port> server|client ...  set up the serial-to-TCP bridge (after reboot)\r
+presence threshold <dB^2> set the signal variance that counts as motion (until reboot)\r
+assets                    show which assets the SD card bundle replaced\r
+wifi                      list the saved Wi-Fi networks in the order they are tried\r
+wifi <ssid> [password]    add a Wi-Fi network or change its password (after reboot)\r
+wifi forget <ssid>        remove a saved Wi-Fi network\r
+wifi priority <ssid> <n>  set the priority of a network (0-255, higher first)\r
+country [<cc> [<first>-<last>]]   show or set the Wi-Fi country and channels (after reboot)\r
+scan                      show the access points found by background scans\r
+hostname [<name>|default] show or set the DHCP host name (after reboot)\r
+device [name|location|tags <text>|off]    show or set the device identity\r
+auth [token|pin <value>|off]      show or set the HTTP token and the console PIN\r
+unlock <pin>              allow protected commands for 5 minutes\r
+lock                      lock protected commands again\r
+console [usb|uart]        show or select the console (after reboot)\r
+lang [en|zh]              show or select the UI language\r
+date [<unix seconds>]     show or set the UTC time\r
+can [sniff [<seconds>]]   show CAN status or print received frames (any key stops)\r
+rc [watch [<seconds>]]    show or print RC receiver channels (any key stops)\r
+lin                       show the LIN schedule and frame data\r
+lin add <id> pub|sub <len> <ms>   append a frame to the LIN schedule (after reboot)\r
+lin clear                 clear the LIN schedule (after reboot)\r
+lin checksum classic|enhanced     set the LIN checksum type (after reboot)\r
+lin set <id> <hex>        set the data the master publishes for a frame\r
+can send <id> [<hex>]     send a CAN frame (8-digit id: extended)\r
+can filter all|std|ext [<code> <mask>]    set the CAN acceptance filter (after reboot)\r
+dmx get <channel>         show a DMX channel (1-512)\r
+dmx set <ch>=<v>&...      set DMX channels\r
+dmx blackout              set all DMX channels to 0\r
+profile [<name>]          list or select the application (after reboot)\r
+keymap [<action> <key>]   show or change the application key bindings\r
+forecast                  show the downloaded weather forecast\r
+forecast location <lat>,<lon>     set the forecast location\r
+forecast provider <name> [<key>]  open-meteo or openweathermap (key sent over plain HTTP)\r
+forecast upload <key>|off upload readings to a ThingSpeak channel (key sent over plain HTTP)\r
+photo                     show the photo frame settings\r
+photo interval <seconds>  set the slideshow interval\r
+photo transition cut|blinds       set the slideshow transition\r
+photo next|prev|pause     control the running photo frame\r
+clock                     show the clock settings\r
+clock face digital|analog select the clock face\r
+clock tz <+hh:mm>         set the local time offset from UTC\r
+clock night <from>-<to>|off       set the night mode hours\r
+theme [dark|light|contrast|auto]  show or select the color theme\r
+theme accent <rrggbb>|default     set the accent color\r
+lcd [<option> <value>]    show or tune the display gamma and contrast\r
+lcd table pos|neg <hex>|default   replace a gamma correction table\r
+fps [on|off|<1-60>]       show frame statistics, toggle the overlay or set the fps\r
+screen status|blank       show the status pages or a blank screen\r
+webhook [<url>|off|test]  show or set the alarm notification webhook\r
+webhook format json|cbor  select the webhook body encoding\r
+ota <url>                 download a signed firmware and reboot into it\r
+syslog [<host>[:<port>]|off]      set the syslog collector (after reboot)\r
+mqtt                      show the MQTT broker and connection state\r
+mqtt broker <host>[:<port>]|off   set the MQTT broker (after reboot)\r
+mqtt user <name> [<password>]|off set the MQTT credentials (after reboot)\r
+mqtt topic <prefix>|default       set the MQTT base topic (after reboot)\r
+mqtt sas <key>|off        authenticate to Azure IoT Hub with SAS tokens (after reboot)\r
+sync [<group>|off]        show or set the settings sync group\r
+thermostat [<option> ...] show or configure the thermostat relay output\r
+pid [<option> <value>]    show or tune the PID loop (takes effect next period)\r
+relay [<n> on|off|<min>]  show or switch the relay outputs\r
+relay pin <n> <pin>|off   assign a pin to a relay output\r
+espnow                    list the paired ESP-NOW peers\r
+espnow forget <id>|all    remove an ESP-NOW pairing\r
+schedule [<rules>|off]    show or set the cron-like scheduled actions\r
+rules [add <rule>]        show the automation rules or append one\r
+rules del <n>|clear       delete one or all automation rules\r
+",
+                "\
+help                      显示本帮助\r
+uptime                    显示启动以来的运行时间\r
+status                    显示各子系统的状态汇总\r
+reboot                    重启设备\r
+sleep <seconds>           关机并深度睡眠，到时重新启动\r
+log dump                  以十六进制输出日志环形缓冲区（defmt 帧）\r
+log clear                 清空日志环形缓冲区\r
+crash [clear]             显示或清除最近一次崩溃记录\r
+jitter                    显示周期任务的调度延迟\r
+bench                     显示最近一次显示性能测试结果\r
+cap [<name> on|off]       显示或开关子系统（重启后生效）\r
+peripherals list          显示各外设的总线、状态和最近一次错误\r
+board                     显示开发板型号、引脚表和装有的外设\r
+board dnesp32s3|custom    选择开发板型号（重启后生效）\r
+board pin <signal> <gpio> 设置自定义开发板的引脚（重启后生效）\r
+board has <name> on|off   设置自定义开发板是否装有某个外设（重启后生效）\r
+board port <名称> <gpio>... [<参数>]|off 设置扩展排针上接口的引脚（重启后生效）\r
+power                     显示上电间隔、电流预算和各负载\r
+power stagger <ms>        设置大电流负载的上电间隔（重启后生效）\r
+power budget <mA>         设置同时上电的负载冲击电流预算（重启后生效）\r
+modbus                    显示 Modbus RTU 从站地址\r
+modbus unit <地址>        设置 Modbus RTU 从站地址（1-247）\r
+service                   列出可在运行中启停的服务\r
+service <name> start|stop 启动或停止服务（重启前有效）\r
+presence                  显示 Wi-Fi 信号方差和存在检测状态（实验性）\r
+presence on|off           开关基于 Wi-Fi 信号的存在检测（重启前有效）\r
+serial tap on|off         开关扩展串口的原始字节日志（重启前有效）\r
+bridge                    显示串口透传设置\r
+bridge off|<接口> server|client ...  设置串口透传（重启后生效）\r
+presence threshold <dB^2> 设置判定为有人活动的信号方差（重启前有效）\r
+assets                    显示 TF 卡资源包替换了哪些资源\r
+wifi                      按尝试顺序列出保存的 Wi-Fi 网络\r
+wifi <ssid> [password]    添加 Wi-Fi 网络或修改密码（重启后生效）\r
+wifi forget <ssid>        删除保存的 Wi-Fi 网络\r
+wifi priority <ssid> <n>  设置网络的优先级（0-255，越大越先尝试）\r
+country [<cc> [<first>-<last>]]   显示或设置 Wi-Fi 国家代码和信道（重启后生效）\r
+scan                      显示后台扫描到的接入点\r
+hostname [<name>|default] 显示或设置 DHCP 主机名（重启后生效）\r
+device [name|location|tags <text>|off]    显示或设置设备标识\r
+auth [token|pin <value>|off]      显示或设置 HTTP 令牌和命令行 PIN\r
+unlock <pin>              允许执行受保护的命令，5 分钟有效\r
+lock                      重新锁定受保护的命令\r
+console [usb|uart]        显示或选择控制台（重启后生效）\r
+lang [en|zh]              显示或选择界面语言\r
+date [<unix seconds>]     显示或设置 UTC 时间\r
+can [sniff [<seconds>]]   显示 CAN 状态或打印收到的帧（按任意键结束）\r
+rc [watch [<seconds>]]    显示或持续打印遥控接收机通道（µs，按任意键结束）\r
+lin                       显示 LIN 调度表和帧数据\r
+lin add <id> pub|sub <len> <ms>   在 LIN 调度表末尾添加一帧（重启后生效）\r
+lin clear                 清空 LIN 调度表（重启后生效）\r
+lin checksum classic|enhanced     设置 LIN 校验和类型（重启后生效）\r
+lin set <id> <hex>        设置主节点发送的帧数据\r
+can send <id> [<hex>]     发送 CAN 帧（8 位 ID 为扩展帧）\r
+can filter all|std|ext [<code> <mask>]    设置 CAN 验收滤波器（重启后生效）\r
+dmx get <通道>            显示 DMX 通道值（1-512）\r
+dmx set <通道>=<值>&...   设置 DMX 通道\r
+dmx blackout              DMX 所有通道归零\r
+profile [<name>]          列出或选择应用模式（重启后生效）\r
+keymap [<action> <key>]   显示或修改应用的按键映射\r
+forecast                  显示下载的天气预报\r
+forecast location <lat>,<lon>     设置天气预报位置\r
+forecast provider <name> [<key>]  open-meteo 或 openweathermap（Key 经明文 HTTP 发送）\r
+forecast upload <key>|off 上传读数到 ThingSpeak 频道（Key 经明文 HTTP 发送）\r
+photo                     显示数码相框设置\r
+photo interval <seconds>  设置图片切换间隔\r
+photo transition cut|blinds       设置过渡效果\r
+photo next|prev|pause     控制正在运行的数码相框\r
+clock                     显示时钟设置\r
+clock face digital|analog 选择时钟表盘\r
+clock tz <+hh:mm>         设置本地时间与 UTC 之差\r
+clock night <from>-<to>|off       设置夜间模式时段\r
+theme [dark|light|contrast|auto]  显示或选择界面配色\r
+theme accent <rrggbb>|default     设置强调色\r
+lcd [<option> <value>]    显示或调校屏幕的 Gamma 和对比度\r
+lcd table pos|neg <hex>|default   替换 Gamma 校正表\r
+fps [on|off|<1-60>]       显示帧率统计、开关屏幕显示或设置目标帧率\r
+screen status|blank       显示状态页面或空白屏幕\r
+webhook [<url>|off|test]  显示或设置告警通知 webhook\r
+webhook format json|cbor  选择 webhook 请求体的编码\r
+ota <url>                 下载签名固件并重启进入\r
+syslog [<host>[:<port>]|off]      设置 syslog 收集器（重启后生效）\r
+mqtt                      显示 MQTT 代理和连接状态\r
+mqtt broker <host>[:<port>]|off   设置 MQTT 代理（重启后生效）\r
+mqtt user <name> [<password>]|off 设置 MQTT 用户名和密码（重启后生效）\r
+mqtt topic <prefix>|default       设置 MQTT 基础主题（重启后生效）\r
+mqtt sas <key>|off        用 SAS 令牌向 Azure IoT Hub 认证（重启后生效）\r
+sync [<group>|off]        显示或设置设置同步组\r
+thermostat [<option> ...] 显示或设置恒温控制器的继电器输出\r
+pid [<option> <value>]    显示或调整 PID 回路参数（下一个周期生效）\r
+relay [<n> on|off|<min>]  显示或开关继电器输出\r
+relay pin <n> <pin>|off   为继电器输出分配引脚\r
+espnow                    列出配对的 ESP-NOW 对端\r
+espnow forget <id>|all    删除 ESP-NOW 配对\r
+schedule [<rules>|off]    显示或设置类似 cron 的定时任务\r
+rules [add <rule>]        显示自动化规则或追加一条\r
+rules del <n>|clear       删除一条或全部自动化规则\r
+",
+            ],
+            Msg::CliUnknownCommand => {
+                ["unknown command (try 'help')", "未知命令（输入 'help' 查看帮助）"]
+            }
+            Msg::CliNoCrashRecord => ["no crash record", "没有崩溃记录"],
+            Msg::CliBenchNoResults => [
+                "no benchmark results (select the 'bench' profile)",
+                "没有测试结果（需选择 bench 应用模式）",
+            ],
+            Msg::CliCrashCleared => ["crash record cleared", "崩溃记录已清除"],
+            Msg::CliCrashClearFailed => ["failed to clear crash record", "清除崩溃记录失败"],
+            Msg::CliCapUsage => ["usage: cap <name> on|off", "用法：cap <name> on|off"],
+            #[cfg(feature = "fault-injection")]
+            Msg::CliFaultUsage => [
+                "usage: fault [i2c nack|timeout <n> | lcd nack|timeout|corrupt <n> | off]",
+                "用法：fault [i2c nack|timeout <n> | lcd nack|timeout|corrupt <n> | off]",
+            ],
+            Msg::CliWifiTooLong => ["ssid or password too long", "SSID 或密码过长"],
+            Msg::CliWifiUsage => [
+                "usage: wifi [<ssid> [password] | forget <ssid> | priority <ssid> <0-255>]",
+                "用法：wifi [<ssid> [密码] | forget <ssid> | priority <ssid> <0-255>]",
+            ],
+            Msg::CliWifiNone => ["no Wi-Fi networks saved", "没有保存 Wi-Fi 网络"],
+            Msg::CliWifiNotSaved => ["network not saved", "没有保存该网络"],
+            Msg::CliConsoleUsage => ["usage: console usb|uart", "用法：console usb|uart"],
+            Msg::CliCountryUsage => [
+                "usage: country <cc> [<first>-<last>] (e.g. US 1-11)",
+                "用法：country <国家代码> [<最低>-<最高>]（例如 CN 1-13）",
+            ],
+            Msg::CliScanEmpty => ["no access points found yet", "尚未扫描到接入点"],
+            Msg::CliHostnameUsage => [
+                "usage: hostname <name>|default (a-z, 0-9 and '-', up to 32 characters)",
+                "用法：hostname <名称>|default（a-z、0-9 和 '-'，最多 32 个字符）",
+            ],
+            Msg::CliAuthUsage => [
+                "usage: auth token <8-32 characters>|off | auth pin <4-8 digits>|off",
+                "用法：auth token <8-32 个字符>|off | auth pin <4-8 位数字>|off",
+            ],
+            Msg::CliLocked => [
+                "locked, run unlock <pin> first",
+                "已锁定，请先执行 unlock <PIN>",
+            ],
+            Msg::CliUnlocked => ["unlocked for 5 minutes", "已解锁 5 分钟"],
+            Msg::CliWrongPin => ["wrong PIN", "PIN 错误"],
+            Msg::CliLockedOut => [
+                "too many wrong PINs, try again later",
+                "PIN 错误次数过多，请稍后再试",
+            ],
+            Msg::CliDeviceUsage => [
+                "usage: device name|location|tags <text>|off (tags separated by commas)",
+                "用法：device name|location|tags <文本>|off（标签用逗号分隔）",
+            ],
+            Msg::CliDateNotSet => ["time not set", "系统时间未设置"],
+            Msg::CliDateUsage => ["usage: date [<unix seconds>]", "用法：date [<UNIX 秒数>]"],
+            Msg::CliDateRejected => [
+                "time not accepted: a more reliable source synced recently",
+                "时间未被采纳：更可靠的时间源近期校准过",
+            ],
+            Msg::CliLangUsage => ["usage: lang en|zh", "用法：lang en|zh"],
+            Msg::CliLangSaved => ["language saved", "语言已保存"],
+            Msg::CliCanNotStarted => ["CAN bus not started", "CAN 总线未启动"],
+            Msg::CliCanSendUsage => {
+                ["usage: can send <id> [<hex data>]", "用法：can send <id> [<十六进制数据>]"]
+            }
+            Msg::CliCanSendFailed => ["CAN send failed", "CAN 发送失败"],
+            Msg::CliCanSniffUsage => [
+                "usage: can sniff [<seconds, max 600>] (any key stops)",
+                "用法：can sniff [<秒数，最多 600>]（按任意键结束）",
+            ],
+            Msg::CliCanSniffBusy => ["too many CAN listeners", "CAN 监听者过多"],
+            Msg::CliRcNone => ["no RC receiver data", "没有收到遥控接收机数据"],
+            Msg::CliRcWatchUsage => [
+                "usage: rc watch [<seconds, max 600>] (any key stops)",
+                "用法：rc watch [<秒数，最多 600>]（按任意键结束）",
+            ],
+            Msg::CliRcWatchBusy => ["too many RC listeners", "遥控数据监听者过多"],
+            Msg::CliLinUsage => [
+                "usage: lin [add <id> pub|sub <len> <ms> | clear | checksum classic|enhanced \
+                 | set <id> <hex>] (id 0-3f hex, len 1-8)",
+                "用法：lin [add <id> pub|sub <长度> <毫秒> | clear | checksum classic|enhanced \
+                 | set <id> <十六进制数据>]（id 为十六进制 0-3f，长度 1-8）",
+            ],
+            Msg::CliLinNone => ["LIN schedule is empty", "LIN 调度表为空"],
+            Msg::CliCanFilterUsage => [
+                "usage: can filter all | std <code> <mask> | ext <code> <mask> (hex, after reboot)",
+                "用法：can filter all | std <code> <mask> | ext <code> <mask>（十六进制，重启后生效）",
+            ],
+            Msg::CliDmxUsage => [
+                "usage: dmx get <channel> | dmx set <channel>=<value>&... | dmx blackout",
+                "用法：dmx get <通道> | dmx set <通道>=<值>&... | dmx blackout",
+            ],
+            Msg::CliKeymapUsage => {
+                ["usage: keymap <action> key0..key3", "用法：keymap <动作> key0..key3"]
+            }
+            Msg::CliProfileUsage => {
+                ["usage: profile <name> (list: profile)", "用法：profile <名称>（列表：profile）"]
+            }
+            Msg::CliForecastUsage => [
+                "usage: forecast location <lat>,<lon> | provider <name> [<key>] | upload <key>|off",
+                "用法：forecast location <纬度>,<经度> | provider <名称> [<key>] | upload <key>|off",
+            ],
+            Msg::CliForecastNone => ["no forecast yet", "尚未获取天气预报"],
+            Msg::CliForecastSaved => {
+                ["saved, applied at the next update", "已保存，下次更新时生效"]
+            }
+            Msg::CliPhotoUsage => [
+                "usage: photo interval <1-3600> | transition cut|blinds | next|prev|pause",
+                "用法：photo interval <1-3600> | transition cut|blinds | next|prev|pause",
+            ],
+            Msg::CliPhotoSaved => ["saved, applied to the next photo", "已保存，下一张图片生效"],
+            Msg::CliClockUsage => [
+                "usage: clock face digital|analog | tz <+hh:mm> | night <0-23>-<0-23>|off",
+                "用法：clock face digital|analog | tz <+hh:mm> | night <0-23>-<0-23>|off",
+            ],
+            Msg::CliClockSaved => ["clock settings saved", "时钟设置已保存"],
+            Msg::CliThemeUsage => [
+                "usage: theme dark|light|contrast|auto | accent <rrggbb>|default",
+                "用法：theme dark|light|contrast|auto | accent <rrggbb>|default",
+            ],
+            Msg::CliThemeSaved => ["theme saved", "配色已保存"],
+            Msg::CliLcdUsage => [
+                "usage: lcd gamma 2.2|1.8|2.5|1.0 | contrast <0-27> | invert on|off | reset\r\n\
+                 lcd table pos|neg <28 hex digits>|default",
+                "用法：lcd gamma 2.2|1.8|2.5|1.0 | contrast <0-27> | invert on|off | reset\r\n\
+                 lcd table pos|neg <28 位十六进制>|default",
+            ],
+            Msg::CliLcdSaved => ["display tuning saved", "屏幕调校参数已保存"],
+            Msg::CliFpsUsage => ["usage: fps [on|off|<1-60>]", "用法：fps [on|off|<1-60>]"],
+            Msg::CliFpsSaved => ["target frame rate saved", "目标帧率已保存"],
+            Msg::CliSleepUsage => ["usage: sleep <seconds>", "用法：sleep <秒数>"],
+            Msg::CliWebhookUsage => [
+                "usage: webhook http://<host>[:<port>]/<path> | off | test | format json|cbor",
+                "用法：webhook http://<主机>[:<端口>]/<路径> | off | test | format json|cbor",
+            ],
+            Msg::CliWebhookNone => ["no webhook set", "未设置 webhook"],
+            Msg::CliWebhookSaved => ["webhook saved", "webhook 已保存"],
+            Msg::CliOtaUsage => [
+                "usage: ota http://<host>[:<port>]/<path> (signature at <path>.sig)",
+                "用法：ota http://<主机>[:<端口>]/<路径>（签名位于 <路径>.sig）",
+            ],
+            Msg::CliOtaStarted => [
+                "downloading firmware, the device reboots when it is verified",
+                "正在下载固件，校验通过后设备将重启",
+            ],
+            Msg::CliSyslogUsage => {
+                ["usage: syslog <host>[:<port>] | off", "用法：syslog <主机>[:<端口>] | off"]
+            }
+            Msg::CliSyslogNone => ["no syslog collector set", "未设置 syslog 收集器"],
+            Msg::CliMqttUsage => [
+                "usage: mqtt broker <host>[:<port>]|off | user <name> [<password>]|off\r\n\
+                 or: mqtt topic <prefix>|default | sas <base64 key>|off",
+                "用法：mqtt broker <主机>[:<端口>]|off | user <用户名> [<密码>]|off\r\n\
+                 或：mqtt topic <前缀>|default | sas <Base64 密钥>|off",
+            ],
+            Msg::CliMqttNone => [
+                "no MQTT broker set, discovered with mDNS",
+                "未设置 MQTT 代理，用 mDNS 查找",
+            ],
+            Msg::CliSyncUsage => [
+                "usage: sync <group> (up to 16 letters, digits, '-' or '_') | off",
+                "用法：sync <组名>（最多 16 个字母、数字、'-' 或 '_'）| off",
+            ],
+            Msg::CliSyncNone => ["settings sync is off", "设置同步未启用"],
+            Msg::CliThermostatUsage => [
+                "usage: thermostat source <sensor>.<quantity>|off | set <C> | mode heat|cool\r\n\
+                 or: thermostat band <C> (0.1-10) | min <on s> <off s>",
+                "用法：thermostat source <传感器>.<物理量>|off | set <C> | mode heat|cool\r\n\
+                 或：thermostat band <C>（0.1-10）| min <吸合秒数> <释放秒数>",
+            ],
+            Msg::CliThermostatNone => ["thermostat is off", "恒温控制器未启用"],
+            Msg::CliPidUsage => [
+                "usage: pid source <sensor>.<quantity>|off | set|kp|ki|kd <value> | period <ms>\r\n\
+                 values take up to 3 decimals, period 10-60000 ms",
+                "用法：pid source <传感器>.<物理量>|off | set|kp|ki|kd <数值> | period <毫秒>\r\n\
+                 数值最多 3 位小数，周期 10-60000 毫秒",
+            ],
+            Msg::CliPidNone => ["PID loop is off", "PID 回路未启用"],
+            Msg::CliPidNotRunning => [
+                "PID output not created, loop not running",
+                "未创建 PID 输出，回路没有运行",
+            ],
+            Msg::CliRelayUsage => [
+                "usage: relay <n> on|off|<minutes> (1-1440) | relay pin <n> <pin> [low]|off\r\n\
+                 pins: p0.6 p0.7 gpio4-10 gpio14-18 gpio38 gpio39 gpio47 gpio48",
+                "用法：relay <n> on|off|<分钟>（1-1440）| relay pin <n> <引脚> [low]|off\r\n\
+                 引脚：p0.6 p0.7 gpio4-10 gpio14-18 gpio38 gpio39 gpio47 gpio48",
+            ],
+            Msg::CliEspNowUsage => [
+                "usage: espnow [forget <id>|all]",
+                "用法：espnow [forget <id>|all]",
+            ],
+            Msg::CliEspNowNone => [
+                "no ESP-NOW peers paired (pair on the link test screen)",
+                "没有配对的 ESP-NOW 对端（在链路测试屏幕上配对）",
+            ],
+            Msg::CliEspNowNotPaired => ["peer not paired", "没有与该对端配对"],
+            Msg::CliBoardUsage => [
+                "usage: board [dnesp32s3|custom | pin <signal> <gpio> | has <name> on|off]\r\n\
+                 signals: sda scl sck mosi miso lcd_cs lcd_dc sd_cs led; \
+                 gpio: 1-18 21 38-42 47 48, all different\r\n\
+                 peripherals: xl9555 lcd sdcard bme280",
+                "用法：board [dnesp32s3|custom | pin <信号> <gpio> | has <名称> on|off]\r\n\
+                 信号：sda scl sck mosi miso lcd_cs lcd_dc sd_cs led；\
+                 gpio：1-18 21 38-42 47 48，互不相同\r\n\
+                 外设：xl9555 lcd sdcard bme280",
+            ],
+            Msg::CliBoardPortUsage => [
+                "usage: board port <name> <gpio>... [<param>] | board port <name> off\r\n\
+                 can: rx tx [kbit/s: 125 250 500 1000]\r\n\
+                 dmx: tx de\r\n\
+                 pwm: out\r\n\
+                 gps: tx rx [baud: 1200-115200]\r\n\
+                 rs485: tx rx de [baud: 1200-115200]\r\n\
+                 serial: tx rx [baud: 1200-115200]\r\n\
+                 rc: rx [baud: 100000 SBUS, 420000 CRSF]\r\n\
+                 lin: tx rx [baud: 1000-20000]\r\n\
+                 gpio: 1-18 21 38-42 47 48, not in the pin map or another port",
+                "用法：board port <名称> <gpio>... [<参数>] | board port <名称> off\r\n\
+                 can：rx tx [kbit/s：125 250 500 1000]\r\n\
+                 dmx：tx de\r\n\
+                 pwm：out\r\n\
+                 gps：tx rx [baud：1200-115200]\r\n\
+                 rs485：tx rx de [baud：1200-115200]\r\n\
+                 serial：tx rx [baud：1200-115200]\r\n\
+                 rc：rx [baud：100000 SBUS，420000 CRSF]\r\n\
+                 lin：tx rx [baud：1000-20000]\r\n\
+                 gpio：1-18 21 38-42 47 48，不能与引脚表或其他接口重复",
+            ],
+            Msg::CliPowerUsage => [
+                "usage: power [stagger <ms> (0-2000, 0 = off) | budget <mA> (50-2000)]",
+                "用法：power [stagger <毫秒>（0-2000，0 表示不错开）| budget <mA>（50-2000）]",
+            ],
+            Msg::CliModbusUsage => [
+                "usage: modbus [unit <address> (1-247)]",
+                "用法：modbus [unit <地址>（1-247）]",
+            ],
+            Msg::CliServiceUsage => [
+                "usage: service [<name> start|stop]\r\nservices: datalog modbus snmp peersync",
+                "用法：service [<名称> start|stop]\r\n服务：datalog modbus snmp peersync",
+            ],
+            Msg::CliSerialUsage => ["usage: serial [tap on|off]", "用法：serial [tap on|off]"],
+            Msg::CliBridgeUsage => [
+                "usage: bridge off | bridge serial|rs485 server <port> | \
+                 bridge serial|rs485 client <ipv4>:<port> (after reboot)",
+                "用法：bridge off | bridge serial|rs485 server <端口> | \
+                 bridge serial|rs485 client <IPv4 地址>:<端口>（重启后生效）",
+            ],
+            Msg::CliPresenceUsage => [
+                "usage: presence [on|off | threshold <dB^2> (0.5-50)]",
+                "用法：presence [on|off | threshold <dB^2>（0.5-50）]",
+            ],
+            Msg::CliScheduleUsage => [
+                "usage: schedule <min> <hour> <day> <month> <weekday> <action>[; ...] | off\r\n\
+                 actions: backlight on|off, beep, notify, publish, reboot, relay <n> on|off|<min>",
+                "用法：schedule <分> <时> <日> <月> <星期> <动作>[; ...] | off\r\n\
+                 动作：backlight on|off、beep、notify、publish、reboot、relay <n> on|off|<分钟>",
+            ],
+            Msg::CliScheduleNone => ["no schedule set", "未设置定时任务"],
+            Msg::CliScheduleSaved => ["schedule saved", "定时任务已保存"],
+            Msg::CliRulesUsage => [
+                "usage: rules [add if <condition> then <action>[, ...] | del <n> | clear]",
+                "用法：rules [add if <条件> then <动作>[, ...] | del <n> | clear]",
+            ],
+            Msg::CliRulesNone => ["no automation rules", "没有自动化规则"],
+            Msg::CliRulesInvalid => ["invalid rule", "规则无效"],
+            Msg::CliRulesSaveFailed => ["failed to save rules", "保存规则失败"],
+            Msg::CliRulesSaved => ["rules saved", "规则已保存"],
+            Msg::CliSaved => ["saved, reboot to apply", "已保存，重启后生效"],
+            Msg::CliSaveFailed => ["failed to save settings", "保存设置失败"],
+        }
+    }
+
+    /// 指定语言的译文
+    pub const fn in_language(self, language: Language) -> &'static str {
+        self.translations()[language as usize]
+    }
+}
+
+/// 设置中选择的语言
+pub fn current() -> Language {
+    Language::from_u8(settings::get().language)
+}
+
+/// 当前语言的译文
+pub fn tr(msg: Msg) -> &'static str {
+    translate(msg, current())
+}
+
+/// 用于 LCD 显示的译文
+///
+/// 当前语言在 LCD 上没有可用字体时回退为英文；全角字符转换为 [assets::font] 中的字形
+pub fn lcd(msg: Msg) -> &'static str {
+    let language = current();
+    if language.has_lcd_font() {
+        assets::cjk_text(translate(msg, language))
+    } else {
+        translate(msg, Language::English)
+    }
+}
+
+/// 用小字体（`FONT_6X10`）显示的屏幕文本，小字体只有 ASCII 字形，始终为英文
+#[cfg(feature = "ui")]
+pub fn lcd_small(msg: Msg) -> &'static str {
+    translate(msg, Language::English)
+}
+
+/// 指定语言的译文，TF 卡资源包中有对应条目时优先使用（见 [crate::assets]）
+fn translate(msg: Msg, language: Language) -> &'static str {
+    assets::text(language, msg).unwrap_or_else(|| msg.in_language(language))
+}
@@ -1,3 +1,4 @@
+use crate::error::{Context, Error};
 use core::cell::RefCell;
 use critical_section::Mutex;
 use esp_hal::gpio::interconnect::PeripheralOutput;
@@ -39,30 +40,20 @@ pub async fn init(
 /// 通过闭包访问 I2C 实例
 ///
 /// # 参数
+/// * `op` - 操作说明，出错时记录在错误中
 /// * `f` - 闭包函数，接受 I2C 实例作为参数
-pub fn with_i2c<F, R>(f: F) -> Result<R, I2cError>
-where
-    F: FnOnce(&mut I2c<Blocking>) -> Result<R, I2cError>,
-{
-    critical_section::with(|cs| {
-        let mut i2c_ref = I2C.borrow_ref_mut(cs);
-        let mut i2c = i2c_ref.as_mut().unwrap();
-        f(&mut i2c)
-    })
-}
-
-/// 通过闭包访问 I2C 实例（无返回值版本）
 ///
-/// # 参数
-/// * `f` - 闭包函数，接受 I2C 实例作为参数
-#[allow(unused)]
-pub fn with_i2c_mut<F>(f: F)
+/// # 返回
+/// 总线尚未初始化时返回 [Error::NotInitialized]，闭包返回的总线错误包装为 [Error::I2c]
+pub fn with_i2c<F, R>(op: &'static str, f: F) -> Result<R, Error>
 where
-    F: FnOnce(&mut I2c<Blocking>),
+    F: FnOnce(&mut I2c<Blocking>) -> Result<R, I2cError>,
 {
+    #[cfg(feature = "fault-injection")]
+    crate::fault::inject_i2c().context(op)?;
     critical_section::with(|cs| {
         let mut i2c_ref = I2C.borrow_ref_mut(cs);
-        let mut i2c = i2c_ref.as_mut().unwrap();
-        f(&mut i2c);
+        let i2c = i2c_ref.as_mut().ok_or(Error::NotInitialized { op })?;
+        f(i2c).context(op)
     })
 }
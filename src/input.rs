@@ -0,0 +1,65 @@
+//! 按键输入事件
+//!
+//! [crate::xl9555::read_keys] 检测到 KEY0-KEY3 按下时发布 [Key] 事件，
+//! 界面代码通过 [subscribe] 接收。
+//!
+//! 界面需要独占按键时调用 [set_captured]，此时按键的默认功能
+//! （例如 KEY1 切换背光）不再生效。
+
+use core::sync::atomic::{AtomicBool, Ordering};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::pubsub::PubSubChannel;
+#[cfg(feature = "ui")]
+use embassy_sync::pubsub::Subscriber;
+
+/// 事件队列长度
+const QUEUE_LEN: usize = 8;
+
+/// 最多同时存在的订阅者数量
+const MAX_SUBSCRIBERS: usize = 4;
+
+/// 按键
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Key {
+    Key0,
+    Key1,
+    Key2,
+    Key3,
+}
+
+/// 按键事件订阅者
+#[cfg(feature = "ui")]
+pub type KeySubscriber =
+    Subscriber<'static, CriticalSectionRawMutex, Key, QUEUE_LEN, MAX_SUBSCRIBERS, 1>;
+
+static KEY_EVENTS: PubSubChannel<CriticalSectionRawMutex, Key, QUEUE_LEN, MAX_SUBSCRIBERS, 1> =
+    PubSubChannel::new();
+
+static CAPTURED: AtomicBool = AtomicBool::new(false);
+
+/// 发布按键按下事件
+///
+/// 订阅者处理不及时时丢弃最早的事件
+pub fn publish(key: Key) {
+    KEY_EVENTS.immediate_publisher().publish_immediate(key);
+}
+
+/// 订阅按键事件
+///
+/// # 返回
+/// 订阅者数量已满时返回 None
+#[cfg(feature = "ui")]
+pub fn subscribe() -> Option<KeySubscriber> {
+    KEY_EVENTS.subscriber().ok()
+}
+
+/// 设置界面是否独占按键
+#[cfg(feature = "ui")]
+pub fn set_captured(captured: bool) {
+    CAPTURED.store(captured, Ordering::Relaxed);
+}
+
+/// 界面是否独占按键
+pub fn is_captured() -> bool {
+    CAPTURED.load(Ordering::Relaxed)
+}
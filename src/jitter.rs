@@ -0,0 +1,154 @@
+//! 周期任务调度抖动测量
+//!
+//! 周期任务用 [Monitor::wait] 代替 `Timer::after`，每次唤醒时记录实际唤醒时间
+//! 比期望时间晚了多少。统计按任务汇总，由 [report_task] 定期打印每个任务
+//! 在统计窗口内的最大和平均延迟，也可通过 HTTP `GET /stats/jitter` 查看。
+//!
+//! 某个任务的延迟持续偏大，说明同一核心上有其他代码长时间阻塞了执行器
+//! （例如阻塞式 SPI 填充或传感器读取）。
+
+use alloc::string::String;
+use core::cell::RefCell;
+use core::fmt::Write;
+use critical_section::Mutex;
+use defmt::{info, warn};
+use embassy_time::{Duration, Instant, Timer};
+
+/// 最多可登记的周期任务数量
+const MAX_MONITORS: usize = 8;
+
+/// 统计窗口长度
+const REPORT_PERIOD: Duration = Duration::from_secs(60);
+
+/// 单个任务的延迟统计
+#[derive(Debug, Clone, Copy)]
+struct Stats {
+    name: &'static str,
+    period_ms: u64,
+    /// 窗口内唤醒次数
+    count: u32,
+    /// 窗口内延迟总和（微秒）
+    total_late_us: u64,
+    /// 窗口内最大延迟（微秒）
+    max_late_us: u64,
+    /// 启动以来的最大延迟（微秒）
+    peak_late_us: u64,
+}
+
+static STATS: Mutex<RefCell<[Option<Stats>; MAX_MONITORS]>> =
+    Mutex::new(RefCell::new([None; MAX_MONITORS]));
+
+/// 周期任务监视器
+pub struct Monitor {
+    slot: Option<usize>,
+    period: Duration,
+}
+
+impl Monitor {
+    /// 登记一个周期任务
+    ///
+    /// 登记已满时仍可正常等待，只是不再记录统计
+    ///
+    /// # 参数
+    /// * `name` - 任务名
+    /// * `period` - 任务周期
+    pub fn new(name: &'static str, period: Duration) -> Self {
+        let slot = critical_section::with(|cs| {
+            let mut stats = STATS.borrow_ref_mut(cs);
+            let slot = stats.iter().position(|entry| entry.is_none())?;
+            stats[slot] = Some(Stats {
+                name,
+                period_ms: period.as_millis(),
+                count: 0,
+                total_late_us: 0,
+                max_late_us: 0,
+                peak_late_us: 0,
+            });
+            Some(slot)
+        });
+        if slot.is_none() {
+            warn!("Too many jitter monitors, not tracking {}", name);
+        }
+        Monitor { slot, period }
+    }
+
+    /// 等待一个周期并记录唤醒延迟
+    pub async fn wait(&mut self) {
+        let deadline = Instant::now() + self.period;
+        Timer::at(deadline).await;
+        let late = Instant::now().saturating_duration_since(deadline);
+        self.record(late);
+    }
+
+    /// 记录一次唤醒延迟
+    ///
+    /// 用于不方便改用 [Monitor::wait] 的任务（例如使用 `Ticker` 的任务）
+    ///
+    /// # 参数
+    /// * `late` - 实际唤醒时间比期望时间晚了多少
+    pub fn record(&mut self, late: Duration) {
+        let Some(slot) = self.slot else {
+            return;
+        };
+        let late_us = late.as_micros();
+        critical_section::with(|cs| {
+            if let Some(stats) = STATS.borrow_ref_mut(cs)[slot].as_mut() {
+                stats.count += 1;
+                stats.total_late_us += late_us;
+                stats.max_late_us = stats.max_late_us.max(late_us);
+                stats.peak_late_us = stats.peak_late_us.max(late_us);
+            }
+        });
+    }
+}
+
+/// 取出当前统计并开始新的统计窗口
+fn take_window() -> [Option<Stats>; MAX_MONITORS] {
+    critical_section::with(|cs| {
+        let mut stats = STATS.borrow_ref_mut(cs);
+        let snapshot = *stats;
+        for entry in stats.iter_mut().flatten() {
+            entry.count = 0;
+            entry.total_late_us = 0;
+            entry.max_late_us = 0;
+        }
+        snapshot
+    })
+}
+
+/// 定期打印各任务的调度延迟
+#[embassy_executor::task]
+pub async fn report_task() {
+    loop {
+        Timer::after(REPORT_PERIOD).await;
+        for stats in take_window().iter().flatten() {
+            let avg = stats.total_late_us / (stats.count.max(1) as u64);
+            info!(
+                "Jitter {}: period {} ms, {} wakeups, avg {} us, max {} us, peak {} us",
+                stats.name,
+                stats.period_ms,
+                stats.count,
+                avg,
+                stats.max_late_us,
+                stats.peak_late_us
+            );
+        }
+    }
+}
+
+/// 将启动以来的统计格式化为文本
+///
+/// 不会重置统计窗口
+pub fn format_report() -> String {
+    let stats = critical_section::with(|cs| *STATS.borrow_ref(cs));
+    let mut text = String::new();
+    for stats in stats.iter().flatten() {
+        writeln!(
+            text,
+            "{}: period_ms={} window_max_us={} peak_us={}",
+            stats.name, stats.period_ms, stats.max_late_us, stats.peak_late_us
+        )
+        .ok();
+    }
+    text
+}
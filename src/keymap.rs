@@ -0,0 +1,86 @@
+//! 可配置的按键映射
+//!
+//! 应用屏幕不直接判断 KEY0-KEY3，而是通过 [action] 把按键转换为 [Action]，
+//! 映射保存在设置的 `keymap` 字段中，可用命令行 `keymap` 修改，立即生效。
+//!
+//! 每个按键只对应一个动作：把按键分配给新动作时，原来使用该按键的动作换用新动作的旧按键。
+
+use crate::input::Key;
+use crate::settings;
+
+/// 应用屏幕中的动作
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Action {
+    /// 开始/停止
+    StartStop,
+    /// 计圈，停止时为清零
+    LapReset,
+    /// 列表上移
+    ScrollUp,
+    /// 列表下移
+    ScrollDown,
+}
+
+impl Action {
+    /// 所有动作，下标与设置中 `keymap` 数组的下标一致
+    pub const ALL: [Action; 4] = [
+        Action::StartStop,
+        Action::LapReset,
+        Action::ScrollUp,
+        Action::ScrollDown,
+    ];
+
+    /// 动作名称，用于命令行参数
+    pub const fn name(self) -> &'static str {
+        match self {
+            Action::StartStop => "start",
+            Action::LapReset => "lap",
+            Action::ScrollUp => "up",
+            Action::ScrollDown => "down",
+        }
+    }
+}
+
+/// 所有按键，下标即设置中保存的按键编码
+pub const KEYS: [Key; 4] = [Key::Key0, Key::Key1, Key::Key2, Key::Key3];
+
+/// 默认映射（与设置向导一致：KEY2 确认，KEY3 返回，KEY0/KEY1 移动）
+pub const DEFAULT: [u8; 4] = [2, 3, 1, 0];
+
+/// 按键名称，用于命令行参数
+pub const fn key_name(key: Key) -> &'static str {
+    match key {
+        Key::Key0 => "key0",
+        Key::Key1 => "key1",
+        Key::Key2 => "key2",
+        Key::Key3 => "key3",
+    }
+}
+
+/// 动作当前对应的按键
+pub fn key_for(action: Action) -> Key {
+    let code = settings::get().keymap[action as usize];
+    KEYS.get(code as usize).copied().unwrap_or(KEYS[DEFAULT[action as usize] as usize])
+}
+
+/// 按键当前对应的动作
+#[cfg(feature = "ui")]
+pub fn action(key: Key) -> Option<Action> {
+    Action::ALL.into_iter().find(|&action| key_for(action) == key)
+}
+
+/// 把按键分配给动作（不会自动保存）
+///
+/// 原来使用该按键的动作换用 `action` 的旧按键
+pub fn assign(action: Action, key: Key) {
+    let code = KEYS.iter().position(|&k| k == key).unwrap_or_default() as u8;
+    settings::update(|s| {
+        let old = s.keymap[action as usize];
+        for slot in s.keymap.iter_mut() {
+            if *slot == code {
+                *slot = old;
+            }
+        }
+        s.keymap[action as usize] = code;
+    });
+}
@@ -0,0 +1,412 @@
+//! 板载 LCD
+//!
+//! ATK-MD0240 模块的复位（SLCD_RST，P1.2）和背光电源（SLCD_PWR，P1.3）由 XL9555 控制，
+//! 控制器（[crate::st7789]）在 SPI 总线上。[Lcd] 持有控制器，把两边的操作合在一起，
+//! 面板从上电、开关背光、睡眠到绘制都通过这一个类型完成。
+//!
+//! 复位后显存的内容是随机的，而控制器初始化的最后一步就会开启显示，如果此时背光已经点亮，
+//! 会先闪过一帧花屏。[Lcd::init] 因此按以下顺序上电：
+//!
+//! 1. 关闭背光
+//! 2. 硬件复位控制器
+//! 3. 识别并初始化控制器，应用面板调校参数（见 [crate::tuning]）
+//! 4. 把整个显存清为黑色
+//! 5. 最后打开背光
+//!
+//! 清屏在 10MHz 的 SPI 上约需 125ms，背光因此晚亮一点，换来的是第一眼看到的就是黑屏。
+//!
+//! [Lcd] 实现了 [DrawTarget]，各应用直接在上面绘制；`blit` 等驱动层接口通过解引用调用。
+//! 多层叠加的全屏画面用 [Lcd::render_strips] 按条带合成后整块发送，不需要整帧帧缓冲区；
+//! 有 PSRAM 时也可以在整帧帧缓冲区（见 [crate::framebuffer]）上绘制，
+//! 用 [Lcd::flush_framebuffer] 只发送改变过的区域。
+//!
+//! 单色填充（[Lcd::fill_rectangle]、[Lcd::fill_screen] 以及 `fill_solid`、`clear`）使用
+//! [FILL_BUF_LEN] 字节的静态缓冲区，而不是驱动默认的 2KB 栈缓冲区。缓冲区与共享总线的
+//! DMA 缓冲区一样大（见 [crate::spi]），整屏填充只需 5 次传输，每次都是一整块 DMA 传输，
+//! 省去了每次传输加锁、片选和启动 DMA 的固定开销。传输本身仍受 SPI 时钟限制。
+//!
+//! [Lcd::fill_screen]、[Lcd::flush] 和 [Lcd::flush_framebuffer] 是异步的，经异步驱动
+//! （见 [crate::st7789]）启动 DMA 后让出执行器，由 DMA 完成中断唤醒，整屏清除约 125ms
+//! 期间其他任务照常运行。其余的绘制是阻塞的，在当前核上等待传输完成但不关中断。
+//!
+//! 异步传输交给 PRO_CPU 高优先级执行器（见 [crate::multicore]）上的 [transfer_task] 完成：
+//! DMA 完成中断也在 PRO_CPU 上，每块传输结束后立即在中断上下文中启动下一块，
+//! 不必等 WiFi 收发或 TF 卡写入让出普通执行器，动画因此不会随网络和存储负载卡顿。
+//! 屏幕任务借出控制器和数据后一直等到传输结束；任务尚未启动时（例如上电清屏）就地传输。
+//! 总线上的异步传输都来自 LCD 自己，持有 [Lcd] 时不会有挂起的异步传输，
+//! 阻塞绘制不需要先等待总线空闲。
+//!
+//! 背光状态记录在 [crate::xl9555] 中，按键和定时任务不持有 LCD 也可以开关背光。
+
+use crate::error::{Context, Error};
+use crate::power::{self, Load};
+use crate::spi::{self, SpiDevice};
+use crate::st7789::{self, LcdSpi, St7789};
+use crate::{tuning, xl9555};
+use core::convert::Infallible;
+use core::ops::{Deref, DerefMut};
+use core::sync::atomic::{AtomicBool, Ordering};
+use defmt::{info, warn};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::signal::Signal;
+use embassy_time::Delay;
+use embedded_graphics::pixelcolor::Rgb565;
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::Rectangle;
+use esp_hal::gpio::Output;
+use esp_hal::spi::Error as SpiError;
+use static_cell::StaticCell;
+use ui::framebuffer::{self, Framebuffer};
+use ui::strip::{self, Strip};
+
+/// 单色填充缓冲区大小（字节），与共享总线的 DMA 缓冲区相同，每块正好一次 DMA 传输
+pub const FILL_BUF_LEN: usize = spi::DMA_BUFFER_SIZE;
+
+static FILL_BUF: StaticCell<[u8; FILL_BUF_LEN]> = StaticCell::new();
+
+/// [transfer_task] 是否已在运行
+static OFFLOADED: AtomicBool = AtomicBool::new(false);
+
+/// 交给 [transfer_task] 的传输
+static REQUEST: Signal<CriticalSectionRawMutex, Request> = Signal::new();
+
+/// [transfer_task] 完成传输后的结果
+static DONE: Signal<CriticalSectionRawMutex, Result<(), SpiError>> = Signal::new();
+
+/// 一次异步传输，指针借自请求方的 [Lcd] 和参数
+enum Job {
+    /// 用单一颜色填充整个屏幕，`buf` 为填充缓冲区
+    Fill { color: Rgb565, buf: *mut [u8] },
+    /// 把像素数据写入矩形区域
+    Flush {
+        x: u16,
+        y: u16,
+        w: u16,
+        h: u16,
+        pixels: *const [u8],
+    },
+}
+
+/// 交给 [transfer_task] 的请求
+struct Request {
+    panel: *mut St7789,
+    job: Job,
+}
+
+// SAFETY: 请求方在传输结束前一直等待，不会访问或释放借出的数据，见 [run]
+unsafe impl Send for Request {}
+
+/// 请求方在传输结束前被丢弃时，忙等 [transfer_task] 完成，借出的数据在此之前保持有效
+///
+/// 请求方在 APP_CPU 上时由另一个核完成传输；在 PRO_CPU 的普通执行器上时高优先级执行器会抢占忙等
+struct Pending {
+    /// 传输尚未结束
+    waiting: bool,
+}
+
+impl Drop for Pending {
+    fn drop(&mut self) {
+        if !self.waiting {
+            return;
+        }
+        while !DONE.signaled() {
+            core::hint::spin_loop();
+        }
+        DONE.reset();
+    }
+}
+
+/// 执行一次异步传输
+///
+/// # Safety
+///
+/// `job` 中的指针在传输结束前必须有效，且没有其他访问
+async unsafe fn transfer(panel: &mut St7789, job: Job) -> Result<(), SpiError> {
+    match job {
+        Job::Fill { color, buf } => {
+            // SAFETY: 由调用方保证
+            let buf = unsafe { &mut *buf };
+            panel.as_async().fill_screen(color, buf).await
+        }
+        Job::Flush { x, y, w, h, pixels } => {
+            // SAFETY: 由调用方保证
+            let pixels = unsafe { &*pixels };
+            panel.as_async().flush(x, y, w, h, pixels).await
+        }
+    }
+}
+
+/// 把异步传输交给 [transfer_task] 并等待完成，任务未启动时就地传输
+async fn run(panel: &mut St7789, job: Job) -> Result<(), SpiError> {
+    if !OFFLOADED.load(Ordering::Acquire) {
+        // SAFETY: 指针借自调用方，传输结束前调用方一直等待
+        return unsafe { transfer(panel, job) }.await;
+    }
+    DONE.reset();
+    REQUEST.signal(Request { panel, job });
+    // 从这里起被丢弃时忙等传输结束
+    let mut pending = Pending { waiting: true };
+    let result = DONE.wait().await;
+    pending.waiting = false;
+    result
+}
+
+/// LCD 异步传输任务，由 [crate::multicore::spawn_realtime] 生成到高优先级执行器
+///
+/// 只在等待总线和 DMA 完成时运行，每块传输之间的工作很少，不会拖慢普通任务
+#[embassy_executor::task]
+pub async fn transfer_task() {
+    OFFLOADED.store(true, Ordering::Release);
+    loop {
+        let Request { panel, job } = REQUEST.wait().await;
+        // SAFETY: 请求方在 DONE 之前一直等待，被丢弃时在 [Pending] 中忙等，借出的数据保持有效
+        let result = unsafe { transfer(&mut *panel, job) }.await;
+        DONE.signal(result);
+    }
+}
+
+/// 板载 LCD：ST7789 控制器及其由 XL9555 控制的复位和背光
+pub struct Lcd {
+    panel: St7789,
+    /// 控制器是否处于睡眠模式
+    asleep: bool,
+    /// 单色填充缓冲区
+    fill_buf: &'static mut [u8; FILL_BUF_LEN],
+}
+
+impl Lcd {
+    /// 按顺序完成 LCD 上电，背光在显存清空后才打开，并与其他负载错开（见 [crate::power]）
+    ///
+    /// 背光和复位引脚的操作失败只记录警告：复位失败时控制器可能仍保持上电前的状态，
+    /// 背光打不开时屏幕内容依然正确
+    ///
+    /// # 参数
+    /// * `spi` - 共享 SPI 总线上的 LCD 设备
+    /// * `dc` - LCD 数据/命令选择引脚
+    ///
+    /// # 返回
+    /// 已初始化并清屏的 LCD，控制器初始化或清屏失败时返回错误
+    pub async fn init(spi: SpiDevice, dc: Output<'static>) -> Result<Lcd, Error> {
+        let mut lcd = Lcd {
+            panel: St7789::new(LcdSpi::new(spi), dc),
+            asleep: false,
+            fill_buf: FILL_BUF.init([0; FILL_BUF_LEN]),
+        };
+        // XL9555 初始化时已拉低所有输出，这里再关一次，同时让记录的背光状态与引脚一致
+        if let Err(err) = lcd.set_backlight(false).await {
+            warn!("Failed to turn off LCD backlight: {}", err);
+        }
+
+        if let Err(err) = xl9555::init_atk_md0240().await {
+            warn!("Failed to reset LCD: {}", err);
+        }
+
+        st7789::init(&mut lcd.panel).await.context("LCD init")?;
+        // 失败时保持初始化的默认参数
+        if let Err(err) = tuning::apply(&mut lcd.panel, &tuning::current()) {
+            warn!("Failed to apply display tuning: {}", err);
+        }
+        lcd.fill_screen(Rgb565::BLACK).await.context("LCD clear")?;
+
+        power::power_up(Load::Backlight).await;
+        match lcd.set_backlight(true).await {
+            Ok(()) => info!("LCD ready, backlight on"),
+            Err(err) => warn!("Failed to turn on LCD backlight: {}", err),
+        }
+        Ok(lcd)
+    }
+
+    /// 开关背光
+    ///
+    /// 通过 XL9555 的 P1.3 引脚控制 ATK-MD0240 模块的 PWR 引脚，没有 PWM 调光
+    pub async fn set_backlight(&mut self, on: bool) -> Result<(), Error> {
+        xl9555::set_lcd_backlight(on).await
+    }
+
+    /// 背光当前是否开启，包括其他任务直接通过 [crate::xl9555] 做的改变
+    pub fn is_backlight_on(&self) -> bool {
+        xl9555::lcd_backlight()
+    }
+
+    /// 控制器是否处于睡眠模式
+    pub fn is_asleep(&self) -> bool {
+        self.asleep
+    }
+
+    /// 让控制器进入或退出睡眠模式
+    ///
+    /// 睡眠前先关闭背光，唤醒并开启显示后再打开背光，避免看到控制器关闭显示时的画面。
+    /// 睡眠期间显存内容保持不变，仍然可以写入，唤醒后直接显示最新内容。
+    ///
+    /// # 参数
+    /// * `sleep` - true 表示进入睡眠，false 表示唤醒
+    pub async fn sleep(&mut self, sleep: bool) -> Result<(), Error> {
+        if sleep == self.asleep {
+            return Ok(());
+        }
+        if sleep {
+            self.set_backlight(false).await?;
+        }
+        self.panel
+            .set_sleep(sleep, &mut Delay)
+            .await
+            .context("LCD sleep")?;
+        self.asleep = sleep;
+        if !sleep {
+            self.set_backlight(true).await?;
+        }
+        Ok(())
+    }
+
+    /// 用单一颜色填充矩形区域，按 [FILL_BUF_LEN] 字节分块传输
+    ///
+    /// 区域超出屏幕的部分会被裁剪
+    ///
+    /// # 参数
+    /// * `x`, `y` - 左上角坐标
+    /// * `w`, `h` - 宽度和高度
+    /// * `color` - 填充颜色
+    pub fn fill_rectangle(
+        &mut self,
+        x: u16,
+        y: u16,
+        w: u16,
+        h: u16,
+        color: Rgb565,
+    ) -> Result<(), SpiError> {
+        self.panel
+            .fill_rectangle_with(x, y, w, h, color, self.fill_buf.as_mut_slice())
+    }
+
+    /// 用单一颜色填充整个屏幕，每次传输之间让出执行器
+    pub async fn fill_screen(&mut self, color: Rgb565) -> Result<(), SpiError> {
+        let buf = self.fill_buf.as_mut_slice();
+        run(&mut self.panel, Job::Fill { color, buf }).await
+    }
+
+    /// 把一块 RGB565 像素数据写入矩形区域，传输完成后让出执行器
+    ///
+    /// 参数和限制与驱动的 `blit` 相同，用于刷新绘制好的条带等大块数据
+    ///
+    /// # 参数
+    /// * `x`, `y` - 左上角坐标，区域必须完整位于屏幕内，否则忽略
+    /// * `w`, `h` - 宽度和高度
+    /// * `pixels` - 按行排列的像素，每像素 2 字节（大端），长度至少为 `w * h * 2`
+    pub async fn flush(
+        &mut self,
+        x: u16,
+        y: u16,
+        w: u16,
+        h: u16,
+        pixels: &[u8],
+    ) -> Result<(), SpiError> {
+        run(&mut self.panel, Job::Flush { x, y, w, h, pixels }).await
+    }
+
+    /// 把帧缓冲区中改变过的区域发送到 LCD，按 [FILL_BUF_LEN] 字节分块，每块传输后让出执行器
+    ///
+    /// 与屏幕等宽的块直接从帧缓冲区发送，较窄的块先逐行复制到填充缓冲区。
+    /// 发送失败时把整个屏幕重新标记为已改变，下次完整发送
+    ///
+    /// # 参数
+    /// * `framebuffer` - 与屏幕同样大小的帧缓冲区
+    pub async fn flush_framebuffer(
+        &mut self,
+        framebuffer: &mut Framebuffer<'_>,
+    ) -> Result<(), SpiError> {
+        for area in framebuffer.take_dirty() {
+            for chunk in framebuffer::chunks(area, FILL_BUF_LEN) {
+                let Some(pixels) = framebuffer.window(&chunk, self.fill_buf.as_mut_slice()) else {
+                    continue;
+                };
+                let (x, y) = (chunk.top_left.x as u16, chunk.top_left.y as u16);
+                let (w, h) = (chunk.size.width as u16, chunk.size.height as u16);
+                let job = Job::Flush { x, y, w, h, pixels };
+                if let Err(err) = run(&mut self.panel, job).await {
+                    framebuffer.invalidate();
+                    return Err(err);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// 按条带绘制整个屏幕，见 [ui::strip]
+    ///
+    /// 每条绘制完成后用 `blit` 整块发送，经共享 SPI 总线的 DMA 缓冲区阻塞传输，
+    /// 发送期间不能同时绘制下一条
+    ///
+    /// # 参数
+    /// * `buf` - 条带缓冲区，`WIDTH * 行数 * 2` 字节，16 行约 10KB，内部 SRAM 即可容纳
+    /// * `scene` - 画面的绘制函数，使用整个屏幕的坐标，每一条调用一次
+    pub fn render_strips<S>(&mut self, buf: &mut [u8], scene: S) -> Result<(), SpiError>
+    where
+        S: FnMut(&mut Strip<'_>) -> Result<(), Infallible>,
+    {
+        let screen = Size::new(st7789::WIDTH as u32, st7789::HEIGHT as u32);
+        strip::render(buf, screen, scene, |area, pixels| {
+            let (y, rows) = (area.top_left.y as u16, area.size.height as u16);
+            self.panel.blit(0, y, st7789::WIDTH, rows, pixels)
+        })
+    }
+
+    /// 绘制目标
+    ///
+    /// 用于按 [St7789] 类型实现的接口（例如状态页面），其他场合直接在 [Lcd] 上绘制即可
+    pub fn draw(&mut self) -> &mut St7789 {
+        &mut self.panel
+    }
+}
+
+impl Deref for Lcd {
+    type Target = St7789;
+
+    fn deref(&self) -> &St7789 {
+        &self.panel
+    }
+}
+
+impl DerefMut for Lcd {
+    fn deref_mut(&mut self) -> &mut St7789 {
+        &mut self.panel
+    }
+}
+
+impl OriginDimensions for Lcd {
+    fn size(&self) -> Size {
+        self.panel.size()
+    }
+}
+
+impl DrawTarget for Lcd {
+    type Color = Rgb565;
+    type Error = SpiError;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        self.panel.draw_iter(pixels)
+    }
+
+    fn fill_contiguous<I>(&mut self, area: &Rectangle, colors: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Self::Color>,
+    {
+        self.panel.fill_contiguous(area, colors)
+    }
+
+    fn fill_solid(&mut self, area: &Rectangle, color: Self::Color) -> Result<(), Self::Error> {
+        let area = area.intersection(&self.bounding_box());
+        if area.is_zero_sized() {
+            return Ok(());
+        }
+        let (x, y) = (area.top_left.x as u16, area.top_left.y as u16);
+        let (w, h) = (area.size.width as u16, area.size.height as u16);
+        self.fill_rectangle(x, y, w, h, color)
+    }
+
+    fn clear(&mut self, color: Self::Color) -> Result<(), Self::Error> {
+        self.fill_rectangle(0, 0, st7789::WIDTH, st7789::HEIGHT, color)
+    }
+}
@@ -19,3 +19,21 @@ pub async fn led0_toggle() {
         led0.toggle();
     }
 }
+
+/// 设置 LED0 状态（低电平点亮）
+///
+/// # 参数
+/// * `on` - true 表示点亮
+pub async fn led0_set(on: bool) {
+    if let Some(led0) = LED0.lock().await.as_mut() {
+        led0.set_level((!on).into());
+    }
+}
+
+/// LED0 当前是否点亮
+pub async fn led0_is_on() -> bool {
+    match LED0.lock().await.as_ref() {
+        Some(led0) => led0.is_set_low(),
+        None => false,
+    }
+}
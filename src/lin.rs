@@ -0,0 +1,432 @@
+//! LIN 2.x 主节点
+//!
+//! 通过 UART 和 LIN 收发器（例如 TJA1021）访问 LIN 从节点。每个帧由主节点发出帧头：
+//!
+//! - break：至少 13 个位时间的低电平。与 [crate::dmx] 相同，临时降低波特率发送一个 0x00
+//!   产生（9 个低电平位 × 13/9）
+//! - 同步字节 0x55
+//! - 受保护 ID（PID）：6 位帧 ID 加 2 位奇偶校验
+//!
+//! 随后由主节点（[Lin::publish]）或从节点（[Lin::subscribe]）发送 1 到 8 字节数据和校验和。
+//! 校验和分经典（只含数据，LIN 1.x 和诊断帧）和增强（含 PID，LIN 2.x）两种，见 [Checksum]，
+//! 与受保护 ID 一起在 [proto::lin] 中计算。
+//!
+//! LIN 是单线总线，收发器会把发送的字节回送到 RX，发送后读回比较，不一致说明总线冲突。
+//!
+//! [lin_task] 按调度表（[ScheduleEntry]）循环执行帧，主节点发送的数据和从节点的应答
+//! 都保存在按帧 ID 索引的表中，通过 [set_frame_data] 和 [frame_data] 访问。
+//!
+//! 应用启动时按 `board port lin <tx> <rx> [baud]` 的接线创建主节点，
+//! 调度表和校验和类型保存在设置中（见 [saved_schedule] 和 [saved_checksum]），
+//! 用命令行的 `lin` 命令修改。
+//!
+//! 也可以由应用提供调度表：
+//!
+//! ```ignore
+//! static SCHEDULE: [ScheduleEntry; 2] = [
+//!     ScheduleEntry {
+//!         id: 0x10,
+//!         direction: Direction::Publish,
+//!         len: 2,
+//!         slot: Duration::from_millis(10),
+//!     },
+//!     ScheduleEntry {
+//!         id: 0x20,
+//!         direction: Direction::Subscribe,
+//!         len: 8,
+//!         slot: Duration::from_millis(20),
+//!     },
+//! ];
+//! lin::set_frame_data(0x10, &[0x01, 0x00])?;
+//! let lin = Lin::new(peripherals.UART1, tx, rx, 19_200, Checksum::Enhanced)?;
+//! spawner.spawn(lin::lin_task(lin, &SCHEDULE))?;
+//! ```
+
+use core::cell::RefCell;
+use core::ops::RangeInclusive;
+use critical_section::Mutex;
+use defmt::{info, warn};
+use embassy_time::{Duration, Instant, Timer, with_timeout};
+use esp_hal::Async;
+use esp_hal::gpio::interconnect::{PeripheralInput, PeripheralOutput};
+use esp_hal::uart::{self, Config as UartConfig, Uart, UartRx, UartTx};
+use heapless::Vec;
+use proto::lin::{SYNC, protected_id};
+use static_cell::StaticCell;
+
+pub use proto::lin::Checksum;
+
+/// 帧 ID 数量（6 位）
+pub const FRAME_IDS: usize = 64;
+
+/// 数据最大长度
+pub const MAX_DATA_LEN: usize = 8;
+
+/// 设置中保存的调度表最多的项数
+pub const MAX_SCHEDULE_LEN: usize = 16;
+
+/// 调度表中一项编码后的长度
+pub const SCHEDULE_ENTRY_LEN: usize = 4;
+
+/// 总线波特率范围（LIN 规范为 1 到 20 kbit/s）
+pub const BAUDRATES: RangeInclusive<u32> = 1000..=20_000;
+
+/// LIN 错误
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum LinError {
+    /// UART 配置无效
+    Config,
+    /// 帧 ID 超过 0x3F 或数据长度不在 1..=8 范围内
+    InvalidFrame,
+    /// 发送出错
+    Transmit,
+    /// 接收出错
+    Receive,
+    /// 从节点没有应答
+    Timeout,
+    /// 回读的字节与发送的不一致（总线冲突）
+    Collision,
+    /// 应答的校验和错误
+    Checksum,
+}
+
+/// 帧方向
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Direction {
+    /// 主节点发送数据
+    Publish,
+    /// 从节点应答数据
+    Subscribe,
+}
+
+/// 调度表中的一项
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct ScheduleEntry {
+    /// 帧 ID（0 到 0x3F）
+    pub id: u8,
+    pub direction: Direction,
+    /// 数据长度
+    pub len: u8,
+    /// 时间槽长度，下一帧在本帧开始后这么久发出
+    pub slot: Duration,
+}
+
+impl ScheduleEntry {
+    /// 创建调度表项，帧 ID、数据长度无效或时间槽超过 65535 ms 时返回 None
+    pub fn new(id: u8, direction: Direction, len: u8, slot_ms: u32) -> Option<Self> {
+        check_frame(id, len as usize).ok()?;
+        let slot_ms = u16::try_from(slot_ms).ok().filter(|&ms| ms > 0)?;
+        Some(ScheduleEntry {
+            id,
+            direction,
+            len,
+            slot: Duration::from_millis(slot_ms as u64),
+        })
+    }
+
+    /// 编码后保存到设置中
+    ///
+    /// 第 0 字节为帧 ID，最高位为 1 表示从节点应答；第 1 字节为数据长度；
+    /// 第 2、3 字节为时间槽毫秒数（小端）
+    pub fn to_bytes(self) -> [u8; SCHEDULE_ENTRY_LEN] {
+        let subscribe = match self.direction {
+            Direction::Publish => 0,
+            Direction::Subscribe => 0x80,
+        };
+        let [s0, s1] = (self.slot.as_millis() as u16).to_le_bytes();
+        [self.id | subscribe, self.len, s0, s1]
+    }
+
+    /// 从设置中的编码还原，编码无效时返回 None
+    pub fn from_bytes(bytes: &[u8; SCHEDULE_ENTRY_LEN]) -> Option<Self> {
+        let direction = if bytes[0] & 0x80 == 0 {
+            Direction::Publish
+        } else {
+            Direction::Subscribe
+        };
+        let slot_ms = u16::from_le_bytes([bytes[2], bytes[3]]);
+        ScheduleEntry::new(bytes[0] & 0x7F, direction, bytes[1], slot_ms as u32)
+    }
+}
+
+/// 设置中保存的调度表，无效的项被跳过
+pub fn saved_schedule() -> Vec<ScheduleEntry, MAX_SCHEDULE_LEN> {
+    crate::settings::get()
+        .lin_schedule
+        .iter()
+        .filter_map(ScheduleEntry::from_bytes)
+        .collect()
+}
+
+/// 设置中选择的校验和类型
+pub fn saved_checksum() -> Checksum {
+    if crate::settings::get().lin_classic {
+        Checksum::Classic
+    } else {
+        Checksum::Enhanced
+    }
+}
+
+/// 启动时从设置载入的调度表，[lin_task] 在整个运行期间引用它
+static SCHEDULE: StaticCell<Vec<ScheduleEntry, MAX_SCHEDULE_LEN>> = StaticCell::new();
+
+/// 载入设置中保存的调度表，交给 [lin_task]
+///
+/// # Panics
+///
+/// 重复调用时会 panic
+pub fn load_schedule() -> &'static [ScheduleEntry] {
+    SCHEDULE.init(saved_schedule())
+}
+
+/// 一个帧 ID 的数据
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct FrameData {
+    pub len: u8,
+    pub data: [u8; MAX_DATA_LEN],
+    /// 最近一次成功收发的时间
+    pub updated: Option<Instant>,
+}
+
+impl FrameData {
+    const EMPTY: FrameData = FrameData {
+        len: 0,
+        data: [0; MAX_DATA_LEN],
+        updated: None,
+    };
+
+    /// 有效数据
+    pub fn bytes(&self) -> &[u8] {
+        &self.data[..self.len as usize]
+    }
+}
+
+static FRAMES: Mutex<RefCell<[FrameData; FRAME_IDS]>> =
+    Mutex::new(RefCell::new([FrameData::EMPTY; FRAME_IDS]));
+
+/// 设置主节点发送的帧数据
+///
+/// # 参数
+/// * `id` - 帧 ID
+/// * `data` - 1 到 8 字节数据
+pub fn set_frame_data(id: u8, data: &[u8]) -> Result<(), LinError> {
+    check_frame(id, data.len())?;
+    critical_section::with(|cs| {
+        let frame = &mut FRAMES.borrow_ref_mut(cs)[id as usize];
+        frame.len = data.len() as u8;
+        frame.data[..data.len()].copy_from_slice(data);
+    });
+    Ok(())
+}
+
+/// 帧数据（主节点发送的或最近一次从节点应答的）
+pub fn frame_data(id: u8) -> Option<FrameData> {
+    critical_section::with(|cs| FRAMES.borrow_ref(cs).get(id as usize).copied())
+}
+
+fn check_frame(id: u8, len: usize) -> Result<(), LinError> {
+    if id as usize >= FRAME_IDS || !(1..=MAX_DATA_LEN).contains(&len) {
+        return Err(LinError::InvalidFrame);
+    }
+    Ok(())
+}
+
+/// LIN 主节点
+pub struct Lin {
+    rx: UartRx<'static, Async>,
+    tx: UartTx<'static, Async>,
+    baudrate: u32,
+    checksum: Checksum,
+    data_config: UartConfig,
+    break_config: UartConfig,
+}
+
+impl Lin {
+    /// 创建主节点
+    ///
+    /// # 参数
+    /// * `uart` - UART 外设
+    /// * `tx`, `rx` - 接收发器的引脚
+    /// * `baudrate` - 总线波特率，通常为 19200 或 9600
+    /// * `checksum` - 校验和类型
+    pub fn new(
+        uart: impl uart::Instance + 'static,
+        tx: impl PeripheralOutput<'static>,
+        rx: impl PeripheralInput<'static>,
+        baudrate: u32,
+        checksum: Checksum,
+    ) -> Result<Self, LinError> {
+        let data_config = UartConfig::default().with_baudrate(baudrate);
+        let break_config = UartConfig::default().with_baudrate(baudrate * 9 / 13);
+        let (rx, tx) = Uart::new(uart, data_config)
+            .map_err(|_| LinError::Config)?
+            .with_tx(tx)
+            .with_rx(rx)
+            .into_async()
+            .split();
+        Ok(Lin {
+            rx,
+            tx,
+            baudrate,
+            checksum,
+            data_config,
+            break_config,
+        })
+    }
+
+    /// 发送一个数据帧
+    ///
+    /// # 参数
+    /// * `id` - 帧 ID
+    /// * `data` - 1 到 8 字节数据
+    pub async fn publish(&mut self, id: u8, data: &[u8]) -> Result<(), LinError> {
+        check_frame(id, data.len())?;
+        self.send_header(id).await?;
+
+        let mut response = [0u8; MAX_DATA_LEN + 1];
+        response[..data.len()].copy_from_slice(data);
+        response[data.len()] = self.checksum.compute(id, data);
+        let response = &response[..data.len() + 1];
+        self.write_all(response).await?;
+
+        // 回读比较，发现总线冲突
+        let mut echo = [0u8; MAX_DATA_LEN + 1];
+        let echo = &mut echo[..response.len()];
+        let read = self.rx.read_buffered(echo).map_err(|_| LinError::Receive)?;
+        if read != response.len() || echo != response {
+            return Err(LinError::Collision);
+        }
+        Ok(())
+    }
+
+    /// 发送帧头并读取从节点的应答
+    ///
+    /// # 参数
+    /// * `id` - 帧 ID
+    /// * `out` - 应答数据缓冲区，长度即期望的数据长度（1 到 8 字节）
+    pub async fn subscribe(&mut self, id: u8, out: &mut [u8]) -> Result<(), LinError> {
+        check_frame(id, out.len())?;
+        self.send_header(id).await?;
+
+        let mut response = [0u8; MAX_DATA_LEN + 1];
+        let response = &mut response[..out.len() + 1];
+        // 规范允许应答比标称时间长 40%，另留 1 ms 给从节点处理
+        let bits = response.len() as u64 * 10 * 14 / 10;
+        let timeout = Duration::from_micros(bits * 1_000_000 / self.baudrate as u64 + 1000);
+        match with_timeout(timeout, self.rx.read_exact_async(response)).await {
+            Ok(Ok(())) => {}
+            Ok(Err(_)) => return Err(LinError::Receive),
+            Err(_) => return Err(LinError::Timeout),
+        }
+
+        let (data, received) = response.split_at(out.len());
+        if self.checksum.compute(id, data) != received[0] {
+            return Err(LinError::Checksum);
+        }
+        out.copy_from_slice(data);
+        Ok(())
+    }
+
+    /// 发送 break、同步字节和 PID
+    async fn send_header(&mut self, id: u8) -> Result<(), LinError> {
+        self.discard_input();
+
+        self.tx.apply_config(&self.break_config).map_err(|_| LinError::Config)?;
+        self.write_all(&[0x00]).await?;
+        self.tx.apply_config(&self.data_config).map_err(|_| LinError::Config)?;
+
+        self.write_all(&[SYNC, protected_id(id)]).await?;
+        // 帧头的回读（break 在正常波特率下是一个帧错误的 0x00）不做比较
+        self.discard_input();
+        Ok(())
+    }
+
+    /// 写入所有数据并等待最后一个字节移出
+    async fn write_all(&mut self, mut data: &[u8]) -> Result<(), LinError> {
+        while !data.is_empty() {
+            let written = self
+                .tx
+                .write_async(data)
+                .await
+                .map_err(|_| LinError::Transmit)?;
+            data = &data[written..];
+        }
+        self.tx.flush_async().await.map_err(|_| LinError::Transmit)
+    }
+
+    /// 丢弃接收 FIFO 中的数据
+    fn discard_input(&mut self) {
+        let mut scratch = [0u8; 16];
+        while let Ok(read) = self.rx.read_buffered(&mut scratch)
+            && read > 0
+        {}
+    }
+}
+
+/// 调度表任务
+///
+/// # 参数
+/// * `lin` - 主节点
+/// * `schedule` - 调度表，循环执行
+#[embassy_executor::task]
+pub async fn lin_task(mut lin: Lin, schedule: &'static [ScheduleEntry]) {
+    info!("LIN master started, {} frames in schedule", schedule.len());
+    // 还没有数据的主节点帧先发送全 0，直到应用设置数据
+    for entry in schedule {
+        if entry.direction == Direction::Publish
+            && frame_data(entry.id).is_some_and(|frame| frame.len == 0)
+        {
+            set_frame_data(entry.id, &[0; MAX_DATA_LEN][..entry.len as usize]).ok();
+        }
+    }
+    let mut last_error: [Option<LinError>; FRAME_IDS] = [None; FRAME_IDS];
+
+    loop {
+        for entry in schedule {
+            let start = Instant::now();
+            let result = run_entry(&mut lin, entry).await;
+
+            // 只在状态变化时记录，避免离线的从节点刷屏
+            let slot = &mut last_error[entry.id as usize % FRAME_IDS];
+            match result {
+                Ok(()) if slot.is_some() => info!("LIN frame {:#04x} recovered", entry.id),
+                Err(err) if *slot != Some(err) => {
+                    warn!("LIN frame {:#04x} failed: {}", entry.id, err)
+                }
+                _ => {}
+            }
+            *slot = result.err();
+
+            Timer::at(start + entry.slot).await;
+        }
+        if schedule.is_empty() {
+            Timer::after(Duration::from_secs(1)).await;
+        }
+    }
+}
+
+/// 执行调度表中的一帧，结果写入帧数据表
+async fn run_entry(lin: &mut Lin, entry: &ScheduleEntry) -> Result<(), LinError> {
+    match entry.direction {
+        Direction::Publish => {
+            let frame = frame_data(entry.id).ok_or(LinError::InvalidFrame)?;
+            lin.publish(entry.id, frame.bytes()).await?;
+        }
+        Direction::Subscribe => {
+            let mut data = [0u8; MAX_DATA_LEN];
+            let data = data
+                .get_mut(..entry.len as usize)
+                .ok_or(LinError::InvalidFrame)?;
+            lin.subscribe(entry.id, data).await?;
+            critical_section::with(|cs| {
+                let frame = &mut FRAMES.borrow_ref_mut(cs)[entry.id as usize];
+                frame.len = data.len() as u8;
+                frame.data[..data.len()].copy_from_slice(data);
+            });
+        }
+    }
+    critical_section::with(|cs| {
+        FRAMES.borrow_ref_mut(cs)[entry.id as usize].updated = Some(Instant::now());
+    });
+    Ok(())
+}
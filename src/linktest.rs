@@ -0,0 +1,667 @@
+//! 双板链路测试
+//!
+//! [Profile::LinkTest](crate::profile::Profile::LinkTest) 模式下，两块运行本固件的开发板
+//! 每 [PROBE_INTERVAL] 向对方发送一个探测包，并把收到的探测包原样回显，
+//! 由此测量往返时延和丢包率。三种链路同时测量，各自独立寻找对端和统计，
+//! 结果显示在各自的 LCD 上，并每 [REPORT_PERIOD] 打印一次：
+//!
+//! - UDP：找到对端之前探测包以广播发送到 UDP [PORT] 端口，收到对方的第一个数据包后改为单播
+//! - ESP-NOW：同样先广播后单播，对端以 MAC 地址标识（见 [crate::espnow]）
+//! - MQTT：经代理转发，主题为 `esp-app-4/linktest/<接收方>/<发送方>`，
+//!   接收方和发送方是设备 ID（见 [crate::device::id]），寻找对端时接收方为 `all`。
+//!   两块板都订阅 `esp-app-4/linktest/#`，不是发给自己的消息在本地丢弃
+//!
+//! 两块板都选择此模式并连接同一个 WiFi 网络即可，不需要配置对端地址；MQTT 还需要
+//! 两块板连接同一个代理（见 [crate::mqtt]）。对端超过 [PEER_TIMEOUT] 没有回应时重新寻找。
+//!
+//! 屏幕上按确认键与另一块板配对（见 [crate::pairing]），之后发给它的 ESP-NOW 单播帧加密；
+//! 寻找对端时的广播帧仍是明文。
+
+#[cfg(feature = "ui")]
+use crate::i18n::{self, Msg};
+#[cfg(feature = "ui")]
+use crate::input;
+#[cfg(feature = "ui")]
+use crate::keymap::{self, Action};
+#[cfg(feature = "ui")]
+use crate::lcd::Lcd;
+use crate::netstats::{self, Link};
+#[cfg(feature = "ui")]
+use crate::pairing::{self, Phase};
+#[cfg(feature = "ui")]
+use crate::st7789::St7789;
+#[cfg(feature = "ui")]
+use crate::{assets, wifi};
+use crate::{device, espnow, mqtt};
+use core::cell::RefCell;
+use core::fmt::{self, Write};
+use critical_section::Mutex;
+use defmt::{info, warn};
+use embassy_futures::select::{Either3, select3};
+use embassy_net::udp::{PacketMetadata, UdpSocket};
+use embassy_net::{IpAddress, Stack};
+#[cfg(feature = "ui")]
+use embassy_time::with_timeout;
+use embassy_time::{Duration, Instant, Timer};
+#[cfg(feature = "ui")]
+use embedded_graphics::mono_font::{MonoTextStyle, MonoTextStyleBuilder};
+#[cfg(feature = "ui")]
+use embedded_graphics::pixelcolor::Rgb565;
+#[cfg(feature = "ui")]
+use embedded_graphics::prelude::*;
+#[cfg(feature = "ui")]
+use embedded_graphics::text::Text;
+use heapless::String;
+
+/// 测试使用的 UDP 端口
+pub const PORT: u16 = 7007;
+
+/// 探测间隔
+const PROBE_INTERVAL: Duration = Duration::from_millis(200);
+
+/// 超过此时间未收到回显的探测包计为丢失
+const TIMEOUT: Duration = Duration::from_secs(1);
+
+/// 仍可能收到回显的探测包数量，统计丢包时不计入
+const IN_FLIGHT: u32 = (TIMEOUT.as_millis() / PROBE_INTERVAL.as_millis()) as u32 + 1;
+
+/// 统计最近多少个探测包
+const WINDOW: usize = 64;
+
+/// 对端无回应多久后重新寻找
+const PEER_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// 统计打印周期
+const REPORT_PERIOD: Duration = Duration::from_secs(10);
+
+/// 屏幕刷新周期
+#[cfg(feature = "ui")]
+const REFRESH_PERIOD: Duration = Duration::from_millis(500);
+
+/// 数据包格式：魔数 2 字节、类型 1 字节、保留 1 字节、序号 u32、发送时刻 u64（微秒），小端
+const PACKET_LEN: usize = 16;
+const MAGIC: [u8; 2] = *b"LT";
+const KIND_PROBE: u8 = 0;
+const KIND_ECHO: u8 = 1;
+
+/// 受限广播地址
+const BROADCAST: IpAddress = IpAddress::v4(255, 255, 255, 255);
+
+/// MQTT 主题前缀，之后是接收方和发送方
+const MQTT_PREFIX: &str = "esp-app-4/linktest/";
+
+/// 订阅的 MQTT 主题
+const MQTT_FILTER: &str = "esp-app-4/linktest/#";
+
+/// 寻找对端时 MQTT 主题中的接收方
+const MQTT_BROADCAST: &str = "all";
+
+/// 屏幕每行的字符数
+#[cfg(feature = "ui")]
+const LINE_CHARS: usize = 32;
+
+/// 各链路的测试状态，按 [Transport::ALL] 的顺序，网络任务写入，屏幕任务读取
+static STATES: Mutex<RefCell<[State; 3]>> =
+    Mutex::new(RefCell::new([State::new(), State::new(), State::new()]));
+
+/// 测试的链路
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Transport {
+    Udp,
+    EspNow,
+    Mqtt,
+}
+
+impl Transport {
+    pub const ALL: [Transport; 3] = [Transport::Udp, Transport::EspNow, Transport::Mqtt];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Transport::Udp => "UDP",
+            Transport::EspNow => "ESP-NOW",
+            Transport::Mqtt => "MQTT",
+        }
+    }
+}
+
+/// 对端：UDP 为 IP 地址，ESP-NOW 和 MQTT 为 MAC 地址
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Peer {
+    Ip(IpAddress),
+    Mac([u8; 6]),
+}
+
+/// MAC 地址显示为小写十六进制，与设备 ID 相同
+impl fmt::Display for Peer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Peer::Ip(address) => write!(f, "{}", address),
+            Peer::Mac(mac) => f.write_str(&device::format_id(mac)),
+        }
+    }
+}
+
+impl defmt::Format for Peer {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(f, "{}", defmt::Display2Format(self))
+    }
+}
+
+/// 往返时延统计（微秒）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct Rtt {
+    pub min_us: u32,
+    pub avg_us: u32,
+    pub max_us: u32,
+}
+
+/// 统计窗口内的测试结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Summary {
+    /// 对端，尚未找到时为 `None`
+    pub peer: Option<Peer>,
+    /// 找到对端以来发送的探测包数
+    pub sent: u32,
+    /// 窗口内已可判定是否丢失的探测包数
+    pub settled: u32,
+    /// 其中丢失的探测包数
+    pub lost: u32,
+    /// 窗口内收到回显的探测包的往返时延
+    pub rtt: Option<Rtt>,
+}
+
+struct State {
+    peer: Option<Peer>,
+    /// 最近一次收到对端数据包的时刻
+    last_heard: Instant,
+    next_seq: u32,
+    /// 找到对端时的序号，之前的探测包不计入统计
+    first_seq: u32,
+    /// 按序号取模保存的往返时延，未收到回显时为 `None`
+    rtt_us: [Option<u32>; WINDOW],
+}
+
+impl State {
+    const fn new() -> Self {
+        State {
+            peer: None,
+            last_heard: Instant::from_ticks(0),
+            next_seq: 0,
+            first_seq: 0,
+            rtt_us: [None; WINDOW],
+        }
+    }
+
+    /// 记录对端，找到新对端时重新开始统计
+    fn heard_from(&mut self, transport: Transport, peer: Peer) {
+        self.last_heard = Instant::now();
+        if self.peer != Some(peer) {
+            info!("Link test {} peer: {}", transport.name(), peer);
+            self.peer = Some(peer);
+            self.first_seq = self.next_seq;
+        }
+    }
+
+    /// 分配下一个探测包的序号
+    fn next_seq(&mut self) -> u32 {
+        let seq = self.next_seq;
+        self.rtt_us[seq as usize % WINDOW] = None;
+        self.next_seq = seq.wrapping_add(1);
+        seq
+    }
+
+    /// 记录回显，超时或已移出窗口的回显忽略
+    fn record_echo(&mut self, seq: u32, rtt: Duration) {
+        let age = self.next_seq.wrapping_sub(seq);
+        if rtt > TIMEOUT || age == 0 || age > WINDOW as u32 {
+            return;
+        }
+        self.rtt_us[seq as usize % WINDOW] = Some(rtt.as_micros() as u32);
+    }
+
+    fn summary(&self) -> Summary {
+        let sent = self.next_seq.wrapping_sub(self.first_seq);
+        let window = sent.min(WINDOW as u32);
+        let (mut settled, mut lost) = (0, 0);
+        let (mut count, mut total) = (0u32, 0u64);
+        let mut rtt = Rtt {
+            min_us: u32::MAX,
+            avg_us: 0,
+            max_us: 0,
+        };
+        for age in 1..=window {
+            let slot = self.rtt_us[self.next_seq.wrapping_sub(age) as usize % WINDOW];
+            if age > IN_FLIGHT {
+                settled += 1;
+                lost += slot.is_none() as u32;
+            }
+            if let Some(us) = slot {
+                count += 1;
+                total += us as u64;
+                rtt.min_us = rtt.min_us.min(us);
+                rtt.max_us = rtt.max_us.max(us);
+            }
+        }
+        rtt.avg_us = total.checked_div(count as u64).unwrap_or(0) as u32;
+        Summary {
+            peer: self.peer,
+            sent,
+            settled,
+            lost,
+            rtt: (count > 0).then_some(rtt),
+        }
+    }
+}
+
+/// 当前的测试结果
+pub fn summary(transport: Transport) -> Summary {
+    critical_section::with(|cs| STATES.borrow_ref(cs)[transport as usize].summary())
+}
+
+/// 编码数据包
+fn encode(kind: u8, seq: u32, sent_us: u64) -> [u8; PACKET_LEN] {
+    let mut packet = [0u8; PACKET_LEN];
+    packet[..2].copy_from_slice(&MAGIC);
+    packet[2] = kind;
+    packet[4..8].copy_from_slice(&seq.to_le_bytes());
+    packet[8..].copy_from_slice(&sent_us.to_le_bytes());
+    packet
+}
+
+/// 解码数据包
+///
+/// # 返回
+/// 类型、序号和发送时刻，不是测试数据包时返回 `None`
+fn decode(packet: &[u8]) -> Option<(u8, u32, u64)> {
+    if packet.len() != PACKET_LEN || packet[..2] != MAGIC {
+        return None;
+    }
+    let seq = u32::from_le_bytes(packet[4..8].try_into().ok()?);
+    let sent_us = u64::from_le_bytes(packet[8..].try_into().ok()?);
+    Some((packet[2], seq, sent_us))
+}
+
+/// 处理收到的数据包
+///
+/// # 参数
+/// * `transport` - 收到数据包的链路
+/// * `peer` - 发送方
+/// * `packet` - 数据包
+///
+/// # 返回
+/// 收到探测包时返回要发回的回显
+fn receive(transport: Transport, peer: Peer, packet: &[u8]) -> Option<[u8; PACKET_LEN]> {
+    netstats::received(Link::Test, packet.len());
+    let (kind, seq, sent_us) = decode(packet)?;
+    critical_section::with(|cs| {
+        let mut states = STATES.borrow_ref_mut(cs);
+        let state = &mut states[transport as usize];
+        state.heard_from(transport, peer);
+        if kind == KIND_ECHO {
+            let now_us = Instant::now().as_micros();
+            let rtt = Duration::from_micros(now_us.saturating_sub(sent_us));
+            state.record_echo(seq, rtt);
+        }
+    });
+    (kind == KIND_PROBE).then(|| encode(KIND_ECHO, seq, sent_us))
+}
+
+/// 生成下一个探测包，对端超时未回应时重新寻找
+///
+/// # 返回
+/// 探测包和目标对端，正在寻找对端时目标为 `None`
+fn next_probe(transport: Transport) -> ([u8; PACKET_LEN], Option<Peer>) {
+    let (seq, peer) = critical_section::with(|cs| {
+        let mut states = STATES.borrow_ref_mut(cs);
+        let state = &mut states[transport as usize];
+        if state.peer.is_some() && state.last_heard.elapsed() > PEER_TIMEOUT {
+            warn!("Link test {} peer lost, searching again", transport.name());
+            state.peer = None;
+        }
+        (state.next_seq(), state.peer)
+    });
+    (encode(KIND_PROBE, seq, Instant::now().as_micros()), peer)
+}
+
+/// 经 ESP-NOW 发送数据包，没有对端时广播
+fn send_espnow(peer: Option<Peer>, packet: &[u8]) {
+    let mac = match peer {
+        Some(Peer::Mac(mac)) => mac,
+        _ => espnow::BROADCAST,
+    };
+    if espnow::send(mac, packet) {
+        netstats::sent(Link::Test, packet.len());
+    }
+}
+
+/// 经 MQTT 发送数据包，没有对端时发给 `all`
+fn send_mqtt(peer: Option<Peer>, packet: &[u8]) {
+    let mut topic: String<{ mqtt::TOPIC_LEN }> = String::new();
+    match peer {
+        Some(peer) => write!(topic, "{}{}/{}", MQTT_PREFIX, peer, device::id()),
+        None => write!(topic, "{}{}/{}", MQTT_PREFIX, MQTT_BROADCAST, device::id()),
+    }
+    .ok();
+    if mqtt::publish_to(&topic, packet, false) {
+        netstats::sent(Link::Test, packet.len());
+    }
+}
+
+/// 处理 MQTT 消息，由 MQTT 客户端任务调用（见 [mqtt::subscribe]）
+fn on_mqtt(topic: &str, payload: &[u8]) {
+    let Some((to, from)) = topic
+        .strip_prefix(MQTT_PREFIX)
+        .and_then(|rest| rest.split_once('/'))
+    else {
+        return;
+    };
+    let own = device::id();
+    if from == own || (to != own && to != MQTT_BROADCAST) {
+        return;
+    }
+    let Some(mac) = device::parse_id(from) else {
+        return;
+    };
+    if let Some(reply) = receive(Transport::Mqtt, Peer::Mac(mac), payload) {
+        send_mqtt(Some(Peer::Mac(mac)), &reply);
+    }
+}
+
+/// 打印统计
+fn report(transport: Transport, summary: &Summary) {
+    let name = transport.name();
+    let Some(peer) = summary.peer else {
+        info!("Link test {}: searching for peer", name);
+        return;
+    };
+    match summary.rtt {
+        Some(rtt) => info!(
+            "Link test {} {}: rtt min {} us, avg {} us, max {} us, lost {}/{}",
+            name, peer, rtt.min_us, rtt.avg_us, rtt.max_us, summary.lost, summary.settled
+        ),
+        None => info!(
+            "Link test {} {}: no replies, lost {}/{}",
+            name, peer, summary.lost, summary.settled
+        ),
+    }
+}
+
+/// 链路测试网络任务：在每种链路上发送探测包并回显对端的探测包
+///
+/// ESP-NOW 的收发由 [espnow::espnow_task] 完成，MQTT 消息由 MQTT 客户端任务转交
+///
+/// # 参数
+/// * `stack` - 网络协议栈
+#[embassy_executor::task]
+pub async fn linktest_task(stack: Stack<'static>) {
+    let mut rx_meta = [PacketMetadata::EMPTY; 8];
+    let mut rx_buffer = [0u8; PACKET_LEN * 8];
+    let mut tx_meta = [PacketMetadata::EMPTY; 8];
+    let mut tx_buffer = [0u8; PACKET_LEN * 8];
+    let mut socket = UdpSocket::new(
+        stack,
+        &mut rx_meta,
+        &mut rx_buffer,
+        &mut tx_meta,
+        &mut tx_buffer,
+    );
+    if let Err(err) = socket.bind(PORT) {
+        warn!(
+            "Failed to bind link test port: {}",
+            defmt::Debug2Format(&err)
+        );
+        return;
+    }
+    info!("Link test listening on UDP port {}", PORT);
+    if !mqtt::subscribe(MQTT_FILTER, on_mqtt) {
+        warn!("Failed to subscribe to link test MQTT topic");
+    }
+
+    let mut next_probe_at = Instant::now();
+    let mut next_report = Instant::now() + REPORT_PERIOD;
+    let mut packet = [0u8; PACKET_LEN];
+    loop {
+        stack.wait_config_up().await;
+        let event = select3(
+            socket.recv_from(&mut packet),
+            espnow::receive(),
+            Timer::at(next_probe_at),
+        )
+        .await;
+        match event {
+            Either3::First(Ok((len, meta))) => {
+                // 广播的探测包可能被自己收到
+                let own = stack
+                    .config_v4()
+                    .map(|config| IpAddress::Ipv4(config.address.address()));
+                if own == Some(meta.endpoint.addr) {
+                    continue;
+                }
+                let peer = Peer::Ip(meta.endpoint.addr);
+                if let Some(reply) = receive(Transport::Udp, peer, &packet[..len]) {
+                    match socket.send_to(&reply, meta).await {
+                        Ok(()) => netstats::sent(Link::Test, reply.len()),
+                        Err(err) => warn!("Link test echo failed: {}", defmt::Debug2Format(&err)),
+                    }
+                }
+            }
+            Either3::First(Err(err)) => {
+                warn!("Link test receive failed: {}", defmt::Debug2Format(&err));
+            }
+            Either3::Second(frame) => {
+                let peer = Peer::Mac(frame.peer);
+                if let Some(reply) = receive(Transport::EspNow, peer, &frame.data) {
+                    send_espnow(Some(peer), &reply);
+                }
+            }
+            Either3::Third(()) => {
+                next_probe_at += PROBE_INTERVAL;
+                // 离线时发送失败也计入丢包，不逐个打印
+                let (probe, peer) = next_probe(Transport::Udp);
+                let target = match peer {
+                    Some(Peer::Ip(address)) => address,
+                    _ => BROADCAST,
+                };
+                if socket.send_to(&probe, (target, PORT)).await.is_ok() {
+                    netstats::sent(Link::Test, probe.len());
+                }
+                let (probe, peer) = next_probe(Transport::EspNow);
+                send_espnow(peer, &probe);
+                let (probe, peer) = next_probe(Transport::Mqtt);
+                send_mqtt(peer, &probe);
+
+                if Instant::now() >= next_report {
+                    next_report += REPORT_PERIOD;
+                    for transport in Transport::ALL {
+                        report(transport, &summary(transport));
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// 绘制一行文本，用空格补足整行以覆盖上次的内容
+#[cfg(feature = "ui")]
+fn draw_line(lcd: &mut St7789, y: i32, text: &str, style: MonoTextStyle<'_, Rgb565>) {
+    let mut line: String<LINE_CHARS> = String::new();
+    for c in text.chars() {
+        if line.push(c).is_err() {
+            break;
+        }
+    }
+    while line.push(' ').is_ok() {}
+    if let Err(err) = Text::new(&line, Point::new(10, y), style).draw(lcd) {
+        warn!("Failed to draw link test line: {}", err);
+    }
+}
+
+/// 把微秒格式化为保留一位小数的毫秒
+#[cfg(feature = "ui")]
+fn write_ms(text: &mut String<64>, us: u32) {
+    write!(text, "{}.{}", us / 1000, us / 100 % 10).ok();
+}
+
+/// 清屏
+#[cfg(feature = "ui")]
+async fn clear(lcd: &mut Lcd) {
+    if let Err(err) = lcd.fill_screen(Rgb565::BLACK).await {
+        warn!("Failed to clear LCD: {}", err);
+    }
+}
+
+/// 按当前映射显示的按键编号，例如 `K2`
+#[cfg(feature = "ui")]
+fn key_label(action: Action) -> String<4> {
+    let mut label = String::new();
+    let name = keymap::key_name(keymap::key_for(action));
+    write!(label, "K{}", name.trim_start_matches("key")).ok();
+    label
+}
+
+/// 绘制配对屏幕
+#[cfg(feature = "ui")]
+fn draw_pairing(lcd: &mut St7789, phase: Phase, style: MonoTextStyle<'_, Rgb565>) {
+    draw_line(lcd, 25, i18n::lcd(Msg::PairingTitle), style);
+    let confirm = key_label(Action::StartStop);
+    let cancel = key_label(Action::LapReset);
+    let mut text: String<64> = String::new();
+    match phase {
+        Phase::Idle => {}
+        Phase::Searching => {
+            draw_line(lcd, 85, i18n::lcd(Msg::PairingSearching), style);
+            write!(text, "{} {}", cancel, i18n::lcd(Msg::PairingCancel)).ok();
+            draw_line(lcd, 205, &text, style);
+        }
+        Phase::Compare { peer, code } => {
+            draw_line(lcd, 85, &device::format_id(&peer), style);
+            write!(text, "{:03} {:03}", code / 1000, code % 1000).ok();
+            draw_line(lcd, 125, &text, style);
+            draw_line(lcd, 165, i18n::lcd(Msg::PairingCompare), style);
+            text.clear();
+            write!(
+                text,
+                "{} {}  {} {}",
+                confirm,
+                i18n::lcd(Msg::PairingConfirm),
+                cancel,
+                i18n::lcd(Msg::PairingCancel)
+            )
+            .ok();
+            draw_line(lcd, 205, &text, style);
+        }
+        Phase::Paired(peer) => {
+            draw_line(lcd, 85, i18n::lcd(Msg::PairingDone), style);
+            draw_line(lcd, 125, &device::format_id(&peer), style);
+        }
+        Phase::Failed => draw_line(lcd, 85, i18n::lcd(Msg::PairingFailed), style),
+    }
+}
+
+/// 链路测试屏幕任务
+///
+/// 标题之下每种链路两行：链路名称和对端（或寻找、离线状态），平均往返时延和丢包率。
+/// 确认键开始 ESP-NOW 配对，配对期间显示配对屏幕
+///
+/// # 参数
+/// * `lcd` - 已完成初始化的 LCD
+#[cfg(feature = "ui")]
+#[embassy_executor::task]
+pub async fn display_task(mut lcd: Lcd) {
+    let style: MonoTextStyle<'_, Rgb565> = MonoTextStyleBuilder::new()
+        .font(assets::font())
+        .text_color(Rgb565::WHITE)
+        .background_color(Rgb565::BLACK)
+        .build();
+    let mut keys = input::subscribe();
+    if keys.is_some() {
+        input::set_captured(true);
+    } else {
+        warn!("No key subscriber available for link test, pairing disabled");
+    }
+
+    let mut text: String<64> = String::new();
+    // 已显示的配对进度，None 表示需要重绘
+    let mut shown = None;
+    loop {
+        let phase = pairing::phase();
+        if shown != Some(phase) {
+            clear(&mut lcd).await;
+            if phase == Phase::Idle {
+                text.clear();
+                write!(
+                    text,
+                    "{}  {} {}",
+                    i18n::lcd(Msg::LinkTestTitle),
+                    key_label(Action::StartStop),
+                    i18n::lcd(Msg::LinkTestPair)
+                )
+                .ok();
+                draw_line(&mut lcd, 25, &text, style);
+            } else {
+                draw_pairing(&mut lcd, phase, style);
+            }
+            shown = Some(phase);
+        }
+
+        // 配对期间不显示测试结果
+        if phase == Phase::Idle {
+            for (i, transport) in Transport::ALL.into_iter().enumerate() {
+                let y = 65 + i as i32 * 60;
+                let summary = summary(transport);
+                let online = match transport {
+                    Transport::Udp | Transport::EspNow => wifi::is_connected(),
+                    Transport::Mqtt => mqtt::is_connected(),
+                };
+
+                text.clear();
+                write!(text, "{} ", transport.name()).ok();
+                if !online {
+                    text.push_str(i18n::lcd(Msg::LinkTestOffline)).ok();
+                } else if let Some(peer) = summary.peer {
+                    write!(text, "{}", peer).ok();
+                } else {
+                    text.push_str(i18n::lcd(Msg::LinkTestSearching)).ok();
+                }
+                draw_line(&mut lcd, y, &text, style);
+
+                // 对端丢失后不再显示旧的结果
+                text.clear();
+                if summary.peer.is_some() {
+                    if let Some(rtt) = summary.rtt {
+                        write_ms(&mut text, rtt.avg_us);
+                        text.push_str(" ms  ").ok();
+                    }
+                    if let Some(percent) = (summary.lost * 100).checked_div(summary.settled) {
+                        let label = i18n::lcd(Msg::LinkTestLoss);
+                        write!(
+                            text,
+                            "{} {}/{} ({}%)",
+                            label, summary.lost, summary.settled, percent
+                        )
+                        .ok();
+                    }
+                }
+                draw_line(&mut lcd, y + 25, &text, style);
+            }
+        }
+
+        let key = match keys.as_mut() {
+            Some(keys) => with_timeout(REFRESH_PERIOD, keys.next_message_pure())
+                .await
+                .ok(),
+            None => {
+                Timer::after(REFRESH_PERIOD).await;
+                None
+            }
+        };
+        match (key.and_then(keymap::action), phase) {
+            (Some(Action::StartStop), Phase::Idle) => pairing::start(),
+            (Some(Action::StartStop), Phase::Compare { .. }) => pairing::answer(true),
+            (Some(Action::LapReset), Phase::Searching | Phase::Compare { .. }) => {
+                pairing::answer(false)
+            }
+            _ => {}
+        }
+    }
+}
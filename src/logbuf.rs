@@ -0,0 +1,209 @@
+//! 日志环形缓冲区
+//!
+//! 本模块实现 defmt 的全局 logger：每条日志编码后同时输出到控制台（见 [crate::console]）
+//! （与 espflash 的 defmt 帧格式兼容），并保存到 PSRAM 中最近 [RING_LEN] 字节的环形缓冲区中，
+//! 以便在没有连接 RTT/串口主机时也能事后取回最近的日志。缓冲区在 board 阶段初始化 PSRAM
+//! 之后由 [init] 分配，之前的日志只输出到控制台；模组没有 PSRAM 时不保存日志。
+//!
+//! 缓冲区中保存的是 rzCOBS 编码的 defmt 帧（以 0x00 分隔），而不是格式化后的文本：
+//! defmt 的格式字符串只存在于固件 ELF 中，设备上无法还原成文本。
+//! 在主机上用 `scripts/log-ring.sh` 取回并解码，它按下面的步骤调用 defmt-print：
+//!
+//! ```text
+//! curl http://<设备地址>/logs/ring -o ring.bin
+//! defmt-print -e target/xtensa-esp32s3-none-elf/release/esp-app-4 < ring.bin
+//! ```
+//!
+//! 没有网络时可以用命令行 `log dump` 以十六进制输出，保存到 `ring.txt` 后用
+//! `scripts/log-ring.sh -f ring.txt` 解码（内部用 `xxd -r -p` 还原）。
+//! 解码必须使用与设备上运行的固件相同的 ELF。
+//!
+//! 缓冲区写满后覆盖最早的数据，读取时会丢弃开头不完整的帧。
+//!
+//! 配置了 syslog 收集器时，每一帧还会交给 [crate::syslog] 转发。
+
+use crate::{console, syslog};
+use alloc::vec::Vec;
+use core::alloc::Layout;
+use core::cell::RefCell;
+use core::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use critical_section::{Mutex, RestoreState};
+use defmt::warn;
+use esp_alloc::MemoryCapability;
+
+/// 环形缓冲区大小（字节）
+pub const RING_LEN: usize = 32 * 1024;
+
+/// espflash 用于区分 defmt 帧与普通文本的帧起始标记
+const FRAME_START: [u8; 2] = [0xFF, 0x00];
+
+static RING: Mutex<RefCell<Ring>> = Mutex::new(RefCell::new(Ring::new()));
+
+/// 环形缓冲区
+struct Ring {
+    /// PSRAM 中的缓冲区，分配之前为空
+    buf: &'static mut [u8],
+    /// 下一个写入位置
+    head: usize,
+    /// 是否已经写满过一轮
+    wrapped: bool,
+}
+
+impl Ring {
+    const fn new() -> Self {
+        Ring {
+            buf: &mut [],
+            head: 0,
+            wrapped: false,
+        }
+    }
+
+    fn push(&mut self, bytes: &[u8]) {
+        if self.buf.is_empty() {
+            return;
+        }
+        for &byte in bytes {
+            self.buf[self.head] = byte;
+            self.head += 1;
+            if self.head == RING_LEN {
+                self.head = 0;
+                self.wrapped = true;
+            }
+        }
+    }
+}
+
+// 每条日志带上启动以来的时间戳
+defmt::timestamp!("{=u64:us}", embassy_time::Instant::now().as_micros());
+
+#[defmt::global_logger]
+struct Logger;
+
+/// logger 是否已被获取，用于检测重入
+static TAKEN: AtomicBool = AtomicBool::new(false);
+
+/// 重入的帧的嵌套层数，这些帧被丢弃
+static NESTED: AtomicU32 = AtomicU32::new(0);
+
+static mut CS_RESTORE: RestoreState = RestoreState::invalid();
+
+static mut ENCODER: defmt::Encoder = defmt::Encoder::new();
+
+/// 输出到控制台，控制台尚未初始化时经由 esp-println 输出
+fn output(bytes: &[u8]) {
+    if !console::write_bytes(bytes) {
+        esp_println::Printer::write_bytes(bytes);
+    }
+}
+
+/// 编码器输出：同时写入控制台、环形缓冲区和 syslog 发送队列
+fn do_write(bytes: &[u8]) {
+    output(bytes);
+    syslog::frame_encoded(bytes);
+    critical_section::with(|cs| RING.borrow_ref_mut(cs).push(bytes));
+}
+
+unsafe impl defmt::Logger for Logger {
+    fn acquire() {
+        // SAFETY: 在 release 中配对释放
+        let restore = unsafe { critical_section::acquire() };
+        // 同一个核上的重入（例如输出日志时发生 panic，panic 处理又要输出日志）：
+        // 丢弃新的帧，不能 panic，否则 panic 处理会再次重入
+        if TAKEN.load(Ordering::Relaxed) {
+            NESTED.fetch_add(1, Ordering::Relaxed);
+            // SAFETY: 与上面的 acquire 配对，外层帧的临界区仍然有效
+            unsafe { critical_section::release(restore) };
+            return;
+        }
+        TAKEN.store(true, Ordering::Relaxed);
+
+        // SAFETY: 处于临界区内，且已检查没有重入
+        unsafe {
+            CS_RESTORE = restore;
+            output(&FRAME_START);
+            syslog::frame_start();
+            let encoder = &raw mut ENCODER;
+            (*encoder).start_frame(do_write);
+        }
+    }
+
+    unsafe fn flush() {}
+
+    unsafe fn release() {
+        if NESTED.load(Ordering::Relaxed) > 0 {
+            NESTED.fetch_sub(1, Ordering::Relaxed);
+            return;
+        }
+        // SAFETY: 调用者保证已通过 acquire 进入临界区
+        unsafe {
+            let encoder = &raw mut ENCODER;
+            (*encoder).end_frame(do_write);
+            syslog::frame_end();
+            TAKEN.store(false, Ordering::Relaxed);
+            critical_section::release(CS_RESTORE);
+        }
+    }
+
+    unsafe fn write(bytes: &[u8]) {
+        if NESTED.load(Ordering::Relaxed) > 0 {
+            return;
+        }
+        syslog::frame_raw(bytes);
+        // SAFETY: 调用者保证已通过 acquire 进入临界区
+        unsafe {
+            let encoder = &raw mut ENCODER;
+            (*encoder).write(bytes, do_write);
+        }
+    }
+}
+
+/// 在 PSRAM 中分配环形缓冲区
+///
+/// 在 PSRAM 加入堆之后调用一次
+pub fn init() {
+    let layout = Layout::new::<[u8; RING_LEN]>();
+    // SAFETY: 长度不为 0
+    let ptr = unsafe { esp_alloc::HEAP.alloc_caps(MemoryCapability::External.into(), layout) };
+    if ptr.is_null() {
+        warn!("No PSRAM for the log ring buffer, logs go to the console only");
+        return;
+    }
+    // SAFETY: 刚分配的 RING_LEN 字节，先清零再使用，之后只由 RING 引用
+    let buf = unsafe {
+        ptr.write_bytes(0, RING_LEN);
+        core::slice::from_raw_parts_mut(ptr, RING_LEN)
+    };
+    critical_section::with(|cs| RING.borrow_ref_mut(cs).buf = buf);
+}
+
+/// 复制当前缓冲区中的日志
+///
+/// # 返回
+/// 按时间顺序排列的完整 defmt 帧
+pub fn snapshot() -> Vec<u8> {
+    let mut data = Vec::with_capacity(RING_LEN);
+    critical_section::with(|cs| {
+        let ring = RING.borrow_ref(cs);
+        if ring.wrapped {
+            data.extend_from_slice(&ring.buf[ring.head..]);
+        }
+        data.extend_from_slice(&ring.buf[..ring.head]);
+    });
+
+    // 覆盖写入后开头可能是半个帧，丢弃到第一个帧分隔符为止
+    if data.len() == RING_LEN
+        && let Some(end) = data.iter().position(|&byte| byte == 0)
+    {
+        data.drain(..=end);
+    }
+    data
+}
+
+/// 清空缓冲区
+pub fn clear() {
+    critical_section::with(|cs| {
+        let mut ring = RING.borrow_ref_mut(cs);
+        ring.head = 0;
+        ring.wrapped = false;
+    });
+}
@@ -48,16 +48,19 @@
 //!
 //! ## 硬件连接说明
 //!
+//! 以下为 DNESP32S3 的引脚，其他底板在设置中选择型号或自定义引脚（见 `board` 模块）。
+//!
 //! ### I2C 接口 (用于 XL9555 通信)
 //! - SDA: IO41 (GPIO41)
 //! - SCL: IO42 (GPIO42)
 //!
-//! ### SPI 接口 (用于 LCD 通信)
+//! ### SPI 接口 (LCD 与 TF 卡共享)
 //! - MOSI: IO11 (GPIO11)
 //! - SCK:  IO12 (GPIO12)
 //! - MISO: IO13 (GPIO13)
-//! - CS:   IO21 (GPIO21)
-//! - DC:   IO40 (GPIO40)
+//! - LCD CS: IO21 (GPIO21)
+//! - LCD DC: IO40 (GPIO40)
+//! - TF CS:  IO2 (GPIO2)
 //!
 //! ### XL9555 GPIO 扩展功能
 //! - P1.3: LCD 背光控制 (连接到 ATK-MD0240 模块的 PWR 引脚)
@@ -67,22 +70,39 @@
 //! ### 按键功能
 //! - KEY0: 未分配特定功能
 //! - KEY1: 切换 LCD 背光状态
-//! - KEY2: 未分配特定功能
+//! - KEY2: 切换状态屏幕的背景颜色
 //! - KEY3: 未分配特定功能
 //!
 //! ## 功能说明
 //!
 //! 1. 初始化 ESP32-S3 系统时钟和外设
-//! 2. 初始化 XL9555 GPIO 扩展芯片
-//! 3. 初始化 ATK-MD0240 LCD 模块
-//! 4. 开启 LCD 背光
-//! 5. 启动按键检测任务
+//! 2. 加载 Flash 中的设置，按子系统启用状态决定初始化内容
+//! 3. 初始化 XL9555 GPIO 扩展芯片
+//! 4. 初始化 ATK-MD0240 LCD 模块
+//! 5. 开启 LCD 背光
+//! 6. 启动按键检测任务
 //!
 //! ## 使用方法
 //!
 //! 1. 烧录程序到开发板
 //! 2. 程序启动后 LCD 背光会自动开启
 //! 3. 按下 KEY1 可切换 LCD 背光的开/关状态
+//! 4. BOOT 按键：短按开关屏幕背光，长按 5 秒后松开进入配网模式（重启后运行设置向导），
+//!    长按 10 秒恢复出厂设置
+//! 5. 将新固件 `FIRMWARE.BIN` 及其 ed25519 签名文件 `FIRMWARE.SIG` 放入 TF 卡根目录，
+//!    上电后自动完成离线升级（签名方法见 `ota` 模块文档）
+//! 6. 编译时通过 `WIFI_SSID` / `WIFI_PASSWORD` 环境变量配置 WiFi 网络，
+//!    连接后可通过 `GET /logs/ring` 下载最近的日志，`GET /crash` 查看崩溃记录
+//! 7. 日志和命令行默认使用 USB Serial/JTAG 控制台（启用 `console-uart` feature
+//!    改为 UART0/CH340），输入 `help` 查看可用命令
+//! 8. 首次启动（Flash 中没有设置）时 LCD 显示设置向导，用 KEY0/KEY1 选择、
+//!    KEY2 确认、KEY3 返回，完成语言和 WiFi 设置后自动重启
+//! 9. 连接 WiFi 后可通过 Modbus TCP（502 端口）读取按键状态、控制 LED 和背光，
+//!    地址表见 `modbus` 模块文档
+//! 10. 组装好的板子可先烧录自检程序（`cargo run --release --bin selftest`），
+//!     逐项检查外设并在控制台输出 PASS/FAIL 报告，格式见 `src/bin/selftest.rs`
+//! 11. 没有屏幕或 TF 卡槽的板子可以关闭默认的 `ui`、`sd` feature 减小固件，例如
+//!     `cargo build --release --no-default-features --features sd`，对应外设在注册表中显示为不存在
 
 #![no_std]
 #![no_main]
@@ -93,26 +113,127 @@
 )]
 
 extern crate alloc;
-use defmt::info;
+use app::App;
 use embassy_executor::Spawner;
 use esp_hal::clock::CpuClock;
-use esp_hal::spi::master::{Config, Spi};
-use esp_hal::spi::Mode;
-use esp_hal::time::Rate;
-use esp_hal::timer::timg::TimerGroup;
-use esp_hal::{
-    dma::{DmaRxBuf, DmaTxBuf},
-    dma_buffers,
-};
-// 保留以引入panic handler
+// panic 处理函数见 crash 模块，esp_backtrace 仅用于采集回溯地址
 #[allow(unused)]
-use {esp_backtrace, esp_println};
-
+use esp_backtrace as _;
+use esp_println as _;
+
+mod access;
+mod app;
+mod assets;
+#[cfg(feature = "ui")]
+mod bench;
+mod bme280;
+mod board;
+mod bridge;
 mod button;
+mod buzzer;
+mod can;
+mod capability;
+mod cli;
+#[cfg(feature = "ui")]
+mod clock;
+mod console;
+mod crash;
+mod device;
+mod dmx;
+mod error;
+mod espnow;
+#[cfg(feature = "sd")]
+mod fatcheck;
+// 总线故障注入只用于测试
+#[cfg(feature = "fault-injection")]
+mod fault;
+mod forecast;
+#[cfg(feature = "ui")]
+mod framebuffer;
+mod gps;
+mod http;
+mod http_client;
+mod i18n;
 mod i2c;
+mod input;
+mod jitter;
+mod keymap;
+#[cfg(feature = "ui")]
 mod lcd;
+mod linktest;
 mod led;
+mod lin;
+mod logbuf;
+mod mdns;
+mod modbus;
+mod monotonic;
+mod mqtt;
+mod multicore;
+mod net;
+mod netstats;
+mod notifier;
+mod ota;
+#[cfg(feature = "ui")]
+mod pairing;
+#[cfg(feature = "sd")]
+mod outbox;
+mod peersync;
+#[cfg(all(feature = "sd", feature = "ui"))]
+mod photo;
+mod pid;
+#[cfg(feature = "ui")]
+mod pomodoro;
+mod power;
+mod presence;
+mod profile;
+mod progress;
+mod ratelimit;
+mod registry;
+mod relay;
+mod rc;
+#[cfg(feature = "ui")]
+mod remote;
+#[cfg(feature = "ui")]
+mod render;
+mod rs485;
+mod rules;
+mod sas;
+mod scheduler;
+#[cfg(feature = "ui")]
+mod screens;
+#[cfg(feature = "sd")]
+mod sdcard;
+#[cfg(feature = "sd")]
+mod sdlog;
+mod secret;
+mod sensor;
+mod serial;
+mod service;
+mod settings;
+mod shadow;
+#[cfg(feature = "ui")]
+mod snake;
+mod snmp;
+mod sntp;
+mod spi;
+#[cfg(feature = "ui")]
+mod st7789;
+#[cfg(feature = "ui")]
+mod stopwatch;
+mod storage;
+mod syslog;
+mod system;
+mod theme;
+mod thermostat;
+mod thingspeak;
+#[cfg(feature = "ui")]
+mod tuning;
+mod wallclock;
+#[cfg(feature = "ui")]
+mod weather;
 mod wifi;
+#[cfg(feature = "ui")]
+mod wizard;
 mod xl9555;
 
 // 创建 esp-idf bootloader 所需的默认应用程序描述符
@@ -122,79 +243,13 @@ esp_bootloader_esp_idf::esp_app_desc!();
 #[esp_rtos::main]
 /// 主函数
 ///
-/// 系统启动入口点，负责初始化所有外设并启动相关任务
+/// 系统启动入口点，按阶段初始化所有外设（见 [app]）并启动相关任务
 async fn main(spawner: Spawner) {
     // generator version: 0.6.0
 
     let config = esp_hal::Config::default().with_cpu_clock(CpuClock::max());
     let peripherals = esp_hal::init(config);
 
-    esp_alloc::heap_allocator!( size : 64 * 1024 );
-
-    let time_g0_timer = peripherals.TIMG0;
-    let time_g0 = TimerGroup::new(time_g0_timer);
-    esp_rtos::start(time_g0.timer0);
-
-    info!("Embassy initialized!");
-
-    // 初始化 LED0 (GPIO1)
-    led::led0_init(peripherals.GPIO1).await;
-
-    // 初始化 BOOT 按键 (GPIO0)
-    button::boot_button_init(peripherals.GPIO0).await;
-
-    // 初始化 WiFi
-    wifi::init(peripherals.WIFI).await;
-    spawner
-        .spawn(wifi::wifi_scan())
-        .expect("failed to spawn wifi task");
-
-    // 初始化 XL9555 GPIO 扩展芯片
-    // 使用 I2C0 接口，SDA 连接 GPIO41，SCL 连接 GPIO42
-    i2c::init(peripherals.I2C0, peripherals.GPIO41, peripherals.GPIO42).await;
-    let result = xl9555::init().await;
-    if result.is_err() {
-        info!("Failed to initialize XL9555 GPIO expander");
-    }
-    // 启动按键检测任务
-    spawner
-        .spawn(xl9555::read_keys())
-        .expect("failed to spawn xl9555 task");
-
-    // 配置 SPI 接口引脚
-    let sck = peripherals.GPIO12; // SPI 时钟线
-    let mos = peripherals.GPIO11; // SPI 主输出从输入线
-    let mis = peripherals.GPIO13; // SPI 主输入从输出线
-    let cs = peripherals.GPIO21; // SPI 片选线
-    let dc = peripherals.GPIO40; // LCD 数据/命令选择线
-
-    let dma_channel = peripherals.DMA_CH0;
-    let (rx_buffer, rx_descriptors, tx_buffer, tx_descriptors) = dma_buffers!(32000);
-
-    let dma_rx_buf = DmaRxBuf::new(rx_descriptors, rx_buffer).unwrap();
-
-    let dma_tx_buf = DmaTxBuf::new(tx_descriptors, tx_buffer).unwrap();
-
-    // 初始化 SPI 接口
-    let mut spi = Spi::new(
-        peripherals.SPI2,
-        Config::default()
-            .with_frequency(Rate::from_mhz(10))
-            .with_mode(Mode::_0),
-    )
-    .expect("failed to initialize SPI")
-    .with_sck(sck)
-    .with_mosi(mos)
-    .with_miso(mis)
-    .with_dma(dma_channel)
-    .with_buffers(dma_rx_buf, dma_tx_buf);
-
-    // 初始化 ATK-MD0240 LCD 模块
-    xl9555::init_atk_md0240().await;
-
-    info!("Turning on LCD backlight");
-    // 开启 LCD 背光
-    // 通过 XL9555 的 P1.3 引脚控制 ATK-MD0240 模块的 PWR 引脚
-    xl9555::set_lcd_backlight(true).await;
-    info!("LCD backlight should be on now");
+    let app = App::init(peripherals).await;
+    app.start(spawner);
 }
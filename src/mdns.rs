@@ -0,0 +1,266 @@
+//! mDNS 服务发现（客户端）
+//!
+//! 用一次性 mDNS 查询（RFC 6762 第 5.1 节）在局域网中查找 DNS-SD 服务（RFC 6763）：
+//! 从临时端口向 224.0.0.251:5353 发送查询，响应方把应答单播回这个端口，
+//! 因此不需要加入组播组，也不需要常驻的 mDNS 任务。应答中的 PTR、SRV 和 A 记录
+//! 依次给出服务实例、端口和主机地址；应答里没有主机地址时再单独查询 A 记录。
+//!
+//! 目前 syslog 在没有设置收集器时用它查找 `_syslog._udp.local`（见 [crate::syslog]），
+//! MQTT 客户端在没有设置代理时查找 `_mqtt._tcp.local`（见 [crate::mqtt]）。
+//!
+//! 限制：固件不响应 mDNS 查询，其他设备不能用 `<主机名>.local` 找到本板；
+//! 没有通过网络的固件更新，因此不查找 OTA 服务器。
+//! 只支持 IPv4，找到多个实例时使用最先应答的一个。
+
+use crate::netstats::{self, Link};
+use defmt::{debug, info, warn};
+use embassy_net::udp::{PacketMetadata, UdpSocket};
+use embassy_net::{IpAddress, Ipv4Address, Stack};
+use embassy_time::{Duration, Instant, with_deadline};
+use heapless::String;
+
+/// mDNS 组播地址
+const MDNS_ADDRESS: Ipv4Address = Ipv4Address::new(224, 0, 0, 251);
+
+/// mDNS 端口
+const MDNS_PORT: u16 = 5353;
+
+/// 报文最大长度
+const PACKET_LEN: usize = 512;
+
+/// 域名最大长度
+pub const NAME_LEN: usize = 128;
+
+/// 每次查询等待应答的时间
+const QUERY_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// 查询次数，包括补充查询主机地址
+const ATTEMPTS: usize = 3;
+
+/// 报文头长度
+const HEADER_LEN: usize = 12;
+
+/// 读取一个域名最多处理的标签和压缩指针数量，防止指针成环
+const MAX_LABELS: usize = 32;
+
+/// 记录类型
+const TYPE_A: u16 = 1;
+const TYPE_PTR: u16 = 12;
+const TYPE_SRV: u16 = 33;
+
+/// 类别 IN
+const CLASS_IN: u16 = 1;
+
+/// 问题类别的最高位：要求单播应答
+const UNICAST_RESPONSE: u16 = 0x8000;
+
+/// 发现的服务
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Service {
+    /// 服务实例名，例如 `collector._syslog._udp.local`
+    pub instance: String<NAME_LEN>,
+    pub address: IpAddress,
+    pub port: u16,
+}
+
+/// 查找服务
+///
+/// # 参数
+/// * `stack` - 网络协议栈，应已获取地址
+/// * `service` - 服务类型，例如 `_syslog._udp.local`
+///
+/// # 返回
+/// 最先应答的服务实例，没有找到时返回 None
+pub async fn browse(stack: Stack<'_>, service: &str) -> Option<Service> {
+    let mut rx_meta = [PacketMetadata::EMPTY; 4];
+    let mut rx_buffer = [0u8; PACKET_LEN * 2];
+    let mut tx_meta = [PacketMetadata::EMPTY; 1];
+    let mut tx_buffer = [0u8; PACKET_LEN];
+    let mut socket = UdpSocket::new(
+        stack,
+        &mut rx_meta,
+        &mut rx_buffer,
+        &mut tx_meta,
+        &mut tx_buffer,
+    );
+    if let Err(err) = socket.bind(0) {
+        warn!("Failed to bind mDNS socket: {}", defmt::Debug2Format(&err));
+        return None;
+    }
+
+    let mut packet = [0u8; PACKET_LEN];
+    let mut found = Found::default();
+    for _ in 0..ATTEMPTS {
+        // 已经知道主机名、只缺地址时查询 A 记录
+        let (name, kind) = match &found.target {
+            Some(target) if found.address.is_none() => (target.as_str(), TYPE_A),
+            _ => (service, TYPE_PTR),
+        };
+        let len = encode_query(&mut packet, name, kind)?;
+        if let Err(err) = socket
+            .send_to(&packet[..len], (MDNS_ADDRESS, MDNS_PORT))
+            .await
+        {
+            warn!("mDNS query failed: {}", defmt::Debug2Format(&err));
+            return None;
+        }
+        netstats::sent(Link::Mdns, len);
+
+        // 收集等待时间内的所有应答
+        let deadline = Instant::now() + QUERY_TIMEOUT;
+        while let Ok(Ok((len, _))) = with_deadline(deadline, socket.recv_from(&mut packet)).await {
+            netstats::received(Link::Mdns, len);
+            found.update(&packet[..len], service);
+        }
+        if let Some(result) = found.service() {
+            info!(
+                "Found {} at {}:{}",
+                result.instance.as_str(),
+                result.address,
+                result.port
+            );
+            return Some(result);
+        }
+    }
+    debug!("No {} service found", service);
+    None
+}
+
+/// 从应答中收集到的信息
+#[derive(Debug, Default)]
+struct Found {
+    /// PTR 记录给出的服务实例名
+    instance: Option<String<NAME_LEN>>,
+    /// SRV 记录给出的主机名
+    target: Option<String<NAME_LEN>>,
+    port: u16,
+    /// A 记录给出的主机地址
+    address: Option<Ipv4Address>,
+}
+
+impl Found {
+    /// 处理一个应答报文
+    ///
+    /// 按 PTR、SRV、A 的顺序各扫描一遍记录，记录在报文中的顺序不影响结果
+    fn update(&mut self, packet: &[u8], service: &str) {
+        let matches = |name: &Option<String<NAME_LEN>>, record: &str| {
+            name.as_ref()
+                .is_some_and(|name| name.eq_ignore_ascii_case(record))
+        };
+        for wanted in [TYPE_PTR, TYPE_SRV, TYPE_A] {
+            for_each_record(packet, |name, kind, data| match kind {
+                _ if kind != wanted => {}
+                TYPE_PTR if self.instance.is_none() && name.eq_ignore_ascii_case(service) => {
+                    self.instance = read_name(packet, data).map(|(instance, _)| instance);
+                }
+                TYPE_SRV if matches(&self.instance, name) => {
+                    // 优先级、权重、端口，然后是主机名
+                    if let Some(port) = packet.get(data + 4..data + 6) {
+                        self.port = u16::from_be_bytes([port[0], port[1]]);
+                        self.target = read_name(packet, data + 6).map(|(target, _)| target);
+                    }
+                }
+                TYPE_A if matches(&self.target, name) => {
+                    if let Some(a) = packet.get(data..data + 4) {
+                        self.address = Some(Ipv4Address::new(a[0], a[1], a[2], a[3]));
+                    }
+                }
+                _ => {}
+            });
+        }
+    }
+
+    /// 信息齐全时返回服务
+    fn service(&self) -> Option<Service> {
+        Some(Service {
+            instance: self.instance.clone()?,
+            address: IpAddress::Ipv4(self.address?),
+            port: self.port,
+        })
+    }
+}
+
+/// 编码查询报文
+///
+/// # 返回
+/// 报文长度，名称不合法或超出缓冲区时返回 None
+fn encode_query(buf: &mut [u8], name: &str, kind: u16) -> Option<usize> {
+    // 事务 ID 和标志均为 0（标准查询），一个问题
+    buf.get_mut(..HEADER_LEN)?
+        .copy_from_slice(&[0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0]);
+    let mut pos = HEADER_LEN;
+    for label in name.split('.') {
+        let label = label.as_bytes();
+        if label.is_empty() || label.len() > 63 {
+            return None;
+        }
+        *buf.get_mut(pos)? = label.len() as u8;
+        buf.get_mut(pos + 1..pos + 1 + label.len())?
+            .copy_from_slice(label);
+        pos += 1 + label.len();
+    }
+    let tail = buf.get_mut(pos..pos + 5)?;
+    tail[0] = 0;
+    tail[1..3].copy_from_slice(&kind.to_be_bytes());
+    tail[3..].copy_from_slice(&(CLASS_IN | UNICAST_RESPONSE).to_be_bytes());
+    Some(pos + 5)
+}
+
+/// 逐条处理应答报文中的资源记录（应答、授权和附加部分）
+///
+/// # 参数
+/// * `f` - 参数为记录名、类型和数据在报文中的位置
+fn for_each_record(packet: &[u8], mut f: impl FnMut(&str, u16, usize)) -> Option<()> {
+    let header = packet.get(..HEADER_LEN)?;
+    // 只处理应答
+    if header[2] & 0x80 == 0 {
+        return None;
+    }
+    let count = |i: usize| u16::from_be_bytes([header[i], header[i + 1]]) as usize;
+    let mut pos = HEADER_LEN;
+    for _ in 0..count(4) {
+        // 问题：名称、类型和类别
+        pos = read_name(packet, pos)?.1 + 4;
+    }
+    for _ in 0..count(6) + count(8) + count(10) {
+        let (name, after) = read_name(packet, pos)?;
+        // 类型、类别、TTL 和数据长度
+        let fixed = packet.get(after..after + 10)?;
+        let kind = u16::from_be_bytes([fixed[0], fixed[1]]);
+        let len = u16::from_be_bytes([fixed[8], fixed[9]]) as usize;
+        let data = after + 10;
+        packet.get(data..data + len)?;
+        f(&name, kind, data);
+        pos = data + len;
+    }
+    Some(())
+}
+
+/// 读取域名，处理压缩指针
+///
+/// # 返回
+/// 以 `.` 分隔的名称和名称之后的位置
+fn read_name(packet: &[u8], mut pos: usize) -> Option<(String<NAME_LEN>, usize)> {
+    let mut name = String::new();
+    // 第一个压缩指针之后的位置
+    let mut end = None;
+    for _ in 0..MAX_LABELS {
+        let len = *packet.get(pos)? as usize;
+        if len & 0xC0 == 0xC0 {
+            let low = *packet.get(pos + 1)? as usize;
+            end.get_or_insert(pos + 2);
+            pos = (len & 0x3F) << 8 | low;
+            continue;
+        }
+        if len == 0 {
+            return Some((name, end.unwrap_or(pos + 1)));
+        }
+        let label = packet.get(pos + 1..pos + 1 + len)?;
+        if !name.is_empty() {
+            name.push('.').ok()?;
+        }
+        name.push_str(core::str::from_utf8(label).ok()?).ok()?;
+        pos += 1 + len;
+    }
+    None
+}
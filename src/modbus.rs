@@ -0,0 +1,370 @@
+//! Modbus TCP 服务和 RTU 从站
+//!
+//! 在 502 端口提供 Modbus TCP 从站，PLC/SCADA 系统无需 MQTT 即可读取板载数据和控制输出。
+//! 与 [crate::http] 一样每次处理一个连接，连接内可以连续发送多个请求。
+//! 单元标识符不做检查。报文的编码和检查见 [proto::modbus]。
+//!
+//! 每个客户端每秒最多 [RATE] 个请求，超出时返回异常码 06（从站设备忙，见 [crate::ratelimit]）。
+//!
+//! 扩展排针上接了 RS485 收发器时（`board port rs485 <tx> <rx> <de> [<波特率>]`，见 [crate::board]），
+//! [rtu_task] 同时在 RS485 总线上提供 Modbus RTU 从站，地址表相同。从站地址用命令行
+//! `modbus unit <地址>` 设置，广播（地址 0）的写请求执行但不应答。RTU 从站不受
+//! `service modbus stop` 影响，总线上只有已接线的主站能访问。
+//!
+//! 地址表（均从 0 开始）：
+//!
+//! | 类型 | 地址 | 内容 |
+//! |------|------|------|
+//! | 线圈 (01/05/15) | 0 | LED0 |
+//! | 线圈 (01/05/15) | 1 | LCD 背光 |
+//! | 离散输入 (02) | 0-3 | KEY0-KEY3 是否按下 |
+//! | 离散输入 (02) | 4 | WiFi 是否已连接 |
+//! | 输入寄存器 (04) | 0-1 | 运行时间（秒，高字在前） |
+//! | 输入寄存器 (04) | 2-3 | 空闲堆内存（字节，高字在前） |
+//! | 输入寄存器 (04) | 4-5 | 已用堆内存（字节，高字在前） |
+//! | 保持寄存器 (03/06/16) | 0-1 | 与线圈 0-1 相同，0 为关，非 0 为开 |
+//!
+//! 板上的传感器驱动接入后，其读数追加到输入寄存器中。
+
+use crate::net::{SocketOptions, TcpBuffers};
+use crate::netstats::{self, Link};
+use crate::ratelimit::RateLimiter;
+use crate::rs485::{Rs485, Rs485Error};
+use crate::service::{self, Service};
+use crate::{led, settings, xl9555};
+use defmt::{info, warn};
+use embassy_net::tcp::{Error as TcpError, TcpSocket};
+use embassy_net::Stack;
+use embassy_time::{Duration, Instant};
+use embedded_io_async::Write;
+use esp_radio::wifi::WifiStaState;
+use proto::modbus::{
+    self, BROADCAST, Exception, MAX_PDU_LEN, MAX_READ_BITS, MAX_READ_REGISTERS, MBAP_LEN,
+    RTU_FRAME_LEN, Request, check_range, function, pack_bits, write_data,
+};
+
+/// 监听端口
+const PORT: u16 = 502;
+
+/// Modbus TCP 报文最大长度
+const FRAME_LEN: usize = MBAP_LEN + MAX_PDU_LEN;
+
+/// 每个客户端每秒允许的请求数
+const RATE: u32 = 20;
+
+/// 每个客户端短时间内最多允许的连续请求数
+const BURST: u32 = 40;
+
+/// 限速时记录的客户端数量
+const RATE_CLIENTS: usize = 4;
+
+/// 连接参数：一问一答的小报文，缓冲区容纳一个完整的帧
+const SOCKET: SocketOptions = SocketOptions {
+    timeout: Some(Duration::from_secs(60)),
+    rx_buffer: FRAME_LEN,
+    tx_buffer: FRAME_LEN,
+    ..SocketOptions::DEFAULT
+};
+
+/// RTU 从站地址的范围
+pub const UNITS: core::ops::RangeInclusive<u8> = 1..=247;
+
+/// RTU 从站等待请求的超时，超时后继续等待
+const RTU_IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// 离散输入数量
+const DISCRETE_INPUT_COUNT: u16 = 5;
+
+/// 输入寄存器数量
+const INPUT_REGISTER_COUNT: u16 = 6;
+
+/// 可写输出，线圈和保持寄存器都映射到这里
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+enum Output {
+    Led0,
+    Backlight,
+}
+
+impl Output {
+    const ALL: [Output; 2] = [Output::Led0, Output::Backlight];
+
+    async fn get(self) -> bool {
+        match self {
+            Output::Led0 => led::led0_is_on().await,
+            Output::Backlight => xl9555::lcd_backlight(),
+        }
+    }
+
+    async fn set(self, on: bool) {
+        info!("Modbus: {} -> {}", self, on);
+        match self {
+            Output::Led0 => led::led0_set(on).await,
+            Output::Backlight => {
+                if let Err(err) = xl9555::set_lcd_backlight(on).await {
+                    warn!("Modbus: failed to set {}: {}", self, err);
+                }
+            }
+        }
+    }
+}
+
+/// 离散输入
+fn discrete_input(address: u16) -> bool {
+    match address {
+        0..=3 => xl9555::key_states()[address as usize],
+        _ => esp_radio::wifi::sta_state() == WifiStaState::Connected,
+    }
+}
+
+/// 输入寄存器
+fn input_register(address: u16) -> u16 {
+    let value = match address / 2 {
+        0 => Instant::now().as_secs() as u32,
+        1 => esp_alloc::HEAP.free() as u32,
+        _ => esp_alloc::HEAP.used() as u32,
+    };
+    // 32 位数值占两个寄存器，高字在前
+    if address.is_multiple_of(2) {
+        (value >> 16) as u16
+    } else {
+        value as u16
+    }
+}
+
+/// Modbus TCP 服务任务
+///
+/// 服务停止时关闭所有连接并释放缓冲区（见 [crate::service]）
+#[embassy_executor::task]
+pub async fn server(stack: Stack<'static>) {
+    loop {
+        service::wait_started(Service::Modbus).await;
+        service::run(Service::Modbus, serve(stack)).await;
+    }
+}
+
+/// 监听端口并逐个处理连接
+async fn serve(stack: Stack<'static>) {
+    let mut buffers = TcpBuffers::new(SOCKET);
+    let mut limiter = RateLimiter::<RATE_CLIENTS>::new(RATE, BURST);
+
+    stack.wait_config_up().await;
+    info!("Modbus TCP server listening on port {}", PORT);
+
+    loop {
+        let mut socket = buffers.socket(stack);
+
+        if let Err(err) = socket.accept(PORT).await {
+            warn!("Modbus accept failed: {}", err);
+            continue;
+        }
+        netstats::opened(Link::Modbus);
+
+        // TCP 错误只有连接复位一种（对端断开或超时），属于正常结束
+        handle(&mut socket, &mut limiter).await.ok();
+        socket.close();
+        socket.flush().await.ok();
+    }
+}
+
+/// 处理一个连接上的所有请求，对方关闭连接时返回
+async fn handle(
+    socket: &mut TcpSocket<'_>,
+    limiter: &mut RateLimiter<RATE_CLIENTS>,
+) -> Result<(), TcpError> {
+    let mut header = [0u8; MBAP_LEN];
+    let mut request = [0u8; MAX_PDU_LEN];
+    let mut response = [0u8; FRAME_LEN];
+    let peer = socket.remote_endpoint().map(|endpoint| endpoint.addr);
+
+    loop {
+        if !read_exact(socket, &mut header).await? {
+            return Ok(());
+        }
+        let Some(pdu_len) = modbus::mbap_pdu_len(&header) else {
+            warn!("Modbus: invalid MBAP header, closing connection");
+            return Ok(());
+        };
+        let pdu = &mut request[..pdu_len];
+        if !read_exact(socket, pdu).await? {
+            return Ok(());
+        }
+
+        let result = if peer.is_some_and(|addr| !limiter.allow(addr)) {
+            Err(Exception::ServerDeviceBusy)
+        } else {
+            process(pdu, &mut response[MBAP_LEN..]).await
+        };
+        let pdu_len = match result {
+            Ok(len) => len,
+            Err(exception) => exception_pdu(pdu[0], exception, &mut response[MBAP_LEN..]),
+        };
+
+        // 事务标识符、协议标识符和单元标识符原样返回
+        modbus::mbap_response(&header, pdu_len, &mut response);
+        socket.write_all(&response[..MBAP_LEN + pdu_len]).await?;
+        netstats::sent(Link::Modbus, MBAP_LEN + pdu_len);
+    }
+}
+
+/// Modbus RTU 从站任务
+///
+/// # 参数
+/// * `bus` - 接在 RS485 总线上的串口
+#[embassy_executor::task]
+pub async fn rtu_task(mut bus: Rs485) {
+    let mut request = [0u8; RTU_FRAME_LEN];
+    let mut response = [0u8; RTU_FRAME_LEN];
+
+    info!("Modbus RTU slave started");
+    loop {
+        let len = match bus.receive(&mut request, RTU_IDLE_TIMEOUT).await {
+            Ok(len) => len,
+            Err(Rs485Error::Timeout) => continue,
+            Err(err) => {
+                warn!("Modbus RTU receive failed: {}", err);
+                continue;
+            }
+        };
+        // 过短、CRC 错误或发给其他从站的帧直接丢弃
+        let Some((unit, pdu)) = modbus::rtu_pdu(&request[..len]) else {
+            continue;
+        };
+        if unit != BROADCAST && unit != settings::get().modbus_unit {
+            continue;
+        }
+
+        let result = process(pdu, &mut response[1..RTU_FRAME_LEN - 2]).await;
+        if unit == BROADCAST {
+            continue;
+        }
+        let pdu_len = match result {
+            Ok(len) => len,
+            Err(exception) => exception_pdu(pdu[0], exception, &mut response[1..]),
+        };
+        let len = modbus::seal_rtu(unit, pdu_len, &mut response);
+        if let Err(err) = bus.send(&response[..len]).await {
+            warn!("Modbus RTU send failed: {}", err);
+        }
+    }
+}
+
+/// 记录日志并写入异常应答 PDU
+///
+/// # 返回
+/// 应答 PDU 长度
+fn exception_pdu(code: u8, exception: Exception, out: &mut [u8]) -> usize {
+    warn!("Modbus: function {=u8:#x} failed: {}", code, exception);
+    modbus::exception_pdu(code, exception, out)
+}
+
+/// 读满缓冲区
+///
+/// # 返回
+/// 对方在读满之前关闭连接时返回 false
+async fn read_exact(socket: &mut TcpSocket<'_>, buf: &mut [u8]) -> Result<bool, TcpError> {
+    let mut len = 0;
+    while len < buf.len() {
+        let read = socket.read(&mut buf[len..]).await?;
+        netstats::received(Link::Modbus, read);
+        if read == 0 {
+            return Ok(false);
+        }
+        len += read;
+    }
+    Ok(true)
+}
+
+/// 处理请求 PDU
+///
+/// # 参数
+/// * `pdu` - 请求 PDU（功能码 + 数据）
+/// * `out` - 响应 PDU 缓冲区
+///
+/// # 返回
+/// 响应 PDU 长度
+async fn process(pdu: &[u8], out: &mut [u8]) -> Result<usize, Exception> {
+    let Request {
+        code,
+        address,
+        value,
+    } = Request::parse(pdu)?;
+    out[0] = code;
+
+    match code {
+        function::READ_COILS => {
+            let outputs = output_range(address, value, MAX_READ_BITS)?;
+            let mut bits = [false; Output::ALL.len()];
+            for (bit, output) in bits.iter_mut().zip(outputs) {
+                *bit = output.get().await;
+            }
+            Ok(pack_bits(&bits[..outputs.len()], out))
+        }
+        function::READ_DISCRETE_INPUTS => {
+            check_range(address, value, DISCRETE_INPUT_COUNT, MAX_READ_BITS)?;
+            let mut bits = [false; DISCRETE_INPUT_COUNT as usize];
+            for (offset, bit) in bits.iter_mut().take(value as usize).enumerate() {
+                *bit = discrete_input(address + offset as u16);
+            }
+            Ok(pack_bits(&bits[..value as usize], out))
+        }
+        function::READ_HOLDING_REGISTERS => {
+            let outputs = output_range(address, value, MAX_READ_REGISTERS)?;
+            out[1] = (outputs.len() * 2) as u8;
+            for (index, output) in outputs.iter().enumerate() {
+                let register = output.get().await as u16;
+                out[2 + index * 2..4 + index * 2].copy_from_slice(&register.to_be_bytes());
+            }
+            Ok(2 + outputs.len() * 2)
+        }
+        function::READ_INPUT_REGISTERS => {
+            check_range(address, value, INPUT_REGISTER_COUNT, MAX_READ_REGISTERS)?;
+            out[1] = (value * 2) as u8;
+            for index in 0..value as usize {
+                let register = input_register(address + index as u16);
+                out[2 + index * 2..4 + index * 2].copy_from_slice(&register.to_be_bytes());
+            }
+            Ok(2 + value as usize * 2)
+        }
+        function::WRITE_SINGLE_COIL => {
+            let on = match value {
+                0xFF00 => true,
+                0x0000 => false,
+                _ => return Err(Exception::IllegalDataValue),
+            };
+            output_range(address, 1, 1)?[0].set(on).await;
+            // 响应与请求相同
+            out[..5].copy_from_slice(&pdu[..5]);
+            Ok(5)
+        }
+        function::WRITE_SINGLE_REGISTER => {
+            output_range(address, 1, 1)?[0].set(value != 0).await;
+            out[..5].copy_from_slice(&pdu[..5]);
+            Ok(5)
+        }
+        function::WRITE_MULTIPLE_COILS => {
+            let outputs = output_range(address, value, MAX_READ_BITS)?;
+            let data = write_data(pdu, outputs.len().div_ceil(8))?;
+            for (index, output) in outputs.iter().enumerate() {
+                output.set(data[index / 8] & (1 << (index % 8)) != 0).await;
+            }
+            out[..5].copy_from_slice(&pdu[..5]);
+            Ok(5)
+        }
+        function::WRITE_MULTIPLE_REGISTERS => {
+            let outputs = output_range(address, value, MAX_READ_REGISTERS)?;
+            let data = write_data(pdu, outputs.len() * 2)?;
+            for (register, output) in data.chunks_exact(2).zip(outputs) {
+                output.set(register != [0, 0]).await;
+            }
+            out[..5].copy_from_slice(&pdu[..5]);
+            Ok(5)
+        }
+        _ => Err(Exception::IllegalFunction),
+    }
+}
+
+/// 检查范围并返回对应的输出
+fn output_range(address: u16, count: u16, max: u16) -> Result<&'static [Output], Exception> {
+    check_range(address, count, Output::ALL.len() as u16, max)?;
+    Ok(&Output::ALL[address as usize..(address + count) as usize])
+}
@@ -0,0 +1,21 @@
+//! 驱动逻辑使用的时间源
+//!
+//! [drivers::clock::Clock] 在固件中的实现，读取 embassy-time 的时钟。驱动中的计时逻辑
+//! （例如按键消抖 [drivers::clock::Debouncer]）只依赖该 trait，在主机上测试时换成
+//! [drivers::clock::MockClock]。
+//!
+//! embassy-time 的计数频率为 1 MHz，与直接读取 SYSTIMER 的 `esp_hal::time::Instant`
+//! 精度相同，因此目前只有这一种实现。
+
+use drivers::clock::Clock;
+use embassy_time::Instant;
+
+/// embassy-time 时钟
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Monotonic;
+
+impl Clock for Monotonic {
+    fn now_us(&self) -> u64 {
+        Instant::now().as_micros()
+    }
+}
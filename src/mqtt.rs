@@ -0,0 +1,862 @@
+//! MQTT 客户端
+//!
+//! 最小的 MQTT 3.1.1 客户端，报文的编解码见 [proto::mqtt]。
+//! 连接设置中的代理（命令行 `mqtt broker <host>[:<port>]`），
+//! 没有设置时用 mDNS 在局域网中查找 `_mqtt._tcp` 服务（见 [crate::mdns]），
+//! 只使用 QoS 0，不需要保存未确认的消息。所有主题都在基础主题（[base_topic]）之下，
+//! 默认为 `esp-app-4/<主机名>`，可以在设置中修改：
+//!
+//! - `<基础主题>/status`：保留消息，连接后为 `online`；遗嘱消息（LWT）为 `offline`，
+//!   板子掉线后由代理发布。正常重启或休眠前 [shutdown] 主动发布 `offline` 再断开
+//! - `<基础主题>/cmd/<命令>`：订阅的命令，消息内容是命令的参数（UTF-8 文本），见 [handle_command]
+//! - `<基础主题>/telemetry`：传感器读数，由定时任务的 `publish` 动作发布（见 [publish_telemetry]）
+//! - `<基础主题>/shadow/desired`、`<基础主题>/shadow/reported`：设备影子，见 [crate::shadow]
+//! - 其他模块用 [publish] 发布到 `<基础主题>/<子主题>`；需要与其他设备交换消息的模块
+//!   用 [publish_to] 发布到完整的主题，用 [subscribe] 订阅基础主题以外的主题
+//!
+//! 未连接时 [publish] 直接丢弃消息（QoS 0）；遥测消息例外，设置或找到了代理时转存到 TF 卡
+//! （见 [crate::outbox::TELEMETRY]），下次连接后先按时间顺序发布卡上的消息，再发布新消息。
+//! 发布只保证写入了 TCP 发送缓冲区，随后断开时这几条消息仍可能丢失。
+//! 连接断开后按 [RETRY_MIN] 起加倍、最长 [RETRY_MAX] 的间隔重连。代理地址修改后重启生效。
+//!
+//! 设置了 IoT Hub 设备密钥时用 SAS 令牌代替用户名和密码，见 [crate::sas]。
+//!
+//! 限制：只支持明文 TCP，不支持 TLS 和 X.509 客户端证书，用户名和密码在局域网中以明文传输；
+//! 命令和影子的期望状态不检查 PIN 和令牌，由代理的认证和主题权限控制谁能发送，见 [crate::access]。
+
+use crate::access;
+use crate::dmx;
+use crate::mdns;
+use crate::net::{self, SocketOptions, TcpBuffers};
+use crate::netstats::{self, Link};
+#[cfg(all(feature = "sd", feature = "ui"))]
+use crate::photo;
+use crate::service::{self, Service};
+use crate::settings::{self, MQTT_TOPIC_LEN, Settings};
+use crate::system::{self, RebootReason};
+#[cfg(feature = "sd")]
+use crate::{outbox, sdcard};
+use crate::{pid, sas, scheduler, sensor, shadow, thermostat, wallclock};
+use alloc::vec::Vec;
+use core::cell::RefCell;
+use core::fmt::Write as _;
+use core::sync::atomic::{AtomicBool, Ordering};
+use critical_section::Mutex;
+use defmt::{info, warn};
+use embassy_futures::select::{Either4, select4};
+use embassy_net::dns::DnsQueryType;
+use embassy_net::tcp::TcpSocket;
+use embassy_net::{IpAddress, Stack};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::signal::Signal;
+use embassy_time::{Duration, Instant, Timer, with_timeout};
+use embedded_io_async::Write;
+use heapless::{Deque, String};
+use proto::json::Object;
+use proto::mqtt::{
+    self, DISCONNECT, Malformed, PINGREQ, PINGRESP, PUBLISH, SUBACK, connect_packet,
+    publish_packet, subscribe_packet,
+};
+
+/// 默认的代理端口
+const DEFAULT_PORT: u16 = 1883;
+
+/// 没有设置代理时用 mDNS 查找的服务
+const SERVICE: &str = "_mqtt._tcp.local";
+
+/// 保活时间（秒），在 CONNECT 中告诉代理
+const KEEP_ALIVE_SECS: u16 = 60;
+
+/// 发送 PINGREQ 的间隔，保活时间的一半
+const PING_INTERVAL: Duration = Duration::from_secs(KEEP_ALIVE_SECS as u64 / 2);
+
+/// 等待 CONNACK 的时间
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// 重连的最短和最长间隔
+const RETRY_MIN: Duration = Duration::from_secs(5);
+const RETRY_MAX: Duration = Duration::from_secs(300);
+
+/// [shutdown] 等待发出离线消息的最长时间
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// 基础主题的最大长度
+const BASE_TOPIC_LEN: usize = MQTT_TOPIC_LEN;
+
+/// 子主题的最大长度
+pub const SUBTOPIC_LEN: usize = 32;
+
+/// 完整主题的最大长度
+pub const TOPIC_LEN: usize = BASE_TOPIC_LEN + 1 + SUBTOPIC_LEN;
+
+/// 接收缓冲区大小，更长的报文被跳过
+const RX_LEN: usize = 512;
+
+/// 发送队列容量
+const QUEUE_LEN: usize = 8;
+
+/// 订阅命令和设备影子时使用的报文标识符，[subscribe] 的订阅依次加 1
+const SUBSCRIBE_ID: u16 = 1;
+
+/// [subscribe] 最多的订阅数
+const MAX_LISTENERS: usize = 2;
+
+/// 连接参数：保活由 MQTT 的 PINGREQ 完成，超时略长于代理的保活判定
+const SOCKET: SocketOptions = SocketOptions {
+    timeout: Some(Duration::from_secs(KEEP_ALIVE_SECS as u64 * 3 / 2)),
+    ..SocketOptions::DEFAULT
+};
+
+/// 是否设置或找到了代理
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// 是否已连接到代理
+static CONNECTED: AtomicBool = AtomicBool::new(false);
+
+/// 待发布的消息
+static QUEUE: Mutex<RefCell<Deque<Message, QUEUE_LEN>>> = Mutex::new(RefCell::new(Deque::new()));
+
+/// 其他模块的订阅
+static LISTENERS: Mutex<RefCell<heapless::Vec<Listener, MAX_LISTENERS>>> =
+    Mutex::new(RefCell::new(heapless::Vec::new()));
+
+/// 有新消息入队或新增了订阅
+static QUEUED: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+
+/// 请求发布离线消息并断开
+static SHUTDOWN: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+
+/// 离线消息已发出
+static SHUTDOWN_DONE: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+
+/// MQTT 错误
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum MqttError {
+    /// 无法建立 TCP 连接
+    Connect,
+    /// 发送或接收失败（包括超时）
+    Io,
+    /// 代理拒绝连接，值为 CONNACK 的返回码
+    Refused(u8),
+    /// 报文格式错误
+    Protocol,
+    /// 无法生成 SAS 令牌（密钥无效、系统时间未校准或代理不是按主机名设置的）
+    Credentials,
+}
+
+impl From<Malformed> for MqttError {
+    fn from(_: Malformed) -> Self {
+        MqttError::Protocol
+    }
+}
+
+/// 一条待发布的消息
+struct Message {
+    /// 子主题，`absolute` 时为完整的主题
+    topic: String<TOPIC_LEN>,
+    absolute: bool,
+    payload: Vec<u8>,
+    retain: bool,
+}
+
+/// 订阅消息的处理函数，参数为主题和消息内容，在客户端任务中调用，不能等待
+pub type Handler = fn(&str, &[u8]);
+
+/// 一个订阅
+#[derive(Clone, Copy)]
+struct Listener {
+    filter: &'static str,
+    handler: Handler,
+}
+
+/// 会话结束的原因
+enum Exit {
+    /// 已发布离线消息并断开（[shutdown]）
+    Shutdown,
+    /// 收到 `reboot` 命令，已发布离线消息并断开
+    Reboot,
+}
+
+/// 解析代理地址 `<host>[:<port>]`
+pub fn parse_broker(broker: &str) -> Option<(&str, u16)> {
+    let (host, port) = match broker.split_once(':') {
+        Some((host, port)) => (host, port.parse().ok()?),
+        None => (broker, DEFAULT_PORT),
+    };
+    (!host.is_empty()).then_some((host, port))
+}
+
+/// 基础主题是否合法：不为空，不含通配符 `+`、`#`，不以 `/` 开头或结尾
+pub fn is_valid_base_topic(topic: &str) -> bool {
+    (1..=BASE_TOPIC_LEN).contains(&topic.len())
+        && !topic.contains(['+', '#'])
+        && !topic.starts_with('/')
+        && !topic.ends_with('/')
+}
+
+/// 当前的基础主题：设置中的主题，未设置时为 `esp-app-4/<主机名>`
+pub fn base_topic() -> String<BASE_TOPIC_LEN> {
+    let configured = settings::get().mqtt_topic;
+    let mut topic = String::new();
+    if configured.is_empty() {
+        write!(topic, "esp-app-4/{}", net::hostname()).ok();
+    } else {
+        topic.push_str(&configured).ok();
+    }
+    topic
+}
+
+/// 是否已连接到代理
+pub fn is_connected() -> bool {
+    CONNECTED.load(Ordering::Relaxed)
+}
+
+/// 发布消息到 `<基础主题>/<subtopic>`
+///
+/// # 参数
+/// * `subtopic` - 子主题，最长 [SUBTOPIC_LEN] 字节
+/// * `payload` - 消息内容
+/// * `retain` - 是否要求代理保留
+///
+/// # 返回
+/// 未连接、子主题过长或发送队列已满时丢弃消息并返回 false
+pub fn publish(subtopic: &str, payload: &[u8], retain: bool) -> bool {
+    subtopic.len() <= SUBTOPIC_LEN && enqueue(subtopic, false, payload, retain)
+}
+
+/// 发布消息到完整的主题 `topic`，不加基础主题
+///
+/// # 参数
+/// * `topic` - 主题，最长 [TOPIC_LEN] 字节
+/// * `payload` - 消息内容
+/// * `retain` - 是否要求代理保留
+///
+/// # 返回
+/// 未连接、主题过长或发送队列已满时丢弃消息并返回 false
+pub fn publish_to(topic: &str, payload: &[u8], retain: bool) -> bool {
+    enqueue(topic, true, payload, retain)
+}
+
+/// 把消息放入发送队列
+fn enqueue(topic: &str, absolute: bool, payload: &[u8], retain: bool) -> bool {
+    if !is_connected() {
+        return false;
+    }
+    let Ok(topic) = String::try_from(topic) else {
+        return false;
+    };
+    let message = Message {
+        topic,
+        absolute,
+        payload: payload.to_vec(),
+        retain,
+    };
+    let queued = critical_section::with(|cs| QUEUE.borrow_ref_mut(cs).push_back(message).is_ok());
+    if queued {
+        QUEUED.signal(());
+    }
+    queued
+}
+
+/// 订阅基础主题以外的主题
+///
+/// 已连接时立即订阅，之后每次连接时重新订阅。收到的消息不再转发给其他订阅
+///
+/// # 参数
+/// * `filter` - 主题过滤器，可以使用通配符 `+` 和 `#`
+/// * `handler` - 处理函数
+///
+/// # 返回
+/// 订阅数已达 [MAX_LISTENERS] 时返回 false
+pub fn subscribe(filter: &'static str, handler: Handler) -> bool {
+    let listener = Listener { filter, handler };
+    let added = critical_section::with(|cs| LISTENERS.borrow_ref_mut(cs).push(listener).is_ok());
+    if added {
+        QUEUED.signal(());
+    }
+    added
+}
+
+/// 把当前的传感器读数发布到 `<基础主题>/telemetry`
+///
+/// 消息内容为 JSON：`{"time":1760000000,"uptime":1234,"readings":{"bme280.t":23.5}}`，
+/// 系统时间尚未校准时没有 `time`。未连接时转存到 TF 卡，连接后补发，`time` 是采集的时间
+///
+/// # 返回
+/// 已发布或已转存时返回 true
+pub async fn publish_telemetry() -> bool {
+    let mut body = alloc::string::String::new();
+    {
+        let mut object = Object::new(&mut body);
+        if let Some(time) = wallclock::now() {
+            object.int("time", time as i64);
+        }
+        object.int("uptime", Instant::now().as_secs() as i64);
+        let mut readings = object.object("readings");
+        for reading in sensor::all() {
+            readings.number(reading.name, reading.value);
+        }
+    }
+    publish("telemetry", body.as_bytes(), false) || store_telemetry(&body).await
+}
+
+/// 把遥测消息转存到 TF 卡，没有设置也没有找到代理时不转存，以免卡上的消息无处发送
+///
+/// # 返回
+/// 是否已转存
+#[cfg(feature = "sd")]
+async fn store_telemetry(body: &str) -> bool {
+    if !ENABLED.load(Ordering::Relaxed) || !sdcard::is_mounted() {
+        return false;
+    }
+    outbox::TELEMETRY
+        .push(body)
+        .await
+        .inspect_err(|err| warn!("Failed to store telemetry: {}", defmt::Debug2Format(err)))
+        .is_ok()
+}
+
+/// 没有 TF 卡支持时无处转存
+#[cfg(not(feature = "sd"))]
+async fn store_telemetry(_body: &str) -> bool {
+    false
+}
+
+/// 发布离线消息并断开，由 [crate::system] 在重启或休眠前调用
+///
+/// 未连接时立即返回，最多等待 [SHUTDOWN_TIMEOUT]
+pub async fn shutdown() {
+    if !is_connected() {
+        return;
+    }
+    SHUTDOWN.signal(());
+    if with_timeout(SHUTDOWN_TIMEOUT, SHUTDOWN_DONE.wait())
+        .await
+        .is_err()
+    {
+        warn!("MQTT offline message not sent in time");
+    }
+}
+
+/// 执行代理发来的命令
+///
+/// - `reboot`：发布离线消息后重启
+/// - `dmx`：`<通道>=<值>&...`，设置 DMX512 通道（见 [dmx::set_list]）
+/// - `photo`：`next`、`prev` 或 `pause`，控制相框（见 [crate::photo]）
+/// - `schedule`：替换全部定时任务并保存，内容与命令行 `schedule` 相同，`off` 清除
+///   （见 [crate::scheduler]）
+/// - `thermostat`：`<°C>`，修改恒温控制器的设定值并保存（见 [crate::thermostat]）
+/// - `pid`：`<参数>=<值>&...`，调整 PID 回路的 `set`、`kp`、`ki`、`kd`、`period` 并保存
+///   （见 [crate::pid]）
+/// - `service`：`<名称> start|stop`，启动或停止服务，与命令行 `service` 相同（见 [crate::service]）
+///
+/// # 参数
+/// * `command` - 主题中 `cmd/` 之后的部分
+/// * `payload` - 消息内容
+///
+/// # 返回
+/// 需要结束会话时返回结束的原因
+fn handle_command(command: &str, payload: &[u8]) -> Option<Exit> {
+    let Ok(payload) = core::str::from_utf8(payload) else {
+        warn!("MQTT command {} payload is not UTF-8", command);
+        return None;
+    };
+    let handled = match command {
+        "reboot" => return Some(Exit::Reboot),
+        "dmx" => dmx::set_list(payload).is_some(),
+        "schedule" => set_schedule(payload.trim()),
+        "thermostat" => set_setpoint(payload.trim()),
+        "pid" => tune_pid(payload.trim()),
+        "service" => set_service(payload.trim()),
+        #[cfg(all(feature = "sd", feature = "ui"))]
+        "photo" => photo::Command::from_name(payload.trim())
+            .map(photo::command)
+            .is_some(),
+        _ => {
+            warn!("Unknown MQTT command {}", command);
+            return None;
+        }
+    };
+    if !handled {
+        warn!("Invalid MQTT command {}: {}", command, payload);
+    }
+    None
+}
+
+/// 修改恒温控制器的设定值并保存
+///
+/// # 参数
+/// * `text` - 设定值（°C），最多一位小数
+///
+/// # 返回
+/// 格式错误或超出范围时返回 false
+fn set_setpoint(text: &str) -> bool {
+    let tenths = thermostat::parse_tenths(text);
+    let Some(tenths) = tenths.filter(|tenths| thermostat::SETPOINT_RANGE.contains(tenths)) else {
+        return false;
+    };
+    thermostat::set_setpoint(tenths);
+    if let Err(err) = settings::save() {
+        warn!("Failed to save thermostat setpoint: {}", err);
+    }
+    true
+}
+
+/// 调整 PID 回路参数并保存
+///
+/// # 参数
+/// * `list` - `<参数>=<值>&...`
+///
+/// # 返回
+/// 格式错误或参数无效时返回 false，此时之前的参数仍会生效
+fn tune_pid(list: &str) -> bool {
+    let mut valid = true;
+    for pair in list.split('&').filter(|pair| !pair.is_empty()) {
+        let tuned = pair
+            .split_once('=')
+            .is_some_and(|(name, value)| pid::tune(name, value));
+        if !tuned {
+            valid = false;
+            break;
+        }
+    }
+    if let Err(err) = settings::save() {
+        warn!("Failed to save PID settings: {}", err);
+    }
+    valid
+}
+
+/// 启动或停止服务
+///
+/// # 参数
+/// * `text` - `<名称> start|stop`
+///
+/// # 返回
+/// 服务不存在或动作无效时返回 false；服务已处于目标状态时什么也不做
+fn set_service(text: &str) -> bool {
+    let mut words = text.split_ascii_whitespace();
+    let target = words
+        .next()
+        .and_then(Service::from_name)
+        .filter(|s| s.is_available());
+    let Some(service) = target else {
+        return false;
+    };
+    match (words.next(), words.next()) {
+        (Some("start"), None) => service::start(service),
+        (Some("stop"), None) => service::stop(service),
+        _ => return false,
+    };
+    true
+}
+
+/// 替换定时任务并保存
+///
+/// # 返回
+/// 规则无效时返回 false，当前的定时任务不变
+fn set_schedule(rules: &str) -> bool {
+    let rules = if rules == "off" { "" } else { rules };
+    let valid = scheduler::parse(rules).is_ok();
+    let Some(rules) = rules.try_into().ok().filter(|_| valid) else {
+        return false;
+    };
+    settings::update(|s| s.schedule = rules);
+    if let Err(err) = settings::save() {
+        warn!("Failed to save schedule: {}", err);
+    }
+    true
+}
+
+/// MQTT 客户端任务
+///
+/// 没有设置代理时先在局域网中查找，也没有找到时退出。
+/// 由 mDNS 发现的代理每次重连前重新查找，代理换了地址也能连上
+///
+/// # 参数
+/// * `stack` - 网络协议栈
+#[embassy_executor::task]
+pub async fn mqtt_task(stack: Stack<'static>) {
+    let broker = settings::get().mqtt_broker;
+    let configured = parse_broker(&broker);
+    let mut discovered = None;
+    if configured.is_none() {
+        stack.wait_config_up().await;
+        let Some(service) = mdns::browse(stack, SERVICE).await else {
+            info!("No MQTT broker configured or found");
+            return;
+        };
+        discovered = Some((service.address, service.port));
+    }
+    ENABLED.store(true, Ordering::Relaxed);
+
+    let mut buffers = TcpBuffers::new(SOCKET);
+    let mut retry = RETRY_MIN;
+    loop {
+        stack.wait_config_up().await;
+        let target = match configured {
+            Some((host, port)) => stack
+                .dns_query(host, DnsQueryType::A)
+                .await
+                .ok()
+                .and_then(|addresses| addresses.first().map(|&address| (address, port))),
+            None => match discovered.take() {
+                Some(target) => Some(target),
+                None => mdns::browse(stack, SERVICE)
+                    .await
+                    .map(|service| (service.address, service.port)),
+            },
+        };
+        let Some((address, port)) = target else {
+            let name = if broker.is_empty() { SERVICE } else { &broker };
+            warn!("MQTT broker {} not found", name);
+            Timer::after(retry).await;
+            retry = (retry * 2).min(RETRY_MAX);
+            continue;
+        };
+
+        let mut socket = buffers.socket(stack);
+        let result = session(&mut socket, address, port, &mut retry).await;
+        CONNECTED.store(false, Ordering::Relaxed);
+        critical_section::with(|cs| QUEUE.borrow_ref_mut(cs).clear());
+        socket.close();
+        socket.flush().await.ok();
+        drop(socket);
+        match result {
+            Ok(Exit::Reboot) => system::reboot(RebootReason::UserRequest).await,
+            Ok(Exit::Shutdown) => info!("MQTT disconnected for shutdown"),
+            Err(err) => warn!("MQTT connection to {}:{} failed: {}", address, port, err),
+        }
+        Timer::after(retry).await;
+        retry = (retry * 2).min(RETRY_MAX);
+    }
+}
+
+/// 连接代理并处理一次会话
+///
+/// # 参数
+/// * `address`, `port` - 代理的地址和端口
+/// * `retry` - 重连间隔，连接成功后恢复为 [RETRY_MIN]
+async fn session(
+    socket: &mut TcpSocket<'_>,
+    address: IpAddress,
+    port: u16,
+    retry: &mut Duration,
+) -> Result<Exit, MqttError> {
+    let s = settings::get();
+    let client_id = net::hostname();
+    let sas = sas_credentials(&s, &client_id)?;
+    socket
+        .connect((address, port))
+        .await
+        .map_err(|_| MqttError::Connect)?;
+    netstats::opened(Link::Mqtt);
+
+    let base = base_topic();
+    let mut status: String<TOPIC_LEN> = String::new();
+    write!(status, "{}/status", base).ok();
+    let credentials = match &sas {
+        Some(sas) => Some((sas.user.as_str(), sas.password.as_str())),
+        None => (!s.mqtt_user.is_empty()).then_some((&*s.mqtt_user, &*s.mqtt_password)),
+    };
+    if credentials.is_none() && access::is_restricted() {
+        warn!("MQTT commands bypass the PIN and token, set broker credentials");
+    }
+    let packet = connect_packet(&client_id, KEEP_ALIVE_SECS, &status, credentials);
+    send(socket, &packet).await?;
+
+    let mut rx = [0u8; RX_LEN];
+    let mut len = 0;
+    let (kind, body) = with_timeout(CONNECT_TIMEOUT, read_packet(socket, &mut rx, &mut len))
+        .await
+        .map_err(|_| MqttError::Io)??;
+    let code = mqtt::parse_connack(kind, body)?;
+    if code != 0 {
+        return Err(MqttError::Refused(code));
+    }
+    len = 0;
+
+    let mut commands: String<TOPIC_LEN> = String::new();
+    write!(commands, "{}/cmd/+", base).ok();
+    let mut desired: String<TOPIC_LEN> = String::new();
+    write!(desired, "{}/{}", base, shadow::DESIRED).ok();
+    send(
+        socket,
+        &subscribe_packet(SUBSCRIBE_ID, &[&commands, &desired]),
+    )
+    .await?;
+    let mut subscribed = 0;
+    subscribe_new(socket, &mut subscribed).await?;
+    send(socket, &publish_packet(&status, b"online", true)).await?;
+    // 先报告实际状态，代理随后转发保留的期望状态，见 [shadow]
+    let mut reported: String<TOPIC_LEN> = String::new();
+    write!(reported, "{}/{}", base, shadow::REPORTED).ok();
+    send(
+        socket,
+        &publish_packet(&reported, shadow::report().as_bytes(), true),
+    )
+    .await?;
+    info!(
+        "MQTT connected to {}:{} as {}",
+        address,
+        port,
+        base.as_str()
+    );
+    // 卡上的遥测消息比之后发布的早，先全部发出再接受新消息
+    let mut telemetry: String<TOPIC_LEN> = String::new();
+    write!(telemetry, "{}/telemetry", base).ok();
+    send_stored(socket, &telemetry).await?;
+    CONNECTED.store(true, Ordering::Relaxed);
+    *retry = RETRY_MIN;
+    SHUTDOWN.reset();
+
+    let mut next_ping = Instant::now() + PING_INTERVAL;
+    loop {
+        let event = select4(
+            socket.read(&mut rx[len..]),
+            QUEUED.wait(),
+            Timer::at(next_ping),
+            SHUTDOWN.wait(),
+        )
+        .await;
+        match event {
+            Either4::First(Ok(0)) | Either4::First(Err(_)) => return Err(MqttError::Io),
+            Either4::First(Ok(read)) => {
+                netstats::received(Link::Mqtt, read);
+                len += read;
+                while let Some((kind, header_len, body_len)) = mqtt::parse_header(&rx[..len])? {
+                    let end = header_len + body_len;
+                    if end > rx.len() {
+                        // 放不下的报文（例如很长的保留消息）跳过
+                        warn!("MQTT packet of {} bytes too large, skipping", end);
+                        skip(socket, end - len).await?;
+                        len = 0;
+                        break;
+                    }
+                    if len < end {
+                        break;
+                    }
+                    let exit = handle_packet(&base, kind, &rx[header_len..end])?;
+                    rx.copy_within(end..len, 0);
+                    len -= end;
+                    if let Some(exit) = exit {
+                        disconnect(socket, &status).await?;
+                        return Ok(exit);
+                    }
+                }
+            }
+            Either4::Second(()) => {
+                subscribe_new(socket, &mut subscribed).await?;
+                while let Some(message) =
+                    critical_section::with(|cs| QUEUE.borrow_ref_mut(cs).pop_front())
+                {
+                    let topic = if message.absolute {
+                        message.topic
+                    } else {
+                        let mut topic: String<TOPIC_LEN> = String::new();
+                        write!(topic, "{}/{}", base, message.topic).ok();
+                        topic
+                    };
+                    send(
+                        socket,
+                        &publish_packet(&topic, &message.payload, message.retain),
+                    )
+                    .await?;
+                }
+            }
+            Either4::Third(()) => {
+                send(socket, &[PINGREQ, 0]).await?;
+                if let Some(body) = shadow::report_changed() {
+                    send(socket, &publish_packet(&reported, body.as_bytes(), true)).await?;
+                }
+                next_ping = Instant::now() + PING_INTERVAL;
+            }
+            Either4::Fourth(()) => {
+                disconnect(socket, &status).await?;
+                SHUTDOWN_DONE.signal(());
+                return Ok(Exit::Shutdown);
+            }
+        }
+    }
+}
+
+/// 设置了 SAS 密钥时生成 IoT Hub 的连接凭据
+///
+/// # 参数
+/// * `device` - 设备 ID，即客户端标识
+///
+/// # 返回
+/// 没有设置 SAS 密钥时返回 None
+fn sas_credentials(s: &Settings, device: &str) -> Result<Option<sas::Credentials>, MqttError> {
+    if s.mqtt_sas_key.is_empty() {
+        return Ok(None);
+    }
+    // 令牌中的资源是代理的主机名，mDNS 发现的代理没有主机名
+    let host = parse_broker(&s.mqtt_broker).map(|(host, _)| host);
+    let credentials = host.and_then(|host| sas::credentials(host, device, &s.mqtt_sas_key));
+    credentials.map(Some).ok_or(MqttError::Credentials)
+}
+
+/// 发送本次会话中还没有发送的 [subscribe] 订阅
+///
+/// # 参数
+/// * `subscribed` - 已发送的订阅数
+async fn subscribe_new(
+    socket: &mut TcpSocket<'_>,
+    subscribed: &mut usize,
+) -> Result<(), MqttError> {
+    loop {
+        let listener =
+            critical_section::with(|cs| LISTENERS.borrow_ref(cs).get(*subscribed).copied());
+        let Some(listener) = listener else {
+            return Ok(());
+        };
+        *subscribed += 1;
+        let id = SUBSCRIBE_ID + *subscribed as u16;
+        send(socket, &subscribe_packet(id, &[listener.filter])).await?;
+    }
+}
+
+/// 处理收到的报文
+///
+/// # 返回
+/// 需要结束会话时返回结束的原因
+fn handle_packet(base: &str, kind: u8, body: &[u8]) -> Result<Option<Exit>, MqttError> {
+    match kind & 0xF0 {
+        PUBLISH => {
+            let (topic, payload) = mqtt::parse_publish(kind, body)?;
+            let subtopic = topic
+                .strip_prefix(base)
+                .and_then(|rest| rest.strip_prefix('/'));
+            if let Some(command) = subtopic.and_then(|rest| rest.strip_prefix("cmd/")) {
+                return Ok(handle_command(command, payload));
+            }
+            if subtopic == Some(shadow::DESIRED) {
+                shadow::apply_desired(payload);
+                // 无论是否应用都报告实际状态，云端据此知道期望状态是否生效
+                publish(shadow::REPORTED, shadow::report().as_bytes(), true);
+                return Ok(None);
+            }
+            let listeners = critical_section::with(|cs| LISTENERS.borrow_ref(cs).clone());
+            if let Some(listener) = listeners
+                .iter()
+                .find(|listener| mqtt::topic_matches(listener.filter, topic))
+            {
+                (listener.handler)(topic, payload);
+            }
+            Ok(None)
+        }
+        SUBACK => {
+            if let Some(id) = mqtt::rejected_subscription(body) {
+                warn!("MQTT broker rejected subscription {}", id);
+            }
+            Ok(None)
+        }
+        PINGRESP => Ok(None),
+        _ => Err(MqttError::Protocol),
+    }
+}
+
+/// 发布离线消息并发送 DISCONNECT
+///
+/// 主动断开时代理不发布遗嘱消息，因此先自己发布
+async fn disconnect(socket: &mut TcpSocket<'_>, status: &str) -> Result<(), MqttError> {
+    send(socket, &publish_packet(status, b"offline", true)).await?;
+    send(socket, &[DISCONNECT, 0]).await?;
+    socket.flush().await.map_err(|_| MqttError::Io)
+}
+
+/// 发布 TF 卡上转存的遥测消息，发出一条删除一条
+///
+/// # 参数
+/// * `topic` - 遥测主题
+#[cfg(feature = "sd")]
+async fn send_stored(socket: &mut TcpSocket<'_>, topic: &str) -> Result<(), MqttError> {
+    if !sdcard::is_mounted() {
+        return Ok(());
+    }
+    let mut record = alloc::vec![0u8; outbox::MAX_RECORD_LEN + 1];
+    let mut count = 0;
+    loop {
+        let len = match outbox::TELEMETRY.peek(&mut record).await {
+            Ok(Some(len)) => len,
+            Ok(None) => break,
+            Err(err) => {
+                warn!(
+                    "Failed to read stored telemetry: {}",
+                    defmt::Debug2Format(&err)
+                );
+                break;
+            }
+        };
+        send(socket, &publish_packet(topic, &record[..len], false)).await?;
+        count += 1;
+        if let Err(err) = outbox::TELEMETRY.pop(len).await {
+            warn!(
+                "Failed to remove sent telemetry: {}",
+                defmt::Debug2Format(&err)
+            );
+            break;
+        }
+    }
+    if count > 0 {
+        info!("Published {} stored telemetry messages", count);
+    }
+    Ok(())
+}
+
+#[cfg(not(feature = "sd"))]
+async fn send_stored(_socket: &mut TcpSocket<'_>, _topic: &str) -> Result<(), MqttError> {
+    Ok(())
+}
+
+/// 发送一个报文
+async fn send(socket: &mut TcpSocket<'_>, packet: &[u8]) -> Result<(), MqttError> {
+    socket.write_all(packet).await.map_err(|_| MqttError::Io)?;
+    netstats::sent(Link::Mqtt, packet.len());
+    Ok(())
+}
+
+/// 读取并丢弃 `count` 字节
+async fn skip(socket: &mut TcpSocket<'_>, mut count: usize) -> Result<(), MqttError> {
+    let mut buf = [0u8; 64];
+    while count > 0 {
+        let want = count.min(buf.len());
+        match socket.read(&mut buf[..want]).await {
+            Ok(0) | Err(_) => return Err(MqttError::Io),
+            Ok(read) => {
+                netstats::received(Link::Mqtt, read);
+                count -= read;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// 读取一个完整的报文，只用于等待 CONNACK
+///
+/// # 返回
+/// 报文类型和报文体，位于 `rx` 中
+async fn read_packet<'a>(
+    socket: &mut TcpSocket<'_>,
+    rx: &'a mut [u8],
+    len: &mut usize,
+) -> Result<(u8, &'a [u8]), MqttError> {
+    loop {
+        if let Some((kind, header_len, body_len)) = mqtt::parse_header(&rx[..*len])? {
+            let end = header_len + body_len;
+            if end > rx.len() {
+                return Err(MqttError::Protocol);
+            }
+            if *len >= end {
+                return Ok((kind, &rx[header_len..end]));
+            }
+        }
+        match socket.read(&mut rx[*len..]).await {
+            Ok(0) | Err(_) => return Err(MqttError::Io),
+            Ok(read) => {
+                netstats::received(Link::Mqtt, read);
+                *len += read;
+            }
+        }
+    }
+}
@@ -0,0 +1,148 @@
+//! 多核任务分配
+//!
+//! ESP32-S3 有两个核心：PRO_CPU（核心 0）运行 `main` 中的执行器，
+//! WiFi 协议栈和 I2C 等外设任务都在这里；APP_CPU（核心 1）由 [start_app_core]
+//! 启动第二个执行器，用于运行显示刷新等耗时的渲染工作，避免阻塞核心 0。
+//!
+//! 通过 [spawn_on] 将任务固定到指定核心。跨核生成的任务必须满足 `Send`，
+//! 任务之间通过 embassy-sync 的 `CriticalSectionRawMutex` 同步原语通信
+//! （esp-hal 的临界区实现在双核下是安全的）。
+//!
+//! # 高优先级执行器
+//!
+//! PRO_CPU 上还有一个由软件中断驱动的执行器（[start_realtime]），优先级高于 `main` 中的执行器。
+//! 对延迟敏感的短任务用 [spawn_realtime] 生成到这里：它们唤醒时直接抢占正在运行的普通任务，
+//! 不必等 WiFi 收发、TF 卡写入等长时间占用执行器的任务让出。目前运行在这里的是：
+//!
+//! - LCD 的异步 DMA 传输（[crate::lcd::transfer_task]）：DMA 完成中断唤醒后立即启动下一块
+//! - 蜂鸣器的节奏（[crate::buzzer::buzzer_task]）：每段只写一次 XL9555 的输出
+//!
+//! 高优先级任务运行在中断上下文中，只能做很短的工作后就 `await`，否则反过来拖慢所有普通任务；
+//! 不能调用会阻塞等待普通任务的代码。共享 I2C 总线的每次传输都在临界区内进行，
+//! 单次传输期间既不能抢占也不能响应其他中断，需要多次读取并处理结果的任务（例如按键检测）
+//! 不放在这里；共享 SPI 总线的 DMA 传输不关中断（见 [crate::spi]），不影响抢占。
+
+use core::cell::Cell;
+use critical_section::Mutex;
+use defmt::info;
+use embassy_executor::{SendSpawner, SpawnError, SpawnToken, Spawner};
+use esp_hal::interrupt::Priority;
+use esp_hal::interrupt::software::SoftwareInterrupt;
+use esp_hal::peripherals::CPU_CTRL;
+use esp_hal::system::Stack;
+use esp_rtos::embassy::{Executor, InterruptExecutor};
+use static_cell::StaticCell;
+
+/// APP_CPU 栈大小（字节）
+const APP_CORE_STACK_SIZE: usize = 16 * 1024;
+
+/// 高优先级执行器的中断优先级，高于 `main` 中的线程模式执行器
+const REALTIME_PRIORITY: Priority = Priority::Priority2;
+
+/// CPU 核心
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Core {
+    /// PRO_CPU（核心 0），运行 WiFi、I2C 等外设任务
+    Pro,
+    /// APP_CPU（核心 1），运行渲染任务
+    App,
+}
+
+static APP_CORE_STACK: StaticCell<Stack<APP_CORE_STACK_SIZE>> = StaticCell::new();
+
+static APP_EXECUTOR: StaticCell<Executor> = StaticCell::new();
+
+static PRO_SPAWNER: Mutex<Cell<Option<SendSpawner>>> = Mutex::new(Cell::new(None));
+
+static APP_SPAWNER: Mutex<Cell<Option<SendSpawner>>> = Mutex::new(Cell::new(None));
+
+static REALTIME_EXECUTOR: StaticCell<InterruptExecutor<2>> = StaticCell::new();
+
+static REALTIME_SPAWNER: Mutex<Cell<Option<SendSpawner>>> = Mutex::new(Cell::new(None));
+
+/// 注册 PRO_CPU 的任务生成器
+///
+/// # 参数
+/// * `spawner` - `main` 中执行器的任务生成器
+pub fn register_pro(spawner: Spawner) {
+    critical_section::with(|cs| PRO_SPAWNER.borrow(cs).set(Some(spawner.make_send())));
+}
+
+/// 启动 APP_CPU 并在其上运行第二个执行器
+///
+/// 返回前会等待 APP_CPU 的执行器就绪，之后即可通过 [spawn_on] 向其生成任务
+///
+/// # 参数
+/// * `cpu_ctrl` - CPU 控制外设
+/// * `int0` - 核间调度使用的软件中断 0
+/// * `int1` - 核间调度使用的软件中断 1
+pub fn start_app_core(
+    cpu_ctrl: CPU_CTRL<'static>,
+    int0: SoftwareInterrupt<'static, 0>,
+    int1: SoftwareInterrupt<'static, 1>,
+) {
+    let stack = APP_CORE_STACK.init(Stack::new());
+    esp_rtos::start_second_core(cpu_ctrl, int0, int1, stack, || {
+        let executor = APP_EXECUTOR.init(Executor::new());
+        executor.run(|spawner| {
+            critical_section::with(|cs| APP_SPAWNER.borrow(cs).set(Some(spawner.make_send())));
+        });
+    });
+
+    while spawner(Core::App).is_none() {
+        core::hint::spin_loop();
+    }
+    info!("APP_CPU executor started");
+}
+
+/// 在 PRO_CPU 上启动高优先级执行器
+///
+/// 之后即可通过 [spawn_realtime] 向其生成任务
+///
+/// # 参数
+/// * `int2` - 驱动执行器的软件中断 2
+pub fn start_realtime(int2: SoftwareInterrupt<'static, 2>) {
+    let executor = REALTIME_EXECUTOR.init(InterruptExecutor::new(int2));
+    let spawner = executor.start(REALTIME_PRIORITY);
+    critical_section::with(|cs| REALTIME_SPAWNER.borrow(cs).set(Some(spawner)));
+    info!("Realtime executor started");
+}
+
+/// 获取指定核心的任务生成器
+///
+/// # 返回
+/// 核心的执行器尚未启动时返回 None
+pub fn spawner(core: Core) -> Option<SendSpawner> {
+    critical_section::with(|cs| match core {
+        Core::Pro => PRO_SPAWNER.borrow(cs).get(),
+        Core::App => APP_SPAWNER.borrow(cs).get(),
+    })
+}
+
+/// 在指定核心上生成任务
+///
+/// APP_CPU 未启动时任务会退回到 PRO_CPU 上运行
+///
+/// # 参数
+/// * `core` - 目标核心
+/// * `token` - 任务
+#[cfg(feature = "ui")]
+pub fn spawn_on<S: Send>(core: Core, token: SpawnToken<S>) -> Result<(), SpawnError> {
+    let target = spawner(core)
+        .or_else(|| spawner(Core::Pro))
+        .expect("PRO_CPU spawner not registered");
+    target.spawn(token)
+}
+
+/// 在高优先级执行器上生成任务
+///
+/// 高优先级执行器未启动时任务会退回到 PRO_CPU 的普通执行器上运行
+///
+/// # 参数
+/// * `token` - 任务，应只做很短的工作就让出
+pub fn spawn_realtime<S: Send>(token: SpawnToken<S>) -> Result<(), SpawnError> {
+    let target = critical_section::with(|cs| REALTIME_SPAWNER.borrow(cs).get())
+        .or_else(|| spawner(Core::Pro))
+        .expect("PRO_CPU spawner not registered");
+    target.spawn(token)
+}
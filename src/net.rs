@@ -0,0 +1,195 @@
+//! 网络协议栈
+//!
+//! 在 WiFi 客户端接口上运行 embassy-net 协议栈，通过 DHCP 获取地址。
+//! 协议栈句柄 [Stack] 可以复制，由各网络服务（例如 [crate::http]）共享；
+//! 主机名通过 DHCP 下发的 DNS 服务器解析（[Stack::dns_query]）。
+//!
+//! 本机的主机名（[hostname]）在 DHCP 请求中发送（选项 12），路由器的设备列表据此显示，
+//! syslog 也用它标识设备。未在设置中指定时由 MAC 地址的后三个字节生成，
+//! 例如 `esp-app-4-a1b2c3`，同一网络中的多块板子不会重名。
+//!
+//! TCP 连接的参数（保活、超时、收发缓冲区大小）由 [SocketOptions] 描述，
+//! 各服务按连接的特点选择：长时间空闲的连接打开保活，及时发现对端掉线并维持 NAT 映射；
+//! 大量传输加大缓冲区，窗口更大，吞吐量更高。缓冲区由 [TcpBuffers] 在堆上分配。
+//!
+//! embassy-net 0.7 没有开放 smoltcp 套接字的 Nagle 开关，Nagle 算法始终启用。
+//! 一问一答的小包协议应把一个报文拼好后一次写入，避免与对端的延迟确认叠加出几百毫秒的延迟。
+
+use crate::settings::{self, HOSTNAME_LEN};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cell::RefCell;
+use core::fmt::Write;
+use critical_section::Mutex;
+use defmt::info;
+use embassy_net::tcp::TcpSocket;
+use embassy_net::{Config, DhcpConfig, Runner, Stack, StackResources};
+use embassy_time::Duration;
+use esp_hal::rng::Rng;
+use esp_radio::wifi::WifiDevice;
+use heapless::String;
+use static_cell::StaticCell;
+
+/// 协议栈可同时使用的最大套接字数量（DHCP 和 DNS 各占用一个）
+const MAX_SOCKETS: usize = 12;
+
+/// 协议栈后台运行器类型
+pub type NetRunner = Runner<'static, WifiDevice<'static>>;
+
+/// 默认主机名的前缀
+const HOSTNAME_PREFIX: &str = "esp-app-4";
+
+static RESOURCES: StaticCell<StackResources<MAX_SOCKETS>> = StaticCell::new();
+
+/// 本次启动使用的主机名，[init] 之前为空
+static HOSTNAME: Mutex<RefCell<String<HOSTNAME_LEN>>> = Mutex::new(RefCell::new(String::new()));
+
+/// 创建网络协议栈
+///
+/// # 参数
+/// * `device` - WiFi 客户端网络接口
+///
+/// # 返回
+/// 协议栈句柄和后台运行器，运行器需交给 [net_task] 运行
+///
+/// # Panics
+///
+/// 重复调用时会 panic
+pub fn init(device: WifiDevice<'static>) -> (Stack<'static>, NetRunner) {
+    let rng = Rng::new();
+    let seed = ((rng.random() as u64) << 32) | rng.random() as u64;
+
+    let configured = settings::get().hostname;
+    let name = if is_valid_hostname(&configured) {
+        configured
+    } else {
+        default_hostname(device.mac_address())
+    };
+    info!("Hostname: {}", name.as_str());
+    critical_section::with(|cs| *HOSTNAME.borrow_ref_mut(cs) = name.clone());
+    let mut dhcp = DhcpConfig::default();
+    dhcp.hostname = Some(name);
+
+    embassy_net::new(
+        device,
+        Config::dhcpv4(dhcp),
+        RESOURCES.init(StackResources::new()),
+        seed,
+    )
+}
+
+/// 本次启动使用的主机名
+///
+/// 设置中的主机名修改后重启才生效；网络协议栈没有初始化时返回默认前缀
+pub fn hostname() -> String<HOSTNAME_LEN> {
+    let name = critical_section::with(|cs| HOSTNAME.borrow_ref(cs).clone());
+    if name.is_empty() {
+        // 前缀比长度上限短
+        String::try_from(HOSTNAME_PREFIX).unwrap_or_default()
+    } else {
+        name
+    }
+}
+
+/// 主机名是否合法（RFC 1123 的单个标签）
+///
+/// 只允许小写字母、数字和 `-`，不能以 `-` 开头或结尾，最长 [HOSTNAME_LEN] 字节
+pub fn is_valid_hostname(name: &str) -> bool {
+    let allowed = |c: char| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-';
+    (1..=HOSTNAME_LEN).contains(&name.len())
+        && name.chars().all(allowed)
+        && !name.starts_with('-')
+        && !name.ends_with('-')
+}
+
+/// 由 MAC 地址生成默认主机名，例如 `esp-app-4-a1b2c3`
+fn default_hostname(mac: [u8; 6]) -> String<HOSTNAME_LEN> {
+    let mut name = String::new();
+    write!(name, "{}-", HOSTNAME_PREFIX).ok();
+    for byte in &mac[3..] {
+        write!(name, "{:02x}", byte).ok();
+    }
+    name
+}
+
+/// TCP 连接参数
+///
+/// 以 [SocketOptions::DEFAULT] 为基础，用结构体更新语法修改需要的字段：
+///
+/// ```ignore
+/// const OPTIONS: SocketOptions = SocketOptions {
+///     keep_alive: Some(Duration::from_secs(30)),
+///     ..SocketOptions::DEFAULT
+/// };
+/// let mut buffers = TcpBuffers::new(OPTIONS);
+/// let mut socket = buffers.socket(stack);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct SocketOptions {
+    /// 保活间隔，连接空闲这么久后发送保活包；None 表示不发送
+    pub keep_alive: Option<Duration>,
+    /// 超时，这么久没有收到对端的任何数据（包括保活应答）就断开连接；None 表示不超时
+    pub timeout: Option<Duration>,
+    /// 接收缓冲区大小（字节），也是通告给对端的最大窗口
+    pub rx_buffer: usize,
+    /// 发送缓冲区大小（字节）
+    pub tx_buffer: usize,
+}
+
+impl SocketOptions {
+    /// 默认参数：不保活，15 秒超时，收发缓冲区各 1KB
+    pub const DEFAULT: SocketOptions = SocketOptions {
+        keep_alive: None,
+        timeout: Some(Duration::from_secs(15)),
+        rx_buffer: 1024,
+        tx_buffer: 1024,
+    };
+
+    /// 把保活和超时设置应用到套接字，缓冲区大小在创建套接字时决定
+    pub fn apply(&self, socket: &mut TcpSocket<'_>) {
+        socket.set_timeout(self.timeout);
+        socket.set_keep_alive(self.keep_alive);
+    }
+}
+
+/// TCP 套接字的收发缓冲区
+///
+/// 在堆上按 [SocketOptions] 分配一次，之后每个连接都可以复用
+pub struct TcpBuffers {
+    options: SocketOptions,
+    rx: Vec<u8>,
+    tx: Vec<u8>,
+}
+
+impl TcpBuffers {
+    /// 分配缓冲区
+    pub fn new(options: SocketOptions) -> Self {
+        TcpBuffers {
+            options,
+            rx: vec![0; options.rx_buffer],
+            tx: vec![0; options.tx_buffer],
+        }
+    }
+
+    /// 创建使用这组缓冲区的套接字，并应用连接参数
+    pub fn socket<'a>(&'a mut self, stack: Stack<'a>) -> TcpSocket<'a> {
+        let mut socket = TcpSocket::new(stack, &mut self.rx, &mut self.tx);
+        self.options.apply(&mut socket);
+        socket
+    }
+}
+
+/// 协议栈后台任务
+#[embassy_executor::task]
+pub async fn net_task(mut runner: NetRunner) {
+    runner.run().await
+}
+
+/// 等待 DHCP 获取地址并打印
+#[embassy_executor::task]
+pub async fn report_address(stack: Stack<'static>) {
+    stack.wait_config_up().await;
+    if let Some(config) = stack.config_v4() {
+        info!("IP address: {}", config.address);
+    }
+}
@@ -0,0 +1,206 @@
+//! 按连接统计网络流量
+//!
+//! 各网络服务在收发数据后调用 [received] 和 [sent]，建立连接时调用 [opened]，
+//! 统计按逻辑连接（[Link]）汇总，而不是按套接字：HTTP 服务先后处理的所有连接都计入
+//! [Link::HttpServer]。统计从启动开始累计，重启后清零。
+//!
+//! 字节数只包括应用层数据，不含 TCP/IP 和 WiFi 的帧头；包数对 UDP 是数据报个数，
+//! 对 TCP 是读写的次数，与实际的 TCP 报文段数不一定相同。用手机热点按流量计费时，
+//! 用它找出是哪个服务在消耗流量。
+//!
+//! 统计通过 HTTP `GET /metrics`（Prometheus 文本格式，见 [format_metrics]）和状态屏幕的
+//! 网络页面（见 [crate::screens]）查看。
+//!
+//! 限制：没有通过网络的固件更新（OTA 只从 TF 卡读取），因此没有这类连接。
+
+use alloc::string::String;
+use core::cell::RefCell;
+use core::fmt::Write;
+use critical_section::Mutex;
+
+/// 逻辑连接
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Link {
+    /// HTTP 服务（[crate::http]）
+    HttpServer,
+    /// HTTP 客户端：天气预报、通知等（[crate::http_client]）
+    HttpClient,
+    /// Modbus TCP 从站（[crate::modbus]）
+    Modbus,
+    /// 串口透传（[crate::bridge]）
+    Bridge,
+    /// SNMP 代理（[crate::snmp]）
+    Snmp,
+    /// 网络校时（[crate::sntp]）
+    Sntp,
+    /// 远程日志（[crate::syslog]）
+    Syslog,
+    /// mDNS 服务发现（[crate::mdns]）
+    Mdns,
+    /// 设置同步（[crate::peersync]）
+    PeerSync,
+    /// MQTT 客户端（[crate::mqtt]）
+    Mqtt,
+    /// 链路测试（[crate::linktest]）
+    Test,
+    /// 远程显示（[crate::remote]）
+    Remote,
+}
+
+impl Link {
+    /// 所有连接
+    pub const ALL: [Link; 12] = [
+        Link::HttpServer,
+        Link::HttpClient,
+        Link::Modbus,
+        Link::Bridge,
+        Link::Snmp,
+        Link::Sntp,
+        Link::Syslog,
+        Link::Mdns,
+        Link::PeerSync,
+        Link::Mqtt,
+        Link::Test,
+        Link::Remote,
+    ];
+
+    /// 连接名称，用于指标标签和屏幕显示，不超过 8 个字符
+    pub const fn name(self) -> &'static str {
+        match self {
+            Link::HttpServer => "http",
+            Link::HttpClient => "http-out",
+            Link::Modbus => "modbus",
+            Link::Bridge => "bridge",
+            Link::Snmp => "snmp",
+            Link::Sntp => "sntp",
+            Link::Syslog => "syslog",
+            Link::Mdns => "mdns",
+            Link::PeerSync => "peersync",
+            Link::Mqtt => "mqtt",
+            Link::Test => "linktest",
+            Link::Remote => "remote",
+        }
+    }
+}
+
+/// 一个连接的累计流量
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Counters {
+    pub rx_bytes: u64,
+    pub tx_bytes: u64,
+    pub rx_packets: u32,
+    pub tx_packets: u32,
+    /// 建立的连接数，UDP 服务为 0
+    pub connections: u32,
+}
+
+impl Counters {
+    /// 收发的总字节数
+    #[cfg(feature = "ui")]
+    pub fn total_bytes(&self) -> u64 {
+        self.rx_bytes + self.tx_bytes
+    }
+}
+
+const EMPTY: Counters = Counters {
+    rx_bytes: 0,
+    tx_bytes: 0,
+    rx_packets: 0,
+    tx_packets: 0,
+    connections: 0,
+};
+
+static COUNTERS: Mutex<RefCell<[Counters; Link::ALL.len()]>> =
+    Mutex::new(RefCell::new([EMPTY; Link::ALL.len()]));
+
+/// 修改一个连接的统计
+fn update(link: Link, f: impl FnOnce(&mut Counters)) {
+    critical_section::with(|cs| f(&mut COUNTERS.borrow_ref_mut(cs)[link as usize]));
+}
+
+/// 记录建立了一个连接
+pub fn opened(link: Link) {
+    update(link, |c| c.connections = c.connections.wrapping_add(1));
+}
+
+/// 记录收到的数据，`len` 为 0（连接关闭）时不计
+///
+/// # 参数
+/// * `link` - 连接
+/// * `len` - 本次读取或收到的数据报的字节数
+pub fn received(link: Link, len: usize) {
+    if len == 0 {
+        return;
+    }
+    update(link, |c| {
+        c.rx_bytes += len as u64;
+        c.rx_packets = c.rx_packets.wrapping_add(1);
+    });
+}
+
+/// 记录发送的数据
+///
+/// # 参数
+/// * `link` - 连接
+/// * `len` - 本次写入或发送的数据报的字节数
+pub fn sent(link: Link, len: usize) {
+    if len == 0 {
+        return;
+    }
+    update(link, |c| {
+        c.tx_bytes += len as u64;
+        c.tx_packets = c.tx_packets.wrapping_add(1);
+    });
+}
+
+/// 所有连接的当前统计，顺序与 [Link::ALL] 相同
+pub fn all() -> [(Link, Counters); Link::ALL.len()] {
+    let counters = critical_section::with(|cs| *COUNTERS.borrow_ref(cs));
+    Link::ALL.map(|link| (link, counters[link as usize]))
+}
+
+/// 把字节数写成便于阅读的形式，例如 `512B`、`12.3K`、`4.5M`
+#[cfg(feature = "ui")]
+pub fn write_size(out: &mut impl Write, bytes: u64) -> core::fmt::Result {
+    const UNITS: [char; 3] = ['K', 'M', 'G'];
+    if bytes < 1024 {
+        return write!(out, "{}B", bytes);
+    }
+    let mut scaled = bytes * 10 / 1024;
+    let mut unit = 0;
+    while scaled >= 10 * 1024 && unit + 1 < UNITS.len() {
+        scaled /= 1024;
+        unit += 1;
+    }
+    write!(out, "{}.{}{}", scaled / 10, scaled % 10, UNITS[unit])
+}
+
+/// 指标名、说明和取值方法
+type Metric = (&'static str, &'static str, fn(&Counters) -> u64);
+
+/// 将统计格式化为 Prometheus 文本格式
+pub fn format_metrics() -> String {
+    let counters = all();
+    let metrics: [Metric; 5] = [
+        ("net_rx_bytes_total", "Bytes received", |c| c.rx_bytes),
+        ("net_tx_bytes_total", "Bytes sent", |c| c.tx_bytes),
+        ("net_rx_packets_total", "Reads or datagrams received", |c| {
+            c.rx_packets as u64
+        }),
+        ("net_tx_packets_total", "Writes or datagrams sent", |c| {
+            c.tx_packets as u64
+        }),
+        ("net_connections_total", "Connections opened", |c| {
+            c.connections as u64
+        }),
+    ];
+    let mut text = String::new();
+    for (name, help, value) in metrics {
+        writeln!(text, "# HELP {} {}", name, help).ok();
+        writeln!(text, "# TYPE {} counter", name).ok();
+        for (link, c) in &counters {
+            writeln!(text, "{}{{link=\"{}\"}} {}", name, link.name(), value(c)).ok();
+        }
+    }
+    text
+}
@@ -0,0 +1,354 @@
+//! 告警通知
+//!
+//! [notify] 把告警事件连同当时的传感器读数编码为 JSON 放入队列，
+//! [notifier_task] 联网后逐个以 HTTP `POST` 发送到设置中的 webhook 地址：
+//!
+//! ```text
+//! {"device":{"id":"f412fa1b2c3d","name":"esp-app-4-1b2c3d","location":"Lab","tags":[]},
+//!  "event":"Timer expired","uptime":1234,"readings":{"bme280.t":23.5}}
+//! ```
+//!
+//! `device` 是设备标识（见 [crate::device]），接收方据此区分多块板子。
+//!
+//! 请求体默认为 JSON，也可以在设置中改为 CBOR（见 [Format] 和 [proto::cbor]），
+//! 在按流量计费的网络上减少数据量。
+//!
+//! 两次发送至少间隔 [MIN_INTERVAL]；发送失败时 [RETRY_INTERVAL] 后重试。
+//!
+//! 离线期间和发送失败的事件转存到 TF 卡（见 [crate::outbox]），重启后不会丢失，
+//! 恢复联网后先按时间顺序发送卡上的事件，再发送内存中的新事件。没有插卡时事件留在内存队列中，
+//! 队列满时丢弃最早的事件。关闭 `sd` feature 的构建不转存，离线期间的事件只保存在内存队列中。
+//!
+//! 限制：HTTP 客户端不支持 TLS，只能发送到明文 HTTP 地址，
+//! Telegram Bot API 等只提供 HTTPS 的服务需要经过局域网内的转发服务。
+
+use crate::http_client::HttpClientError;
+#[cfg(feature = "ui")]
+use crate::render;
+use crate::{device, http_client, sensor, settings};
+#[cfg(feature = "sd")]
+use crate::{outbox, sdcard};
+use alloc::string::String;
+use core::cell::RefCell;
+use critical_section::Mutex;
+use defmt::{info, warn};
+use embassy_futures::select::select;
+use embassy_net::Stack;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::signal::Signal;
+use embassy_time::{Duration, Instant, Timer};
+use heapless::Deque;
+use proto::cbor;
+use proto::json::{Object, ToJson};
+
+/// 队列容量
+const QUEUE_LEN: usize = 8;
+
+/// 两次发送的最小间隔
+const MIN_INTERVAL: Duration = Duration::from_secs(10);
+
+/// 发送失败后的重试间隔
+const RETRY_INTERVAL: Duration = Duration::from_secs(60);
+
+/// 响应缓冲区大小，响应内容会被丢弃
+const RESPONSE_BUF_LEN: usize = 512;
+
+/// 待发送的请求体
+static QUEUE: Mutex<RefCell<Deque<String, QUEUE_LEN>>> = Mutex::new(RefCell::new(Deque::new()));
+
+/// 内存队列满时挤出的事件，由 [notifier_task] 转存到 TF 卡
+#[cfg(feature = "sd")]
+static OVERFLOW: Mutex<RefCell<Deque<String, QUEUE_LEN>>> = Mutex::new(RefCell::new(Deque::new()));
+
+/// 有新事件入队
+static QUEUED: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+
+/// 请求体的编码
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Format {
+    Json,
+    Cbor,
+}
+
+impl Format {
+    /// 所有编码，下标与设置中保存的编码一致
+    pub const ALL: [Format; 2] = [Format::Json, Format::Cbor];
+
+    /// 设置中保存的编码
+    pub const fn to_u8(self) -> u8 {
+        self as u8
+    }
+
+    /// 从设置中的编码解析，未知编码视为 JSON
+    pub const fn from_u8(value: u8) -> Format {
+        match value {
+            1 => Format::Cbor,
+            _ => Format::Json,
+        }
+    }
+
+    /// 编码名称，用于命令行参数
+    pub const fn name(self) -> &'static str {
+        match self {
+            Format::Json => "json",
+            Format::Cbor => "cbor",
+        }
+    }
+}
+
+/// 发送告警事件
+///
+/// 事件描述同时以横幅显示在状态屏幕底部（见 [crate::render::banner]）；未设置 webhook 时不发送
+///
+/// # 参数
+/// * `event` - 事件描述
+pub fn notify(event: &str) {
+    #[cfg(feature = "ui")]
+    render::banner(event);
+    if settings::get().webhook_url.is_empty() {
+        return;
+    }
+
+    let mut body = String::new();
+    {
+        let mut object = Object::new(&mut body);
+        device::identity().write_members(&mut object.object("device"));
+        object
+            .str("event", event)
+            .int("uptime", Instant::now().as_secs() as i64);
+        let mut readings = object.object("readings");
+        for reading in sensor::all() {
+            readings.number(reading.name, reading.value);
+        }
+    }
+
+    let oldest = critical_section::with(|cs| {
+        let mut queue = QUEUE.borrow_ref_mut(cs);
+        let oldest = if queue.is_full() {
+            queue.pop_front()
+        } else {
+            None
+        };
+        queue.push_back(body).ok();
+        oldest
+    });
+    // 内存队列满时把最早的事件交给发送任务转存到 TF 卡
+    if let Some(oldest) = oldest
+        && !overflow(oldest)
+    {
+        warn!("Notification queue full, dropping oldest event");
+    }
+    QUEUED.signal(());
+}
+
+/// 发送一个请求体
+///
+/// 队列和 TF 卡上保存的都是 JSON，发送时才按设置转换编码
+async fn send(stack: Stack<'_>, body: &str) -> Result<(), HttpClientError> {
+    let s = settings::get();
+    let Ok((host, port, path)) = http_client::parse_url(&s.webhook_url) else {
+        // 地址在命令行中已经校验过，这里只可能是旧设置
+        warn!("Invalid webhook URL, dropping event");
+        return Ok(());
+    };
+    let encoded;
+    let (content_type, payload) = match Format::from_u8(s.webhook_format) {
+        Format::Json => ("application/json", body.as_bytes()),
+        Format::Cbor => match cbor::from_json(body) {
+            Ok(data) => {
+                encoded = data;
+                (cbor::CONTENT_TYPE, encoded.as_slice())
+            }
+            Err(err) => {
+                warn!(
+                    "Failed to encode notification as CBOR: {}, dropping event",
+                    err
+                );
+                return Ok(());
+            }
+        },
+    };
+    let mut response = [0u8; RESPONSE_BUF_LEN];
+    http_client::post(
+        stack,
+        host,
+        port,
+        path,
+        content_type,
+        payload,
+        &mut response,
+    )
+    .await?;
+    info!("Notification sent to {}", host);
+    Ok(())
+}
+
+/// 检查发送结果
+///
+/// # 返回
+/// 事件是否已经处理完：发送成功，或被服务器拒绝（重试也不会成功）
+fn settle(result: Result<(), HttpClientError>) -> bool {
+    match result {
+        Ok(()) => true,
+        Err(HttpClientError::Status(status)) => {
+            warn!("Webhook rejected notification with status {}", status);
+            true
+        }
+        Err(err) => {
+            warn!("Failed to send notification: {}, retrying later", err);
+            false
+        }
+    }
+}
+
+/// 读取 TF 卡上的事件所用的缓冲区长度
+#[cfg(feature = "sd")]
+const RECORD_LEN: usize = outbox::MAX_RECORD_LEN + 1;
+#[cfg(not(feature = "sd"))]
+const RECORD_LEN: usize = 0;
+
+/// 把内存队列挤出的事件交给发送任务转存
+///
+/// [notify] 是同步的，不能等待卡槽，转存由 [archive_overflow] 完成
+///
+/// # 返回
+/// 是否已交出，等待转存的事件也已满时返回 false
+#[cfg(feature = "sd")]
+fn overflow(body: String) -> bool {
+    critical_section::with(|cs| OVERFLOW.borrow_ref_mut(cs).push_back(body).is_ok())
+}
+
+/// 没有 TF 卡支持时无处转存
+#[cfg(not(feature = "sd"))]
+fn overflow(_body: String) -> bool {
+    false
+}
+
+/// 把内存队列挤出的事件追加到 TF 卡，不检查是否插卡，写入失败时丢弃
+#[cfg(feature = "sd")]
+async fn archive_overflow() {
+    while let Some(body) = critical_section::with(|cs| OVERFLOW.borrow_ref_mut(cs).pop_front()) {
+        if let Err(err) = outbox::WEBHOOK.push(&body).await {
+            warn!(
+                "Failed to store overflowed notification, dropping: {}",
+                defmt::Debug2Format(&err)
+            );
+        }
+    }
+}
+
+/// 没有 TF 卡支持时不会有挤出的事件
+#[cfg(not(feature = "sd"))]
+async fn archive_overflow() {}
+
+/// 把事件转存到 TF 卡，未插卡或写入失败时放回内存队列的队首
+///
+/// # 返回
+/// 是否已转存
+async fn spill(body: String) -> bool {
+    #[cfg(feature = "sd")]
+    if sdcard::is_mounted() {
+        match outbox::WEBHOOK.push(&body).await {
+            Ok(()) => return true,
+            Err(err) => warn!(
+                "Failed to store notification: {}",
+                defmt::Debug2Format(&err)
+            ),
+        }
+    }
+    // 队列已被新事件占满时放弃这一条
+    critical_section::with(|cs| QUEUE.borrow_ref_mut(cs).push_front(body).ok());
+    false
+}
+
+/// 读取 TF 卡上最早的事件
+///
+/// # 返回
+/// 事件的长度，没有插卡、没有事件或读取失败时为 None
+#[cfg(feature = "sd")]
+async fn stored(buf: &mut [u8]) -> Option<usize> {
+    if !sdcard::is_mounted() {
+        return None;
+    }
+    outbox::WEBHOOK
+        .peek(buf)
+        .await
+        .inspect_err(|err| warn!("Failed to read outbox: {}", defmt::Debug2Format(err)))
+        .ok()
+        .flatten()
+}
+
+/// 没有 TF 卡支持时卡上没有事件
+#[cfg(not(feature = "sd"))]
+async fn stored(_buf: &mut [u8]) -> Option<usize> {
+    None
+}
+
+/// 删除 TF 卡上最早的事件
+///
+/// # 参数
+/// * `len` - 事件的长度，由 [stored] 返回
+#[cfg(feature = "sd")]
+async fn discard_stored(len: usize) {
+    if let Err(err) = outbox::WEBHOOK.pop(len).await {
+        warn!(
+            "Failed to remove sent notification: {}",
+            defmt::Debug2Format(&err)
+        );
+    }
+}
+
+#[cfg(not(feature = "sd"))]
+async fn discard_stored(_len: usize) {}
+
+/// 通知发送任务
+///
+/// # 参数
+/// * `stack` - 网络协议栈
+#[embassy_executor::task]
+pub async fn notifier_task(stack: Stack<'static>) {
+    let mut next_send = Instant::now();
+    let mut record = [0u8; RECORD_LEN];
+    loop {
+        archive_overflow().await;
+        if !stack.is_config_up() {
+            // 离线期间把内存中的事件转存到 TF 卡
+            while let Some(body) = critical_section::with(|cs| QUEUE.borrow_ref_mut(cs).pop_front())
+            {
+                if !spill(body).await {
+                    break;
+                }
+            }
+            select(stack.wait_config_up(), QUEUED.wait()).await;
+            continue;
+        }
+        Timer::at(next_send).await;
+
+        // 卡上的事件比内存中的早，先发送
+        let done = if let Some(len) = stored(&mut record).await {
+            let done = match core::str::from_utf8(&record[..len]) {
+                Ok(body) => settle(send(stack, body).await),
+                Err(_) => {
+                    warn!("Dropping malformed outbox record");
+                    true
+                }
+            };
+            if done {
+                discard_stored(len).await;
+            }
+            done
+        } else if let Some(body) = critical_section::with(|cs| QUEUE.borrow_ref_mut(cs).pop_front())
+        {
+            let done = settle(send(stack, &body).await);
+            if !done {
+                spill(body).await;
+            }
+            done
+        } else {
+            QUEUED.wait().await;
+            continue;
+        };
+        let interval = if done { MIN_INTERVAL } else { RETRY_INTERVAL };
+        next_send = Instant::now() + interval;
+    }
+}
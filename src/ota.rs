@@ -0,0 +1,696 @@
+//! 固件更新 (OTA)
+//!
+//! 将新固件写入下一个 OTA 分区并切换启动分区。固件有两个来源：
+//!
+//! - TF 卡上的离线升级文件，启动时检查（见 [apply_from_sd]，需要 `sd` feature）
+//! - HTTP 服务器，用命令行 `ota http://<主机>[:<端口>]/<路径>` 触发下载（见 [request_http]）。
+//!   与 [crate::http_client] 一样只支持明文 HTTP，固件靠签名保证来源可信
+//!
+//! TF 卡上的文件可以读两遍：
+//!
+//! 1. 第一遍读取固件，检查镜像头、校验 ed25519 签名并计算 SHA-256
+//! 2. 第二遍读取固件并写入下一个 OTA 分区，同时再次计算 SHA-256，
+//!    与第一遍比较，确保写入的数据就是签名校验时读到的数据（两遍之间文件可能被替换或读错）
+//! 3. 两次校验均通过后才切换启动分区
+//!
+//! 下载的固件放不进内存，只下载一遍：边下载边写入分区，同时校验签名并计算 SHA-256；
+//! 签名正确后再从分区读回写入的内容计算 SHA-256，一致才切换启动分区。
+//!
+//! 两个阶段的进度各占一半，通过 [Progress] 显示在屏幕上并写入日志。
+//!
+//! # 固件签名
+//!
+//! 固件必须使用 ed25519 私钥签名，签名对象为完整的固件文件。对应的公钥在构建时嵌入固件：
+//! 默认读取 `keys/ota_ed25519.pub`，也可以用环境变量 `OTA_PUBLIC_KEY` 指定文件（见 build.rs）。
+//! 仓库中不提交密钥，找不到公钥时构建失败。每个部署生成自己的密钥对，私钥由发布固件的人保管，
+//! 不要放进仓库。使用 OpenSSL 生成密钥和签名：
+//!
+//! ```text
+//! openssl genpkey -algorithm ed25519 -out ota_key.pem
+//! openssl pkey -in ota_key.pem -pubout -outform DER | tail -c 32 > keys/ota_ed25519.pub
+//! openssl pkeyutl -sign -inkey ota_key.pem -rawin -in FIRMWARE.BIN -out FIRMWARE.SIG
+//! ```
+//!
+//! 新固件启动后需调用 [mark_running_image_valid]，
+//! 否则启用回滚功能的引导程序会在下次复位时回到旧固件。
+
+use crate::http_client::{self, HttpClientError};
+use crate::i18n::{self, Msg};
+use crate::net::SocketOptions;
+use crate::progress::Progress;
+#[cfg(feature = "sd")]
+use crate::sdcard::{self, Dir, SdError, SdFile};
+use crate::storage::{self, StorageError};
+use crate::system::{self, RebootReason};
+use core::fmt::Write;
+use defmt::{info, warn};
+use ed25519_compact::{PublicKey, Signature, VerifyingState};
+use embassy_net::Stack;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::signal::Signal;
+use embassy_time::Duration;
+#[cfg(feature = "sd")]
+use embedded_sdmmc::Mode;
+use embedded_storage::{ReadStorage, Storage};
+use esp_bootloader_esp_idf::ota::OtaImageState;
+use esp_bootloader_esp_idf::ota_updater::OtaUpdater;
+use esp_bootloader_esp_idf::partitions::{FlashRegion, PARTITION_TABLE_MAX_LEN};
+use esp_storage::FlashStorage;
+use heapless::String;
+use sha2::{Digest, Sha256};
+
+/// 固件读写块大小，与 Flash 扇区大小一致
+const CHUNK_LEN: usize = 4096;
+
+/// ESP 应用镜像头魔数
+const ESP_IMAGE_MAGIC: u8 = 0xE9;
+
+/// ed25519 签名长度
+pub const SIGNATURE_LEN: usize = 64;
+
+/// 固件签名公钥，由 build.rs 复制到 `OUT_DIR`
+static OTA_PUBLIC_KEY: &[u8; 32] = include_bytes!(concat!(env!("OUT_DIR"), "/ota_ed25519.pub"));
+
+/// 固件下载地址的最大长度
+pub const URL_LEN: usize = 128;
+
+/// 签名文件的地址是固件地址加上这个后缀
+pub const SIGNATURE_SUFFIX: &str = ".sig";
+
+/// 下载固件的连接参数：接收缓冲区为一个 Flash 扇区，写入扇区期间不读网络，超时放宽到 30 秒
+const DOWNLOAD: SocketOptions = SocketOptions {
+    timeout: Some(Duration::from_secs(30)),
+    rx_buffer: CHUNK_LEN,
+    ..http_client::OPTIONS
+};
+
+/// 等待 [http_task] 执行的下载地址
+static HTTP_REQUEST: Signal<CriticalSectionRawMutex, String<URL_LEN>> = Signal::new();
+
+/// TF 卡上的升级文件名
+#[cfg(feature = "sd")]
+pub const SD_FIRMWARE_FILE: &str = "FIRMWARE.BIN";
+/// TF 卡上的签名文件名（64 字节 ed25519 签名）
+#[cfg(feature = "sd")]
+pub const SD_SIGNATURE_FILE: &str = "FIRMWARE.SIG";
+/// TF 卡上的校验文件名（8 位十六进制 CRC32，可选）
+#[cfg(feature = "sd")]
+pub const SD_CRC_FILE: &str = "FIRMWARE.CRC";
+/// 升级成功后固件文件的新名字
+#[cfg(feature = "sd")]
+pub const SD_APPLIED_FILE: &str = "FIRMWARE.OLD";
+/// 校验失败的固件文件的新名字，避免每次启动重复尝试
+#[cfg(feature = "sd")]
+pub const SD_REJECTED_FILE: &str = "FIRMWARE.BAD";
+
+/// 固件更新错误
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum OtaError {
+    /// 读取固件来源失败
+    Source,
+    /// 下载固件或签名失败
+    Download(HttpClientError),
+    /// Flash 读写失败
+    Storage(StorageError),
+    /// 分区表读取或 OTA 数据更新失败
+    Partition,
+    /// 固件大于 OTA 分区
+    ImageTooLarge { image: u32, partition: u32 },
+    /// 不是有效的 ESP 应用镜像
+    InvalidImage,
+    /// CRC32 校验失败
+    #[cfg(feature = "sd")]
+    CrcMismatch { expected: u32, actual: u32 },
+    /// 写入时读到的固件与签名校验时不同
+    ImageChanged,
+    /// 缺少签名
+    MissingSignature,
+    /// 签名格式错误或与固件内容不匹配
+    BadSignature,
+}
+
+impl From<StorageError> for OtaError {
+    fn from(err: StorageError) -> Self {
+        OtaError::Storage(err)
+    }
+}
+
+/// 固件来源
+#[cfg(feature = "sd")]
+pub trait FirmwareSource {
+    /// 固件总长度
+    fn len(&self) -> u32;
+
+    /// 从指定偏移读取固件数据
+    ///
+    /// # 返回
+    /// 读取的字节数，0 表示已到末尾
+    fn read_at(&mut self, offset: u32, buf: &mut [u8]) -> Result<usize, OtaError>;
+}
+
+/// 签名校验通过的固件的摘要
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg(feature = "sd")]
+pub struct Verified {
+    /// CRC32，用于和升级文件附带的校验值比较
+    pub crc: u32,
+    /// SHA-256，写入时用它确认写入的就是校验过的内容
+    pub sha256: [u8; 32],
+}
+
+/// 一遍读取中已完成的进度，每遍占总进度的一半
+fn half_percent(done: u32, total: u32) -> u8 {
+    (done as u64 * 50 / total.max(1) as u64) as u8
+}
+
+/// 逐块读取整个固件
+#[cfg(feature = "sd")]
+fn for_each_chunk<S, F>(source: &mut S, mut f: F) -> Result<(), OtaError>
+where
+    S: FirmwareSource,
+    F: FnMut(u32, &[u8]) -> Result<(), OtaError>,
+{
+    let len = source.len();
+    let mut buf = [0u8; CHUNK_LEN];
+    let mut offset = 0u32;
+    while offset < len {
+        let want = (len - offset).min(CHUNK_LEN as u32) as usize;
+        let read = source.read_at(offset, &mut buf[..want])?;
+        if read == 0 {
+            return Err(OtaError::Source);
+        }
+        f(offset, &buf[..read])?;
+        offset += read as u32;
+    }
+    Ok(())
+}
+
+/// 校验固件镜像头和签名
+///
+/// # 参数
+/// * `source` - 固件来源
+/// * `signature` - 固件的 ed25519 签名
+/// * `progress` - 进度报告器，校验占 0-50%
+///
+/// # 返回
+/// 校验通过时返回固件的摘要，供 [write_image] 确认写入内容一致
+#[cfg(feature = "sd")]
+pub fn verify<S: FirmwareSource>(
+    source: &mut S,
+    signature: &[u8; SIGNATURE_LEN],
+    progress: &mut Progress<'_>,
+) -> Result<Verified, OtaError> {
+    let public_key = PublicKey::new(*OTA_PUBLIC_KEY);
+    let mut state = public_key
+        .verify_incremental(&Signature::new(*signature))
+        .map_err(|_| OtaError::BadSignature)?;
+
+    let len = source.len();
+    let message = i18n::lcd(Msg::OtaVerifying);
+    let mut crc = 0xFFFF_FFFF;
+    let mut sha256 = Sha256::new();
+    for_each_chunk(source, |offset, chunk| {
+        if offset == 0 && chunk.first() != Some(&ESP_IMAGE_MAGIC) {
+            return Err(OtaError::InvalidImage);
+        }
+        state.absorb(chunk);
+        crc = storage::crc32_update(crc, chunk);
+        sha256.update(chunk);
+        let done = offset + chunk.len() as u32;
+        progress.update(half_percent(done, len), message);
+        Ok(())
+    })?;
+
+    if state.verify().is_err() {
+        warn!("Firmware signature does not match the embedded public key");
+        return Err(OtaError::BadSignature);
+    }
+    Ok(Verified {
+        crc: crc ^ 0xFFFF_FFFF,
+        sha256: sha256.finalize().into(),
+    })
+}
+
+/// 将固件写入下一个 OTA 分区并切换启动分区
+///
+/// 调用前应先通过 [verify] 校验固件签名，并将其返回的摘要传入。写入过程中会再次计算
+/// SHA-256，与签名校验时不一致（CRC32 可以构造碰撞，不足以保证）时不会切换启动分区。
+///
+/// 写入期间独占 Flash 实例（见 [storage::take_flash]），只有每一块的擦除和写入关闭中断，
+/// 设置等数据块的读写在此期间返回 [StorageError::Busy]。
+///
+/// # 参数
+/// * `source` - 固件来源
+/// * `verified` - [verify] 返回的摘要
+/// * `progress` - 进度报告器，写入占 50-100%
+#[cfg(feature = "sd")]
+pub fn write_image<S: FirmwareSource>(
+    source: &mut S,
+    verified: &Verified,
+    progress: &mut Progress<'_>,
+) -> Result<(), OtaError> {
+    let mut flash = storage::take_flash().map_err(OtaError::Storage)?;
+    let result = write_image_to(&mut flash, source, verified, progress);
+    storage::restore_flash(flash);
+    result
+}
+
+/// 在已获取的 Flash 实例上执行固件写入
+#[cfg(feature = "sd")]
+fn write_image_to<S: FirmwareSource>(
+    flash: &mut FlashStorage<'static>,
+    source: &mut S,
+    verified: &Verified,
+    progress: &mut Progress<'_>,
+) -> Result<(), OtaError> {
+    let mut buffer = [0u8; PARTITION_TABLE_MAX_LEN];
+    let mut ota = open_updater(flash, &mut buffer)?;
+
+    {
+        let mut partition = next_partition(&mut ota)?;
+
+        let capacity = partition.capacity() as u32;
+        if source.len() > capacity {
+            return Err(OtaError::ImageTooLarge {
+                image: source.len(),
+                partition: capacity,
+            });
+        }
+
+        let len = source.len();
+        let message = i18n::lcd(Msg::OtaWriting);
+        let mut sha256 = Sha256::new();
+        for_each_chunk(source, |offset, chunk| {
+            sha256.update(chunk);
+            partition
+                .write(offset, chunk)
+                .map_err(|_| OtaError::Storage(StorageError::Flash))?;
+            let done = offset + chunk.len() as u32;
+            progress.update(50 + half_percent(done, len), message);
+            Ok(())
+        })?;
+
+        if sha256.finalize()[..] != verified.sha256 {
+            warn!("Firmware changed between verifying and writing");
+            return Err(OtaError::ImageChanged);
+        }
+    }
+
+    activate(&mut ota)
+}
+
+/// 读取分区表
+fn open_updater<'a>(
+    flash: &'a mut FlashStorage<'static>,
+    buffer: &'a mut [u8; PARTITION_TABLE_MAX_LEN],
+) -> Result<OtaUpdater<'a, FlashStorage<'static>>, OtaError> {
+    OtaUpdater::new(flash, buffer).map_err(|err| {
+        warn!("Failed to read partition table: {}", defmt::Debug2Format(&err));
+        OtaError::Partition
+    })
+}
+
+/// 下一个 OTA 分区，新固件写到这里
+fn next_partition<'a>(
+    ota: &'a mut OtaUpdater<'_, FlashStorage<'static>>,
+) -> Result<FlashRegion<'a, FlashStorage<'static>>, OtaError> {
+    let (partition, part_type) = ota.next_partition().map_err(|err| {
+        warn!("No OTA partition available: {}", defmt::Debug2Format(&err));
+        OtaError::Partition
+    })?;
+    info!("Writing firmware to {}", defmt::Debug2Format(&part_type));
+    Ok(partition)
+}
+
+/// 切换到写好的分区，标记为新固件
+fn activate(ota: &mut OtaUpdater<'_, FlashStorage<'static>>) -> Result<(), OtaError> {
+    ota.activate_next_partition().map_err(|err| {
+        warn!("Failed to activate OTA partition: {}", defmt::Debug2Format(&err));
+        OtaError::Partition
+    })?;
+    ota.set_current_ota_state(OtaImageState::New)
+        .map_err(|_| OtaError::Partition)
+}
+
+/// 将当前运行的固件标记为有效
+///
+/// 在新固件首次启动且初始化成功后调用，避免引导程序回滚
+pub fn mark_running_image_valid() {
+    let result = storage::with_flash(|flash| {
+        let mut buffer = [0u8; PARTITION_TABLE_MAX_LEN];
+        let mut ota = OtaUpdater::new(flash, &mut buffer).map_err(|_| StorageError::Flash)?;
+        match ota.current_ota_state() {
+            Ok(OtaImageState::New) | Ok(OtaImageState::PendingVerify) => {
+                info!("Marking running firmware as valid");
+                ota.set_current_ota_state(OtaImageState::Valid)
+                    .map_err(|_| StorageError::Flash)
+            }
+            _ => Ok(()),
+        }
+    });
+    if let Err(err) = result {
+        warn!("Failed to update OTA image state: {}", err);
+    }
+}
+
+/// 请求从 HTTP 服务器下载固件升级，由 [http_task] 执行
+///
+/// 签名从固件地址加上 [SIGNATURE_SUFFIX] 的地址下载。
+///
+/// # 返回
+/// 地址格式错误或超过 [URL_LEN] 时返回 false
+pub fn request_http(url: &str) -> bool {
+    if http_client::parse_url(url).is_err() {
+        return false;
+    }
+    let Ok(url) = String::try_from(url) else {
+        return false;
+    };
+    HTTP_REQUEST.signal(url);
+    true
+}
+
+/// 下载升级任务，执行 [request_http] 请求的升级，成功后重启
+#[embassy_executor::task]
+pub async fn http_task(stack: Stack<'static>) {
+    loop {
+        let url = HTTP_REQUEST.wait().await;
+        info!("Downloading firmware from {}", url.as_str());
+        let mut progress = Progress::new("ota");
+        match apply_from_http(stack, &url, &mut progress).await {
+            Ok(()) => {
+                info!("Downloaded firmware update applied");
+                system::reboot(RebootReason::FirmwareUpdate).await
+            }
+            Err(err) => warn!("Firmware download rejected: {}", err),
+        }
+    }
+}
+
+/// 下载签名和固件并写入下一个 OTA 分区
+///
+/// 下载期间独占 Flash 实例（见 [storage::take_flash]），设置等数据块的读写返回
+/// [StorageError::Busy]
+async fn apply_from_http(
+    stack: Stack<'_>,
+    url: &str,
+    progress: &mut Progress<'_>,
+) -> Result<(), OtaError> {
+    let (host, port, path) = http_client::parse_url(url).map_err(|_| OtaError::Source)?;
+    let mut signature_path: String<{ URL_LEN + SIGNATURE_SUFFIX.len() }> = String::new();
+    write!(signature_path, "{path}{SIGNATURE_SUFFIX}").map_err(|_| OtaError::Source)?;
+    let mut response = [0u8; 512];
+    let options = &http_client::OPTIONS;
+    let signature = http_client::get(stack, options, host, port, &signature_path, &mut response)
+        .await
+        .map_err(OtaError::Download)?;
+    let signature =
+        <[u8; SIGNATURE_LEN]>::try_from(signature).map_err(|_| OtaError::MissingSignature)?;
+
+    let mut flash = storage::take_flash()?;
+    let mut buffer = [0u8; PARTITION_TABLE_MAX_LEN];
+    let result = async {
+        let mut ota = open_updater(&mut flash, &mut buffer)?;
+        {
+            let mut partition = next_partition(&mut ota)?;
+            let mut writer = PartitionWriter::new(&mut partition, &signature)?;
+            let message = i18n::lcd(Msg::OtaWriting);
+            let mut failed = None;
+            let download =
+                http_client::get_streamed(stack, &DOWNLOAD, host, port, path, |data, total| {
+                    if let Err(err) = writer.push(data) {
+                        failed = Some(err);
+                        return false;
+                    }
+                    let total = total.unwrap_or(writer.capacity);
+                    progress.update(half_percent(writer.written(), total), message);
+                    true
+                })
+                .await;
+            if let Some(err) = failed {
+                return Err(err);
+            }
+            let len = download.map_err(OtaError::Download)?;
+            let sha256 = writer.finish()?;
+            info!("Signature verified, checking {} bytes in flash", len);
+            check_partition(&mut partition, len, &sha256, progress)?;
+        }
+        activate(&mut ota)
+    }
+    .await;
+    storage::restore_flash(flash);
+    result
+}
+
+/// 从分区读回写入的固件，确认 SHA-256 与下载时一致
+///
+/// # 参数
+/// * `progress` - 进度报告器，读回占 50-100%
+fn check_partition(
+    partition: &mut FlashRegion<'_, FlashStorage<'static>>,
+    len: u32,
+    expected: &[u8; 32],
+    progress: &mut Progress<'_>,
+) -> Result<(), OtaError> {
+    let message = i18n::lcd(Msg::OtaVerifying);
+    let mut sha256 = Sha256::new();
+    let mut buf = [0u8; CHUNK_LEN];
+    let mut offset = 0u32;
+    while offset < len {
+        let chunk = &mut buf[..(len - offset).min(CHUNK_LEN as u32) as usize];
+        partition
+            .read(offset, chunk)
+            .map_err(|_| OtaError::Storage(StorageError::Flash))?;
+        sha256.update(&*chunk);
+        offset += chunk.len() as u32;
+        progress.update(50 + half_percent(offset, len), message);
+    }
+    if sha256.finalize()[..] != expected[..] {
+        warn!("Firmware in flash differs from the download");
+        return Err(OtaError::ImageChanged);
+    }
+    Ok(())
+}
+
+/// 把下载的固件按扇区写入分区，同时校验签名并计算 SHA-256
+struct PartitionWriter<'p, 'f> {
+    partition: &'p mut FlashRegion<'f, FlashStorage<'static>>,
+    capacity: u32,
+    /// 凑满一个扇区再写入，避免同一扇区反复擦除
+    block: [u8; CHUNK_LEN],
+    filled: usize,
+    /// 已写入分区的长度
+    offset: u32,
+    signature: VerifyingState,
+    sha256: Sha256,
+}
+
+impl<'p, 'f> PartitionWriter<'p, 'f> {
+    fn new(
+        partition: &'p mut FlashRegion<'f, FlashStorage<'static>>,
+        signature: &[u8; SIGNATURE_LEN],
+    ) -> Result<Self, OtaError> {
+        let signature = PublicKey::new(*OTA_PUBLIC_KEY)
+            .verify_incremental(&Signature::new(*signature))
+            .map_err(|_| OtaError::BadSignature)?;
+        Ok(PartitionWriter {
+            capacity: partition.capacity() as u32,
+            partition,
+            block: [0; CHUNK_LEN],
+            filled: 0,
+            offset: 0,
+            signature,
+            sha256: Sha256::new(),
+        })
+    }
+
+    /// 已接收的长度
+    fn written(&self) -> u32 {
+        self.offset + self.filled as u32
+    }
+
+    /// 接收一块下载的数据
+    fn push(&mut self, mut data: &[u8]) -> Result<(), OtaError> {
+        if self.written() == 0 && data.first().is_some_and(|&byte| byte != ESP_IMAGE_MAGIC) {
+            return Err(OtaError::InvalidImage);
+        }
+        let image = self.written() as usize + data.len();
+        if image > self.capacity as usize {
+            return Err(OtaError::ImageTooLarge {
+                image: image as u32,
+                partition: self.capacity,
+            });
+        }
+        while !data.is_empty() {
+            let take = (CHUNK_LEN - self.filled).min(data.len());
+            self.block[self.filled..self.filled + take].copy_from_slice(&data[..take]);
+            self.filled += take;
+            data = &data[take..];
+            if self.filled == CHUNK_LEN {
+                self.flush()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// 写入凑好的数据
+    fn flush(&mut self) -> Result<(), OtaError> {
+        let block = &self.block[..self.filled];
+        self.signature.absorb(block);
+        self.sha256.update(block);
+        self.partition
+            .write(self.offset, block)
+            .map_err(|_| OtaError::Storage(StorageError::Flash))?;
+        self.offset += self.filled as u32;
+        self.filled = 0;
+        Ok(())
+    }
+
+    /// 写入剩余的数据并校验签名
+    ///
+    /// # 返回
+    /// 写入内容的 SHA-256
+    fn finish(mut self) -> Result<[u8; 32], OtaError> {
+        if self.filled > 0 {
+            self.flush()?;
+        }
+        if self.offset == 0 {
+            return Err(OtaError::InvalidImage);
+        }
+        if self.signature.verify().is_err() {
+            warn!("Firmware signature does not match the embedded public key");
+            return Err(OtaError::BadSignature);
+        }
+        Ok(self.sha256.finalize().into())
+    }
+}
+
+/// TF 卡上的固件文件
+#[cfg(feature = "sd")]
+struct SdFirmware<'a, 'b> {
+    file: &'a mut SdFile<'b>,
+    len: u32,
+}
+
+#[cfg(feature = "sd")]
+impl FirmwareSource for SdFirmware<'_, '_> {
+    fn len(&self) -> u32 {
+        self.len
+    }
+
+    fn read_at(&mut self, offset: u32, buf: &mut [u8]) -> Result<usize, OtaError> {
+        self.file
+            .seek_from_start(offset)
+            .map_err(|_| OtaError::Source)?;
+        self.file.read(buf).map_err(|_| OtaError::Source)
+    }
+}
+
+/// 解析 8 位十六进制 CRC32 文本
+#[cfg(feature = "sd")]
+fn parse_crc(text: &[u8]) -> Option<u32> {
+    let text = core::str::from_utf8(text).ok()?.trim();
+    let text = text.strip_prefix("0x").unwrap_or(text);
+    // 兼容 `crc32` 命令输出的 "<crc>  <文件名>" 格式
+    let hex = text.split_whitespace().next()?;
+    u32::from_str_radix(hex, 16).ok()
+}
+
+/// 检查 TF 卡上的离线升级文件并执行升级
+///
+/// 在根目录找到 `FIRMWARE.BIN` 和 `FIRMWARE.SIG` 时执行升级，
+/// 若同时存在 `FIRMWARE.CRC` 也会一并校验。
+/// 成功后将固件文件重命名为 `FIRMWARE.OLD` 并重启；
+/// 校验失败时重命名为 `FIRMWARE.BAD`，避免每次启动重复尝试。
+///
+/// # 参数
+/// * `progress` - 进度报告器，启动阶段由调用方直接绘制到 LCD
+///
+/// # 返回
+/// 没有升级文件时返回 Ok(())，升级成功时不会返回
+#[cfg(feature = "sd")]
+pub async fn apply_from_sd(progress: &mut Progress<'_>) -> Result<(), OtaError> {
+    if !sdcard::is_mounted() {
+        return Ok(());
+    }
+
+    let result = sdcard::with_root_dir(|dir| {
+        if !sdcard::file_exists(dir, SD_FIRMWARE_FILE)? {
+            return Ok(None);
+        }
+        info!("Found {} on SD card", SD_FIRMWARE_FILE);
+        let outcome = flash_from_dir(dir, progress)?;
+        let new_name = if outcome.is_ok() {
+            SD_APPLIED_FILE
+        } else {
+            SD_REJECTED_FILE
+        };
+        if sdcard::file_exists(dir, new_name)? {
+            dir.delete_file_in_dir(new_name)?;
+        }
+        sdcard::rename(dir, SD_FIRMWARE_FILE, new_name)?;
+        Ok(Some(outcome))
+    })
+    .await;
+
+    match result {
+        Ok(None) => Ok(()),
+        Ok(Some(Ok(()))) => {
+            info!("Offline firmware update applied");
+            system::reboot(RebootReason::FirmwareUpdate).await
+        }
+        Ok(Some(Err(err))) => {
+            warn!("Offline firmware update rejected: {}", err);
+            Err(err)
+        }
+        Err(err) => {
+            warn!("SD card error during firmware update: {}", defmt::Debug2Format(&err));
+            Err(OtaError::Source)
+        }
+    }
+}
+
+/// 从目录中读取签名和校验信息并写入固件
+///
+/// 外层 Result 表示 TF 卡访问错误，内层 Result 表示固件校验或写入结果
+#[cfg(feature = "sd")]
+fn flash_from_dir(
+    dir: &mut Dir<'_>,
+    progress: &mut Progress<'_>,
+) -> Result<Result<(), OtaError>, SdError> {
+    let mut signature = [0u8; SIGNATURE_LEN];
+    if !sdcard::file_exists(dir, SD_SIGNATURE_FILE)?
+        || sdcard::read_file(dir, SD_SIGNATURE_FILE, &mut signature)? != SIGNATURE_LEN
+    {
+        return Ok(Err(OtaError::MissingSignature));
+    }
+
+    let mut crc_text = [0u8; 64];
+    let expected_crc = if sdcard::file_exists(dir, SD_CRC_FILE)? {
+        let len = sdcard::read_file(dir, SD_CRC_FILE, &mut crc_text)?;
+        parse_crc(&crc_text[..len])
+    } else {
+        None
+    };
+
+    let mut file = dir.open_file_in_dir(SD_FIRMWARE_FILE, Mode::ReadOnly)?;
+    let mut source = SdFirmware {
+        len: file.length(),
+        file: &mut file,
+    };
+    info!("Verifying firmware ({} bytes)", source.len);
+    let outcome = verify(&mut source, &signature, progress).and_then(|verified| {
+        if let Some(expected) = expected_crc
+            && expected != verified.crc
+        {
+            return Err(OtaError::CrcMismatch {
+                expected,
+                actual: verified.crc,
+            });
+        }
+        info!("Firmware signature verified");
+        write_image(&mut source, &verified, progress)
+    });
+    file.close()?;
+    Ok(outcome)
+}
@@ -0,0 +1,239 @@
+//! 离线时暂存遥测数据的 TF 卡队列
+//!
+//! 每个发送方使用自己的队列（[Outbox]），互不影响：
+//!
+//! - [WEBHOOK]：[crate::notifier] 的 webhook 请求体
+//! - [TELEMETRY]：[crate::mqtt] 的遥测消息
+//!
+//! 离线或发送失败时，发送方用 [Outbox::push] 把待发送的数据追加到 TF 卡根目录的队列文件，
+//! 每行一条；恢复联网后用 [Outbox::peek] 从最早的一条开始读取，发送成功后用 [Outbox::pop]
+//! 移除。记录保存在卡上，断电重启后继续发送。
+//!
+//! 文件只追加：已发送的记录不立即删除，而是把第一条未发送记录的位置写入位置文件，
+//! 全部发送完后再删除两个文件。待发送的数据超过 [MAX_PENDING] 时丢弃最早的记录，
+//! 剩余的复制到新文件，一次腾出四分之一的空间，不必每次追加都复制。
+//!
+//! 掉电保护：每次写完即关闭文件；位置文件没有写完时从头发送，已发送的记录可能重发，
+//! 但不会丢失。末尾写了一半的记录（没有换行）在读取时丢弃。
+//!
+//! 限制：只使用 TF 卡，没有插卡时离线的数据仍只保存在内存中（或直接丢弃，见各发送方）。
+
+use crate::sdcard::{self, Dir, SdError};
+use defmt::{info, warn};
+use embedded_sdmmc::Mode;
+
+/// webhook 通知的队列
+pub const WEBHOOK: Outbox = Outbox {
+    queue_file: "OUTBOX.TXT",
+    offset_file: "OUTBOX.POS",
+    compact_file: "OUTBOX.TMP",
+};
+
+/// MQTT 遥测的队列
+pub const TELEMETRY: Outbox = Outbox {
+    queue_file: "MQTTQ.TXT",
+    offset_file: "MQTTQ.POS",
+    compact_file: "MQTTQ.TMP",
+};
+
+/// 每个队列的待发送数据上限（字节）
+const MAX_PENDING: u32 = 64 * 1024;
+
+/// 单条记录的最大长度，不含换行
+pub const MAX_RECORD_LEN: usize = 1024;
+
+/// 查找记录开头时的读缓冲区大小
+const SCAN_BUF_LEN: usize = 512;
+
+/// TF 卡上的一个队列
+pub struct Outbox {
+    /// 队列文件名
+    queue_file: &'static str,
+    /// 第一条未发送记录的位置（4 字节，小端）
+    offset_file: &'static str,
+    /// 丢弃最早的记录时使用的临时文件
+    compact_file: &'static str,
+}
+
+impl Outbox {
+    /// 追加一条记录
+    ///
+    /// 待发送的数据将超过 [MAX_PENDING] 时先丢弃最早的记录
+    ///
+    /// # 参数
+    /// * `record` - 一行文本，不含换行，超过 [MAX_RECORD_LEN] 时丢弃
+    ///
+    /// # 返回
+    /// 未插入 TF 卡或写入失败时返回错误，由调用方保留记录
+    pub async fn push(&self, record: &str) -> Result<(), SdError> {
+        if record.len() > MAX_RECORD_LEN {
+            warn!(
+                "Outbox record of {} bytes is too long, dropping",
+                record.len()
+            );
+            return Ok(());
+        }
+        sdcard::with_root_dir(|dir| {
+            let offset = self.read_offset(dir)?;
+            let pending = self.queue_len(dir)?.saturating_sub(offset);
+            if pending + record.len() as u32 + 1 > MAX_PENDING {
+                self.compact(dir, offset, MAX_PENDING * 3 / 4)?;
+            }
+            let mut file = dir.open_file_in_dir(self.queue_file, Mode::ReadWriteCreateOrAppend)?;
+            file.write(record.as_bytes())?;
+            file.write(b"\n")?;
+            file.close()
+        })
+        .await
+    }
+
+    /// 读取最早的一条记录，不移除
+    ///
+    /// # 参数
+    /// * `buf` - 接收缓冲区，应能放下 [MAX_RECORD_LEN] 加换行
+    ///
+    /// # 返回
+    /// 记录的长度（不含换行），队列为空时返回 None
+    pub async fn peek(&self, buf: &mut [u8]) -> Result<Option<usize>, SdError> {
+        sdcard::with_root_dir(|dir| {
+            if !sdcard::file_exists(dir, self.queue_file)? {
+                return Ok(None);
+            }
+            let offset = self.read_offset(dir)?;
+            let mut file = dir.open_file_in_dir(self.queue_file, Mode::ReadOnly)?;
+            let mut len = 0;
+            if offset < file.length() {
+                file.seek_from_start(offset)?;
+                while len < buf.len() && !file.is_eof() {
+                    let read = file.read(&mut buf[len..])?;
+                    if read == 0 {
+                        break;
+                    }
+                    len += read;
+                }
+            }
+            file.close()?;
+            match buf[..len].iter().position(|&b| b == b'\n') {
+                Some(end) => Ok(Some(end)),
+                None => {
+                    // 没有未发送的记录，或只剩掉电时写了一半的记录
+                    if len > 0 {
+                        warn!("Discarding {} bytes of incomplete outbox record", len);
+                    }
+                    self.clear(dir)?;
+                    Ok(None)
+                }
+            }
+        })
+        .await
+    }
+
+    /// 移除最早的一条记录
+    ///
+    /// # 参数
+    /// * `len` - [peek] 返回的记录长度
+    pub async fn pop(&self, len: usize) -> Result<(), SdError> {
+        sdcard::with_root_dir(|dir| {
+            let offset = self.read_offset(dir)? + len as u32 + 1;
+            if offset >= self.queue_len(dir)? {
+                info!("Outbox {} drained", self.queue_file);
+                self.clear(dir)
+            } else {
+                self.write_offset(dir, offset)
+            }
+        })
+        .await
+    }
+
+    /// TF 卡上待发送的字节数，未插卡或读取失败时为 0
+    pub async fn pending(&self) -> u32 {
+        if !sdcard::is_mounted() {
+            return 0;
+        }
+        sdcard::with_root_dir(|dir| {
+            let offset = self.read_offset(dir)?;
+            Ok(self.queue_len(dir)?.saturating_sub(offset))
+        })
+        .await
+        .unwrap_or(0)
+    }
+
+    /// 队列文件的长度，文件不存在时为 0
+    fn queue_len(&self, dir: &mut Dir<'_>) -> Result<u32, SdError> {
+        if !sdcard::file_exists(dir, self.queue_file)? {
+            return Ok(0);
+        }
+        let file = dir.open_file_in_dir(self.queue_file, Mode::ReadOnly)?;
+        let len = file.length();
+        file.close()?;
+        Ok(len)
+    }
+
+    /// 读取第一条未发送记录的位置，位置文件不存在或不完整时为 0
+    fn read_offset(&self, dir: &mut Dir<'_>) -> Result<u32, SdError> {
+        if !sdcard::file_exists(dir, self.offset_file)? {
+            return Ok(0);
+        }
+        let mut bytes = [0u8; 4];
+        let len = sdcard::read_file(dir, self.offset_file, &mut bytes)?;
+        Ok(if len == bytes.len() {
+            u32::from_le_bytes(bytes)
+        } else {
+            0
+        })
+    }
+
+    fn write_offset(&self, dir: &mut Dir<'_>, offset: u32) -> Result<(), SdError> {
+        let mut file = dir.open_file_in_dir(self.offset_file, Mode::ReadWriteCreateOrTruncate)?;
+        file.write(&offset.to_le_bytes())?;
+        file.close()
+    }
+
+    /// 删除队列文件和位置文件
+    fn clear(&self, dir: &mut Dir<'_>) -> Result<(), SdError> {
+        for name in [self.offset_file, self.queue_file] {
+            if sdcard::file_exists(dir, name)? {
+                dir.delete_file_in_dir(name)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// 丢弃最早的记录，直到待发送的数据不超过 `target` 字节
+    ///
+    /// 保留的记录从一条记录的开头开始复制到新文件（见 [sdcard::copy_file]），再替换队列文件
+    fn compact(&self, dir: &mut Dir<'_>, offset: u32, target: u32) -> Result<(), SdError> {
+        let mut src = dir.open_file_in_dir(self.queue_file, Mode::ReadOnly)?;
+        let mut buf = [0u8; SCAN_BUF_LEN];
+        let mut start = src.length().saturating_sub(target).max(offset);
+        src.seek_from_start(start)?;
+        // 不在记录开头时跳到下一条记录
+        if start > offset {
+            while !src.is_eof() {
+                let len = src.read(&mut buf)?;
+                if len == 0 {
+                    break;
+                }
+                if let Some(end) = buf[..len].iter().position(|&b| b == b'\n') {
+                    start += end as u32 + 1;
+                    break;
+                }
+                start += len as u32;
+            }
+        }
+        src.close()?;
+
+        sdcard::copy_file(dir, self.queue_file, start, self.compact_file)?;
+        warn!(
+            "Outbox {} full, dropped {} bytes of oldest records",
+            self.queue_file,
+            start - offset
+        );
+
+        // 先删除位置文件：替换中途掉电时从旧文件的开头重发，不会跳过记录
+        if sdcard::file_exists(dir, self.offset_file)? {
+            dir.delete_file_in_dir(self.offset_file)?;
+        }
+        sdcard::rename(dir, self.compact_file, self.queue_file)
+    }
+}
@@ -0,0 +1,273 @@
+//! ESP-NOW 配对
+//!
+//! 两块板在链路测试屏幕（[crate::linktest::display_task]）上各按一次确认键（[Action::StartStop]，
+//! 默认 KEY2，见 [crate::keymap]）开始配对。两块板在 [PAIR_TIMEOUT] 内找到对方后交换 X25519 公钥，
+//! 协商出 ESP-NOW 加密使用的密钥，并在两块板的 LCD 上显示同一个 6 位配对码。
+//! 两边的配对码相同时在两块板上分别再按确认键保存密钥（见 [espnow::pair]），
+//! 不同时按返回键（[Action::LapReset]，默认 KEY3）取消。
+//!
+//! # 协议
+//!
+//! 配对报文以广播发送，每 [RESEND_INTERVAL] 重发一次，[FRAME_LEN] 字节：
+//! 魔数 `PR`、类型 1 字节、内容 32 字节。
+//!
+//! 1. HELLO：自己公钥的 SHA-256，作为承诺
+//! 2. KEY：自己的公钥，收到对方的 HELLO 之后才发送
+//!
+//! 收到对方的 KEY 并确认它与 HELLO 中的承诺一致后，双方计算：
+//!
+//! - 共享密钥：X25519(自己的私钥, 对方的公钥)
+//! - 配对码：SHA-256(`esp-app-4 pair code` ‖ 较小的公钥 ‖ 较大的公钥 ‖ 共享密钥)
+//!   的前 4 字节（大端）模 1000000
+//! - ESP-NOW 密钥：SHA-256(`esp-app-4 pair key` ‖ 同上) 的前 16 字节
+//!
+//! 配对码只显示在屏幕上，不经过无线。中间人要让两边的配对码相同，只能在看到对方的公钥之前
+//! 承诺自己的公钥，配对码一致的机会是百万分之一。
+//!
+//! 限制：附近同时只能有一对板子在配对，第一个收到的 HELLO 决定对端。
+//! 只有一方确认时，另一方没有保存密钥，两者的单播帧无法互通，需要重新配对。
+
+use crate::espnow::{self, Frame};
+use crate::settings::ESPNOW_KEY_LEN;
+use core::cell::Cell;
+use critical_section::Mutex;
+use defmt::{info, warn};
+use ed25519_compact::x25519::{PublicKey, SecretKey};
+use embassy_futures::select::{Either, Either3, select, select3};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::channel::Channel;
+use embassy_sync::signal::Signal;
+use embassy_time::{Duration, Instant, Timer, with_timeout};
+use esp_hal::rng::Rng;
+use sha2::{Digest, Sha256};
+
+#[cfg(doc)]
+use crate::keymap::Action;
+
+/// 配对的最长时间，包括比较配对码
+const PAIR_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// 配对报文的重发间隔
+const RESEND_INTERVAL: Duration = Duration::from_millis(500);
+
+/// 配对结果显示的时间
+const RESULT_TIME: Duration = Duration::from_secs(3);
+
+/// 配对报文格式
+const FRAME_LEN: usize = 3 + 32;
+const MAGIC: [u8; 2] = *b"PR";
+const KIND_HELLO: u8 = 0;
+const KIND_KEY: u8 = 1;
+
+/// 配对码的位数对应的模
+const CODE_MODULUS: u32 = 1_000_000;
+
+/// 配对的进度
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    /// 未在配对
+    Idle,
+    /// 正在寻找对方
+    Searching,
+    /// 等待用户比较配对码
+    Compare { peer: [u8; 6], code: u32 },
+    /// 已保存密钥
+    Paired([u8; 6]),
+    /// 取消、超时或出错
+    Failed,
+}
+
+/// 当前的配对进度
+static PHASE: Mutex<Cell<Phase>> = Mutex::new(Cell::new(Phase::Idle));
+
+/// 请求开始配对
+static START: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+
+/// 用户的回答：true 为确认，false 为取消
+static ANSWER: Signal<CriticalSectionRawMutex, bool> = Signal::new();
+
+/// 收到的配对报文
+static FRAMES: Channel<CriticalSectionRawMutex, Frame, 4> = Channel::new();
+
+/// 当前的配对进度
+pub fn phase() -> Phase {
+    critical_section::with(|cs| PHASE.borrow(cs).get())
+}
+
+fn set_phase(phase: Phase) {
+    critical_section::with(|cs| PHASE.borrow(cs).set(phase));
+}
+
+/// 开始配对，正在配对时忽略
+pub fn start() {
+    if phase() == Phase::Idle {
+        START.signal(());
+    }
+}
+
+/// 回答是否保存配对：比较配对码时确认或取消，寻找对方时只能取消
+pub fn answer(accept: bool) {
+    ANSWER.signal(accept);
+}
+
+/// 是否为配对报文，由 [espnow::espnow_task] 分流
+pub fn is_pairing_frame(data: &[u8]) -> bool {
+    data.len() == FRAME_LEN && data[..2] == MAGIC
+}
+
+/// 交给配对任务，未在配对或处理不及时时丢弃
+pub fn deliver(frame: Frame) {
+    if matches!(phase(), Phase::Searching | Phase::Compare { .. }) {
+        FRAMES.try_send(frame).ok();
+    }
+}
+
+/// 配对任务，等待 [start] 后完成一次配对
+#[embassy_executor::task]
+pub async fn pairing_task() {
+    loop {
+        START.wait().await;
+        ANSWER.reset();
+        FRAMES.clear();
+        set_phase(Phase::Searching);
+        info!("ESP-NOW pairing started");
+        let phase = match with_timeout(PAIR_TIMEOUT, exchange()).await {
+            Ok(Some(peer)) => {
+                info!("ESP-NOW paired with {}", espnow_id(&peer).as_str());
+                Phase::Paired(peer)
+            }
+            Ok(None) => Phase::Failed,
+            Err(_) => {
+                warn!("ESP-NOW pairing timed out");
+                Phase::Failed
+            }
+        };
+        set_phase(phase);
+        Timer::after(RESULT_TIME).await;
+        set_phase(Phase::Idle);
+        START.reset();
+    }
+}
+
+/// 交换公钥、显示配对码并等待用户确认
+///
+/// # 返回
+/// 保存了密钥的对端；取消、出错时返回 None
+async fn exchange() -> Option<[u8; 6]> {
+    let secret = random_secret();
+    let public = secret.recover_public_key().ok()?;
+    let hello = encode(KIND_HELLO, &sha256(&[&public[..]]));
+    let key = encode(KIND_KEY, &public);
+
+    // 对方的地址和承诺
+    let mut peer: Option<([u8; 6], [u8; 32])> = None;
+    let mut next_send = Instant::now();
+    let (mac, peer_public) = loop {
+        match select3(FRAMES.receive(), Timer::at(next_send), ANSWER.wait()).await {
+            Either3::First(frame) => {
+                let Some((kind, body)) = decode(&frame.data) else {
+                    continue;
+                };
+                match peer {
+                    None if kind == KIND_HELLO => {
+                        info!("ESP-NOW pairing with {}", espnow_id(&frame.peer).as_str());
+                        peer = Some((frame.peer, body));
+                        espnow::send(espnow::BROADCAST, &key);
+                    }
+                    Some((mac, commit)) if kind == KIND_KEY && frame.peer == mac => {
+                        if sha256(&[&body]) != commit {
+                            warn!("ESP-NOW pairing key does not match its commitment");
+                            return None;
+                        }
+                        break (mac, PublicKey::from_slice(&body).ok()?);
+                    }
+                    _ => {}
+                }
+            }
+            Either3::Second(()) => {
+                next_send += RESEND_INTERVAL;
+                espnow::send(espnow::BROADCAST, &hello);
+                if peer.is_some() {
+                    espnow::send(espnow::BROADCAST, &key);
+                }
+            }
+            Either3::Third(_) => return None,
+        }
+    };
+
+    let shared = peer_public.dh(&secret).ok()?;
+    let (low, high) = if public[..] < peer_public[..] {
+        (&public[..], &peer_public[..])
+    } else {
+        (&peer_public[..], &public[..])
+    };
+    let digest = sha256(&[b"esp-app-4 pair code", low, high, &shared[..]]);
+    let code = u32::from_be_bytes([digest[0], digest[1], digest[2], digest[3]]) % CODE_MODULUS;
+    let digest = sha256(&[b"esp-app-4 pair key", low, high, &shared[..]]);
+    let mut link_key = [0u8; ESPNOW_KEY_LEN];
+    link_key.copy_from_slice(&digest[..ESPNOW_KEY_LEN]);
+    set_phase(Phase::Compare { peer: mac, code });
+
+    // 对方可能还没有收到自己的公钥，等待确认期间继续重发
+    loop {
+        match select(ANSWER.wait(), Timer::at(next_send)).await {
+            Either::First(true) => break,
+            Either::First(false) => return None,
+            Either::Second(()) => {
+                next_send += RESEND_INTERVAL;
+                espnow::send(espnow::BROADCAST, &key);
+            }
+        }
+    }
+    match espnow::pair(mac, link_key) {
+        Ok(()) => Some(mac),
+        Err(err) => {
+            warn!("Failed to save ESP-NOW pairing: {}", err);
+            None
+        }
+    }
+}
+
+/// 随机生成 X25519 私钥，射频开启时硬件随机数发生器输出真随机数
+fn random_secret() -> SecretKey {
+    let rng = Rng::new();
+    let mut bytes = [0u8; SecretKey::BYTES];
+    for chunk in bytes.chunks_exact_mut(4) {
+        chunk.copy_from_slice(&rng.random().to_le_bytes());
+    }
+    SecretKey::new(bytes)
+}
+
+/// 依次计算各部分的 SHA-256
+fn sha256(parts: &[&[u8]]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    for part in parts {
+        hasher.update(part);
+    }
+    hasher.finalize().into()
+}
+
+/// 编码配对报文
+fn encode(kind: u8, body: &[u8; 32]) -> [u8; FRAME_LEN] {
+    let mut frame = [0u8; FRAME_LEN];
+    frame[..2].copy_from_slice(&MAGIC);
+    frame[2] = kind;
+    frame[3..].copy_from_slice(body);
+    frame
+}
+
+/// 解码配对报文
+///
+/// # 返回
+/// 类型和内容，长度或魔数不对时返回 None
+fn decode(frame: &[u8]) -> Option<(u8, [u8; 32])> {
+    if !is_pairing_frame(frame) {
+        return None;
+    }
+    Some((frame[2], frame[3..].try_into().ok()?))
+}
+
+/// 对端的设备 ID，用于日志
+fn espnow_id(mac: &[u8; 6]) -> heapless::String<{ crate::device::ID_LEN }> {
+    crate::device::format_id(mac)
+}
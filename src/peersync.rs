@@ -0,0 +1,273 @@
+//! 多块板子之间同步设置
+//!
+//! 在设置中填写同步组名（命令行 `sync <组名>`）后启用，组名相同的板子在同一局域网中
+//! 自动互相发现，不需要服务器或对端地址：每 [ANNOUNCE_INTERVAL] 把共享的设置（[Shared]）
+//! 连同修改时间广播到 UDP [PORT] 端口，收到更新的设置时写入本机并保存。
+//!
+//! 冲突按“最后写入者胜出”处理：每次在本机修改共享设置（命令行、HTTP 或按键均可）
+//! 都记录修改时间（系统时间，未校时时在上次的基础上加一），时间较新的一方胜出，
+//! 时间相同时 MAC 地址较大的一方胜出，各板最终一致。本机已校时时，修改时间超前本机时间
+//! [MAX_CLOCK_AHEAD] 秒以上的报文被丢弃。
+//!
+//! 共享的设置只有界面语言、配色、强调色、时钟表盘、时区和夜间模式时段。
+//! WiFi、主机名、设备标识、webhook、syslog、定时任务和访问控制等与单板或安全相关的设置
+//! 不同步。
+//!
+//! 限制：报文不加密也不认证，同一局域网中知道组名的人可以修改各板的共享设置；
+//! 同步只在可信的网络中启用。固件中没有告警阈值设置，因此没有阈值可同步。
+
+use crate::netstats::{self, Link};
+use crate::service::{self, Service};
+use crate::settings::{self, SYNC_GROUP_LEN, Settings};
+use crate::wallclock;
+use defmt::{info, warn};
+use embassy_net::udp::{PacketMetadata, UdpSocket};
+use embassy_net::{IpAddress, Stack};
+use embassy_time::{Duration, Instant, with_deadline};
+use esp_hal::efuse::Efuse;
+
+/// 同步使用的 UDP 端口
+pub const PORT: u16 = 7008;
+
+/// 广播间隔
+const ANNOUNCE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// 对端的修改时间最多比本机时间超前这么多秒，再超前的报文丢弃，
+/// 避免时钟错误（或伪造）的对端用遥远的将来时间压住之后所有的修改
+const MAX_CLOCK_AHEAD: u64 = 300;
+
+/// 受限广播地址
+const BROADCAST: IpAddress = IpAddress::v4(255, 255, 255, 255);
+
+/// 报文格式：魔数 2 字节、版本 1 字节、组名长度 1 字节、组名、修改时间 u32（Unix 秒，小端）、
+/// MAC 地址 6 字节、共享的设置
+const MAGIC: [u8; 2] = *b"PS";
+const VERSION: u8 = 1;
+const PACKET_LEN: usize = 4 + SYNC_GROUP_LEN + 4 + 6 + Shared::LEN;
+
+/// 在各板之间共享的设置
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Shared {
+    language: u8,
+    theme: u8,
+    accent: u16,
+    clock_face: u8,
+    utc_offset: i16,
+    night_hours: [u8; 2],
+}
+
+impl Shared {
+    /// 编码后的长度
+    const LEN: usize = 9;
+
+    /// 从设置中取出共享的部分
+    fn from_settings(s: &Settings) -> Self {
+        Shared {
+            language: s.language,
+            theme: s.theme,
+            accent: s.accent,
+            clock_face: s.clock_face,
+            utc_offset: s.utc_offset,
+            night_hours: s.night_hours,
+        }
+    }
+
+    /// 写入设置
+    fn apply(&self, s: &mut Settings) {
+        s.language = self.language;
+        s.theme = self.theme;
+        s.accent = self.accent;
+        s.clock_face = self.clock_face;
+        s.utc_offset = self.utc_offset;
+        s.night_hours = self.night_hours;
+    }
+
+    fn encode(&self) -> [u8; Self::LEN] {
+        let [a0, a1] = self.accent.to_le_bytes();
+        let [u0, u1] = self.utc_offset.to_le_bytes();
+        let [from, to] = self.night_hours;
+        [
+            self.language,
+            self.theme,
+            a0,
+            a1,
+            self.clock_face,
+            u0,
+            u1,
+            from,
+            to,
+        ]
+    }
+
+    fn decode(b: &[u8; Self::LEN]) -> Self {
+        Shared {
+            language: b[0],
+            theme: b[1],
+            accent: u16::from_le_bytes([b[2], b[3]]),
+            clock_face: b[4],
+            utc_offset: i16::from_le_bytes([b[5], b[6]]),
+            night_hours: [b[7], b[8]],
+        }
+    }
+}
+
+/// 收到的同步报文
+struct Announce<'a> {
+    group: &'a [u8],
+    stamp: u32,
+    mac: [u8; 6],
+    shared: Shared,
+}
+
+impl<'a> Announce<'a> {
+    fn parse(packet: &'a [u8]) -> Option<Self> {
+        let (header, rest) = packet.split_first_chunk::<4>()?;
+        if header[..2] != MAGIC || header[2] != VERSION {
+            return None;
+        }
+        let (group, rest) = rest.split_at_checked(header[3] as usize)?;
+        let (stamp, rest) = rest.split_first_chunk::<4>()?;
+        let (mac, rest) = rest.split_first_chunk::<6>()?;
+        let shared = rest.first_chunk::<{ Shared::LEN }>()?;
+        Some(Announce {
+            group,
+            stamp: u32::from_le_bytes(*stamp),
+            mac: *mac,
+            shared: Shared::decode(shared),
+        })
+    }
+}
+
+/// 设置同步任务
+///
+/// 没有设置同步组时只跟踪本机的修改，不收发报文；服务停止时关闭套接字（见 [crate::service]）
+///
+/// # 参数
+/// * `stack` - 网络协议栈
+#[embassy_executor::task]
+pub async fn peersync_task(stack: Stack<'static>) {
+    loop {
+        service::wait_started(Service::PeerSync).await;
+        service::run(Service::PeerSync, serve(stack)).await;
+    }
+}
+
+/// 绑定端口并收发同步报文，绑定失败时返回
+async fn serve(stack: Stack<'static>) {
+    let mut rx_meta = [PacketMetadata::EMPTY; 4];
+    let mut rx_buffer = [0u8; PACKET_LEN * 4];
+    let mut tx_meta = [PacketMetadata::EMPTY; 1];
+    let mut tx_buffer = [0u8; PACKET_LEN];
+    let mut socket = UdpSocket::new(
+        stack,
+        &mut rx_meta,
+        &mut rx_buffer,
+        &mut tx_meta,
+        &mut tx_buffer,
+    );
+    if let Err(err) = socket.bind(PORT) {
+        warn!("Failed to bind sync socket: {}", defmt::Debug2Format(&err));
+        return;
+    }
+
+    let mac = Efuse::mac_address();
+    // 上次看到的共享设置，与当前设置不同说明在本机修改过
+    let mut known = Shared::from_settings(&settings::get());
+    let mut packet = [0u8; PACKET_LEN];
+    let mut next_announce = Instant::now() + ANNOUNCE_INTERVAL;
+    loop {
+        match with_deadline(next_announce, socket.recv_from(&mut packet)).await {
+            Ok(Ok((len, meta))) => {
+                netstats::received(Link::PeerSync, len);
+                let stamp = track_local(&mut known);
+                let Some(announce) = Announce::parse(&packet[..len]) else {
+                    continue;
+                };
+                let s = settings::get();
+                // 按修改时间和 MAC 地址比较，只接受更新的设置
+                if announce.group != s.sync_group.as_bytes()
+                    || too_far_ahead(announce.stamp)
+                    || (announce.stamp, announce.mac) <= (stamp, mac)
+                    || announce.shared == known
+                {
+                    continue;
+                }
+                info!("Settings updated by peer {}", meta.endpoint.addr);
+                settings::update(|s| {
+                    announce.shared.apply(s);
+                    s.sync_stamp = announce.stamp;
+                });
+                known = announce.shared;
+                if let Err(err) = settings::save() {
+                    warn!("Failed to save synced settings: {}", err);
+                }
+            }
+            Ok(Err(_)) => {}
+            Err(_) => {
+                next_announce = Instant::now() + ANNOUNCE_INTERVAL;
+                let stamp = track_local(&mut known);
+                let group = settings::get().sync_group;
+                if group.is_empty() || !stack.is_config_up() {
+                    continue;
+                }
+                packet[..2].copy_from_slice(&MAGIC);
+                packet[2] = VERSION;
+                packet[3] = group.len() as u8;
+                let fields: [&[u8]; 4] = [
+                    group.as_bytes(),
+                    &stamp.to_le_bytes(),
+                    &mac,
+                    &known.encode(),
+                ];
+                let mut pos = 4;
+                for field in fields {
+                    packet[pos..pos + field.len()].copy_from_slice(field);
+                    pos += field.len();
+                }
+                match socket.send_to(&packet[..pos], (BROADCAST, PORT)).await {
+                    Ok(()) => netstats::sent(Link::PeerSync, pos),
+                    Err(err) => warn!("Sync announce failed: {}", defmt::Debug2Format(&err)),
+                }
+            }
+        }
+    }
+}
+
+/// 检查本机是否修改过共享设置，修改过时记录修改时间并保存
+///
+/// # 参数
+/// * `known` - 上次看到的共享设置，更新为当前值
+///
+/// # 返回
+/// 当前共享设置的修改时间
+fn track_local(known: &mut Shared) -> u32 {
+    let s = settings::get();
+    let current = Shared::from_settings(&s);
+    if current == *known {
+        return s.sync_stamp;
+    }
+    *known = current;
+    // 之前保存了超前的修改时间时（例如本机校时之前接受的报文）从当前时间重新开始，
+    // 否则本机的修改永远无法胜出
+    let last = if too_far_ahead(s.sync_stamp) {
+        0
+    } else {
+        s.sync_stamp
+    };
+    let stamp = match wallclock::now() {
+        Some(now) => (now as u32).max(last.saturating_add(1)),
+        None => last.saturating_add(1),
+    };
+    settings::update(|s| s.sync_stamp = stamp);
+    if let Err(err) = settings::save() {
+        warn!("Failed to save settings: {}", err);
+    }
+    stamp
+}
+
+/// 检查对端的修改时间是否超前本机时间 [MAX_CLOCK_AHEAD] 以上
+///
+/// 本机尚未校时时无法判断，返回 false
+fn too_far_ahead(stamp: u32) -> bool {
+    wallclock::now().is_some_and(|now| stamp as u64 > now + MAX_CLOCK_AHEAD)
+}
@@ -0,0 +1,474 @@
+//! 数码相框
+//!
+//! [Profile::PhotoFrame](crate::profile::Profile::PhotoFrame) 模式下代替渲染任务占用 LCD，
+//! 按文件名顺序轮流显示 TF 卡 `PHOTOS` 目录中的 BMP 图片。切换间隔和过渡效果保存在设置中，
+//! 可用命令行 `photo` 修改，下一张图片开始生效。
+//!
+//! 支持未压缩的 24 位 BMP 和 16 位 RGB565（BI_BITFIELDS）BMP。图片大于屏幕时居中裁剪，
+//! 小于屏幕时居中显示。没有帧缓冲，图片逐行从 TF 卡读出后直接写入 LCD；
+//! [sdcard::with_root_dir] 执行期间独占 TF 卡，因此每次只读取 [CHUNK_ROWS] 行，
+//! 避免长时间占用 TF 卡和执行器，其他任务可以在两次读取之间访问 TF 卡。
+//!
+//! 按键：KEY0 下一张，KEY1 上一张，KEY2 暂停/继续，KEY3 重新扫描目录。
+//! 命令行 `photo next|prev|pause` 和 MQTT 的 `cmd/photo` 主题（消息内容为 `next`、`prev` 或
+//! `pause`）通过 [command] 发送同样的命令。
+
+use crate::assets;
+use crate::i18n::{self, Msg};
+use crate::input::{self, Key};
+use crate::lcd::Lcd;
+use crate::sdcard::{self, SdError, SdFile};
+use crate::settings;
+use crate::st7789::{self, St7789};
+use core::fmt::Write;
+use defmt::{info, warn};
+use embassy_futures::select::{Either, select};
+use embassy_futures::yield_now;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::channel::Channel;
+use embassy_time::{Duration, Instant, with_deadline};
+use embedded_graphics::mono_font::ascii::FONT_6X10;
+use embedded_graphics::mono_font::{MonoFont, MonoTextStyleBuilder};
+use embedded_graphics::pixelcolor::Rgb565;
+use embedded_graphics::prelude::*;
+use embedded_graphics::text::Text;
+use embedded_sdmmc::Mode;
+use heapless::{String, Vec};
+
+/// 图片目录（根目录下）
+const PHOTO_DIR: &str = "PHOTOS";
+
+/// 最多显示的图片数量
+const MAX_PHOTOS: usize = 64;
+
+/// 每次从 TF 卡读取的行数
+const CHUNK_ROWS: usize = 8;
+
+/// 一行像素在文件中的最大字节数（24 位，屏幕宽度）
+const MAX_ROW_BYTES: usize = st7789::WIDTH as usize * 3;
+
+/// 读取的文件头长度：文件头 14 字节 + 信息头 40 字节 + 颜色掩码 12 字节
+const HEADER_LEN: usize = 66;
+
+/// 百叶窗效果的条带高度
+const BLIND_HEIGHT: u16 = 16;
+
+/// 目录为空或没有 TF 卡时重新扫描的间隔
+const RESCAN_INTERVAL: Duration = Duration::from_secs(30);
+
+/// 可设置的切换间隔范围（秒）
+pub const MIN_INTERVAL: u16 = 1;
+pub const MAX_INTERVAL: u16 = 3600;
+
+/// BMP 压缩方式
+const BI_RGB: u32 = 0;
+const BI_BITFIELDS: u32 = 3;
+
+/// 命令队列长度
+const COMMAND_QUEUE_LEN: usize = 4;
+
+static COMMANDS: Channel<CriticalSectionRawMutex, Command, COMMAND_QUEUE_LEN> = Channel::new();
+
+/// 过渡效果
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Transition {
+    /// 从上到下逐行替换
+    Cut,
+    /// 百叶窗：分条带交错替换
+    Blinds,
+}
+
+impl Transition {
+    /// 所有过渡效果，下标与设置中保存的编码一致
+    pub const ALL: [Transition; 2] = [Transition::Cut, Transition::Blinds];
+
+    /// 设置中保存的编码
+    pub const fn to_u8(self) -> u8 {
+        self as u8
+    }
+
+    /// 从设置中的编码解析，未知编码视为 [Transition::Cut]
+    pub const fn from_u8(value: u8) -> Transition {
+        match value {
+            1 => Transition::Blinds,
+            _ => Transition::Cut,
+        }
+    }
+
+    /// 过渡效果名称，用于命令行参数
+    pub const fn name(self) -> &'static str {
+        match self {
+            Transition::Cut => "cut",
+            Transition::Blinds => "blinds",
+        }
+    }
+}
+
+/// 相框控制命令
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Command {
+    Next,
+    Previous,
+    /// 暂停或继续自动切换
+    Pause,
+    /// 重新扫描图片目录
+    Rescan,
+}
+
+impl Command {
+    /// 按名称 `next`、`prev`、`pause` 查找命令
+    pub fn from_name(name: &str) -> Option<Command> {
+        match name {
+            "next" => Some(Command::Next),
+            "prev" => Some(Command::Previous),
+            "pause" => Some(Command::Pause),
+            _ => None,
+        }
+    }
+
+    const fn from_key(key: Key) -> Command {
+        match key {
+            Key::Key0 => Command::Next,
+            Key::Key1 => Command::Previous,
+            Key::Key2 => Command::Pause,
+            Key::Key3 => Command::Rescan,
+        }
+    }
+}
+
+/// 向相框发送命令，相框未运行或队列已满时丢弃
+pub fn command(command: Command) {
+    if COMMANDS.try_send(command).is_err() {
+        warn!("Photo frame command queue full, dropping {}", command);
+    }
+}
+
+/// 图片显示错误
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+enum PhotoError {
+    /// TF 卡访问失败
+    Sd,
+    /// LCD 写入失败
+    Lcd,
+    /// 不是 BMP 文件
+    NotBmp,
+    /// 不支持的 BMP 格式（压缩、调色板或尺寸）
+    Unsupported,
+}
+
+fn sd_error(err: SdError) -> PhotoError {
+    warn!("SD card error: {}", defmt::Debug2Format(&err));
+    PhotoError::Sd
+}
+
+/// BMP 图片信息
+struct Bmp {
+    /// 像素数据在文件中的偏移
+    offset: u32,
+    width: u32,
+    height: u32,
+    /// 行从上到下存储（高度为负数）
+    top_down: bool,
+    /// 每像素字节数，3 为 BGR888，2 为 RGB565
+    bytes_per_pixel: u32,
+}
+
+impl Bmp {
+    /// 解析文件头
+    fn parse(header: &[u8]) -> Result<Bmp, PhotoError> {
+        if header.len() < HEADER_LEN || &header[..2] != b"BM" {
+            return Err(PhotoError::NotBmp);
+        }
+        let u16_at = |i: usize| u16::from_le_bytes([header[i], header[i + 1]]);
+        let u32_at = |i: usize| {
+            u32::from_le_bytes([header[i], header[i + 1], header[i + 2], header[i + 3]])
+        };
+
+        let width = u32_at(18) as i32;
+        let height = u32_at(22) as i32;
+        let bytes_per_pixel = match (u16_at(28), u32_at(30)) {
+            (24, BI_RGB) => 3,
+            // 只支持 RGB565 掩码（红色在最高 5 位）
+            (16, BI_BITFIELDS) if u32_at(54) == 0xF800 => 2,
+            _ => return Err(PhotoError::Unsupported),
+        };
+        if width <= 0 || height == 0 || height == i32::MIN {
+            return Err(PhotoError::Unsupported);
+        }
+        Ok(Bmp {
+            offset: u32_at(10),
+            width: width as u32,
+            height: height.unsigned_abs(),
+            top_down: height < 0,
+            bytes_per_pixel,
+        })
+    }
+
+    /// 一行在文件中占用的字节数（按 4 字节对齐）
+    fn stride(&self) -> u32 {
+        (self.width * self.bytes_per_pixel).div_ceil(4) * 4
+    }
+
+    /// 第 `row` 行（从上往下数）第 `x` 个像素在文件中的偏移
+    fn pixel_offset(&self, row: u32, x: u32) -> u32 {
+        let row = if self.top_down {
+            row
+        } else {
+            self.height - 1 - row
+        };
+        self.offset + row * self.stride() + x * self.bytes_per_pixel
+    }
+}
+
+/// 图片在屏幕上的显示区域
+struct View {
+    /// 屏幕上的左上角
+    x: u16,
+    y: u16,
+    width: u16,
+    height: u16,
+    /// 图片中的裁剪起点
+    src_x: u32,
+    src_y: u32,
+}
+
+impl View {
+    fn new(bmp: &Bmp) -> View {
+        let width = bmp.width.min(st7789::WIDTH as u32);
+        let height = bmp.height.min(st7789::HEIGHT as u32);
+        View {
+            x: ((st7789::WIDTH as u32 - width) / 2) as u16,
+            y: ((st7789::HEIGHT as u32 - height) / 2) as u16,
+            width: width as u16,
+            height: height as u16,
+            src_x: (bmp.width - width) / 2,
+            src_y: (bmp.height - height) / 2,
+        }
+    }
+}
+
+/// 扫描图片目录，按文件名排序
+async fn scan() -> Result<Vec<String<12>, MAX_PHOTOS>, SdError> {
+    let mut photos: Vec<String<12>, MAX_PHOTOS> = Vec::new();
+    sdcard::with_root_dir(|root| {
+        let mut dir = root.open_dir(PHOTO_DIR)?;
+        dir.iterate_dir(|entry| {
+            if entry.attributes.is_directory() || entry.name.extension() != b"BMP" {
+                return;
+            }
+            let mut name = String::new();
+            if write!(name, "{}", entry.name).is_ok() && photos.push(name).is_err() {
+                warn!("More than {} photos, ignoring the rest", MAX_PHOTOS);
+            }
+        })
+    })
+    .await?;
+    photos.sort_unstable();
+    Ok(photos)
+}
+
+/// 读满缓冲区，文件不足时返回已读取的长度
+fn read_full(file: &mut SdFile<'_>, buf: &mut [u8]) -> Result<usize, SdError> {
+    let mut total = 0;
+    while total < buf.len() && !file.is_eof() {
+        let len = file.read(&mut buf[total..])?;
+        if len == 0 {
+            break;
+        }
+        total += len;
+    }
+    Ok(total)
+}
+
+/// 数码相框任务
+///
+/// # 参数
+/// * `lcd` - 已完成初始化的 LCD
+#[embassy_executor::task]
+pub async fn photo_task(mut lcd: Lcd) {
+    let Some(mut keys) = input::subscribe() else {
+        warn!("No key subscriber available for photo frame");
+        return;
+    };
+    input::set_captured(true);
+    lcd.fill_screen(Rgb565::BLACK).await.ok();
+
+    let mut photos = Vec::new();
+    let mut index = 0;
+    let mut paused = false;
+    let mut rescan = true;
+
+    loop {
+        if rescan {
+            rescan = false;
+            photos = if sdcard::is_mounted() {
+                scan().await.unwrap_or_else(|err| {
+                    warn!("Failed to list {}: {}", PHOTO_DIR, defmt::Debug2Format(&err));
+                    Vec::new()
+                })
+            } else {
+                Vec::new()
+            };
+            info!("Photo frame: {} photos", photos.len());
+            index = 0;
+        }
+
+        let interval = if let Some(name) = photos.get(index) {
+            let transition = Transition::from_u8(settings::get().photo_transition);
+            if let Err(err) = show(&mut lcd, name, transition).await {
+                warn!("Failed to show {}: {}", name.as_str(), err);
+            }
+            let secs = settings::get().photo_interval.clamp(MIN_INTERVAL, MAX_INTERVAL);
+            Duration::from_secs(secs as u64)
+        } else {
+            let msg = if sdcard::is_mounted() {
+                Msg::PhotoNoPhotos
+            } else {
+                Msg::PhotoNoCard
+            };
+            lcd.fill_screen(Rgb565::BLACK).await.ok();
+            draw_text(&mut lcd, i18n::lcd(msg), assets::font(), 10, 120);
+            RESCAN_INTERVAL
+        };
+
+        let deadline = Instant::now() + interval;
+        loop {
+            let next = async {
+                match select(keys.next_message_pure(), COMMANDS.receive()).await {
+                    Either::First(key) => Command::from_key(key),
+                    Either::Second(command) => command,
+                }
+            };
+            let command = if paused && !photos.is_empty() {
+                next.await
+            } else {
+                // 到时后切换到下一张（没有图片时重新扫描）
+                with_deadline(deadline, next).await.unwrap_or(if photos.is_empty() {
+                    Command::Rescan
+                } else {
+                    Command::Next
+                })
+            };
+
+            let count = photos.len().max(1);
+            match command {
+                Command::Next => index = (index + 1) % count,
+                Command::Previous => index = (index + count - 1) % count,
+                Command::Pause => {
+                    paused = !paused;
+                    if paused {
+                        let text = i18n::lcd_small(Msg::PhotoPaused);
+                        draw_text(&mut lcd, text, &FONT_6X10, 4, 234);
+                        continue;
+                    }
+                }
+                Command::Rescan => rescan = true,
+            }
+            break;
+        }
+    }
+}
+
+/// 显示一张图片
+///
+/// # 参数
+/// * `name` - `PHOTOS` 目录中的文件名
+/// * `transition` - 过渡效果
+async fn show(lcd: &mut St7789, name: &str, transition: Transition) -> Result<(), PhotoError> {
+    let bmp = sdcard::with_root_dir(|root| {
+        let mut dir = root.open_dir(PHOTO_DIR)?;
+        let mut file = dir.open_file_in_dir(name, Mode::ReadOnly)?;
+        let mut header = [0u8; HEADER_LEN];
+        let len = read_full(&mut file, &mut header)?;
+        Ok(Bmp::parse(&header[..len]))
+    })
+    .await
+    .map_err(sd_error)??;
+    let view = View::new(&bmp);
+    info!("Showing {} ({}x{})", name, bmp.width, bmp.height);
+
+    clear_margins(lcd, &view);
+
+    // 百叶窗效果先画每个条带的第一行，再画第二行，依此类推
+    let bands = match transition {
+        Transition::Cut => 1,
+        Transition::Blinds => BLIND_HEIGHT,
+    };
+    let mut rows = (0..bands).flat_map(|pass| (pass..view.height).step_by(bands as usize));
+
+    let row_bytes = view.width as usize * bmp.bytes_per_pixel as usize;
+    let mut raw = [0u8; CHUNK_ROWS * MAX_ROW_BYTES];
+    let mut pixels = [0u8; st7789::WIDTH as usize * 2];
+    loop {
+        let chunk: Vec<u16, CHUNK_ROWS> = rows.by_ref().take(CHUNK_ROWS).collect();
+        if chunk.is_empty() {
+            return Ok(());
+        }
+
+        sdcard::with_root_dir(|root| {
+            let mut dir = root.open_dir(PHOTO_DIR)?;
+            let mut file = dir.open_file_in_dir(name, Mode::ReadOnly)?;
+            for (i, &row) in chunk.iter().enumerate() {
+                let line = &mut raw[i * row_bytes..(i + 1) * row_bytes];
+                file.seek_from_start(bmp.pixel_offset(view.src_y + row as u32, view.src_x))?;
+                let len = read_full(&mut file, line)?;
+                // 文件被截断时剩余部分显示为黑色
+                line[len..].fill(0);
+            }
+            Ok(())
+        })
+        .await
+        .map_err(sd_error)?;
+
+        for (i, &row) in chunk.iter().enumerate() {
+            let line = &raw[i * row_bytes..(i + 1) * row_bytes];
+            let out = &mut pixels[..view.width as usize * 2];
+            convert_row(line, bmp.bytes_per_pixel, out);
+            lcd.blit(view.x, view.y + row, view.width, 1, out).map_err(|err| {
+                warn!("Failed to draw photo: {}", err);
+                PhotoError::Lcd
+            })?;
+        }
+        // 让出执行器，刷新期间仍能处理按键
+        yield_now().await;
+    }
+}
+
+/// 把一行 BMP 像素转换为大端 RGB565
+fn convert_row(line: &[u8], bytes_per_pixel: u32, out: &mut [u8]) {
+    if bytes_per_pixel == 2 {
+        for (src, dst) in line.chunks_exact(2).zip(out.chunks_exact_mut(2)) {
+            dst[0] = src[1];
+            dst[1] = src[0];
+        }
+        return;
+    }
+    // 24 位 BMP 按 B、G、R 顺序存储
+    for (src, dst) in line.chunks_exact(3).zip(out.chunks_exact_mut(2)) {
+        let (b, g, r) = (src[0] as u16, src[1] as u16, src[2] as u16);
+        let rgb565 = ((r >> 3) << 11) | ((g >> 2) << 5) | (b >> 3);
+        dst.copy_from_slice(&rgb565.to_be_bytes());
+    }
+}
+
+/// 图片小于屏幕时清除四周的空白区域
+fn clear_margins(lcd: &mut St7789, view: &View) {
+    let (w, h) = (st7789::WIDTH, st7789::HEIGHT);
+    let bottom = view.y + view.height;
+    let right = view.x + view.width;
+    lcd.fill_rectangle(0, 0, w, view.y, Rgb565::BLACK).ok();
+    lcd.fill_rectangle(0, bottom, w, h - bottom, Rgb565::BLACK).ok();
+    lcd.fill_rectangle(0, view.y, view.x, view.height, Rgb565::BLACK).ok();
+    lcd.fill_rectangle(right, view.y, w - right, view.height, Rgb565::BLACK).ok();
+}
+
+fn draw_text(lcd: &mut St7789, text: &str, font: &MonoFont<'_>, x: i32, y: i32) {
+    let style = MonoTextStyleBuilder::new()
+        .font(font)
+        .text_color(Rgb565::WHITE)
+        .background_color(Rgb565::BLACK)
+        .build();
+    if let Err(err) = Text::new(text, Point::new(x, y), style).draw(lcd) {
+        warn!("Failed to draw photo frame text: {}", err);
+    }
+}
@@ -0,0 +1,512 @@
+//! PID 控制回路
+//!
+//! [pid_task] 每个控制周期读取设置中选择的读数（例如 `bme280.t`，见 [crate::sensor]）
+//! 作为过程量，与设定值比较后由 [Pid] 算出输出，写入 LEDC 产生的 PWM（[PwmOutput]），
+//! 例如驱动加热片的 MOSFET。输出为 0-100% 的占空比，同时以读数 `pid.out`（%）发布。
+//!
+//! [Pid] 用定点数计算，过程量、设定值和增益都以千分之一为单位保存为整数，不依赖浮点运算：
+//!
+//! - 比例：`Kp × 误差`，Kp 的单位是每单位过程量的输出百分比
+//! - 积分：`Ki × ∫误差 dt`，Ki 的单位是每单位过程量每秒的输出百分比。输出饱和时，
+//!   误差把输出继续推向饱和方向的周期不累积积分，积分项也限制在输出范围内（抗积分饱和）
+//! - 微分：`Kd × d过程量/dt` 取负号，只对过程量求导，修改设定值时输出不会突跳
+//!
+//! 增益为负时是反作用控制（例如制冷：温度高于设定值时输出增大）。
+//! 读数超过 [STALE_AFTER] 没有更新时输出置零并清除积分，读数恢复后重新开始。
+//!
+//! 参数都在设置中，命令行 `pid kp|ki|kd|set|period <值>` 或 MQTT 的 `cmd/pid` 主题
+//! （消息内容为 `<参数>=<值>&...`，例如 `kp=2.5&ki=0.1`，见 [crate::mqtt]）修改后
+//! 从下一个周期起生效，不需要重启回路。[Profile::Pid](crate::profile::Profile::Pid) 模式的屏幕（[screen_task]）
+//! 用 [ui::chart] 画出最近 [HISTORY_LEN] 个周期的设定值和过程量。
+//!
+//! PWM 输出所用的引脚接扩展排针，用命令行 `board port pwm <gpio>` 设置（见 [crate::board]）。
+//! 应用只在 PID 模式下创建输出并启动回路：
+//!
+//! ```ignore
+//! let output = PwmOutput::new(peripherals.LEDC, peripherals.GPIO4)?;
+//! spawner.spawn(pid::pid_task(output))?;
+//! ```
+//!
+//! 限制：只有一个回路，使用 LEDC 低速定时器 0 和通道 0。
+
+#[cfg(feature = "ui")]
+use crate::i18n::{self, Msg};
+#[cfg(feature = "ui")]
+use crate::lcd::Lcd;
+use crate::sensor;
+use crate::settings::{self, Settings};
+#[cfg(feature = "ui")]
+use crate::st7789;
+#[cfg(feature = "ui")]
+use crate::{assets, theme};
+#[cfg(feature = "ui")]
+use alloc::vec;
+#[cfg(feature = "ui")]
+use alloc::vec::Vec;
+use core::cell::RefCell;
+#[cfg(feature = "ui")]
+use core::convert::Infallible;
+#[cfg(feature = "ui")]
+use core::fmt::Write;
+use critical_section::Mutex;
+use defmt::info;
+#[cfg(feature = "ui")]
+use defmt::warn;
+use embassy_time::{Duration, Timer};
+#[cfg(feature = "ui")]
+use embedded_graphics::mono_font::MonoTextStyle;
+#[cfg(feature = "ui")]
+use embedded_graphics::mono_font::ascii::FONT_6X10;
+#[cfg(feature = "ui")]
+use embedded_graphics::prelude::*;
+#[cfg(feature = "ui")]
+use embedded_graphics::primitives::Rectangle;
+#[cfg(feature = "ui")]
+use embedded_graphics::text::{Baseline, Text};
+use embedded_hal::pwm::SetDutyCycle;
+use esp_hal::gpio::DriveMode;
+use esp_hal::gpio::interconnect::PeripheralOutput;
+use esp_hal::ledc::channel::{self, Channel, ChannelIFace};
+use esp_hal::ledc::timer::{self, TimerIFace};
+use esp_hal::ledc::{LSGlobalClkSource, Ledc, LowSpeed};
+use esp_hal::peripherals::LEDC;
+use esp_hal::time::Rate;
+use heapless::Deque;
+#[cfg(feature = "ui")]
+use heapless::String;
+use static_cell::StaticCell;
+#[cfg(feature = "ui")]
+use ui::chart::{Chart, Series};
+#[cfg(feature = "ui")]
+use ui::strip::Strip;
+#[cfg(feature = "ui")]
+use ui::theme::Theme;
+
+/// 定点数的比例：过程量、设定值和增益都以千分之一为单位
+pub const MILLI: i32 = 1000;
+
+/// 输出上限（千分之一个百分点）
+const OUTPUT_MAX: i64 = 100 * MILLI as i64;
+
+/// 控制周期的范围（毫秒）
+pub const PERIOD_RANGE: core::ops::RangeInclusive<u16> = 10..=60_000;
+
+/// 读数超过此时间没有更新时视为失效
+const STALE_AFTER: Duration = Duration::from_secs(60);
+
+/// PWM 频率，适合经 MOSFET 驱动的加热片、风扇等直流负载
+const PWM_FREQUENCY: Rate = Rate::from_khz(1);
+
+/// 屏幕上显示的周期数
+pub const HISTORY_LEN: usize = 150;
+
+/// 输出读数的名称
+const OUTPUT_READING: &str = "pid.out";
+
+/// 增益（千分之一）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct Gains {
+    pub kp: i32,
+    pub ki: i32,
+    pub kd: i32,
+}
+
+/// 定点数 PID 控制器
+#[derive(Debug, Default)]
+pub struct Pid {
+    /// 积分项（千分之一个百分点）
+    integral: i64,
+    /// 上一周期的过程量，用于微分
+    last_input: Option<i32>,
+}
+
+impl Pid {
+    /// 清除积分和微分的历史
+    pub fn reset(&mut self) {
+        *self = Pid::default();
+    }
+
+    /// 计算一个周期的输出
+    ///
+    /// # 参数
+    /// * `gains` - 增益
+    /// * `setpoint` - 设定值（千分之一）
+    /// * `input` - 过程量（千分之一）
+    /// * `period_ms` - 距上一周期的时间（毫秒），不能为 0
+    ///
+    /// # 返回
+    /// 输出（千分之一个百分点），范围 0 到 100000
+    pub fn update(&mut self, gains: &Gains, setpoint: i32, input: i32, period_ms: u32) -> i32 {
+        let dt = period_ms.max(1) as i64;
+        let error = setpoint as i64 - input as i64;
+        let p = (gains.kp as i64).saturating_mul(error) / MILLI as i64;
+        let d = match self.last_input {
+            Some(last) => -(gains.kd as i64).saturating_mul(input as i64 - last as i64) / dt,
+            None => 0,
+        };
+        self.last_input = Some(input);
+
+        let push = (gains.ki as i64).saturating_mul(error);
+        let integral = self
+            .integral
+            .saturating_add(push.saturating_mul(dt) / (MILLI as i64 * MILLI as i64));
+        // 抗积分饱和：输出已饱和且积分会把它推得更远时不累积
+        let unclamped = p.saturating_add(integral).saturating_add(d);
+        let winding = (unclamped > OUTPUT_MAX && push > 0) || (unclamped < 0 && push < 0);
+        if !winding {
+            self.integral = integral.clamp(0, OUTPUT_MAX);
+        }
+        p.saturating_add(self.integral)
+            .saturating_add(d)
+            .clamp(0, OUTPUT_MAX) as i32
+    }
+}
+
+/// PWM 输出错误
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum PwmError {
+    /// 定时器配置失败（频率和分辨率的组合无法实现）
+    Timer,
+    /// 通道配置失败
+    Channel,
+}
+
+/// LEDC 定时器，通道在整个运行期间引用它
+static PWM_TIMER: StaticCell<timer::Timer<'static, LowSpeed>> = StaticCell::new();
+
+/// LEDC 产生的 PWM 输出
+pub struct PwmOutput {
+    _ledc: Ledc<'static>,
+    channel: Channel<'static, LowSpeed>,
+}
+
+impl PwmOutput {
+    /// 配置 LEDC 低速定时器 0 和通道 0，初始占空比为 0
+    ///
+    /// # 参数
+    /// * `ledc` - LEDC 外设，只能创建一次
+    /// * `pin` - 输出引脚
+    pub fn new(ledc: LEDC<'static>, pin: impl PeripheralOutput<'static>) -> Result<Self, PwmError> {
+        let mut ledc = Ledc::new(ledc);
+        ledc.set_global_slow_clock(LSGlobalClkSource::APBClk);
+        let timer = PWM_TIMER.init(ledc.timer::<LowSpeed>(timer::Number::Timer0));
+        timer
+            .configure(timer::config::Config {
+                duty: timer::config::Duty::Duty10Bit,
+                clock_source: timer::LSClockSource::APBClk,
+                frequency: PWM_FREQUENCY,
+            })
+            .map_err(|_| PwmError::Timer)?;
+        let timer: &'static timer::Timer<'static, LowSpeed> = timer;
+        let mut channel = ledc.channel(channel::Number::Channel0, pin);
+        channel
+            .configure(channel::config::Config {
+                timer,
+                duty_pct: 0,
+                drive_mode: DriveMode::PushPull,
+            })
+            .map_err(|_| PwmError::Channel)?;
+        Ok(PwmOutput {
+            _ledc: ledc,
+            channel,
+        })
+    }
+
+    /// 设置占空比
+    ///
+    /// # 参数
+    /// * `output` - 占空比（千分之一个百分点）
+    fn set(&mut self, output: i32) {
+        let permille = (output / 100).clamp(0, 1000) as u16;
+        self.channel.set_duty_cycle_fraction(permille, 1000).ok();
+    }
+}
+
+/// 一个周期的记录
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Sample {
+    setpoint: f32,
+    /// 没有有效读数时为 NaN
+    input: f32,
+}
+
+/// 回路状态
+#[derive(Debug, Clone, Copy, PartialEq, defmt::Format)]
+pub struct Status {
+    /// 回路任务是否在运行
+    pub running: bool,
+    /// 过程量，没有有效读数时为 None
+    pub input: Option<f32>,
+    /// 输出（%）
+    pub output: f32,
+}
+
+/// 回路状态和最近的记录
+struct State {
+    status: Status,
+    history: Deque<Sample, HISTORY_LEN>,
+}
+
+static STATE: Mutex<RefCell<State>> = Mutex::new(RefCell::new(State {
+    status: Status {
+        running: false,
+        input: None,
+        output: 0.0,
+    },
+    history: Deque::new(),
+}));
+
+/// 当前状态
+pub fn status() -> Status {
+    critical_section::with(|cs| STATE.borrow_ref(cs).status)
+}
+
+/// 解析最多三位小数的数值，例如 `21.5`、`-0.125`
+///
+/// # 返回
+/// 数值（千分之一），格式错误或超出范围时返回 None
+pub fn parse_milli(text: &str) -> Option<i32> {
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, text),
+    };
+    let (whole, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+    let is_digits = |part: &str| part.bytes().all(|b| b.is_ascii_digit());
+    if whole.is_empty() || !is_digits(whole) || !is_digits(fraction) || fraction.len() > 3 {
+        return None;
+    }
+    let mut value = whole.parse::<i32>().ok()?.checked_mul(MILLI)?;
+    let mut scale = MILLI;
+    for digit in fraction.bytes() {
+        scale /= 10;
+        value = value.checked_add((digit - b'0') as i32 * scale)?;
+    }
+    Some(if negative { -value } else { value })
+}
+
+/// 修改一个回路参数（不会自动保存）
+///
+/// # 参数
+/// * `name` - `set`、`kp`、`ki`、`kd` 或 `period`
+/// * `value` - 参数值，`period` 为毫秒，其余最多三位小数
+///
+/// # 返回
+/// 参数名无效、格式错误或超出范围时返回 false，设置不变
+pub fn tune(name: &str, value: &str) -> bool {
+    if name == "period" {
+        let period = value.parse::<u16>().ok();
+        let Some(period) = period.filter(|p| PERIOD_RANGE.contains(p)) else {
+            return false;
+        };
+        settings::update(|s| s.pid_period = period);
+        return true;
+    }
+    let Some(value) = parse_milli(value) else {
+        return false;
+    };
+    settings::update(|s| match name {
+        "set" => s.pid_setpoint = value,
+        "kp" => s.pid_kp = value,
+        "ki" => s.pid_ki = value,
+        "kd" => s.pid_kd = value,
+        _ => {}
+    });
+    matches!(name, "set" | "kp" | "ki" | "kd")
+}
+
+/// 回路参数
+struct Config {
+    gains: Gains,
+    setpoint: i32,
+    period_ms: u32,
+}
+
+impl Config {
+    fn from_settings(s: &Settings) -> Self {
+        Config {
+            gains: Gains {
+                kp: s.pid_kp,
+                ki: s.pid_ki,
+                kd: s.pid_kd,
+            },
+            setpoint: s.pid_setpoint,
+            period_ms: s
+                .pid_period
+                .clamp(*PERIOD_RANGE.start(), *PERIOD_RANGE.end()) as u32,
+        }
+    }
+}
+
+/// PID 控制任务
+///
+/// 每个周期重新读取设置，没有选择读数时输出保持为 0
+///
+/// # 参数
+/// * `output` - PWM 输出
+#[embassy_executor::task]
+pub async fn pid_task(mut output: PwmOutput) {
+    let mut pid = Pid::default();
+    let mut last_gains = None;
+    critical_section::with(|cs| STATE.borrow_ref_mut(cs).status.running = true);
+    loop {
+        let s = settings::get();
+        let config = Config::from_settings(&s);
+        if last_gains != Some(config.gains) {
+            info!("PID gains {}", config.gains);
+            last_gains = Some(config.gains);
+        }
+        let input = sensor::get(&s.pid_source)
+            .filter(|reading| reading.updated.elapsed() < STALE_AFTER)
+            .map(|reading| reading.value)
+            .filter(|_| !s.pid_source.is_empty());
+        let duty = match input {
+            Some(value) => {
+                let milli = value * MILLI as f64;
+                let milli = (milli + if milli < 0.0 { -0.5 } else { 0.5 }) as i32;
+                pid.update(&config.gains, config.setpoint, milli, config.period_ms)
+            }
+            None => {
+                pid.reset();
+                0
+            }
+        };
+        output.set(duty);
+        let percent = duty as f32 / MILLI as f32;
+        if !s.pid_source.is_empty() {
+            sensor::publish(OUTPUT_READING, percent as f64, "%");
+        }
+
+        let sample = Sample {
+            setpoint: config.setpoint as f32 / MILLI as f32,
+            input: input.map_or(f32::NAN, |value| value as f32),
+        };
+        critical_section::with(|cs| {
+            let mut state = STATE.borrow_ref_mut(cs);
+            state.status.input = input.map(|value| value as f32);
+            state.status.output = percent;
+            if state.history.is_full() {
+                state.history.pop_front();
+            }
+            state.history.push_back(sample).ok();
+        });
+        Timer::after_millis(config.period_ms as u64).await;
+    }
+}
+
+/// 屏幕刷新周期
+#[cfg(feature = "ui")]
+const REFRESH_PERIOD: Duration = Duration::from_secs(1);
+
+/// 条带渲染的行数
+#[cfg(feature = "ui")]
+const STRIP_ROWS: usize = 16;
+
+/// 图表区域
+#[cfg(feature = "ui")]
+const CHART_AREA: Rectangle = Rectangle::new(Point::new(10, 60), Size::new(300, 170));
+
+/// 纵轴的最小跨度
+#[cfg(feature = "ui")]
+const CHART_MIN_SPAN: f32 = 1.0;
+
+/// PID 屏幕任务
+///
+/// 显示设定值、过程量和输出，并画出最近的变化曲线，整屏按条带合成后发送，刷新时不闪烁
+///
+/// # 参数
+/// * `lcd` - 已完成初始化的 LCD
+#[cfg(feature = "ui")]
+#[embassy_executor::task]
+pub async fn screen_task(mut lcd: Lcd) {
+    let mut buffer = vec![0u8; st7789::WIDTH as usize * STRIP_ROWS * 2];
+    let mut setpoints: Vec<f32> = Vec::with_capacity(HISTORY_LEN);
+    let mut inputs: Vec<f32> = Vec::with_capacity(HISTORY_LEN);
+    loop {
+        let colors = theme::current();
+        let status = critical_section::with(|cs| {
+            let state = STATE.borrow_ref(cs);
+            setpoints.clear();
+            inputs.clear();
+            for sample in &state.history {
+                setpoints.push(sample.setpoint);
+                inputs.push(sample.input);
+            }
+            state.status
+        });
+        let setpoint = settings::get().pid_setpoint as f32 / MILLI as f32;
+        let series = [
+            Series {
+                values: &setpoints,
+                color: colors.accent,
+            },
+            Series {
+                values: &inputs,
+                color: colors.foreground,
+            },
+        ];
+        let result = lcd.render_strips(&mut buffer, |target| {
+            draw_scene(target, &colors, &status, setpoint, &series)
+        });
+        if let Err(err) = result {
+            warn!("Failed to draw PID screen: {}", err);
+        }
+        Timer::after(REFRESH_PERIOD).await;
+    }
+}
+
+/// 绘制整个画面
+#[cfg(feature = "ui")]
+fn draw_scene(
+    target: &mut Strip<'_>,
+    colors: &Theme,
+    status: &Status,
+    setpoint: f32,
+    series: &[Series<'_>],
+) -> Result<(), Infallible> {
+    target.clear(colors.background)?;
+    let title = MonoTextStyle::new(assets::font(), colors.accent);
+    Text::with_baseline(
+        i18n::lcd(Msg::PidTitle),
+        Point::new(10, 4),
+        title,
+        Baseline::Top,
+    )
+    .draw(target)?;
+
+    let mut line: String<24> = String::new();
+    let row = Point::new(10, 30);
+    write!(line, "SP {:.2}", setpoint).ok();
+    Text::with_baseline(&line, row, title, Baseline::Top).draw(target)?;
+    let text = MonoTextStyle::new(assets::font(), colors.foreground);
+    line.clear();
+    match (status.running, status.input) {
+        (false, _) => line.push_str(i18n::lcd(Msg::PidNotRunning)).ok(),
+        (true, Some(input)) => write!(line, "PV {:.2} {:.0}%", input, status.output).ok(),
+        (true, None) => write!(line, "PV -- {:.0}%", status.output).ok(),
+    };
+    Text::with_baseline(&line, row + Point::new(110, 0), text, Baseline::Top).draw(target)?;
+
+    let chart = Chart::new(
+        CHART_AREA,
+        HISTORY_LEN,
+        CHART_MIN_SPAN,
+        colors.background,
+        colors.muted,
+    );
+    // 纵轴范围标在图表左侧的上下两端
+    if let Some((min, max)) = chart.draw(target, series)? {
+        let label = MonoTextStyle::new(&FONT_6X10, colors.muted);
+        let (top, bottom) = (
+            CHART_AREA.top_left,
+            CHART_AREA.bottom_right().unwrap_or_default(),
+        );
+        line.clear();
+        write!(line, "{:.2}", max).ok();
+        Text::with_baseline(&line, top + Point::new(4, 0), label, Baseline::Top).draw(target)?;
+        line.clear();
+        write!(line, "{:.2}", min).ok();
+        let at = Point::new(top.x + 4, bottom.y - 2);
+        Text::with_baseline(&line, at, label, Baseline::Bottom).draw(target)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,250 @@
+//! 番茄钟 / 厨房定时器
+//!
+//! [Profile::Timer](crate::profile::Profile::Timer) 模式下代替渲染任务占用 LCD，
+//! 以大号数字（见 [ui::segment]）显示倒计时，到时后蜂鸣器按节奏鸣响。
+//! 颜色来自 [crate::theme]，配色改变时整屏重绘。
+//!
+//! 按键（独占，KEY1 不再切换背光）：
+//!
+//! - 设置时：KEY0 减 1 分钟，KEY1 加 1 分钟，KEY2 开始，KEY3 切换预设（25/5/15 分钟）
+//! - 计时中：KEY2 暂停，KEY3 取消
+//! - 暂停时：KEY2 继续，KEY3 取消
+//! - 到时后：任意键停止鸣响并回到设置
+
+use crate::i18n::{self, Msg};
+use crate::input::{self, Key};
+use crate::lcd::Lcd;
+use crate::st7789::{self, St7789};
+use crate::{assets, buzzer, notifier, theme};
+use core::fmt::Write;
+use defmt::{info, warn};
+use embassy_time::{Duration, Instant, with_timeout};
+use embedded_graphics::mono_font::{MonoTextStyle, MonoTextStyleBuilder};
+use embedded_graphics::pixelcolor::Rgb565;
+use embedded_graphics::prelude::*;
+use embedded_graphics::text::Text;
+use heapless::String;
+use ui::segment::SegmentDisplay;
+use ui::theme::Theme;
+
+/// 屏幕刷新周期
+const TICK: Duration = Duration::from_millis(100);
+
+/// 预设时长（分钟）：工作、短休息、长休息
+const PRESETS: [u32; 3] = [25, 5, 15];
+
+/// 可设置的最长时长（分钟）
+const MAX_MINUTES: u32 = 99;
+
+/// 到时提示音节奏（毫秒）：三声短鸣，每 [ALARM_PERIOD] 重复一次
+const ALARM: [u16; 5] = [100, 100, 100, 100, 100];
+const ALARM_PERIOD: Duration = Duration::from_secs(1);
+
+/// 到时后最长鸣响时间
+const ALARM_DURATION: Duration = Duration::from_secs(30);
+
+/// 标题基线位置
+const TITLE_Y: i32 = 26;
+
+/// 大号数字的上边界和尺寸
+const DIGITS_Y: i32 = 60;
+const DIGIT_WIDTH: u32 = 48;
+const DIGIT_HEIGHT: u32 = 96;
+
+/// 进度条位置和尺寸
+const BAR_X: i32 = 20;
+const BAR_Y: i32 = 180;
+const BAR_WIDTH: u32 = 280;
+const BAR_HEIGHT: u32 = 10;
+
+/// 底部提示行基线位置
+const HINT_Y: i32 = 232;
+
+/// 定时器状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    /// 设置时长
+    Setup,
+    /// 计时中，到 `deadline` 结束
+    Running { deadline: Instant },
+    /// 已暂停，剩余 `remaining`
+    Paused { remaining: Duration },
+    /// 已到时，从 `since` 开始鸣响
+    Expired { since: Instant },
+}
+
+/// 番茄钟任务
+///
+/// # 参数
+/// * `lcd` - 已完成初始化的 LCD
+#[embassy_executor::task]
+pub async fn pomodoro_task(mut lcd: Lcd) {
+    let Some(mut keys) = input::subscribe() else {
+        warn!("No key subscriber available for timer");
+        return;
+    };
+    input::set_captured(true);
+
+    let mut colors = theme::current();
+    let mut digits = clear_screen(&mut lcd, &colors);
+
+    let mut preset = 0;
+    let mut minutes = PRESETS[preset];
+    let mut state = State::Setup;
+    let mut shown_state = None;
+    let mut next_alarm = Instant::now();
+    let mut text: String<8> = String::new();
+
+    loop {
+        let now = Instant::now();
+        let total = Duration::from_secs(minutes as u64 * 60);
+
+        let current = theme::current();
+        if current != colors {
+            colors = current;
+            digits = clear_screen(&mut lcd, &colors);
+            shown_state = None;
+        }
+
+        // 计时结束
+        if let State::Running { deadline } = state
+            && now >= deadline
+        {
+            info!("Timer expired after {} min", minutes);
+            let mut event: String<32> = String::new();
+            write!(event, "Timer expired after {} min", minutes).ok();
+            notifier::notify(&event);
+            state = State::Expired { since: now };
+            next_alarm = now;
+        }
+
+        // 到时后周期性鸣响，每段节奏由蜂鸣器任务播放完后自动停止
+        if let State::Expired { since } = state
+            && now.duration_since(since) < ALARM_DURATION
+            && now >= next_alarm
+        {
+            buzzer::pattern(&ALARM);
+            next_alarm = now + ALARM_PERIOD;
+        }
+
+        // 标题、提示和数字颜色只在状态改变时重绘
+        let kind = core::mem::discriminant(&state);
+        if shown_state != Some(kind) {
+            let (title, hint, color) = match state {
+                State::Setup => (Msg::TimerSetup, Msg::TimerSetupHint, colors.foreground),
+                State::Running { .. } => (Msg::TimerRunning, Msg::TimerRunningHint, colors.accent),
+                State::Paused { .. } => (Msg::TimerPaused, Msg::TimerPausedHint, colors.highlight),
+                State::Expired { .. } => (Msg::TimerExpired, Msg::TimerExpiredHint, Rgb565::RED),
+            };
+            draw_line(&mut lcd, &colors, i18n::lcd(title), TITLE_Y, colors.accent);
+            draw_line(
+                &mut lcd,
+                &colors,
+                i18n::lcd(hint),
+                HINT_Y,
+                colors.foreground,
+            );
+            digits.set_color(color);
+            shown_state = Some(kind);
+        }
+
+        let remaining = match state {
+            State::Setup => total,
+            State::Running { deadline } => deadline.saturating_duration_since(now),
+            State::Paused { remaining } => remaining,
+            State::Expired { .. } => Duration::from_ticks(0),
+        };
+        // 向上取整，开始计时后仍先显示完整的分钟数
+        let secs = remaining.as_millis().div_ceil(1000);
+        text.clear();
+        write!(text, "{:02}:{:02}", secs / 60, secs % 60).ok();
+        if let Err(err) = digits.show(&mut lcd, &text) {
+            warn!("Failed to draw timer digits: {}", err);
+        }
+        draw_progress(&mut lcd, &colors, total, remaining);
+
+        let Ok(key) = with_timeout(TICK, keys.next_message_pure()).await else {
+            continue;
+        };
+        state = match (state, key) {
+            (State::Setup, Key::Key0) => {
+                minutes = minutes.saturating_sub(1).max(1);
+                State::Setup
+            }
+            (State::Setup, Key::Key1) => {
+                minutes = (minutes + 1).min(MAX_MINUTES);
+                State::Setup
+            }
+            (State::Setup, Key::Key2) => State::Running {
+                deadline: Instant::now() + total,
+            },
+            (State::Setup, Key::Key3) => {
+                preset = (preset + 1) % PRESETS.len();
+                minutes = PRESETS[preset];
+                State::Setup
+            }
+            (State::Running { deadline }, Key::Key2) => State::Paused {
+                remaining: deadline.saturating_duration_since(Instant::now()),
+            },
+            (State::Paused { remaining }, Key::Key2) => State::Running {
+                deadline: Instant::now() + remaining,
+            },
+            (State::Running { .. } | State::Paused { .. }, Key::Key3) => State::Setup,
+            (State::Expired { .. }, _) => State::Setup,
+            (state, _) => state,
+        };
+    }
+}
+
+/// 用配色的背景色清屏
+///
+/// # 返回
+/// 新的大号数字显示区域，下次显示时完整绘制
+fn clear_screen(lcd: &mut St7789, colors: &Theme) -> SegmentDisplay {
+    if let Err(err) = lcd.fill_screen(colors.background) {
+        warn!("Failed to clear LCD: {}", err);
+    }
+    SegmentDisplay::new(
+        Point::new(0, DIGITS_Y),
+        DIGIT_WIDTH,
+        DIGIT_HEIGHT,
+        colors.foreground,
+    )
+    .with_background(colors.background)
+    .centered("00:00")
+}
+
+/// 清除一行后居中显示文本
+fn draw_line(lcd: &mut St7789, colors: &Theme, text: &str, y: i32, color: Rgb565) {
+    let style: MonoTextStyle<'_, Rgb565> = MonoTextStyleBuilder::new()
+        .font(assets::font())
+        .text_color(color)
+        .background_color(colors.background)
+        .build();
+    lcd.fill_rectangle(0, (y - 18) as u16, st7789::WIDTH, 24, colors.background)
+        .ok();
+    let x = (st7789::WIDTH as i32 - text.len() as i32 * 10).max(0) / 2;
+    if let Err(err) = Text::new(text, Point::new(x, y), style).draw(lcd) {
+        warn!("Failed to draw timer text: {}", err);
+    }
+}
+
+/// 绘制剩余时间进度条
+fn draw_progress(lcd: &mut St7789, colors: &Theme, total: Duration, remaining: Duration) {
+    let filled = if total.as_ticks() == 0 {
+        0
+    } else {
+        (BAR_WIDTH as u64 * remaining.as_ticks() / total.as_ticks()) as u32
+    };
+    let (x, y) = (BAR_X as u16, BAR_Y as u16);
+    lcd.fill_rectangle(x, y, filled as u16, BAR_HEIGHT as u16, colors.accent)
+        .ok();
+    lcd.fill_rectangle(
+        x + filled as u16,
+        y,
+        (BAR_WIDTH - filled) as u16,
+        BAR_HEIGHT as u16,
+        colors.muted,
+    )
+    .ok();
+}
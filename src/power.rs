@@ -0,0 +1,136 @@
+//! 上电顺序
+//!
+//! WiFi 射频、LCD 背光和 TF 卡上电时的冲击电流叠加起来，可能超过部分 USB 口的供电能力，
+//! 导致掉电复位（brownout）。启动阶段这些负载并发初始化（见 [crate::app]），打开之前都先调用
+//! [power_up]，按以下规则错开：
+//!
+//! - 负载打开后的 [Config::stagger] 内视为正在上电，冲击电流（[Load::inrush_ma]）计入预算
+//! - 正在上电的负载加上新负载的冲击电流不超过 [Config::budget] 时才能打开，否则等到最早的
+//!   一个上电完成再检查；冲击电流本身超过预算的负载等其他负载上电完成后单独打开
+//!
+//! 间隔和预算保存在设置中，用命令行 `power stagger`、`power budget` 修改，下次启动时生效。
+//! 间隔为 0 时不错开。
+//!
+//! 限制：冲击电流是估计值，没有实测；固件中没有摄像头和功放的驱动，蜂鸣器电流很小不参与排队。
+//! 只有启动时的上电经过这里，运行中唤醒屏幕、插入 TF 卡等单独发生的操作不排队。
+
+use crate::settings;
+use core::cell::Cell;
+use critical_section::Mutex;
+use defmt::info;
+use embassy_time::{Duration, Instant, Timer};
+
+/// 上电间隔的上限（毫秒）
+pub const MAX_STAGGER_MS: u16 = 2000;
+
+/// 冲击电流预算的范围（mA）
+pub const MIN_BUDGET_MA: u16 = 50;
+pub const MAX_BUDGET_MA: u16 = 2000;
+
+/// 需要错开上电的负载
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Load {
+    /// WiFi 射频启动和校准
+    Radio,
+    /// LCD 背光
+    Backlight,
+    /// TF 卡初始化
+    SdCard,
+}
+
+impl Load {
+    /// 所有负载
+    pub const ALL: [Load; 3] = [Load::Radio, Load::Backlight, Load::SdCard];
+
+    /// 负载名称，用于日志和命令行
+    pub const fn name(self) -> &'static str {
+        match self {
+            Load::Radio => "radio",
+            Load::Backlight => "backlight",
+            Load::SdCard => "sdcard",
+        }
+    }
+
+    /// 估计的上电冲击电流（mA）
+    pub const fn inrush_ma(self) -> u16 {
+        match self {
+            Load::Radio => 300,
+            Load::Backlight => 80,
+            Load::SdCard => 100,
+        }
+    }
+}
+
+/// 上电规则
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Config {
+    /// 负载打开后计入预算的时长，为 0 时不错开
+    pub stagger: Duration,
+    /// 同时上电的负载冲击电流之和的上限（mA）
+    pub budget: u16,
+}
+
+impl Config {
+    /// 从设置读取
+    pub fn current() -> Config {
+        let s = settings::get();
+        Config {
+            stagger: Duration::from_millis(s.power_stagger as u64),
+            budget: s.power_budget,
+        }
+    }
+}
+
+/// 各负载最近一次上电的时刻，下标与 [Load::ALL] 一致
+static STARTED: Mutex<Cell<[Option<Instant>; Load::ALL.len()]>> =
+    Mutex::new(Cell::new([None; Load::ALL.len()]));
+
+/// 负载最近一次上电的时刻，不错开上电时不记录
+pub fn started(load: Load) -> Option<Instant> {
+    critical_section::with(|cs| STARTED.borrow(cs).get()[load as usize])
+}
+
+/// 检查负载现在能否打开，能打开时记下上电时刻
+///
+/// # 返回
+/// 能打开时为 None，否则为下一次检查的时刻
+fn try_start(load: Load, config: &Config, now: Instant) -> Option<Instant> {
+    critical_section::with(|cs| {
+        let cell = STARTED.borrow(cs);
+        let mut started = cell.get();
+        let mut current = 0;
+        let mut earliest: Option<Instant> = None;
+        for (other, start) in Load::ALL.into_iter().zip(started) {
+            let Some(end) = start.map(|t| t + config.stagger).filter(|&end| end > now) else {
+                continue;
+            };
+            current += other.inrush_ma();
+            earliest = Some(earliest.map_or(end, |e| e.min(end)));
+        }
+        if current > 0 && current + load.inrush_ma() > config.budget {
+            return earliest;
+        }
+        started[load as usize] = Some(now);
+        cell.set(started);
+        None
+    })
+}
+
+/// 等到可以打开负载，在负载上电之前调用
+///
+/// # 参数
+/// * `load` - 即将打开的负载
+pub async fn power_up(load: Load) {
+    let config = Config::current();
+    if config.stagger.as_ticks() == 0 {
+        return;
+    }
+    let requested = Instant::now();
+    while let Some(next) = try_start(load, &config, Instant::now()) {
+        Timer::at(next).await;
+    }
+    let waited = requested.elapsed().as_millis();
+    if waited > 0 {
+        info!("Power-up of {} delayed by {} ms", load.name(), waited);
+    }
+}
@@ -0,0 +1,183 @@
+//! 基于 WiFi 信号强度的存在检测（实验性）
+//!
+//! 人在房间里走动时会遮挡和反射接入点到板子之间的信号，连接的信号强度随之抖动，
+//! 房间里没人时信号强度基本不变。启用后 WiFi 连接任务（见 [crate::wifi::connection]）
+//! 每 [SAMPLE_PERIOD] 采样一次信号强度交给 [sample]，本模块计算最近 [WINDOW] 个样本的方差：
+//!
+//! - 方差超过阈值时判定为有人活动，立即报告
+//! - 方差持续 [QUIET_HOLD] 低于阈值的一半后判定为无人，两个阈值之间不改变状态
+//!
+//! 状态变化作为告警事件发出（见 [crate::notifier]），同时登记为 `presence.motion` 读数
+//! （1 为有人活动，0 为无人），屏幕、HTTP、Modbus 等消费者都可以读取。
+//!
+//! 命令行 `presence on|off` 开关检测，`presence threshold <dB²>` 调整阈值，
+//! `presence` 查看当前的方差和状态。启用状态和阈值只在本次运行中有效，
+//! 重启后恢复为关闭和 [DEFAULT_THRESHOLD]。
+//!
+//! 限制：
+//! - 只使用信号强度，不使用 CSI（信道状态信息）：固件没有启用 esp-radio 的 CSI 接口，
+//!   CSI 的数据量也需要单独的处理任务
+//! - 驱动报告的是整数 dBm，只有在板子与接入点之间的路径附近活动才有明显变化，
+//!   静止的人检测不到
+//! - 合适的阈值与房间布局和接入点距离有关，应先在无人时观察 `presence` 显示的方差再设置
+//! - 每次连接（包括漫游）后样本清空，窗口填满前不做判断
+
+use crate::{notifier, sensor};
+use core::cell::{Cell, RefCell};
+use critical_section::Mutex;
+use defmt::info;
+use embassy_time::{Duration, Instant};
+use heapless::Deque;
+
+/// 启用检测时采样信号强度的周期
+pub const SAMPLE_PERIOD: Duration = Duration::from_millis(500);
+
+/// 计算方差的样本数，约 8 秒
+const WINDOW: usize = 16;
+
+/// 方差低于阈值的一半持续这么久后判定为无人
+const QUIET_HOLD: Duration = Duration::from_secs(60);
+
+/// 默认阈值（dB²）
+pub const DEFAULT_THRESHOLD: f32 = 2.0;
+
+/// 阈值的允许范围（dB²）
+pub const MIN_THRESHOLD: f32 = 0.5;
+pub const MAX_THRESHOLD: f32 = 50.0;
+
+/// 检测状态
+struct Detector {
+    /// 最近的信号强度样本（dBm）
+    samples: Deque<i8, WINDOW>,
+    /// 是否判定为有人活动
+    motion: bool,
+    /// 方差开始低于阈值一半的时刻
+    quiet_since: Option<Instant>,
+}
+
+impl Detector {
+    const fn new() -> Self {
+        Detector {
+            samples: Deque::new(),
+            motion: false,
+            quiet_since: None,
+        }
+    }
+
+    /// 样本的方差（dB²），窗口未填满时为 None
+    fn variance(&self) -> Option<f32> {
+        if !self.samples.is_full() {
+            return None;
+        }
+        let n = self.samples.len() as f32;
+        let mean = self.samples.iter().map(|&s| s as f32).sum::<f32>() / n;
+        let squares: f32 = self
+            .samples
+            .iter()
+            .map(|&s| (s as f32 - mean) * (s as f32 - mean))
+            .sum();
+        Some(squares / n)
+    }
+}
+
+static ENABLED: Mutex<Cell<bool>> = Mutex::new(Cell::new(false));
+
+static THRESHOLD: Mutex<Cell<f32>> = Mutex::new(Cell::new(DEFAULT_THRESHOLD));
+
+static DETECTOR: Mutex<RefCell<Detector>> = Mutex::new(RefCell::new(Detector::new()));
+
+/// 检测是否已启用
+pub fn is_enabled() -> bool {
+    critical_section::with(|cs| ENABLED.borrow(cs).get())
+}
+
+/// 启用或关闭检测，关闭时撤下 `presence.motion` 读数
+pub fn set_enabled(enabled: bool) {
+    critical_section::with(|cs| ENABLED.borrow(cs).set(enabled));
+    reset();
+    if !enabled {
+        sensor::withdraw("presence.");
+    }
+    let state = if enabled { "enabled" } else { "disabled" };
+    info!("Presence sensing {}", state);
+}
+
+/// 当前阈值（dB²）
+pub fn threshold() -> f32 {
+    critical_section::with(|cs| THRESHOLD.borrow(cs).get())
+}
+
+/// 设置阈值，超出范围的值被限制在 [MIN_THRESHOLD]..=[MAX_THRESHOLD] 内
+///
+/// # 参数
+/// * `threshold` - 判定为有人活动的方差（dB²）
+pub fn set_threshold(threshold: f32) {
+    let threshold = threshold.clamp(MIN_THRESHOLD, MAX_THRESHOLD);
+    critical_section::with(|cs| THRESHOLD.borrow(cs).set(threshold));
+}
+
+/// 清空样本并把状态恢复为无人，在重新连接后调用
+///
+/// 不同接入点、不同连接的信号强度不能放在一起比较
+pub fn reset() {
+    critical_section::with(|cs| *DETECTOR.borrow_ref_mut(cs) = Detector::new());
+}
+
+/// 最近一个窗口的方差（dB²），窗口未填满时为 None
+pub fn variance() -> Option<f32> {
+    critical_section::with(|cs| DETECTOR.borrow_ref(cs).variance())
+}
+
+/// 是否判定为有人活动
+pub fn motion() -> bool {
+    critical_section::with(|cs| DETECTOR.borrow_ref(cs).motion)
+}
+
+/// 输入一次信号强度采样，状态变化时发出告警事件
+///
+/// 未启用检测时忽略
+///
+/// # 参数
+/// * `rssi` - 连接的信号强度（dBm）
+pub fn sample(rssi: i8) {
+    if !is_enabled() {
+        return;
+    }
+    let threshold = threshold();
+    let now = Instant::now();
+    let decision = critical_section::with(|cs| {
+        let mut detector = DETECTOR.borrow_ref_mut(cs);
+        if detector.samples.is_full() {
+            detector.samples.pop_front();
+        }
+        detector.samples.push_back(rssi).ok();
+        let variance = detector.variance()?;
+
+        let was_moving = detector.motion;
+        if variance > threshold {
+            detector.motion = true;
+            detector.quiet_since = None;
+        } else if variance < threshold / 2.0 {
+            let since = *detector.quiet_since.get_or_insert(now);
+            if now.duration_since(since) >= QUIET_HOLD {
+                detector.motion = false;
+            }
+        } else {
+            detector.quiet_since = None;
+        }
+        Some((detector.motion, detector.motion != was_moving))
+    });
+    let Some((motion, changed)) = decision else {
+        return;
+    };
+
+    sensor::publish("presence.motion", if motion { 1.0 } else { 0.0 }, "");
+    if changed {
+        info!("Presence: {}", if motion { "motion" } else { "quiet" });
+        notifier::notify(if motion {
+            "Presence: motion detected"
+        } else {
+            "Presence: room quiet"
+        });
+    }
+}
@@ -0,0 +1,105 @@
+//! 应用模式
+//!
+//! 同一份固件可按不同的模式运行，由设置中的 `profile` 字段选择（命令行 `profile`，
+//! 重启后生效），不需要修改 main。模式决定屏幕显示的内容和额外启动的任务，
+//! 见 [crate::app::App::start]。
+
+use crate::settings;
+
+/// 应用模式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Profile {
+    /// 默认的状态屏幕
+    Status,
+    /// 气象站：BME280 读数、最高/最低值和变化趋势，见 [crate::weather]
+    WeatherStation,
+    /// 番茄钟 / 厨房定时器，见 [crate::pomodoro]
+    Timer,
+    /// 秒表和计圈，见 [crate::stopwatch]
+    Stopwatch,
+    /// 贪吃蛇小游戏，见 [crate::snake]
+    Game,
+    /// 数码相框，见 [crate::photo]
+    PhotoFrame,
+    /// 桌面时钟，见 [crate::clock]
+    Clock,
+    /// 双板链路测试，见 [crate::linktest]
+    LinkTest,
+    /// 显示性能测试，见 [crate::bench]
+    Bench,
+    /// 恒温控制器，见 [crate::thermostat]
+    Thermostat,
+    /// PID 控制回路的曲线，见 [crate::pid]
+    Pid,
+    /// 继电器输出的状态和手动开关，见 [crate::relay]
+    Relay,
+    /// PC 的无线副屏，见 [crate::remote]
+    Remote,
+}
+
+impl Profile {
+    /// 所有模式，下标与设置中保存的编码一致
+    pub const ALL: [Profile; 13] = [
+        Profile::Status,
+        Profile::WeatherStation,
+        Profile::Timer,
+        Profile::Stopwatch,
+        Profile::Game,
+        Profile::PhotoFrame,
+        Profile::Clock,
+        Profile::LinkTest,
+        Profile::Bench,
+        Profile::Thermostat,
+        Profile::Pid,
+        Profile::Relay,
+        Profile::Remote,
+    ];
+
+    /// 设置中保存的编码
+    pub const fn to_u8(self) -> u8 {
+        self as u8
+    }
+
+    /// 从设置中的编码解析，未知编码视为状态屏幕
+    pub const fn from_u8(value: u8) -> Profile {
+        match value {
+            1 => Profile::WeatherStation,
+            2 => Profile::Timer,
+            3 => Profile::Stopwatch,
+            4 => Profile::Game,
+            5 => Profile::PhotoFrame,
+            6 => Profile::Clock,
+            7 => Profile::LinkTest,
+            8 => Profile::Bench,
+            9 => Profile::Thermostat,
+            10 => Profile::Pid,
+            11 => Profile::Relay,
+            12 => Profile::Remote,
+            _ => Profile::Status,
+        }
+    }
+
+    /// 模式名称，用于命令行参数
+    pub const fn name(self) -> &'static str {
+        match self {
+            Profile::Status => "status",
+            Profile::WeatherStation => "weather",
+            Profile::Timer => "timer",
+            Profile::Stopwatch => "stopwatch",
+            Profile::Game => "game",
+            Profile::PhotoFrame => "photo",
+            Profile::Clock => "clock",
+            Profile::LinkTest => "linktest",
+            Profile::Bench => "bench",
+            Profile::Thermostat => "thermostat",
+            Profile::Pid => "pid",
+            Profile::Relay => "relay",
+            Profile::Remote => "remote",
+        }
+    }
+}
+
+/// 设置中选择的模式
+pub fn current() -> Profile {
+    Profile::from_u8(settings::get().profile)
+}
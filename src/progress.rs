@@ -0,0 +1,178 @@
+//! 长操作的进度
+//!
+//! 固件更新、TF 卡操作、启动过程、长按 BOOT 按键的倒计时等耗时较长的操作都用 [Progress]
+//! 报告进度（百分比和一行说明），不必各自实现反馈方式。每次进度改变时：
+//!
+//! - 渲染任务运行时，经 [render::command] 在屏幕顶部显示进度条、底部显示说明
+//!   （[Command::Progress]），其他应用模式下不显示
+//! - 渲染任务启动之前（启动阶段），用 [Progress::attach] 交给报告器的 LCD 直接绘制
+//!   同样的进度条和说明
+//! - 说明改变或进度每跨过 10% 时写一条日志，联网后日志同时转发到 syslog（见 [crate::syslog]）；
+//!   连接了 MQTT 代理时同时发布到 `<基础主题>/progress`（见 [crate::mqtt]）：
+//!   `{"task":"ota","percent":40,"message":"..."}`
+//!
+//! 报告器离开作用域时隐藏进度条和说明，并发布 `{"task":"ota","done":true}`。
+//! 关闭 `ui` feature 的构建没有屏幕，只写日志和发布 MQTT 消息。
+
+#[cfg(feature = "ui")]
+use crate::lcd::Lcd;
+use crate::mqtt;
+#[cfg(feature = "ui")]
+use crate::render::{self, Command};
+#[cfg(feature = "ui")]
+use crate::screens::Style;
+#[cfg(feature = "ui")]
+use crate::theme;
+#[cfg(not(feature = "ui"))]
+use core::marker::PhantomData;
+use defmt::info;
+#[cfg(feature = "ui")]
+use embedded_graphics::pixelcolor::Rgb565;
+#[cfg(feature = "ui")]
+use embedded_graphics::prelude::*;
+use heapless::String;
+use proto::json::Object;
+
+/// 进度报告器
+pub struct Progress<'a> {
+    /// 操作名称，用于日志
+    task: &'static str,
+    /// 当前进度，尚未更新时为 None
+    percent: Option<u8>,
+    /// 进度说明，超过 32 字节的部分截断
+    message: String<32>,
+    /// 直接绘制的 LCD，None 时交给渲染任务
+    #[cfg(feature = "ui")]
+    lcd: Option<&'a mut Lcd>,
+    /// 直接绘制时屏幕上进度条的长度（像素）
+    #[cfg(feature = "ui")]
+    drawn: i32,
+    #[cfg(not(feature = "ui"))]
+    _lcd: PhantomData<&'a mut ()>,
+}
+
+impl<'a> Progress<'a> {
+    /// 创建报告器，第一次 [Progress::update] 时才显示
+    ///
+    /// # 参数
+    /// * `task` - 操作名称，例如 `ota`，只用于日志
+    pub fn new(task: &'static str) -> Self {
+        Progress {
+            task,
+            percent: None,
+            message: String::new(),
+            #[cfg(feature = "ui")]
+            lcd: None,
+            #[cfg(feature = "ui")]
+            drawn: 0,
+            #[cfg(not(feature = "ui"))]
+            _lcd: PhantomData,
+        }
+    }
+
+    /// 改为直接在 LCD 上绘制，用于渲染任务启动之前
+    ///
+    /// LCD 初始化后是黑屏，进度条和说明绘制在黑色背景上
+    #[cfg(feature = "ui")]
+    pub fn attach(&mut self, lcd: &'a mut Lcd) {
+        self.lcd = Some(lcd);
+        self.drawn = 0;
+        if let Some(percent) = self.percent {
+            self.show(percent, true);
+        }
+    }
+
+    /// 更新进度和说明，与上一次相同时不做任何事
+    ///
+    /// # 参数
+    /// * `percent` - 进度，超过 100 时按 100 处理
+    /// * `message` - 当前步骤的说明，显示在屏幕上，应使用 [crate::i18n::lcd] 的译文
+    pub fn update(&mut self, percent: u8, message: &str) {
+        let percent = percent.min(100);
+        let changed = self.message != message;
+        if !changed && self.percent == Some(percent) {
+            return;
+        }
+        if changed {
+            self.message.clear();
+            for c in message.chars() {
+                if self.message.push(c).is_err() {
+                    break;
+                }
+            }
+        }
+        if changed || self.percent.map(|p| p / 10) != Some(percent / 10) {
+            info!("{}: {}% {}", self.task, percent, self.message.as_str());
+            self.publish(Some(percent));
+        }
+        self.percent = Some(percent);
+        self.show(percent, changed);
+    }
+
+    /// 发布进度到 MQTT，`percent` 为 None 表示操作结束
+    fn publish(&self, percent: Option<u8>) {
+        let mut body = alloc::string::String::new();
+        {
+            let mut object = Object::new(&mut body);
+            object.str("task", self.task);
+            match percent {
+                Some(percent) => object
+                    .int("percent", percent as i64)
+                    .str("message", &self.message),
+                None => object.bool("done", true),
+            };
+        }
+        mqtt::publish("progress", body.as_bytes(), false);
+    }
+
+    /// 显示进度，`changed` 表示说明需要重绘
+    #[cfg(feature = "ui")]
+    fn show(&mut self, percent: u8, changed: bool) {
+        let Some(lcd) = self.lcd.as_deref_mut() else {
+            let update = (percent, self.message.clone());
+            render::command(Command::Progress(Some(update)));
+            return;
+        };
+        let style = Style::new(theme::current(), Rgb565::BLACK);
+        let width = render::progress_width(percent);
+        if width > self.drawn {
+            render::fill_progress(lcd.draw(), self.drawn, width, style.colors.accent);
+        } else {
+            render::fill_progress(lcd.draw(), width, self.drawn, style.background);
+        }
+        self.drawn = width;
+        if changed {
+            render::draw_progress_message(lcd.draw(), &self.message, style);
+        }
+    }
+
+    /// 没有屏幕时只写日志
+    #[cfg(not(feature = "ui"))]
+    fn show(&mut self, _percent: u8, _changed: bool) {}
+
+    /// 隐藏进度条和说明
+    #[cfg(feature = "ui")]
+    fn hide(&mut self) {
+        match self.lcd.as_deref_mut() {
+            Some(lcd) => {
+                render::fill_progress(lcd.draw(), 0, self.drawn, Rgb565::BLACK);
+                let style = Style::new(theme::current(), Rgb565::BLACK);
+                render::draw_progress_message(lcd.draw(), "", style);
+            }
+            None => render::command(Command::Progress(None)),
+        }
+    }
+
+    #[cfg(not(feature = "ui"))]
+    fn hide(&mut self) {}
+}
+
+impl Drop for Progress<'_> {
+    fn drop(&mut self) {
+        if self.percent.is_none() {
+            return;
+        }
+        self.publish(None);
+        self.hide();
+    }
+}
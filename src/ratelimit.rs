@@ -0,0 +1,106 @@
+//! 按客户端限制请求速率
+//!
+//! 各网络服务只有一个套接字，同一时间只服务一个连接（SNMP 逐个处理数据报），
+//! 连接数本身已有上限；但一个客户端可以不停地发请求，占满这个套接字和处理时间，
+//! 其他客户端和同一执行器上的任务因此得不到服务。[RateLimiter] 按对端 IP 地址
+//! 用令牌桶限制请求速率：每个请求消耗一个令牌，令牌按固定速率补充，最多积攒 `burst` 个，
+//! 正常的轮询不受影响，短时间的突发也允许。
+//!
+//! 超出限制时的处理由各服务决定：
+//!
+//! - HTTP（[crate::http]）：返回 429，并在 `Retry-After` 中建议 1 秒后重试
+//! - Modbus TCP（[crate::modbus]）：返回异常码 06（从站设备忙）
+//! - SNMP（[crate::snmp]）：丢弃请求，不回复
+//!
+//! 限制器由服务任务自己持有，不需要共享。只记录最近的若干个客户端，
+//! 表满时替换最久没有请求的客户端，被替换的客户端重新从满的令牌桶开始。
+//!
+//! 限制：固件中没有 WebSocket 服务。MQTT 是主动连接代理的客户端（见 [crate::mqtt]），
+//! 命令经代理转发，发送方的 IP 地址不可见，不在这里限速，由代理的权限控制。
+
+use embassy_net::IpAddress;
+use embassy_time::Instant;
+use heapless::Vec;
+
+/// 一个客户端的令牌桶
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    addr: IpAddress,
+    /// 剩余令牌，单位为千分之一个
+    tokens: u32,
+    /// 上次补充令牌的时间
+    updated: Instant,
+}
+
+/// 令牌桶限速器
+///
+/// # 类型参数
+/// * `N` - 记录的客户端数量
+pub struct RateLimiter<const N: usize> {
+    /// 每秒补充的令牌数
+    rate: u32,
+    /// 最多积攒的令牌数
+    burst: u32,
+    buckets: Vec<Bucket, N>,
+}
+
+impl<const N: usize> RateLimiter<N> {
+    /// 创建限速器
+    ///
+    /// # 参数
+    /// * `rate` - 每秒允许的请求数
+    /// * `burst` - 短时间内最多允许的连续请求数
+    pub const fn new(rate: u32, burst: u32) -> Self {
+        RateLimiter {
+            rate,
+            burst,
+            buckets: Vec::new(),
+        }
+    }
+
+    /// 记录一个请求
+    ///
+    /// # 参数
+    /// * `addr` - 客户端地址
+    ///
+    /// # 返回
+    /// 请求是否在限制之内，超出时不消耗令牌
+    pub fn allow(&mut self, addr: IpAddress) -> bool {
+        let now = Instant::now();
+        let full = self.burst * 1000;
+        let index = match self.buckets.iter().position(|b| b.addr == addr) {
+            Some(index) => index,
+            None => {
+                let bucket = Bucket {
+                    addr,
+                    tokens: full,
+                    updated: now,
+                };
+                if let Err(bucket) = self.buckets.push(bucket) {
+                    // 表满：替换最久没有请求的客户端
+                    let oldest = self
+                        .buckets
+                        .iter()
+                        .enumerate()
+                        .min_by_key(|(_, b)| b.updated)
+                        .map_or(0, |(index, _)| index);
+                    self.buckets[oldest] = bucket;
+                    oldest
+                } else {
+                    self.buckets.len() - 1
+                }
+            }
+        };
+
+        let bucket = &mut self.buckets[index];
+        let elapsed = now.duration_since(bucket.updated).as_millis();
+        let refill = elapsed.saturating_mul(self.rate as u64).min(full as u64) as u32;
+        bucket.tokens = (bucket.tokens + refill).min(full);
+        bucket.updated = now;
+        if bucket.tokens < 1000 {
+            return false;
+        }
+        bucket.tokens -= 1000;
+        true
+    }
+}
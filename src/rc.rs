@@ -0,0 +1,168 @@
+//! 航模遥控接收机输入
+//!
+//! 解码接收机串口输出的通道数据（帧解析见 [proto::rc]），支持两种协议：
+//!
+//! - [Protocol::Sbus]：100000 波特率 8E2，信号反相（内部自动设置引脚反相），
+//!   25 字节定长帧，每 7 或 14 ms 一帧
+//! - [Protocol::Crsf]：420000 波特率 8N1，变长帧，带 CRC8 校验，只解码通道帧（0x16）
+//!
+//! 两种协议都是 16 个 11 位通道，解码后换算为常见的舵机脉宽（微秒，中位 1500），
+//! 以 [RcFrame] 发布，通过 [subscribe] 接收，[latest] 读取最新一帧。
+//! 接收机报告失控（SBUS 标志位）或超过 [FAILSAFE_TIMEOUT] 没有收到数据时，
+//! 发布一帧 `failsafe` 为 true 的数据，通道值保持失控前的最后一帧。
+//!
+//! 接收机接在扩展排针上，用命令行 `board port rc <rx> [100000|420000]` 设置（见 [crate::board]），
+//! 波特率同时选择协议：100000 为 SBUS，420000 为 CRSF。
+//!
+//! ```ignore
+//! let receiver = RcReceiver::new(peripherals.UART2, peripherals.GPIO18, Protocol::Sbus)?;
+//! spawner.spawn(rc::rc_task(receiver))?;
+//! ```
+
+use core::cell::RefCell;
+use critical_section::Mutex;
+use defmt::{info, warn};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::pubsub::{PubSubChannel, Subscriber};
+use embassy_time::{Duration, with_timeout};
+use esp_hal::Async;
+use esp_hal::gpio::interconnect::{InputSignal, PeripheralInput};
+use esp_hal::uart::{self, Config as UartConfig, Parity, StopBits, Uart, UartRx};
+use proto::rc::Parser;
+
+pub use proto::rc::{Protocol, RcFrame};
+
+/// 超过该时间没有收到数据即进入失控状态
+pub const FAILSAFE_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// 事件队列长度
+const QUEUE_LEN: usize = 4;
+
+/// 最多同时存在的订阅者数量
+const MAX_SUBSCRIBERS: usize = 2;
+
+/// 按协议配置 UART
+fn uart_config(protocol: Protocol) -> UartConfig {
+    let config = UartConfig::default().with_baudrate(protocol.baudrate());
+    match protocol {
+        Protocol::Sbus => config
+            .with_parity(Parity::Even)
+            .with_stop_bits(StopBits::_2),
+        Protocol::Crsf => config,
+    }
+}
+
+/// 接收机错误
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum RcError {
+    /// UART 配置无效
+    Config,
+}
+
+/// 通道数据订阅者
+pub type RcSubscriber =
+    Subscriber<'static, CriticalSectionRawMutex, RcFrame, QUEUE_LEN, MAX_SUBSCRIBERS, 1>;
+
+static RC_EVENTS: PubSubChannel<CriticalSectionRawMutex, RcFrame, QUEUE_LEN, MAX_SUBSCRIBERS, 1> =
+    PubSubChannel::new();
+
+static LATEST: Mutex<RefCell<Option<RcFrame>>> = Mutex::new(RefCell::new(None));
+
+/// 订阅通道数据
+///
+/// # 返回
+/// 订阅者数量已满时返回 None
+pub fn subscribe() -> Option<RcSubscriber> {
+    RC_EVENTS.subscriber().ok()
+}
+
+/// 最新一帧通道数据，尚未收到时返回 None
+pub fn latest() -> Option<RcFrame> {
+    critical_section::with(|cs| *LATEST.borrow_ref(cs))
+}
+
+/// 发布一帧，订阅者处理不及时时丢弃最早的帧
+fn publish(frame: RcFrame) {
+    critical_section::with(|cs| *LATEST.borrow_ref_mut(cs) = Some(frame));
+    RC_EVENTS.immediate_publisher().publish_immediate(frame);
+}
+
+/// 接收机
+pub struct RcReceiver {
+    rx: UartRx<'static, Async>,
+    protocol: Protocol,
+}
+
+impl RcReceiver {
+    /// 创建接收机，只使用 UART 的接收引脚
+    ///
+    /// # 参数
+    /// * `uart` - UART 外设
+    /// * `rx` - 接收引脚，接接收机的信号输出
+    /// * `protocol` - 接收机协议
+    pub fn new(
+        uart: impl uart::Instance + 'static,
+        rx: impl PeripheralInput<'static>,
+        protocol: Protocol,
+    ) -> Result<Self, RcError> {
+        let rx: InputSignal<'static> = rx.into();
+        let rx = rx.with_input_inverter(protocol == Protocol::Sbus);
+        let (rx, _tx) = Uart::new(uart, uart_config(protocol))
+            .map_err(|_| RcError::Config)?
+            .with_rx(rx)
+            .into_async()
+            .split();
+        Ok(RcReceiver { rx, protocol })
+    }
+}
+
+/// 接收机任务
+///
+/// # 参数
+/// * `receiver` - 接收机
+#[embassy_executor::task]
+pub async fn rc_task(mut receiver: RcReceiver) {
+    let mut parser = Parser::new(receiver.protocol);
+    let mut chunk = [0u8; 32];
+    let mut failsafe = true;
+
+    info!("RC receiver started: {}", receiver.protocol);
+    loop {
+        let read = match with_timeout(FAILSAFE_TIMEOUT, receiver.rx.read_async(&mut chunk)).await {
+            Ok(Ok(read)) => read,
+            Ok(Err(err)) => {
+                warn!("RC receive failed: {}", defmt::Debug2Format(&err));
+                continue;
+            }
+            Err(_) => {
+                // 信号中断，保持最后的通道值并报告失控
+                if !failsafe
+                    && let Some(last) = latest()
+                {
+                    warn!("RC signal lost");
+                    publish(RcFrame {
+                        failsafe: true,
+                        ..last
+                    });
+                }
+                failsafe = true;
+                continue;
+            }
+        };
+
+        for &byte in &chunk[..read] {
+            let Some(frame) = parser.push(byte) else {
+                continue;
+            };
+            if frame.failsafe != failsafe {
+                if frame.failsafe {
+                    warn!("RC receiver in failsafe");
+                } else {
+                    info!("RC signal acquired");
+                }
+            }
+            failsafe = frame.failsafe;
+            publish(frame);
+        }
+    }
+}
@@ -0,0 +1,245 @@
+//! 外设注册表
+//!
+//! 记录板上每个外设驱动的名称、总线、地址或引脚、当前状态和最近一次出错的操作，
+//! 是“哪些外设正在工作”的唯一依据。各驱动在初始化、周期访问或探测设备后通过
+//! [set_online]、[report] 报告结果，状态变化时记录日志；没有启用的子系统
+//! （见 [crate::capability]、[crate::profile]）由 app 标记为 [State::Disabled]，
+//! 当前开发板上没有的外设（见 [crate::board]）标记为 [State::Absent]。
+//! 命令行 `peripherals list` 和状态屏幕的诊断页面（[crate::screens::Page::Peripherals]）
+//! 显示注册表内容。
+//!
+//! 可以热插拔的模块：
+//!
+//! - XL9555：[crate::xl9555::watchdog_task] 每次校验寄存器时更新。重新插上后按影子寄存器
+//!   重写全部输出和方向寄存器，背光、蜂鸣器和继电器恢复原来的状态
+//! - BME280：[crate::bme280::bme280_task] 测量失败时离线，撤下 `bme280.*` 读数
+//!   （[crate::sensor::withdraw]），界面显示 `--` 而不是过时的数值；之后每
+//!   [PROBE_PERIOD] 重新探测，找到后重新读取校准参数
+//! - TF 卡：[crate::sdcard::watch_task] 检测插拔
+//!
+//! 限制：固件中没有触摸屏和 RTC 芯片的驱动，因此不登记它们。启动时没有找到 XL9555 时
+//! LCD 和按键不会启动，之后插上也不会启动。
+
+use crate::board::{self, Signal};
+use crate::error::Error;
+use core::cell::Cell;
+use core::fmt::Write;
+use critical_section::Mutex;
+use defmt::{info, warn};
+use embassy_time::Duration;
+use heapless::String;
+
+/// 离线设备的探测周期
+pub const PROBE_PERIOD: Duration = Duration::from_secs(5);
+
+/// 外设所在的总线
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Bus {
+    Gpio,
+    I2c,
+    Spi,
+    /// 片上射频
+    Radio,
+}
+
+impl Bus {
+    /// 总线名称，用于命令行和诊断页面
+    pub const fn name(self) -> &'static str {
+        match self {
+            Bus::Gpio => "gpio",
+            Bus::I2c => "i2c",
+            Bus::Spi => "spi",
+            Bus::Radio => "rf",
+        }
+    }
+}
+
+/// 登记的外设
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Peripheral {
+    /// 板载 LED
+    Led,
+    /// BOOT 按键
+    BootButton,
+    /// XL9555 GPIO 扩展芯片
+    Expander,
+    /// ST7789 LCD
+    Lcd,
+    /// TF 卡
+    SdCard,
+    /// BME280 温湿度气压传感器
+    Bme280,
+    /// WiFi
+    Wifi,
+}
+
+impl Peripheral {
+    /// 所有外设，按初始化顺序排列
+    pub const ALL: [Peripheral; 7] = [
+        Peripheral::Led,
+        Peripheral::BootButton,
+        Peripheral::Expander,
+        Peripheral::Lcd,
+        Peripheral::SdCard,
+        Peripheral::Bme280,
+        Peripheral::Wifi,
+    ];
+
+    /// 外设名称，用于日志、命令行和诊断页面
+    pub const fn name(self) -> &'static str {
+        match self {
+            Peripheral::Led => "led",
+            Peripheral::BootButton => "button",
+            Peripheral::Expander => "xl9555",
+            Peripheral::Lcd => "lcd",
+            Peripheral::SdCard => "sdcard",
+            Peripheral::Bme280 => "bme280",
+            Peripheral::Wifi => "wifi",
+        }
+    }
+
+    /// 所在总线
+    pub const fn bus(self) -> Bus {
+        match self {
+            Peripheral::Led | Peripheral::BootButton => Bus::Gpio,
+            Peripheral::Expander | Peripheral::Bme280 => Bus::I2c,
+            Peripheral::Lcd | Peripheral::SdCard => Bus::Spi,
+            Peripheral::Wifi => Bus::Radio,
+        }
+    }
+
+    /// I2C 地址或使用的引脚，引脚来自当前开发板的引脚表（见 [crate::board]）
+    pub fn location(self) -> String<12> {
+        let pins = board::current().pins;
+        let mut text = String::new();
+        match self {
+            Peripheral::Led => write!(text, "gpio{}", pins.get(Signal::Led)),
+            Peripheral::BootButton => text.write_str("gpio0"),
+            Peripheral::Expander => text.write_str("0x20"),
+            Peripheral::Lcd => write!(
+                text,
+                "cs{} dc{}",
+                pins.get(Signal::LcdCs),
+                pins.get(Signal::LcdDc)
+            ),
+            Peripheral::SdCard => write!(text, "cs{}", pins.get(Signal::SdCs)),
+            Peripheral::Bme280 => text.write_str("0x76/0x77"),
+            Peripheral::Wifi => text.write_str("-"),
+        }
+        .ok();
+        text
+    }
+}
+
+/// 外设状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum State {
+    /// 驱动尚未访问过设备（任务未启动或正在启动）
+    Unknown,
+    /// 子系统已关闭，不初始化
+    Disabled,
+    /// 当前开发板上没有该外设
+    Absent,
+    Online,
+    Offline,
+}
+
+impl State {
+    /// 状态名称，用于命令行和诊断页面
+    pub const fn name(self) -> &'static str {
+        match self {
+            State::Unknown => "unknown",
+            State::Disabled => "off",
+            State::Absent => "absent",
+            State::Online => "online",
+            State::Offline => "offline",
+        }
+    }
+}
+
+/// 注册表中的一项
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Entry {
+    pub state: State,
+    /// 最近一次出错的操作，恢复在线后保留，便于事后排查
+    pub last_error: Option<&'static str>,
+}
+
+/// 各外设的记录，下标与 [Peripheral::ALL] 一致
+static ENTRIES: Mutex<Cell<[Entry; Peripheral::ALL.len()]>> = Mutex::new(Cell::new(
+    [Entry {
+        state: State::Unknown,
+        last_error: None,
+    }; Peripheral::ALL.len()],
+));
+
+/// 修改外设状态，状态变化时记录日志
+fn update(peripheral: Peripheral, current: State, error: Option<&'static str>) {
+    let previous = critical_section::with(|cs| {
+        let cell = ENTRIES.borrow(cs);
+        let mut entries = cell.get();
+        let entry = &mut entries[peripheral as usize];
+        let previous = core::mem::replace(&mut entry.state, current);
+        if error.is_some() {
+            entry.last_error = error;
+        }
+        cell.set(entries);
+        previous
+    });
+    match (previous, current) {
+        _ if previous == current => {}
+        (_, State::Disabled) => info!("Peripheral {} disabled", peripheral.name()),
+        (_, State::Absent) => info!("Peripheral {} not fitted", peripheral.name()),
+        (State::Offline, State::Online) => info!("Peripheral {} reconnected", peripheral.name()),
+        (_, State::Online) => info!("Peripheral {} online", peripheral.name()),
+        _ => warn!("Peripheral {} offline", peripheral.name()),
+    }
+}
+
+/// 报告外设已就绪
+pub fn set_online(peripheral: Peripheral) {
+    update(peripheral, State::Online, None);
+}
+
+/// 报告一次操作的结果，失败时同时记下出错的操作
+///
+/// # 参数
+/// * `peripheral` - 外设
+/// * `result` - 驱动操作的结果
+pub fn report<T>(peripheral: Peripheral, result: &Result<T, Error>) {
+    match result {
+        Ok(_) => update(peripheral, State::Online, None),
+        Err(err) => update(peripheral, State::Offline, Some(err.op())),
+    }
+}
+
+/// 报告外设不可用，记下出错的操作
+///
+/// 用于没有包装为 [Error] 的驱动错误
+///
+/// # 参数
+/// * `peripheral` - 外设
+/// * `op` - 出错的操作
+pub fn set_failed(peripheral: Peripheral, op: &'static str) {
+    update(peripheral, State::Offline, Some(op));
+}
+
+/// 标记外设所属的子系统已关闭
+pub fn set_disabled(peripheral: Peripheral) {
+    update(peripheral, State::Disabled, None);
+}
+
+/// 标记当前开发板上没有该外设（见 [crate::board::Board::has]）
+pub fn set_absent(peripheral: Peripheral) {
+    update(peripheral, State::Absent, None);
+}
+
+/// 外设当前状态
+pub fn state(peripheral: Peripheral) -> State {
+    entry(peripheral).state
+}
+
+/// 外设的完整记录
+pub fn entry(peripheral: Peripheral) -> Entry {
+    critical_section::with(|cs| ENTRIES.borrow(cs).get()[peripheral as usize])
+}
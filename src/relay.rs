@@ -0,0 +1,588 @@
+//! 继电器输出（灌溉等定时开关）
+//!
+//! 最多 [OUTPUTS] 路输出，每路的引脚在设置中配置（命令行 `relay pin <n> <引脚> [low]`）：
+//!
+//! - XL9555 扩展接口上的空闲引脚 `p0.6`、`p0.7`。P0.6 同时是恒温控制器的继电器
+//!   （见 [crate::thermostat]），启用恒温控制器时不要再分配给这里
+//! - ESP32-S3 的 `gpio<n>`，只允许 [GPIO_PINS] 中固件本身不使用的引脚，自定义开发板
+//!   引脚表（见 [crate::board]）中的引脚除外；
+//!   不要与应用按需创建的外设（RS485、DMX 等）共用引脚
+//!
+//! 默认高电平吸合，加 `low` 表示低电平吸合的继电器模块。
+//!
+//! 输出由 [relay_task] 统一驱动，以下几种方式发出的开关命令（[Switch]）效果相同，后发出的生效：
+//!
+//! - 定时任务（[crate::scheduler]）的动作 `relay <n> on|off`，或 `relay <n> <分钟>`：
+//!   打开指定的分钟数后自动关闭，例如 `0 6 * * * relay 1 15` 每天 6 点浇水 15 分钟
+//! - [Profile::Relay](crate::profile::Profile::Relay) 模式的屏幕（[screen_task]）：
+//!   按键选择输出并手动开关
+//! - HTTP `POST /relay`，请求体为 `<n>=on|off|<分钟>`；`GET /api/relays` 查看状态
+//! - 命令行 `relay <n> on|off|<分钟>`
+//!
+//! 每次关闭输出时把这次打开的时长追加到 TF 卡的数据日志（[crate::sdlog]），格式为
+//! `<Unix 时间>,<开机秒数>,relay<n>,<打开秒数>`，同时累计到开机以来的运行时间。
+//!
+//! 上电和修改引脚后输出为关闭；重启前打开的输出不会恢复。
+
+use crate::board;
+use crate::error::Error;
+#[cfg(feature = "ui")]
+use crate::i18n::{self, Msg};
+#[cfg(feature = "ui")]
+use crate::keymap::{self, Action};
+#[cfg(feature = "ui")]
+use crate::lcd::Lcd;
+#[cfg(feature = "sd")]
+use crate::sdlog;
+#[cfg(feature = "ui")]
+use crate::st7789::{self, St7789};
+#[cfg(feature = "ui")]
+use crate::{assets, input, theme};
+use crate::{settings, wallclock, xl9555};
+use core::cell::Cell;
+use core::fmt::Write;
+use critical_section::Mutex;
+use defmt::{info, warn};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::channel::Channel;
+use embassy_time::{Duration, Instant, with_timeout};
+#[cfg(feature = "ui")]
+use embedded_graphics::mono_font::{MonoTextStyle, MonoTextStyleBuilder};
+#[cfg(feature = "ui")]
+use embedded_graphics::pixelcolor::Rgb565;
+#[cfg(feature = "ui")]
+use embedded_graphics::prelude::*;
+#[cfg(feature = "ui")]
+use embedded_graphics::text::Text;
+use esp_hal::gpio::{AnyPin, Level, Output, OutputConfig};
+use heapless::String;
+use proto::json::{Object, ToJson};
+#[cfg(feature = "ui")]
+use ui::theme::Theme;
+
+/// 输出数量
+pub const OUTPUTS: usize = 4;
+
+/// 可以分配给继电器的 XL9555 引脚（P0 端口的位）
+const EXPANDER_PINS: [u8; 2] = [6, 7];
+
+/// 可以分配给继电器的 GPIO：排除固件使用的引脚、启动配置引脚、USB 和 Flash/PSRAM 引脚
+pub const GPIO_PINS: [u8; 16] = [4, 5, 6, 7, 8, 9, 10, 14, 15, 16, 17, 18, 38, 39, 47, 48];
+
+/// 一次最长的定时打开时间（分钟）
+pub const MAX_MINUTES: u16 = 24 * 60;
+
+/// 检查定时关闭的周期
+const TICK: Duration = Duration::from_secs(1);
+
+/// 命令队列长度
+const QUEUE_LEN: usize = 4;
+
+/// 屏幕刷新周期
+#[cfg(feature = "ui")]
+const REFRESH_PERIOD: Duration = Duration::from_millis(500);
+
+/// 标题、第一路输出和按键提示的基线位置，以及列表的行高
+#[cfg(feature = "ui")]
+const TITLE_Y: i32 = 26;
+#[cfg(feature = "ui")]
+const LIST_Y: i32 = 80;
+#[cfg(feature = "ui")]
+const LINE_HEIGHT: i32 = 32;
+#[cfg(feature = "ui")]
+const HINT_Y: i32 = 232;
+
+/// 继电器引脚
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct Pin {
+    kind: PinKind,
+    /// 低电平吸合
+    active_low: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+enum PinKind {
+    /// XL9555 引脚序号，0-15 对应 P0.0-P1.7
+    Expander(u8),
+    /// ESP32-S3 GPIO 编号
+    Gpio(u8),
+}
+
+impl Pin {
+    /// 设置中的编码：最高位表示 GPIO，次高位表示低电平吸合，低 6 位为编号，
+    /// XL9555 引脚编号加 1，0 表示未分配
+    const GPIO_FLAG: u8 = 0x80;
+    const ACTIVE_LOW_FLAG: u8 = 0x40;
+
+    /// 解析引脚名称，例如 `p0.7`、`gpio5`
+    ///
+    /// # 参数
+    /// * `name` - 引脚名称
+    /// * `active_low` - 是否低电平吸合
+    ///
+    /// # 返回
+    /// 名称无效或引脚不能分配给继电器时返回 None
+    pub fn parse(name: &str, active_low: bool) -> Option<Pin> {
+        let kind = match name.strip_prefix("p0.") {
+            Some(bit) => PinKind::Expander(bit.parse().ok()?),
+            None => PinKind::Gpio(name.strip_prefix("gpio")?.parse().ok()?),
+        };
+        Some(Pin {
+            kind: kind.allowed()?,
+            active_low,
+        })
+    }
+
+    /// 从设置中的编码解析，未分配或无效时返回 None
+    pub fn from_u8(code: u8) -> Option<Pin> {
+        let number = code & 0x3F;
+        let kind = if code & Self::GPIO_FLAG != 0 {
+            PinKind::Gpio(number)
+        } else {
+            PinKind::Expander(number.checked_sub(1)?)
+        };
+        Some(Pin {
+            kind: kind.allowed()?,
+            active_low: code & Self::ACTIVE_LOW_FLAG != 0,
+        })
+    }
+
+    /// 编码为设置中的值
+    pub const fn to_u8(self) -> u8 {
+        let code = match self.kind {
+            PinKind::Expander(bit) => bit + 1,
+            PinKind::Gpio(gpio) => Self::GPIO_FLAG | gpio,
+        };
+        if self.active_low {
+            code | Self::ACTIVE_LOW_FLAG
+        } else {
+            code
+        }
+    }
+
+    /// 引脚名称，低电平吸合时加 `-low`
+    pub fn name(&self) -> String<12> {
+        let mut name = String::new();
+        match self.kind {
+            PinKind::Expander(bit) => write!(name, "p{}.{}", bit / 8, bit % 8).ok(),
+            PinKind::Gpio(gpio) => write!(name, "gpio{}", gpio).ok(),
+        };
+        if self.active_low {
+            name.push_str("-low").ok();
+        }
+        name
+    }
+}
+
+impl PinKind {
+    /// 只保留可以分配给继电器的引脚
+    fn allowed(self) -> Option<PinKind> {
+        let allowed = match self {
+            PinKind::Expander(bit) => EXPANDER_PINS.contains(&bit),
+            PinKind::Gpio(gpio) => GPIO_PINS.contains(&gpio) && !board::current().uses(gpio),
+        };
+        allowed.then_some(self)
+    }
+}
+
+/// 开关命令
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Switch {
+    On,
+    Off,
+    /// 打开指定的分钟数后关闭
+    For(u16),
+}
+
+impl Switch {
+    /// 解析 `on`、`off` 或分钟数（1 到 [MAX_MINUTES]）
+    pub fn parse(text: &str) -> Option<Switch> {
+        match text {
+            "on" => Some(Switch::On),
+            "off" => Some(Switch::Off),
+            _ => {
+                let minutes = text.parse().ok();
+                minutes
+                    .filter(|m| (1..=MAX_MINUTES).contains(m))
+                    .map(Switch::For)
+            }
+        }
+    }
+}
+
+/// 一路输出的状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RelayStatus {
+    /// 分配的引脚，未分配时为 None
+    pub pin: Option<Pin>,
+    /// 本次打开的时刻，关闭时为 None
+    pub on_since: Option<Instant>,
+    /// 定时关闭的时刻
+    pub off_at: Option<Instant>,
+    /// 开机以来累计打开的时间，不含本次
+    pub runtime: Duration,
+}
+
+impl RelayStatus {
+    const OFF: RelayStatus = RelayStatus {
+        pin: None,
+        on_since: None,
+        off_at: None,
+        runtime: Duration::from_ticks(0),
+    };
+
+    pub fn is_on(&self) -> bool {
+        self.on_since.is_some()
+    }
+
+    /// 开机以来累计打开的时间，包括本次
+    pub fn total_runtime(&self) -> Duration {
+        let current = self
+            .on_since
+            .map_or(Duration::from_ticks(0), |at| at.elapsed());
+        self.runtime + current
+    }
+
+    /// 距定时关闭的剩余时间
+    pub fn remaining(&self) -> Option<Duration> {
+        self.off_at
+            .map(|at| at.saturating_duration_since(Instant::now()))
+    }
+}
+
+impl ToJson for RelayStatus {
+    /// 引脚名称、开关状态、定时关闭的剩余秒数和累计运行秒数；
+    /// 未分配引脚时名称为空，没有定时关闭时剩余秒数为 `null`
+    fn write_members(&self, object: &mut Object<'_>) {
+        let pin = self.pin.map(|pin| pin.name());
+        let remaining = self.remaining().map_or(f64::NAN, |d| d.as_secs() as f64);
+        object
+            .str("pin", pin.as_deref().unwrap_or(""))
+            .bool("on", self.is_on())
+            .number("remaining", remaining)
+            .int("runtime", self.total_runtime().as_secs() as i64);
+    }
+}
+
+/// 各路输出的状态
+static STATUS: Mutex<Cell<[RelayStatus; OUTPUTS]>> =
+    Mutex::new(Cell::new([RelayStatus::OFF; OUTPUTS]));
+
+/// 等待执行的命令：输出序号（从 0 开始）和开关命令
+static COMMANDS: Channel<CriticalSectionRawMutex, (usize, Switch), QUEUE_LEN> = Channel::new();
+
+/// 各路输出的状态
+pub fn status() -> [RelayStatus; OUTPUTS] {
+    critical_section::with(|cs| STATUS.borrow(cs).get())
+}
+
+/// 开关一路输出，由 [relay_task] 执行
+///
+/// # 参数
+/// * `output` - 输出编号，从 1 开始
+/// * `switch` - 开关命令
+///
+/// # 返回
+/// 编号无效时返回 false；队列已满时丢弃命令
+pub fn command(output: usize, switch: Switch) -> bool {
+    if !(1..=OUTPUTS).contains(&output) {
+        return false;
+    }
+    if COMMANDS.try_send((output - 1, switch)).is_err() {
+        warn!("Relay command queue full, dropping command");
+    }
+    true
+}
+
+/// 已创建的输出驱动
+enum Driver {
+    None,
+    Expander {
+        bits: u16,
+        active_low: bool,
+    },
+    Gpio {
+        output: Output<'static>,
+        active_low: bool,
+    },
+}
+
+impl Driver {
+    /// 为引脚创建驱动，输出为关闭
+    async fn new(pin: Option<Pin>) -> Driver {
+        let Some(pin) = pin else {
+            return Driver::None;
+        };
+        let active_low = pin.active_low;
+        let mut driver = match pin.kind {
+            PinKind::Expander(bit) => Driver::Expander {
+                bits: 1 << bit,
+                active_low,
+            },
+            PinKind::Gpio(gpio) => {
+                // SAFETY: GPIO_PINS 中的引脚固件本身不使用，同一时刻只在这里创建一个驱动，
+                // 修改引脚时旧的驱动先被释放
+                let any = unsafe { AnyPin::steal(gpio) };
+                let output = Output::new(any, Level::from(active_low), OutputConfig::default());
+                Driver::Gpio { output, active_low }
+            }
+        };
+        if let Err(err) = driver.set(false).await {
+            warn!("Failed to release relay {}: {}", pin.name().as_str(), err);
+        }
+        driver
+    }
+
+    /// 设置输出
+    ///
+    /// # 参数
+    /// * `on` - true 表示吸合
+    async fn set(&mut self, on: bool) -> Result<(), Error> {
+        match self {
+            Driver::None => Ok(()),
+            Driver::Expander { bits, active_low } => {
+                xl9555::set_spare_output(*bits, on != *active_low).await
+            }
+            Driver::Gpio { output, active_low } => {
+                output.set_level(Level::from(on != *active_low));
+                Ok(())
+            }
+        }
+    }
+}
+
+/// 继电器控制任务
+///
+/// 按设置中的引脚创建输出，执行开关命令，定时关闭，并记录运行时间
+#[embassy_executor::task]
+pub async fn relay_task() {
+    let mut drivers: [Driver; OUTPUTS] = core::array::from_fn(|_| Driver::None);
+    let mut pins = [None; OUTPUTS];
+    loop {
+        // 引脚改变时关闭原来的输出，创建新的驱动
+        let configured = settings::get().relay_pins.map(Pin::from_u8);
+        let outputs = drivers.iter_mut().zip(&mut pins).zip(configured);
+        for (index, ((driver, pin), new)) in outputs.enumerate() {
+            if *pin == new {
+                continue;
+            }
+            switch(driver, index, false).await;
+            // 先释放旧的驱动，再创建新的
+            *driver = Driver::None;
+            *driver = Driver::new(new).await;
+            *pin = new;
+            update(index, |relay| relay.pin = new);
+        }
+
+        if let Ok((index, command)) = with_timeout(TICK, COMMANDS.receive()).await {
+            info!("Relay {} {}", index + 1, command);
+            if pins[index].is_none() {
+                warn!("Relay {} has no pin assigned", index + 1);
+            } else if switch(&mut drivers[index], index, command != Switch::Off).await {
+                let off_at = match command {
+                    Switch::For(minutes) => {
+                        Some(Instant::now() + Duration::from_secs(minutes as u64 * 60))
+                    }
+                    _ => None,
+                };
+                update(index, |relay| relay.off_at = off_at);
+            }
+        }
+
+        let now = Instant::now();
+        for (index, relay) in status().iter().enumerate() {
+            if relay.off_at.is_some_and(|at| at <= now) {
+                info!("Relay {} timer expired", index + 1);
+                switch(&mut drivers[index], index, false).await;
+            }
+        }
+    }
+}
+
+/// 开关一路输出并记录运行时间
+///
+/// # 返回
+/// 未分配引脚或设置输出失败时返回 false，此时状态不变
+async fn switch(driver: &mut Driver, index: usize, on: bool) -> bool {
+    let relay = status()[index];
+    if relay.pin.is_none() {
+        return false;
+    }
+    if let Err(err) = driver.set(on).await {
+        warn!("Failed to switch relay {}: {}", index + 1, err);
+        return false;
+    }
+    match (relay.on_since, on) {
+        (None, true) => update(index, |relay| relay.on_since = Some(Instant::now())),
+        (Some(since), false) => {
+            let elapsed = since.elapsed();
+            update(index, |relay| {
+                relay.on_since = None;
+                relay.off_at = None;
+                relay.runtime += elapsed;
+            });
+            log_run(index, elapsed);
+        }
+        _ => {}
+    }
+    true
+}
+
+/// 修改一路输出的状态
+fn update(index: usize, f: impl FnOnce(&mut RelayStatus)) {
+    critical_section::with(|cs| {
+        let cell = STATUS.borrow(cs);
+        let mut all = cell.get();
+        f(&mut all[index]);
+        cell.set(all);
+    });
+}
+
+/// 追加一条运行记录到 TF 卡
+fn log_run(index: usize, elapsed: Duration) {
+    let mut line: String<64> = String::new();
+    if let Some(unix) = wallclock::now() {
+        write!(line, "{}", unix).ok();
+    }
+    write!(
+        line,
+        ",{},relay{},{}",
+        Instant::now().as_secs(),
+        index + 1,
+        elapsed.as_secs()
+    )
+    .ok();
+    #[cfg(feature = "sd")]
+    sdlog::append(&line);
+}
+
+/// 继电器屏幕任务
+///
+/// 列出各路输出的引脚、开关状态、剩余时间和累计运行时间，按键选择输出并手动开关
+///
+/// # 参数
+/// * `lcd` - LCD 屏幕
+#[cfg(feature = "ui")]
+#[embassy_executor::task]
+pub async fn screen_task(mut lcd: Lcd) {
+    let Some(mut keys) = input::subscribe() else {
+        warn!("No key subscriber available for relays");
+        return;
+    };
+    input::set_captured(true);
+
+    let mut colors = theme::current();
+    clear_screen(&mut lcd, &colors);
+    // 已显示的各行文字和选中的行，None 表示需要重绘
+    let mut shown: [Option<(String<40>, bool)>; OUTPUTS] = Default::default();
+    let mut selected = 0;
+    loop {
+        let current = theme::current();
+        if current != colors {
+            colors = current;
+            clear_screen(&mut lcd, &colors);
+            shown = Default::default();
+        }
+
+        for (index, relay) in status().iter().enumerate() {
+            let line = format_line(index, relay);
+            let entry = Some((line, index == selected));
+            if shown[index] != entry {
+                let style = if index == selected {
+                    text_style(colors.accent, &colors)
+                } else {
+                    text_style(colors.foreground, &colors)
+                };
+                let y = LIST_Y + index as i32 * LINE_HEIGHT;
+                lcd.fill_rectangle(0, (y - 18) as u16, st7789::WIDTH, 24, colors.background)
+                    .ok();
+                if let Some((line, _)) = &entry {
+                    draw_text(&mut lcd, line, y, style);
+                }
+                shown[index] = entry;
+            }
+        }
+
+        let Ok(key) = with_timeout(REFRESH_PERIOD, keys.next_message_pure()).await else {
+            continue;
+        };
+        match keymap::action(key) {
+            Some(Action::ScrollUp) => selected = (selected + OUTPUTS - 1) % OUTPUTS,
+            Some(Action::ScrollDown) => selected = (selected + 1) % OUTPUTS,
+            Some(Action::StartStop) => {
+                let on = status()[selected].is_on();
+                command(selected + 1, if on { Switch::Off } else { Switch::On });
+            }
+            _ => {}
+        }
+    }
+}
+
+/// 屏幕上一路输出的文字，例如 `1 gpio5  on 14:59 2h`
+#[cfg(feature = "ui")]
+fn format_line(index: usize, relay: &RelayStatus) -> String<40> {
+    let mut line = String::new();
+    write!(line, "{} ", index + 1).ok();
+    let Some(pin) = relay.pin else {
+        line.push_str(i18n::lcd(Msg::RelayUnassigned)).ok();
+        return line;
+    };
+    write!(
+        line,
+        "{:<11}{:<4}",
+        pin.name().as_str(),
+        if relay.is_on() { "on" } else { "off" }
+    )
+    .ok();
+    if let Some(remaining) = relay.remaining() {
+        let secs = remaining.as_secs();
+        write!(line, "{:2}:{:02} ", secs / 60, secs % 60).ok();
+    }
+    let runtime = relay.total_runtime().as_secs();
+    if runtime >= 3600 {
+        write!(line, "{}h", runtime / 3600).ok();
+    } else {
+        write!(line, "{}m", runtime / 60).ok();
+    }
+    line
+}
+
+/// 用配色的背景色清屏并显示标题和按键提示
+#[cfg(feature = "ui")]
+fn clear_screen(lcd: &mut St7789, colors: &Theme) {
+    if let Err(err) = lcd.fill_screen(colors.background) {
+        warn!("Failed to clear LCD: {}", err);
+    }
+    let accent = text_style(colors.accent, colors);
+    draw_text(lcd, i18n::lcd(Msg::RelayTitle), TITLE_Y, accent);
+
+    let number = |action| keymap::key_name(keymap::key_for(action)).trim_start_matches("key");
+    let mut hint: String<40> = String::new();
+    write!(
+        hint,
+        "K{}/K{} {} K{} {}",
+        number(Action::ScrollUp),
+        number(Action::ScrollDown),
+        i18n::lcd(Msg::RelaySelect),
+        number(Action::StartStop),
+        i18n::lcd(Msg::RelayToggle)
+    )
+    .ok();
+    draw_text(lcd, &hint, HINT_Y, text_style(colors.foreground, colors));
+}
+
+#[cfg(feature = "ui")]
+fn text_style(color: Rgb565, colors: &Theme) -> MonoTextStyle<'static, Rgb565> {
+    MonoTextStyleBuilder::new()
+        .font(assets::font())
+        .text_color(color)
+        .background_color(colors.background)
+        .build()
+}
+
+#[cfg(feature = "ui")]
+fn draw_text(lcd: &mut St7789, text: &str, y: i32, style: MonoTextStyle<'_, Rgb565>) {
+    if let Err(err) = Text::new(text, Point::new(10, y), style).draw(lcd) {
+        warn!("Failed to draw relay text: {}", err);
+    }
+}
@@ -0,0 +1,291 @@
+//! 远程显示
+//!
+//! [Profile::Remote](crate::profile::Profile::Remote) 模式下 LCD 成为 PC 的无线副屏：
+//! PC 上的脚本连接 TCP [PORT] 端口，把画面按矩形块推送过来，板子依次写到 LCD 上。
+//! 每次只处理一个连接，连接断开后屏幕保留最后的画面，直到下一个连接推送新的画面。
+//!
+//! # 协议
+//!
+//! 所有整数都是小端：
+//!
+//! 1. 连接建立后板子发送 [HELLO_LEN] 字节的问候：魔数 `RD`、版本 [VERSION]、保留 1 字节、
+//!    屏幕宽度和高度（u16）、发送窗口（u32，字节）
+//! 2. PC 发送块：[HEADER_LEN] 字节的块头 `x`、`y`、`w`、`h`（u16），后跟 `w * h * 2` 字节的
+//!    像素，按行排列，RGB565 大端（与面板相同，板子不做转换）。块必须完整位于屏幕内，
+//!    像素不超过 [MAX_TILE_LEN] 字节，否则板子关闭连接
+//! 3. 每个块写到 LCD 之后板子回复 4 字节（u32）：这个块的字节数，包括块头
+//!
+//! # 流量控制
+//!
+//! LCD 的 SPI 总线（10MHz，约 1.2MB/s）比 WiFi 慢，PC 不加限制地发送时，画面会在两端的
+//! TCP 缓冲区里排队，看到的画面越来越滞后。因此 PC 已发送但还没有收到回复的字节数不能超过
+//! 问候中的窗口 [WINDOW]：SPI 总线在 [QUEUE_TIME_MS] 毫秒内能发送的字节数。
+//! 排队的数据最多等这么久就能上屏，窗口也不超过板子的接收缓冲区，TCP 窗口不会关闭。
+//! 往返时延比这长时 SPI 总线会有空闲，换来的是画面的延迟有上限。
+//!
+//! 接收和上屏由两个任务完成：网络任务 [server] 在 PRO_CPU 上接收块，
+//! 屏幕任务 [display_task] 在 APP_CPU 上写 LCD，两者之间轮流使用 [SLOTS] 个块缓冲区，
+//! 一个块上屏的同时接收下一个块。
+
+use crate::i18n::{self, Msg};
+use crate::lcd::Lcd;
+use crate::net::{SocketOptions, TcpBuffers};
+use crate::netstats::{self, Link};
+use crate::{assets, spi, st7789};
+use alloc::vec::Vec;
+use core::fmt::Write as _;
+use defmt::{info, warn};
+use embassy_futures::select::{Either, select};
+use embassy_net::Stack;
+use embassy_net::tcp::{Error as TcpError, TcpReader, TcpSocket, TcpWriter};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::channel::Channel;
+use embassy_time::{Duration, Timer};
+use embedded_graphics::mono_font::{MonoTextStyle, MonoTextStyleBuilder};
+use embedded_graphics::pixelcolor::Rgb565;
+use embedded_graphics::prelude::*;
+use embedded_graphics::text::Text;
+use embedded_io_async::Write;
+use heapless::String;
+
+/// 监听端口
+pub const PORT: u16 = 7010;
+
+/// 协议版本
+const VERSION: u8 = 1;
+
+/// 问候的魔数
+const MAGIC: [u8; 2] = *b"RD";
+
+/// 问候长度
+const HELLO_LEN: usize = 12;
+
+/// 块头长度
+const HEADER_LEN: usize = 8;
+
+/// 一个块的像素最多的字节数（整屏宽 16 行）
+pub const MAX_TILE_LEN: usize = st7789::WIDTH as usize * 16 * 2;
+
+/// 块缓冲区数量
+const SLOTS: usize = 2;
+
+/// LCD 的 SPI 总线每秒发送的字节数
+const SPI_BYTES_PER_SEC: usize = spi::DEFAULT_FREQUENCY.as_hz() as usize / 8;
+
+/// 发送窗口对应的 SPI 传输时间（毫秒）
+const QUEUE_TIME_MS: usize = 20;
+
+/// 发送窗口（字节）
+pub const WINDOW: usize = SPI_BYTES_PER_SEC * QUEUE_TIME_MS / 1000;
+
+// PC 至少要能发送一个完整的块
+const _: () = assert!(WINDOW >= HEADER_LEN + MAX_TILE_LEN);
+
+/// 连接参数：接收缓冲区容纳整个窗口；
+/// PC 脚本可能很久才推送一次画面，打开保活及时发现掉线的 PC，释放唯一的连接
+const SOCKET: SocketOptions = SocketOptions {
+    keep_alive: Some(Duration::from_secs(10)),
+    timeout: Some(Duration::from_secs(30)),
+    rx_buffer: WINDOW,
+    tx_buffer: 256,
+};
+
+/// 一个块
+struct Tile {
+    x: u16,
+    y: u16,
+    w: u16,
+    h: u16,
+    /// 像素，长度为 `w * h * 2`
+    pixels: Vec<u8>,
+}
+
+/// 接收完整、等待上屏的块
+static TILES: Channel<CriticalSectionRawMutex, Tile, SLOTS> = Channel::new();
+
+/// 空闲的块缓冲区，屏幕任务上屏后归还
+static FREE: Channel<CriticalSectionRawMutex, Vec<u8>, SLOTS> = Channel::new();
+
+/// 已上屏的块的字节数，由网络任务回复给 PC
+static DONE: Channel<CriticalSectionRawMutex, u32, SLOTS> = Channel::new();
+
+/// 远程显示网络任务：监听端口并逐个处理连接
+///
+/// # 参数
+/// * `stack` - 网络协议栈
+#[embassy_executor::task]
+pub async fn server(stack: Stack<'static>) {
+    for _ in 0..SLOTS {
+        FREE.try_send(Vec::with_capacity(MAX_TILE_LEN)).ok();
+    }
+    let mut buffers = TcpBuffers::new(SOCKET);
+
+    stack.wait_config_up().await;
+    info!(
+        "Remote display listening on port {} (window {} bytes)",
+        PORT, WINDOW
+    );
+
+    loop {
+        let mut socket = buffers.socket(stack);
+        if let Err(err) = socket.accept(PORT).await {
+            warn!("Remote display accept failed: {}", err);
+            continue;
+        }
+        netstats::opened(Link::Remote);
+        info!("Remote display connected");
+
+        // 接收中的块缓冲区放在这里，连接出错中断接收时不会丢失
+        let mut spare = None;
+        let result = match send_hello(&mut socket).await {
+            Ok(()) => {
+                let (reader, writer) = socket.split();
+                match select(receive(reader, &mut spare), acknowledge(writer)).await {
+                    Either::First(result) | Either::Second(result) => result,
+                }
+            }
+            Err(err) => Err(err),
+        };
+        // TCP 错误只有连接复位一种（对端断开或超时），与正常断开一样处理
+        result.ok();
+        info!("Remote display disconnected");
+        socket.close();
+        socket.flush().await.ok();
+
+        // 等已收到的块全部上屏，丢弃这些块的回复，不发给下一个连接。
+        // 屏幕任务先发送回复再归还缓冲区，等待期间也要清空回复，否则屏幕任务会卡在发送回复上
+        if let Some(pixels) = spare.take() {
+            FREE.try_send(pixels).ok();
+        }
+        while !FREE.is_full() {
+            DONE.clear();
+            Timer::after(Duration::from_millis(10)).await;
+        }
+        DONE.clear();
+    }
+}
+
+/// 发送问候
+async fn send_hello(socket: &mut TcpSocket<'_>) -> Result<(), TcpError> {
+    let mut hello = [0u8; HELLO_LEN];
+    hello[0..2].copy_from_slice(&MAGIC);
+    hello[2] = VERSION;
+    hello[4..6].copy_from_slice(&st7789::WIDTH.to_le_bytes());
+    hello[6..8].copy_from_slice(&st7789::HEIGHT.to_le_bytes());
+    hello[8..12].copy_from_slice(&(WINDOW as u32).to_le_bytes());
+    socket.write_all(&hello).await?;
+    netstats::sent(Link::Remote, hello.len());
+    Ok(())
+}
+
+/// 接收块并交给屏幕任务，PC 关闭连接或块头无效时返回
+///
+/// # 参数
+/// * `reader` - 连接的接收端
+/// * `spare` - 接收中的块缓冲区
+async fn receive(mut reader: TcpReader<'_>, spare: &mut Option<Vec<u8>>) -> Result<(), TcpError> {
+    let mut header = [0u8; HEADER_LEN];
+    loop {
+        if !read_exact(&mut reader, &mut header).await? {
+            return Ok(());
+        }
+        let field = |i: usize| u16::from_le_bytes([header[i], header[i + 1]]);
+        let (x, y, w, h) = (field(0), field(2), field(4), field(6));
+        let Some(len) = tile_len(x, y, w, h) else {
+            warn!(
+                "Remote display: invalid tile {}x{} at ({}, {}), closing connection",
+                w, h, x, y
+            );
+            return Ok(());
+        };
+
+        if spare.is_none() {
+            *spare = Some(FREE.receive().await);
+        }
+        let pixels = spare.get_or_insert_with(Vec::new);
+        pixels.clear();
+        pixels.resize(len, 0);
+        if !read_exact(&mut reader, pixels).await? {
+            return Ok(());
+        }
+        // 块缓冲区只有 SLOTS 个，队列不会满，发送不会等待，因此不会在这里被中断而丢失缓冲区
+        if let Some(pixels) = spare.take() {
+            TILES.send(Tile { x, y, w, h, pixels }).await;
+        }
+    }
+}
+
+/// 把已上屏的块的字节数回复给 PC
+///
+/// # 参数
+/// * `writer` - 连接的发送端
+async fn acknowledge(mut writer: TcpWriter<'_>) -> Result<(), TcpError> {
+    loop {
+        let len = DONE.receive().await;
+        writer.write_all(&len.to_le_bytes()).await?;
+        netstats::sent(Link::Remote, 4);
+    }
+}
+
+/// 读满缓冲区
+///
+/// # 返回
+/// 对方在读满之前关闭连接时返回 false
+async fn read_exact(reader: &mut TcpReader<'_>, buf: &mut [u8]) -> Result<bool, TcpError> {
+    let mut len = 0;
+    while len < buf.len() {
+        let read = reader.read(&mut buf[len..]).await?;
+        netstats::received(Link::Remote, read);
+        if read == 0 {
+            return Ok(false);
+        }
+        len += read;
+    }
+    Ok(true)
+}
+
+/// 检查块的位置和大小
+///
+/// # 返回
+/// 像素的字节数；块为空、超出屏幕或超过 [MAX_TILE_LEN] 时返回 None
+fn tile_len(x: u16, y: u16, w: u16, h: u16) -> Option<usize> {
+    let inside =
+        x as u32 + w as u32 <= st7789::WIDTH as u32 && y as u32 + h as u32 <= st7789::HEIGHT as u32;
+    let len = w as usize * h as usize * 2;
+    (inside && len > 0 && len <= MAX_TILE_LEN).then_some(len)
+}
+
+/// 远程显示屏幕任务
+///
+/// 启动时显示监听的端口，之后把收到的块依次写到 LCD 上
+///
+/// # 参数
+/// * `lcd` - 已完成初始化的 LCD
+#[embassy_executor::task]
+pub async fn display_task(mut lcd: Lcd) {
+    let style: MonoTextStyle<'_, Rgb565> = MonoTextStyleBuilder::new()
+        .font(assets::font())
+        .text_color(Rgb565::WHITE)
+        .background_color(Rgb565::BLACK)
+        .build();
+    if let Err(err) = lcd.fill_screen(Rgb565::BLACK).await {
+        warn!("Failed to clear LCD: {}", err);
+    }
+    let mut port: String<16> = String::new();
+    write!(port, "TCP {}", PORT).ok();
+    for (line, y) in [(i18n::lcd(Msg::RemoteTitle), 110), (port.as_str(), 140)] {
+        if let Err(err) = Text::new(line, Point::new(10, y), style).draw(&mut lcd) {
+            warn!("Failed to draw remote display banner: {}", err);
+        }
+    }
+
+    loop {
+        let Tile { x, y, w, h, pixels } = TILES.receive().await;
+        if let Err(err) = lcd.flush(x, y, w, h, &pixels).await {
+            // 回复照常发送，PC 的窗口不会因此卡住
+            warn!("Remote display flush failed: {}", err);
+        }
+        DONE.send((HEADER_LEN + pixels.len()) as u32).await;
+        FREE.send(pixels).await;
+    }
+}
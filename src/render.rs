@@ -0,0 +1,584 @@
+//! 渲染任务
+//!
+//! 独占 LCD 并负责所有屏幕刷新，运行在 APP_CPU 上（见 [crate::multicore]），
+//! 大块 SPI 填充不会拖慢核心 0 上的 WiFi 和按键任务。
+//!
+//! 其他任务不直接访问 LCD，而是通过 [command] 发送 [Command]，由渲染任务按顺序执行。
+//! 例如 KEY2 切换背景颜色时发送 [Command::FillColor]。
+//!
+//! 颜色来自 [crate::theme]，配色改变时（命令行切换或自动模式下环境亮度变化）恢复配色的背景色并重绘。
+//! 面板调校参数（[crate::tuning]）改变时重新发送给控制器，屏幕内容不需要重绘。
+//!
+//! 背光关闭时控制器进入睡眠模式（见 [Lcd::sleep]），页面照常刷新到显存中，
+//! 背光打开后在下一次刷新时唤醒，直接显示最新内容。
+//!
+//! # 动画
+//!
+//! 底部横幅（[Command::Banner]）和顶部进度条（[Command::Progress]）用 [ui::tween] 做过渡：
+//! 横幅滑入、停留后淡出，进度条平滑地伸缩到新的长度。进度条由 [crate::progress] 的报告器发出，
+//! 进度说明显示在横幅的位置，横幅显示期间让位于横幅。有动画时渲染任务按设置中的目标帧率
+//! （默认 20，命令行 `fps <n>` 修改）绘制，只重绘变化的区域。帧的节拍由 [ui::frame] 控制：
+//! 单帧绘制超时就跳过错过的帧，动画数值只取决于时间，不会变慢，只是帧数减少，
+//! 给命令处理和状态行刷新留出 SPI 时间。
+//!
+//! 调试界面性能时用 `fps on` 在屏幕右上角显示每秒绘制的帧数和平均帧时间（页面刷新也计入），
+//! 每次刷新页面时更新；`fps` 在控制台输出同样的统计和跳过的帧数。
+
+use crate::input::{self, Key, KeySubscriber};
+use crate::lcd::Lcd;
+use crate::screens::{self, NAV_DEPTH, Page, PageSet, Style};
+use crate::st7789::{self, St7789};
+use crate::{assets, jitter, settings, theme, tuning};
+use core::cell::Cell;
+use core::fmt::Write;
+use core::sync::atomic::{AtomicBool, Ordering};
+use critical_section::Mutex;
+use defmt::{debug, warn};
+use embassy_futures::select::{Either4, select4};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::channel::Channel;
+use embassy_time::{Duration, Instant, Ticker, Timer};
+use embedded_graphics::mono_font::MonoTextStyle;
+use embedded_graphics::mono_font::MonoTextStyleBuilder;
+use embedded_graphics::mono_font::ascii::FONT_6X10;
+use embedded_graphics::pixelcolor::Rgb565;
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::Rectangle;
+use embedded_graphics::text::{Baseline, Text};
+use heapless::String;
+use ui::frame::{FrameLimiter, FrameStats};
+use ui::screens::Navigator;
+use ui::text::{Align, TextBox};
+use ui::theme::Theme;
+use ui::tween::{self, Easing, Tween};
+
+/// 页面刷新周期
+const REFRESH_PERIOD: Duration = Duration::from_secs(1);
+
+/// 命令队列长度
+const COMMAND_QUEUE_LEN: usize = 4;
+
+/// 横幅区域（屏幕底部）
+const BANNER_HEIGHT: u32 = 24;
+const BANNER_TOP: i32 = st7789::HEIGHT as i32 - BANNER_HEIGHT as i32;
+const BANNER_TEXT_OFFSET: Point = Point::new(10, 18);
+
+/// 横幅滑入、停留和淡出的时长（毫秒）
+const BANNER_SLIDE_MS: u32 = 250;
+const BANNER_HOLD_MS: u32 = 3000;
+const BANNER_FADE_MS: u32 = 500;
+
+/// 进度条区域（屏幕顶部，标题上方）
+const PROGRESS_HEIGHT: u32 = 4;
+
+/// 进度条过渡到新长度的时长（毫秒）
+const PROGRESS_MS: u32 = 400;
+
+/// 帧率统计的位置（屏幕右上角，进度条下方、状态图标上方），宽度可容纳 `99fps 999ms`
+const FPS_POSITION: Point = Point::new(st7789::WIDTH as i32 - 70, PROGRESS_HEIGHT as i32);
+
+/// KEY2 依次切换的背景颜色，只使用深色，文字为白色
+pub const PALETTE: [Rgb565; 5] = [
+    Rgb565::BLACK,
+    Rgb565::CSS_NAVY,
+    Rgb565::CSS_DARK_GREEN,
+    Rgb565::CSS_MAROON,
+    Rgb565::CSS_INDIGO,
+];
+
+static COMMANDS: Channel<CriticalSectionRawMutex, Command, COMMAND_QUEUE_LEN> = Channel::new();
+
+/// 渲染任务是否在运行，其他应用模式下命令直接丢弃
+static RUNNING: AtomicBool = AtomicBool::new(false);
+
+/// 是否在屏幕上显示帧率统计
+static FPS_OVERLAY: AtomicBool = AtomicBool::new(false);
+
+/// 最近一秒的帧率统计，渲染任务每次刷新页面时更新
+static FRAME_STATS: Mutex<Cell<FrameStats>> = Mutex::new(Cell::new(FrameStats {
+    fps: 0,
+    average_ms: 0,
+    max_ms: 0,
+    skipped: 0,
+}));
+
+/// 屏幕
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Screen {
+    /// [crate::screens] 中的页面，默认为仪表盘
+    Status,
+    /// 只显示背景颜色，运行时间停止刷新
+    Blank,
+}
+
+/// 渲染命令
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    /// 更换背景颜色并重绘当前屏幕
+    FillColor(Rgb565),
+    /// 切换屏幕
+    ShowScreen(Screen),
+    /// 在屏幕底部滑入一条横幅，几秒后淡出，新的横幅替换正在显示的横幅
+    Banner(String<32>),
+    /// 显示顶部进度条（百分比）和底部的进度说明，None 表示隐藏，见 [crate::progress]
+    Progress(Option<(u8, String<32>)>),
+}
+
+/// 向渲染任务发送命令，渲染任务未运行或队列已满时丢弃
+pub fn command(command: Command) {
+    if !RUNNING.load(Ordering::Relaxed) {
+        return;
+    }
+    if COMMANDS.try_send(command).is_err() {
+        warn!("Render command queue full, dropping command");
+    }
+}
+
+/// 开关屏幕上的帧率统计（不保存，重启后关闭）
+pub fn set_fps_overlay(on: bool) {
+    FPS_OVERLAY.store(on, Ordering::Relaxed);
+}
+
+/// 屏幕上是否显示帧率统计
+pub fn fps_overlay() -> bool {
+    FPS_OVERLAY.load(Ordering::Relaxed)
+}
+
+/// 最近一秒的帧率统计，渲染任务未运行时各项为 0
+pub fn frame_stats() -> FrameStats {
+    critical_section::with(|cs| FRAME_STATS.borrow(cs).get())
+}
+
+/// 在屏幕底部显示一条横幅，超过 32 字节的部分截断
+pub fn banner(text: &str) {
+    let mut line: String<32> = String::new();
+    for c in text.chars() {
+        if line.push(c).is_err() {
+            break;
+        }
+    }
+    command(Command::Banner(line));
+}
+
+/// 横幅动画
+struct Banner {
+    text: String<32>,
+    /// 横幅顶边相对 [BANNER_TOP] 的偏移，从 [BANNER_HEIGHT] 滑到 0
+    slide: Tween,
+    /// 淡出进度（千分比），停留结束后开始
+    fade: Tween,
+    /// 上一帧绘制时的偏移和淡出进度，None 表示需要完整重绘
+    drawn: Option<(i32, i32)>,
+}
+
+impl Banner {
+    fn new(text: String<32>, now_ms: u64) -> Self {
+        let mut slide = Tween::new(
+            BANNER_HEIGHT as i32,
+            0,
+            BANNER_SLIDE_MS,
+            Easing::EaseOutCubic,
+        );
+        slide.start(now_ms);
+        let mut fade = Tween::new(0, tween::FULL as i32, BANNER_FADE_MS, Easing::Linear);
+        fade.start(now_ms + (BANNER_SLIDE_MS + BANNER_HOLD_MS) as u64);
+        Banner {
+            text,
+            slide,
+            fade,
+            drawn: None,
+        }
+    }
+
+    /// 绘制一帧，数值与上一帧相同时跳过
+    fn draw(&mut self, lcd: &mut St7789, colors: &Theme, background: Rgb565, now_ms: u64) {
+        let frame = (self.slide.value(now_ms), self.fade.value(now_ms));
+        if self.drawn == Some(frame) {
+            return;
+        }
+        let (offset, fade) = frame;
+        let fill = tween::mix(colors.accent, background, fade as u32);
+        let text = tween::mix(colors.background, background, fade as u32);
+        // 横幅只会向上滑动，从新的顶边画到屏幕底部即可覆盖上一帧
+        let top = Point::new(0, BANNER_TOP + offset);
+        let size = Size::new(st7789::WIDTH as u32, BANNER_HEIGHT - offset as u32);
+        if let Err(err) = lcd.fill_solid(&Rectangle::new(top, size), fill) {
+            warn!("Failed to draw banner: {}", err);
+        }
+        // 不设文字背景色，只写笔画像素，LCD 驱动逐像素绘制文字
+        let style = MonoTextStyle::new(assets::font(), text);
+        Text::new(&self.text, top + BANNER_TEXT_OFFSET, style)
+            .draw(lcd)
+            .ok();
+        self.drawn = Some(frame);
+    }
+
+    fn is_finished(&self, now_ms: u64) -> bool {
+        self.fade.is_finished(now_ms)
+    }
+}
+
+/// 进度条在 `percent` 时的长度（像素）
+pub fn progress_width(percent: u8) -> i32 {
+    st7789::WIDTH as i32 * percent.min(100) as i32 / 100
+}
+
+/// 用 `color` 填充进度条从 `from` 到 `to`（像素）的一段
+pub fn fill_progress(lcd: &mut St7789, from: i32, to: i32, color: Rgb565) {
+    if from >= to {
+        return;
+    }
+    let area = Rectangle::new(
+        Point::new(from, 0),
+        Size::new((to - from) as u32, PROGRESS_HEIGHT),
+    );
+    if let Err(err) = lcd.fill_solid(&area, color) {
+        warn!("Failed to draw progress bar: {}", err);
+    }
+}
+
+/// 在屏幕底部（横幅的位置）居中显示进度说明，覆盖整行
+pub fn draw_progress_message(lcd: &mut St7789, message: &str, style: Style) {
+    let size = Size::new(st7789::WIDTH as u32, BANNER_HEIGHT);
+    let area = Rectangle::new(Point::new(0, BANNER_TOP), size);
+    if let Err(err) = TextBox::new(area, style.text(), Align::Center).draw_line(lcd, message) {
+        warn!("Failed to draw progress message: {}", err);
+    }
+}
+
+/// 进度条动画
+struct ProgressBar {
+    /// 长度（像素）
+    width: Tween,
+    /// 屏幕上当前的长度
+    drawn: i32,
+    /// 进度说明
+    message: String<32>,
+    /// 进度说明是否已显示在屏幕上
+    message_drawn: bool,
+}
+
+impl ProgressBar {
+    /// 只绘制与上一帧相差的部分：变长时补上强调色，变短时用背景色擦除
+    fn draw(&mut self, lcd: &mut St7789, colors: &Theme, background: Rgb565, now_ms: u64) {
+        let width = self.width.value(now_ms);
+        if width > self.drawn {
+            fill_progress(lcd, self.drawn, width, colors.accent);
+        } else {
+            fill_progress(lcd, width, self.drawn, background);
+        }
+        self.drawn = width;
+    }
+
+    fn is_animating(&self, now_ms: u64) -> bool {
+        self.drawn != self.width.target() || !self.width.is_finished(now_ms)
+    }
+}
+
+/// 正在显示的横幅和进度条
+struct Overlays {
+    banner: Option<Banner>,
+    progress: Option<ProgressBar>,
+    /// 动画的帧节拍和帧率统计
+    frames: FrameLimiter,
+}
+
+impl Overlays {
+    fn new(fps: u8) -> Self {
+        Overlays {
+            banner: None,
+            progress: None,
+            frames: FrameLimiter::new(fps as u32),
+        }
+    }
+
+    fn is_animating(&self, now_ms: u64) -> bool {
+        self.banner.is_some()
+            || self
+                .progress
+                .as_ref()
+                .is_some_and(|p| p.is_animating(now_ms))
+    }
+
+    /// 处理横幅和进度条命令
+    ///
+    /// # 返回
+    /// 隐藏了屏幕底部的进度说明时返回 true，调用方需要重绘屏幕以恢复下面的内容
+    fn apply(&mut self, command: Command, lcd: &mut St7789, background: Rgb565) -> bool {
+        let now_ms = Instant::now().as_millis();
+        match command {
+            Command::Banner(text) => {
+                let mut banner = Banner::new(text, now_ms);
+                // 替换正在显示的横幅时直接换上新内容，从底部滑入会留下旧横幅的上半部分
+                if self.banner.is_some() {
+                    banner.slide = Tween::at(0);
+                }
+                self.banner = Some(banner);
+            }
+            Command::Progress(Some((percent, message))) => {
+                let progress = self.progress.get_or_insert_with(|| {
+                    let mut width = Tween::new(0, 0, PROGRESS_MS, Easing::EaseOutQuad);
+                    width.start(now_ms);
+                    ProgressBar {
+                        width,
+                        drawn: 0,
+                        message: String::new(),
+                        message_drawn: false,
+                    }
+                });
+                progress.width.retarget(progress_width(percent), now_ms);
+                if progress.message != message {
+                    progress.message = message;
+                    progress.message_drawn = false;
+                }
+            }
+            Command::Progress(None) => {
+                let Some(progress) = self.progress.take() else {
+                    return false;
+                };
+                fill_progress(lcd, 0, progress.drawn, background);
+                return progress.message_drawn;
+            }
+            _ => return false,
+        }
+        self.frames.request(now_ms);
+        false
+    }
+
+    /// 屏幕清除后重新绘制完整的横幅和进度条
+    fn invalidate(&mut self) {
+        if let Some(banner) = &mut self.banner {
+            banner.drawn = None;
+        }
+        if let Some(progress) = &mut self.progress {
+            progress.drawn = 0;
+            progress.message_drawn = false;
+        }
+        if self.banner.is_some() || self.progress.is_some() {
+            self.frames.request(Instant::now().as_millis());
+        }
+    }
+
+    /// 等待下一帧，没有动画时一直等待
+    async fn frame(&self) {
+        match self.frames.next_frame() {
+            Some(at) => Timer::at(Instant::from_millis(at)).await,
+            None => core::future::pending().await,
+        }
+    }
+
+    /// 绘制一帧并安排下一帧
+    ///
+    /// # 返回
+    /// 横幅刚结束时返回 true，调用方需要重绘屏幕以恢复横幅下面的内容
+    fn draw(&mut self, lcd: &mut St7789, colors: &Theme, background: Rgb565) -> bool {
+        let now_ms = Instant::now().as_millis();
+        self.frames.begin(now_ms);
+        let mut cleared = false;
+        if let Some(banner) = &mut self.banner {
+            if banner.is_finished(now_ms) {
+                self.banner = None;
+                cleared = true;
+            } else {
+                banner.draw(lcd, colors, background, now_ms);
+            }
+        }
+        if let Some(progress) = &mut self.progress {
+            progress.draw(lcd, colors, background, now_ms);
+            if self.banner.is_none() && !progress.message_drawn {
+                draw_progress_message(lcd, &progress.message, Style::new(*colors, background));
+                progress.message_drawn = true;
+            }
+        }
+
+        let animating = self.is_animating(now_ms);
+        let finished_ms = Instant::now().as_millis();
+        let period = self.frames.period_ms() as u64;
+        if finished_ms - now_ms > period {
+            debug!(
+                "Render frame took {} ms, skipping frames",
+                finished_ms - now_ms
+            );
+        }
+        self.frames.finish(finished_ms, animating);
+        cleared
+    }
+}
+
+/// 渲染任务
+///
+/// 状态屏幕显示 [crate::screens] 中的页面，每秒刷新一次当前页面（仪表盘的运行时间和状态图标），
+/// 按键在页面之间切换。等待刷新期间处理 [command] 发来的命令，有动画时按帧绘制横幅和进度条。
+///
+/// # 参数
+/// * `lcd` - 已完成初始化的 LCD
+#[embassy_executor::task]
+pub async fn render_task(mut lcd: Lcd) {
+    let colors = theme::current();
+    let mut style = Style::new(colors, colors.background);
+    let mut screen = Screen::Status;
+    let mut pages = PageSet::new(style);
+    let mut nav: Navigator<Page, NAV_DEPTH> = Navigator::new(&Page::TABS);
+    nav.start(&mut pages);
+    let mut keys = input::subscribe();
+    if keys.is_none() {
+        warn!("Too many key subscribers, status pages cannot be switched");
+    }
+    RUNNING.store(true, Ordering::Relaxed);
+
+    // 刷新按固定节拍进行，不受每次绘制耗时和提前唤醒的影响；Ticker 不公开到期时间，
+    // 另记一份用于测量唤醒延迟
+    let mut monitor = jitter::Monitor::new("render", REFRESH_PERIOD);
+    let mut ticker = Ticker::every(REFRESH_PERIOD);
+    let mut deadline = Instant::now() + REFRESH_PERIOD;
+    let mut target_fps = settings::get().render_fps;
+    let mut overlays = Overlays::new(target_fps);
+    let mut fps_shown = false;
+    let mut panel = tuning::current();
+    loop {
+        // 背光被关闭（KEY1、定时任务或 Modbus）期间让控制器睡眠，背光重新打开后唤醒
+        let dark = !lcd.is_backlight_on();
+        if dark != lcd.is_asleep()
+            && let Err(err) = lcd.sleep(dark).await
+        {
+            warn!("Failed to switch LCD sleep mode: {}", err);
+        }
+
+        let tuned = tuning::current();
+        if tuned != panel {
+            if let Err(err) = tuning::apply(&mut lcd, &tuned) {
+                warn!("Failed to apply display tuning: {}", err);
+            }
+            panel = tuned;
+        }
+
+        let current = theme::current();
+        if current != style.colors {
+            style = Style::new(current, current.background);
+            pages.set_style(style);
+            redraw(&mut lcd, screen, style, &mut nav);
+            overlays.invalidate();
+        }
+
+        let fps = settings::get().render_fps;
+        if fps != target_fps {
+            overlays.frames.set_fps(fps as u32);
+            target_fps = fps;
+        }
+
+        // 关闭帧率统计后重绘，擦掉屏幕上的数字
+        let show_fps = fps_overlay();
+        if fps_shown && !show_fps {
+            redraw(&mut lcd, screen, style, &mut nav);
+            overlays.invalidate();
+        }
+        fps_shown = show_fps;
+
+        if screen == Screen::Status {
+            pages.prepare().await;
+            overlays.frames.begin(Instant::now().as_millis());
+            if let Err(err) = nav.render(&mut pages, lcd.draw()) {
+                warn!("Failed to draw {}: {}", nav.current(), err);
+            }
+            overlays.frames.record(Instant::now().as_millis());
+        }
+
+        let stats = overlays.frames.stats(Instant::now().as_millis());
+        critical_section::with(|cs| FRAME_STATS.borrow(cs).set(stats));
+        if show_fps {
+            draw_fps(&mut lcd, &stats, style);
+        }
+
+        // 收到命令或按键时提前结束本周期，执行后立即刷新页面；动画帧不影响刷新周期
+        let command = loop {
+            let key = next_key(&mut keys);
+            match select4(ticker.next(), COMMANDS.receive(), overlays.frame(), key).await {
+                Either4::First(()) => {
+                    monitor.record(Instant::now().saturating_duration_since(deadline));
+                    deadline += REFRESH_PERIOD;
+                    break None;
+                }
+                Either4::Second(command) => break Some(command),
+                Either4::Third(()) => {
+                    if overlays.draw(&mut lcd, &style.colors, style.background) {
+                        redraw(&mut lcd, screen, style, &mut nav);
+                        overlays.invalidate();
+                        break None;
+                    }
+                }
+                Either4::Fourth(_) if screen != Screen::Status => {}
+                Either4::Fourth(key) => {
+                    let page = nav.current();
+                    if let Err(err) = nav.handle(&mut pages, screens::event(key)) {
+                        warn!("Failed to open page: {}", err);
+                    }
+                    if nav.current() != page {
+                        overlays.invalidate();
+                    }
+                    // 仪表盘以外的页面用 KEY1/KEY2 滚动，暂停按键的默认功能
+                    input::set_captured(nav.current() != Page::Dashboard);
+                    break None;
+                }
+            }
+        };
+        let Some(command) = command else {
+            continue;
+        };
+        match command {
+            Command::FillColor(color) => {
+                style.background = color;
+                pages.set_style(style);
+                redraw(&mut lcd, screen, style, &mut nav);
+                overlays.invalidate();
+            }
+            Command::ShowScreen(next) => {
+                screen = next;
+                redraw(&mut lcd, screen, style, &mut nav);
+                overlays.invalidate();
+            }
+            command => {
+                if overlays.apply(command, &mut lcd, style.background) {
+                    redraw(&mut lcd, screen, style, &mut nav);
+                    overlays.invalidate();
+                }
+            }
+        }
+    }
+}
+
+/// 等待下一次按键，没有订阅时一直等待
+async fn next_key(keys: &mut Option<KeySubscriber>) -> Key {
+    match keys {
+        Some(keys) => keys.next_message_pure().await,
+        None => core::future::pending().await,
+    }
+}
+
+/// 在屏幕右上角显示帧率统计，带背景色，覆盖上一次的数字
+fn draw_fps(lcd: &mut St7789, stats: &FrameStats, style: Style) {
+    let mut text: String<16> = String::new();
+    let (fps, average) = (stats.fps.min(99), stats.average_ms.min(999));
+    write!(text, "{:>2}fps {:>3}ms", fps, average).ok();
+    let text_style = MonoTextStyleBuilder::new()
+        .font(&FONT_6X10)
+        .text_color(style.foreground())
+        .background_color(style.background)
+        .build();
+    Text::with_baseline(&text, FPS_POSITION, text_style, Baseline::Top)
+        .draw(lcd)
+        .ok();
+}
+
+/// 背景或屏幕改变后重绘
+///
+/// 空白屏幕直接清屏；状态屏幕在下一次刷新时完整重绘当前页面
+fn redraw(lcd: &mut St7789, screen: Screen, style: Style, nav: &mut Navigator<Page, NAV_DEPTH>) {
+    match screen {
+        Screen::Blank => {
+            if let Err(err) = lcd.fill_screen(style.background) {
+                warn!("Failed to clear LCD: {}", err);
+            }
+        }
+        Screen::Status => nav.invalidate(),
+    }
+}
@@ -0,0 +1,195 @@
+//! RS485 半双工串口
+//!
+//! RS485 收发器同一时刻只能发送或接收：发送前拉高 DE（驱动使能，RE 与之并联，
+//! 同时关闭接收），最后一个停止位移出后再拉低，恢复接收。[Rs485] 在每次发送时
+//! 自动完成这一切换，调用者只需使用 [Rs485::send] 和 [Rs485::receive]。
+//!
+//! 接收以帧为单位：收到第一个字节后，线路空闲超过帧间隔（默认 3.5 个字符时间，
+//! 与 Modbus RTU 一致）即认为一帧结束。
+//!
+//! UART 和引脚由调用者指定，扩展排针上的收发器用作 Modbus RTU 从站（见 [crate::modbus]）：
+//!
+//! ```ignore
+//! let de = Output::new(peripherals.GPIO16, Level::Low, OutputConfig::default());
+//! let mut bus = Rs485::new(peripherals.UART1, tx, rx, de, Rs485Config::DEFAULT)?;
+//! let len = bus.receive(&mut buf, Duration::from_secs(1)).await?;
+//! bus.send(&reply[..reply_len]).await?;
+//! ```
+
+use embassy_time::{Duration, with_timeout};
+use esp_hal::Async;
+use esp_hal::gpio::Output;
+use esp_hal::gpio::interconnect::{PeripheralInput, PeripheralOutput};
+use esp_hal::uart::{self, Config as UartConfig, Parity, StopBits, Uart};
+
+/// 波特率高于该值时帧间隔固定为 [MIN_FRAME_GAP]（Modbus RTU 规范）
+const FIXED_GAP_BAUDRATE: u32 = 19_200;
+
+/// 高波特率下的最小帧间隔
+const MIN_FRAME_GAP: Duration = Duration::from_micros(1750);
+
+/// RS485 错误
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Rs485Error {
+    /// UART 配置无效（例如波特率无法实现）
+    Config,
+    /// 发送出错
+    Transmit,
+    /// 接收出错（奇偶校验、帧格式或 FIFO 溢出）
+    Receive,
+    /// 等待第一个字节超时
+    Timeout,
+    /// 接收到的帧超过缓冲区大小
+    Overflow,
+}
+
+/// RS485 配置
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rs485Config {
+    /// 波特率
+    pub baudrate: u32,
+    /// 校验位
+    pub parity: Parity,
+    /// 停止位
+    pub stop_bits: StopBits,
+    /// 帧间隔，None 表示按波特率取 3.5 个字符时间
+    pub frame_gap: Option<Duration>,
+}
+
+impl Rs485Config {
+    /// 默认配置：9600 8N1
+    pub const DEFAULT: Rs485Config = Rs485Config {
+        baudrate: 9600,
+        parity: Parity::None,
+        stop_bits: StopBits::_1,
+        frame_gap: None,
+    };
+
+    /// 一个字符（起始位 + 8 个数据位 + 校验位 + 停止位）的传输时间（微秒）
+    fn char_time_us(&self) -> u64 {
+        let parity = if self.parity == Parity::None { 0 } else { 1 };
+        let stop = if self.stop_bits == StopBits::_1 { 1 } else { 2 };
+        let bits = 1 + 8 + parity + stop;
+        bits * 1_000_000 / self.baudrate.max(1) as u64
+    }
+
+    /// 实际使用的帧间隔
+    pub fn frame_gap(&self) -> Duration {
+        match self.frame_gap {
+            Some(gap) => gap,
+            None if self.baudrate > FIXED_GAP_BAUDRATE => MIN_FRAME_GAP,
+            None => Duration::from_micros(self.char_time_us() * 7 / 2),
+        }
+    }
+
+    fn uart_config(&self) -> UartConfig {
+        UartConfig::default()
+            .with_baudrate(self.baudrate)
+            .with_parity(self.parity)
+            .with_stop_bits(self.stop_bits)
+    }
+}
+
+/// RS485 半双工串口
+pub struct Rs485 {
+    uart: Uart<'static, Async>,
+    /// 驱动使能（DE/RE），高电平发送，低电平接收
+    de: Output<'static>,
+    config: Rs485Config,
+}
+
+impl Rs485 {
+    /// 创建 RS485 串口
+    ///
+    /// # 参数
+    /// * `uart` - UART 外设
+    /// * `tx`, `rx` - 收发器 DI、RO 的引脚
+    /// * `de` - 收发器 DE/RE 的输出引脚，应以低电平（接收）初始化
+    /// * `config` - 串口配置
+    pub fn new(
+        uart: impl uart::Instance + 'static,
+        tx: impl PeripheralOutput<'static>,
+        rx: impl PeripheralInput<'static>,
+        mut de: Output<'static>,
+        config: Rs485Config,
+    ) -> Result<Self, Rs485Error> {
+        let uart = Uart::new(uart, config.uart_config())
+            .map_err(|_| Rs485Error::Config)?
+            .with_tx(tx)
+            .with_rx(rx)
+            .into_async();
+        de.set_low();
+        Ok(Rs485 { uart, de, config })
+    }
+
+    /// 发送一帧
+    ///
+    /// 发送期间拉高 DE，等待最后一个字节完全移出后恢复接收
+    pub async fn send(&mut self, frame: &[u8]) -> Result<(), Rs485Error> {
+        self.discard_input();
+
+        self.de.set_high();
+        let result = self.write_all(frame).await;
+        // 出错时也要释放总线
+        self.de.set_low();
+        result
+    }
+
+    /// 写入所有数据并等待最后一个字节移出
+    async fn write_all(&mut self, mut data: &[u8]) -> Result<(), Rs485Error> {
+        while !data.is_empty() {
+            let written = self
+                .uart
+                .write_async(data)
+                .await
+                .map_err(|_| Rs485Error::Transmit)?;
+            data = &data[written..];
+        }
+        self.uart.flush_async().await.map_err(|_| Rs485Error::Transmit)
+    }
+
+    /// 接收一帧
+    ///
+    /// # 参数
+    /// * `buf` - 接收缓冲区
+    /// * `timeout` - 等待第一个字节的时间
+    ///
+    /// # 返回
+    /// 帧长度
+    pub async fn receive(
+        &mut self,
+        buf: &mut [u8],
+        timeout: Duration,
+    ) -> Result<usize, Rs485Error> {
+        let mut len = match with_timeout(timeout, self.uart.read_async(buf)).await {
+            Ok(Ok(len)) => len,
+            Ok(Err(_)) => return Err(Rs485Error::Receive),
+            Err(_) => return Err(Rs485Error::Timeout),
+        };
+
+        // 线路空闲超过帧间隔即认为帧结束
+        let gap = self.config.frame_gap();
+        loop {
+            if len == buf.len() {
+                // 缓冲区已满，还有数据说明帧过长
+                return match with_timeout(gap, self.uart.read_async(&mut [0u8])).await {
+                    Err(_) => Ok(len),
+                    Ok(_) => Err(Rs485Error::Overflow),
+                };
+            }
+            match with_timeout(gap, self.uart.read_async(&mut buf[len..])).await {
+                Ok(Ok(read)) => len += read,
+                Ok(Err(_)) => return Err(Rs485Error::Receive),
+                Err(_) => return Ok(len),
+            }
+        }
+    }
+
+    /// 丢弃接收 FIFO 中残留的数据（上一次应答的尾部或总线噪声）
+    fn discard_input(&mut self) {
+        let mut scratch = [0u8; 16];
+        while let Ok(read) = self.uart.read_buffered(&mut scratch)
+            && read > 0
+        {}
+    }
+}
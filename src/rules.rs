@@ -0,0 +1,299 @@
+//! 自动化规则
+//!
+//! 条件满足时执行动作的规则，运行时通过 HTTP 或命令行上传，新增规则不需要修改固件：
+//!
+//! ```text
+//! if bme280.temp > 30 and time >= 20:00 then beep, notify
+//! if not (bme280.humidity < 70) then relay 1 on
+//! if bme280.temp - thermostat.setpoint > 2 or time < 6:30 then backlight off
+//! ```
+//!
+//! - 条件中的名称是传感器读数（`<传感器>.<物理量>`，见 [crate::sensor]），`time` 是本地时间
+//!   从零点起的分钟数，`HH:MM` 写法的常数同样换算为分钟
+//! - 运算符的优先级从低到高为 `or`、`and`、`not`、比较（`>` `<` `>=` `<=` `==` `!=`）、
+//!   `+` `-`、`*` `/`、负号，括号改变优先级；比较成立为 1，不成立为 0，
+//!   非 0 的值视为成立（NaN 除外）
+//! - 动作与定时任务相同（见 [crate::scheduler::Action]），多个动作用 `,` 分隔；
+//!   `notify` 通过 webhook 发送当前的传感器读数（见 [crate::notifier]）
+//!
+//! 规则每 [PERIOD] 求值一次，条件从不成立变为成立时执行一次动作，保持成立期间不重复执行。
+//! 读数不存在或系统时间尚未校准时对应的值为 NaN，表示“未知”：算术和比较的结果仍是 NaN，
+//! `not` 未知仍是未知；`and` 有一侧不成立时不成立，`or` 有一侧成立时成立，其余情况为未知。
+//! 条件的结果为未知时视为不成立，因此 `not (bme280.humidity < 70)` 在没有读数时不会误触发。
+//! 启动或上传新规则后，已经成立的条件在第一次求值时执行一次动作。
+//!
+//! # 字节码
+//!
+//! 规则在设备上编译为栈式字节码，求值时不再解析文本，编译器和字节码格式见
+//! [automation::condition]。
+//!
+//! # 上传
+//!
+//! - `GET /rules` 查看，`PUT /rules` 整体替换（请求体每行一条规则），`DELETE /rules` 清除
+//! - 命令行 `rules` 查看规则和状态，`rules add <规则>` 追加一条，`rules del <n>` 删除第 n 条，
+//!   `rules clear` 清除
+//!
+//! 任何一条规则编译失败时整体拒绝，当前的规则不变。规则原文保存在 Flash 的独立扇区
+//! [RULES_OFFSET]，启动时重新编译。空行和以 `#` 开头的行被忽略，不保存。
+
+use crate::crash::CRASH_OFFSET;
+use crate::scheduler::Action;
+use crate::storage::{self, StorageError};
+use crate::{sensor, wallclock};
+use alloc::string::String;
+use alloc::vec::Vec;
+use automation::condition::{Condition, Fault};
+use core::cell::RefCell;
+use core::fmt::Write;
+use critical_section::Mutex;
+use defmt::{info, warn};
+use embassy_time::{Duration, Timer};
+
+/// 规则所在的 Flash 扇区，紧接崩溃记录
+pub const RULES_OFFSET: u32 = CRASH_OFFSET + storage::SECTOR_SIZE;
+
+/// 最多规则数量
+pub const MAX_RULES: usize = 16;
+
+/// 规则原文的最大长度（字节）
+pub const MAX_SOURCE_LEN: usize = 1024;
+
+/// 求值周期
+pub const PERIOD: Duration = Duration::from_secs(1);
+
+/// 一条规则最多的动作数量
+const MAX_ACTIONS: usize = 4;
+
+/// 规则编译错误
+///
+/// 值为第几条规则（从 1 开始，不计空行和注释）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum CompileError {
+    /// 语法错误，或者缺少 `if` / `then`
+    Syntax(usize),
+    /// 条件太复杂：词法单元、字节码、读数名称或栈深度超出上限
+    TooComplex(usize),
+    /// 动作无效或动作太多
+    Action(usize),
+    /// 规则超过 [MAX_RULES] 条或原文超过 [MAX_SOURCE_LEN] 字节
+    TooLong,
+}
+
+/// 修改规则的错误
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum RulesError {
+    /// 规则无效，当前的规则不变
+    Compile(CompileError),
+    /// 保存到 Flash 失败，新规则只在本次运行中有效
+    Storage(StorageError),
+}
+
+/// 一条编译好的规则
+struct Rule {
+    /// 规则原文
+    source: String,
+    condition: Condition,
+    actions: heapless::Vec<Action, MAX_ACTIONS>,
+    /// 上次求值时条件是否成立
+    active: bool,
+    /// 启动或上传以来执行动作的次数
+    fired: u32,
+}
+
+impl Rule {
+    /// 按当前的读数和时间求值条件
+    ///
+    /// # 参数
+    /// * `time` - 本地时间从零点起的分钟数，未校准时为 NaN
+    fn evaluate(&self, time: f32) -> bool {
+        let load = |name: &str| sensor::get(name).map_or(f32::NAN, |reading| reading.value as f32);
+        self.condition.is_met(load, time)
+    }
+}
+
+static RULES: Mutex<RefCell<Vec<Rule>>> = Mutex::new(RefCell::new(Vec::new()));
+
+/// 从 Flash 加载并编译规则，在启动时调用一次
+pub fn load() {
+    let mut buf = [0u8; MAX_SOURCE_LEN];
+    let source = match storage::read_blob(RULES_OFFSET, &mut buf) {
+        Ok(len) => core::str::from_utf8(&buf[..len]).unwrap_or(""),
+        Err(StorageError::Corrupted) => return,
+        Err(err) => {
+            warn!("Failed to read automation rules: {}", err);
+            return;
+        }
+    };
+    match compile(source) {
+        Ok(rules) => {
+            info!("Loaded {} automation rules", rules.len());
+            critical_section::with(|cs| *RULES.borrow_ref_mut(cs) = rules);
+        }
+        Err(err) => warn!("Saved automation rules are invalid: {}", err),
+    }
+}
+
+/// 当前的规则原文，每行一条
+pub fn source() -> String {
+    critical_section::with(|cs| {
+        let mut text = String::new();
+        for rule in RULES.borrow_ref(cs).iter() {
+            text.push_str(&rule.source);
+            text.push('\n');
+        }
+        text
+    })
+}
+
+/// 编译并替换全部规则，保存到 Flash
+///
+/// # 参数
+/// * `source` - 规则原文，每行一条，也可以用 `;` 分隔
+///
+/// # 返回
+/// 规则数量
+pub fn set(source: &str) -> Result<usize, RulesError> {
+    let rules = compile(source).map_err(RulesError::Compile)?;
+    let count = rules.len();
+    let mut text = String::new();
+    for rule in &rules {
+        text.push_str(&rule.source);
+        text.push('\n');
+    }
+    critical_section::with(|cs| *RULES.borrow_ref_mut(cs) = rules);
+    info!("Installed {} automation rules", count);
+
+    let saved = if text.is_empty() {
+        storage::erase_blob(RULES_OFFSET)
+    } else {
+        storage::write_blob(RULES_OFFSET, text.as_bytes())
+    };
+    saved.map_err(RulesError::Storage)?;
+    Ok(count)
+}
+
+/// 在最后追加一条规则
+///
+/// # 返回
+/// 规则数量
+pub fn add(rule: &str) -> Result<usize, RulesError> {
+    let mut text = source();
+    text.push_str(rule);
+    set(&text)
+}
+
+/// 删除第 `number` 条规则（从 1 开始）
+///
+/// # 返回
+/// 没有这条规则时返回 false
+pub fn remove(number: usize) -> Result<bool, RulesError> {
+    let text = source();
+    if number == 0 || number > text.lines().count() {
+        return Ok(false);
+    }
+    let rest: Vec<&str> = text
+        .lines()
+        .enumerate()
+        .filter(|&(index, _)| index + 1 != number)
+        .map(|(_, line)| line)
+        .collect();
+    set(&rest.join("\n")).map(|_| true)
+}
+
+/// 格式化规则列表：编号、条件当前是否成立、执行次数和原文，每条一行
+pub fn format_report() -> String {
+    critical_section::with(|cs| {
+        let mut text = String::new();
+        for (index, rule) in RULES.borrow_ref(cs).iter().enumerate() {
+            let state = if rule.active { "on" } else { "off" };
+            let (number, fired, source) = (index + 1, rule.fired, &rule.source);
+            writeln!(text, "{number:>2} {state:<3} {fired:>5}x  {source}").ok();
+        }
+        text
+    })
+}
+
+/// 编译全部规则
+fn compile(source: &str) -> Result<Vec<Rule>, CompileError> {
+    let mut rules = Vec::new();
+    let mut len = 0;
+    let lines = source
+        .split(['\n', ';'])
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'));
+    for line in lines {
+        len += line.len() + 1;
+        if rules.len() == MAX_RULES || len > MAX_SOURCE_LEN {
+            return Err(CompileError::TooLong);
+        }
+        rules.push(compile_rule(line, rules.len() + 1)?);
+    }
+    Ok(rules)
+}
+
+/// 编译一条规则 `if <条件> then <动作>[, <动作>...]`
+///
+/// # 参数
+/// * `line` - 规则原文
+/// * `number` - 规则编号，用于错误信息
+fn compile_rule(line: &str, number: usize) -> Result<Rule, CompileError> {
+    let syntax = CompileError::Syntax(number);
+    let rest = line.strip_prefix("if ").ok_or(syntax)?;
+    let (condition, action_list) = rest.split_once(" then ").ok_or(syntax)?;
+
+    let mut actions = heapless::Vec::new();
+    for part in action_list.split(',') {
+        let words: heapless::Vec<&str, 4> = part.split_whitespace().take(4).collect();
+        let action = Action::parse(&words).ok_or(CompileError::Action(number))?;
+        actions
+            .push(action)
+            .map_err(|_| CompileError::Action(number))?;
+    }
+
+    let condition =
+        Condition::compile(condition, sensor::is_valid_name).map_err(|fault| match fault {
+            Fault::Syntax => CompileError::Syntax(number),
+            Fault::TooComplex => CompileError::TooComplex(number),
+        })?;
+    Ok(Rule {
+        source: String::from(line),
+        condition,
+        actions,
+        active: false,
+        fired: 0,
+    })
+}
+
+/// 规则任务
+///
+/// 每 [PERIOD] 求值一次所有规则，条件从不成立变为成立时依次执行动作
+#[embassy_executor::task]
+pub async fn rules_task() {
+    loop {
+        Timer::after(PERIOD).await;
+        let time = wallclock::local_now().map_or(f32::NAN, |(t, _)| {
+            (t.hour as u32 * 60 + t.minute as u32) as f32
+        });
+        let fired = critical_section::with(|cs| {
+            let mut fired: heapless::Vec<(usize, Action), { MAX_RULES * MAX_ACTIONS }> =
+                heapless::Vec::new();
+            for (index, rule) in RULES.borrow_ref_mut(cs).iter_mut().enumerate() {
+                let active = rule.evaluate(time);
+                if active && !rule.active {
+                    rule.fired += 1;
+                    for &action in &rule.actions {
+                        fired.push((index + 1, action)).ok();
+                    }
+                }
+                rule.active = active;
+            }
+            fired
+        });
+        for (number, action) in fired {
+            info!("Rule {} fired: {}", number, action);
+            let mut event: heapless::String<32> = heapless::String::new();
+            write!(event, "Rule {number} triggered").ok();
+            action.run(&event).await;
+        }
+    }
+}
@@ -0,0 +1,94 @@
+//! Azure IoT Hub 的 SAS 令牌
+//!
+//! 设置了设备的对称密钥（命令行 `mqtt sas <key>`）时，MQTT 客户端（见 [crate::mqtt]）按 IoT Hub
+//! 的格式生成连接凭据，代替设置中的用户名和密码：
+//!
+//! - 客户端标识：设备 ID，即主机名（见 [crate::net::hostname]），应与 IoT Hub 中注册的设备 ID 相同
+//! - 用户名：`<代理主机>/<设备 ID>/?api-version=2021-04-12`
+//! - 密码：`SharedAccessSignature sr=<资源>&sig=<签名>&se=<过期时间>`，资源为
+//!   `<代理主机>/devices/<设备 ID>`，签名是用 Base64 解码后的密钥对
+//!   `<URL 编码的资源>\n<过期时间>` 计算的 HMAC-SHA256
+//!
+//! 令牌在每次连接时生成，有效期 [LIFETIME_SECS] 秒。过期时代理断开连接，重连时生成新的令牌。
+//! 生成令牌需要系统时间，SNTP 校时之前不连接。
+//!
+//! 限制：IoT Hub 本身只接受 TLS 连接（8883 端口），固件没有 TLS 协议栈，只能经由局域网中
+//! 终结 TLS 的网关连接；同样的原因，不支持 X.509 客户端证书（双向 TLS）认证。
+
+use crate::wallclock;
+use alloc::string::String;
+use core::fmt::Write;
+use crypto::hmac;
+use defmt::warn;
+use proto::base64;
+
+/// 令牌的有效期
+pub const LIFETIME_SECS: u64 = 24 * 60 * 60;
+
+/// IoT Hub 的 MQTT 接口版本
+const API_VERSION: &str = "2021-04-12";
+
+/// 解码后的密钥最大长度
+const MAX_KEY_LEN: usize = 64;
+
+/// 生成的连接凭据
+pub struct Credentials {
+    pub user: String,
+    pub password: String,
+}
+
+/// 密钥是否是有效的 Base64
+pub fn is_valid_key(key: &str) -> bool {
+    base64::decode(key, &mut [0u8; MAX_KEY_LEN]).is_some_and(|len| len > 0)
+}
+
+/// 生成连接凭据
+///
+/// # 参数
+/// * `host` - 代理主机名，即 `<hub>.azure-devices.net`
+/// * `device` - 设备 ID
+/// * `key` - Base64 编码的设备密钥
+///
+/// # 返回
+/// 密钥无效或系统时间尚未校准时返回 None
+pub fn credentials(host: &str, device: &str, key: &str) -> Option<Credentials> {
+    let mut secret = [0u8; MAX_KEY_LEN];
+    let Some(len) = base64::decode(key, &mut secret) else {
+        warn!("SAS key is not valid Base64");
+        return None;
+    };
+    let Some(now) = wallclock::now() else {
+        warn!("SAS token needs the system time, waiting for SNTP");
+        return None;
+    };
+    let expiry = now + LIFETIME_SECS;
+
+    let mut resource = String::new();
+    url_encode(&mut resource, host);
+    resource.push_str("%2Fdevices%2F");
+    url_encode(&mut resource, device);
+    let mut to_sign = resource.clone();
+    write!(to_sign, "\n{expiry}").ok();
+    let mac = hmac::sha256(&secret[..len], to_sign.as_bytes());
+    let mut signature = String::new();
+    base64::encode(&mac, &mut signature).ok();
+
+    let mut password = String::new();
+    write!(password, "SharedAccessSignature sr={resource}&sig=").ok();
+    url_encode(&mut password, &signature);
+    write!(password, "&se={expiry}").ok();
+    let mut user = String::new();
+    write!(user, "{host}/{device}/?api-version={API_VERSION}").ok();
+    Some(Credentials { user, password })
+}
+
+/// URL 编码：保留字母、数字和 `-_.~`，其余字节编码为 `%XX`
+fn url_encode(out: &mut String, text: &str) {
+    for byte in text.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-_.~".contains(&byte) {
+            out.push(byte as char);
+        } else {
+            write!(out, "%{byte:02X}").ok();
+        }
+    }
+}
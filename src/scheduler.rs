@@ -0,0 +1,181 @@
+//! 定时任务
+//!
+//! 按类似 cron 的规则在本地时间（系统时间加设置中的 `utc_offset`）的指定时刻执行动作。
+//! 规则保存在设置中，多条规则用 `;` 分隔，命令行 `schedule` 查看和修改，
+//! 也可以把规则发布到 MQTT 的 `cmd/schedule` 主题（见 [crate::mqtt]），都立即生效：
+//!
+//! ```text
+//! <分> <时> <日> <月> <星期> <动作>
+//! 0 22 * * * backlight off; 0 7 * * * backlight on; */5 * * * * publish
+//! 0 6 * * * relay 1 15
+//! ```
+//!
+//! 每个时间字段支持 `*`、数字、范围 `a-b`、步长 `*/n` 或 `a-b/n`，以及用 `,` 分隔的列表。
+//! 星期 0 和 7 都表示星期日。与 cron 相同，日和星期都不是 `*` 时，满足其一即可。
+//! 时间字段的解析和匹配见 [automation::cron]。
+//!
+//! 动作见 [Action]，自动化规则（见 [crate::rules]）使用同样的动作。
+//! 系统时间尚未校准（NTP、GPS 或手动设置）时不执行任何规则。
+
+use crate::relay::{self, OUTPUTS, Switch};
+use crate::system::{self, RebootReason};
+use crate::wallclock;
+use crate::{buzzer, mqtt, notifier, settings, xl9555};
+use automation::cron::Cron;
+use defmt::{info, warn};
+use embassy_time::{Duration, Timer};
+use heapless::Vec;
+
+/// 最多规则数量
+pub const MAX_RULES: usize = 8;
+
+/// 系统时间未校准时重新检查的间隔
+const UNSYNCED_RETRY: Duration = Duration::from_secs(10);
+
+/// `beep` 动作的鸣响时长（毫秒）
+const BEEP_MS: u16 = 200;
+
+/// 规则无效
+///
+/// 值为第几条规则（从 1 开始）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct InvalidRule(pub usize);
+
+/// 动作
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Action {
+    /// `backlight on`：打开 LCD 背光
+    BacklightOn,
+    /// `backlight off`：关闭 LCD 背光
+    BacklightOff,
+    /// `beep`：蜂鸣器短鸣
+    Beep,
+    /// `notify`：通过 webhook 发送当前的传感器读数（见 [crate::notifier]）
+    Notify,
+    /// `publish`：把当前的传感器读数发布到 MQTT（见 [crate::mqtt::publish_telemetry]）
+    Publish,
+    /// `reboot`：重启设备
+    Reboot,
+    /// `relay <n> on|off|<分钟>`：开关继电器输出（见 [crate::relay]），输出编号从 1 开始
+    Relay { output: u8, switch: Switch },
+}
+
+impl Action {
+    /// 从规则中的动作部分解析
+    ///
+    /// # 参数
+    /// * `words` - 按空白拆分的动作
+    pub fn parse(words: &[&str]) -> Option<Action> {
+        match words {
+            ["backlight", "on"] => Some(Action::BacklightOn),
+            ["backlight", "off"] => Some(Action::BacklightOff),
+            ["beep"] => Some(Action::Beep),
+            ["notify"] => Some(Action::Notify),
+            ["publish"] => Some(Action::Publish),
+            ["reboot"] => Some(Action::Reboot),
+            ["relay", output, switch] => {
+                let output = output.parse().ok();
+                let output = output.filter(|n| (1..=OUTPUTS as u8).contains(n))?;
+                Some(Action::Relay {
+                    output,
+                    switch: Switch::parse(switch)?,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// 执行动作
+    ///
+    /// # 参数
+    /// * `event` - `notify` 动作发送的事件描述
+    pub async fn run(self, event: &str) {
+        match self {
+            Action::BacklightOn | Action::BacklightOff => {
+                let on = self == Action::BacklightOn;
+                if let Err(err) = xl9555::set_lcd_backlight(on).await {
+                    warn!("Scheduled backlight change failed: {}", err);
+                }
+            }
+            Action::Beep => buzzer::chirp(BEEP_MS),
+            Action::Notify => notifier::notify(event),
+            Action::Publish => {
+                if !mqtt::publish_telemetry().await {
+                    warn!("Telemetry dropped, MQTT is not connected");
+                }
+            }
+            Action::Reboot => system::reboot(RebootReason::Scheduled).await,
+            Action::Relay { output, switch } => {
+                relay::command(output as usize, switch);
+            }
+        }
+    }
+}
+
+/// 一条规则
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rule {
+    pub cron: Cron,
+    pub action: Action,
+}
+
+/// 解析设置中的规则
+///
+/// # 参数
+/// * `text` - 用 `;` 分隔的规则，空白规则被忽略
+///
+/// # 返回
+/// 第一条无效的规则，规则超过 [MAX_RULES] 条时也视为无效
+pub fn parse(text: &str) -> Result<Vec<Rule, MAX_RULES>, InvalidRule> {
+    let mut rules = Vec::new();
+    for (index, rule) in text.split(';').enumerate() {
+        let words: Vec<&str, 9> = rule.split_whitespace().take(9).collect();
+        if words.is_empty() {
+            continue;
+        }
+        let invalid = InvalidRule(index + 1);
+        // 五个时间字段加一到三个词的动作
+        if !(6..=8).contains(&words.len()) {
+            return Err(invalid);
+        }
+        let cron = Cron::parse(&words[..5]).ok_or(invalid)?;
+        let action = Action::parse(&words[5..]).ok_or(invalid)?;
+        rules.push(Rule { cron, action }).map_err(|_| invalid)?;
+    }
+    Ok(rules)
+}
+
+/// 定时任务
+///
+/// 每分钟开始时按当前设置中的规则检查一次，同一分钟内不会重复执行
+#[embassy_executor::task]
+pub async fn scheduler_task() {
+    let mut last_minute = None;
+    loop {
+        let Some((t, weekday)) = wallclock::local_now() else {
+            Timer::after(UNSYNCED_RETRY).await;
+            continue;
+        };
+        let minute = t.to_unix().map(|secs| secs / 60);
+        if minute != last_minute {
+            last_minute = minute;
+            let rules = match parse(&settings::get().schedule) {
+                Ok(rules) => rules,
+                Err(err) => {
+                    warn!("Invalid schedule: {}", err);
+                    Vec::new()
+                }
+            };
+            let matches = |rule: &&Rule| {
+                rule.cron
+                    .matches(t.minute, t.hour, t.day, t.month, weekday as u8)
+            };
+            for rule in rules.iter().filter(matches) {
+                info!("Scheduled action: {}", rule.action);
+                rule.action.run("Scheduled report").await;
+            }
+        }
+        // 等到下一分钟开始
+        Timer::after_secs(60 - t.second as u64).await;
+    }
+}
@@ -0,0 +1,904 @@
+//! 状态屏幕的页面
+//!
+//! 渲染任务（[crate::render]）用 [ui::screens::Navigator] 在以下标签页之间切换：
+//!
+//! - [Page::Dashboard] 仪表盘：标题、运行时间、WiFi 信号和 TF 卡图标
+//! - [Page::Settings] 设置：应用模式、语言、配色、WiFi 名称和设备标识（见 [crate::device]），
+//!   只读，修改通过命令行
+//! - [Page::Files] 文件：TF 卡根目录的文件列表，关闭 `sd` feature 时总是显示没有卡
+//! - [Page::Network] 网络：各连接启动以来收发的字节数，按总量排序（见 [crate::netstats]）
+//! - [Page::Peripherals] 外设：各外设的总线、地址或引脚和状态（见 [crate::registry]），
+//!   最近一次错误屏幕上放不下，用命令行 `peripherals list` 查看
+//! - [Page::Services] 服务：可以在运行中启停的服务及其状态（见 [crate::service]），
+//!   KEY1 选择服务，KEY2 启动或停止
+//!
+//! 设置页面按 KEY2 打开 [Page::Calibration] 屏幕校准子页面：灰阶和三原色渐变、
+//! 近黑和近白的色块以及棋盘格，KEY1 选择参数，KEY2 调整，调整立即生效（见 [crate::tuning]），
+//! 离开页面时保存。
+//!
+//! 按键（见 [event]）：KEY0 下一页，KEY3 返回仪表盘，KEY1/KEY2 上下滚动列表（服务页面除外）。
+//! 仪表盘上不独占按键，KEY1 开关背光、KEY2 切换背景颜色的默认功能保持不变。
+//!
+//! 状态屏幕的字体和状态栏图标可以由 TF 卡资源包替换（见 [crate::assets]）。
+//!
+//! 板上没有触摸屏，滑动手势由 KEY0 模拟；也没有摄像头驱动，暂无摄像头预览页面。
+
+use crate::i18n::{self, Msg};
+use crate::input::Key;
+use crate::netstats;
+use crate::profile;
+use crate::registry::{self, Peripheral};
+#[cfg(feature = "sd")]
+use crate::sdcard::{self, SdError};
+use crate::service::{self, Service};
+use crate::settings::Settings;
+use crate::st7789::St7789;
+use crate::theme::Mode;
+use crate::tuning::{CONTRAST_MAX, Curve};
+use crate::{assets, device, sensor, settings, wifi};
+use alloc::boxed::Box;
+use core::fmt::Write;
+use defmt::{info, warn};
+use embassy_time::Instant;
+use embedded_graphics::mono_font::ascii::FONT_6X10;
+use embedded_graphics::mono_font::{MonoTextStyle, MonoTextStyleBuilder};
+use embedded_graphics::pixelcolor::Rgb565;
+use embedded_graphics::pixelcolor::raw::RawU16;
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::Rectangle;
+use embedded_graphics::text::Text;
+use esp_hal::spi::Error as SpiError;
+use heapless::{String, Vec};
+use ui::icon::{self, Icon};
+use ui::screens::{Event, Pages, Response, Screen};
+use ui::text::{Align, TextBox};
+use ui::theme::Theme;
+
+/// 标签页之上最多打开的子页面层数
+pub const NAV_DEPTH: usize = 2;
+
+/// 标题和状态行的位置
+const TITLE_POSITION: Point = Point::new(10, 30);
+const STATUS_POSITION: Point = Point::new(10, 60);
+
+/// WiFi 信号和 TF 卡图标的左上角（标题行右侧）
+const WIFI_ICON_POSITION: Point = Point::new(272, 14);
+const SD_ICON_POSITION: Point = Point::new(296, 14);
+
+/// WiFi 信号格数的门限（dBm），由强到弱依次对应 4 到 1 格
+const WIFI_BARS_DBM: [f64; 4] = [-55.0, -65.0, -75.0, -85.0];
+
+/// 列表第一行的基线和行距
+const LIST_TOP: i32 = 64;
+const LIST_ROW_HEIGHT: i32 = 22;
+
+/// 列表一屏显示的行数和每行的字符数（文件列表不足时用空格补齐，覆盖上一次的内容）
+const LIST_ROWS: usize = 7;
+const LIST_WIDTH: usize = 30;
+
+/// 按键提示行的位置
+const HINT_POSITION: Point = Point::new(10, 230);
+
+/// 文件列表最多记录的文件数
+const MAX_FILES: usize = 64;
+
+/// 校准页面的渐变条：左边距、每级的宽度、顶边、高度和间距，每条 32 级
+const RAMP_LEFT: i32 = 32;
+const RAMP_STEP_WIDTH: u32 = 8;
+const RAMP_TOP: i32 = 40;
+const RAMP_HEIGHT: u32 = 10;
+const RAMP_GAP: i32 = 2;
+
+/// 测试图案的顶边和高度
+const PATTERN_TOP: u16 = 94;
+const PATTERN_HEIGHT: u16 = 36;
+
+/// 近黑和近白色块的灰阶（5 位）和宽度
+const PATCH_LEVELS: [u8; 8] = [0, 1, 2, 3, 28, 29, 30, 31];
+const PATCH_WIDTH: u32 = 16;
+
+/// 与棋盘格对照的灰块：Gamma 2.2 时黑白各半的棋盘格远看与 23/31 灰阶亮度相同
+const MIDTONE_LEFT: i32 = 176;
+const MIDTONE_WIDTH: u32 = 48;
+const MIDTONE_LEVEL: u8 = 23;
+
+/// 棋盘格的左边和宽度
+const CHECKER_LEFT: u16 = 224;
+const CHECKER_WIDTH: u16 = 64;
+
+/// 校准参数第一行的基线
+const KNOB_TOP: i32 = 156;
+
+/// 每次调整对比度的步长（VRHS 的一级约为 0.05V）
+const CONTRAST_STEP: u8 = 2;
+
+/// 页面
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Page {
+    /// 仪表盘
+    Dashboard,
+    /// 设置
+    Settings,
+    /// 文件
+    Files,
+    /// 网络流量
+    Network,
+    /// 外设诊断
+    Peripherals,
+    /// 服务启停
+    Services,
+    /// 屏幕校准，从设置页面打开
+    Calibration,
+}
+
+impl Page {
+    /// 标签页，KEY0 按此顺序切换
+    pub const TABS: [Page; 6] = [
+        Page::Dashboard,
+        Page::Settings,
+        Page::Files,
+        Page::Network,
+        Page::Peripherals,
+        Page::Services,
+    ];
+}
+
+/// 把按键转换为页面事件
+pub fn event(key: Key) -> Event {
+    match key {
+        Key::Key0 => Event::SwipeLeft,
+        Key::Key1 => Event::Up,
+        Key::Key2 => Event::Down,
+        Key::Key3 => Event::Back,
+    }
+}
+
+/// 页面使用的颜色
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Style {
+    pub colors: Theme,
+    /// 屏幕背景，KEY2 可以换成 [crate::render::PALETTE] 中的深色
+    pub background: Rgb565,
+}
+
+impl Style {
+    pub fn new(colors: Theme, background: Rgb565) -> Self {
+        Style { colors, background }
+    }
+
+    /// 文字和图标的颜色
+    ///
+    /// 使用配色的背景时为配色的正文颜色，KEY2 选择的深色背景上为白色
+    pub fn foreground(&self) -> Rgb565 {
+        if self.background == self.colors.background {
+            self.colors.foreground
+        } else {
+            Rgb565::WHITE
+        }
+    }
+
+    /// 文字样式，背景与屏幕背景相同，重绘时覆盖旧内容
+    pub fn text(&self) -> MonoTextStyle<'static, Rgb565> {
+        MonoTextStyleBuilder::new()
+            .font(assets::font())
+            .text_color(self.foreground())
+            .background_color(self.background)
+            .build()
+    }
+
+    /// 标题样式，使用强调色
+    fn title(&self) -> MonoTextStyle<'static, Rgb565> {
+        let color = if self.background == self.colors.background {
+            self.colors.accent
+        } else {
+            Rgb565::WHITE
+        };
+        MonoTextStyleBuilder::new()
+            .font(assets::font())
+            .text_color(color)
+            .background_color(self.background)
+            .build()
+    }
+}
+
+/// 清屏并绘制页面标题和按键提示
+fn draw_frame(lcd: &mut St7789, style: &Style, title: &str, hint: Msg) -> Result<(), SpiError> {
+    lcd.fill_screen(style.background)?;
+    Text::new(title, TITLE_POSITION, style.title()).draw(lcd)?;
+    let hint_style = MonoTextStyleBuilder::new()
+        .font(&FONT_6X10)
+        .text_color(style.colors.muted)
+        .build();
+    Text::new(i18n::lcd_small(hint), HINT_POSITION, hint_style).draw(lcd)?;
+    Ok(())
+}
+
+/// 列表第 `row` 行的基线位置
+fn list_position(row: usize) -> Point {
+    Point::new(10, LIST_TOP + row as i32 * LIST_ROW_HEIGHT)
+}
+
+/// 从 `position`（基线）开始绘制列表行的值，超出列表宽度的部分截断为省略号
+fn draw_list_value(
+    lcd: &mut St7789,
+    position: Point,
+    value: &str,
+    style: MonoTextStyle<'static, Rgb565>,
+) -> Result<(), SpiError> {
+    let font = style.font;
+    let right = list_position(0).x + (LIST_WIDTH as u32 * font.character_size.width) as i32;
+    let origin = Point::new(position.x, position.y - font.baseline as i32);
+    let size = Size::new(
+        (right - position.x).max(0) as u32,
+        font.character_size.height,
+    );
+    TextBox::new(Rectangle::new(origin, size), style, Align::Left).draw_line(lcd, value)?;
+    Ok(())
+}
+
+/// 仪表盘
+struct Dashboard {
+    style: Style,
+    line: String<32>,
+}
+
+impl Screen<Page, St7789> for Dashboard {
+    fn render(&mut self, lcd: &mut St7789, full: bool) -> Result<(), SpiError> {
+        let style = self.style.text();
+        if full {
+            lcd.fill_screen(self.style.background)?;
+            Text::new("ESP32-S3", TITLE_POSITION, style).draw(lcd)?;
+        }
+
+        let secs = Instant::now().as_secs();
+        self.line.clear();
+        let (hours, minutes, seconds) = (secs / 3600, secs / 60 % 60, secs % 60);
+        let label = i18n::lcd(Msg::Uptime);
+        write!(
+            self.line,
+            "{} {:02}:{:02}:{:02}",
+            label, hours, minutes, seconds
+        )
+        .ok();
+        Text::new(&self.line, STATUS_POSITION, style).draw(lcd)?;
+
+        // 已连接 WiFi 时显示信号强度，已挂载 TF 卡时显示卡片图标，断开或拔出后清除
+        #[cfg(feature = "sd")]
+        let sd = sdcard::is_mounted().then(|| assets::icon("sd", icon::SD));
+        #[cfg(not(feature = "sd"))]
+        let sd = None;
+        draw_status_icon(lcd, WIFI_ICON_POSITION, wifi_icon(), &self.style)?;
+        draw_status_icon(lcd, SD_ICON_POSITION, sd, &self.style)
+    }
+}
+
+/// 按 `wifi.rssi` 读数选择信号图标，未连接时为 None
+fn wifi_icon() -> Option<Icon> {
+    if !wifi::is_connected() {
+        return None;
+    }
+    let rssi = sensor::get("wifi.rssi").map_or(f64::MIN, |reading| reading.value);
+    let bars = WIFI_BARS_DBM.iter().filter(|&&dbm| rssi >= dbm).count();
+    Some(assets::signal_icon(bars as u8))
+}
+
+/// 绘制状态图标，None 时用背景色清除图标区域
+fn draw_status_icon(
+    lcd: &mut St7789,
+    origin: Point,
+    icon: Option<Icon>,
+    style: &Style,
+) -> Result<(), SpiError> {
+    match icon {
+        Some(icon) => icon.draw_opaque(lcd, origin, style.foreground(), style.background),
+        None => {
+            let area = Rectangle::new(origin, Size::new(icon::SIZE, icon::SIZE));
+            lcd.fill_solid(&area, style.background)
+        }
+    }
+}
+
+/// 设置页面
+struct SettingsPage {
+    style: Style,
+}
+
+impl Screen<Page, St7789> for SettingsPage {
+    fn on_event(&mut self, event: Event) -> Response<Page> {
+        match event {
+            Event::Down => Response::Push(Page::Calibration),
+            _ => Response::Ignored,
+        }
+    }
+
+    fn render(&mut self, lcd: &mut St7789, full: bool) -> Result<(), SpiError> {
+        if !full {
+            return Ok(());
+        }
+        draw_frame(
+            lcd,
+            &self.style,
+            i18n::lcd(Msg::PageSettings),
+            Msg::PagesHint,
+        )?;
+        let s = settings::get();
+        // 保存了多个网络时显示首选的一个
+        let ssid = wifi::profiles().into_iter().next().map(|p| p.ssid);
+        let identity = device::identity();
+        let rows = [
+            (Msg::SettingsProfile, profile::current().name()),
+            (Msg::SettingsLanguage, i18n::current().name()),
+            (Msg::SettingsTheme, Mode::from_u8(s.theme).name()),
+            (Msg::SettingsWifi, ssid.as_deref().unwrap_or("-")),
+            (Msg::SettingsDevice, identity.name.as_str()),
+            (Msg::SettingsId, identity.id.as_str()),
+        ];
+        let style = self.style.text();
+        let mut line: String<32> = String::new();
+        for (row, (label, value)) in rows.into_iter().enumerate() {
+            line.clear();
+            write!(line, "{:<9} ", i18n::lcd(label)).ok();
+            let next = Text::new(&line, list_position(row), style).draw(lcd)?;
+            // WiFi 名称和设备名称最长 32 个字符，超出列表宽度时截断
+            draw_list_value(lcd, next, value, style)?;
+        }
+        // 列表只有 LIST_ROWS 行，校准提示紧接在最后一项之后，不再空一行
+        let position = list_position(rows.len());
+        Text::new(i18n::lcd(Msg::SettingsCalibrate), position, style).draw(lcd)?;
+        Ok(())
+    }
+}
+
+/// 5 位灰阶对应的颜色
+fn gray(level: u8) -> Rgb565 {
+    Rgb565::new(level, level << 1 | level >> 4, level)
+}
+
+/// 校准页面的渐变条：灰阶和红、绿、蓝，参数为 5 位亮度
+const RAMPS: [fn(u8) -> Rgb565; 4] = [
+    gray,
+    |level| Rgb565::new(level, 0, 0),
+    |level| Rgb565::new(0, level << 1 | level >> 4, 0),
+    |level| Rgb565::new(0, 0, level),
+];
+
+/// 校准页面可调整的参数
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Knob {
+    Curve,
+    Contrast,
+    Inversion,
+}
+
+impl Knob {
+    /// 所有参数，按显示顺序排列
+    const ALL: [Knob; 3] = [Knob::Curve, Knob::Contrast, Knob::Inversion];
+
+    fn label(self) -> Msg {
+        match self {
+            Knob::Curve => Msg::CalibrationGamma,
+            Knob::Contrast => Msg::CalibrationContrast,
+            Knob::Inversion => Msg::CalibrationInvert,
+        }
+    }
+
+    /// 把参数调整为下一个取值，到头后回到最小值
+    fn step(self, s: &mut Settings) {
+        match self {
+            Knob::Curve => {
+                let next = Curve::from_u8(s.lcd_curve).to_u8() as usize + 1;
+                s.lcd_curve = Curve::ALL[next % Curve::ALL.len()].to_u8();
+            }
+            Knob::Contrast => {
+                let contrast = s.lcd_contrast.min(CONTRAST_MAX) + CONTRAST_STEP;
+                s.lcd_contrast = contrast % (CONTRAST_MAX + 1);
+            }
+            Knob::Inversion => s.lcd_inverted = !s.lcd_inverted,
+        }
+    }
+
+    /// 输出参数的当前取值
+    fn write_value(self, s: &Settings, out: &mut String<32>) {
+        match self {
+            Knob::Curve => write!(out, "{}", Curve::from_u8(s.lcd_curve).name()),
+            Knob::Contrast => write!(out, "{}", s.lcd_contrast),
+            Knob::Inversion => write!(out, "{}", if s.lcd_inverted { "on" } else { "off" }),
+        }
+        .ok();
+    }
+}
+
+/// 屏幕校准页面
+///
+/// 渐变和图案只在进入页面时绘制一次，调整参数后由面板直接改变显示效果，只需重绘参数行
+struct CalibrationPage {
+    style: Style,
+    /// 选中的参数在 [Knob::ALL] 中的下标
+    selected: usize,
+    /// 参数改变后需要重绘参数行
+    dirty: bool,
+    /// 有尚未保存的调整
+    changed: bool,
+}
+
+impl CalibrationPage {
+    fn draw_ramps(lcd: &mut St7789) -> Result<(), SpiError> {
+        let size = Size::new(RAMP_STEP_WIDTH, RAMP_HEIGHT);
+        for (row, ramp) in RAMPS.iter().enumerate() {
+            let top = RAMP_TOP + row as i32 * (RAMP_HEIGHT as i32 + RAMP_GAP);
+            for level in 0..32u8 {
+                let left = RAMP_LEFT + level as i32 * RAMP_STEP_WIDTH as i32;
+                lcd.fill_solid(&Rectangle::new(Point::new(left, top), size), ramp(level))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn draw_patterns(lcd: &mut St7789) -> Result<(), SpiError> {
+        // 近黑和近白的色块：对比度或 Gamma 不合适时相邻的色块难以分辨
+        let top = PATTERN_TOP as i32;
+        let size = Size::new(PATCH_WIDTH, PATTERN_HEIGHT as u32);
+        for (i, level) in PATCH_LEVELS.into_iter().enumerate() {
+            let origin = Point::new(RAMP_LEFT + i as i32 * PATCH_WIDTH as i32, top);
+            lcd.fill_solid(&Rectangle::new(origin, size), gray(level))?;
+        }
+
+        let size = Size::new(MIDTONE_WIDTH, PATTERN_HEIGHT as u32);
+        let area = Rectangle::new(Point::new(MIDTONE_LEFT, top), size);
+        lcd.fill_solid(&area, gray(MIDTONE_LEVEL))?;
+
+        // 逐像素黑白相间的棋盘格，预先生成奇偶两种行，逐行写入
+        let mut rows = [[0u8; CHECKER_WIDTH as usize * 2]; 2];
+        for (parity, row) in rows.iter_mut().enumerate() {
+            for (x, pixel) in row.chunks_exact_mut(2).enumerate() {
+                let color = if (x + parity) % 2 == 0 {
+                    Rgb565::WHITE
+                } else {
+                    Rgb565::BLACK
+                };
+                pixel.copy_from_slice(&RawU16::from(color).into_inner().to_be_bytes());
+            }
+        }
+        for y in 0..PATTERN_HEIGHT {
+            let row = &rows[y as usize % 2];
+            lcd.blit(CHECKER_LEFT, PATTERN_TOP + y, CHECKER_WIDTH, 1, row)?;
+        }
+        Ok(())
+    }
+
+    fn draw_knobs(&self, lcd: &mut St7789) -> Result<(), SpiError> {
+        let s = settings::get();
+        let style = self.style.text();
+        let mut line: String<32> = String::new();
+        for (row, knob) in Knob::ALL.into_iter().enumerate() {
+            line.clear();
+            let marker = if row == self.selected { '>' } else { ' ' };
+            write!(line, "{} {:<9} ", marker, i18n::lcd(knob.label())).ok();
+            knob.write_value(&s, &mut line);
+            while line.len() < LIST_WIDTH && line.push(' ').is_ok() {}
+            let position = Point::new(10, KNOB_TOP + row as i32 * LIST_ROW_HEIGHT);
+            Text::new(&line, position, style).draw(lcd)?;
+        }
+        Ok(())
+    }
+
+    /// 有调整时保存设置
+    fn save(&mut self) {
+        if !self.changed {
+            return;
+        }
+        self.changed = false;
+        match settings::save() {
+            Ok(()) => info!("Display tuning saved"),
+            Err(err) => warn!("Failed to save display tuning: {}", err),
+        }
+    }
+}
+
+impl Screen<Page, St7789> for CalibrationPage {
+    fn on_enter(&mut self) {
+        self.selected = 0;
+        self.changed = false;
+    }
+
+    fn on_event(&mut self, event: Event) -> Response<Page> {
+        match event {
+            Event::Up => self.selected = (self.selected + 1) % Knob::ALL.len(),
+            Event::Down => {
+                settings::update(|s| Knob::ALL[self.selected].step(s));
+                self.changed = true;
+            }
+            _ => {
+                // 离开页面（返回或切换标签页）时保存，由导航器处理页面切换
+                self.save();
+                return Response::Ignored;
+            }
+        }
+        self.dirty = true;
+        Response::Handled
+    }
+
+    fn render(&mut self, lcd: &mut St7789, full: bool) -> Result<(), SpiError> {
+        if full {
+            let title = i18n::lcd(Msg::PageCalibration);
+            draw_frame(lcd, &self.style, title, Msg::CalibrationHint)?;
+            Self::draw_ramps(lcd)?;
+            Self::draw_patterns(lcd)?;
+        } else if !self.dirty {
+            return Ok(());
+        }
+        self.dirty = false;
+        self.draw_knobs(lcd)
+    }
+}
+
+/// 文件列表中的一项
+struct FileEntry {
+    name: String<12>,
+    size: u32,
+    is_dir: bool,
+}
+
+/// 文件列表的状态
+enum Listing {
+    /// 未插入 TF 卡或读取失败
+    NoCard,
+    #[cfg_attr(
+        not(feature = "sd"),
+        expect(dead_code, reason = "only the `sd` feature reads a card")
+    )]
+    Files(Box<Vec<FileEntry, MAX_FILES>>),
+}
+
+/// 文件页面
+struct FilesPage {
+    style: Style,
+    listing: Listing,
+    /// 第一行显示的文件下标
+    scroll: usize,
+    /// 滚动后需要重绘列表
+    dirty: bool,
+    /// 进入页面后需要重新读取文件列表，见 [PageSet::prepare]
+    stale: bool,
+}
+
+impl FilesPage {
+    /// 读取 TF 卡根目录，目录排在前面，同类按名称排序
+    #[cfg(feature = "sd")]
+    async fn scan() -> Result<Vec<FileEntry, MAX_FILES>, SdError> {
+        let mut files: Vec<FileEntry, MAX_FILES> = Vec::new();
+        sdcard::with_root_dir(|root| {
+            root.iterate_dir(|entry| {
+                if entry.attributes.is_volume() {
+                    return;
+                }
+                let mut name = String::new();
+                write!(name, "{}", entry.name).ok();
+                let file = FileEntry {
+                    name,
+                    size: entry.size,
+                    is_dir: entry.attributes.is_directory(),
+                };
+                if files.push(file).is_err() {
+                    warn!("More than {} files, ignoring the rest", MAX_FILES);
+                }
+            })
+        })
+        .await?;
+        files.sort_unstable_by(|a, b| b.is_dir.cmp(&a.is_dir).then_with(|| a.name.cmp(&b.name)));
+        Ok(files)
+    }
+
+    /// 读取当前的文件列表
+    #[cfg(feature = "sd")]
+    async fn list() -> Listing {
+        if !sdcard::is_mounted() {
+            return Listing::NoCard;
+        }
+        match Self::scan().await {
+            Ok(files) => Listing::Files(Box::new(files)),
+            Err(err) => {
+                warn!("Failed to list SD card: {}", defmt::Debug2Format(&err));
+                Listing::NoCard
+            }
+        }
+    }
+
+    /// 没有 TF 卡支持时总是没有卡
+    #[cfg(not(feature = "sd"))]
+    async fn list() -> Listing {
+        Listing::NoCard
+    }
+
+    /// 进入页面后重新读取文件列表
+    async fn refresh(&mut self) {
+        if self.stale {
+            self.listing = Self::list().await;
+            self.stale = false;
+        }
+    }
+
+    fn draw_list(&self, lcd: &mut St7789) -> Result<(), SpiError> {
+        let style = self.style.text();
+        let mut line: String<32> = String::new();
+        for row in 0..LIST_ROWS {
+            line.clear();
+            match &self.listing {
+                Listing::NoCard if row == 0 => line.push_str(i18n::lcd(Msg::FilesNoCard)).ok(),
+                Listing::Files(files) if files.is_empty() && row == 0 => {
+                    line.push_str(i18n::lcd(Msg::FilesEmpty)).ok()
+                }
+                Listing::Files(files) => match files.get(self.scroll + row) {
+                    Some(file) if file.is_dir => write!(line, "{}/", file.name).ok(),
+                    Some(file) if file.size < 1024 => {
+                        write!(line, "{:<13}{:>6} B", file.name, file.size).ok()
+                    }
+                    Some(file) => {
+                        write!(line, "{:<13}{:>5} KB", file.name, file.size.div_ceil(1024)).ok()
+                    }
+                    None => None,
+                },
+                Listing::NoCard => None,
+            };
+            while line.len() < LIST_WIDTH && line.push(' ').is_ok() {}
+            Text::new(&line, list_position(row), style).draw(lcd)?;
+        }
+        Ok(())
+    }
+}
+
+impl Screen<Page, St7789> for FilesPage {
+    fn on_enter(&mut self) {
+        self.scroll = 0;
+        self.stale = true;
+    }
+
+    fn on_event(&mut self, event: Event) -> Response<Page> {
+        let Listing::Files(files) = &self.listing else {
+            return Response::Ignored;
+        };
+        let last = files.len().saturating_sub(LIST_ROWS);
+        let scroll = match event {
+            Event::Up => self.scroll.saturating_sub(1),
+            Event::Down => (self.scroll + 1).min(last),
+            _ => return Response::Ignored,
+        };
+        self.dirty = scroll != self.scroll;
+        self.scroll = scroll;
+        Response::Handled
+    }
+
+    fn render(&mut self, lcd: &mut St7789, full: bool) -> Result<(), SpiError> {
+        if full {
+            draw_frame(lcd, &self.style, i18n::lcd(Msg::PageFiles), Msg::PagesHint)?;
+        } else if !self.dirty {
+            return Ok(());
+        }
+        self.dirty = false;
+        self.draw_list(lcd)
+    }
+}
+
+/// 网络流量页面
+///
+/// 第一行为表头，其余各行是有流量的连接，按收发总量从多到少排列，只显示前几个
+struct NetworkPage {
+    style: Style,
+}
+
+impl NetworkPage {
+    fn draw_list(&self, lcd: &mut St7789) -> Result<(), SpiError> {
+        let mut links = netstats::all();
+        links.sort_unstable_by_key(|(_, counters)| core::cmp::Reverse(counters.total_bytes()));
+        let mut active = links.iter().filter(|(_, c)| c.total_bytes() > 0);
+
+        let style = self.style.text();
+        let mut line: String<32> = String::new();
+        for row in 0..LIST_ROWS {
+            line.clear();
+            if row == 0 {
+                write!(line, "{:<9}{:>10}{:>10}", "", "RX", "TX").ok();
+            } else if let Some((link, counters)) = active.next() {
+                let (mut rx, mut tx): (String<8>, String<8>) = (String::new(), String::new());
+                netstats::write_size(&mut rx, counters.rx_bytes).ok();
+                netstats::write_size(&mut tx, counters.tx_bytes).ok();
+                write!(line, "{:<9}{:>10}{:>10}", link.name(), rx, tx).ok();
+            } else if row == 1 {
+                line.push_str(i18n::lcd(Msg::NetworkIdle)).ok();
+            }
+            while line.len() < LIST_WIDTH && line.push(' ').is_ok() {}
+            Text::new(&line, list_position(row), style).draw(lcd)?;
+        }
+        Ok(())
+    }
+}
+
+impl Screen<Page, St7789> for NetworkPage {
+    fn render(&mut self, lcd: &mut St7789, full: bool) -> Result<(), SpiError> {
+        if full {
+            let title = i18n::lcd(Msg::PageNetwork);
+            draw_frame(lcd, &self.style, title, Msg::PagesHint)?;
+        }
+        // 每次刷新都重绘，数字随流量变化
+        self.draw_list(lcd)
+    }
+}
+
+/// 外设诊断页面
+///
+/// 每行一个外设：名称、总线、地址或引脚、状态，外设数量与列表行数相同
+struct PeripheralsPage {
+    style: Style,
+}
+
+impl PeripheralsPage {
+    fn draw_list(&self, lcd: &mut St7789) -> Result<(), SpiError> {
+        let style = self.style.text();
+        let mut line: String<32> = String::new();
+        for (row, peripheral) in Peripheral::ALL.into_iter().take(LIST_ROWS).enumerate() {
+            line.clear();
+            write!(
+                line,
+                "{:<7}{:<5}{:<10}{}",
+                peripheral.name(),
+                peripheral.bus().name(),
+                peripheral.location(),
+                registry::state(peripheral).name()
+            )
+            .ok();
+            while line.len() < LIST_WIDTH && line.push(' ').is_ok() {}
+            Text::new(&line, list_position(row), style).draw(lcd)?;
+        }
+        Ok(())
+    }
+}
+
+impl Screen<Page, St7789> for PeripheralsPage {
+    fn render(&mut self, lcd: &mut St7789, full: bool) -> Result<(), SpiError> {
+        if full {
+            let title = i18n::lcd(Msg::PagePeripherals);
+            draw_frame(lcd, &self.style, title, Msg::PagesHint)?;
+        }
+        // 每次刷新都重绘，模块插拔后状态随之变化
+        self.draw_list(lcd)
+    }
+}
+
+/// 服务页面
+///
+/// 每行一个当前固件中包含的服务：名称和运行状态，选中的一行前面有 `>`
+struct ServicesPage {
+    style: Style,
+    /// 选中的服务在可用服务中的下标
+    selected: usize,
+}
+
+impl ServicesPage {
+    /// 当前固件中包含的服务
+    fn available() -> impl Iterator<Item = Service> {
+        Service::ALL.into_iter().filter(|s| s.is_available())
+    }
+
+    fn draw_list(&self, lcd: &mut St7789) -> Result<(), SpiError> {
+        let style = self.style.text();
+        let mut line: String<32> = String::new();
+        for (row, service) in Self::available().take(LIST_ROWS).enumerate() {
+            line.clear();
+            let marker = if row == self.selected { '>' } else { ' ' };
+            let state = if service::is_running(service) {
+                Msg::ServicesRunning
+            } else {
+                Msg::ServicesStopped
+            };
+            let (name, state) = (service.name(), i18n::lcd(state));
+            write!(line, "{} {:<10}{}", marker, name, state).ok();
+            while line.len() < LIST_WIDTH && line.push(' ').is_ok() {}
+            Text::new(&line, list_position(row), style).draw(lcd)?;
+        }
+        Ok(())
+    }
+}
+
+impl Screen<Page, St7789> for ServicesPage {
+    fn on_enter(&mut self) {
+        self.selected = 0;
+    }
+
+    fn on_event(&mut self, event: Event) -> Response<Page> {
+        let count = Self::available().count();
+        match event {
+            Event::Up => self.selected = (self.selected + 1) % count,
+            Event::Down => {
+                let Some(service) = Self::available().nth(self.selected) else {
+                    return Response::Ignored;
+                };
+                if service::is_running(service) {
+                    service::stop(service);
+                } else {
+                    service::start(service);
+                }
+            }
+            _ => return Response::Ignored,
+        }
+        Response::Handled
+    }
+
+    fn render(&mut self, lcd: &mut St7789, full: bool) -> Result<(), SpiError> {
+        if full {
+            let title = i18n::lcd(Msg::PageServices);
+            draw_frame(lcd, &self.style, title, Msg::ServicesHint)?;
+        }
+        // 每次刷新都重绘，服务也可能由命令行或 MQTT 启停
+        self.draw_list(lcd)
+    }
+}
+
+/// 状态屏幕的所有页面
+pub struct PageSet {
+    dashboard: Dashboard,
+    settings: SettingsPage,
+    files: FilesPage,
+    network: NetworkPage,
+    peripherals: PeripheralsPage,
+    services: ServicesPage,
+    calibration: CalibrationPage,
+}
+
+impl PageSet {
+    pub fn new(style: Style) -> Self {
+        PageSet {
+            dashboard: Dashboard {
+                style,
+                line: String::new(),
+            },
+            settings: SettingsPage { style },
+            files: FilesPage {
+                style,
+                listing: Listing::NoCard,
+                scroll: 0,
+                dirty: false,
+                stale: false,
+            },
+            network: NetworkPage { style },
+            peripherals: PeripheralsPage { style },
+            services: ServicesPage { style, selected: 0 },
+            calibration: CalibrationPage {
+                style,
+                selected: 0,
+                dirty: false,
+                changed: false,
+            },
+        }
+    }
+
+    /// 更换颜色，之后需要完整重绘
+    pub fn set_style(&mut self, style: Style) {
+        self.dashboard.style = style;
+        self.settings.style = style;
+        self.files.style = style;
+        self.network.style = style;
+        self.peripherals.style = style;
+        self.services.style = style;
+        self.calibration.style = style;
+    }
+
+    /// 绘制前完成页面需要的异步工作：文件页面进入后在这里读取 TF 卡，
+    /// 页面的回调是同步的，不能等待卡槽和共享总线（见 [crate::sdcard]）
+    pub async fn prepare(&mut self) {
+        self.files.refresh().await;
+    }
+}
+
+impl Pages<Page, St7789> for PageSet {
+    fn page(&mut self, id: Page) -> &mut dyn Screen<Page, St7789> {
+        match id {
+            Page::Dashboard => &mut self.dashboard,
+            Page::Settings => &mut self.settings,
+            Page::Files => &mut self.files,
+            Page::Network => &mut self.network,
+            Page::Peripherals => &mut self.peripherals,
+            Page::Services => &mut self.services,
+            Page::Calibration => &mut self.calibration,
+        }
+    }
+}
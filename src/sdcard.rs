@@ -0,0 +1,386 @@
+//! TF 卡存储
+//!
+//! TF 卡通过 SPI 模式连接在共享 SPI2 总线上（片选引脚见 [crate::board]），
+//! 使用 embedded-sdmmc 访问 FAT 文件系统。文件名为 8.3 短文件名格式。
+//!
+//! # 使用方法
+//!
+//! 1. 调用 [init] 初始化 TF 卡并挂载第一个分区
+//! 2. 启动 [watch_task] 检测插拔
+//! 3. 通过 [with_root_dir] 在根目录中读写文件
+//!
+//! # 独占访问
+//!
+//! [with_root_dir] 执行期间卡槽从共享状态中取出，由调用方独占，文件操作不在临界区中进行，
+//! 中断、WiFi 和看门狗照常运行；每次 SPI 传输只在共享总线（[crate::spi]）上短暂加锁。
+//! 卡槽被占用时让出执行器等待它放回。取出卡槽后先等待总线上的异步传输（LCD 刷新）完成，
+//! 之后访问卡的闭包是同步的，执行期间不让出执行器，见 [spi::wait_idle]。
+//! 复制大文件等耗时较长的访问会让等待卡槽的任务同样等这么久。
+//!
+//! # 插拔检测
+//!
+//! 卡座没有检测引脚，[watch_task] 定期探测：已挂载时读取 CSD 寄存器，失败即视为拔出；
+//! 未挂载时按初始化流程重新识别。重新识别期间总线降到 400kHz，共享总线的 LCD
+//! 刷新会短暂变慢，因此未插卡时的探测间隔较长。离线升级只在启动时检查，
+//! 运行中插入的卡需要重启后才会升级。挂载和拔出都登记到外设注册表（[crate::registry]）。
+
+use crate::registry::{self, Peripheral};
+use crate::spi::{self, SharedSpiBus, SpiDevice};
+use alloc::vec;
+use core::cell::{Cell, RefCell};
+use critical_section::Mutex;
+use defmt::{info, warn};
+use embassy_futures::yield_now;
+use embassy_time::{Duration, Timer};
+use embedded_sdmmc::{Mode, SdCard, TimeSource, Timestamp, VolumeIdx, VolumeManager};
+use esp_hal::delay::Delay;
+use esp_hal::gpio::Output;
+use esp_hal::time::Rate;
+
+/// TF 卡块设备类型
+pub type Card = SdCard<SpiDevice, Delay>;
+
+/// 卷管理器类型
+pub type Volumes = VolumeManager<Card, BoardTimeSource>;
+
+/// 目录句柄类型
+pub type Dir<'a> = embedded_sdmmc::Directory<'a, Card, BoardTimeSource, 4, 4, 1>;
+
+/// 文件句柄类型
+pub type SdFile<'a> = embedded_sdmmc::File<'a, Card, BoardTimeSource, 4, 4, 1>;
+
+/// TF 卡操作错误类型
+pub type SdError = embedded_sdmmc::Error<embedded_sdmmc::SdCardError>;
+
+/// 卡槽未初始化或卡未挂载时返回的错误
+const CARD_NOT_FOUND: SdError = SdError::DeviceError(embedded_sdmmc::SdCardError::CardNotFound);
+
+/// 文件复制时每批读写的字节数，缓冲区从堆中分配
+const COPY_BUF_LEN: usize = 8 * 1024;
+
+/// 已挂载时检查卡是否仍在的间隔
+const PRESENT_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// 未挂载时尝试识别新插入的卡的间隔
+const INSERT_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// TF 卡槽
+struct Slot {
+    /// 所在的共享总线，重新识别时需要修改时钟
+    bus: &'static SharedSpiBus,
+    volumes: Volumes,
+}
+
+/// 卡槽，访问期间取出（见 [take_slot]），避免在临界区中等待卡响应
+static SD_CARD: Mutex<RefCell<Option<Slot>>> = Mutex::new(RefCell::new(None));
+
+/// 卡槽是否已被取出使用
+static IN_USE: Mutex<Cell<bool>> = Mutex::new(Cell::new(false));
+
+/// 卡是否已识别并可以访问
+static MOUNTED: Mutex<Cell<bool>> = Mutex::new(Cell::new(false));
+
+/// 取出卡槽独占使用，用完后用 [put_slot] 放回
+///
+/// 卡槽正在使用时让出执行器等它放回，取出后等待共享总线空闲，
+/// 调用方在放回之前不能再让出执行器
+///
+/// # 返回
+/// 卡槽未初始化时返回 None
+async fn take_slot() -> Option<Slot> {
+    let slot = loop {
+        let taken = critical_section::with(|cs| {
+            let in_use = IN_USE.borrow(cs);
+            if in_use.get() {
+                return None;
+            }
+            let slot = SD_CARD.borrow_ref_mut(cs).take();
+            in_use.set(slot.is_some());
+            Some(slot)
+        });
+        match taken {
+            Some(slot) => break slot?,
+            None => yield_now().await,
+        }
+    };
+    spi::wait_idle(slot.bus).await;
+    Some(slot)
+}
+
+/// 放回 [take_slot] 取出的卡槽
+fn put_slot(slot: Slot) {
+    critical_section::with(|cs| {
+        SD_CARD.borrow_ref_mut(cs).replace(slot);
+        IN_USE.borrow(cs).set(false);
+    });
+}
+
+/// 记录卡是否已挂载，并登记到外设注册表
+fn set_mounted(mounted: bool, op: &'static str) {
+    critical_section::with(|cs| MOUNTED.borrow(cs).set(mounted));
+    if mounted {
+        registry::set_online(Peripheral::SdCard);
+    } else {
+        registry::set_failed(Peripheral::SdCard, op);
+    }
+}
+
+/// 文件系统时间戳来源
+///
+/// 目前板上没有可用的实时时钟，统一使用固定时间
+pub struct BoardTimeSource;
+
+impl TimeSource for BoardTimeSource {
+    fn get_timestamp(&self) -> Timestamp {
+        Timestamp {
+            year_since_1970: 55,
+            zero_indexed_month: 0,
+            zero_indexed_day: 0,
+            hours: 0,
+            minutes: 0,
+            seconds: 0,
+        }
+    }
+}
+
+/// 初始化 TF 卡
+///
+/// 初始化阶段以 400kHz 时钟与 TF 卡通信，完成后恢复总线默认时钟。
+/// 未插卡时卡槽仍然保留，之后插入的卡由 [watch_task] 识别。
+///
+/// # 参数
+/// * `bus` - 共享 SPI 总线
+/// * `cs_pin` - TF 卡片选引脚
+///
+/// # 返回
+/// 成功时返回卡容量（字节）
+pub async fn init(bus: &'static SharedSpiBus, cs_pin: Output<'static>) -> Result<u64, SdError> {
+    let card = SdCard::new(spi::device(bus, cs_pin), Delay::new());
+    put_slot(Slot {
+        bus,
+        volumes: VolumeManager::new(card, BoardTimeSource),
+    });
+    mount().await
+}
+
+/// 识别卡槽中的卡
+///
+/// # 返回
+/// 成功时返回卡容量（字节）
+async fn mount() -> Result<u64, SdError> {
+    let Some(mut slot) = take_slot().await else {
+        return Err(CARD_NOT_FOUND);
+    };
+
+    spi::set_frequency(slot.bus, Rate::from_khz(400));
+    // 上电后需在片选无效时发送至少 74 个时钟
+    spi::with_bus(slot.bus, |bus| {
+        embedded_hal::spi::SpiBus::write(bus, &[0xFF; 10]).ok()
+    });
+    let card = slot.volumes.device();
+    card.mark_card_uninit();
+    let result = card.num_bytes();
+    spi::set_frequency(slot.bus, spi::DEFAULT_FREQUENCY);
+
+    put_slot(slot);
+    set_mounted(result.is_ok(), "SD card mount");
+    let size = result.map_err(embedded_sdmmc::Error::DeviceError)?;
+    info!("SD card size: {} MB", size / (1024 * 1024));
+    Ok(size)
+}
+
+/// 检查已挂载的卡是否仍在，不在时标记为未挂载
+///
+/// # 返回
+/// 卡被拔出时返回 true
+async fn check_removed() -> bool {
+    if !is_mounted() {
+        return false;
+    }
+    let Some(mut slot) = take_slot().await else {
+        return false;
+    };
+    let card = slot.volumes.device();
+    let removed = card.num_bytes().is_err();
+    if removed {
+        card.mark_card_uninit();
+    }
+    put_slot(slot);
+    if removed {
+        set_mounted(false, "SD card presence check");
+    }
+    removed
+}
+
+/// TF 卡是否已挂载
+pub fn is_mounted() -> bool {
+    critical_section::with(|cs| MOUNTED.borrow(cs).get())
+}
+
+/// TF 卡插拔检测任务
+///
+/// 定期探测卡槽，卡被拔出后 [with_root_dir] 返回 `CardNotFound`，重新插入后自动挂载
+#[embassy_executor::task]
+pub async fn watch_task() {
+    loop {
+        if is_mounted() {
+            Timer::after(PRESENT_POLL_INTERVAL).await;
+            if check_removed().await {
+                warn!("SD card removed");
+            }
+        } else {
+            Timer::after(INSERT_POLL_INTERVAL).await;
+            if mount().await.is_ok() {
+                info!("SD card inserted");
+            }
+        }
+    }
+}
+
+/// 通过闭包访问第一个分区的根目录
+///
+/// 闭包执行期间独占卡槽，不在临界区中（见模块文档）
+///
+/// # 参数
+/// * `f` - 闭包函数，接受根目录句柄作为参数
+pub async fn with_root_dir<F, R>(f: F) -> Result<R, SdError>
+where
+    F: FnOnce(&mut Dir<'_>) -> Result<R, SdError>,
+{
+    if !is_mounted() {
+        return Err(CARD_NOT_FOUND);
+    }
+    let Some(mut slot) = take_slot().await else {
+        return Err(CARD_NOT_FOUND);
+    };
+    let result = open_root_dir(&mut slot.volumes, f);
+    put_slot(slot);
+    result
+}
+
+/// 通过闭包直接读写卡的块设备，绕过文件系统
+///
+/// 闭包执行期间独占卡槽；embedded-sdmmc 不知道块被修改，调用方不能持有打开的文件或目录。
+/// 用于文件系统一致性检查（见 [crate::fatcheck]）
+///
+/// # 参数
+/// * `f` - 闭包函数，接受块设备作为参数
+pub async fn with_device<F, R>(f: F) -> Result<R, SdError>
+where
+    F: FnOnce(&Card) -> Result<R, SdError>,
+{
+    if !is_mounted() {
+        return Err(CARD_NOT_FOUND);
+    }
+    let Some(mut slot) = take_slot().await else {
+        return Err(CARD_NOT_FOUND);
+    };
+    let result = f(slot.volumes.device());
+    put_slot(slot);
+    result
+}
+
+/// 打开第一个分区的根目录并执行闭包
+fn open_root_dir<F, R>(volumes: &mut Volumes, f: F) -> Result<R, SdError>
+where
+    F: FnOnce(&mut Dir<'_>) -> Result<R, SdError>,
+{
+    let mut volume = volumes.open_volume(VolumeIdx(0))?;
+    let mut root = volume.open_root_dir()?;
+    f(&mut root)
+}
+
+/// 判断目录中是否存在指定文件
+///
+/// # 参数
+/// * `dir` - 目录句柄
+/// * `name` - 文件名
+pub fn file_exists(dir: &mut Dir<'_>, name: &str) -> Result<bool, SdError> {
+    match dir.find_directory_entry(name) {
+        Ok(_) => Ok(true),
+        Err(embedded_sdmmc::Error::NotFound) => Ok(false),
+        Err(err) => Err(err),
+    }
+}
+
+/// 重命名文件
+///
+/// embedded-sdmmc 不支持直接修改目录项，这里通过复制后删除原文件实现。
+/// 目标文件已存在时会被覆盖。
+///
+/// # 参数
+/// * `dir` - 目录句柄
+/// * `from` - 原文件名
+/// * `to` - 新文件名
+pub fn rename(dir: &mut Dir<'_>, from: &str, to: &str) -> Result<(), SdError> {
+    copy_file(dir, from, 0, to)?;
+    dir.delete_file_in_dir(from)?;
+    info!("Renamed {} to {}", from, to);
+    Ok(())
+}
+
+/// 把文件从 `offset` 开始的内容复制到另一个文件，目标文件已存在时会被覆盖
+///
+/// 打开的文件借用目录句柄，同一时间只能打开一个文件，因此每批先打开原文件读满缓冲区，
+/// 关闭后再打开目标文件写入。每次打开都要从目录中查找文件、沿簇链定位，批量越大开销越小。
+///
+/// # 参数
+/// * `dir` - 目录句柄
+/// * `from` - 原文件名
+/// * `offset` - 开始复制的位置
+/// * `to` - 目标文件名
+pub fn copy_file(dir: &mut Dir<'_>, from: &str, offset: u32, to: &str) -> Result<(), SdError> {
+    dir.open_file_in_dir(to, Mode::ReadWriteCreateOrTruncate)?
+        .close()?;
+    let mut buf = vec![0u8; COPY_BUF_LEN];
+    let mut pos = offset;
+    loop {
+        let mut src = dir.open_file_in_dir(from, Mode::ReadOnly)?;
+        src.seek_from_start(pos)?;
+        // 每次读取最多到块边界，读满一批或到文件末尾为止
+        let mut len = 0;
+        while len < buf.len() && !src.is_eof() {
+            let read = src.read(&mut buf[len..])?;
+            if read == 0 {
+                break;
+            }
+            len += read;
+        }
+        src.close()?;
+        if len > 0 {
+            let mut dst = dir.open_file_in_dir(to, Mode::ReadWriteAppend)?;
+            dst.write(&buf[..len])?;
+            dst.close()?;
+            pos += len as u32;
+        }
+        if len < buf.len() {
+            return Ok(());
+        }
+    }
+}
+
+/// 读取整个小文件到缓冲区
+///
+/// # 参数
+/// * `dir` - 目录句柄
+/// * `name` - 文件名
+/// * `buf` - 接收缓冲区，文件超出部分会被截断
+///
+/// # 返回
+/// 读取的字节数
+pub fn read_file(dir: &mut Dir<'_>, name: &str, buf: &mut [u8]) -> Result<usize, SdError> {
+    let mut file = dir.open_file_in_dir(name, Mode::ReadOnly)?;
+    let mut total = 0;
+    while total < buf.len() && !file.is_eof() {
+        let len = file.read(&mut buf[total..])?;
+        if len == 0 {
+            break;
+        }
+        total += len;
+    }
+    if !file.is_eof() {
+        warn!("{} is larger than {} bytes, truncated", name, buf.len());
+    }
+    file.close()?;
+    Ok(total)
+}
@@ -0,0 +1,219 @@
+//! TF 卡数据记录
+//!
+//! 记录（一行文本）通过 [append] 放入内存缓冲区，由 [writer_task] 每 [FLUSH_INTERVAL]
+//! 或缓冲区过半时批量追加到 TF 卡根目录的 [LOG_FILE]。TF 卡拔出期间记录留在缓冲区，
+//! 缓冲区满后丢弃新记录。
+//!
+//! 数据记录可以用命令行 `service datalog stop` 停止（见 [crate::service]）：停止时写出剩余记录、
+//! 删除未正常关机标记，之后 TF 卡可以安全拔出；停止期间的记录直接丢弃。
+//!
+//! # 掉电保护
+//!
+//! - 每批写完即关闭文件：embedded-sdmmc 在关闭文件时才更新目录项中的长度，
+//!   关闭之后的数据在掉电后仍然完整
+//! - 开始写入前在根目录创建 [DIRTY_FILE] 标记，正常关机时（关机钩子）写出剩余记录并删除标记
+//! - 挂载后发现标记仍在，说明上次没有正常关机：先检查日志文件的簇链与目录项中的长度是否一致
+//!   （见 [crate::fatcheck]），簇链断开时截短文件；再检查文件末尾，
+//!   最后一条记录没有写完时补上换行，后续记录从新的一行开始
+//!
+//! 写了一半的记录本身保留在文件中，读取时应跳过字段数不对的行。掉电时已分配但未写入
+//! 目录项的簇在之后追加时继续使用；其他文件和整个卷的一致性不检查，需要时在电脑上用
+//! chkdsk/fsck 处理。
+
+use crate::fatcheck::{self, ChainState};
+use crate::sdcard::{self, Dir, SdError};
+use crate::service::{self, Service};
+use crate::system;
+use alloc::boxed::Box;
+use core::cell::RefCell;
+use core::mem;
+use core::sync::atomic::{AtomicBool, Ordering};
+use critical_section::Mutex;
+use defmt::{info, warn};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::signal::Signal;
+use embassy_time::{Duration, with_timeout};
+use embedded_sdmmc::Mode;
+use heapless::Vec;
+
+/// 日志文件名
+pub const LOG_FILE: &str = "DATALOG.CSV";
+
+/// 未正常关机标记文件名
+const DIRTY_FILE: &str = "DATALOG.DRT";
+
+/// 写入周期
+const FLUSH_INTERVAL: Duration = Duration::from_secs(30);
+
+/// 缓冲区大小，超过一半时提前写入
+const BUFFER_LEN: usize = 2048;
+
+/// 检查文件末尾时读取的最大长度
+const TAIL_LEN: usize = 128;
+
+/// 等待写入的记录
+static BUFFER: Mutex<RefCell<Vec<u8, BUFFER_LEN>>> = Mutex::new(RefCell::new(Vec::new()));
+
+/// 缓冲区过半时通知写入任务
+static FLUSH: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+
+/// 当前卡上的未正常关机标记是否已由本次运行创建（之前的日志已检查过）
+static MARKED: AtomicBool = AtomicBool::new(false);
+
+/// 追加一条记录
+///
+/// 记录末尾自动加换行；缓冲区放不下或数据记录已停止时丢弃整条记录
+///
+/// # 参数
+/// * `record` - 一行文本，不含换行
+pub fn append(record: &str) {
+    if !service::is_running(Service::Datalog) {
+        return;
+    }
+    let (stored, half_full) = critical_section::with(|cs| {
+        let mut buffer = BUFFER.borrow_ref_mut(cs);
+        let stored = buffer.len() + record.len() < BUFFER_LEN;
+        if stored {
+            // 长度已检查，不会失败
+            buffer.extend_from_slice(record.as_bytes()).ok();
+            buffer.push(b'\n').ok();
+        }
+        (stored, buffer.len() >= BUFFER_LEN / 2)
+    });
+    if !stored {
+        warn!("Data log buffer full, dropping record");
+    }
+    if half_full {
+        FLUSH.signal(());
+    }
+}
+
+/// 写入任务
+///
+/// 定期把缓冲区写入 TF 卡，并注册关机钩子在重启前写出剩余记录；服务停止时同样写出剩余记录
+#[embassy_executor::task]
+pub async fn writer_task() {
+    system::on_shutdown(|| Box::pin(close()));
+    loop {
+        service::wait_started(Service::Datalog).await;
+        service::run(Service::Datalog, write_periodically()).await;
+        close().await;
+    }
+}
+
+/// 每 [FLUSH_INTERVAL] 或缓冲区过半时写入
+async fn write_periodically() {
+    loop {
+        with_timeout(FLUSH_INTERVAL, FLUSH.wait()).await.ok();
+        if !sdcard::is_mounted() {
+            // 卡被拔出或更换，重新插入后需要重新检查
+            MARKED.store(false, Ordering::Relaxed);
+            continue;
+        }
+        if let Err(err) = flush().await {
+            warn!("Failed to write data log: {}", defmt::Debug2Format(&err));
+        }
+    }
+}
+
+/// 把缓冲区写入日志文件
+///
+/// 本次运行第一次写入当前的卡时，先检查未正常关机标记并修复日志文件
+async fn flush() -> Result<(), SdError> {
+    let records = critical_section::with(|cs| mem::take(&mut *BUFFER.borrow_ref_mut(cs)));
+    let marked = MARKED.load(Ordering::Relaxed);
+    if records.is_empty() && marked {
+        return Ok(());
+    }
+    // 簇链检查直接读写块设备，不能在打开根目录期间进行
+    let dirty =
+        !marked && sdcard::with_root_dir(|dir| sdcard::file_exists(dir, DIRTY_FILE)).await?;
+    if dirty {
+        check_chain().await?;
+    }
+    sdcard::with_root_dir(|dir| {
+        if !marked {
+            if dirty {
+                repair(dir)?;
+            } else {
+                dir.open_file_in_dir(DIRTY_FILE, Mode::ReadWriteCreateOrTruncate)?
+                    .close()?;
+            }
+            MARKED.store(true, Ordering::Relaxed);
+        }
+        if records.is_empty() {
+            return Ok(());
+        }
+        let mut file = dir.open_file_in_dir(LOG_FILE, Mode::ReadWriteCreateOrAppend)?;
+        file.write(&records)?;
+        // 关闭时更新目录项，之后的数据不会因掉电丢失
+        file.close()
+    })
+    .await
+}
+
+/// 上次没有正常关机时检查日志文件的簇链，簇链断开时截短文件
+async fn check_chain() -> Result<(), SdError> {
+    match fatcheck::check_file(LOG_FILE).await {
+        Ok(ChainState::Consistent) => info!("Data log cluster chain is consistent"),
+        Ok(ChainState::Overlong) => {
+            info!("Data log has clusters beyond its length, reused on next append")
+        }
+        Ok(ChainState::Truncated { from, to }) => warn!(
+            "Data log cluster chain is broken, truncated from {} to {} bytes",
+            from, to
+        ),
+        Err(embedded_sdmmc::Error::NotFound) => {}
+        Err(err) => return Err(err),
+    }
+    Ok(())
+}
+
+/// 上次没有正常关机时检查日志文件末尾
+///
+/// 最后一条记录没有写完时补上换行
+fn repair(dir: &mut Dir<'_>) -> Result<(), SdError> {
+    if !sdcard::file_exists(dir, LOG_FILE)? {
+        return Ok(());
+    }
+    let mut file = dir.open_file_in_dir(LOG_FILE, Mode::ReadWriteAppend)?;
+    let len = file.length();
+    let tail_len = (len as usize).min(TAIL_LEN);
+    let mut tail = [0u8; TAIL_LEN];
+    if tail_len > 0 {
+        file.seek_from_end(tail_len as u32)?;
+        file.read(&mut tail[..tail_len])?;
+    }
+    let tail = &tail[..tail_len];
+    match tail.last() {
+        Some(b'\n') | None => info!("Data log closed uncleanly, last record is intact"),
+        Some(_) => {
+            let torn = tail.iter().rev().take_while(|&&b| b != b'\n').count();
+            warn!(
+                "Data log closed uncleanly, terminating partial record of {} bytes",
+                torn
+            );
+            file.seek_from_end(0)?;
+            file.write(b"\n")?;
+        }
+    }
+    file.close()
+}
+
+/// 关机钩子：写出剩余记录并删除未正常关机标记
+async fn close() {
+    if !sdcard::is_mounted() {
+        return;
+    }
+    let result = match flush().await {
+        Ok(()) => {
+            MARKED.store(false, Ordering::Relaxed);
+            sdcard::with_root_dir(|dir| dir.delete_file_in_dir(DIRTY_FILE)).await
+        }
+        Err(err) => Err(err),
+    };
+    match result {
+        Ok(()) => info!("Data log closed"),
+        Err(err) => warn!("Failed to close data log: {}", defmt::Debug2Format(&err)),
+    }
+}
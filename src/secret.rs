@@ -0,0 +1,187 @@
+//! 设置中密码类数据的加密
+//!
+//! WiFi 密码、天气预报 API Key、HTTP 访问令牌、PIN、MQTT 密码和 ESP-NOW 配对密钥写入设置扇区前
+//! 用 [seal] 加密，读取时用 [open] 解密（见 [crate::settings]）。
+//! 密钥在启动时由 [init] 确定，不保存在 Flash 中：
+//!
+//! - eFuse 的 [HMAC_KEY] 密钥块烧写了用途为 `HMAC_UP` 的密钥时，用 HMAC 外设对固定的
+//!   [KDF_LABEL] 计算 HMAC-SHA256 作为密钥。烧写时读保护的 eFuse 密钥软件无法读出，
+//!   只有 HMAC 外设能使用，转储 Flash 或在板上运行代码都拿不到密钥，这是真正的加密。
+//!   烧写方法（不可逆）：`espefuse.py burn_key BLOCK_KEY5 <32 字节随机数文件> HMAC_UP`
+//! - 没有烧写时退回到由 eFuse 出厂数据（基准 MAC 地址和 128 位唯一 ID）派生密钥，
+//!   这只是混淆：出厂数据可以读出，见下面的限制
+//!
+//! 两种密钥加密的数据互不相通，烧写 HMAC 密钥后之前保存的密码无法解密，需要重新设置。
+//!
+//! 加密算法为 ChaCha20（RFC 8439，见 [crypto::chacha20]），每次加密随机生成 8 字节 nonce。
+//! 明文前附加 CRC32，解密后校验，用于识别在其他芯片上加密的数据；它不是消息认证码，不防篡改。
+//!
+//! 密文格式为 `[MARKER] [nonce] [CRC32 + 明文]`，后两部分加密。[MARKER] 不会出现在
+//! UTF-8 文本的开头，旧固件保存的明文因此仍能读取，下次保存设置时改为密文。
+//!
+//! 限制：没有烧写 HMAC 密钥时，eFuse 出厂数据在芯片上可以读出，能在板上运行代码或用
+//! espefuse 读取 eFuse 的人仍能算出密钥，这时只防止从 Flash 镜像中直接读出密码
+//! （混淆，不是加密）。转储的 Flash 写到另一块板子上同样无法解密。
+
+use crate::storage;
+use core::cell::Cell;
+use critical_section::Mutex;
+use crypto::chacha20;
+use defmt::{info, warn};
+use esp_hal::efuse::{self, Efuse};
+use esp_hal::hmac::{Hmac, HmacPurpose, KeyId};
+use esp_hal::peripherals::HMAC;
+use esp_hal::rng::Rng;
+
+/// 密文的第一个字节
+pub const MARKER: u8 = 0xFF;
+
+/// nonce 长度
+const NONCE_LEN: usize = 8;
+
+/// 校验值（CRC32）长度
+const CHECK_LEN: usize = 4;
+
+/// 加密后增加的字节数
+pub const OVERHEAD: usize = 1 + NONCE_LEN + CHECK_LEN;
+
+/// 派生密钥时使用的 nonce，区分用途
+const KDF_NONCE: [u8; chacha20::NONCE_LEN] = *b"esp-app-4 kd";
+
+/// 保存 HMAC 密钥的 eFuse 密钥块（BLOCK_KEY5）
+pub const HMAC_KEY: KeyId = KeyId::Key5;
+
+/// 用 HMAC 外设派生密钥时的消息，区分用途
+const KDF_LABEL: &[u8] = b"esp-app-4 settings secret key";
+
+/// 启动时确定的密钥，[init] 之前为 None
+static KEY: Mutex<Cell<Option<[u8; chacha20::KEY_LEN]>>> = Mutex::new(Cell::new(None));
+
+/// 确定密钥，在加载设置之前调用
+///
+/// # 参数
+/// * `hmac` - HMAC 外设
+///
+/// # 返回
+/// 是否使用 eFuse 中的 HMAC 密钥；为 false 时密钥由出厂数据派生，只是混淆
+pub fn init(hmac: HMAC<'static>) -> bool {
+    let (key, hardware) = match hmac_key(hmac) {
+        Some(key) => {
+            info!("Secrets encrypted with the eFuse HMAC key");
+            (key, true)
+        }
+        None => {
+            warn!("No HMAC key in eFuse BLOCK_KEY5, secrets are only obfuscated");
+            (factory_key(), false)
+        }
+    };
+    critical_section::with(|cs| KEY.borrow(cs).set(Some(key)));
+    hardware
+}
+
+/// 加密
+///
+/// # 参数
+/// * `plain` - 明文，为空时不加密
+/// * `out` - 密文缓冲区，至少 `plain.len() + OVERHEAD` 字节
+///
+/// # 返回
+/// 密文长度，`plain` 为空时为 0
+///
+/// # Panics
+///
+/// `out` 放不下密文时 panic
+pub fn seal(plain: &[u8], out: &mut [u8]) -> usize {
+    if plain.is_empty() {
+        return 0;
+    }
+    let len = OVERHEAD + plain.len();
+    let rng = Rng::new();
+    let mut nonce = [0u8; NONCE_LEN];
+    nonce[..4].copy_from_slice(&rng.random().to_le_bytes());
+    nonce[4..].copy_from_slice(&rng.random().to_le_bytes());
+
+    out[0] = MARKER;
+    out[1..1 + NONCE_LEN].copy_from_slice(&nonce);
+    let body = &mut out[1 + NONCE_LEN..len];
+    body[..CHECK_LEN].copy_from_slice(&storage::crc32(plain).to_le_bytes());
+    body[CHECK_LEN..].copy_from_slice(plain);
+    apply_keystream(&key(), &nonce, body);
+    len
+}
+
+/// 解密
+///
+/// 不以 [MARKER] 开头的数据是旧固件保存的明文，原样复制
+///
+/// # 参数
+/// * `data` - [seal] 生成的密文或明文
+/// * `out` - 明文缓冲区
+///
+/// # 返回
+/// 明文长度；密文损坏、在其他芯片上加密或 `out` 放不下时返回 None
+pub fn open(data: &[u8], out: &mut [u8]) -> Option<usize> {
+    let Some((&MARKER, sealed)) = data.split_first() else {
+        out.get_mut(..data.len())?.copy_from_slice(data);
+        return Some(data.len());
+    };
+    let (nonce, body) = sealed.split_first_chunk::<NONCE_LEN>()?;
+    let len = body.len().checked_sub(CHECK_LEN)?;
+    // 设置的 TLV 字段不超过 255 字节
+    let mut buf = [0u8; 255];
+    let buf = buf.get_mut(..body.len())?;
+    buf.copy_from_slice(body);
+    apply_keystream(&key(), nonce, buf);
+    let (check, plain) = buf.split_at(CHECK_LEN);
+    if check != storage::crc32(plain).to_le_bytes() {
+        return None;
+    }
+    out.get_mut(..len)?.copy_from_slice(plain);
+    Some(len)
+}
+
+/// 当前的密钥，[init] 之前由出厂数据派生
+fn key() -> [u8; chacha20::KEY_LEN] {
+    critical_section::with(|cs| KEY.borrow(cs).get()).unwrap_or_else(factory_key)
+}
+
+/// 用 HMAC 外设和 eFuse 中的密钥派生密钥
+///
+/// # 返回
+/// [HMAC_KEY] 没有烧写用途为 `HMAC_UP` 的密钥时返回 None
+fn hmac_key(hmac: HMAC<'static>) -> Option<[u8; chacha20::KEY_LEN]> {
+    let mut hmac = Hmac::new(hmac);
+    hmac.init();
+    // 密钥块的用途不符时外设拒绝计算
+    hmac.configure(HmacPurpose::ToUser, HMAC_KEY).ok()?;
+    let mut rest = KDF_LABEL;
+    while !rest.is_empty() {
+        if let Ok(remaining) = hmac.update(rest) {
+            rest = remaining;
+        }
+    }
+    let mut key = [0u8; chacha20::KEY_LEN];
+    while hmac.finalize(&mut key).is_err() {}
+    Some(key)
+}
+
+/// 由 eFuse 出厂数据派生密钥
+fn factory_key() -> [u8; chacha20::KEY_LEN] {
+    let mut material = [0u8; chacha20::KEY_LEN];
+    material[..6].copy_from_slice(&Efuse::mac_address());
+    let unique_id: [u8; 16] = Efuse::read_field_le(efuse::OPTIONAL_UNIQUE_ID);
+    material[6..22].copy_from_slice(&unique_id);
+    // 出厂数据作为 ChaCha20 密钥，用专用的 nonce 生成一个分组，取前 32 字节
+    let block = chacha20::block(&material, 0, &KDF_NONCE);
+    let mut key = [0u8; chacha20::KEY_LEN];
+    key.copy_from_slice(&block[..chacha20::KEY_LEN]);
+    key
+}
+
+/// 用密钥流异或数据，计数器从 1 开始
+fn apply_keystream(key: &[u8; chacha20::KEY_LEN], nonce: &[u8; NONCE_LEN], data: &mut [u8]) {
+    // 96 位 nonce 的前 4 字节为 0
+    let mut full = [0u8; chacha20::NONCE_LEN];
+    full[4..].copy_from_slice(nonce);
+    chacha20::apply_keystream(key, 1, &full, data);
+}
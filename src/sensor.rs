@@ -0,0 +1,112 @@
+//! 传感器读数登记表
+//!
+//! 各传感器驱动通过 [publish] 登记最新读数，按名称区分（例如 `gps.lat`），
+//! 显示、HTTP、Modbus 等消费者通过 [get] 或 [all] 读取，不需要依赖具体驱动。
+//! 每个名称只保留最新一次读数；传感器拔出后驱动通过 [withdraw] 撤下它的读数。
+
+use alloc::vec::Vec;
+use core::cell::RefCell;
+use critical_section::Mutex;
+use defmt::warn;
+use embassy_time::Instant;
+use proto::json::{Object, ToJson};
+
+/// 最多可登记的读数数量
+const MAX_READINGS: usize = 16;
+
+/// 一个读数
+#[derive(Debug, Clone, Copy, PartialEq, defmt::Format)]
+pub struct Reading {
+    /// 名称，格式为 `<传感器>.<物理量>`
+    pub name: &'static str,
+    pub value: f64,
+    /// 单位，例如 "m"、"deg"、"C"
+    pub unit: &'static str,
+    /// 更新时间
+    pub updated: Instant,
+}
+
+impl ToJson for Reading {
+    /// 名称、数值、单位和距上次更新的秒数，数值无效时为 `null`
+    fn write_members(&self, object: &mut Object<'_>) {
+        object
+            .str("name", self.name)
+            .number("value", self.value)
+            .str("unit", self.unit)
+            .int("age", self.updated.elapsed().as_secs() as i64);
+    }
+}
+
+/// 传感器错误（总线错误见 [crate::error::Error::I2c]）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum SensorError {
+    /// 总线上没有找到传感器
+    NotFound,
+}
+
+static READINGS: Mutex<RefCell<[Option<Reading>; MAX_READINGS]>> =
+    Mutex::new(RefCell::new([None; MAX_READINGS]));
+
+/// 登记或更新读数
+///
+/// # 参数
+/// * `name` - 读数名称
+/// * `value` - 数值
+/// * `unit` - 单位
+pub fn publish(name: &'static str, value: f64, unit: &'static str) {
+    let reading = Reading {
+        name,
+        value,
+        unit,
+        updated: Instant::now(),
+    };
+    critical_section::with(|cs| {
+        let mut readings = READINGS.borrow_ref_mut(cs);
+        let slot = match readings.iter().position(|r| r.is_some_and(|r| r.name == name)) {
+            Some(slot) => Some(slot),
+            None => readings.iter().position(|r| r.is_none()),
+        };
+        match slot {
+            Some(slot) => readings[slot] = Some(reading),
+            None => warn!("Too many sensor readings, ignoring {}", name),
+        }
+    });
+}
+
+/// 撤下名称以 `prefix` 开头的读数，例如传感器拔出后撤下 `bme280.`
+///
+/// # 参数
+/// * `prefix` - 名称前缀
+pub fn withdraw(prefix: &str) {
+    critical_section::with(|cs| {
+        for slot in READINGS.borrow_ref_mut(cs).iter_mut() {
+            if slot.is_some_and(|r| r.name.starts_with(prefix)) {
+                *slot = None;
+            }
+        }
+    });
+}
+
+/// 读数名称是否合法：`<传感器>.<物理量>`，用于检查设置中填写的名称
+pub fn is_valid_name(name: &str) -> bool {
+    name.split_once('.')
+        .is_some_and(|(sensor, quantity)| !sensor.is_empty() && !quantity.is_empty())
+        && name.bytes().all(|b| b.is_ascii_graphic())
+}
+
+/// 按名称读取
+pub fn get(name: &str) -> Option<Reading> {
+    critical_section::with(|cs| {
+        READINGS
+            .borrow_ref(cs)
+            .iter()
+            .flatten()
+            .find(|r| r.name == name)
+            .copied()
+    })
+}
+
+/// 所有读数，按登记顺序排列
+pub fn all() -> Vec<Reading> {
+    critical_section::with(|cs| READINGS.borrow_ref(cs).iter().flatten().copied().collect())
+}
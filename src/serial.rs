@@ -0,0 +1,214 @@
+//! 通用异步串口
+//!
+//! 封装 UART1/UART2（UART0 由控制台使用，见 [crate::console]），用于连接 RS232 或
+//! TTL 电平的外部设备，例如 GPS 接收机（[crate::gps]）和串口透传（[crate::bridge]）。
+//! 除了原始字节读写外，[Serial::read_line] 读取以分隔符（通常是 `\n`）结尾的文本行，
+//! 行尾的 `\r` 会被去掉。
+//!
+//! 调试外部设备的协议时可用命令行 `serial tap on` 打开原始字节日志（[set_tap]），
+//! 所有串口收发的原始字节以十六进制写入 defmt 日志（同样进入日志环形缓冲区，见 [crate::logbuf]）。
+
+use core::sync::atomic::{AtomicBool, Ordering};
+use defmt::{debug, warn};
+use esp_hal::Async;
+use esp_hal::gpio::interconnect::{PeripheralInput, PeripheralOutput};
+use esp_hal::uart::{self, Config as UartConfig, Parity, StopBits, Uart, UartRx, UartTx};
+
+/// 外接设备常用的标准波特率
+pub const BAUDRATES: [u32; 8] = [1200, 2400, 4800, 9600, 19_200, 38_400, 57_600, 115_200];
+
+/// 内部接收缓冲区大小，也是一行的最大长度
+const RX_BUF_LEN: usize = 256;
+
+/// 串口错误
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum SerialError {
+    /// UART 配置无效（例如波特率无法实现）
+    Config,
+    /// 发送出错
+    Transmit,
+    /// 接收出错（奇偶校验、帧格式或 FIFO 溢出）
+    Receive,
+    /// 行超过缓冲区大小，该行已被丢弃
+    Overflow,
+}
+
+/// 是否将收发的原始字节写入日志
+static TAP: AtomicBool = AtomicBool::new(false);
+
+/// 打开或关闭所有串口的原始字节日志（重启前有效）
+pub fn set_tap(on: bool) {
+    TAP.store(on, Ordering::Relaxed);
+}
+
+/// 原始字节日志是否打开
+pub fn tap() -> bool {
+    TAP.load(Ordering::Relaxed)
+}
+
+/// 串口配置
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SerialConfig {
+    /// 波特率
+    pub baudrate: u32,
+    /// 校验位
+    pub parity: Parity,
+    /// 停止位
+    pub stop_bits: StopBits,
+}
+
+impl SerialConfig {
+    /// 默认配置：115200 8N1
+    pub const DEFAULT: SerialConfig = SerialConfig {
+        baudrate: 115_200,
+        parity: Parity::None,
+        stop_bits: StopBits::_1,
+    };
+
+    fn uart_config(&self) -> UartConfig {
+        UartConfig::default()
+            .with_baudrate(self.baudrate)
+            .with_parity(self.parity)
+            .with_stop_bits(self.stop_bits)
+    }
+}
+
+/// 异步串口
+pub struct Serial {
+    /// 日志中使用的名称
+    name: &'static str,
+    rx: UartRx<'static, Async>,
+    tx: UartTx<'static, Async>,
+    /// 已从 UART 读出、尚未交给调用者的数据
+    buf: [u8; RX_BUF_LEN],
+    len: usize,
+}
+
+impl Serial {
+    /// 创建串口
+    ///
+    /// # 参数
+    /// * `name` - 日志中使用的名称，例如 "gps"
+    /// * `uart` - UART 外设（UART1 或 UART2）
+    /// * `tx`, `rx` - 发送和接收引脚
+    /// * `config` - 串口配置
+    pub fn new(
+        name: &'static str,
+        uart: impl uart::Instance + 'static,
+        tx: impl PeripheralOutput<'static>,
+        rx: impl PeripheralInput<'static>,
+        config: SerialConfig,
+    ) -> Result<Self, SerialError> {
+        let (rx, tx) = Uart::new(uart, config.uart_config())
+            .map_err(|_| SerialError::Config)?
+            .with_tx(tx)
+            .with_rx(rx)
+            .into_async()
+            .split();
+        Ok(Serial {
+            name,
+            rx,
+            tx,
+            buf: [0; RX_BUF_LEN],
+            len: 0,
+        })
+    }
+
+    /// 发送原始数据，等待全部写入发送 FIFO
+    pub async fn write(&mut self, mut data: &[u8]) -> Result<(), SerialError> {
+        if tap() {
+            debug!("{} tx: {=[u8]:02x}", self.name, data);
+        }
+        while !data.is_empty() {
+            let written = self
+                .tx
+                .write_async(data)
+                .await
+                .map_err(|_| SerialError::Transmit)?;
+            data = &data[written..];
+        }
+        Ok(())
+    }
+
+    /// 读取原始数据，至少读到 1 个字节才返回
+    ///
+    /// # 返回
+    /// 读取的字节数
+    pub async fn read(&mut self, out: &mut [u8]) -> Result<usize, SerialError> {
+        if self.len > 0 {
+            return Ok(self.take(out));
+        }
+        self.read_uart(out).await
+    }
+
+    /// 读取以 `delimiter` 结尾的一行
+    ///
+    /// # 参数
+    /// * `out` - 行数据缓冲区（不含分隔符）
+    /// * `delimiter` - 分隔符
+    ///
+    /// # 返回
+    /// 行长度
+    pub async fn read_line(&mut self, out: &mut [u8], delimiter: u8) -> Result<usize, SerialError> {
+        loop {
+            if let Some(pos) = self.buf[..self.len].iter().position(|&b| b == delimiter) {
+                let mut line = &self.buf[..pos];
+                if delimiter == b'\n' && line.last() == Some(&b'\r') {
+                    line = &line[..line.len() - 1];
+                }
+                let result = if line.len() <= out.len() {
+                    out[..line.len()].copy_from_slice(line);
+                    Ok(line.len())
+                } else {
+                    Err(SerialError::Overflow)
+                };
+                self.consume(pos + 1);
+                return result;
+            }
+
+            if self.len == RX_BUF_LEN {
+                warn!("{}: line longer than {} bytes, discarded", self.name, RX_BUF_LEN);
+                self.len = 0;
+                return Err(SerialError::Overflow);
+            }
+            self.fill().await?;
+        }
+    }
+
+    /// 从 UART 读取更多数据到内部缓冲区
+    async fn fill(&mut self) -> Result<(), SerialError> {
+        let mut chunk = [0u8; 64];
+        let free = (RX_BUF_LEN - self.len).min(chunk.len());
+        let read = self.read_uart(&mut chunk[..free]).await?;
+        self.buf[self.len..self.len + read].copy_from_slice(&chunk[..read]);
+        self.len += read;
+        Ok(())
+    }
+
+    /// 直接从 UART 读取，所有接收数据都经过这里记录日志
+    async fn read_uart(&mut self, out: &mut [u8]) -> Result<usize, SerialError> {
+        let read = self
+            .rx
+            .read_async(out)
+            .await
+            .map_err(|_| SerialError::Receive)?;
+        if tap() {
+            debug!("{} rx: {=[u8]:02x}", self.name, &out[..read]);
+        }
+        Ok(read)
+    }
+
+    /// 从内部缓冲区取出数据
+    fn take(&mut self, out: &mut [u8]) -> usize {
+        let len = self.len.min(out.len());
+        out[..len].copy_from_slice(&self.buf[..len]);
+        self.consume(len);
+        len
+    }
+
+    /// 丢弃内部缓冲区开头的 `count` 字节
+    fn consume(&mut self, count: usize) {
+        self.buf.copy_within(count..self.len, 0);
+        self.len -= count;
+    }
+}
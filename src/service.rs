@@ -0,0 +1,153 @@
+//! 运行时启停的服务
+//!
+//! 部分后台服务可以在运行中用命令行 `service <名称> start|stop`、MQTT 命令 `cmd/service`
+//! （见 [crate::mqtt]）或状态屏幕的服务页面（见 [crate::screens]）启动或停止，不需要重启。
+//! 每个服务仍由启动时创建的任务承载，任务在服务停止期间等待 [wait_started]，
+//! 运行时把服务主体交给 [run]：
+//!
+//! - 停止时服务主体的 future 被丢弃，其中的套接字、缓冲区和打开的文件随之释放，
+//!   对端连接被关闭
+//! - 重新启动时服务主体从头运行，重新绑定端口、分配缓冲区
+//!
+//! 服务主体自行返回（例如绑定端口失败）时视为停止，可以再次启动重试。
+//!
+//! 启停状态只在本次运行中有效，重启后所有服务都处于运行状态。
+//!
+//! 限制：embassy 的任务不能销毁，停止的服务仍占用任务槽和任务 future 的静态内存，
+//! 释放的只是服务运行时持有的资源。HTTP 服务器是远程管理的入口，不允许停止。
+
+use core::cell::Cell;
+use critical_section::Mutex;
+use defmt::{info, warn};
+use embassy_futures::select::{Either, select};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::signal::Signal;
+
+/// 可以启停的服务
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Service {
+    /// TF 卡数据记录（见 [crate::sdlog]）
+    Datalog,
+    /// Modbus TCP 服务器
+    Modbus,
+    /// SNMP 代理
+    Snmp,
+    /// 多块板子之间的设置同步
+    PeerSync,
+}
+
+impl Service {
+    /// 所有服务
+    pub const ALL: [Service; 4] = [
+        Service::Datalog,
+        Service::Modbus,
+        Service::Snmp,
+        Service::PeerSync,
+    ];
+
+    /// 服务名称，用于日志和命令行
+    pub const fn name(self) -> &'static str {
+        match self {
+            Service::Datalog => "datalog",
+            Service::Modbus => "modbus",
+            Service::Snmp => "snmp",
+            Service::PeerSync => "peersync",
+        }
+    }
+
+    /// 按名称查找服务
+    pub fn from_name(name: &str) -> Option<Service> {
+        Service::ALL
+            .into_iter()
+            .find(|service| service.name() == name)
+    }
+
+    /// 当前固件中是否包含该服务
+    pub const fn is_available(self) -> bool {
+        !matches!(self, Service::Datalog) || cfg!(feature = "sd")
+    }
+}
+
+/// 已停止的服务，第 n 位对应 [Service::ALL] 中的第 n 个服务
+static STOPPED: Mutex<Cell<u8>> = Mutex::new(Cell::new(0));
+
+/// 启停状态变化时通知对应的服务任务，下标与 [Service::ALL] 一致
+static CHANGED: [Signal<CriticalSectionRawMutex, ()>; Service::ALL.len()] =
+    [const { Signal::new() }; Service::ALL.len()];
+
+/// 服务是否处于运行状态
+pub fn is_running(service: Service) -> bool {
+    critical_section::with(|cs| STOPPED.borrow(cs).get() & (1 << service as u8) == 0)
+}
+
+/// 设置服务的启停状态
+///
+/// # 返回
+/// 状态有变化时返回 true
+fn set_running(service: Service, running: bool) -> bool {
+    let changed = critical_section::with(|cs| {
+        let cell = STOPPED.borrow(cs);
+        let stopped = cell.get();
+        let updated = if running {
+            stopped & !(1 << service as u8)
+        } else {
+            stopped | (1 << service as u8)
+        };
+        cell.set(updated);
+        updated != stopped
+    });
+    if changed {
+        CHANGED[service as usize].signal(());
+    }
+    changed
+}
+
+/// 启动服务
+///
+/// # 返回
+/// 服务原本已在运行时返回 false
+pub fn start(service: Service) -> bool {
+    let changed = set_running(service, true);
+    if changed {
+        info!("Service {} started", service.name());
+    }
+    changed
+}
+
+/// 停止服务，服务任务随后释放其资源
+///
+/// # 返回
+/// 服务原本已停止时返回 false
+pub fn stop(service: Service) -> bool {
+    let changed = set_running(service, false);
+    if changed {
+        info!("Service {} stopped", service.name());
+    }
+    changed
+}
+
+/// 等到服务处于运行状态
+pub async fn wait_started(service: Service) {
+    while !is_running(service) {
+        CHANGED[service as usize].wait().await;
+    }
+}
+
+/// 等到服务被停止
+async fn wait_stopped(service: Service) {
+    while is_running(service) {
+        CHANGED[service as usize].wait().await;
+    }
+}
+
+/// 运行服务主体，服务被停止时丢弃主体并返回
+///
+/// # 参数
+/// * `service` - 服务
+/// * `body` - 服务主体，丢弃时应释放其持有的所有资源
+pub async fn run(service: Service, body: impl Future<Output = ()>) {
+    if let Either::First(()) = select(body, wait_stopped(service)).await {
+        warn!("Service {} exited, marking it stopped", service.name());
+        set_running(service, false);
+    }
+}
@@ -0,0 +1,873 @@
+use crate::board::{Interface, MAX_INTERFACE_PINS, Wiring};
+use crate::error::{Context, Error};
+use crate::lin::{MAX_SCHEDULE_LEN, SCHEDULE_ENTRY_LEN};
+use crate::{secret, storage};
+use core::cell::RefCell;
+use critical_section::Mutex;
+use defmt::{info, warn};
+use drivers::st7789::Tuning;
+use heapless::{String, Vec};
+use proto::json::{Object, ToJson};
+
+/// 持久化设置
+///
+/// 设置以 TLV（标签 + 长度 + 值）格式编码后保存到 Flash 的设置扇区中，
+/// 解码时忽略未知标签，缺失的字段使用默认值，
+/// 因此新增字段不会导致旧固件保存的设置失效。
+///
+/// WiFi 密码、API Key、HTTP 访问令牌、PIN、MQTT 密码和 ESP-NOW 配对密钥加密后保存
+/// （见 [crate::secret]），只在内存中的副本里是明文。
+///
+/// 运行时的设置副本保存在 [SETTINGS] 中，修改后需调用 [save] 写回 Flash。
+static SETTINGS: Mutex<RefCell<Settings>> = Mutex::new(RefCell::new(Settings::DEFAULT));
+
+/// 设置编码缓冲区大小，包括加密字段增加的长度
+const SETTINGS_BUF_LEN: usize = 2048;
+
+/// 一个外接接口的接线编码后的长度：引脚和 4 字节参数
+const PORT_LEN: usize = MAX_INTERFACE_PINS + 4;
+
+/// 加密字段明文的最大长度
+const SECRET_LEN: usize = WIFI_PASSWORD_LEN;
+
+/// 字段标签定义
+mod tags {
+    pub const CAPABILITIES: u8 = 0x01;
+    pub const WIFI_SSID: u8 = 0x02;
+    pub const WIFI_PASSWORD: u8 = 0x03;
+    pub const CONSOLE: u8 = 0x04;
+    pub const LANGUAGE: u8 = 0x05;
+    pub const PROFILE: u8 = 0x06;
+    pub const FORECAST_PROVIDER: u8 = 0x07;
+    pub const FORECAST_LOCATION: u8 = 0x08;
+    pub const FORECAST_KEY: u8 = 0x09;
+    pub const KEYMAP: u8 = 0x0A;
+    pub const PHOTO_INTERVAL: u8 = 0x0B;
+    pub const PHOTO_TRANSITION: u8 = 0x0C;
+    pub const CLOCK_FACE: u8 = 0x0D;
+    pub const UTC_OFFSET: u8 = 0x0E;
+    pub const NIGHT_HOURS: u8 = 0x0F;
+    // 0x10 曾用于 Matter 配网信息，不再使用
+    pub const WEBHOOK_URL: u8 = 0x11;
+    pub const SYSLOG_SERVER: u8 = 0x12;
+    pub const SCHEDULE: u8 = 0x13;
+    pub const THEME: u8 = 0x14;
+    pub const ACCENT: u8 = 0x15;
+    pub const LCD_PANEL: u8 = 0x16;
+    pub const LCD_GAMMA: u8 = 0x17;
+    pub const RENDER_FPS: u8 = 0x18;
+    pub const WIFI_COUNTRY: u8 = 0x19;
+    pub const WIFI_PROFILE: u8 = 0x1A;
+    pub const HOSTNAME: u8 = 0x1B;
+    pub const WEBHOOK_FORMAT: u8 = 0x1C;
+    pub const DEVICE_NAME: u8 = 0x1D;
+    pub const DEVICE_LOCATION: u8 = 0x1E;
+    pub const DEVICE_TAGS: u8 = 0x1F;
+    pub const HTTP_TOKEN: u8 = 0x20;
+    pub const PIN: u8 = 0x21;
+    pub const SYNC_GROUP: u8 = 0x22;
+    pub const SYNC_STAMP: u8 = 0x23;
+    pub const CLOCK_DRIFT: u8 = 0x24;
+    pub const THERMOSTAT_SOURCE: u8 = 0x25;
+    pub const THERMOSTAT: u8 = 0x26;
+    pub const PID_SOURCE: u8 = 0x27;
+    pub const PID: u8 = 0x28;
+    pub const RELAY_PINS: u8 = 0x29;
+    pub const BOARD: u8 = 0x2A;
+    pub const POWER: u8 = 0x2B;
+    pub const MQTT_BROKER: u8 = 0x2C;
+    pub const MQTT_USER: u8 = 0x2D;
+    pub const MQTT_PASSWORD: u8 = 0x2E;
+    pub const MQTT_TOPIC: u8 = 0x2F;
+    pub const ESPNOW_PEER: u8 = 0x30;
+    pub const BOARD_PORTS: u8 = 0x31;
+    pub const CAN_FILTER: u8 = 0x32;
+    pub const MODBUS_UNIT: u8 = 0x33;
+    pub const BRIDGE: u8 = 0x34;
+    pub const LIN_SCHEDULE: u8 = 0x35;
+    pub const LIN_CHECKSUM: u8 = 0x36;
+    pub const SHADOW_VERSION: u8 = 0x37;
+    pub const MQTT_SAS_KEY: u8 = 0x38;
+    pub const THINGSPEAK_KEY: u8 = 0x39;
+}
+
+/// WiFi SSID 最大长度
+pub const WIFI_SSID_LEN: usize = 32;
+
+/// WiFi 密码最大长度
+pub const WIFI_PASSWORD_LEN: usize = 64;
+
+/// 最多保存的 WiFi 网络数量
+pub const WIFI_PROFILES_MAX: usize = 4;
+
+/// 主机名最大长度（embassy-net 的 DHCP 主机名上限）
+pub const HOSTNAME_LEN: usize = 32;
+
+/// 设备名称最大长度
+pub const DEVICE_NAME_LEN: usize = 32;
+
+/// 设备安装位置最大长度
+pub const DEVICE_LOCATION_LEN: usize = 32;
+
+/// 设备标签最大长度（逗号分隔）
+pub const DEVICE_TAGS_LEN: usize = 64;
+
+/// HTTP 访问令牌最大长度
+pub const HTTP_TOKEN_LEN: usize = 32;
+
+/// 命令行 PIN 最大长度
+pub const PIN_LEN: usize = 8;
+
+/// 设置同步组名最大长度
+pub const SYNC_GROUP_LEN: usize = 16;
+
+/// 恒温控制器温度读数名称最大长度
+pub const THERMOSTAT_SOURCE_LEN: usize = 16;
+
+/// PID 回路过程量读数名称最大长度
+pub const PID_SOURCE_LEN: usize = 16;
+
+/// 天气预报位置最大长度
+pub const FORECAST_LOCATION_LEN: usize = 32;
+
+/// 天气预报 API Key 最大长度
+pub const FORECAST_KEY_LEN: usize = 64;
+
+/// ThingSpeak 写入 Key 最大长度
+pub const THINGSPEAK_KEY_LEN: usize = 16;
+
+/// 告警 webhook 地址最大长度
+pub const WEBHOOK_URL_LEN: usize = 96;
+
+/// syslog 收集器地址最大长度
+pub const SYSLOG_SERVER_LEN: usize = 64;
+
+/// MQTT 代理地址最大长度
+pub const MQTT_BROKER_LEN: usize = 64;
+
+/// MQTT 用户名最大长度
+pub const MQTT_USER_LEN: usize = 32;
+
+/// MQTT 密码最大长度
+pub const MQTT_PASSWORD_LEN: usize = 64;
+
+/// MQTT SAS 密钥（Base64）最大长度
+pub const MQTT_SAS_KEY_LEN: usize = 64;
+
+/// MQTT 基础主题最大长度
+pub const MQTT_TOPIC_LEN: usize = 48;
+
+/// 最多保存的 ESP-NOW 配对对端数量
+pub const ESPNOW_PEERS_MAX: usize = 4;
+
+/// ESP-NOW 配对密钥长度
+pub const ESPNOW_KEY_LEN: usize = 16;
+
+/// 定时任务规则最大长度
+pub const SCHEDULE_LEN: usize = 160;
+
+/// 一个保存的 WiFi 网络，见 [crate::wifi::profiles]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WifiProfile {
+    pub ssid: String<WIFI_SSID_LEN>,
+    pub password: String<WIFI_PASSWORD_LEN>,
+    /// 优先级，越大越先尝试
+    pub priority: u8,
+    /// 最近一次连接成功的序号，越大越近，0 表示从未连接成功
+    pub last_success: u32,
+}
+
+/// 配对的 ESP-NOW 对端，见 [crate::espnow]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EspNowPeer {
+    pub mac: [u8; 6],
+    /// 双方配对时协商的密钥，用作驱动的本地主密钥（LMK）
+    pub key: [u8; ESPNOW_KEY_LEN],
+}
+
+/// 设置内容
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Settings {
+    /// 启用的子系统位图，见 [crate::capability::Capability]
+    pub capabilities: u8,
+    /// 保存的 WiFi 网络，为空时使用编译时的 `WIFI_SSID` / `WIFI_PASSWORD` 环境变量
+    pub wifi_profiles: Vec<WifiProfile, WIFI_PROFILES_MAX>,
+    /// 控制台后端，0 表示编译时默认值，见 [crate::console::Backend]
+    pub console: u8,
+    /// 界面语言，0 为英文，1 为中文
+    pub language: u8,
+    /// 应用模式，0 为默认的状态屏幕，见 [crate::profile::Profile]
+    pub profile: u8,
+    /// 天气预报服务商，见 [crate::forecast::Provider]
+    pub forecast_provider: u8,
+    /// 天气预报位置 `<纬度>,<经度>`，为空时不下载预报
+    pub forecast_location: String<FORECAST_LOCATION_LEN>,
+    /// 天气预报服务商的 API Key
+    pub forecast_key: String<FORECAST_KEY_LEN>,
+    /// 气象读数上传到的 ThingSpeak 频道写入 Key，为空时不上传，见 [crate::thingspeak]
+    pub thingspeak_key: String<THINGSPEAK_KEY_LEN>,
+    /// 每个动作对应的按键编码，见 [crate::keymap]
+    pub keymap: [u8; 4],
+    /// 数码相框切换图片的间隔（秒）
+    pub photo_interval: u16,
+    /// 数码相框的过渡效果，见 [crate::photo::Transition]
+    pub photo_transition: u8,
+    /// 时钟表盘，见 [crate::clock::Face]
+    pub clock_face: u8,
+    /// 本地时间与 UTC 之差（分钟）
+    pub utc_offset: i16,
+    /// 时钟夜间模式的开始和结束小时（本地时间），两者相等时不启用
+    pub night_hours: [u8; 2],
+    /// 告警通知的 webhook 地址，为空时不发送，见 [crate::notifier]
+    pub webhook_url: String<WEBHOOK_URL_LEN>,
+    /// 告警通知请求体的编码，见 [crate::notifier::Format]
+    pub webhook_format: u8,
+    /// syslog 收集器 `<host>[:<port>]`，为空时不转发日志，见 [crate::syslog]
+    pub syslog_server: String<SYSLOG_SERVER_LEN>,
+    /// MQTT 代理 `<host>[:<port>]`，为空时不连接，见 [crate::mqtt]
+    pub mqtt_broker: String<MQTT_BROKER_LEN>,
+    /// MQTT 用户名，为空时不认证
+    pub mqtt_user: String<MQTT_USER_LEN>,
+    /// MQTT 密码
+    pub mqtt_password: String<MQTT_PASSWORD_LEN>,
+    /// MQTT 基础主题，为空时为 `esp-app-4/<主机名>`
+    pub mqtt_topic: String<MQTT_TOPIC_LEN>,
+    /// IoT Hub 设备密钥（Base64），不为空时用 SAS 令牌认证，见 [crate::sas]
+    pub mqtt_sas_key: String<MQTT_SAS_KEY_LEN>,
+    /// 配对的 ESP-NOW 对端，按配对的先后排列
+    pub espnow_peers: Vec<EspNowPeer, ESPNOW_PEERS_MAX>,
+    /// 定时任务规则，为空时不执行，见 [crate::scheduler]
+    pub schedule: String<SCHEDULE_LEN>,
+    /// 界面配色，见 [crate::theme::Mode]
+    pub theme: u8,
+    /// 强调色（RGB565），0 表示使用配色自带的强调色
+    pub accent: u16,
+    /// LCD 预设 Gamma 曲线，见 [crate::tuning::Curve]
+    pub lcd_curve: u8,
+    /// LCD 对比度（VRHS 参数）
+    pub lcd_contrast: u8,
+    /// LCD 是否开启颜色反转
+    pub lcd_inverted: bool,
+    /// LCD 正极性 Gamma 校正表
+    pub lcd_positive_gamma: [u8; 14],
+    /// LCD 负极性 Gamma 校正表
+    pub lcd_negative_gamma: [u8; 14],
+    /// 渲染任务动画的目标帧率，见 [crate::render]
+    pub render_fps: u8,
+    /// WiFi 国家代码（ISO 3166），决定允许的信道，见 [crate::wifi::channel_plan]
+    pub wifi_country: [u8; 2],
+    /// WiFi 扫描和连接的最低、最高信道，与国家允许的信道取交集
+    pub wifi_channels: [u8; 2],
+    /// DHCP 主机名，为空时由 MAC 地址生成，见 [crate::net::hostname]
+    pub hostname: String<HOSTNAME_LEN>,
+    /// 设备名称，为空时使用主机名，见 [crate::device]
+    pub device_name: String<DEVICE_NAME_LEN>,
+    /// 设备安装位置
+    pub device_location: String<DEVICE_LOCATION_LEN>,
+    /// 设备标签，逗号分隔
+    pub device_tags: String<DEVICE_TAGS_LEN>,
+    /// HTTP 访问令牌，为空时不需要认证，见 [crate::access]
+    pub http_token: String<HTTP_TOKEN_LEN>,
+    /// 命令行 PIN，为空时不锁定
+    pub pin: String<PIN_LEN>,
+    /// 设置同步组名，为空时不与其他板子同步，见 [crate::peersync]
+    pub sync_group: String<SYNC_GROUP_LEN>,
+    /// 共享设置的修改时间（Unix 秒）
+    pub sync_stamp: u32,
+    /// 测得的单调时钟频率偏差（ppb），0 表示未测量，见 [crate::wallclock]
+    pub clock_drift_ppb: i32,
+    /// 恒温控制器使用的温度读数，为空时不控制，见 [crate::thermostat]
+    pub thermostat_source: String<THERMOSTAT_SOURCE_LEN>,
+    /// 恒温控制方式，见 [crate::thermostat::Mode]
+    pub thermostat_mode: u8,
+    /// 恒温设定值（0.1 °C）
+    pub thermostat_setpoint: i16,
+    /// 恒温回差（0.1 °C）
+    pub thermostat_hysteresis: u8,
+    /// 继电器吸合、释放后的最短保持时间（秒）
+    pub thermostat_min_on: u16,
+    pub thermostat_min_off: u16,
+    /// PID 回路的过程量读数，为空时不控制，见 [crate::pid]
+    pub pid_source: String<PID_SOURCE_LEN>,
+    /// PID 设定值和增益（千分之一）
+    pub pid_setpoint: i32,
+    pub pid_kp: i32,
+    pub pid_ki: i32,
+    pub pid_kd: i32,
+    /// PID 控制周期（毫秒）
+    pub pid_period: u16,
+    /// 继电器输出的引脚编码，0 表示未分配，见 [crate::relay::Pin]
+    pub relay_pins: [u8; 4],
+    /// 开发板型号，见 [crate::board::Variant]
+    pub board: u8,
+    /// 自定义型号装有的可选外设位图，见 [crate::board::OPTIONAL]
+    pub board_present: u8,
+    /// 自定义型号的引脚表，见 [crate::board::Signal]
+    pub board_pins: [u8; 9],
+    /// 扩展排针上外接接口的接线，见 [crate::board::Interface]
+    pub board_ports: [Wiring; Interface::ALL.len()],
+    /// CAN 验收滤波器的编码，见 [crate::can::Filter::to_bytes]
+    pub can_filter: [u8; 9],
+    /// Modbus RTU 从站地址（1-247），见 [crate::modbus]
+    pub modbus_unit: u8,
+    /// 串口透传的编码，见 [crate::bridge::Setup::to_bytes]
+    pub bridge: [u8; 8],
+    /// LIN 调度表各项的编码，见 [crate::lin::ScheduleEntry::to_bytes]
+    pub lin_schedule: Vec<[u8; SCHEDULE_ENTRY_LEN], MAX_SCHEDULE_LEN>,
+    /// LIN 从节点使用经典校验和（LIN 1.x），否则为增强校验和
+    pub lin_classic: bool,
+    /// 最后应用的设备影子期望状态版本，见 [crate::shadow]
+    pub shadow_version: u32,
+    /// 启动时负载上电的间隔（毫秒），见 [crate::power]
+    pub power_stagger: u16,
+    /// 同时上电的负载冲击电流预算（mA）
+    pub power_budget: u16,
+}
+
+impl Settings {
+    /// 出厂默认设置
+    pub const DEFAULT: Settings = Settings {
+        capabilities: crate::capability::Capabilities::DEFAULT.bits(),
+        wifi_profiles: Vec::new(),
+        console: 0,
+        language: 0,
+        profile: 0,
+        forecast_provider: 0,
+        forecast_location: String::new(),
+        forecast_key: String::new(),
+        thingspeak_key: String::new(),
+        keymap: crate::keymap::DEFAULT,
+        photo_interval: 10,
+        photo_transition: 0,
+        clock_face: 0,
+        utc_offset: 0,
+        night_hours: [22, 7],
+        webhook_url: String::new(),
+        webhook_format: 0,
+        syslog_server: String::new(),
+        mqtt_broker: String::new(),
+        mqtt_user: String::new(),
+        mqtt_password: String::new(),
+        mqtt_topic: String::new(),
+        mqtt_sas_key: String::new(),
+        espnow_peers: Vec::new(),
+        schedule: String::new(),
+        theme: 0,
+        accent: 0,
+        lcd_curve: 0,
+        lcd_contrast: Tuning::DEFAULT.contrast,
+        lcd_inverted: Tuning::DEFAULT.inverted,
+        lcd_positive_gamma: Tuning::DEFAULT.positive_gamma,
+        lcd_negative_gamma: Tuning::DEFAULT.negative_gamma,
+        render_fps: 20,
+        wifi_country: *b"CN",
+        wifi_channels: [1, 13],
+        hostname: String::new(),
+        device_name: String::new(),
+        device_location: String::new(),
+        device_tags: String::new(),
+        http_token: String::new(),
+        pin: String::new(),
+        sync_group: String::new(),
+        sync_stamp: 0,
+        clock_drift_ppb: 0,
+        thermostat_source: String::new(),
+        thermostat_mode: 0,
+        thermostat_setpoint: 200,
+        thermostat_hysteresis: 5,
+        thermostat_min_on: 60,
+        thermostat_min_off: 60,
+        pid_source: String::new(),
+        pid_setpoint: 0,
+        pid_kp: 1000,
+        pid_ki: 0,
+        pid_kd: 0,
+        pid_period: 1000,
+        relay_pins: [0; 4],
+        board: 0,
+        board_present: crate::board::ALL_PRESENT,
+        board_pins: crate::board::PinMap::DNESP32S3.0,
+        board_ports: [Wiring::NONE; Interface::ALL.len()],
+        can_filter: crate::can::Filter::AcceptAll.to_bytes(),
+        modbus_unit: 1,
+        bridge: crate::bridge::Setup::OFF,
+        lin_schedule: Vec::new(),
+        lin_classic: false,
+        shadow_version: 0,
+        power_stagger: 150,
+        power_budget: 300,
+    };
+
+    /// 将设置编码为 TLV 字节流
+    ///
+    /// # 返回
+    /// 编码后的长度
+    fn encode(&self, buf: &mut [u8]) -> usize {
+        let mut writer = TlvWriter { buf, pos: 0 };
+        writer.put(tags::CAPABILITIES, &[self.capabilities]);
+        writer.put(tags::CONSOLE, &[self.console]);
+        writer.put(tags::LANGUAGE, &[self.language]);
+        writer.put(tags::PROFILE, &[self.profile]);
+        writer.put(tags::FORECAST_PROVIDER, &[self.forecast_provider]);
+        writer.put(tags::FORECAST_LOCATION, self.forecast_location.as_bytes());
+        writer.put_secret(tags::FORECAST_KEY, &self.forecast_key);
+        writer.put_secret(tags::THINGSPEAK_KEY, &self.thingspeak_key);
+        writer.put(tags::KEYMAP, &self.keymap);
+        writer.put(tags::PHOTO_INTERVAL, &self.photo_interval.to_le_bytes());
+        writer.put(tags::PHOTO_TRANSITION, &[self.photo_transition]);
+        writer.put(tags::CLOCK_FACE, &[self.clock_face]);
+        writer.put(tags::UTC_OFFSET, &self.utc_offset.to_le_bytes());
+        writer.put(tags::NIGHT_HOURS, &self.night_hours);
+        writer.put(tags::WEBHOOK_URL, self.webhook_url.as_bytes());
+        writer.put(tags::WEBHOOK_FORMAT, &[self.webhook_format]);
+        writer.put(tags::SYSLOG_SERVER, self.syslog_server.as_bytes());
+        writer.put(tags::MQTT_BROKER, self.mqtt_broker.as_bytes());
+        writer.put(tags::MQTT_USER, self.mqtt_user.as_bytes());
+        writer.put_secret(tags::MQTT_PASSWORD, &self.mqtt_password);
+        writer.put(tags::MQTT_TOPIC, self.mqtt_topic.as_bytes());
+        writer.put_secret(tags::MQTT_SAS_KEY, &self.mqtt_sas_key);
+        writer.put(tags::SCHEDULE, self.schedule.as_bytes());
+        writer.put(tags::THEME, &[self.theme]);
+        writer.put(tags::ACCENT, &self.accent.to_le_bytes());
+        let panel = [self.lcd_curve, self.lcd_contrast, self.lcd_inverted as u8];
+        writer.put(tags::LCD_PANEL, &panel);
+        let mut gamma = [0u8; 28];
+        gamma[..14].copy_from_slice(&self.lcd_positive_gamma);
+        gamma[14..].copy_from_slice(&self.lcd_negative_gamma);
+        writer.put(tags::LCD_GAMMA, &gamma);
+        writer.put(tags::RENDER_FPS, &[self.render_fps]);
+        let [first, last] = self.wifi_channels;
+        let [c0, c1] = self.wifi_country;
+        writer.put(tags::WIFI_COUNTRY, &[c0, c1, first, last]);
+        writer.put(tags::HOSTNAME, self.hostname.as_bytes());
+        writer.put(tags::DEVICE_NAME, self.device_name.as_bytes());
+        writer.put(tags::DEVICE_LOCATION, self.device_location.as_bytes());
+        writer.put(tags::DEVICE_TAGS, self.device_tags.as_bytes());
+        writer.put_secret(tags::HTTP_TOKEN, &self.http_token);
+        writer.put_secret(tags::PIN, &self.pin);
+        writer.put(tags::SYNC_GROUP, self.sync_group.as_bytes());
+        writer.put(tags::SYNC_STAMP, &self.sync_stamp.to_le_bytes());
+        writer.put(tags::CLOCK_DRIFT, &self.clock_drift_ppb.to_le_bytes());
+        writer.put(tags::THERMOSTAT_SOURCE, self.thermostat_source.as_bytes());
+        let mut thermostat = [0u8; 8];
+        thermostat[0] = self.thermostat_mode;
+        thermostat[1..3].copy_from_slice(&self.thermostat_setpoint.to_le_bytes());
+        thermostat[3] = self.thermostat_hysteresis;
+        thermostat[4..6].copy_from_slice(&self.thermostat_min_on.to_le_bytes());
+        thermostat[6..].copy_from_slice(&self.thermostat_min_off.to_le_bytes());
+        writer.put(tags::THERMOSTAT, &thermostat);
+        writer.put(tags::PID_SOURCE, self.pid_source.as_bytes());
+        let mut pid = [0u8; 18];
+        let values = [self.pid_setpoint, self.pid_kp, self.pid_ki, self.pid_kd];
+        for (chunk, value) in pid.chunks_exact_mut(4).zip(values) {
+            chunk.copy_from_slice(&value.to_le_bytes());
+        }
+        pid[16..].copy_from_slice(&self.pid_period.to_le_bytes());
+        writer.put(tags::PID, &pid);
+        writer.put(tags::RELAY_PINS, &self.relay_pins);
+        let mut board = [0u8; 11];
+        board[0] = self.board;
+        board[1] = self.board_present;
+        board[2..].copy_from_slice(&self.board_pins);
+        writer.put(tags::BOARD, &board);
+        let mut ports = [0u8; PORT_LEN * Interface::ALL.len()];
+        for (chunk, wiring) in ports.chunks_exact_mut(PORT_LEN).zip(&self.board_ports) {
+            chunk[..MAX_INTERFACE_PINS].copy_from_slice(&wiring.pins);
+            chunk[MAX_INTERFACE_PINS..].copy_from_slice(&wiring.param.to_le_bytes());
+        }
+        writer.put(tags::BOARD_PORTS, &ports);
+        writer.put(tags::CAN_FILTER, &self.can_filter);
+        writer.put(tags::MODBUS_UNIT, &[self.modbus_unit]);
+        writer.put(tags::BRIDGE, &self.bridge);
+        let mut schedule = Vec::<u8, { SCHEDULE_ENTRY_LEN * MAX_SCHEDULE_LEN }>::new();
+        for entry in &self.lin_schedule {
+            schedule.extend_from_slice(entry).ok();
+        }
+        writer.put(tags::LIN_SCHEDULE, &schedule);
+        writer.put(tags::LIN_CHECKSUM, &[self.lin_classic as u8]);
+        writer.put(tags::SHADOW_VERSION, &self.shadow_version.to_le_bytes());
+        let [s0, s1] = self.power_stagger.to_le_bytes();
+        let [b0, b1] = self.power_budget.to_le_bytes();
+        writer.put(tags::POWER, &[s0, s1, b0, b1]);
+        for profile in &self.wifi_profiles {
+            let mut value = [0u8; 6 + WIFI_SSID_LEN + WIFI_PASSWORD_LEN + secret::OVERHEAD];
+            let ssid = profile.ssid.as_bytes();
+            value[0] = profile.priority;
+            value[1..5].copy_from_slice(&profile.last_success.to_le_bytes());
+            value[5] = ssid.len() as u8;
+            value[6..6 + ssid.len()].copy_from_slice(ssid);
+            let password = &mut value[6 + ssid.len()..];
+            let len = 6 + ssid.len() + secret::seal(profile.password.as_bytes(), password);
+            writer.put(tags::WIFI_PROFILE, &value[..len]);
+        }
+        for peer in &self.espnow_peers {
+            let mut value = [0u8; 6 + ESPNOW_KEY_LEN + secret::OVERHEAD];
+            value[..6].copy_from_slice(&peer.mac);
+            let len = 6 + secret::seal(&peer.key, &mut value[6..]);
+            writer.put(tags::ESPNOW_PEER, &value[..len]);
+        }
+        writer.pos
+    }
+
+    /// 从 TLV 字节流解码设置
+    fn decode(data: &[u8]) -> Settings {
+        let mut settings = Settings::DEFAULT;
+        // 旧版本只保存一个网络，没有网络列表时迁移过来
+        let mut legacy = WifiProfile {
+            ssid: String::new(),
+            password: String::new(),
+            priority: 0,
+            last_success: 0,
+        };
+        let mut pos = 0;
+        while pos + 2 <= data.len() {
+            let tag = data[pos];
+            let len = data[pos + 1] as usize;
+            pos += 2;
+            if pos + len > data.len() {
+                warn!("Truncated settings field {}", tag);
+                break;
+            }
+            let value = &data[pos..pos + len];
+            pos += len;
+
+            match tag {
+                tags::CAPABILITIES if len == 1 => settings.capabilities = value[0],
+                tags::WIFI_SSID => legacy.ssid = decode_str(value),
+                tags::WIFI_PASSWORD => legacy.password = decode_str(value),
+                tags::CONSOLE if len == 1 => settings.console = value[0],
+                tags::LANGUAGE if len == 1 => settings.language = value[0],
+                tags::PROFILE if len == 1 => settings.profile = value[0],
+                tags::FORECAST_PROVIDER if len == 1 => settings.forecast_provider = value[0],
+                tags::FORECAST_LOCATION => settings.forecast_location = decode_str(value),
+                tags::FORECAST_KEY => settings.forecast_key = decode_secret(value),
+                tags::THINGSPEAK_KEY => settings.thingspeak_key = decode_secret(value),
+                tags::KEYMAP if len == 4 => settings.keymap.copy_from_slice(value),
+                tags::PHOTO_INTERVAL if len == 2 => {
+                    settings.photo_interval = u16::from_le_bytes([value[0], value[1]])
+                }
+                tags::PHOTO_TRANSITION if len == 1 => settings.photo_transition = value[0],
+                tags::CLOCK_FACE if len == 1 => settings.clock_face = value[0],
+                tags::UTC_OFFSET if len == 2 => {
+                    settings.utc_offset = i16::from_le_bytes([value[0], value[1]])
+                }
+                tags::NIGHT_HOURS if len == 2 => settings.night_hours.copy_from_slice(value),
+                tags::WEBHOOK_URL => settings.webhook_url = decode_str(value),
+                tags::WEBHOOK_FORMAT if len == 1 => settings.webhook_format = value[0],
+                tags::SYSLOG_SERVER => settings.syslog_server = decode_str(value),
+                tags::MQTT_BROKER => settings.mqtt_broker = decode_str(value),
+                tags::MQTT_USER => settings.mqtt_user = decode_str(value),
+                tags::MQTT_PASSWORD => settings.mqtt_password = decode_secret(value),
+                tags::MQTT_TOPIC => settings.mqtt_topic = decode_str(value),
+                tags::MQTT_SAS_KEY => settings.mqtt_sas_key = decode_secret(value),
+                tags::SCHEDULE => settings.schedule = decode_str(value),
+                tags::THEME if len == 1 => settings.theme = value[0],
+                tags::ACCENT if len == 2 => {
+                    settings.accent = u16::from_le_bytes([value[0], value[1]])
+                }
+                tags::LCD_PANEL if len == 3 => {
+                    settings.lcd_curve = value[0];
+                    settings.lcd_contrast = value[1];
+                    settings.lcd_inverted = value[2] != 0;
+                }
+                tags::LCD_GAMMA if len == 28 => {
+                    settings.lcd_positive_gamma.copy_from_slice(&value[..14]);
+                    settings.lcd_negative_gamma.copy_from_slice(&value[14..]);
+                }
+                tags::RENDER_FPS if len == 1 => settings.render_fps = value[0],
+                tags::WIFI_COUNTRY if len == 4 => {
+                    settings.wifi_country.copy_from_slice(&value[..2]);
+                    settings.wifi_channels.copy_from_slice(&value[2..]);
+                }
+                tags::HOSTNAME => settings.hostname = decode_str(value),
+                tags::DEVICE_NAME => settings.device_name = decode_str(value),
+                tags::DEVICE_LOCATION => settings.device_location = decode_str(value),
+                tags::DEVICE_TAGS => settings.device_tags = decode_str(value),
+                tags::HTTP_TOKEN => settings.http_token = decode_secret(value),
+                tags::PIN => settings.pin = decode_secret(value),
+                tags::SYNC_GROUP => settings.sync_group = decode_str(value),
+                tags::SYNC_STAMP if len == 4 => {
+                    settings.sync_stamp =
+                        u32::from_le_bytes([value[0], value[1], value[2], value[3]])
+                }
+                tags::CLOCK_DRIFT if len == 4 => {
+                    settings.clock_drift_ppb =
+                        i32::from_le_bytes([value[0], value[1], value[2], value[3]])
+                }
+                tags::THERMOSTAT_SOURCE => settings.thermostat_source = decode_str(value),
+                tags::THERMOSTAT if len == 8 => {
+                    settings.thermostat_mode = value[0];
+                    settings.thermostat_setpoint = i16::from_le_bytes([value[1], value[2]]);
+                    settings.thermostat_hysteresis = value[3];
+                    settings.thermostat_min_on = u16::from_le_bytes([value[4], value[5]]);
+                    settings.thermostat_min_off = u16::from_le_bytes([value[6], value[7]]);
+                }
+                tags::PID_SOURCE => settings.pid_source = decode_str(value),
+                tags::PID if len == 18 => {
+                    let word = |i: usize| {
+                        i32::from_le_bytes([value[i], value[i + 1], value[i + 2], value[i + 3]])
+                    };
+                    settings.pid_setpoint = word(0);
+                    settings.pid_kp = word(4);
+                    settings.pid_ki = word(8);
+                    settings.pid_kd = word(12);
+                    settings.pid_period = u16::from_le_bytes([value[16], value[17]]);
+                }
+                tags::RELAY_PINS if len == 4 => settings.relay_pins.copy_from_slice(value),
+                tags::BOARD if len == 11 => {
+                    settings.board = value[0];
+                    settings.board_present = value[1];
+                    settings.board_pins.copy_from_slice(&value[2..]);
+                }
+                // 旧固件的接口较少，新固件的接口较多，只取双方都有的部分
+                tags::BOARD_PORTS if len.is_multiple_of(PORT_LEN) => {
+                    let chunks = value.chunks_exact(PORT_LEN);
+                    for (wiring, chunk) in settings.board_ports.iter_mut().zip(chunks) {
+                        wiring.pins.copy_from_slice(&chunk[..MAX_INTERFACE_PINS]);
+                        let param = &chunk[MAX_INTERFACE_PINS..];
+                        wiring.param = u32::from_le_bytes([param[0], param[1], param[2], param[3]]);
+                    }
+                }
+                tags::CAN_FILTER if len == 9 => settings.can_filter.copy_from_slice(value),
+                tags::MODBUS_UNIT if len == 1 => settings.modbus_unit = value[0],
+                tags::BRIDGE if len == 8 => settings.bridge.copy_from_slice(value),
+                tags::LIN_SCHEDULE if len.is_multiple_of(SCHEDULE_ENTRY_LEN) => {
+                    for chunk in value.chunks_exact(SCHEDULE_ENTRY_LEN) {
+                        let mut entry = [0u8; SCHEDULE_ENTRY_LEN];
+                        entry.copy_from_slice(chunk);
+                        settings.lin_schedule.push(entry).ok();
+                    }
+                }
+                tags::LIN_CHECKSUM if len == 1 => settings.lin_classic = value[0] != 0,
+                tags::SHADOW_VERSION if len == 4 => {
+                    settings.shadow_version =
+                        u32::from_le_bytes([value[0], value[1], value[2], value[3]])
+                }
+                tags::POWER if len == 4 => {
+                    settings.power_stagger = u16::from_le_bytes([value[0], value[1]]);
+                    settings.power_budget = u16::from_le_bytes([value[2], value[3]]);
+                }
+                tags::WIFI_PROFILE if len >= 6 && value[5] as usize <= len - 6 => {
+                    let (ssid, password) = value[6..].split_at(value[5] as usize);
+                    let profile = WifiProfile {
+                        ssid: decode_str(ssid),
+                        password: decode_secret(password),
+                        priority: value[0],
+                        last_success: u32::from_le_bytes([value[1], value[2], value[3], value[4]]),
+                    };
+                    if !profile.ssid.is_empty() {
+                        settings.wifi_profiles.push(profile).ok();
+                    }
+                }
+                tags::ESPNOW_PEER if len > 6 => {
+                    let mut peer = EspNowPeer {
+                        mac: [0; 6],
+                        key: [0; ESPNOW_KEY_LEN],
+                    };
+                    peer.mac.copy_from_slice(&value[..6]);
+                    match secret::open(&value[6..], &mut peer.key) {
+                        Some(ESPNOW_KEY_LEN) => {
+                            settings.espnow_peers.push(peer).ok();
+                        }
+                        _ => warn!("Failed to decrypt an ESP-NOW pairing key, dropping the peer"),
+                    }
+                }
+                _ => {}
+            }
+        }
+        if settings.wifi_profiles.is_empty() && !legacy.ssid.is_empty() {
+            settings.wifi_profiles.push(legacy).ok();
+        }
+        settings
+    }
+}
+
+/// 解码字符串字段，非法 UTF-8 或超长时返回空字符串
+fn decode_str<const N: usize>(value: &[u8]) -> String<N> {
+    core::str::from_utf8(value)
+        .ok()
+        .and_then(|text| String::try_from(text).ok())
+        .unwrap_or_default()
+}
+
+/// 解码加密的字符串字段，无法解密时（例如设置来自另一块芯片）返回空字符串
+fn decode_secret<const N: usize>(value: &[u8]) -> String<N> {
+    let mut plain = [0u8; SECRET_LEN];
+    match secret::open(value, &mut plain) {
+        Some(len) => decode_str(&plain[..len]),
+        None => {
+            warn!("Failed to decrypt a stored secret, clearing it");
+            String::new()
+        }
+    }
+}
+
+impl defmt::Format for Settings {
+    fn format(&self, fmt: defmt::Formatter) {
+        // 不在日志中输出 WiFi 密码和 API Key
+        let mask = |secret: &str| if secret.is_empty() { "<unset>" } else { "***" };
+        defmt::write!(
+            fmt,
+            "Settings {{ capabilities: {=u8:#x}, wifi_profiles: {=usize}, console: {=u8}, language: {=u8}, profile: {=u8}, forecast: {=u8} {=str} {=str} }}",
+            self.capabilities,
+            self.wifi_profiles.len(),
+            self.console,
+            self.language,
+            self.profile,
+            self.forecast_provider,
+            self.forecast_location.as_str(),
+            mask(&self.forecast_key)
+        )
+    }
+}
+
+impl ToJson for Settings {
+    /// 各字段按原值写出，不含 WiFi 密码、API Key 和写入 Key、访问令牌、PIN、MQTT 密码和 SAS 密钥、
+    /// ESP-NOW 配对和 LCD 调校参数
+    fn write_members(&self, object: &mut Object<'_>) {
+        let country = core::str::from_utf8(&self.wifi_country).unwrap_or("");
+        object
+            .int("capabilities", self.capabilities as i64)
+            .int("console", self.console as i64)
+            .int("language", self.language as i64)
+            .int("profile", self.profile as i64)
+            .int("board", self.board as i64)
+            .int("board_present", self.board_present as i64)
+            .int("power_stagger", self.power_stagger as i64)
+            .int("power_budget", self.power_budget as i64)
+            .int("theme", self.theme as i64)
+            .int("accent", self.accent as i64)
+            .int("forecast_provider", self.forecast_provider as i64)
+            .str("forecast_location", &self.forecast_location)
+            .int("photo_interval", self.photo_interval as i64)
+            .int("photo_transition", self.photo_transition as i64)
+            .int("clock_face", self.clock_face as i64)
+            .int("utc_offset", self.utc_offset as i64)
+            .str("webhook_url", &self.webhook_url)
+            .int("webhook_format", self.webhook_format as i64)
+            .str("syslog_server", &self.syslog_server)
+            .str("mqtt_broker", &self.mqtt_broker)
+            .str("mqtt_user", &self.mqtt_user)
+            .str("mqtt_topic", &self.mqtt_topic)
+            .str("schedule", &self.schedule)
+            .int("render_fps", self.render_fps as i64)
+            .str("wifi_country", country)
+            .str("hostname", &self.hostname)
+            .str("device_name", &self.device_name)
+            .str("device_location", &self.device_location)
+            .str("device_tags", &self.device_tags)
+            .str("sync_group", &self.sync_group)
+            .str("thermostat_source", &self.thermostat_source)
+            .str("pid_source", &self.pid_source);
+        let arrays = [
+            ("keymap", &self.keymap[..]),
+            ("night_hours", &self.night_hours[..]),
+            ("wifi_channels", &self.wifi_channels[..]),
+            ("relay_pins", &self.relay_pins[..]),
+            ("board_pins", &self.board_pins[..]),
+        ];
+        for (key, values) in arrays {
+            let mut array = object.array(key);
+            for &value in values {
+                array.int(value as i64);
+            }
+        }
+        // WiFi 网络只写出名称和优先级
+        let mut profiles = object.array("wifi_profiles");
+        for profile in &self.wifi_profiles {
+            profiles
+                .object()
+                .str("ssid", &profile.ssid)
+                .int("priority", profile.priority as i64);
+        }
+    }
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings::DEFAULT
+    }
+}
+
+/// TLV 编码辅助结构
+struct TlvWriter<'a> {
+    buf: &'a mut [u8],
+    pos: usize,
+}
+
+impl TlvWriter<'_> {
+    /// 写入一个字段，缓冲区不足时丢弃该字段
+    fn put(&mut self, tag: u8, value: &[u8]) {
+        let len = value.len().min(u8::MAX as usize);
+        if self.pos + 2 + len > self.buf.len() {
+            warn!("Settings buffer full, dropping field {}", tag);
+            return;
+        }
+        self.buf[self.pos] = tag;
+        self.buf[self.pos + 1] = len as u8;
+        self.buf[self.pos + 2..self.pos + 2 + len].copy_from_slice(&value[..len]);
+        self.pos += 2 + len;
+    }
+
+    /// 加密后写入一个字段，空字符串写为空值
+    fn put_secret(&mut self, tag: u8, value: &str) {
+        let mut sealed = [0u8; SECRET_LEN + secret::OVERHEAD];
+        let len = secret::seal(value.as_bytes(), &mut sealed);
+        self.put(tag, &sealed[..len]);
+    }
+}
+
+/// 从 Flash 加载设置
+///
+/// 设置不存在或校验失败时使用默认设置
+///
+/// # 返回
+/// 设置是否从 Flash 成功读取（false 表示首次启动或设置已损坏）
+pub fn load() -> bool {
+    let mut buf = [0u8; SETTINGS_BUF_LEN];
+    let (settings, found) = match storage::read_blob(storage::SETTINGS_OFFSET, &mut buf) {
+        Ok(len) => (Settings::decode(&buf[..len]), true),
+        Err(err) => {
+            info!("No stored settings ({}), using defaults", err);
+            (Settings::DEFAULT, false)
+        }
+    };
+    info!("Settings loaded: {}", settings);
+    critical_section::with(|cs| {
+        *SETTINGS.borrow_ref_mut(cs) = settings;
+    });
+    found
+}
+
+/// 将当前设置写回 Flash
+pub fn save() -> Result<(), Error> {
+    let settings = get();
+    let mut buf = [0u8; SETTINGS_BUF_LEN];
+    let len = settings.encode(&mut buf);
+    storage::write_blob(storage::SETTINGS_OFFSET, &buf[..len]).context("save settings")
+}
+
+/// 擦除 Flash 中保存的设置（恢复出厂设置）
+///
+/// 内存中的设置不变，重启后按首次启动处理
+pub fn erase() -> Result<(), Error> {
+    storage::erase_blob(storage::SETTINGS_OFFSET).context("erase settings")
+}
+
+/// 获取当前设置的副本
+pub fn get() -> Settings {
+    critical_section::with(|cs| SETTINGS.borrow_ref(cs).clone())
+}
+
+/// 通过闭包修改当前设置（不会自动保存）
+///
+/// # 参数
+/// * `f` - 闭包函数，接受设置的可变引用作为参数
+pub fn update<F>(f: F)
+where
+    F: FnOnce(&mut Settings),
+{
+    critical_section::with(|cs| {
+        f(&mut SETTINGS.borrow_ref_mut(cs));
+    });
+}
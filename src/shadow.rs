@@ -0,0 +1,222 @@
+//! 设备影子
+//!
+//! 通过 MQTT（见 [crate::mqtt]）同步一份 JSON 状态文档：云端发布期望状态，设备应用后报告实际状态。
+//!
+//! - `<基础主题>/shadow/desired`：订阅的期望状态，云端应以保留消息发布，
+//!   例如 `{"version":3,"state":{"setpoint":21.5}}`
+//! - `<基础主题>/shadow/reported`：发布的实际状态（保留消息），包含全部字段和已应用的版本，
+//!   例如 `{"version":3,"state":{"setpoint":21.5,"utc_offset":480,"night_hours":[22,7],"schedule":""}}`
+//!
+//! 状态中的字段：
+//!
+//! - `setpoint`：恒温设定值（°C，一位小数），见 [crate::thermostat]
+//! - `utc_offset`：本地时间与 UTC 之差（分钟）
+//! - `night_hours`：时钟夜间模式的 `[开始小时, 结束小时]`，两者相等时不启用
+//! - `schedule`：定时任务规则，与命令行 `schedule` 相同，见 [crate::scheduler]
+//!
+//! 期望状态可以只包含部分字段，未包含的字段不变；无效的字段被跳过，其余字段照常应用。
+//! 报告的始终是实际生效的值，云端比较两份文档就能发现被拒绝的字段。
+//!
+//! 冲突处理：设备保存最后应用的期望状态版本。每次连接后先发布实际状态，代理随后转发保留的期望状态，
+//! 只有版本大于已应用的版本时才应用。离线期间在本机（命令行、HTTP 或按键）做的修改因此不会被
+//! 重连时收到的旧期望状态覆盖，而是通过实际状态报告给云端；云端要改回时发布更高版本的期望状态。
+//! 本机的修改在下一次 MQTT 保活时报告。
+
+use crate::scheduler;
+use crate::settings::{self, SCHEDULE_LEN, Settings};
+use crate::thermostat;
+use alloc::string::String as AllocString;
+use core::cell::RefCell;
+use critical_section::Mutex;
+use defmt::{info, warn};
+use heapless::String;
+use proto::json::{self, Object};
+
+/// 期望状态的子主题
+pub const DESIRED: &str = "shadow/desired";
+
+/// 实际状态的子主题
+pub const REPORTED: &str = "shadow/reported";
+
+/// 时区偏移的范围（分钟），与命令行 `clock offset` 相同
+const UTC_OFFSET_RANGE: core::ops::RangeInclusive<i16> = -14 * 60..=14 * 60;
+
+/// 最近一次报告的状态，用于发现本机的修改
+static LAST_REPORTED: Mutex<RefCell<Option<State>>> = Mutex::new(RefCell::new(None));
+
+/// 影子同步的状态
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct State {
+    /// 已应用的期望状态版本
+    version: u32,
+    /// 恒温设定值（0.1 °C）
+    setpoint: i16,
+    utc_offset: i16,
+    night_hours: [u8; 2],
+    schedule: String<SCHEDULE_LEN>,
+}
+
+impl State {
+    fn from_settings(s: &Settings) -> Self {
+        State {
+            version: s.shadow_version,
+            setpoint: s.thermostat_setpoint,
+            utc_offset: s.utc_offset,
+            night_hours: s.night_hours,
+            schedule: s.schedule.clone(),
+        }
+    }
+
+    /// 编码为实际状态文档
+    fn to_json(&self) -> AllocString {
+        let mut body = AllocString::new();
+        {
+            let mut document = Object::new(&mut body);
+            document.int("version", self.version as i64);
+            let mut state = document.object("state");
+            state
+                .number("setpoint", self.setpoint as f64 / 10.0)
+                .int("utc_offset", self.utc_offset as i64);
+            {
+                let mut night_hours = state.array("night_hours");
+                for hour in self.night_hours {
+                    night_hours.int(hour as i64);
+                }
+            }
+            state.str("schedule", &self.schedule);
+        }
+        body
+    }
+}
+
+/// 当前的实际状态文档，连接代理后发布
+pub fn report() -> AllocString {
+    let state = State::from_settings(&settings::get());
+    let body = state.to_json();
+    critical_section::with(|cs| *LAST_REPORTED.borrow_ref_mut(cs) = Some(state));
+    body
+}
+
+/// 本机修改过状态时返回新的实际状态文档
+pub fn report_changed() -> Option<AllocString> {
+    let state = State::from_settings(&settings::get());
+    let changed = critical_section::with(|cs| {
+        LAST_REPORTED
+            .borrow_ref(cs)
+            .as_ref()
+            .is_some_and(|last| *last != state)
+    });
+    changed.then(report)
+}
+
+/// 应用云端发布的期望状态并保存
+///
+/// 版本不大于已应用的版本时忽略，见模块说明
+///
+/// # 参数
+/// * `payload` - 期望状态文档
+pub fn apply_desired(payload: &[u8]) {
+    let document = core::str::from_utf8(payload)
+        .ok()
+        .and_then(|text| json::parse(text).ok());
+    let Some(document) = document else {
+        warn!("Shadow desired state is not valid JSON");
+        return;
+    };
+    let version = document.get("version").and_then(integer);
+    let Some(version) = version
+        .and_then(|v| u32::try_from(v).ok())
+        .filter(|&v| v > 0)
+    else {
+        warn!("Shadow desired state has no valid version");
+        return;
+    };
+    let applied = settings::get().shadow_version;
+    if version <= applied {
+        info!(
+            "Shadow desired version {} not newer than {}",
+            version, applied
+        );
+        return;
+    }
+
+    let mut s = settings::get();
+    let state = document.get("state");
+    for (name, value) in state.iter().flat_map(|state| state.members()) {
+        if !apply_field(&mut s, name, value) {
+            warn!("Shadow field {} rejected", name);
+        }
+    }
+    settings::update(|current| {
+        current.thermostat_setpoint = s.thermostat_setpoint;
+        current.utc_offset = s.utc_offset;
+        current.night_hours = s.night_hours;
+        current.schedule = s.schedule;
+        current.shadow_version = version;
+    });
+    if let Err(err) = settings::save() {
+        warn!("Failed to save shadow state: {}", err);
+    }
+    info!("Shadow desired version {} applied", version);
+}
+
+/// 把一个期望状态字段写入设置
+///
+/// # 返回
+/// 字段未知或值无效时返回 false
+fn apply_field(s: &mut Settings, name: &str, value: json::Value<'_>) -> bool {
+    match name {
+        "setpoint" => {
+            let Some(celsius) = value.as_f64() else {
+                return false;
+            };
+            // 四舍五入到 0.1 °C
+            let tenths = (celsius * 10.0 + 0.5 * celsius.signum()) as i16;
+            let valid = thermostat::SETPOINT_RANGE.contains(&tenths);
+            if valid {
+                s.thermostat_setpoint = tenths;
+            }
+            valid
+        }
+        "utc_offset" => {
+            let Some(offset) = integer(value).and_then(|v| i16::try_from(v).ok()) else {
+                return false;
+            };
+            let valid = UTC_OFFSET_RANGE.contains(&offset);
+            if valid {
+                s.utc_offset = offset;
+            }
+            valid
+        }
+        "night_hours" => {
+            let mut hours = value.items().map(|hour| {
+                integer(hour)
+                    .and_then(|v| u8::try_from(v).ok())
+                    .filter(|&h| h < 24)
+            });
+            let (Some(Some(from)), Some(Some(to)), None) =
+                (hours.next(), hours.next(), hours.next())
+            else {
+                return false;
+            };
+            s.night_hours = [from, to];
+            true
+        }
+        "schedule" => {
+            let rules = value.as_str().and_then(|raw| json::unescape(raw).ok());
+            let rules = rules.filter(|rules| scheduler::parse(rules).is_ok());
+            let Some(rules) = rules.and_then(|rules| String::try_from(rules.as_str()).ok()) else {
+                return false;
+            };
+            s.schedule = rules;
+            true
+        }
+        _ => false,
+    }
+}
+
+/// 取出整数值，不是整数时返回 None
+fn integer(value: json::Value<'_>) -> Option<i64> {
+    let number = value.as_f64()?;
+    (number as i64 as f64 == number).then_some(number as i64)
+}
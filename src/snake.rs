@@ -0,0 +1,501 @@
+//! 贪吃蛇小游戏
+//!
+//! [Profile::Game](crate::profile::Profile::Game) 模式下代替渲染任务占用 LCD。
+//! 游戏以固定的 30 帧/秒运行，每帧只重绘变化的格子（蛇头、蛇尾和食物），
+//! 格子用 [St7789::blit] 整块写入精灵图。蛇头只存一张朝上的精灵图，按移动方向旋转后写入，
+//! 得分栏的食物图标是放大两倍的食物精灵图（见 [St7789::blit_transformed]）。
+//! 每帧的唤醒延迟记录在 [crate::jitter] 的 `game` 一项中，也可作为 LCD 传输和 APP_CPU 调度的压力测试（命令行 `jitter`）。
+//!
+//! 按键（独占，KEY1 不再切换背光）：KEY0 右转，KEY1 左转，KEY2 开始/暂停，KEY3 重新开始。
+
+use crate::assets;
+use crate::i18n::{self, Msg};
+use crate::input::{self, Key};
+use crate::jitter;
+use crate::lcd::Lcd;
+use crate::st7789::{self, Rotation, St7789, Transform};
+use core::fmt::Write;
+use defmt::{info, warn};
+use embassy_time::{Duration, Instant, Timer};
+use embedded_graphics::mono_font::{MonoTextStyle, MonoTextStyleBuilder};
+use embedded_graphics::pixelcolor::Rgb565;
+use embedded_graphics::pixelcolor::raw::RawU16;
+use embedded_graphics::prelude::*;
+use embedded_graphics::text::Text;
+use esp_hal::rng::Rng;
+use heapless::{Deque, String};
+
+/// 帧周期（30 帧/秒）
+const FRAME: Duration = Duration::from_micros(33_333);
+
+/// 格子边长（像素）
+const CELL: u16 = 10;
+
+/// 游戏区域的列数和行数，每行的占用情况用一个 u32 位图表示
+const COLS: usize = 32;
+const ROWS: usize = 22;
+
+/// 游戏区域上边界，上方为得分栏
+const FIELD_Y: u16 = 20;
+
+/// 得分栏文字基线位置
+const HUD_BASELINE: i32 = 15;
+
+/// 蛇的最大长度
+const MAX_LEN: usize = COLS * ROWS;
+
+/// 开始时每移动一格的帧数，每吃 [SPEEDUP_EVERY] 个食物减少一帧，最少 [MIN_STEP_FRAMES] 帧
+const START_STEP_FRAMES: u32 = 6;
+const MIN_STEP_FRAMES: u32 = 2;
+const SPEEDUP_EVERY: u32 = 5;
+
+/// 蛇的初始长度
+const START_LEN: u8 = 3;
+
+const HUD_BACKGROUND: Rgb565 = Rgb565::CSS_DARK_SLATE_GRAY;
+
+/// 一个格子大小的 RGB565 精灵图（大端）
+type Sprite = [u8; CELL as usize * CELL as usize * 2];
+
+/// 格子坐标（列，行）
+type Cell = (u8, u8);
+
+/// 精灵图形状，每行一个位图，最高位在左
+const BODY_MASK: [u16; CELL as usize] = [
+    0b0000000000,
+    0b0011111100,
+    0b0111111110,
+    0b0111111110,
+    0b0111111110,
+    0b0111111110,
+    0b0111111110,
+    0b0111111110,
+    0b0011111100,
+    0b0000000000,
+];
+/// 蛇头朝上，两个空位是眼睛
+const HEAD_MASK: [u16; CELL as usize] = [
+    0b0011111100,
+    0b0111111110,
+    0b1111111111,
+    0b1110110111,
+    0b1111111111,
+    0b1111111111,
+    0b1111111111,
+    0b1111111111,
+    0b0111111110,
+    0b0111111110,
+];
+const FOOD_MASK: [u16; CELL as usize] = [
+    0b0000010000,
+    0b0000100000,
+    0b0011111100,
+    0b0111111110,
+    0b0111111110,
+    0b0111111110,
+    0b0111111110,
+    0b0011111100,
+    0b0001111000,
+    0b0000000000,
+];
+
+/// 游戏状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    /// 等待开始
+    Ready,
+    Playing,
+    Paused,
+    /// 撞到墙或自己，或者蛇已占满整个区域
+    Over,
+}
+
+/// 移动方向
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Direction {
+    Up,
+    Right,
+    Down,
+    Left,
+}
+
+impl Direction {
+    const fn turn_right(self) -> Direction {
+        match self {
+            Direction::Up => Direction::Right,
+            Direction::Right => Direction::Down,
+            Direction::Down => Direction::Left,
+            Direction::Left => Direction::Up,
+        }
+    }
+
+    const fn turn_left(self) -> Direction {
+        self.turn_right().turn_right().turn_right()
+    }
+
+    /// 朝上的蛇头精灵图转到这个方向的角度
+    const fn rotation(self) -> Rotation {
+        match self {
+            Direction::Up => Rotation::Deg0,
+            Direction::Right => Rotation::Deg90,
+            Direction::Down => Rotation::Deg180,
+            Direction::Left => Rotation::Deg270,
+        }
+    }
+
+    /// 相邻的格子，超出游戏区域时返回 None
+    fn advance(self, (x, y): Cell) -> Option<Cell> {
+        let (x, y) = match self {
+            Direction::Up => (x, y.checked_sub(1)?),
+            Direction::Right => (x + 1, y),
+            Direction::Down => (x, y + 1),
+            Direction::Left => (x.checked_sub(1)?, y),
+        };
+        (usize::from(x) < COLS && usize::from(y) < ROWS).then_some((x, y))
+    }
+}
+
+/// 移动一格的结果
+enum Step {
+    /// 移动了一格，`tail` 为空出的蛇尾格子
+    Moved { tail: Option<Cell> },
+    /// 吃到食物，蛇变长一格
+    Ate,
+    /// 游戏结束
+    Over,
+}
+
+/// 蛇和食物
+struct Game {
+    /// 蛇身各格，最前面是蛇头
+    body: Deque<Cell, MAX_LEN>,
+    /// 每行被蛇身占用的格子位图
+    occupied: [u32; ROWS],
+    direction: Direction,
+    /// 下一步的方向，一步之内只接受一次转向
+    next_direction: Direction,
+    food: Cell,
+    score: u32,
+}
+
+impl Game {
+    fn new() -> Self {
+        let mut game = Game {
+            body: Deque::new(),
+            occupied: [0; ROWS],
+            direction: Direction::Right,
+            next_direction: Direction::Right,
+            food: (0, 0),
+            score: 0,
+        };
+        let row = (ROWS / 2) as u8;
+        for x in 1..=START_LEN {
+            game.body.push_front((x, row)).ok();
+            game.set_occupied((x, row), true);
+        }
+        game.place_food();
+        game
+    }
+
+    fn head(&self) -> Cell {
+        self.body.front().copied().unwrap_or_default()
+    }
+
+    fn is_occupied(&self, (x, y): Cell) -> bool {
+        self.occupied[y as usize] & (1 << x) != 0
+    }
+
+    fn set_occupied(&mut self, (x, y): Cell, occupied: bool) {
+        if occupied {
+            self.occupied[y as usize] |= 1 << x;
+        } else {
+            self.occupied[y as usize] &= !(1 << x);
+        }
+    }
+
+    /// 在随机的空格子上放置食物
+    ///
+    /// # 返回
+    /// 没有空格子时返回 false
+    fn place_food(&mut self) -> bool {
+        let free = MAX_LEN - self.body.len();
+        if free == 0 {
+            return false;
+        }
+        let mut skip = Rng::new().random() as usize % free;
+        for y in 0..ROWS as u8 {
+            for x in 0..COLS as u8 {
+                if self.is_occupied((x, y)) {
+                    continue;
+                }
+                if skip == 0 {
+                    self.food = (x, y);
+                    return true;
+                }
+                skip -= 1;
+            }
+        }
+        false
+    }
+
+    fn turn(&mut self, right: bool) {
+        self.next_direction = if right {
+            self.direction.turn_right()
+        } else {
+            self.direction.turn_left()
+        };
+    }
+
+    /// 当前速度下每移动一格的帧数
+    fn step_frames(&self) -> u32 {
+        START_STEP_FRAMES
+            .saturating_sub(self.score / SPEEDUP_EVERY)
+            .max(MIN_STEP_FRAMES)
+    }
+
+    /// 向当前方向移动一格
+    fn step(&mut self) -> Step {
+        self.direction = self.next_direction;
+        let Some(next) = self.direction.advance(self.head()) else {
+            return Step::Over;
+        };
+        let eating = next == self.food;
+        // 先移走蛇尾，蛇头可以进入蛇尾刚空出的格子
+        let tail = if eating { None } else { self.body.pop_back() };
+        if let Some(tail) = tail {
+            self.set_occupied(tail, false);
+        }
+        if self.is_occupied(next) || self.body.push_front(next).is_err() {
+            return Step::Over;
+        }
+        self.set_occupied(next, true);
+
+        if !eating {
+            return Step::Moved { tail };
+        }
+        self.score += 1;
+        if self.place_food() {
+            Step::Ate
+        } else {
+            Step::Over
+        }
+    }
+}
+
+/// 精灵图
+struct Sprites {
+    /// 朝上的蛇头
+    head: Sprite,
+    body: Sprite,
+    food: Sprite,
+    /// 得分栏背景上的食物图标
+    score: Sprite,
+}
+
+impl Sprites {
+    fn new() -> Self {
+        Sprites {
+            head: sprite(&HEAD_MASK, Rgb565::CSS_LIME_GREEN, Rgb565::BLACK),
+            body: sprite(&BODY_MASK, Rgb565::CSS_FOREST_GREEN, Rgb565::BLACK),
+            food: sprite(&FOOD_MASK, Rgb565::RED, Rgb565::BLACK),
+            score: sprite(&FOOD_MASK, Rgb565::RED, HUD_BACKGROUND),
+        }
+    }
+}
+
+/// 按形状位图生成精灵图
+fn sprite(mask: &[u16; CELL as usize], color: Rgb565, background: Rgb565) -> Sprite {
+    let [hi, lo] = RawU16::from(color).into_inner().to_be_bytes();
+    let background = RawU16::from(background).into_inner().to_be_bytes();
+    let mut pixels = [0; CELL as usize * CELL as usize * 2];
+    for (row, bits) in mask.iter().enumerate() {
+        for col in 0..CELL as usize {
+            let i = (row * CELL as usize + col) * 2;
+            if bits & (1 << (CELL as usize - 1 - col)) != 0 {
+                pixels[i] = hi;
+                pixels[i + 1] = lo;
+            } else {
+                pixels[i..i + 2].copy_from_slice(&background);
+            }
+        }
+    }
+    pixels
+}
+
+/// 贪吃蛇任务
+///
+/// # 参数
+/// * `lcd` - 已完成初始化的 LCD
+#[embassy_executor::task]
+pub async fn game_task(mut lcd: Lcd) {
+    let Some(mut keys) = input::subscribe() else {
+        warn!("No key subscriber available for game");
+        return;
+    };
+    input::set_captured(true);
+
+    let sprites = Sprites::new();
+    let mut monitor = jitter::Monitor::new("game", FRAME);
+    let mut game = Game::new();
+    let mut state = State::Ready;
+    draw_field(&mut lcd, &game, &sprites);
+    let mut hud_dirty = true;
+    // 距离上一次移动经过的帧数
+    let mut frames = 0;
+    // 本局中绘制超出帧周期的次数
+    let mut overruns = 0u32;
+    let mut deadline = Instant::now();
+
+    loop {
+        while let Some(key) = keys.try_next_message_pure() {
+            state = match (state, key) {
+                (State::Playing, Key::Key0) => {
+                    game.turn(true);
+                    state
+                }
+                (State::Playing, Key::Key1) => {
+                    game.turn(false);
+                    state
+                }
+                (State::Playing, Key::Key2) => State::Paused,
+                (State::Ready | State::Paused, Key::Key2) => State::Playing,
+                (State::Over, Key::Key2) | (_, Key::Key3) => {
+                    game = Game::new();
+                    draw_field(&mut lcd, &game, &sprites);
+                    frames = 0;
+                    overruns = 0;
+                    if key == Key::Key2 {
+                        State::Playing
+                    } else {
+                        State::Ready
+                    }
+                }
+                (state, _) => state,
+            };
+            hud_dirty = true;
+        }
+
+        if state == State::Playing {
+            frames += 1;
+            if frames >= game.step_frames() {
+                frames = 0;
+                let neck = game.head();
+                match game.step() {
+                    Step::Moved { tail } => {
+                        if let Some(tail) = tail {
+                            draw_cell(&mut lcd, tail, None);
+                        }
+                    }
+                    Step::Ate => {
+                        draw_cell(&mut lcd, game.food, Some(&sprites.food));
+                        hud_dirty = true;
+                    }
+                    Step::Over => {
+                        info!(
+                            "Game over: score {}, {} frame overruns",
+                            game.score, overruns
+                        );
+                        state = State::Over;
+                        hud_dirty = true;
+                    }
+                }
+                if state == State::Playing {
+                    draw_cell(&mut lcd, neck, Some(&sprites.body));
+                    draw_head(&mut lcd, &game, &sprites);
+                }
+            }
+        }
+
+        if hud_dirty {
+            draw_hud(&mut lcd, &sprites, game.score, state);
+            hud_dirty = false;
+        }
+
+        // 固定帧率：绘制超时后从当前时刻重新计时，不追赶落下的帧
+        deadline += FRAME;
+        let now = Instant::now();
+        if now > deadline {
+            monitor.record(now - deadline);
+            if state == State::Playing {
+                overruns += 1;
+            }
+            deadline = now;
+        } else {
+            Timer::at(deadline).await;
+            monitor.record(Instant::now().saturating_duration_since(deadline));
+        }
+    }
+}
+
+/// 清空游戏区域后绘制整条蛇和食物
+fn draw_field(lcd: &mut St7789, game: &Game, sprites: &Sprites) {
+    let height = st7789::HEIGHT - FIELD_Y;
+    if let Err(err) = lcd.fill_rectangle(0, FIELD_Y, st7789::WIDTH, height, Rgb565::BLACK) {
+        warn!("Failed to clear game field: {}", err);
+    }
+    for &cell in game.body.iter().skip(1) {
+        draw_cell(lcd, cell, Some(&sprites.body));
+    }
+    draw_head(lcd, game, sprites);
+    draw_cell(lcd, game.food, Some(&sprites.food));
+}
+
+/// 格子左上角的屏幕坐标
+fn cell_origin((x, y): Cell) -> (u16, u16) {
+    (x as u16 * CELL, FIELD_Y + y as u16 * CELL)
+}
+
+/// 绘制一个格子，`sprite` 为 None 时清空
+fn draw_cell(lcd: &mut St7789, cell: Cell, sprite: Option<&Sprite>) {
+    let (x, y) = cell_origin(cell);
+    let result = match sprite {
+        Some(sprite) => lcd.blit(x, y, CELL, CELL, sprite),
+        None => lcd.fill_rectangle(x, y, CELL, CELL, Rgb565::BLACK),
+    };
+    if let Err(err) = result {
+        warn!("Failed to draw game cell: {}", err);
+    }
+}
+
+/// 绘制蛇头，朝上的精灵图按移动方向旋转
+fn draw_head(lcd: &mut St7789, game: &Game, sprites: &Sprites) {
+    let (x, y) = cell_origin(game.head());
+    let transform = Transform::rotate(game.direction.rotation());
+    if let Err(err) = lcd.blit_transformed(x, y, CELL, CELL, &sprites.head, transform) {
+        warn!("Failed to draw snake head: {}", err);
+    }
+}
+
+/// 绘制得分栏：左侧食物图标和得分，右侧状态提示
+fn draw_hud(lcd: &mut St7789, sprites: &Sprites, score: u32, state: State) {
+    let style: MonoTextStyle<'_, Rgb565> = MonoTextStyleBuilder::new()
+        .font(assets::font())
+        .text_color(Rgb565::WHITE)
+        .background_color(HUD_BACKGROUND)
+        .build();
+    lcd.fill_rectangle(0, 0, st7789::WIDTH, FIELD_Y, HUD_BACKGROUND)
+        .ok();
+
+    // 放大两倍正好与得分栏同高
+    let icon = Transform::IDENTITY.scaled(2);
+    lcd.blit_transformed(2, 0, CELL, CELL, &sprites.score, icon)
+        .ok();
+
+    let mut text: String<16> = String::new();
+    write!(text, "{} {}", i18n::lcd(Msg::GameScore), score).ok();
+    if let Err(err) = Text::new(&text, Point::new(26, HUD_BASELINE), style).draw(lcd) {
+        warn!("Failed to draw game text: {}", err);
+    }
+
+    let hint = match state {
+        State::Ready => Msg::GameStartHint,
+        State::Playing => return,
+        State::Paused => Msg::GamePaused,
+        State::Over => Msg::GameOver,
+    };
+    let hint = i18n::lcd(hint);
+    let x = st7789::WIDTH as i32 - 4 - hint.len() as i32 * 10;
+    Text::new(hint, Point::new(x, HUD_BASELINE), style)
+        .draw(lcd)
+        .ok();
+}
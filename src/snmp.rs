@@ -0,0 +1,176 @@
+//! SNMP 代理
+//!
+//! 在 UDP 161 端口提供只读的 SNMP v2c 代理，网络监控工具（snmpwalk、Zabbix、LibreNMS 等）
+//! 可以直接轮询本板。支持 GetRequest、GetNextRequest 和 GetBulkRequest，
+//! 团体名固定为 [COMMUNITY]，其他请求（包括 SetRequest）直接丢弃。报文的编解码见 [proto::snmp]。
+//!
+//! 对象表（`E` 为私有子树 1.3.6.1.4.1.[ENTERPRISE]）：
+//!
+//! | OID | 名称 | 类型 | 内容 |
+//! |-----|------|------|------|
+//! | 1.3.6.1.2.1.1.1.0 | sysDescr | OCTET STRING | 设备描述 |
+//! | 1.3.6.1.2.1.1.2.0 | sysObjectID | OBJECT IDENTIFIER | `E` |
+//! | 1.3.6.1.2.1.1.3.0 | sysUpTime | TimeTicks | 运行时间（0.01 秒） |
+//! | 1.3.6.1.2.1.1.5.0 | sysName | OCTET STRING | 设备名称（见 [crate::device]） |
+//! | 1.3.6.1.2.1.1.6.0 | sysLocation | OCTET STRING | 设备安装位置 |
+//! | E.1.1.0 | temperature | INTEGER | 温度（0.1 °C） |
+//! | E.1.2.0 | humidity | INTEGER | 相对湿度（0.1 %） |
+//! | E.1.3.0 | rssi | INTEGER | WiFi 信号强度（dBm） |
+//!
+//! 传感器读数来自 [crate::sensor]，没有读数时 Get 返回 noSuchInstance，GetNext 跳过该对象。
+//!
+//! 每个管理站每秒最多 [RATE] 个请求，超出的请求直接丢弃（见 [crate::ratelimit]），
+//! GetBulkRequest 的响应比请求大得多，不限速时可能被用来放大流量。
+
+use crate::netstats::{self, Link};
+use crate::ratelimit::RateLimiter;
+use crate::service::{self, Service};
+use crate::{device, sensor};
+use defmt::{debug, info, warn};
+use embassy_net::Stack;
+use embassy_net::udp::{PacketMetadata, UdpSocket};
+use embassy_time::Instant;
+use heapless::String;
+use proto::snmp::{self, Object, Value};
+
+/// 监听端口
+const PORT: u16 = 161;
+
+/// 只读团体名
+const COMMUNITY: &[u8] = b"public";
+
+/// 私有企业号，未向 IANA 注册，部署时可替换为自己的 PEN
+const ENTERPRISE: u32 = 99999;
+
+/// 设备描述
+const SYS_DESCR: &str = "esp-app-4 on ESP32-S3";
+
+/// 每个管理站每秒允许的请求数
+const RATE: u32 = 10;
+
+/// 每个管理站短时间内最多允许的连续请求数，snmpwalk 会连续发送请求
+const BURST: u32 = 50;
+
+/// 限速时记录的管理站数量
+const RATE_CLIENTS: usize = 4;
+
+/// 请求和响应报文的最大长度
+const PACKET_LEN: usize = 1024;
+
+/// sysObjectID 的值：私有子树的根
+const SYS_OBJECT_ID: &[u32] = &[1, 3, 6, 1, 4, 1, ENTERPRISE];
+
+/// 对象表，按 OID 升序排列
+const MIB: [Object; 8] = [
+    Object {
+        oid: &[1, 3, 6, 1, 2, 1, 1, 1, 0],
+        get: || text(SYS_DESCR),
+    },
+    Object {
+        oid: &[1, 3, 6, 1, 2, 1, 1, 2, 0],
+        get: || Some(Value::ObjectId(SYS_OBJECT_ID)),
+    },
+    Object {
+        oid: &[1, 3, 6, 1, 2, 1, 1, 3, 0],
+        get: || Some(Value::TimeTicks((Instant::now().as_millis() / 10) as u32)),
+    },
+    Object {
+        oid: &[1, 3, 6, 1, 2, 1, 1, 5, 0],
+        get: || text(&device::identity().name),
+    },
+    Object {
+        oid: &[1, 3, 6, 1, 2, 1, 1, 6, 0],
+        get: || text(&device::identity().location),
+    },
+    Object {
+        oid: &[1, 3, 6, 1, 4, 1, ENTERPRISE, 1, 1, 0],
+        get: || reading("bme280.temp", 10.0),
+    },
+    Object {
+        oid: &[1, 3, 6, 1, 4, 1, ENTERPRISE, 1, 2, 0],
+        get: || reading("bme280.hum", 10.0),
+    },
+    Object {
+        oid: &[1, 3, 6, 1, 4, 1, ENTERPRISE, 1, 3, 0],
+        get: || reading("wifi.rssi", 1.0),
+    },
+];
+
+/// 字符串值，超过 [snmp::OCTET_STRING_LEN] 字节的部分截断
+fn text(value: &str) -> Option<Value> {
+    let mut text = String::new();
+    for c in value.chars() {
+        if text.push(c).is_err() {
+            break;
+        }
+    }
+    Some(Value::OctetString(text))
+}
+
+/// 读取传感器读数，按 `scale` 缩放后四舍五入为整数
+fn reading(name: &str, scale: f64) -> Option<Value> {
+    let scaled = sensor::get(name)?.value * scale;
+    let rounded = scaled + if scaled < 0.0 { -0.5 } else { 0.5 };
+    Some(Value::Integer(rounded as i32))
+}
+
+/// SNMP 代理任务
+///
+/// 服务停止时关闭套接字（见 [crate::service]）
+///
+/// # 参数
+/// * `stack` - 网络协议栈
+#[embassy_executor::task]
+pub async fn server(stack: Stack<'static>) {
+    loop {
+        service::wait_started(Service::Snmp).await;
+        service::run(Service::Snmp, serve(stack)).await;
+    }
+}
+
+/// 绑定端口并应答请求，绑定失败时返回
+async fn serve(stack: Stack<'static>) {
+    let mut rx_meta = [PacketMetadata::EMPTY; 4];
+    let mut rx_buffer = [0u8; PACKET_LEN * 2];
+    let mut tx_meta = [PacketMetadata::EMPTY; 4];
+    let mut tx_buffer = [0u8; PACKET_LEN * 2];
+    let mut socket = UdpSocket::new(
+        stack,
+        &mut rx_meta,
+        &mut rx_buffer,
+        &mut tx_meta,
+        &mut tx_buffer,
+    );
+    if let Err(err) = socket.bind(PORT) {
+        warn!("Failed to bind SNMP port: {}", defmt::Debug2Format(&err));
+        return;
+    }
+    info!("SNMP agent listening on UDP port {}", PORT);
+
+    let mut request = [0u8; PACKET_LEN];
+    let mut response = [0u8; PACKET_LEN];
+    let mut limiter = RateLimiter::<RATE_CLIENTS>::new(RATE, BURST);
+    loop {
+        let (len, meta) = match socket.recv_from(&mut request).await {
+            Ok(received) => {
+                netstats::received(Link::Snmp, received.0);
+                received
+            }
+            Err(err) => {
+                warn!("SNMP receive failed: {}", defmt::Debug2Format(&err));
+                continue;
+            }
+        };
+        if !limiter.allow(meta.endpoint.addr) {
+            debug!("SNMP manager {} over rate limit", meta.endpoint.addr);
+            continue;
+        }
+        match snmp::handle(&request[..len], COMMUNITY, &MIB, &mut response) {
+            Ok(reply) => match socket.send_to(reply, meta).await {
+                Ok(()) => netstats::sent(Link::Snmp, reply.len()),
+                Err(err) => warn!("SNMP send failed: {}", defmt::Debug2Format(&err)),
+            },
+            Err(err) => debug!("Ignoring SNMP request: {}", err),
+        }
+    }
+}
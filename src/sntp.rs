@@ -0,0 +1,137 @@
+//! SNTP 时间同步
+//!
+//! [sntp_task] 联网后向 [NTP_SERVER] 发送 SNTP 请求（RFC 4330 客户端模式），
+//! 用应答中的发送时间戳加上一半往返时间校准系统时间（[TimeSource::Ntp]），
+//! 之后每 [SYNC_INTERVAL] 重新同步一次。
+
+use crate::netstats::{self, Link};
+use crate::wallclock::{self, TimeSource};
+use defmt::{info, warn};
+use embassy_net::Stack;
+use embassy_net::dns::DnsQueryType;
+use embassy_net::udp::{PacketMetadata, UdpSocket};
+use embassy_time::{Duration, Instant, Timer, with_timeout};
+
+/// NTP 服务器
+const NTP_SERVER: &str = "pool.ntp.org";
+
+/// NTP 端口
+const NTP_PORT: u16 = 123;
+
+/// 同步成功后的下次同步间隔
+const SYNC_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// 同步失败后的重试间隔
+const RETRY_INTERVAL: Duration = Duration::from_secs(60);
+
+/// 等待应答的超时
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// NTP 报文长度（不含扩展字段和认证信息）
+const PACKET_LEN: usize = 48;
+
+/// 1900-01-01（NTP 纪元）到 1970-01-01 的秒数
+const NTP_UNIX_OFFSET: u64 = 2_208_988_800;
+
+/// 请求报文首字节：LI = 0，VN = 4，Mode = 3（客户端）
+const CLIENT_REQUEST: u8 = 0x23;
+
+/// 应答模式：服务器
+const MODE_SERVER: u8 = 4;
+
+/// 同步错误
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum SntpError {
+    /// 服务器主机名解析失败
+    Dns,
+    /// 套接字绑定或收发失败
+    Socket,
+    /// 等待应答超时
+    Timeout,
+    /// 应答格式错误
+    Malformed,
+    /// 服务器自身未同步（闰秒指示为 3 或 stratum 为 0）
+    Unsynchronized,
+}
+
+/// 向服务器查询一次当前时间
+///
+/// # 返回
+/// 当前 UNIX 时间（微秒）
+async fn query(stack: Stack<'_>) -> Result<u64, SntpError> {
+    let address = *stack
+        .dns_query(NTP_SERVER, DnsQueryType::A)
+        .await
+        .map_err(|_| SntpError::Dns)?
+        .first()
+        .ok_or(SntpError::Dns)?;
+
+    let mut rx_meta = [PacketMetadata::EMPTY; 1];
+    let mut rx_buffer = [0u8; PACKET_LEN * 2];
+    let mut tx_meta = [PacketMetadata::EMPTY; 1];
+    let mut tx_buffer = [0u8; PACKET_LEN];
+    let mut socket = UdpSocket::new(
+        stack,
+        &mut rx_meta,
+        &mut rx_buffer,
+        &mut tx_meta,
+        &mut tx_buffer,
+    );
+    socket.bind(0).map_err(|_| SntpError::Socket)?;
+
+    let mut packet = [0u8; PACKET_LEN];
+    packet[0] = CLIENT_REQUEST;
+    let sent = Instant::now();
+    socket
+        .send_to(&packet, (address, NTP_PORT))
+        .await
+        .map_err(|_| SntpError::Socket)?;
+    netstats::sent(Link::Sntp, PACKET_LEN);
+
+    let (len, _) = with_timeout(RESPONSE_TIMEOUT, socket.recv_from(&mut packet))
+        .await
+        .map_err(|_| SntpError::Timeout)?
+        .map_err(|_| SntpError::Socket)?;
+    netstats::received(Link::Sntp, len);
+    let round_trip = Instant::now().saturating_duration_since(sent);
+
+    if len < PACKET_LEN || packet[0] & 0x07 != MODE_SERVER {
+        return Err(SntpError::Malformed);
+    }
+    if packet[0] >> 6 == 3 || packet[1] == 0 {
+        return Err(SntpError::Unsynchronized);
+    }
+
+    // 发送时间戳：32 位秒 + 32 位小数
+    let secs = u32::from_be_bytes([packet[40], packet[41], packet[42], packet[43]]) as u64;
+    let fraction = u32::from_be_bytes([packet[44], packet[45], packet[46], packet[47]]) as u64;
+    let unix_secs = secs
+        .checked_sub(NTP_UNIX_OFFSET)
+        .ok_or(SntpError::Malformed)?;
+    let micros = (fraction * 1_000_000) >> 32;
+    Ok(unix_secs * 1_000_000 + micros + round_trip.as_micros() / 2)
+}
+
+/// SNTP 同步任务
+///
+/// # 参数
+/// * `stack` - 网络协议栈
+#[embassy_executor::task]
+pub async fn sntp_task(stack: Stack<'static>) {
+    loop {
+        stack.wait_config_up().await;
+        let delay = match query(stack).await {
+            Ok(unix_us) => {
+                if wallclock::set(unix_us, TimeSource::Ntp) {
+                    info!("Time synchronized from {}", NTP_SERVER);
+                }
+                SYNC_INTERVAL
+            }
+            Err(err) => {
+                warn!("SNTP query failed: {}", err);
+                RETRY_INTERVAL
+            }
+        };
+        Timer::after(delay).await;
+    }
+}
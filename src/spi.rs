@@ -0,0 +1,264 @@
+use core::ops::{Deref, DerefMut};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::mutex::Mutex;
+#[cfg(any(feature = "sd", feature = "ui"))]
+use embassy_sync::mutex::MutexGuard;
+#[cfg(any(feature = "sd", feature = "ui"))]
+use embassy_time::Timer;
+#[cfg(any(feature = "sd", feature = "ui"))]
+use embedded_hal::delay::DelayNs;
+#[cfg(any(feature = "sd", feature = "ui"))]
+use embedded_hal::spi::{ErrorType, Operation};
+use esp_hal::Async;
+#[cfg(any(feature = "sd", feature = "ui"))]
+use esp_hal::delay::Delay;
+use esp_hal::dma::{DmaRxBuf, DmaTxBuf};
+use esp_hal::dma_buffers;
+#[cfg(any(feature = "sd", feature = "ui"))]
+use esp_hal::gpio::Output;
+use esp_hal::gpio::interconnect::{PeripheralInput, PeripheralOutput};
+use esp_hal::peripherals::{DMA_CH0, SPI2};
+#[cfg(any(feature = "sd", feature = "ui"))]
+use esp_hal::spi::Error as SpiError;
+use esp_hal::spi::Mode;
+use esp_hal::spi::master::{Config, Spi, SpiDmaBus};
+use esp_hal::time::Rate;
+use static_cell::StaticCell;
+
+/// 共享 SPI 总线
+///
+/// SPI2 总线同时连接 LCD 和 TF 卡（片选引脚见 [crate::board]），以异步 DMA 模式运行，
+/// 保存在 embassy 的异步互斥锁中。每个设备通过 [device] 获得一个带独立片选的 [SpiDevice]，
+/// 它同时实现了阻塞和异步的 SPI 设备接口：
+///
+/// - 异步传输（LCD 的初始化、填充和整块刷新）启动 DMA 后让出执行器，由 DMA 完成中断唤醒；
+///   等待期间只持有互斥锁，不关中断，其他任务照常运行
+/// - 阻塞传输（TF 卡，以及 LCD 的命令、读寄存器和逐点绘制）忙等互斥锁，拿到后等 DMA 完成
+///
+/// 阻塞传输忙等期间，持有锁的异步传输必须能继续运行。另一个核上的传输会自己完成，
+/// 同一个核上被挂起的传输却要等执行器再次轮询它，而执行器正忙于这次忙等。
+/// 因此同步代码在开始使用阻塞设备之前要先 `await` [wait_idle]，之后直到用完都不让出执行器，
+/// 这期间同一个核上就不会有传输挂在中间。
+pub type SpiBus = SpiDmaBus<'static, Async>;
+
+/// 共享总线的互斥锁类型
+pub type SharedSpiBus = Mutex<CriticalSectionRawMutex, Bus>;
+
+/// DMA 收发缓冲区大小（字节）
+pub const DMA_BUFFER_SIZE: usize = 32000;
+
+/// 默认总线时钟
+pub const DEFAULT_FREQUENCY: Rate = Rate::from_mhz(10);
+
+static SPI_BUS: StaticCell<SharedSpiBus> = StaticCell::new();
+
+/// 互斥锁中的总线
+pub struct Bus(SpiBus);
+
+// SAFETY: 异步模式的驱动不是 Send，因为 DMA 完成中断绑定在初始化总线的核（PRO_CPU）上。
+// 另一个核上启动的传输照样由这个中断结束，中断处理只唤醒等待的任务，
+// embassy 的唤醒可以跨核；总线本身只在互斥锁内使用，不会被两个核同时访问
+unsafe impl Send for Bus {}
+
+impl Deref for Bus {
+    type Target = SpiBus;
+
+    fn deref(&self) -> &SpiBus {
+        &self.0
+    }
+}
+
+impl DerefMut for Bus {
+    fn deref_mut(&mut self) -> &mut SpiBus {
+        &mut self.0
+    }
+}
+
+/// 初始化带 DMA 的 SPI 接口
+///
+/// 使用 SPI2 + DMA_CH0，SPI 模式 0，时钟 10MHz。
+/// 片选由各设备的 GPIO 软件控制，见 [device]。
+///
+/// # 参数
+/// * `spi` - SPI2 实例
+/// * `sck` - SPI 时钟线
+/// * `mosi` - SPI 主输出从输入线
+/// * `miso` - SPI 主输入从输出线
+/// * `dma_channel` - DMA 通道
+///
+/// # Panics
+///
+/// 当 DMA 缓冲区或 SPI 初始化失败，或重复初始化时会 panic
+pub fn init_with_dma(
+    spi: SPI2<'static>,
+    sck: impl PeripheralOutput<'static>,
+    mosi: impl PeripheralOutput<'static>,
+    miso: impl PeripheralInput<'static>,
+    dma_channel: DMA_CH0<'static>,
+) -> &'static SharedSpiBus {
+    let (rx_buffer, rx_descriptors, tx_buffer, tx_descriptors) = dma_buffers!(DMA_BUFFER_SIZE);
+
+    let dma_rx_buf = DmaRxBuf::new(rx_descriptors, rx_buffer).unwrap();
+
+    let dma_tx_buf = DmaTxBuf::new(tx_descriptors, tx_buffer).unwrap();
+
+    let bus = Spi::new(
+        spi,
+        Config::default()
+            .with_frequency(DEFAULT_FREQUENCY)
+            .with_mode(Mode::_0),
+    )
+    .expect("failed to initialize SPI")
+    .with_sck(sck)
+    .with_mosi(mosi)
+    .with_miso(miso)
+    .with_dma(dma_channel)
+    .with_buffers(dma_rx_buf, dma_tx_buf)
+    .into_async();
+
+    SPI_BUS.init(Mutex::new(Bus(bus)))
+}
+
+/// 在共享总线上创建一个设备
+///
+/// # 参数
+/// * `bus` - 共享总线
+/// * `cs` - 设备片选引脚（应以高电平初始化）
+#[cfg(any(feature = "sd", feature = "ui"))]
+pub fn device(bus: &'static SharedSpiBus, cs: Output<'static>) -> SpiDevice {
+    SpiDevice { bus, cs }
+}
+
+/// 等待总线空闲
+///
+/// 同步代码开始使用阻塞设备之前调用，之后直到用完都不能让出执行器，原因见 [SpiBus]
+#[cfg(feature = "sd")]
+pub async fn wait_idle(bus: &SharedSpiBus) {
+    drop(bus.lock().await);
+}
+
+/// 忙等并锁定总线，用于阻塞传输
+#[cfg(any(feature = "sd", feature = "ui"))]
+fn lock_blocking(bus: &SharedSpiBus) -> MutexGuard<'_, CriticalSectionRawMutex, Bus> {
+    loop {
+        if let Ok(guard) = bus.try_lock() {
+            return guard;
+        }
+        core::hint::spin_loop();
+    }
+}
+
+/// 在锁定的总线上执行阻塞操作，不经过任何设备的片选
+///
+/// 只能在 [wait_idle] 之后的同步代码中调用
+///
+/// # 参数
+/// * `bus` - 共享总线
+/// * `f` - 对总线的操作
+#[cfg(feature = "sd")]
+pub fn with_bus<R>(bus: &SharedSpiBus, f: impl FnOnce(&mut SpiBus) -> R) -> R {
+    f(&mut lock_blocking(bus))
+}
+
+/// 修改总线时钟频率
+///
+/// TF 卡初始化阶段要求时钟不高于 400kHz，完成后再恢复到 [DEFAULT_FREQUENCY]。
+/// 只能在 [wait_idle] 之后的同步代码中调用
+///
+/// # 参数
+/// * `bus` - 共享总线
+/// * `frequency` - 新的时钟频率
+#[cfg(feature = "sd")]
+pub fn set_frequency(bus: &SharedSpiBus, frequency: Rate) {
+    with_bus(bus, |bus| {
+        bus.apply_config(
+            &Config::default()
+                .with_frequency(frequency)
+                .with_mode(Mode::_0),
+        )
+        .ok();
+    });
+}
+
+/// 共享总线上的单个设备
+///
+/// 片选是普通 GPIO，设置电平不会失败，错误类型直接是底层的 SPI 错误
+#[cfg(any(feature = "sd", feature = "ui"))]
+pub struct SpiDevice {
+    bus: &'static SharedSpiBus,
+    cs: Output<'static>,
+}
+
+/// 传输结束（包括异步传输被中途取消）时释放片选
+#[cfg(any(feature = "sd", feature = "ui"))]
+struct Selected<'a>(&'a mut Output<'static>);
+
+#[cfg(any(feature = "sd", feature = "ui"))]
+impl<'a> Selected<'a> {
+    fn new(cs: &'a mut Output<'static>) -> Self {
+        cs.set_low();
+        Selected(cs)
+    }
+}
+
+#[cfg(any(feature = "sd", feature = "ui"))]
+impl Drop for Selected<'_> {
+    fn drop(&mut self) {
+        self.0.set_high();
+    }
+}
+
+#[cfg(any(feature = "sd", feature = "ui"))]
+impl ErrorType for SpiDevice {
+    type Error = SpiError;
+}
+
+/// 阻塞接口：忙等总线，在当前核上等待每次 DMA 传输完成
+#[cfg(any(feature = "sd", feature = "ui"))]
+impl embedded_hal::spi::SpiDevice for SpiDevice {
+    fn transaction(&mut self, operations: &mut [Operation<'_, u8>]) -> Result<(), SpiError> {
+        let mut bus = lock_blocking(self.bus);
+        let bus = &mut **bus;
+        let _selected = Selected::new(&mut self.cs);
+        for operation in operations {
+            match operation {
+                Operation::Read(words) => embedded_hal::spi::SpiBus::read(bus, words)?,
+                Operation::Write(words) => embedded_hal::spi::SpiBus::write(bus, words)?,
+                Operation::Transfer(read, write) => {
+                    embedded_hal::spi::SpiBus::transfer(bus, read, write)?
+                }
+                Operation::TransferInPlace(words) => {
+                    embedded_hal::spi::SpiBus::transfer_in_place(bus, words)?
+                }
+                Operation::DelayNs(ns) => Delay::new().delay_ns(*ns),
+            }
+        }
+        embedded_hal::spi::SpiBus::flush(bus)
+    }
+}
+
+/// 异步接口：等待总线和每次 DMA 传输完成时都让出执行器
+#[cfg(any(feature = "sd", feature = "ui"))]
+impl embedded_hal_async::spi::SpiDevice for SpiDevice {
+    async fn transaction(&mut self, operations: &mut [Operation<'_, u8>]) -> Result<(), SpiError> {
+        let mut bus = self.bus.lock().await;
+        let bus = &mut **bus;
+        let _selected = Selected::new(&mut self.cs);
+        for operation in operations {
+            match operation {
+                Operation::Read(words) => embedded_hal_async::spi::SpiBus::read(bus, words).await?,
+                Operation::Write(words) => {
+                    embedded_hal_async::spi::SpiBus::write(bus, words).await?
+                }
+                Operation::Transfer(read, write) => {
+                    embedded_hal_async::spi::SpiBus::transfer(bus, read, write).await?
+                }
+                Operation::TransferInPlace(words) => {
+                    embedded_hal_async::spi::SpiBus::transfer_in_place(bus, words).await?
+                }
+                Operation::DelayNs(ns) => Timer::after_nanos(u64::from(*ns)).await,
+            }
+        }
+        embedded_hal_async::spi::SpiBus::flush(bus).await
+    }
+}
@@ -0,0 +1,170 @@
+//! ST7789 LCD 控制器
+//!
+//! 驱动逻辑在 [drivers::st7789] 中，可在主机上测试；本模块把它接到共享 SPI 总线上。
+//!
+//! 驱动 ATK-MD0240 模块上的 ST7789 控制器（2.4 英寸，240x320，RGB565）。
+//! 屏幕以横屏方式使用，逻辑分辨率为 320x240。
+//! 控制器的复位与背光引脚由 XL9555 扩展芯片控制（见 [crate::xl9555]），
+//! 驱动只负责 SPI 命令/数据传输以及 DC 引脚。
+//!
+//! [init] 先通过 MISO 读回控制器 ID，按型号选择 ST7789 或 ILI9341 的初始化序列，
+//! 初始化后读回显示状态确认控制器在工作；读到的信息用 [panel] 查询（命令行 `lcd`）。
+//!
+//! [LcdSpi] 同时实现了阻塞和异步的 SPI 设备接口（见 [crate::spi]），整屏填充、刷新等大块传输用
+//! [drivers::st7789::St7789Async]（通过 [St7789::as_async] 借用同一个设备）：
+//! 异步传输启动 DMA 后让出执行器，由 DMA 完成中断唤醒，传输期间其他任务照常运行。
+
+use crate::spi::SpiDevice;
+use core::cell::Cell;
+use critical_section::Mutex;
+use defmt::{info, warn};
+use embassy_time::Delay;
+use embedded_hal::spi::{ErrorType, Operation};
+use esp_hal::gpio::Output;
+use esp_hal::spi::Error as SpiError;
+
+pub use drivers::st7789::{
+    Controller, DisplayId, DisplayStatus, HEIGHT, Rotation, Transform, WIDTH,
+};
+
+/// 初始化时读回的面板信息
+static PANEL: Mutex<Cell<Option<PanelInfo>>> = Mutex::new(Cell::new(None));
+
+/// 初始化时读回的面板信息
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PanelInfo {
+    /// 识别到的控制器，无法识别时按 ST7789 初始化
+    pub controller: Controller,
+    /// RDDID 读回的 ID，读取失败时为 None
+    pub id: Option<DisplayId>,
+    /// 初始化后 RDDST 读回的状态，读取失败时为 None
+    pub status: Option<DisplayStatus>,
+}
+
+impl PanelInfo {
+    /// 控制器是否按预期工作（已唤醒且显示开启）
+    pub fn is_responding(&self) -> bool {
+        self.status.is_some_and(|status| status.is_running())
+    }
+}
+
+/// 板载 LCD 驱动
+///
+/// 实现了 embedded-graphics 的 [DrawTarget](embedded_graphics::draw_target::DrawTarget)，
+/// 可直接用于绘制图形和文字。
+pub type St7789 = drivers::st7789::St7789<LcdSpi, Output<'static>>;
+
+/// 共享 SPI 总线上的 LCD 设备
+///
+/// 在 [SpiDevice] 之上加入故障注入（`fault-injection` 特性）
+pub struct LcdSpi(SpiDevice);
+
+impl LcdSpi {
+    /// 包装共享 SPI 总线上的设备
+    ///
+    /// # 参数
+    /// * `spi` - 由 [spi::device] 创建的 LCD 设备
+    pub fn new(spi: SpiDevice) -> Self {
+        LcdSpi(spi)
+    }
+}
+
+impl ErrorType for LcdSpi {
+    type Error = SpiError;
+}
+
+impl embedded_hal_async::spi::SpiDevice for LcdSpi {
+    async fn transaction(&mut self, operations: &mut [Operation<'_, u8>]) -> Result<(), SpiError> {
+        #[cfg(feature = "fault-injection")]
+        let corrupt_read = crate::fault::inject_lcd()?;
+        embedded_hal_async::spi::SpiDevice::transaction(&mut self.0, operations).await?;
+        #[cfg(feature = "fault-injection")]
+        if corrupt_read {
+            drivers::fault::corrupt_spi_reads(operations);
+        }
+        Ok(())
+    }
+}
+
+impl embedded_hal::spi::SpiDevice for LcdSpi {
+    fn transaction(&mut self, operations: &mut [Operation<'_, u8>]) -> Result<(), SpiError> {
+        #[cfg(feature = "fault-injection")]
+        let corrupt_read = crate::fault::inject_lcd()?;
+        embedded_hal::spi::SpiDevice::transaction(&mut self.0, operations)?;
+        #[cfg(feature = "fault-injection")]
+        if corrupt_read {
+            drivers::fault::corrupt_spi_reads(operations);
+        }
+        Ok(())
+    }
+}
+
+/// 识别并初始化控制器
+///
+/// 调用前需已完成硬件复位。ID 无法识别时（例如 MISO 未连接）按板载的 ST7789 初始化；
+/// 初始化后读回的状态不正常只记录警告，不视为失败
+///
+/// # 参数
+/// * `lcd` - 刚完成硬件复位的 LCD
+///
+/// # 返回
+/// 读回的面板信息，同时保存供 [panel] 查询
+pub async fn init(lcd: &mut St7789) -> Result<PanelInfo, SpiError> {
+    let id = lcd
+        .read_id()
+        .inspect_err(|err| warn!("Failed to read LCD ID: {}", err));
+    let controller = match lcd.detect() {
+        Ok(controller) => controller,
+        Err(err) => {
+            warn!("Failed to detect LCD controller: {}", err);
+            Controller::Unknown
+        }
+    };
+    if let Ok(id) = id {
+        let [manufacturer, version, driver] = id.bytes();
+        info!(
+            "LCD controller {}, ID {=u8:02x} {=u8:02x} {=u8:02x}",
+            controller.name(),
+            manufacturer,
+            version,
+            driver
+        );
+    }
+
+    match controller {
+        Controller::Ili9341 => lcd.init_ili9341(&mut Delay).await?,
+        Controller::St7789 => lcd.as_async().init(&mut Delay).await?,
+        Controller::Unknown => {
+            warn!("Unknown LCD controller, assuming ST7789");
+            lcd.init(&mut Delay).await?;
+        }
+    }
+
+    let status = match lcd.read_status() {
+        Ok(status) if status.is_running() => Some(status),
+        Ok(status) => {
+            warn!(
+                "LCD controller not responding as expected, status {=u32:#010x}",
+                status.0
+            );
+            Some(status)
+        }
+        Err(err) => {
+            warn!("Failed to read LCD status: {}", err);
+            None
+        }
+    };
+
+    let info = PanelInfo {
+        controller,
+        id: id.ok(),
+        status,
+    };
+    critical_section::with(|cs| PANEL.borrow(cs).set(Some(info)));
+    Ok(info)
+}
+
+/// 初始化时读回的面板信息，LCD 未初始化时为 None
+pub fn panel() -> Option<PanelInfo> {
+    critical_section::with(|cs| PANEL.borrow(cs).get())
+}
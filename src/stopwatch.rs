@@ -0,0 +1,274 @@
+//! 秒表和计圈
+//!
+//! [Profile::Stopwatch](crate::profile::Profile::Stopwatch) 模式下代替渲染任务占用 LCD。
+//! 计时基于 [Instant]，以 10 ms 分辨率显示 `MM:SS.cc`（超过 1 小时后回绕），
+//! 计圈记录显示在下方的列表中，可上下滚动。颜色来自 [crate::theme]，配色改变时整屏重绘。
+//!
+//! 按键通过 [crate::keymap] 映射，默认：KEY2 开始/停止，KEY3 计圈（停止时清零），
+//! KEY1/KEY0 上移/下移列表。
+
+use crate::assets;
+use crate::i18n::{self, Msg};
+use crate::input;
+use crate::keymap::{self, Action};
+use crate::lcd::Lcd;
+use crate::st7789::{self, St7789};
+use crate::theme;
+use core::fmt::Write;
+use defmt::warn;
+use embassy_time::{Duration, Instant, with_timeout};
+use embedded_graphics::mono_font::ascii::FONT_6X10;
+use embedded_graphics::mono_font::{MonoFont, MonoTextStyle, MonoTextStyleBuilder};
+use embedded_graphics::pixelcolor::Rgb565;
+use embedded_graphics::prelude::*;
+use embedded_graphics::text::Text;
+use heapless::{String, Vec};
+use ui::segment::SegmentDisplay;
+use ui::theme::Theme;
+
+/// 屏幕刷新周期，SPI 刷新变化的数字约需 10 ms
+const TICK: Duration = Duration::from_millis(20);
+
+/// 最多记录的圈数
+const MAX_LAPS: usize = 99;
+
+/// 列表一屏显示的行数
+const LIST_ROWS: usize = 6;
+
+/// 标题基线位置
+const TITLE_Y: i32 = 26;
+
+/// 大号数字的上边界和尺寸
+const DIGITS_Y: i32 = 44;
+const DIGIT_WIDTH: u32 = 32;
+const DIGIT_HEIGHT: u32 = 64;
+
+/// 计圈列表第一行基线位置和行高
+const LIST_Y: i32 = 136;
+const LIST_LINE_HEIGHT: i32 = 14;
+
+/// 底部提示行基线位置
+const HINT_Y: i32 = 232;
+
+/// 秒表状态
+struct Stopwatch {
+    /// 停止前累计的时间
+    accumulated: Duration,
+    /// 正在计时时为本次开始的时刻
+    started: Option<Instant>,
+    /// 每圈结束时的累计时间
+    laps: Vec<Duration, MAX_LAPS>,
+}
+
+impl Stopwatch {
+    const fn new() -> Self {
+        Stopwatch {
+            accumulated: Duration::from_ticks(0),
+            started: None,
+            laps: Vec::new(),
+        }
+    }
+
+    fn elapsed(&self, now: Instant) -> Duration {
+        match self.started {
+            Some(started) => self.accumulated + now.saturating_duration_since(started),
+            None => self.accumulated,
+        }
+    }
+
+    fn start_stop(&mut self, now: Instant) {
+        match self.started.take() {
+            Some(started) => self.accumulated += now.saturating_duration_since(started),
+            None => self.started = Some(now),
+        }
+    }
+
+    /// 计时中记录一圈，停止时清零
+    ///
+    /// # 返回
+    /// 计圈记录是否改变
+    fn lap_reset(&mut self, now: Instant) -> bool {
+        if self.started.is_some() {
+            self.laps.push(self.elapsed(now)).is_ok()
+        } else {
+            let changed = !self.laps.is_empty();
+            *self = Stopwatch::new();
+            changed
+        }
+    }
+}
+
+/// 格式化为 `MM:SS.cc`
+fn format_time(text: &mut String<16>, time: Duration) {
+    let centis = time.as_millis() / 10;
+    let (minutes, seconds) = (centis / 6000 % 60, centis / 100 % 60);
+    write!(text, "{:02}:{:02}.{:02}", minutes, seconds, centis % 100).ok();
+}
+
+/// 秒表任务
+///
+/// # 参数
+/// * `lcd` - 已完成初始化的 LCD
+#[embassy_executor::task]
+pub async fn stopwatch_task(mut lcd: Lcd) {
+    let Some(mut keys) = input::subscribe() else {
+        warn!("No key subscriber available for stopwatch");
+        return;
+    };
+    input::set_captured(true);
+
+    let mut colors = theme::current();
+    let mut digits = clear_screen(&mut lcd, &colors);
+    let mut stopwatch = Stopwatch::new();
+    // 列表跳过的最新圈数，最新一圈显示在最上面
+    let mut scroll = 0;
+    let mut list_dirty = true;
+    let mut hint_dirty = true;
+    let mut text: String<16> = String::new();
+
+    loop {
+        let current = theme::current();
+        if current != colors {
+            colors = current;
+            digits = clear_screen(&mut lcd, &colors);
+            list_dirty = true;
+            hint_dirty = true;
+        }
+
+        let now = Instant::now();
+        text.clear();
+        format_time(&mut text, stopwatch.elapsed(now));
+        let running = stopwatch.started.is_some();
+        digits.set_color(if running { colors.accent } else { colors.foreground });
+        if let Err(err) = digits.show(&mut lcd, &text) {
+            warn!("Failed to draw stopwatch digits: {}", err);
+        }
+
+        if list_dirty {
+            draw_laps(&mut lcd, &stopwatch.laps, scroll, &colors);
+            list_dirty = false;
+        }
+        if hint_dirty {
+            let hint = hint(running);
+            lcd.fill_rectangle(0, (HINT_Y - 18) as u16, st7789::WIDTH, 24, colors.background)
+                .ok();
+            let style = text_style(&colors, assets::font(), colors.foreground);
+            draw_text(&mut lcd, &hint, 10, HINT_Y, style);
+            hint_dirty = false;
+        }
+
+        let Ok(key) = with_timeout(TICK, keys.next_message_pure()).await else {
+            continue;
+        };
+        let now = Instant::now();
+        match keymap::action(key) {
+            Some(Action::StartStop) => {
+                stopwatch.start_stop(now);
+                hint_dirty = true;
+            }
+            Some(Action::LapReset) => {
+                if stopwatch.lap_reset(now) {
+                    scroll = 0;
+                    list_dirty = true;
+                }
+                hint_dirty = true;
+            }
+            Some(Action::ScrollUp) if scroll > 0 => {
+                scroll -= 1;
+                list_dirty = true;
+            }
+            Some(Action::ScrollDown) if scroll + LIST_ROWS < stopwatch.laps.len() => {
+                scroll += 1;
+                list_dirty = true;
+            }
+            _ => {}
+        }
+    }
+}
+
+/// 用配色的背景色清屏并显示标题
+///
+/// # 返回
+/// 新的大号数字显示区域，下次显示时完整绘制
+fn clear_screen(lcd: &mut St7789, colors: &Theme) -> SegmentDisplay {
+    if let Err(err) = lcd.fill_screen(colors.background) {
+        warn!("Failed to clear LCD: {}", err);
+    }
+    let style = text_style(colors, assets::font(), colors.accent);
+    draw_text(lcd, i18n::lcd(Msg::StopwatchTitle), 10, TITLE_Y, style);
+    SegmentDisplay::new(Point::new(0, DIGITS_Y), DIGIT_WIDTH, DIGIT_HEIGHT, colors.foreground)
+        .with_background(colors.background)
+        .centered("00:00.00")
+}
+
+/// 使用配色背景色的文字样式
+fn text_style(
+    colors: &Theme,
+    font: &'static MonoFont<'static>,
+    color: Rgb565,
+) -> MonoTextStyle<'static, Rgb565> {
+    MonoTextStyleBuilder::new()
+        .font(font)
+        .text_color(color)
+        .background_color(colors.background)
+        .build()
+}
+
+/// 底部提示，按当前映射显示按键编号
+fn hint(running: bool) -> String<40> {
+    let number = |action| keymap::key_name(keymap::key_for(action)).trim_start_matches("key");
+    let (start, lap) = if running {
+        (Msg::StopwatchStop, Msg::StopwatchLap)
+    } else {
+        (Msg::StopwatchStart, Msg::StopwatchReset)
+    };
+    let mut hint = String::new();
+    write!(
+        hint,
+        "K{} {}  K{} {}  K{}/K{} {}",
+        number(Action::StartStop),
+        i18n::lcd(start),
+        number(Action::LapReset),
+        i18n::lcd(lap),
+        number(Action::ScrollUp),
+        number(Action::ScrollDown),
+        i18n::lcd(Msg::StopwatchScroll),
+    )
+    .ok();
+    hint
+}
+
+/// 绘制计圈列表，最新一圈在最上面
+///
+/// # 参数
+/// * `scroll` - 跳过的最新圈数
+fn draw_laps(lcd: &mut St7789, laps: &[Duration], scroll: usize, colors: &Theme) {
+    let height = LIST_ROWS as u16 * LIST_LINE_HEIGHT as u16;
+    lcd.fill_rectangle(0, (LIST_Y - 10) as u16, st7789::WIDTH, height, colors.background)
+        .ok();
+    let style = text_style(colors, &FONT_6X10, colors.foreground);
+
+    let label = i18n::lcd_small(Msg::StopwatchLap);
+    let mut line: String<48> = String::new();
+    let mut time: String<16> = String::new();
+    for (row, index) in (0..laps.len()).rev().skip(scroll).take(LIST_ROWS).enumerate() {
+        let previous = index.checked_sub(1).map_or(Duration::from_ticks(0), |i| laps[i]);
+        let split = laps[index] - previous;
+        line.clear();
+        write!(line, "{} {:>2}   ", label, index + 1).ok();
+        time.clear();
+        format_time(&mut time, split);
+        line.push_str(&time).ok();
+        line.push_str("   ").ok();
+        time.clear();
+        format_time(&mut time, laps[index]);
+        line.push_str(&time).ok();
+        draw_text(lcd, &line, 10, LIST_Y + row as i32 * LIST_LINE_HEIGHT, style);
+    }
+}
+
+fn draw_text(lcd: &mut St7789, text: &str, x: i32, y: i32, style: MonoTextStyle<'_, Rgb565>) {
+    if let Err(err) = Text::new(text, Point::new(x, y), style).draw(lcd) {
+        warn!("Failed to draw stopwatch text: {}", err);
+    }
+}
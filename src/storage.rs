@@ -0,0 +1,211 @@
+use core::cell::{Cell, RefCell};
+use critical_section::Mutex;
+use defmt::{info, warn};
+use embedded_storage::{ReadStorage, Storage};
+use esp_hal::peripherals::FLASH;
+use esp_storage::FlashStorage;
+
+/// 片上 Flash 存储访问
+///
+/// 该模块封装了对 ESP32-S3 片上 Flash 的原始读写，用于持久化设置等小块数据。
+/// 数据区域直接复用默认分区表中的 NVS 分区（0x9000 起，共 24KB），
+/// 每个用途占用一个独立的 4KB 扇区，互不干扰：
+/// - 0x9000: 设置数据块（见 [crate::settings]）
+/// - 0xA000: 崩溃记录（见 [crate::crash]）
+/// - 0xB000: 自动化规则（见 [crate::rules]）
+///
+/// 每个数据块都带有魔数、长度和 CRC32 校验，读取时校验失败视为不存在。
+static FLASH_STORAGE: Mutex<RefCell<Option<FlashStorage<'static>>>> =
+    Mutex::new(RefCell::new(None));
+
+/// Flash 实例是否已被 [take_flash] 取出
+static FLASH_TAKEN: Mutex<Cell<bool>> = Mutex::new(Cell::new(false));
+
+/// Flash 扇区大小
+pub const SECTOR_SIZE: u32 = 4096;
+
+/// 设置数据块所在扇区
+pub const SETTINGS_OFFSET: u32 = 0x9000;
+
+/// 数据块头部魔数 ("ESPB")
+const BLOB_MAGIC: u32 = 0x4250_5345;
+
+/// 数据块头部长度：魔数(4) + 长度(2) + 保留(2) + CRC32(4)
+const BLOB_HEADER_LEN: usize = 12;
+
+/// Flash 存储错误
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum StorageError {
+    /// Flash 尚未初始化
+    NotInitialized,
+    /// 底层 Flash 读写失败
+    Flash,
+    /// 数据块不存在或校验失败
+    Corrupted,
+    /// 数据超出扇区容量或缓冲区太小
+    TooLarge,
+    /// Flash 实例正被长时间的操作（例如写入固件）独占
+    Busy,
+}
+
+/// 初始化 Flash 存储
+///
+/// # 参数
+/// * `flash` - FLASH 外设
+pub fn init(flash: FLASH<'static>) {
+    let storage = FlashStorage::new(flash);
+    info!("Flash storage capacity: {} bytes", storage.capacity());
+    critical_section::with(|cs| {
+        FLASH_STORAGE.borrow_ref_mut(cs).replace(storage);
+    });
+}
+
+/// 通过闭包访问 Flash 实例
+///
+/// # 参数
+/// * `f` - 闭包函数，接受 Flash 实例作为参数
+pub fn with_flash<F, R>(f: F) -> Result<R, StorageError>
+where
+    F: FnOnce(&mut FlashStorage<'static>) -> Result<R, StorageError>,
+{
+    critical_section::with(|cs| {
+        let mut flash_ref = FLASH_STORAGE.borrow_ref_mut(cs);
+        let Some(flash) = flash_ref.as_mut() else {
+            return Err(if FLASH_TAKEN.borrow(cs).get() {
+                StorageError::Busy
+            } else {
+                StorageError::NotInitialized
+            });
+        };
+        f(flash)
+    })
+}
+
+/// 取出 Flash 实例供长时间的操作（例如写入固件）独占使用，用完后用 [restore_flash] 放回
+///
+/// 取出的实例不在临界区中使用：FlashStorage 只在每次擦除、写入期间关闭中断，
+/// 两次操作之间中断、WiFi 和看门狗照常运行。取出期间 [with_flash] 返回 [StorageError::Busy]
+pub fn take_flash() -> Result<FlashStorage<'static>, StorageError> {
+    critical_section::with(|cs| {
+        let taken = FLASH_TAKEN.borrow(cs);
+        if taken.get() {
+            return Err(StorageError::Busy);
+        }
+        let flash = FLASH_STORAGE
+            .borrow_ref_mut(cs)
+            .take()
+            .ok_or(StorageError::NotInitialized)?;
+        taken.set(true);
+        Ok(flash)
+    })
+}
+
+/// 放回 [take_flash] 取出的 Flash 实例
+pub fn restore_flash(flash: FlashStorage<'static>) {
+    critical_section::with(|cs| {
+        FLASH_STORAGE.borrow_ref_mut(cs).replace(flash);
+        FLASH_TAKEN.borrow(cs).set(false);
+    });
+}
+
+/// 读取一个带校验的数据块
+///
+/// # 参数
+/// * `offset` - 数据块所在扇区的起始地址
+/// * `buf` - 接收数据的缓冲区
+///
+/// # 返回
+/// 成功时返回数据长度
+pub fn read_blob(offset: u32, buf: &mut [u8]) -> Result<usize, StorageError> {
+    with_flash(|flash| {
+        let mut header = [0u8; BLOB_HEADER_LEN];
+        flash
+            .read(offset, &mut header)
+            .map_err(|_| StorageError::Flash)?;
+
+        let magic = u32::from_le_bytes([header[0], header[1], header[2], header[3]]);
+        let len = u16::from_le_bytes([header[4], header[5]]) as usize;
+        let crc = u32::from_le_bytes([header[8], header[9], header[10], header[11]]);
+        if magic != BLOB_MAGIC {
+            return Err(StorageError::Corrupted);
+        }
+        if len > buf.len() || len > SECTOR_SIZE as usize - BLOB_HEADER_LEN {
+            return Err(StorageError::TooLarge);
+        }
+
+        flash
+            .read(offset + BLOB_HEADER_LEN as u32, &mut buf[..len])
+            .map_err(|_| StorageError::Flash)?;
+        if crc32(&buf[..len]) != crc {
+            warn!("Blob at {:#x} failed CRC check", offset);
+            return Err(StorageError::Corrupted);
+        }
+        Ok(len)
+    })
+}
+
+/// 写入一个带校验的数据块
+///
+/// 写入会整体替换该扇区的内容（FlashStorage 内部完成擦除）
+///
+/// # 参数
+/// * `offset` - 数据块所在扇区的起始地址
+/// * `data` - 待写入的数据
+pub fn write_blob(offset: u32, data: &[u8]) -> Result<(), StorageError> {
+    if data.len() > SECTOR_SIZE as usize - BLOB_HEADER_LEN {
+        return Err(StorageError::TooLarge);
+    }
+    let mut header = [0u8; BLOB_HEADER_LEN];
+    header[0..4].copy_from_slice(&BLOB_MAGIC.to_le_bytes());
+    header[4..6].copy_from_slice(&(data.len() as u16).to_le_bytes());
+    header[8..12].copy_from_slice(&crc32(data).to_le_bytes());
+
+    with_flash(|flash| {
+        flash
+            .write(offset, &header)
+            .map_err(|_| StorageError::Flash)?;
+        flash
+            .write(offset + BLOB_HEADER_LEN as u32, data)
+            .map_err(|_| StorageError::Flash)
+    })
+}
+
+/// 擦除一个数据块
+///
+/// 只需破坏头部魔数，后续读取即会返回 [StorageError::Corrupted]
+///
+/// # 参数
+/// * `offset` - 数据块所在扇区的起始地址
+pub fn erase_blob(offset: u32) -> Result<(), StorageError> {
+    with_flash(|flash| {
+        flash
+            .write(offset, &[0xFF; BLOB_HEADER_LEN])
+            .map_err(|_| StorageError::Flash)
+    })
+}
+
+/// 计算 CRC32 (IEEE 802.3) 校验值
+///
+/// # 参数
+/// * `data` - 待校验的数据
+pub fn crc32(data: &[u8]) -> u32 {
+    crc32_update(0xFFFF_FFFF, data) ^ 0xFFFF_FFFF
+}
+
+/// 增量计算 CRC32，用于分块校验大文件
+///
+/// 初始值为 `0xFFFF_FFFF`，全部数据处理完后需再与 `0xFFFF_FFFF` 异或
+///
+/// # 参数
+/// * `crc` - 上一次的中间结果
+/// * `data` - 本次处理的数据
+pub fn crc32_update(mut crc: u32, data: &[u8]) -> u32 {
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    crc
+}
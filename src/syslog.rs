@@ -0,0 +1,367 @@
+//! Syslog 远程日志
+//!
+//! 把 defmt 日志帧通过 UDP syslog（RFC 5424）转发到设置中的收集器，设施为 local0。
+//! 没有设置收集器时，联网后用 mDNS 在局域网中查找 `_syslog._udp` 服务（见 [crate::mdns]），
+//! 找到时转发到该服务，没有找到时不转发。
+//! 每条日志由 [crate::logbuf] 的 logger 在输出时交给 [frame_start]、[frame_raw]、
+//! [frame_encoded] 和 [frame_end]，放入发送队列；WiFi 断开期间最多缓存 [QUEUE_LEN] 条，
+//! 队列满时丢弃最早的日志。
+//!
+//! 严重级别按 defmt 的日志级别映射：error → Error，warn → Warning，info → Informational，
+//! debug/trace → Debug，`println!` 等其他帧为 Notice。
+//! 级别由帧开头的字符串索引判断：`defmt.x` 按级别排列格式字符串，并用
+//! `__DEFMT_MARKER_*` 符号标出每个级别的索引范围。
+//!
+//! defmt 日志在设备上是二进制帧，不含格式字符串，因此 MSG 部分是该帧（rzCOBS 编码，
+//! 含结尾的 0x00 分隔符）的 Base64 文本，在收集器上配合固件 ELF 解码，例如：
+//!
+//! ```text
+//! awk '{print $NF}' esp.log | while read m; do echo "$m" | base64 -d; done \
+//!     | defmt-print -e target/xtensa-esp32s3-none-elf/release/esp-app-4
+//! ```
+
+use crate::netstats::{self, Link};
+use crate::wallclock::{self, DateTime};
+use crate::{mdns, net, settings};
+use core::cell::RefCell;
+use core::fmt::Write;
+use core::sync::atomic::{AtomicBool, Ordering};
+use critical_section::Mutex;
+use defmt::{info, warn};
+use embassy_net::Stack;
+use embassy_net::dns::DnsQueryType;
+use embassy_net::udp::{PacketMetadata, UdpSocket};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::signal::Signal;
+use embassy_time::{Duration, Instant, Timer};
+use heapless::{Deque, String};
+use proto::base64;
+
+/// 默认的 syslog 端口
+const DEFAULT_PORT: u16 = 514;
+
+/// 没有设置收集器时查找的 DNS-SD 服务类型
+const SERVICE: &str = "_syslog._udp.local";
+
+/// 应用名，主机名见 [net::hostname]
+const APP_NAME: &str = "esp-app-4";
+
+/// 设施：local0
+const FACILITY: u8 = 16;
+
+/// 单条日志帧的最大长度，更长的帧不转发
+const MAX_FRAME_LEN: usize = 160;
+
+/// 离线时最多缓存的日志条数
+const QUEUE_LEN: usize = 32;
+
+/// 一条 syslog 报文的最大长度
+const MESSAGE_LEN: usize = 384;
+
+/// 发送失败后的重试间隔
+const RETRY_INTERVAL: Duration = Duration::from_secs(5);
+
+/// 是否转发日志，配置了收集器时由 [syslog_task] 打开
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// 正在输出的日志帧
+static STAGING: Mutex<RefCell<Staging>> = Mutex::new(RefCell::new(Staging::new()));
+
+/// 待发送的日志
+static QUEUE: Mutex<RefCell<Deque<Record, QUEUE_LEN>>> = Mutex::new(RefCell::new(Deque::new()));
+
+/// 有新日志入队
+static QUEUED: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+
+// defmt.x 中各级别格式字符串索引范围的边界，只使用符号地址
+unsafe extern "C" {
+    static __DEFMT_MARKER_TRACE_START: u8;
+    static __DEFMT_MARKER_DEBUG_END: u8;
+    static __DEFMT_MARKER_INFO_START: u8;
+    static __DEFMT_MARKER_INFO_END: u8;
+    static __DEFMT_MARKER_WARN_START: u8;
+    static __DEFMT_MARKER_WARN_END: u8;
+    static __DEFMT_MARKER_ERROR_START: u8;
+    static __DEFMT_MARKER_ERROR_END: u8;
+}
+
+/// RFC 5424 严重级别
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+#[repr(u8)]
+enum Severity {
+    Error = 3,
+    Warning = 4,
+    Notice = 5,
+    Informational = 6,
+    Debug = 7,
+}
+
+impl Severity {
+    /// 按格式字符串索引判断日志级别
+    fn from_index(index: u16) -> Self {
+        let levels = [
+            (
+                &raw const __DEFMT_MARKER_ERROR_START,
+                &raw const __DEFMT_MARKER_ERROR_END,
+                Severity::Error,
+            ),
+            (
+                &raw const __DEFMT_MARKER_WARN_START,
+                &raw const __DEFMT_MARKER_WARN_END,
+                Severity::Warning,
+            ),
+            (
+                &raw const __DEFMT_MARKER_INFO_START,
+                &raw const __DEFMT_MARKER_INFO_END,
+                Severity::Informational,
+            ),
+            (
+                &raw const __DEFMT_MARKER_TRACE_START,
+                &raw const __DEFMT_MARKER_DEBUG_END,
+                Severity::Debug,
+            ),
+        ];
+        levels
+            .into_iter()
+            .find(|&(start, end, _)| (start as usize..end as usize).contains(&(index as usize)))
+            .map_or(Severity::Notice, |(_, _, severity)| severity)
+    }
+}
+
+/// 正在输出的日志帧
+struct Staging {
+    /// 原始帧开头的格式字符串索引（小端）
+    index: [u8; 2],
+    index_len: usize,
+    /// 编码后的帧
+    frame: [u8; MAX_FRAME_LEN],
+    len: usize,
+    overflow: bool,
+}
+
+impl Staging {
+    const fn new() -> Self {
+        Staging {
+            index: [0; 2],
+            index_len: 0,
+            frame: [0; MAX_FRAME_LEN],
+            len: 0,
+            overflow: false,
+        }
+    }
+}
+
+/// 一条待发送的日志
+struct Record {
+    severity: Severity,
+    /// 输出时刻
+    at: Instant,
+    frame: [u8; MAX_FRAME_LEN],
+    len: usize,
+}
+
+/// 开始一帧，由 logger 在临界区内调用
+pub fn frame_start() {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+    critical_section::with(|cs| {
+        let mut staging = STAGING.borrow_ref_mut(cs);
+        staging.index_len = 0;
+        staging.len = 0;
+        staging.overflow = false;
+    });
+}
+
+/// 编码前的原始数据，用于取出格式字符串索引
+pub fn frame_raw(bytes: &[u8]) {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+    critical_section::with(|cs| {
+        let mut staging = STAGING.borrow_ref_mut(cs);
+        for &byte in bytes {
+            if staging.index_len == staging.index.len() {
+                break;
+            }
+            let at = staging.index_len;
+            staging.index[at] = byte;
+            staging.index_len += 1;
+        }
+    });
+}
+
+/// 编码后的数据
+pub fn frame_encoded(bytes: &[u8]) {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+    critical_section::with(|cs| {
+        let mut staging = STAGING.borrow_ref_mut(cs);
+        let start = staging.len;
+        match staging.frame.get_mut(start..start + bytes.len()) {
+            Some(dest) => {
+                dest.copy_from_slice(bytes);
+                staging.len += bytes.len();
+            }
+            None => staging.overflow = true,
+        }
+    });
+}
+
+/// 结束一帧并放入发送队列
+pub fn frame_end() {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+    critical_section::with(|cs| {
+        let staging = STAGING.borrow_ref(cs);
+        if staging.overflow || staging.index_len < 2 {
+            return;
+        }
+        let record = Record {
+            severity: Severity::from_index(u16::from_le_bytes(staging.index)),
+            at: Instant::now(),
+            frame: staging.frame,
+            len: staging.len,
+        };
+        let mut queue = QUEUE.borrow_ref_mut(cs);
+        if queue.is_full() {
+            queue.pop_front();
+        }
+        queue.push_back(record).ok();
+    });
+    QUEUED.signal(());
+}
+
+/// 解析收集器地址 `<host>[:<port>]`
+pub fn parse_server(server: &str) -> Option<(&str, u16)> {
+    let (host, port) = match server.split_once(':') {
+        Some((host, port)) => (host, port.parse().ok()?),
+        None => (server, DEFAULT_PORT),
+    };
+    (!host.is_empty()).then_some((host, port))
+}
+
+/// 编码 RFC 5424 报文
+fn format_message(record: &Record, message: &mut String<MESSAGE_LEN>) {
+    message.clear();
+    let priority = FACILITY * 8 + record.severity as u8;
+    write!(message, "<{}>1 ", priority).ok();
+
+    // 时间戳由输出时刻和当前的实际时间推算，未校时时省略
+    let age = Instant::now()
+        .saturating_duration_since(record.at)
+        .as_micros();
+    match wallclock::now_micros().and_then(|now| now.checked_sub(age)) {
+        Some(unix_us) => {
+            let t = DateTime::from_unix(unix_us / 1_000_000);
+            write!(
+                message,
+                "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:06}Z",
+                t.year,
+                t.month,
+                t.day,
+                t.hour,
+                t.minute,
+                t.second,
+                unix_us % 1_000_000
+            )
+            .ok();
+        }
+        None => {
+            message.push('-').ok();
+        }
+    }
+    write!(message, " {} {} - defmt - ", net::hostname(), APP_NAME).ok();
+
+    base64::encode(&record.frame[..record.len], message).ok();
+}
+
+/// Syslog 转发任务
+///
+/// 未配置收集器时先在局域网中查找，也没有找到时退出，日志不进入发送队列。
+/// 找到的收集器发送失败后重新查找
+///
+/// # 参数
+/// * `stack` - 网络协议栈
+#[embassy_executor::task]
+pub async fn syslog_task(stack: Stack<'static>) {
+    let server = settings::get().syslog_server;
+    // 主机名为空表示收集器由 mDNS 发现
+    let (host, mut port, mut address) = match parse_server(&server) {
+        Some((host, port)) => (host, port, None),
+        None => {
+            stack.wait_config_up().await;
+            let Some(service) = mdns::browse(stack, SERVICE).await else {
+                info!("No syslog collector configured or found");
+                return;
+            };
+            ("", service.port, Some(service.address))
+        }
+    };
+    ENABLED.store(true, Ordering::Relaxed);
+    match address {
+        Some(address) => info!("Forwarding logs to syslog collector {}:{}", address, port),
+        None => info!("Forwarding logs to syslog collector {}:{}", host, port),
+    }
+
+    let mut rx_meta = [PacketMetadata::EMPTY; 1];
+    let mut rx_buffer = [0u8; 64];
+    let mut tx_meta = [PacketMetadata::EMPTY; 4];
+    let mut tx_buffer = [0u8; MESSAGE_LEN * 4];
+    let mut socket = UdpSocket::new(
+        stack,
+        &mut rx_meta,
+        &mut rx_buffer,
+        &mut tx_meta,
+        &mut tx_buffer,
+    );
+    if let Err(err) = socket.bind(0) {
+        warn!(
+            "Failed to bind syslog socket: {}",
+            defmt::Debug2Format(&err)
+        );
+        return;
+    }
+
+    let mut message = String::new();
+    loop {
+        let Some(record) = critical_section::with(|cs| QUEUE.borrow_ref_mut(cs).pop_front()) else {
+            QUEUED.wait().await;
+            continue;
+        };
+        stack.wait_config_up().await;
+
+        let target = match address {
+            Some(address) => Some(address),
+            None if host.is_empty() => mdns::browse(stack, SERVICE).await.map(|service| {
+                port = service.port;
+                service.address
+            }),
+            None => stack
+                .dns_query(host, DnsQueryType::A)
+                .await
+                .ok()
+                .and_then(|addresses| addresses.first().copied()),
+        };
+        format_message(&record, &mut message);
+        let sent = match target {
+            Some(target) => socket
+                .send_to(message.as_bytes(), (target, port))
+                .await
+                .is_ok(),
+            None => false,
+        };
+
+        if sent {
+            netstats::sent(Link::Syslog, message.len());
+            address = target;
+        } else {
+            // 失败时不输出日志，避免每次重试都产生新的日志
+            address = None;
+            critical_section::with(|cs| QUEUE.borrow_ref_mut(cs).push_front(record).ok());
+            Timer::after(RETRY_INTERVAL).await;
+        }
+    }
+}
@@ -0,0 +1,344 @@
+//! 系统关机、重启与状态报告
+//!
+//! 提供统一的重启 [reboot] 和休眠前关机 [shutdown_for_sleep] 接口，
+//! [deep_sleep]（命令行 `sleep`）关机后进入深度睡眠，到时由 RTC 定时器唤醒。
+//! 它们都会先执行关机流程，再进行后续操作：
+//!
+//! 1. 依次调用通过 [on_shutdown] 注册的关机钩子（例如刷新存储）
+//! 2. 向 MQTT 代理发布离线消息（见 [crate::mqtt::shutdown]）
+//! 3. 关闭 LCD 背光
+//! 4. 使摄像头进入掉电模式
+//!
+//! 重启原因保存在 RTC 快速内存中，复位后可通过 [last_reboot_reason] 读取。
+//!
+//! # 状态报告
+//!
+//! [status] 汇总设备标识、运行时间、内存和 WiFi、传感器、存储、电源等子系统的状态快照
+//! （[StatusReport]）。HTTP `GET /api/status` 和命令行 `status` 都从这里取数据，
+//! 各处显示的内容不会互相不一致；新增的子系统状态加到 [StatusReport] 中即可同时出现在两处。
+
+use crate::device::{self, Identity};
+use crate::mqtt;
+use crate::registry::{self, Peripheral, State};
+use crate::sensor::{self, Reading};
+use crate::service::{self, Service};
+use crate::{wifi, xl9555};
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::cell::RefCell;
+use core::fmt::Write;
+use core::future::Future;
+use core::pin::Pin;
+use critical_section::Mutex;
+use defmt::{info, warn};
+use embassy_time::{Duration, Instant, Timer};
+use esp_hal::peripherals::LPWR;
+use esp_hal::rtc_cntl::sleep::TimerWakeupSource;
+use esp_hal::rtc_cntl::{Rtc, SocResetReason};
+use heapless::String;
+use proto::json::{Object, ToJson};
+
+/// 重启原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+#[repr(u8)]
+pub enum RebootReason {
+    /// 用户通过命令请求重启
+    UserRequest = 1,
+    /// 长按 BOOT 按键
+    ButtonLongPress = 2,
+    /// 固件更新完成
+    FirmwareUpdate = 3,
+    /// 恢复出厂设置
+    FactoryReset = 4,
+    /// 设置变更需要重启生效
+    ConfigChange = 5,
+    /// 长按 BOOT 按键进入配网模式，重启后运行设置向导
+    Provisioning = 6,
+    /// 定时任务（见 [crate::scheduler]）
+    Scheduled = 7,
+}
+
+impl RebootReason {
+    fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            1 => Some(RebootReason::UserRequest),
+            2 => Some(RebootReason::ButtonLongPress),
+            3 => Some(RebootReason::FirmwareUpdate),
+            4 => Some(RebootReason::FactoryReset),
+            5 => Some(RebootReason::ConfigChange),
+            6 => Some(RebootReason::Provisioning),
+            7 => Some(RebootReason::Scheduled),
+            _ => None,
+        }
+    }
+}
+
+/// 关机钩子类型，返回要等待完成的关机工作
+pub type ShutdownHook = fn() -> Pin<Box<dyn Future<Output = ()>>>;
+
+/// 最多可注册的关机钩子数量
+const MAX_SHUTDOWN_HOOKS: usize = 8;
+
+static SHUTDOWN_HOOKS: Mutex<RefCell<[Option<ShutdownHook>; MAX_SHUTDOWN_HOOKS]>> =
+    Mutex::new(RefCell::new([None; MAX_SHUTDOWN_HOOKS]));
+
+/// 深度睡眠使用的 RTC 控制器，由 [init] 保存
+static RTC_CNTL: Mutex<RefCell<Option<LPWR<'static>>>> = Mutex::new(RefCell::new(None));
+
+/// 重启原因记录的魔数，用于区分上电后的随机内容
+const REBOOT_MAGIC: u32 = 0x5242_4F54;
+
+/// 重启原因记录：[魔数, 原因]
+///
+/// 位于 RTC 快速内存，软件复位后内容保持不变
+#[esp_hal::ram(unstable(rtc_fast, persistent))]
+static mut REBOOT_RECORD: [u32; 2] = [0; 2];
+
+/// 注册关机钩子
+///
+/// 钩子在重启或休眠前按注册顺序调用并等待完成，应尽快完成
+///
+/// # 参数
+/// * `hook` - 关机钩子函数
+#[cfg(feature = "sd")]
+pub fn on_shutdown(hook: ShutdownHook) {
+    critical_section::with(|cs| {
+        let mut hooks = SHUTDOWN_HOOKS.borrow_ref_mut(cs);
+        match hooks.iter_mut().find(|slot| slot.is_none()) {
+            Some(slot) => *slot = Some(hook),
+            None => warn!("Too many shutdown hooks, ignoring"),
+        }
+    });
+}
+
+/// 执行关机流程
+async fn run_shutdown_sequence() {
+    let hooks = critical_section::with(|cs| *SHUTDOWN_HOOKS.borrow_ref(cs));
+    for hook in hooks.iter().flatten() {
+        hook().await;
+    }
+
+    mqtt::shutdown().await;
+
+    info!("Turning off LCD backlight and camera");
+    // 关机流程不因单个外设失败而中断
+    if let Err(err) = xl9555::set_lcd_backlight(false).await {
+        warn!("Failed to turn off LCD backlight: {}", err);
+    }
+    if let Err(err) = xl9555::set_camera_power_down(true).await {
+        warn!("Failed to power down camera: {}", err);
+    }
+}
+
+/// 保存深度睡眠使用的 RTC 控制器，在启动时调用一次
+///
+/// # 参数
+/// * `lpwr` - RTC 控制器
+pub fn init(lpwr: LPWR<'static>) {
+    critical_section::with(|cs| RTC_CNTL.borrow(cs).replace(Some(lpwr)));
+}
+
+/// 休眠前关机
+///
+/// 执行关机流程后返回，由调用者决定进入浅睡眠或深度睡眠
+pub async fn shutdown_for_sleep() {
+    info!("Preparing for sleep");
+    run_shutdown_sequence().await;
+}
+
+/// 关机后进入深度睡眠
+///
+/// 到时由 RTC 定时器唤醒，唤醒后与复位一样从头启动，复位原因为深度睡眠唤醒
+///
+/// # 参数
+/// * `duration` - 睡眠时间
+pub async fn deep_sleep(duration: Duration) -> ! {
+    shutdown_for_sleep().await;
+    let lpwr = critical_section::with(|cs| RTC_CNTL.borrow(cs).take())
+        .expect("RTC controller not initialized");
+    info!("Entering deep sleep for {} s", duration.as_secs());
+    let timer = TimerWakeupSource::new(core::time::Duration::from_millis(duration.as_millis()));
+    Rtc::new(lpwr).sleep_deep(&[&timer])
+}
+
+/// 重启系统
+///
+/// 执行关机流程、记录重启原因后进行软件复位
+///
+/// # 参数
+/// * `reason` - 重启原因
+pub async fn reboot(reason: RebootReason) -> ! {
+    info!("Rebooting: {}", reason);
+    run_shutdown_sequence().await;
+
+    // SAFETY: 只在复位前写入一次，此时不会有其他代码访问该记录
+    unsafe {
+        let record = &raw mut REBOOT_RECORD;
+        (*record)[0] = REBOOT_MAGIC;
+        (*record)[1] = reason as u32;
+    }
+
+    // 等待日志输出完成
+    Timer::after_millis(100).await;
+    esp_hal::system::software_reset()
+}
+
+/// 读取并清除上一次的重启原因
+///
+/// # 返回
+/// 上一次通过 [reboot] 重启时记录的原因，其他复位方式返回 None
+pub fn last_reboot_reason() -> Option<RebootReason> {
+    // SAFETY: 只在启动阶段单线程调用
+    unsafe {
+        let record = &raw mut REBOOT_RECORD;
+        let reason = if (*record)[0] == REBOOT_MAGIC {
+            RebootReason::from_u8((*record)[1] as u8)
+        } else {
+            None
+        };
+        *record = [0; 2];
+        reason
+    }
+}
+
+/// 打印复位原因
+///
+/// # 返回
+/// 上一次通过 [reboot] 重启时记录的原因，见 [last_reboot_reason]
+pub fn log_reset_reason() -> Option<RebootReason> {
+    match esp_hal::system::reset_reason() {
+        Some(reason) => info!("Reset reason: {}", defmt::Debug2Format(&reason)),
+        None => info!("Reset reason: unknown"),
+    }
+    let reason = last_reboot_reason();
+    if let Some(reason) = reason {
+        info!("Last reboot requested by firmware: {}", reason);
+    }
+    reason
+}
+
+/// WiFi 状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WifiStatus {
+    pub connected: bool,
+    /// 最近一次登记的信号强度（dBm），未连接时为 None
+    pub rssi: Option<i32>,
+}
+
+impl ToJson for WifiStatus {
+    /// 信号强度未知时为 `null`
+    fn write_members(&self, object: &mut Object<'_>) {
+        object.bool("connected", self.connected);
+        match self.rssi {
+            Some(rssi) => object.int("rssi", rssi as i64),
+            None => object.number("rssi", f64::NAN),
+        };
+    }
+}
+
+/// 存储状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StorageStatus {
+    /// TF 卡在外设注册表中的状态
+    pub sd_card: State,
+    /// 数据记录是否在运行（见 [crate::service]）
+    pub datalog: bool,
+}
+
+impl ToJson for StorageStatus {
+    fn write_members(&self, object: &mut Object<'_>) {
+        object
+            .str("sd_card", self.sd_card.name())
+            .bool("datalog", self.datalog);
+    }
+}
+
+/// 电源状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PowerStatus {
+    /// LCD 背光是否点亮，没有 LCD 时为 false
+    pub backlight: bool,
+    /// 芯片上一次复位的原因，读不出时为 None
+    pub reset: Option<SocResetReason>,
+}
+
+impl PowerStatus {
+    /// 复位原因的名称，例如 `ChipPowerOn`
+    pub fn reset_name(&self) -> String<24> {
+        let mut name = String::new();
+        match self.reset {
+            Some(reason) => write!(name, "{:?}", reason).ok(),
+            None => name.push_str("unknown").ok(),
+        };
+        name
+    }
+}
+
+impl ToJson for PowerStatus {
+    fn write_members(&self, object: &mut Object<'_>) {
+        object
+            .bool("backlight", self.backlight)
+            .str("reset", &self.reset_name());
+    }
+}
+
+/// 系统状态报告，见模块文档
+#[derive(Debug, Clone)]
+pub struct StatusReport {
+    pub device: Identity,
+    /// 启动以来的时间
+    pub uptime: Duration,
+    /// 堆内存已用和空闲字节数
+    pub heap_used: usize,
+    pub heap_free: usize,
+    pub wifi: WifiStatus,
+    /// 当前登记的所有传感器读数
+    pub sensors: Vec<Reading>,
+    pub storage: StorageStatus,
+    pub power: PowerStatus,
+}
+
+impl ToJson for StatusReport {
+    /// 各子系统的状态写为同名的对象成员，传感器读数写为数组
+    fn write_members(&self, object: &mut Object<'_>) {
+        self.device.write_members(&mut object.object("device"));
+        object
+            .int("uptime", self.uptime.as_secs() as i64)
+            .int("heap_used", self.heap_used as i64)
+            .int("heap_free", self.heap_free as i64);
+        self.wifi.write_members(&mut object.object("wifi"));
+        {
+            let mut sensors = object.array("sensors");
+            for reading in &self.sensors {
+                reading.write_members(&mut sensors.object());
+            }
+        }
+        self.storage.write_members(&mut object.object("storage"));
+        self.power.write_members(&mut object.object("power"));
+    }
+}
+
+/// 采集当前的系统状态
+pub fn status() -> StatusReport {
+    let connected = wifi::is_connected();
+    let rssi = sensor::get("wifi.rssi")
+        .filter(|_| connected)
+        .map(|reading| reading.value as i32);
+    let lcd_online = registry::entry(Peripheral::Lcd).state == State::Online;
+    StatusReport {
+        device: device::identity(),
+        uptime: Duration::from_ticks(Instant::now().as_ticks()),
+        heap_used: esp_alloc::HEAP.used(),
+        heap_free: esp_alloc::HEAP.free(),
+        wifi: WifiStatus { connected, rssi },
+        sensors: sensor::all(),
+        storage: StorageStatus {
+            sd_card: registry::entry(Peripheral::SdCard).state,
+            datalog: Service::Datalog.is_available() && service::is_running(Service::Datalog),
+        },
+        power: PowerStatus {
+            backlight: lcd_online && xl9555::lcd_backlight(),
+            reset: esp_hal::system::reset_reason(),
+        },
+    }
+}
@@ -0,0 +1,137 @@
+//! 界面配色选择
+//!
+//! 配色定义在 [ui::theme] 中，本模块按设置选择当前配色，各屏幕绘制时通过 [current] 获取，
+//! 配色改变后重绘。设置用命令行 `theme` 修改，立即生效：
+//!
+//! ```text
+//! theme                              查看当前设置
+//! theme dark|light|contrast|auto     选择配色
+//! theme accent <rrggbb>|default      替换强调色
+//! ```
+//!
+//! `auto` 按环境亮度在深色和浅色之间切换：[ambient_task] 在 [crate::sensor] 登记表中查找单位为
+//! `lx` 的读数，超过 [LIGHT_ABOVE] 时使用浅色，低于 [DARK_BELOW] 时使用深色，两者之间保持不变，
+//! 避免在临界亮度附近来回切换。没有环境光读数时（目前还没有驱动登记 `lx` 读数）保持深色。
+
+use crate::sensor;
+#[cfg(feature = "ui")]
+use crate::settings;
+use core::sync::atomic::{AtomicBool, Ordering};
+use defmt::info;
+use embassy_time::{Duration, Timer};
+#[cfg(feature = "ui")]
+use embedded_graphics::pixelcolor::Rgb565;
+#[cfg(feature = "ui")]
+use embedded_graphics::pixelcolor::raw::RawU16;
+#[cfg(feature = "ui")]
+use ui::theme::{self, Theme};
+
+/// 环境亮度超过该值（lx）时切换到浅色
+pub const LIGHT_ABOVE: f64 = 200.0;
+
+/// 环境亮度低于该值（lx）时切换到深色
+pub const DARK_BELOW: f64 = 50.0;
+
+/// 环境亮度的单位
+const LUX_UNIT: &str = "lx";
+
+/// 超过该时长未更新的读数视为失效
+const READING_MAX_AGE: Duration = Duration::from_secs(60);
+
+/// 检查环境亮度的周期
+const POLL_PERIOD: Duration = Duration::from_secs(5);
+
+/// 自动模式下环境是否明亮
+static BRIGHT: AtomicBool = AtomicBool::new(false);
+
+/// 配色模式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Mode {
+    /// 深色
+    Dark,
+    /// 浅色
+    Light,
+    /// 高对比度
+    HighContrast,
+    /// 按环境亮度在深色和浅色之间切换
+    Auto,
+}
+
+impl Mode {
+    /// 所有模式，下标与设置中保存的编码一致
+    pub const ALL: [Mode; 4] = [Mode::Dark, Mode::Light, Mode::HighContrast, Mode::Auto];
+
+    /// 设置中保存的编码
+    pub const fn to_u8(self) -> u8 {
+        self as u8
+    }
+
+    /// 从设置中的编码解析，未知编码视为 [Mode::Dark]
+    pub const fn from_u8(value: u8) -> Mode {
+        match value {
+            1 => Mode::Light,
+            2 => Mode::HighContrast,
+            3 => Mode::Auto,
+            _ => Mode::Dark,
+        }
+    }
+
+    /// 模式名称，用于命令行参数
+    pub const fn name(self) -> &'static str {
+        match self {
+            Mode::Dark => "dark",
+            Mode::Light => "light",
+            Mode::HighContrast => "contrast",
+            Mode::Auto => "auto",
+        }
+    }
+}
+
+/// 当前配色
+#[cfg(feature = "ui")]
+pub fn current() -> Theme {
+    let settings = settings::get();
+    let base = match Mode::from_u8(settings.theme) {
+        Mode::Dark => theme::DARK,
+        Mode::Light => theme::LIGHT,
+        Mode::HighContrast => theme::HIGH_CONTRAST,
+        Mode::Auto if BRIGHT.load(Ordering::Relaxed) => theme::LIGHT,
+        Mode::Auto => theme::DARK,
+    };
+    match settings.accent {
+        0 => base,
+        accent => base.with_accent(Rgb565::from(RawU16::new(accent))),
+    }
+}
+
+/// 最新的环境亮度（lx），没有读数或读数已失效时为 None
+pub fn ambient_lux() -> Option<f64> {
+    sensor::all()
+        .into_iter()
+        .find(|r| r.unit == LUX_UNIT && r.updated.elapsed() < READING_MAX_AGE)
+        .map(|r| r.value)
+}
+
+/// 环境亮度监测任务
+///
+/// 每 5 秒检查一次环境亮度，更新自动模式使用的配色；
+/// 读数失效时保持上一次的判断。
+#[embassy_executor::task]
+pub async fn ambient_task() {
+    loop {
+        if let Some(lux) = ambient_lux() {
+            let bright = BRIGHT.load(Ordering::Relaxed);
+            let next = if bright {
+                lux >= DARK_BELOW
+            } else {
+                lux > LIGHT_ABOVE
+            };
+            if next != bright {
+                let name = if next { "light" } else { "dark" };
+                info!("Ambient light {} lx, switching to {} theme", lux, name);
+                BRIGHT.store(next, Ordering::Relaxed);
+            }
+        }
+        Timer::after(POLL_PERIOD).await;
+    }
+}
@@ -0,0 +1,474 @@
+//! 恒温控制器
+//!
+//! [thermostat_task] 每 [CONTROL_PERIOD] 把设置中选择的温度读数（例如 `bme280.t`，
+//! 见 [crate::sensor]）与设定值比较，控制 XL9555 P0.6 上的继电器（[crate::xl9555::set_relay]）：
+//!
+//! - 加热（[Mode::Heat]）：温度低于 `设定值 - 回差/2` 时吸合，高于 `设定值 + 回差/2` 时释放
+//! - 制冷（[Mode::Cool]）：方向相反
+//!
+//! 继电器吸合后至少保持 `min_on`，释放后至少保持 `min_off`，避免压缩机等负载频繁启停。
+//! 读数超过 [STALE_AFTER] 没有更新时立即释放继电器。
+//!
+//! 设定值可以通过以下方式调整，立即生效：
+//!
+//! - [Profile::Thermostat](crate::profile::Profile::Thermostat) 模式的屏幕（[screen_task]），
+//!   按键通过 [crate::keymap] 映射，默认 KEY1/KEY0 升高/降低 [KEY_STEP]
+//! - HTTP `POST /thermostat`，请求体为 `setpoint=<°C>`（见 [crate::http]）
+//! - 命令行 `thermostat set <°C>`
+//! - MQTT 的 `cmd/thermostat` 主题，消息内容为 `<°C>`（见 [crate::mqtt]）
+//!
+//! 当前状态见 HTTP `GET /api/thermostat` 和读数 `thermostat.out`。
+
+#[cfg(feature = "ui")]
+use crate::i18n::{self, Msg};
+#[cfg(feature = "ui")]
+use crate::keymap::{self, Action};
+#[cfg(feature = "ui")]
+use crate::lcd::Lcd;
+use crate::settings::{self, Settings};
+#[cfg(feature = "ui")]
+use crate::st7789::{self, St7789};
+#[cfg(feature = "ui")]
+use crate::{assets, input, theme};
+use crate::{sensor, xl9555};
+use core::cell::Cell;
+#[cfg(feature = "ui")]
+use core::fmt::Write;
+use critical_section::Mutex;
+use defmt::{info, warn};
+#[cfg(feature = "ui")]
+use embassy_time::with_timeout;
+use embassy_time::{Duration, Instant, Timer};
+#[cfg(feature = "ui")]
+use embedded_graphics::mono_font::{MonoTextStyle, MonoTextStyleBuilder};
+#[cfg(feature = "ui")]
+use embedded_graphics::pixelcolor::Rgb565;
+#[cfg(feature = "ui")]
+use embedded_graphics::prelude::*;
+#[cfg(feature = "ui")]
+use embedded_graphics::text::Text;
+#[cfg(feature = "ui")]
+use heapless::String;
+use proto::json::{Object, ToJson};
+#[cfg(feature = "ui")]
+use ui::segment::SegmentDisplay;
+#[cfg(feature = "ui")]
+use ui::theme::Theme;
+
+/// 控制周期
+const CONTROL_PERIOD: Duration = Duration::from_secs(2);
+
+/// 读数超过此时间没有更新时视为失效
+const STALE_AFTER: Duration = Duration::from_secs(60);
+
+/// 设定值范围（0.1 °C），与 BME280 的测量范围一致
+pub const SETPOINT_RANGE: core::ops::RangeInclusive<i16> = -400..=850;
+
+/// 按键每次调整的设定值（0.1 °C）
+#[cfg(feature = "ui")]
+const KEY_STEP: i16 = 5;
+
+/// 按键调整后无操作多久保存设置
+#[cfg(feature = "ui")]
+const SAVE_DELAY: Duration = Duration::from_secs(3);
+
+/// 屏幕刷新周期
+#[cfg(feature = "ui")]
+const REFRESH_PERIOD: Duration = Duration::from_millis(500);
+
+/// 输出状态读数的名称
+const OUTPUT_READING: &str = "thermostat.out";
+
+/// 标题和各行文字的基线位置
+#[cfg(feature = "ui")]
+const TITLE_Y: i32 = 26;
+#[cfg(feature = "ui")]
+const SETPOINT_Y: i32 = 150;
+#[cfg(feature = "ui")]
+const OUTPUT_Y: i32 = 180;
+#[cfg(feature = "ui")]
+const HINT_Y: i32 = 232;
+
+/// 大号数字的上边界和尺寸
+#[cfg(feature = "ui")]
+const DIGITS_Y: i32 = 44;
+#[cfg(feature = "ui")]
+const DIGIT_WIDTH: u32 = 40;
+#[cfg(feature = "ui")]
+const DIGIT_HEIGHT: u32 = 80;
+
+/// 控制方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Mode {
+    /// 加热：温度低时吸合
+    Heat,
+    /// 制冷：温度高时吸合
+    Cool,
+}
+
+impl Mode {
+    /// 所有方式，下标与设置中保存的编码一致
+    pub const ALL: [Mode; 2] = [Mode::Heat, Mode::Cool];
+
+    /// 设置中保存的编码
+    pub const fn to_u8(self) -> u8 {
+        self as u8
+    }
+
+    /// 从设置中的编码解析，未知编码视为加热
+    pub const fn from_u8(value: u8) -> Mode {
+        match value {
+            1 => Mode::Cool,
+            _ => Mode::Heat,
+        }
+    }
+
+    /// 方式名称，用于命令行参数和 JSON
+    pub const fn name(self) -> &'static str {
+        match self {
+            Mode::Heat => "heat",
+            Mode::Cool => "cool",
+        }
+    }
+}
+
+/// 控制参数
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Config {
+    mode: Mode,
+    /// 设定值（°C）
+    setpoint: f32,
+    /// 回差（°C）
+    hysteresis: f32,
+    min_on: Duration,
+    min_off: Duration,
+}
+
+impl Config {
+    fn from_settings(s: &Settings) -> Self {
+        Config {
+            mode: Mode::from_u8(s.thermostat_mode),
+            setpoint: s.thermostat_setpoint as f32 / 10.0,
+            hysteresis: s.thermostat_hysteresis as f32 / 10.0,
+            min_on: Duration::from_secs(s.thermostat_min_on as u64),
+            min_off: Duration::from_secs(s.thermostat_min_off as u64),
+        }
+    }
+}
+
+/// 控制器状态
+#[derive(Debug, Clone, Copy, PartialEq, defmt::Format)]
+pub struct Status {
+    /// 是否选择了温度读数
+    pub enabled: bool,
+    /// 当前温度（°C），没有有效读数时为 None
+    pub temperature: Option<f32>,
+    /// 继电器是否吸合
+    pub output: bool,
+}
+
+impl ToJson for Status {
+    /// 状态和当前设置，没有有效读数时温度为 `null`
+    fn write_members(&self, object: &mut Object<'_>) {
+        let s = settings::get();
+        object
+            .str("source", &s.thermostat_source)
+            .str("mode", Mode::from_u8(s.thermostat_mode).name())
+            .number("setpoint", s.thermostat_setpoint as f64 / 10.0)
+            .number("hysteresis", s.thermostat_hysteresis as f64 / 10.0)
+            .int("min_on", s.thermostat_min_on as i64)
+            .int("min_off", s.thermostat_min_off as i64)
+            .number("temperature", self.temperature.map_or(f64::NAN, f64::from))
+            .bool("output", self.output);
+    }
+}
+
+/// 最近一次控制的结果
+static STATUS: Mutex<Cell<Status>> = Mutex::new(Cell::new(Status {
+    enabled: false,
+    temperature: None,
+    output: false,
+}));
+
+/// 当前状态
+pub fn status() -> Status {
+    critical_section::with(|cs| STATUS.borrow(cs).get())
+}
+
+/// 解析以 °C 为单位、最多一位小数的温度，例如 `21.5`、`-3`
+///
+/// # 返回
+/// 温度（0.1 °C），格式错误时返回 None
+pub fn parse_tenths(text: &str) -> Option<i16> {
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, text),
+    };
+    let (whole, fraction) = digits.split_once('.').unwrap_or((digits, "0"));
+    let is_digits = |part: &str| !part.is_empty() && part.bytes().all(|b| b.is_ascii_digit());
+    if !is_digits(whole) || !is_digits(fraction) || fraction.len() != 1 {
+        return None;
+    }
+    let value = whole
+        .parse::<i16>()
+        .ok()?
+        .checked_mul(10)?
+        .checked_add(fraction.parse::<i16>().ok()?)?;
+    Some(if negative { -value } else { value })
+}
+
+/// 修改设定值（不会自动保存）
+///
+/// # 参数
+/// * `tenths` - 新的设定值（0.1 °C），超出 [SETPOINT_RANGE] 时取边界值
+pub fn set_setpoint(tenths: i16) {
+    let tenths = tenths.clamp(*SETPOINT_RANGE.start(), *SETPOINT_RANGE.end());
+    settings::update(|s| s.thermostat_setpoint = tenths);
+}
+
+/// 两位置控制逻辑
+struct Controller {
+    output: bool,
+    /// 上次切换输出的时刻，启动后尚未切换过时为 None
+    switched: Option<Instant>,
+}
+
+impl Controller {
+    /// 计算新的输出
+    ///
+    /// # 参数
+    /// * `temperature` - 当前温度，没有有效读数时为 None
+    ///
+    /// # 返回
+    /// 输出是否改变
+    fn update(&mut self, config: &Config, temperature: Option<f32>, now: Instant) -> bool {
+        let Some(t) = temperature else {
+            // 没有读数时立即释放，不受最短时间限制
+            let changed = self.output;
+            self.output = false;
+            return changed;
+        };
+        let low = config.setpoint - config.hysteresis / 2.0;
+        let high = config.setpoint + config.hysteresis / 2.0;
+        let demand = match (config.mode, self.output) {
+            (Mode::Heat, false) => t < low,
+            (Mode::Heat, true) => t < high,
+            (Mode::Cool, false) => t > high,
+            (Mode::Cool, true) => t > low,
+        };
+        let hold = if self.output {
+            config.min_on
+        } else {
+            config.min_off
+        };
+        let held = self
+            .switched
+            .is_some_and(|at| now.duration_since(at) < hold);
+        if demand == self.output || held {
+            return false;
+        }
+        self.output = demand;
+        self.switched = Some(now);
+        true
+    }
+}
+
+/// 恒温控制任务
+///
+/// 没有选择温度读数时继电器保持释放
+#[embassy_executor::task]
+pub async fn thermostat_task() {
+    if let Err(err) = xl9555::set_relay(false).await {
+        warn!("Failed to release thermostat relay: {}", err);
+    }
+    let mut controller = Controller {
+        output: false,
+        switched: None,
+    };
+    loop {
+        let s = settings::get();
+        let enabled = !s.thermostat_source.is_empty();
+        let temperature = sensor::get(&s.thermostat_source)
+            .filter(|reading| reading.updated.elapsed() < STALE_AFTER)
+            .map(|reading| reading.value as f32)
+            .filter(|_| enabled);
+        let config = Config::from_settings(&s);
+        if controller.update(&config, temperature, Instant::now()) {
+            info!(
+                "Thermostat output {} at {} C",
+                if controller.output { "on" } else { "off" },
+                temperature
+            );
+            if let Err(err) = xl9555::set_relay(controller.output).await {
+                warn!("Failed to switch thermostat relay: {}", err);
+            }
+        }
+        if enabled {
+            sensor::publish(OUTPUT_READING, controller.output as u8 as f64, "");
+        }
+        let status = Status {
+            enabled,
+            temperature,
+            output: controller.output,
+        };
+        critical_section::with(|cs| STATUS.borrow(cs).set(status));
+        Timer::after(CONTROL_PERIOD).await;
+    }
+}
+
+/// 恒温器屏幕任务
+///
+/// 显示当前温度、设定值和继电器状态，按键调整设定值
+///
+/// # 参数
+/// * `lcd` - 已完成初始化的 LCD
+#[cfg(feature = "ui")]
+#[embassy_executor::task]
+pub async fn screen_task(mut lcd: Lcd) {
+    let Some(mut keys) = input::subscribe() else {
+        warn!("No key subscriber available for thermostat");
+        return;
+    };
+    input::set_captured(true);
+
+    let mut colors = theme::current();
+    let mut digits = clear_screen(&mut lcd, &colors);
+    // 已显示的设定值和输出状态，None 表示需要重绘
+    let mut shown: Option<(i16, Msg)> = None;
+    // 按键调整后尚未保存时为最后一次调整的时刻
+    let mut unsaved: Option<Instant> = None;
+    let mut text: String<16> = String::new();
+    let mut line: String<40> = String::new();
+    loop {
+        let current = theme::current();
+        if current != colors {
+            colors = current;
+            digits = clear_screen(&mut lcd, &colors);
+            shown = None;
+        }
+
+        let status = status();
+        let setpoint = settings::get().thermostat_setpoint;
+        text.clear();
+        match status.temperature {
+            Some(t) => write!(text, "{:5.1}", t).ok(),
+            None => text.push_str(" --.-").ok(),
+        };
+        digits.set_color(if status.output {
+            colors.accent
+        } else {
+            colors.foreground
+        });
+        if let Err(err) = digits.show(&mut lcd, &text) {
+            warn!("Failed to draw thermostat digits: {}", err);
+        }
+
+        let output = match (status.enabled, status.output) {
+            (false, _) => Msg::ThermostatNotConfigured,
+            (true, true) => Msg::ThermostatOutputOn,
+            (true, false) => Msg::ThermostatOutputOff,
+        };
+        if shown != Some((setpoint, output)) {
+            let style = text_style(&colors);
+            line.clear();
+            write!(
+                line,
+                "{} {:.1} C",
+                i18n::lcd(Msg::ThermostatSetpoint),
+                setpoint as f32 / 10.0
+            )
+            .ok();
+            draw_line(&mut lcd, &line, SETPOINT_Y, &colors, style);
+            draw_line(&mut lcd, i18n::lcd(output), OUTPUT_Y, &colors, style);
+            shown = Some((setpoint, output));
+        }
+
+        if let Some(at) = unsaved
+            && at.elapsed() >= SAVE_DELAY
+        {
+            unsaved = None;
+            if let Err(err) = settings::save() {
+                warn!("Failed to save thermostat setpoint: {}", err);
+            }
+        }
+
+        let Ok(key) = with_timeout(REFRESH_PERIOD, keys.next_message_pure()).await else {
+            continue;
+        };
+        let step = match keymap::action(key) {
+            Some(Action::ScrollUp) => KEY_STEP,
+            Some(Action::ScrollDown) => -KEY_STEP,
+            _ => continue,
+        };
+        set_setpoint(setpoint.saturating_add(step));
+        unsaved = Some(Instant::now());
+    }
+}
+
+/// 用配色的背景色清屏并显示标题和按键提示
+///
+/// # 返回
+/// 新的大号数字显示区域，下次显示时完整绘制
+#[cfg(feature = "ui")]
+fn clear_screen(lcd: &mut St7789, colors: &Theme) -> SegmentDisplay {
+    if let Err(err) = lcd.fill_screen(colors.background) {
+        warn!("Failed to clear LCD: {}", err);
+    }
+    let accent = MonoTextStyleBuilder::new()
+        .font(assets::font())
+        .text_color(colors.accent)
+        .background_color(colors.background)
+        .build();
+    draw_text(lcd, i18n::lcd(Msg::ThermostatTitle), TITLE_Y, accent);
+
+    let number = |action| keymap::key_name(keymap::key_for(action)).trim_start_matches("key");
+    let mut hint: String<40> = String::new();
+    write!(
+        hint,
+        "K{}/K{} {}",
+        number(Action::ScrollUp),
+        number(Action::ScrollDown),
+        i18n::lcd(Msg::ThermostatAdjust)
+    )
+    .ok();
+    draw_text(lcd, &hint, HINT_Y, text_style(colors));
+
+    SegmentDisplay::new(
+        Point::new(0, DIGITS_Y),
+        DIGIT_WIDTH,
+        DIGIT_HEIGHT,
+        colors.foreground,
+    )
+    .with_background(colors.background)
+    .centered("-00.0")
+}
+
+#[cfg(feature = "ui")]
+fn text_style(colors: &Theme) -> MonoTextStyle<'static, Rgb565> {
+    MonoTextStyleBuilder::new()
+        .font(assets::font())
+        .text_color(colors.foreground)
+        .background_color(colors.background)
+        .build()
+}
+
+/// 清除一行后绘制文字
+#[cfg(feature = "ui")]
+fn draw_line(
+    lcd: &mut St7789,
+    text: &str,
+    y: i32,
+    colors: &Theme,
+    style: MonoTextStyle<'_, Rgb565>,
+) {
+    lcd.fill_rectangle(0, (y - 18) as u16, st7789::WIDTH, 24, colors.background)
+        .ok();
+    draw_text(lcd, text, y, style);
+}
+
+#[cfg(feature = "ui")]
+fn draw_text(lcd: &mut St7789, text: &str, y: i32, style: MonoTextStyle<'_, Rgb565>) {
+    if let Err(err) = Text::new(text, Point::new(10, y), style).draw(lcd) {
+        warn!("Failed to draw thermostat text: {}", err);
+    }
+}
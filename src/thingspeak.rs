@@ -0,0 +1,109 @@
+//! 上传气象读数到 ThingSpeak
+//!
+//! 气象站模式（见 [crate::profile]）下可选：设置了频道的写入 Key（命令行
+//! `forecast upload <key>`）后，[upload_task] 每 [UPLOAD_INTERVAL] 把 BME280 的读数
+//! 上传到 ThingSpeak 频道，字段依次为：
+//!
+//! - `field1`：温度（°C）
+//! - `field2`：相对湿度（%）
+//! - `field3`：气压（hPa）
+//!
+//! 只上传最近 [STALE_AFTER] 内更新过的读数，传感器离线时跳过这一次。
+//!
+//! 与天气预报（见 [crate::forecast]）一样通过明文 HTTP 访问，写入 Key 以明文出现在请求的 URL 中，
+//! 同一网络中的其他设备可以截获，只能写入这一个频道。
+
+use crate::http_client::{self, HttpClientError};
+use crate::{sensor, settings};
+use core::fmt::Write;
+use defmt::{info, warn};
+use embassy_net::Stack;
+use embassy_time::{Duration, Timer};
+use heapless::String;
+
+/// 服务器
+const HOST: &str = "api.thingspeak.com";
+
+/// 上传间隔，与气象站屏幕的历史记录间隔相同
+const UPLOAD_INTERVAL: Duration = Duration::from_secs(600);
+
+/// 未设置 Key、读数过期或上传失败时的重试间隔
+const RETRY_INTERVAL: Duration = Duration::from_secs(60);
+
+/// 读数超过该时长未更新视为传感器离线
+const STALE_AFTER: Duration = Duration::from_secs(60);
+
+/// 依次对应 `field1`、`field2`、`field3` 的读数
+const FIELDS: [&str; 3] = ["bme280.temp", "bme280.hum", "bme280.press"];
+
+/// 上传错误
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum UploadError {
+    /// 未设置写入 Key
+    NoKey,
+    /// 没有最近的读数
+    NoReadings,
+    /// 请求失败
+    Http(HttpClientError),
+    /// 服务器拒绝了写入（Key 错误或超过频率限制），响应为 `0`
+    Rejected,
+}
+
+/// 上传一次当前的读数
+///
+/// # 参数
+/// * `stack` - 网络协议栈
+///
+/// # 返回
+/// 服务器分配的记录编号
+pub async fn upload(stack: Stack<'_>) -> Result<u32, UploadError> {
+    let key = settings::get().thingspeak_key;
+    if key.is_empty() {
+        return Err(UploadError::NoKey);
+    }
+    let mut path: String<160> = String::new();
+    write!(path, "/update?api_key={}", key).ok();
+    let mut found = false;
+    for (i, name) in FIELDS.iter().enumerate() {
+        let reading = sensor::get(name).filter(|r| r.updated.elapsed() < STALE_AFTER);
+        if let Some(reading) = reading {
+            write!(path, "&field{}={:.2}", i + 1, reading.value).ok();
+            found = true;
+        }
+    }
+    if !found {
+        return Err(UploadError::NoReadings);
+    }
+
+    let mut buf = [0u8; 256];
+    let body = http_client::get(stack, &http_client::OPTIONS, HOST, 80, &path, &mut buf)
+        .await
+        .map_err(UploadError::Http)?;
+    let entry = core::str::from_utf8(body)
+        .ok()
+        .and_then(|text| text.trim().parse::<u32>().ok());
+    entry.filter(|&id| id > 0).ok_or(UploadError::Rejected)
+}
+
+/// 周期上传任务
+///
+/// # 参数
+/// * `stack` - 网络协议栈
+#[embassy_executor::task]
+pub async fn upload_task(stack: Stack<'static>) {
+    loop {
+        stack.wait_config_up().await;
+        let delay = match upload(stack).await {
+            Ok(entry) => {
+                info!("Weather readings uploaded as entry {}", entry);
+                UPLOAD_INTERVAL
+            }
+            Err(UploadError::NoKey | UploadError::NoReadings) => RETRY_INTERVAL,
+            Err(err) => {
+                warn!("Weather upload failed: {}", err);
+                RETRY_INTERVAL
+            }
+        };
+        Timer::after(delay).await;
+    }
+}
@@ -0,0 +1,119 @@
+//! LCD 面板调校
+//!
+//! 不同批次的 ATK-MD0240 面板液晶特性不同，同一组 Gamma 参数在有的面板上暗部发灰，
+//! 在有的面板上偏色。调校参数（预设 Gamma 曲线、对比度、颜色反转和两张 Gamma 校正表）
+//! 保存在设置中，启动时和渲染任务（[crate::render]）发现参数改变后用 [apply] 发送给控制器，
+//! 立即生效，不需要重启。
+//!
+//! 在设置页面按 KEY2 打开校准页面（见 [crate::screens]），对照灰阶、三原色渐变和测试图案
+//! 用按键调整曲线、对比度和反转；校正表和其他参数也可以用命令行 `lcd` 修改：
+//!
+//! ```text
+//! lcd                                      查看当前参数
+//! lcd gamma 2.2|1.8|2.5|1.0                选择预设 Gamma 曲线
+//! lcd contrast <0-27>                      设置对比度（VRHS 参数）
+//! lcd invert on|off                        开关颜色反转
+//! lcd table pos|neg <28 位十六进制>|default  替换 Gamma 校正表
+//! lcd reset                                恢复默认参数
+//! ```
+//!
+//! 调校参数只适用于 ST7789，识别为 ILI9341 的面板（见 [crate::st7789::init]）不发送。
+//!
+//! 背光只能通过 XL9555 开关，没有 PWM 调光，亮度无法调节；对比度调整的是 GVDD 电压，
+//! 值越大整体越亮、暗部越浅。
+
+use crate::settings::{self, Settings};
+use crate::st7789::{self, Controller, St7789};
+use drivers::st7789::{GammaCurve, Tuning};
+use esp_hal::spi::Error as SpiError;
+
+pub use drivers::st7789::CONTRAST_MAX;
+
+/// 预设 Gamma 曲线
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Curve {
+    /// Gamma 2.2，默认
+    G2_2,
+    /// Gamma 1.8
+    G1_8,
+    /// Gamma 2.5
+    G2_5,
+    /// Gamma 1.0
+    G1_0,
+}
+
+impl Curve {
+    /// 所有曲线，下标与设置中保存的编码一致，校准页面按此顺序切换
+    pub const ALL: [Curve; 4] = [Curve::G2_2, Curve::G1_8, Curve::G2_5, Curve::G1_0];
+
+    /// 设置中保存的编码
+    pub const fn to_u8(self) -> u8 {
+        self as u8
+    }
+
+    /// 从设置中的编码解析，未知编码视为 [Curve::G2_2]
+    pub const fn from_u8(value: u8) -> Curve {
+        match value {
+            1 => Curve::G1_8,
+            2 => Curve::G2_5,
+            3 => Curve::G1_0,
+            _ => Curve::G2_2,
+        }
+    }
+
+    /// 曲线名称，用于命令行参数和校准页面
+    pub const fn name(self) -> &'static str {
+        match self {
+            Curve::G2_2 => "2.2",
+            Curve::G1_8 => "1.8",
+            Curve::G2_5 => "2.5",
+            Curve::G1_0 => "1.0",
+        }
+    }
+
+    /// 对应的 GAMSET 曲线
+    const fn gamma(self) -> GammaCurve {
+        match self {
+            Curve::G2_2 => GammaCurve::G2_2,
+            Curve::G1_8 => GammaCurve::G1_8,
+            Curve::G2_5 => GammaCurve::G2_5,
+            Curve::G1_0 => GammaCurve::G1_0,
+        }
+    }
+}
+
+/// 当前的调校参数
+pub fn current() -> Tuning {
+    let settings = settings::get();
+    Tuning {
+        positive_gamma: settings.lcd_positive_gamma,
+        negative_gamma: settings.lcd_negative_gamma,
+        curve: Curve::from_u8(settings.lcd_curve).gamma(),
+        contrast: settings.lcd_contrast.min(CONTRAST_MAX),
+        inverted: settings.lcd_inverted,
+    }
+}
+
+/// 把调校参数发送给控制器，面板不是 ST7789 时忽略
+///
+/// # 参数
+/// * `lcd` - 已完成初始化的 LCD
+/// * `tuning` - 调校参数
+pub fn apply(lcd: &mut St7789, tuning: &Tuning) -> Result<(), SpiError> {
+    match st7789::panel() {
+        Some(panel) if panel.controller == Controller::Ili9341 => Ok(()),
+        _ => lcd.apply_tuning(tuning),
+    }
+}
+
+/// 恢复默认参数（不保存）
+pub fn reset() {
+    let defaults = Settings::DEFAULT;
+    settings::update(|s| {
+        s.lcd_curve = defaults.lcd_curve;
+        s.lcd_contrast = defaults.lcd_contrast;
+        s.lcd_inverted = defaults.lcd_inverted;
+        s.lcd_positive_gamma = defaults.lcd_positive_gamma;
+        s.lcd_negative_gamma = defaults.lcd_negative_gamma;
+    });
+}
@@ -0,0 +1,251 @@
+//! 系统时间（UTC）
+//!
+//! 芯片上电后只有单调时钟 [Instant]，没有日历时间。时间源（GPS、NTP 等）通过 [set]
+//! 提供当前 UTC 时间，之后 [now] 以单调时钟推算。
+//!
+//! 时间源按 [TimeSource] 的顺序排列优先级：高优先级的时间源在 [SOURCE_HOLD] 内校准过时，
+//! 低优先级时间源的校准会被忽略，例如 NTP 可用时 GPS 只作为后备。
+//!
+//! 单调时钟来自晶振，有几十 ppm 以内的频率偏差，离线一天可能差出数秒。两次 NTP 或 GPS
+//! 校准相隔 [DRIFT_MIN_INTERVAL] 以上时，用推算时间与新校准时间之差测量这一偏差
+//! （[drift_ppb]），之后推算时间时按它修正，离线记录数据时时间戳更准确。
+//! 测得的偏差保存在设置中，重启后首次校准即可使用。
+//!
+//! 限制：板上没有独立的 RTC 芯片（DS1307 等），断电后时间丢失，重启后仍需要时间源；
+//! 偏差随温度变化，这里只测量平均值，不做温度补偿。
+
+use crate::settings;
+use core::cell::RefCell;
+use critical_section::Mutex;
+use defmt::{info, warn};
+use embassy_time::{Duration, Instant};
+
+/// 高优先级时间源校准后，低优先级时间源被忽略的时长
+const SOURCE_HOLD: Duration = Duration::from_secs(3600);
+
+/// 测量漂移的两次校准之间的最短间隔，间隔太短时网络延迟的抖动占主导
+const DRIFT_MIN_INTERVAL: Duration = Duration::from_secs(30 * 60);
+
+/// 可信的最大频率偏差（ppb），超出时认为时间源跳变，不计入
+const DRIFT_MAX_PPB: i64 = 200_000;
+
+/// 频率偏差变化超过此值（ppb）时保存到设置
+const DRIFT_SAVE_STEP: i32 = 1_000;
+
+/// 时间源，越靠后优先级越高
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, defmt::Format)]
+pub enum TimeSource {
+    /// 用户手动设置
+    Manual,
+    /// GPS 接收机
+    Gps,
+    /// 网络时间协议
+    Ntp,
+}
+
+/// 校准状态
+#[derive(Debug, Clone, Copy)]
+struct Calibration {
+    /// 校准时的 UNIX 时间（微秒）
+    unix_us: i64,
+    source: TimeSource,
+    at: Instant,
+    /// 单调时钟的频率偏差（ppb），正值表示单调时钟走得慢，0 表示未测量
+    drift_ppb: i32,
+}
+
+impl Calibration {
+    /// 按频率偏差修正后推算某一时刻的 UNIX 时间（微秒）
+    fn unix_at(&self, now: Instant) -> i64 {
+        let elapsed = now.as_micros() as i64 - self.at.as_micros() as i64;
+        let correction = elapsed as i128 * self.drift_ppb as i128 / 1_000_000_000;
+        self.unix_us + elapsed + correction as i64
+    }
+
+    /// 用新的校准值修正频率偏差
+    ///
+    /// 两次校准都不是手动设置且间隔足够长时，由推算值与新校准值之差得出剩余的偏差；
+    /// 第一次测量直接采用，之后与原值平均，平滑网络延迟的抖动
+    ///
+    /// # 返回
+    /// 修正后的频率偏差（ppb）
+    fn corrected_drift(&self, unix_us: i64, source: TimeSource, now: Instant) -> i32 {
+        let elapsed = now.duration_since(self.at);
+        if self.source == TimeSource::Manual
+            || source == TimeSource::Manual
+            || elapsed < DRIFT_MIN_INTERVAL
+        {
+            return self.drift_ppb;
+        }
+        let error_us = unix_us - self.unix_at(now);
+        let residual = error_us as i128 * 1_000_000_000 / elapsed.as_micros() as i128;
+        if residual.abs() > DRIFT_MAX_PPB as i128 {
+            warn!("Ignoring clock step of {} us", error_us);
+            return self.drift_ppb;
+        }
+        let measured = self.drift_ppb as i128 + residual;
+        let drift = match self.drift_ppb {
+            0 => measured,
+            current => (current as i128 + measured) / 2,
+        };
+        drift.clamp(-DRIFT_MAX_PPB as i128, DRIFT_MAX_PPB as i128) as i32
+    }
+}
+
+static SYNC: Mutex<RefCell<Option<Calibration>>> = Mutex::new(RefCell::new(None));
+
+/// 日历时间（UTC）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct DateTime {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+}
+
+impl DateTime {
+    /// 转换为 UNIX 时间（秒），1970 年之前的日期返回 None
+    pub fn to_unix(self) -> Option<u64> {
+        let days = days_from_civil(self.year as i64, self.month as i64, self.day as i64);
+        let secs = days * 86400
+            + self.hour as i64 * 3600
+            + self.minute as i64 * 60
+            + self.second as i64;
+        u64::try_from(secs).ok()
+    }
+
+    /// 从 UNIX 时间（秒）转换
+    pub fn from_unix(secs: u64) -> DateTime {
+        let days = (secs / 86400) as i64;
+        let rem = secs % 86400;
+        let (year, month, day) = civil_from_days(days);
+        DateTime {
+            year: year as u16,
+            month: month as u8,
+            day: day as u8,
+            hour: (rem / 3600) as u8,
+            minute: (rem / 60 % 60) as u8,
+            second: (rem % 60) as u8,
+        }
+    }
+}
+
+/// 1970-01-01 起的天数（Howard Hinnant 的 days_from_civil 算法）
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year - era * 400;
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+/// [days_from_civil] 的逆运算
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+/// 用时间源提供的 UTC 时间校准系统时间
+///
+/// # 参数
+/// * `unix_us` - UNIX 时间（微秒）
+/// * `source` - 时间源
+///
+/// # 返回
+/// 是否被采纳；高优先级时间源近期校准过时返回 false
+pub fn set(unix_us: u64, source: TimeSource) -> bool {
+    let now = Instant::now();
+    // 被采纳时返回之前的校准状态和新的频率偏差
+    let accepted = critical_section::with(|cs| {
+        let mut sync = SYNC.borrow_ref_mut(cs);
+        let previous = *sync;
+        if let Some(current) = previous
+            && current.source > source
+            && now.duration_since(current.at) < SOURCE_HOLD
+        {
+            return None;
+        }
+        let drift_ppb = match previous {
+            Some(current) => current.corrected_drift(unix_us as i64, source, now),
+            // 首次校准时沿用上次运行测得的偏差
+            None => settings::get().clock_drift_ppb,
+        };
+        *sync = Some(Calibration {
+            unix_us: unix_us as i64,
+            source,
+            at: now,
+            drift_ppb,
+        });
+        Some((previous, drift_ppb))
+    });
+    let Some((previous, drift_ppb)) = accepted else {
+        return false;
+    };
+    match previous {
+        None => {
+            let time = DateTime::from_unix(unix_us / 1_000_000);
+            info!("System time set from {}: {}", source, time);
+        }
+        Some(previous) if previous.drift_ppb != drift_ppb => {
+            info!("Clock drift measured: {} ppb", drift_ppb);
+            save_drift(drift_ppb);
+        }
+        Some(_) => {}
+    }
+    true
+}
+
+/// 频率偏差变化较大时保存到设置
+fn save_drift(drift_ppb: i32) {
+    let saved = settings::get().clock_drift_ppb;
+    if (drift_ppb - saved).abs() < DRIFT_SAVE_STEP {
+        return;
+    }
+    settings::update(|s| s.clock_drift_ppb = drift_ppb);
+    if let Err(err) = settings::save() {
+        warn!("Failed to save clock drift: {}", err);
+    }
+}
+
+/// 当前 UNIX 时间（微秒），尚未校准时返回 None
+pub fn now_micros() -> Option<u64> {
+    let sync = critical_section::with(|cs| *SYNC.borrow_ref(cs))?;
+    u64::try_from(sync.unix_at(Instant::now())).ok()
+}
+
+/// 当前 UNIX 时间（秒），尚未校准时返回 None
+pub fn now() -> Option<u64> {
+    now_micros().map(|us| us / 1_000_000)
+}
+
+/// 当前本地时间和星期（0 为星期日），按设置中的 `utc_offset` 换算，尚未校准时返回 None
+pub fn local_now() -> Option<(DateTime, usize)> {
+    let offset = settings::get().utc_offset as i64 * 60;
+    let secs = u64::try_from(now()? as i64 + offset).ok()?;
+    // 1970-01-01 是星期四
+    let weekday = ((secs / 86400 + 4) % 7) as usize;
+    Some((DateTime::from_unix(secs), weekday))
+}
+
+/// 最近一次校准使用的时间源
+pub fn source() -> Option<TimeSource> {
+    critical_section::with(|cs| SYNC.borrow_ref(cs).map(|sync| sync.source))
+}
+
+/// 单调时钟的频率偏差（ppb），尚未校准或未测量时返回 None
+pub fn drift_ppb() -> Option<i32> {
+    critical_section::with(|cs| SYNC.borrow_ref(cs).map(|sync| sync.drift_ppb))
+        .filter(|&drift| drift != 0)
+}
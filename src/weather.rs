@@ -0,0 +1,415 @@
+//! 气象站屏幕
+//!
+//! [Profile::WeatherStation](crate::profile::Profile::WeatherStation) 模式下代替渲染任务
+//! 占用 LCD，显示：
+//!
+//! - 当前 UTC 日期时间（见 [crate::wallclock]，未校准时显示运行时间）
+//! - 温度、湿度、气压（来自 [crate::sensor] 中的 `bme280.*` 读数）
+//! - 每项最近 24 小时的最低/最高值，以及与 1 小时前相比的变化趋势箭头
+//! - 屏幕底部未来几天的天气图标和最高/最低气温（见 [crate::forecast]）
+//!
+//! 历史数据每 [SAMPLE_INTERVAL] 记录一次，只保存在内存中，重启后重新累计。
+//! 读数还可以上传到 ThingSpeak 频道（可选），见 [crate::thingspeak]。
+//! 文字和背景颜色来自 [crate::theme]，配色改变时整屏重绘；趋势箭头和天气图标使用固定颜色。
+
+use crate::forecast::{self, Condition, FORECAST_DAYS, Forecast};
+use crate::i18n::{self, Msg};
+use crate::lcd::Lcd;
+use crate::st7789::{self, St7789};
+use crate::wallclock::{self, DateTime};
+use crate::{assets, jitter, sensor, theme};
+use core::fmt::Write;
+use defmt::warn;
+use embassy_time::{Duration, Instant};
+use embedded_graphics::mono_font::ascii::FONT_6X10;
+use embedded_graphics::mono_font::{MonoTextStyle, MonoTextStyleBuilder};
+use embedded_graphics::pixelcolor::Rgb565;
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::{Circle, Line, PrimitiveStyle, Rectangle, Triangle};
+use embedded_graphics::text::Text;
+use esp_hal::spi::Error as SpiError;
+use heapless::{Deque, String};
+use ui::theme::Theme;
+
+/// 屏幕刷新周期
+const REFRESH_PERIOD: Duration = Duration::from_secs(1);
+
+/// 历史数据记录间隔
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(600);
+
+/// 保存的历史样本数（24 小时）
+const HISTORY_LEN: usize = 144;
+
+/// 计算趋势时与多少个样本之前比较（1 小时）
+const TREND_SAMPLES: usize = 6;
+
+/// 读数超过该时长未更新视为传感器离线
+const STALE_AFTER: Duration = Duration::from_secs(60);
+
+/// 标题行基线位置
+const CLOCK_Y: i32 = 26;
+
+/// 第一项读数的基线位置
+const FIRST_ROW_Y: i32 = 60;
+
+/// 每项读数占用的高度（数值行加最低/最高值行）
+const ROW_HEIGHT: i32 = 40;
+
+/// 最低/最高值行相对数值行的偏移
+const RANGE_OFFSET: i32 = 16;
+
+/// 天气预报区域的上边界
+const FORECAST_Y: i32 = 168;
+
+/// 每天预报占用的宽度
+const FORECAST_COLUMN: i32 = st7789::WIDTH as i32 / FORECAST_DAYS as i32;
+
+/// 天气图标边长
+const ICON_SIZE: i32 = 24;
+
+/// 趋势箭头的左边界
+const ARROW_X: i32 = 260;
+
+/// 一项气象读数
+struct Quantity {
+    /// 传感器登记表中的名称
+    sensor: &'static str,
+    label: Msg,
+    unit: &'static str,
+    /// 1 小时内变化超过该值才显示上升或下降
+    threshold: f32,
+}
+
+/// 显示的读数，顺序即屏幕上的顺序
+const QUANTITIES: [Quantity; 3] = [
+    Quantity {
+        sensor: "bme280.temp",
+        label: Msg::WeatherTemperature,
+        unit: "C",
+        threshold: 0.5,
+    },
+    Quantity {
+        sensor: "bme280.hum",
+        label: Msg::WeatherHumidity,
+        unit: "%",
+        threshold: 3.0,
+    },
+    Quantity {
+        sensor: "bme280.press",
+        label: Msg::WeatherPressure,
+        unit: "hPa",
+        threshold: 1.0,
+    },
+];
+
+/// 变化趋势
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Trend {
+    Rising,
+    Steady,
+    Falling,
+}
+
+/// 一项读数的历史样本
+struct History {
+    samples: Deque<f32, HISTORY_LEN>,
+}
+
+impl History {
+    const fn new() -> Self {
+        History {
+            samples: Deque::new(),
+        }
+    }
+
+    fn push(&mut self, value: f32) {
+        if self.samples.is_full() {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(value).ok();
+    }
+
+    /// 包含当前值在内的最低值和最高值
+    fn range(&self, current: f32) -> (f32, f32) {
+        self.samples
+            .iter()
+            .fold((current, current), |(lo, hi), &v| (lo.min(v), hi.max(v)))
+    }
+
+    /// 与 [TREND_SAMPLES] 个样本之前比较的趋势，样本不足时取最早的样本
+    fn trend(&self, current: f32, threshold: f32) -> Trend {
+        let back = self.samples.len().min(TREND_SAMPLES);
+        let Some(&past) = self.samples.iter().nth(self.samples.len() - back) else {
+            return Trend::Steady;
+        };
+        if current - past >= threshold {
+            Trend::Rising
+        } else if past - current >= threshold {
+            Trend::Falling
+        } else {
+            Trend::Steady
+        }
+    }
+}
+
+/// 气象站屏幕任务
+///
+/// # 参数
+/// * `lcd` - 已完成初始化的 LCD
+#[embassy_executor::task]
+pub async fn weather_task(mut lcd: Lcd) {
+    let mut colors = None;
+    let mut histories = [History::new(), History::new(), History::new()];
+    let mut last_sample: Option<Instant> = None;
+    // 预报只在更新后重绘，None 表示尚未绘制过
+    let mut forecast_drawn: Option<Option<Instant>> = None;
+    let mut monitor = jitter::Monitor::new("weather", REFRESH_PERIOD);
+    let mut line: String<40> = String::new();
+
+    loop {
+        let current = theme::current();
+        if colors != Some(current) {
+            if let Err(err) = lcd.fill_screen(current.background).await {
+                warn!("Failed to clear LCD: {}", err);
+            }
+            colors = Some(current);
+            forecast_drawn = None;
+        }
+        let style = |font, color| -> MonoTextStyle<'static, Rgb565> {
+            MonoTextStyleBuilder::new()
+                .font(font)
+                .text_color(color)
+                .background_color(current.background)
+                .build()
+        };
+        let clock_style = style(assets::font(), current.accent);
+        let value_style = style(assets::font(), current.foreground);
+        let range_style = style(&FONT_6X10, current.muted);
+        let forecast_style = style(&FONT_6X10, current.foreground);
+
+        line.clear();
+        format_clock(&mut line);
+        draw_text(&mut lcd, &line, 10, CLOCK_Y, clock_style);
+
+        let now = Instant::now();
+        let sample_due = last_sample.is_none_or(|at| now.duration_since(at) >= SAMPLE_INTERVAL);
+        let mut sampled = false;
+
+        for (row, (quantity, history)) in QUANTITIES.iter().zip(&mut histories).enumerate() {
+            let y = FIRST_ROW_Y + row as i32 * ROW_HEIGHT;
+            let label = i18n::lcd(quantity.label);
+            let reading = sensor::get(quantity.sensor)
+                .filter(|r| now.duration_since(r.updated) < STALE_AFTER)
+                .map(|r| r.value as f32);
+
+            line.clear();
+            let Some(value) = reading else {
+                write!(line, "{:<10}{:>8} {:<4}", label, "--", quantity.unit).ok();
+                draw_text(&mut lcd, &line, 10, y, value_style);
+                let blank = "                              ";
+                draw_text(&mut lcd, blank, 10, y + RANGE_OFFSET, range_style);
+                clear_trend(&mut lcd, &current, y);
+                continue;
+            };
+
+            write!(line, "{:<10}{:>8.1} {:<4}", label, value, quantity.unit).ok();
+            draw_text(&mut lcd, &line, 10, y, value_style);
+
+            let (lo, hi) = history.range(value);
+            line.clear();
+            let (min, max) = (i18n::lcd_small(Msg::WeatherMin), i18n::lcd_small(Msg::WeatherMax));
+            write!(line, "{} {:.1}  {} {:.1}      ", min, lo, max, hi).ok();
+            draw_text(&mut lcd, &line, 10, y + RANGE_OFFSET, range_style);
+
+            let trend = history.trend(value, quantity.threshold);
+            draw_trend(&mut lcd, &current, y, trend);
+
+            if sample_due {
+                history.push(value);
+                sampled = true;
+            }
+        }
+        if sampled {
+            last_sample = Some(now);
+        }
+
+        let latest = forecast::latest();
+        let updated = latest.as_ref().map(|f| f.updated);
+        if forecast_drawn != Some(updated) {
+            draw_forecast(&mut lcd, &current, latest.as_ref(), forecast_style);
+            forecast_drawn = Some(updated);
+        }
+
+        monitor.wait().await;
+    }
+}
+
+/// 标题行：已校准时显示 UTC 时间，否则显示运行时间
+fn format_clock(line: &mut String<40>) {
+    match wallclock::now() {
+        Some(secs) => {
+            let t = DateTime::from_unix(secs);
+            write!(
+                line,
+                "{:04}-{:02}-{:02} {:02}:{:02} UTC",
+                t.year, t.month, t.day, t.hour, t.minute
+            )
+            .ok();
+        }
+        None => {
+            let secs = Instant::now().as_secs();
+            let label = i18n::lcd(Msg::Uptime);
+            write!(line, "{} {:02}:{:02}   ", label, secs / 3600, secs / 60 % 60).ok();
+        }
+    }
+}
+
+fn draw_text(lcd: &mut St7789, text: &str, x: i32, y: i32, style: MonoTextStyle<'_, Rgb565>) {
+    if let Err(err) = Text::new(text, Point::new(x, y), style).draw(lcd) {
+        warn!("Failed to draw weather text: {}", err);
+    }
+}
+
+/// 清除一行右侧的趋势箭头区域
+fn clear_trend(lcd: &mut St7789, colors: &Theme, y: i32) {
+    lcd.fill_rectangle(ARROW_X as u16, (y - 16) as u16, 20, 20, colors.background)
+        .ok();
+}
+
+/// 在一行右侧绘制趋势箭头：上升红色上三角，下降蓝色下三角，平稳绿色横条
+fn draw_trend(lcd: &mut St7789, colors: &Theme, y: i32, trend: Trend) {
+    clear_trend(lcd, colors, y);
+    let (top, bottom) = (y - 14, y + 2);
+    let (left, right, middle) = (ARROW_X + 2, ARROW_X + 18, ARROW_X + 10);
+    let result = match trend {
+        Trend::Rising => Triangle::new(
+            Point::new(middle, top),
+            Point::new(left, bottom),
+            Point::new(right, bottom),
+        )
+        .into_styled(PrimitiveStyle::with_fill(Rgb565::RED))
+        .draw(lcd),
+        Trend::Falling => Triangle::new(
+            Point::new(left, top),
+            Point::new(right, top),
+            Point::new(middle, bottom),
+        )
+        .into_styled(PrimitiveStyle::with_fill(Rgb565::BLUE))
+        .draw(lcd),
+        Trend::Steady => lcd.fill_rectangle(left as u16, (y - 8) as u16, 16, 4, Rgb565::GREEN),
+    };
+    if let Err(err) = result {
+        warn!("Failed to draw trend arrow: {}", err);
+    }
+}
+
+/// 绘制屏幕底部的天气预报，每天一列：日期、图标、最高/最低气温
+fn draw_forecast(
+    lcd: &mut St7789,
+    colors: &Theme,
+    forecast: Option<&Forecast>,
+    style: MonoTextStyle<'_, Rgb565>,
+) {
+    let (height, background) = (st7789::HEIGHT - FORECAST_Y as u16, colors.background);
+    lcd.fill_rectangle(0, FORECAST_Y as u16, st7789::WIDTH, height, background)
+        .ok();
+    Line::new(
+        Point::new(0, FORECAST_Y),
+        Point::new(st7789::WIDTH as i32 - 1, FORECAST_Y),
+    )
+    .into_styled(PrimitiveStyle::with_stroke(colors.muted, 1))
+    .draw(lcd)
+    .ok();
+
+    let Some(forecast) = forecast else {
+        let text = i18n::lcd_small(Msg::WeatherNoForecast);
+        draw_text(lcd, text, 10, FORECAST_Y + 20, style);
+        return;
+    };
+
+    let mut line: String<16> = String::new();
+    for (i, day) in forecast.days.iter().enumerate() {
+        let left = i as i32 * FORECAST_COLUMN;
+        let center = left + FORECAST_COLUMN / 2;
+
+        line.clear();
+        write!(line, "{:02}-{:02}", day.month, day.day).ok();
+        draw_text(lcd, &line, center - 15, FORECAST_Y + 14, style);
+
+        let icon = Point::new(center - ICON_SIZE / 2, FORECAST_Y + 22);
+        if let Err(err) = draw_icon(lcd, icon, day.condition) {
+            warn!("Failed to draw weather icon: {}", err);
+        }
+
+        line.clear();
+        write!(line, "{:.0}/{:.0}", day.max, day.min).ok();
+        let width = line.len() as i32 * 6;
+        draw_text(lcd, &line, center - width / 2, FORECAST_Y + 62, style);
+    }
+}
+
+/// 用基本图形绘制天气图标，`origin` 为 [ICON_SIZE] 见方区域的左上角
+fn draw_icon(lcd: &mut St7789, origin: Point, condition: Condition) -> Result<(), SpiError> {
+    let fill = PrimitiveStyle::with_fill;
+    let stroke = |color: Rgb565| PrimitiveStyle::with_stroke(color, 2);
+    let at = |x: i32, y: i32| origin + Point::new(x, y);
+
+    // 云朵：两个圆加一个底座，下方留出雨、雪、闪电的位置
+    let cloud = |lcd: &mut St7789, color: Rgb565| -> Result<(), SpiError> {
+        Circle::new(at(3, 2), 10).into_styled(fill(color)).draw(lcd)?;
+        Circle::new(at(9, 0), 13).into_styled(fill(color)).draw(lcd)?;
+        Rectangle::new(at(3, 7), Size::new(19, 6))
+            .into_styled(fill(color))
+            .draw(lcd)
+    };
+
+    match condition {
+        Condition::Clear => Circle::new(at(4, 4), 16)
+            .into_styled(fill(Rgb565::YELLOW))
+            .draw(lcd),
+        Condition::Cloudy => {
+            Circle::new(at(2, 6), 12).into_styled(fill(Rgb565::CSS_LIGHT_GRAY)).draw(lcd)?;
+            Circle::new(at(8, 3), 15).into_styled(fill(Rgb565::CSS_LIGHT_GRAY)).draw(lcd)?;
+            Rectangle::new(at(2, 12), Size::new(21, 7))
+                .into_styled(fill(Rgb565::CSS_LIGHT_GRAY))
+                .draw(lcd)
+        }
+        Condition::Fog => {
+            for y in [6, 12, 18] {
+                Line::new(at(2, y), at(21, y))
+                    .into_styled(stroke(Rgb565::CSS_GRAY))
+                    .draw(lcd)?;
+            }
+            Ok(())
+        }
+        Condition::Rain => {
+            cloud(lcd, Rgb565::CSS_GRAY)?;
+            for x in [6, 12, 18] {
+                Line::new(at(x, 16), at(x - 2, 22))
+                    .into_styled(stroke(Rgb565::CSS_DODGER_BLUE))
+                    .draw(lcd)?;
+            }
+            Ok(())
+        }
+        Condition::Snow => {
+            cloud(lcd, Rgb565::CSS_LIGHT_GRAY)?;
+            for x in [5, 11, 17] {
+                Circle::new(at(x, 17), 4).into_styled(fill(Rgb565::WHITE)).draw(lcd)?;
+            }
+            Ok(())
+        }
+        Condition::Storm => {
+            cloud(lcd, Rgb565::CSS_DARK_GRAY)?;
+            Triangle::new(at(13, 13), at(8, 20), at(12, 20))
+                .into_styled(fill(Rgb565::YELLOW))
+                .draw(lcd)?;
+            Triangle::new(at(12, 18), at(15, 18), at(9, 24))
+                .into_styled(fill(Rgb565::YELLOW))
+                .draw(lcd)
+        }
+        Condition::Unknown => {
+            let style = MonoTextStyle::new(&FONT_6X10, Rgb565::WHITE);
+            Text::new("?", at(9, 16), style).draw(lcd).map(|_| ())
+        }
+    }
+}
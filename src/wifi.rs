@@ -1,6 +1,22 @@
+use crate::error::{Context, Error};
+use crate::power::{self, Load};
+use crate::presence;
+use crate::sensor;
+use crate::settings::{self, WIFI_PASSWORD_LEN, WIFI_SSID_LEN, WifiProfile};
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::cell::RefCell;
+use core::cmp::Reverse;
+use critical_section::Mutex;
 use defmt::{info, warn};
+use embassy_futures::select::{Either, select};
+use embassy_time::{Duration, Instant, Timer};
 use esp_hal::peripherals::{WIFI};
-use esp_radio::wifi::{ClientConfig, Config as WifiConfig, ScanConfig, WifiController};
+use esp_radio::esp_now::EspNow;
+use esp_radio::wifi::{
+    ClientConfig, Config as WifiConfig, CountryInfo, ScanConfig, WifiController, WifiDevice,
+    WifiEvent, WifiStaState,
+};
 use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
 use embassy_sync::mutex::Mutex as EmbassyMutex;
 use esp_radio::Controller;
@@ -11,15 +27,147 @@ static RADIO_INIT: StaticCell<Controller> = StaticCell::new();
 static WIFI_CONTROLLER: EmbassyMutex<CriticalSectionRawMutex, Option<WifiController<'static>>> =
     EmbassyMutex::new(None);
 
-pub async fn init(peripherals_wifi: WIFI<'static>) {
+/// 连接失败后重试前的等待时间（秒）
+const RECONNECT_DELAY_SECS: u64 = 5;
+
+/// 连接期间采样信号强度的周期
+const RSSI_PERIOD: Duration = Duration::from_secs(10);
+
+/// 已连接时后台扫描的周期，扫描期间会短暂离开当前信道，不宜频繁
+const SCAN_PERIOD_CONNECTED: Duration = Duration::from_secs(300);
+
+/// 未连接时后台扫描的周期
+const SCAN_PERIOD_OFFLINE: Duration = Duration::from_secs(30);
+
+/// 扫描缓存中网络的有效期，超过后视为已离开
+const SCAN_MAX_AGE: Duration = Duration::from_secs(600);
+
+/// 扫描缓存最多保存的接入点数量
+const SCAN_CACHE_LEN: usize = 16;
+
+/// 后台扫描每次最多返回的网络数量
+const SCAN_MAX: usize = 20;
+
+/// 切换接入点需要的最小信号增益（dB），避免在强度相近的接入点之间来回切换
+const ROAM_MARGIN_DB: i32 = 8;
+
+/// 扫描缓存，每个 BSSID 一条，见 [cached_networks]
+static SCAN_CACHE: Mutex<RefCell<Vec<AccessPoint>>> = Mutex::new(RefCell::new(Vec::new()));
+
+/// 驱动设置国家代码时使用的信道范围，扫描不需要限制信道时整体扫描一次
+const DRIVER_CHANNELS: (u8, u8) = (1, 13);
+
+/// 支持的国家代码及其允许的最高 2.4GHz 信道，所有国家都从信道 1 开始
+///
+/// `01` 是驱动的全球安全模式。日本的信道 14 只允许 802.11b，驱动不支持，按 13 处理
+const COUNTRIES: [(&str, u8); 23] = [
+    ("01", 11),
+    ("US", 11),
+    ("CA", 11),
+    ("MX", 11),
+    ("TW", 11),
+    ("CN", 13),
+    ("HK", 13),
+    ("JP", 13),
+    ("KR", 13),
+    ("SG", 13),
+    ("IN", 13),
+    ("AU", 13),
+    ("NZ", 13),
+    ("BR", 13),
+    ("RU", 13),
+    ("GB", 13),
+    ("DE", 13),
+    ("FR", 13),
+    ("IT", 13),
+    ("ES", 13),
+    ("NL", 13),
+    ("SE", 13),
+    ("CH", 13),
+];
+
+/// 信道规划：国家代码和扫描、连接使用的信道范围
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct ChannelPlan {
+    /// ISO 3166 国家代码
+    pub country: [u8; 2],
+    /// 最低信道
+    pub first: u8,
+    /// 最高信道
+    pub last: u8,
+}
+
+impl ChannelPlan {
+    /// 国家代码的文本形式
+    pub fn country_code(&self) -> &str {
+        core::str::from_utf8(&self.country).unwrap_or("??")
+    }
+
+    /// 信道是否在规划范围内
+    pub fn contains(&self, channel: u8) -> bool {
+        (self.first..=self.last).contains(&channel)
+    }
+}
+
+/// 查找国家允许的最高信道
+///
+/// # 返回
+/// 不支持的国家代码返回 None
+pub fn max_channel(country: &str) -> Option<u8> {
+    COUNTRIES
+        .iter()
+        .find(|(code, _)| *code == country)
+        .map(|&(_, last)| last)
+}
+
+/// 当前的信道规划
+///
+/// 设置中的信道范围与国家允许的信道取交集；国家代码不受支持或交集为空时，
+/// 回退到全球安全模式的信道 1-11
+pub fn channel_plan() -> ChannelPlan {
+    let settings = settings::get();
+    let [first, last] = settings.wifi_channels;
+    let country = core::str::from_utf8(&settings.wifi_country).unwrap_or("");
+    match max_channel(country) {
+        Some(max) if first.max(1) <= last.min(max) => ChannelPlan {
+            country: settings.wifi_country,
+            first: first.max(1),
+            last: last.min(max),
+        },
+        _ => ChannelPlan {
+            country: *b"01",
+            first: 1,
+            last: 11,
+        },
+    }
+}
+
+/// 初始化 WiFi 并以客户端模式启动
+///
+/// 已配置网络时（见 [profiles]）会写入首选网络的客户端配置，由 [connection] 任务负责连接。
+/// 国家代码（见 [channel_plan]）在这里交给驱动，修改后重启才生效
+///
+/// # 返回
+/// 客户端网络接口，交给 [crate::net] 创建协议栈；ESP-NOW 接口，交给 [crate::espnow]
+pub async fn init(peripherals_wifi: WIFI<'static>) -> (WifiDevice<'static>, EspNow<'static>) {
     let radio_init = esp_radio::init().expect("Failed to initialize Wi-Fi/BLE controller");
     let radio_init_ref = RADIO_INIT.init(radio_init);
 
-    let (mut wifi_controller, _interfaces) =
-    esp_radio::wifi::new(radio_init_ref, peripherals_wifi, WifiConfig::default())
+    let plan = channel_plan();
+    let (country, first, last) = (plan.country_code(), plan.first, plan.last);
+    info!("Wi-Fi country {}, channels {}-{}", country, first, last);
+    let config = WifiConfig::default().with_country_code(CountryInfo::from(plan.country));
+    let (mut wifi_controller, interfaces) =
+    esp_radio::wifi::new(radio_init_ref, peripherals_wifi, config)
     .expect("Failed to initialize Wi-Fi controller");
 
-    match wifi_controller.set_config(&Client(ClientConfig::default())) {
+    let client_config = match profiles().into_iter().next() {
+        Some(profile) => ClientConfig::default()
+            .with_ssid(profile.ssid.as_str().into())
+            .with_password(profile.password.as_str().into()),
+        None => ClientConfig::default(),
+    };
+    match wifi_controller.set_config(&Client(client_config)) {
         Ok(()) => {
             info!("Wi-Fi mode set to client");
         }
@@ -28,6 +176,8 @@ pub async fn init(peripherals_wifi: WIFI<'static>) {
         }
     }
 
+    // 射频启动和校准的电流最大，与其他负载错开
+    power::power_up(Load::Radio).await;
     match wifi_controller.start_async().await {
         Ok(()) => {
             info!("starting Wi-Fi");
@@ -50,33 +200,383 @@ pub async fn init(peripherals_wifi: WIFI<'static>) {
         }
     };
     WIFI_CONTROLLER.lock().await.replace(wifi_controller);
+    (interfaces.sta, interfaces.esp_now)
+}
+
+/// 获取要连接的网络，按尝试的顺序排列
+///
+/// 设置中保存的网络按优先级从高到低排列，优先级相同时最近连接成功的在前。
+/// 没有保存任何网络时使用编译时的 `WIFI_SSID` / `WIFI_PASSWORD` 环境变量
+///
+/// # 返回
+/// 保存的网络，都未配置时为空
+pub fn profiles() -> Vec<WifiProfile> {
+    let mut profiles: Vec<WifiProfile> = settings::get().wifi_profiles.into_iter().collect();
+    if profiles.is_empty()
+        && let Some(ssid) = option_env!("WIFI_SSID").and_then(|ssid| ssid.try_into().ok())
+    {
+        let password = option_env!("WIFI_PASSWORD").unwrap_or("");
+        profiles.push(WifiProfile {
+            ssid,
+            password: password.try_into().unwrap_or_default(),
+            priority: 0,
+            last_success: 0,
+        });
+    }
+    profiles.sort_by(|a, b| {
+        let rank = |p: &WifiProfile| (p.priority, p.last_success);
+        rank(b).cmp(&rank(a))
+    });
+    profiles
 }
 
+/// 保存网络或修改已保存网络的密码（不会自动保存设置）
+///
+/// 新网络的优先级为 0；已保存 [settings::WIFI_PROFILES_MAX] 个网络时，
+/// 替换排在最后（优先级最低、最久没有连接成功）的一个
+///
+/// # 参数
+/// * `ssid` - 网络名
+/// * `password` - 密码，开放网络为空
+pub fn save_profile(
+    ssid: heapless::String<WIFI_SSID_LEN>,
+    password: heapless::String<WIFI_PASSWORD_LEN>,
+) {
+    settings::update(|s| {
+        let profiles = &mut s.wifi_profiles;
+        if let Some(profile) = profiles.iter_mut().find(|p| p.ssid == ssid) {
+            profile.password = password;
+            return;
+        }
+        let profile = WifiProfile {
+            ssid,
+            password,
+            priority: 0,
+            last_success: 0,
+        };
+        if let Err(profile) = profiles.push(profile) {
+            let rank = |p: &WifiProfile| (p.priority, p.last_success);
+            if let Some(last) = profiles.iter_mut().min_by_key(|p| rank(p)) {
+                info!("Wi-Fi profiles full, replacing {}", last.ssid.as_str());
+                *last = profile;
+            }
+        }
+    });
+}
+
+/// 记录连接成功的网络，使它在优先级相同的网络中排到最前，顺序改变时保存设置
+fn record_success(ssid: &str) {
+    let mut changed = false;
+    settings::update(|s| {
+        let profiles = &mut s.wifi_profiles;
+        let latest = profiles.iter().map(|p| p.last_success).max().unwrap_or(0);
+        if let Some(profile) = profiles.iter_mut().find(|p| p.ssid == ssid)
+            && (profile.last_success == 0 || profile.last_success < latest)
+        {
+            profile.last_success = latest + 1;
+            changed = true;
+        }
+    });
+    if changed && let Err(err) = settings::save() {
+        warn!("Failed to save Wi-Fi profiles: {}", err);
+    }
+}
+
+/// 当前是否已连接到 WiFi 网络
+pub fn is_connected() -> bool {
+    esp_radio::wifi::sta_state() == WifiStaState::Connected
+}
+
+/// WiFi 连接任务
+///
+/// 连接到配置的网络，断开后自动重连。连接期间持有 WiFi 控制器，
+/// 设置向导需要扫描时不启动本任务。
+///
+/// 保存了多个网络时（见 [profiles]）按顺序逐个尝试，扫描缓存中出现过的网络先试，
+/// 连接成功后记录下来，下次启动先试这个网络。
+///
+/// 同时负责后台扫描：未连接时每 [SCAN_PERIOD_OFFLINE]、已连接时每 [SCAN_PERIOD_CONNECTED]
+/// 扫描一次并更新扫描缓存。连接时选择缓存中信号最强的同名接入点，
+/// 已连接时发现当前网络有信号强 [ROAM_MARGIN_DB] 以上的接入点就切换过去。
+///
+/// 连接期间每 [RSSI_PERIOD] 把信号强度登记为 `wifi.rssi` 读数（见 [crate::sensor]），
+/// 启用了存在检测时改为每 [presence::SAMPLE_PERIOD] 采样一次交给 [presence::sample]
 #[embassy_executor::task]
-pub async fn wifi_scan() {
-    info!("Wifi Scanning...");
+pub async fn connection() {
+    if profiles().is_empty() {
+        warn!("No Wi-Fi network configured, staying offline");
+        return;
+    }
 
-    let mut guard = WIFI_CONTROLLER.lock().await;
-    if let Some(controller) = guard.as_mut() {
-        let scan_config = ScanConfig::default()
-            .with_max(10);
-        let result = controller.scan_with_config_async(scan_config).await;
+    let mut scanned: Option<Instant> = None;
+    // 连续失败的次数，用于轮流尝试各个网络，连接成功后清零
+    let mut attempt = 0;
+    // 当前连接的网络
+    let mut current: Option<String> = None;
+    // 上次登记 `wifi.rssi` 读数的时间
+    let mut rssi_published: Option<Instant> = None;
+    loop {
+        let mut guard = WIFI_CONTROLLER.lock().await;
+        let Some(controller) = guard.as_mut() else {
+            return;
+        };
 
-        match result {
-            Ok(networks) => {
-                info!("Scan done, found {} networks", networks.len());
-                for network in networks {
-                    info!(
-                        "SSID: {}, Channel: {}, RSSI: {}",
-                        core::str::from_utf8((&network.ssid).as_ref()).unwrap_or("<invalid utf-8>"),
-                        network.channel,
-                        network.auth_method
-                    );
+        let period = if is_connected() {
+            SCAN_PERIOD_CONNECTED
+        } else {
+            SCAN_PERIOD_OFFLINE
+        };
+        if scanned.is_none_or(|at| at.elapsed() >= period) {
+            scanned = Some(Instant::now());
+            match scan_with(controller, SCAN_MAX).await {
+                // 只在刚扫描完时比较，缓存中的信号强度过时后不再作为切换依据
+                Ok(_) if is_connected() && should_roam(controller, current.as_deref()) => {
+                    controller.disconnect_async().await.ok();
+                    continue;
                 }
+                Ok(_) => {}
+                Err(err) => warn!("Wi-Fi background scan failed: {}", err),
+            }
+        }
+
+        if is_connected() {
+            let sensing = presence::is_enabled();
+            let period = if sensing {
+                presence::SAMPLE_PERIOD
+            } else {
+                RSSI_PERIOD
+            };
+            let disconnected = controller.wait_for_event(WifiEvent::StaDisconnected);
+            if let Either::Second(()) = select(disconnected, Timer::after(period)).await {
+                if let Ok(rssi) = controller.rssi() {
+                    if sensing {
+                        presence::sample(i8::try_from(rssi).unwrap_or(i8::MIN));
+                    }
+                    if rssi_published.is_none_or(|at| at.elapsed() >= RSSI_PERIOD) {
+                        rssi_published = Some(Instant::now());
+                        sensor::publish("wifi.rssi", rssi as f64, "dBm");
+                    }
+                }
+                continue;
+            }
+            warn!("Wi-Fi disconnected");
+            drop(guard);
+            Timer::after_secs(RECONNECT_DELAY_SECS).await;
+            continue;
+        }
+
+        // 扫描到的网络排在前面，其余（例如隐藏网络）仍按原顺序尝试
+        let mut candidates = profiles();
+        candidates.sort_by_key(|profile| strongest(&profile.ssid).is_none());
+        if candidates.is_empty() {
+            warn!("All Wi-Fi networks removed, staying offline");
+            return;
+        }
+        let profile = &candidates[attempt % candidates.len()];
+        let ssid = profile.ssid.as_str();
+        // 缓存中没有同名接入点时只按名称连接
+        let best = strongest(ssid);
+        let mut config = ClientConfig::default()
+            .with_ssid(ssid.into())
+            .with_password(profile.password.as_str().into());
+        if let Some(best) = &best {
+            config = config.with_bssid(best.bssid).with_channel(best.channel);
+        }
+        let result = match controller.set_config(&Client(config)) {
+            Ok(()) => controller.connect_async().await,
+            Err(err) => Err(err),
+        };
+        match result {
+            Ok(()) => {
+                info!("Wi-Fi connected to {}", ssid);
+                attempt = 0;
+                current = Some(ssid.into());
+                record_success(ssid);
+                presence::reset();
             }
             Err(err) => {
-                warn!("Wi-Fi scan failed: {}", err);
+                warn!("Wi-Fi connect to {} failed: {}", ssid, err);
+                attempt += 1;
+                current = None;
+                // 连不上的接入点移出缓存，下次换一个或只按名称连接
+                if let Some(best) = best {
+                    forget(best.bssid);
+                }
+                drop(guard);
+                Timer::after_secs(RECONNECT_DELAY_SECS).await;
+            }
+        }
+    }
+}
+
+/// 当前网络是否有信号比当前接入点强 [ROAM_MARGIN_DB] 以上的接入点
+fn should_roam(controller: &mut WifiController<'static>, ssid: Option<&str>) -> bool {
+    let best = ssid.and_then(strongest);
+    let (Ok(rssi), Some(best)) = (controller.rssi(), best) else {
+        return false;
+    };
+    let roam = i32::from(best.signal_strength) >= rssi.saturating_add(ROAM_MARGIN_DB);
+    if roam {
+        let (bssid, strength) = (best.bssid, best.signal_strength);
+        info!("Roaming to {:02x} ({} dBm, now {})", bssid, strength, rssi);
+    }
+    roam
+}
+
+/// 扫描到的网络
+#[derive(Debug, Clone)]
+pub struct AccessPoint {
+    pub ssid: String,
+    pub bssid: [u8; 6],
+    pub channel: u8,
+    pub signal_strength: i8,
+    /// 最近一次扫描到的时间
+    pub seen: Instant,
+}
+
+/// 扫描周围的网络
+///
+/// 只扫描信道规划（见 [channel_plan]）内的信道：范围比驱动的 1-13 小时逐个信道扫描，
+/// 不在不允许的信道上发送探测帧
+///
+/// # 参数
+/// * `max` - 最多返回的网络数量
+///
+/// # 返回
+/// 按信号强度从强到弱排列的网络，同时更新扫描缓存
+#[cfg(feature = "ui")]
+pub async fn scan(max: usize) -> Result<Vec<AccessPoint>, Error> {
+    let mut guard = WIFI_CONTROLLER.lock().await;
+    let Some(controller) = guard.as_mut() else {
+        return Err(Error::NotInitialized { op: "Wi-Fi scan" });
+    };
+    scan_with(controller, max).await
+}
+
+/// 用已经持有的控制器扫描，见 [scan]
+async fn scan_with(
+    controller: &mut WifiController<'static>,
+    max: usize,
+) -> Result<Vec<AccessPoint>, Error> {
+    let plan = channel_plan();
+    let found = if (plan.first, plan.last) == DRIVER_CHANNELS {
+        controller
+            .scan_with_config_async(ScanConfig::default().with_max(max))
+            .await
+            .context("Wi-Fi scan")?
+    } else {
+        let mut found = Vec::new();
+        for channel in plan.first..=plan.last {
+            let config = ScanConfig::default().with_channel(channel).with_max(max);
+            let networks = controller
+                .scan_with_config_async(config)
+                .await
+                .context("Wi-Fi scan")?;
+            found.extend(networks);
+        }
+        found
+    };
+    let seen = Instant::now();
+    let mut networks: Vec<AccessPoint> = found
+        .into_iter()
+        .filter(|network| plan.contains(network.channel))
+        .map(|network| AccessPoint {
+            ssid: core::str::from_utf8(network.ssid.as_ref())
+                .unwrap_or("<invalid utf-8>")
+                .into(),
+            bssid: network.bssid,
+            channel: network.channel,
+            signal_strength: network.signal_strength,
+            seen,
+        })
+        .collect();
+    networks.sort_by_key(|network| Reverse(network.signal_strength));
+    networks.truncate(max);
+    remember(&networks);
+    Ok(networks)
+}
+
+/// 把扫描结果合并到扫描缓存
+///
+/// 同一 BSSID 只保留最新的一条，过期的条目被移除；缓存已满时新条目替换信号最弱的一条
+fn remember(networks: &[AccessPoint]) {
+    critical_section::with(|cs| {
+        let mut cache = SCAN_CACHE.borrow_ref_mut(cs);
+        cache.retain(|known| known.seen.elapsed() < SCAN_MAX_AGE);
+        for network in networks {
+            if let Some(known) = cache.iter_mut().find(|known| known.bssid == network.bssid) {
+                *known = network.clone();
+            } else if cache.len() < SCAN_CACHE_LEN {
+                cache.push(network.clone());
+            } else if let Some(weakest) = cache
+                .iter_mut()
+                .min_by_key(|known| known.signal_strength)
+                .filter(|weakest| weakest.signal_strength < network.signal_strength)
+            {
+                *weakest = network.clone();
             }
         }
+        cache.sort_by_key(|network| Reverse(network.signal_strength));
+    });
+}
+
+/// 从扫描缓存中移除接入点
+fn forget(bssid: [u8; 6]) {
+    critical_section::with(|cs| {
+        SCAN_CACHE
+            .borrow_ref_mut(cs)
+            .retain(|known| known.bssid != bssid)
+    });
+}
+
+/// 扫描缓存中尚未过期的接入点，按信号强度从强到弱排列
+pub fn cached_networks() -> Vec<AccessPoint> {
+    critical_section::with(|cs| {
+        let cache = SCAN_CACHE.borrow_ref(cs);
+        cache
+            .iter()
+            .filter(|known| known.seen.elapsed() < SCAN_MAX_AGE)
+            .cloned()
+            .collect()
+    })
+}
+
+/// 扫描缓存中排在最前的已保存网络（见 [profiles]）信号最强的接入点
+///
+/// # 返回
+/// 没有保存网络或缓存中没有任何已保存网络的接入点时返回 None
+pub fn best_known_network() -> Option<AccessPoint> {
+    profiles()
+        .iter()
+        .find_map(|profile| strongest(&profile.ssid))
+}
+
+/// 扫描缓存中指定网络信号最强的接入点
+fn strongest(ssid: &str) -> Option<AccessPoint> {
+    cached_networks()
+        .into_iter()
+        .find(|network| network.ssid == ssid)
+}
+
+/// 使用指定的网络重新连接
+///
+/// 只负责关联到接入点，IP 地址需通过网络协议栈等待 DHCP 完成
+///
+/// # 参数
+/// * `ssid` - 网络名
+/// * `password` - 密码
+#[cfg(feature = "ui")]
+pub async fn try_connect(ssid: &str, password: &str) -> Result<(), Error> {
+    let mut guard = WIFI_CONTROLLER.lock().await;
+    let Some(controller) = guard.as_mut() else {
+        return Err(Error::NotInitialized { op: "Wi-Fi connect" });
+    };
+    if esp_radio::wifi::sta_state() == WifiStaState::Connected {
+        controller.disconnect_async().await.ok();
     }
-}
\ No newline at end of file
+    let config = ClientConfig::default()
+        .with_ssid(ssid.into())
+        .with_password(password.into());
+    controller.set_config(&Client(config)).context("Wi-Fi set config")?;
+    controller.connect_async().await.context("Wi-Fi connect")
+}
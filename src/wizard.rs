@@ -0,0 +1,356 @@
+//! 首次启动设置向导
+//!
+//! Flash 中没有设置时（首次启动或恢复出厂设置后），由 [wizard_task] 代替渲染任务占用 LCD，
+//! 通过按键引导用户完成基本配置：
+//!
+//! 1. 选择界面语言
+//! 2. 扫描并选择 WiFi 网络（也可跳过，保持离线）
+//! 3. 用屏幕键盘（见 [ui::keyboard]）输入 WiFi 密码
+//! 4. 测试连接，直到获取 IP 地址
+//! 5. 保存设置并重启
+//!
+//! 按键：KEY0 下一项，KEY1 上一项，KEY2 确认，KEY3 返回；屏幕键盘中 KEY0 右移，KEY1 下移。
+
+use crate::i18n::{self, Language, Msg};
+use crate::input::{self, Key, KeySubscriber};
+use crate::lcd::Lcd;
+use crate::settings::{self, WIFI_PASSWORD_LEN, WIFI_SSID_LEN};
+use crate::system::{self, RebootReason};
+use crate::{assets, theme, wifi};
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use defmt::{info, warn};
+use embassy_net::Stack;
+use embassy_time::{Duration, Timer, with_timeout};
+use embedded_graphics::mono_font::{MonoTextStyle, MonoTextStyleBuilder};
+use embedded_graphics::pixelcolor::Rgb565;
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::Rectangle;
+use embedded_graphics::text::Text;
+use ui::keyboard::{self, Keyboard, Outcome};
+use ui::text::{self, Align, TextBox};
+use ui::theme::Theme;
+
+/// 扫描最多显示的网络数量
+const MAX_NETWORKS: usize = 10;
+
+/// 等待获取 IP 地址的超时时间
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// 列表一屏显示的行数
+const LIST_ROWS: usize = 6;
+
+/// 行高（像素）
+const LINE_HEIGHT: i32 = 24;
+
+/// 正文一行除去选中标记后的字符数
+const LIST_COLUMNS: usize = 28;
+
+/// 标题基线位置
+const TITLE_Y: i32 = 26;
+
+/// 正文第一行基线位置
+const BODY_Y: i32 = 64;
+
+/// 底部提示行基线位置
+const HINT_Y: i32 = 232;
+
+/// 屏幕键盘输入行的上边缘，位于正文第一行下方，键盘底部在提示行之上
+const KEYBOARD_Y: i32 = BODY_Y + 8;
+
+/// 向导屏幕
+struct Screen {
+    lcd: Lcd,
+    colors: Theme,
+    normal: MonoTextStyle<'static, Rgb565>,
+    highlight: MonoTextStyle<'static, Rgb565>,
+    title: MonoTextStyle<'static, Rgb565>,
+}
+
+impl Screen {
+    fn new(lcd: Lcd, colors: Theme) -> Self {
+        let style = |color| {
+            MonoTextStyleBuilder::new()
+                .font(assets::font())
+                .text_color(color)
+                .background_color(colors.background)
+                .build()
+        };
+        Screen {
+            lcd,
+            colors,
+            normal: style(colors.foreground),
+            highlight: style(colors.highlight),
+            title: style(colors.accent),
+        }
+    }
+
+    /// 清屏并显示标题和底部提示
+    async fn page(&mut self, title: &str, hint: &str) {
+        if let Err(err) = self.lcd.fill_screen(self.colors.background).await {
+            warn!("Failed to clear LCD: {}", err);
+        }
+        self.text(title, 0, TITLE_Y, self.title);
+        self.text(hint, 0, HINT_Y, self.normal);
+    }
+
+    /// 在正文第 `row` 行显示文本，并清除该行剩余部分
+    fn line(&mut self, row: usize, text: &str, selected: bool) {
+        let y = BODY_Y + row as i32 * LINE_HEIGHT;
+        let style = if selected { self.highlight } else { self.normal };
+        let marker = if selected { "> " } else { "  " };
+        // 先清除整行，避免较短的文本残留上一次的内容
+        let background = self.colors.background;
+        self.lcd
+            .fill_rectangle(0, (y - 18) as u16, 320, LINE_HEIGHT as u16, background)
+            .ok();
+        self.text(marker, 0, y, style);
+        // 过长的网络名称或提示截断为省略号，不画出屏幕
+        let font = assets::font().character_size;
+        let origin = Point::new(30, y - assets::font().baseline as i32);
+        let size = Size::new(font.width * LIST_COLUMNS as u32, font.height);
+        let area = Rectangle::new(origin, size);
+        if let Err(err) = TextBox::new(area, style, Align::Left).draw_line(&mut self.lcd, text) {
+            warn!("Failed to draw wizard text: {}", err);
+        }
+    }
+
+    fn text(&mut self, text: &str, column: i32, y: i32, style: MonoTextStyle<'static, Rgb565>) {
+        let x = 10 + column * 10;
+        if let Err(err) = Text::new(text, Point::new(x, y), style).draw(&mut self.lcd) {
+            warn!("Failed to draw wizard text: {}", err);
+        }
+    }
+
+    /// 显示一页提示信息
+    async fn message(&mut self, title: &str, lines: &[&str], hint: &str) {
+        self.page(title, hint).await;
+        for (row, text) in lines.iter().enumerate() {
+            self.line(row, text, false);
+        }
+    }
+}
+
+/// 从列表中选择一项
+///
+/// # 返回
+/// 选中项的下标；按 KEY3 返回时为 None
+async fn choose(
+    screen: &mut Screen,
+    keys: &mut KeySubscriber,
+    title: &str,
+    items: &[&str],
+    initial: usize,
+) -> Option<usize> {
+    let mut selected = initial.min(items.len().saturating_sub(1));
+    let mut first = usize::MAX;
+    loop {
+        // 选中项超出当前一屏时滚动
+        let top = selected.saturating_sub(LIST_ROWS - 1);
+        if top != first {
+            first = top;
+            screen.page(title, i18n::lcd(Msg::WizardListHint)).await;
+        }
+        for (row, item) in items.iter().enumerate().skip(top).take(LIST_ROWS) {
+            screen.line(row - top, item, row == selected);
+        }
+
+        match keys.next_message_pure().await {
+            Key::Key0 => selected = (selected + 1) % items.len(),
+            Key::Key1 => selected = (selected + items.len() - 1) % items.len(),
+            Key::Key2 => return Some(selected),
+            Key::Key3 => return None,
+        }
+    }
+}
+
+/// 用屏幕键盘输入文本
+///
+/// KEY0 右移，KEY1 下移，KEY2 输入选中的键，KEY3 返回
+///
+/// # 参数
+/// * `title` - 标题
+/// * `label` - 键盘上方显示的说明，例如要连接的网络名称
+///
+/// # 返回
+/// 输入的文本；按 KEY3 返回时为 None
+async fn enter_text<const N: usize>(
+    screen: &mut Screen,
+    keys: &mut KeySubscriber,
+    title: &str,
+    label: &str,
+) -> Option<heapless::String<N>> {
+    let mut keyboard = Keyboard::<N>::new(Point::new(0, KEYBOARD_Y), "", screen.colors);
+
+    screen.page(title, i18n::lcd(Msg::WizardKeyboardHint)).await;
+    screen.line(0, label, false);
+    loop {
+        if let Err(err) = keyboard.draw(&mut screen.lcd) {
+            warn!("Failed to draw keyboard: {}", err);
+        }
+
+        let input = match keys.next_message_pure().await {
+            Key::Key0 => keyboard::Input::Right,
+            Key::Key1 => keyboard::Input::Down,
+            Key::Key2 => keyboard::Input::Press,
+            Key::Key3 => keyboard::Input::Cancel,
+        };
+        match keyboard.handle(input) {
+            Some(Outcome::Done) => return Some(keyboard.into_text()),
+            Some(Outcome::Cancelled) => return None,
+            None => {}
+        }
+    }
+}
+
+/// 连接到指定网络并等待获取 IP 地址
+///
+/// # 返回
+/// 失败时返回显示给用户的原因
+async fn test_connection(
+    stack: Stack<'static>,
+    ssid: &str,
+    password: &str,
+) -> Result<(), Msg> {
+    if let Err(err) = wifi::try_connect(ssid, password).await {
+        warn!("Wizard: Wi-Fi connect failed: {}", err);
+        return Err(Msg::WizardAssociateFailed);
+    }
+    match with_timeout(CONNECT_TIMEOUT, stack.wait_config_up()).await {
+        Ok(()) => Ok(()),
+        Err(_) => {
+            warn!("Wizard: no IP address after {} s", CONNECT_TIMEOUT.as_secs());
+            Err(Msg::WizardNoAddress)
+        }
+    }
+}
+
+/// 选择并测试 WiFi 网络
+///
+/// # 返回
+/// 连接成功的 (SSID, 密码)；选择跳过时返回 None
+async fn setup_wifi(
+    screen: &mut Screen,
+    keys: &mut KeySubscriber,
+    stack: Stack<'static>,
+) -> Option<(heapless::String<WIFI_SSID_LEN>, heapless::String<WIFI_PASSWORD_LEN>)> {
+    loop {
+        screen
+            .message("Wi-Fi", &[i18n::lcd(Msg::WizardScanning)], "")
+            .await;
+        let networks = match wifi::scan(MAX_NETWORKS).await {
+            Ok(networks) => networks,
+            Err(err) => {
+                warn!("Wizard: Wi-Fi scan failed: {}", err);
+                Vec::new()
+            }
+        };
+
+        // 名称过长时只截断名称，信号强度始终可见
+        let mut labels: Vec<String> = networks
+            .iter()
+            .map(|network| {
+                let rssi = format!(" {}dBm", network.signal_strength);
+                let room = LIST_COLUMNS.saturating_sub(rssi.len());
+                let (ssid, cut) = text::fit(&network.ssid, room, false);
+                let ellipsis = if cut { text::ELLIPSIS } else { "" };
+                format!("{}{}{}", ssid, ellipsis, rssi)
+            })
+            .collect();
+        labels.push(String::from(i18n::lcd(Msg::WizardRescan)));
+        labels.push(String::from(i18n::lcd(Msg::WizardSkip)));
+        let items: Vec<&str> = labels.iter().map(String::as_str).collect();
+
+        let title = i18n::lcd(Msg::WizardSelectWifi);
+        let choice = match choose(screen, keys, title, &items, 0).await {
+            Some(choice) if choice < networks.len() => choice,
+            // 返回或选择重新扫描
+            None => continue,
+            Some(choice) if choice == networks.len() => continue,
+            Some(_) => return None,
+        };
+
+        let network = &networks[choice];
+        let Ok(ssid) = heapless::String::try_from(network.ssid.as_str()) else {
+            warn!("Wizard: SSID too long");
+            continue;
+        };
+        let title = i18n::lcd(Msg::WizardPassword);
+        let Some(password) = enter_text(screen, keys, title, &ssid).await else {
+            continue;
+        };
+
+        let lines = [ssid.as_str(), i18n::lcd(Msg::WizardPleaseWait)];
+        screen
+            .message(i18n::lcd(Msg::WizardConnecting), &lines, "")
+            .await;
+        match test_connection(stack, &ssid, &password).await {
+            Ok(()) => {
+                info!("Wizard: connected to {}", ssid.as_str());
+                return Some((ssid, password));
+            }
+            Err(reason) => {
+                let lines = [ssid.as_str(), i18n::lcd(reason)];
+                let hint = i18n::lcd(Msg::WizardAnyKey);
+                screen
+                    .message(i18n::lcd(Msg::WizardConnectFailed), &lines, hint)
+                    .await;
+                // 任意键返回网络列表重新选择
+                keys.next_message_pure().await;
+            }
+        }
+    }
+}
+
+/// 设置向导任务
+///
+/// 独占 LCD 和按键，完成后保存设置并重启，不会返回
+///
+/// # 参数
+/// * `lcd` - 已完成初始化的 LCD
+/// * `stack` - 网络协议栈，用于确认连接可用
+#[embassy_executor::task]
+pub async fn wizard_task(lcd: Lcd, stack: Stack<'static>) {
+    let Some(mut keys) = input::subscribe() else {
+        warn!("Wizard: no key subscriber available");
+        return;
+    };
+    input::set_captured(true);
+    info!("Starting setup wizard");
+
+    let mut screen = Screen::new(lcd, theme::current());
+
+    // 语言名称用 ASCII 显示，选择后立即生效
+    let names = Language::ALL.map(Language::name);
+    let language = loop {
+        let current = i18n::current().to_u8() as usize;
+        let title = i18n::lcd(Msg::WizardLanguage);
+        if let Some(choice) = choose(&mut screen, &mut keys, title, &names, current).await {
+            break Language::ALL[choice];
+        }
+    };
+    settings::update(|s| s.language = language.to_u8());
+
+    match setup_wifi(&mut screen, &mut keys, stack).await {
+        Some((ssid, password)) => wifi::save_profile(ssid, password),
+        None => info!("Wizard: Wi-Fi skipped"),
+    }
+
+    if let Err(err) = settings::save() {
+        warn!("Wizard: failed to save settings: {}", err);
+        let lines = [i18n::lcd(Msg::WizardSaveFailed)];
+        screen
+            .message(i18n::lcd(Msg::WizardSetup), &lines, "")
+            .await;
+        input::set_captured(false);
+        return;
+    }
+
+    let lines = [i18n::lcd(Msg::WizardRebooting)];
+    screen
+        .message(i18n::lcd(Msg::WizardComplete), &lines, "")
+        .await;
+    Timer::after_secs(2).await;
+    system::reboot(RebootReason::ConfigChange).await;
+}
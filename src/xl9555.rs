@@ -1,133 +1,106 @@
-use crate::i2c;
+//! XL9555 I2C GPIO 扩展芯片驱动
+//!
+//! 寄存器读写逻辑在 [drivers::xl9555] 中，可在主机上测试；本模块把它接到共享 I2C 总线上，
+//! 提供以下功能：
+//! - LCD 背光控制
+//! - LCD 复位控制
+//! - 摄像头掉电和蜂鸣器控制
+//! - 继电器输出（恒温控制器，见 [crate::thermostat]；继电器控制，见 [crate::relay]）
+//! - 按键输入检测
+//! - 输出寄存器校验：写入后读回，[watchdog_task] 定期检查，芯片复位时自动恢复
+//!
+//! # 使用方法
+//!
+//! 1. 调用 [init] 函数初始化 XL9555
+//! 2. 由 [crate::lcd::Lcd::init] 调用 [init_atk_md0240] 复位 LCD 模块，清屏后再打开背光
+//! 3. 调用 [set_lcd_backlight] 函数控制 LCD 背光
+//! 4. 启动 [read_keys] 任务检测按键输入
+//! 5. 启动 [watchdog_task] 任务定期校验输出
+//!
+//! 所有输出都通过影子寄存器（[driver::Shadow]）写入：芯片欠压或受静电干扰复位后，
+//! 寄存器恢复为上电默认值，背光、蜂鸣器等输出与软件记录的状态不再一致，
+//! 写入时的读回校验或看门狗发现后按记录的状态重写全部输出和方向寄存器。
+
+use crate::error::Error;
+use crate::input::{self, Key};
+use crate::monotonic::Monotonic;
+use crate::registry::{self, Peripheral, State};
+#[cfg(feature = "ui")]
+use crate::render::{self, Command};
+use crate::{buzzer, i2c, jitter};
 use core::cell::RefCell;
 use critical_section::Mutex;
-use defmt::info;
+use defmt::{info, warn};
+use drivers::clock::Debouncer;
+use drivers::xl9555::{self as driver, Shadow, Verify, io_bits};
+#[cfg(feature = "ui")]
 use embassy_time::Timer;
+use embassy_time::{Duration, Ticker};
 use esp_hal::i2c::master::Error as I2cError;
 use esp_hal::i2c::master::I2c;
 use esp_hal::Blocking;
 
-/// XL9555 I2C GPIO 扩展芯片驱动
-///
-/// 该模块提供了对 XL9555 GPIO 扩展芯片的完整控制功能，包括：
-/// - LCD 背光控制
-/// - LCD 复位控制
-/// - 按键输入检测
-///
-/// XL9555 具有 16 个 GPIO 引脚，分为两个 8 位端口：
-/// - P0 端口：P0.0-P0.7 (按键连接在此端口)
-/// - P1 端口：P1.0-P1.7 (LCD 控制信号连接在此端口)
-///
-/// # 使用方法
-///
-/// 1. 调用 [init] 函数初始化 XL9555
-/// 2. 调用 [init_atk_md0240] 函数初始化 LCD 模块
-/// 3. 调用 [set_lcd_backlight] 函数控制 LCD 背光
-/// 4. 启动 [read_keys] 任务检测按键输入
-pub const XL9555_ADDR: u8 = 0x20; // 7-bit I2C 地址
-
 // 在全局静态变量中添加按键状态跟踪
 // [KEY0, KEY1, KEY2, KEY3]
 static KEY_STATES: Mutex<RefCell<[bool; 4]>> = Mutex::new(RefCell::new([false; 4]));
 // 添加背光状态跟踪
 static BL_STATE: Mutex<RefCell<bool>> = Mutex::new(RefCell::new(true));
+// 输出和方向寄存器的期望值
+static SHADOW: Mutex<RefCell<Shadow>> = Mutex::new(RefCell::new(Shadow::INIT));
+// 按键状态数组下标对应的按键
+const KEYS: [Key; 4] = [Key::Key0, Key::Key1, Key::Key2, Key::Key3];
 
-/// 寄存器地址定义
-///
-/// XL9555 芯片包含以下寄存器：
-/// - 输入端口寄存器：用于读取 GPIO 引脚状态
-/// - 输出端口寄存器：用于设置 GPIO 引脚输出状态
-/// - 极性反转寄存器：用于设置 GPIO 引脚极性
-/// - 配置寄存器：用于设置 GPIO 引脚方向（输入/输出）
-///
-/// 寄存器地址说明：
-/// - INPUT_PORT_0: 0x00 - P0 端口输入寄存器
-/// - INPUT_PORT_1: 0x01 - P1 端口输入寄存器
-/// - OUTPUT_PORT_0: 0x02 - P0 端口输出寄存器
-/// - OUTPUT_PORT_1: 0x03 - P1 端口输出寄存器
-/// - INVERSION_PORT_0: 0x04 - P0 端口极性反转寄存器
-/// - INVERSION_PORT_1: 0x05 - P1 端口极性反转寄存器
-/// - CONFIG_PORT_0: 0x06 - P0 端口方向配置寄存器
-/// - CONFIG_PORT_1: 0x07 - P1 端口方向配置寄存器
-#[allow(unused)]
-pub mod registers {
-    pub const INPUT_PORT_0: u8 = 0;
-    pub const INPUT_PORT_1: u8 = 1;
-    pub const OUTPUT_PORT_0: u8 = 2;
-    pub const OUTPUT_PORT_1: u8 = 3;
-    pub const INVERSION_PORT_0: u8 = 4;
-    pub const INVERSION_PORT_1: u8 = 5;
-    pub const CONFIG_PORT_0: u8 = 6;
-    pub const CONFIG_PORT_1: u8 = 7;
-}
+/// 按键状态变化后的消抖锁定时长，长于一个轮询周期以滤除松开时的抖动
+const KEY_SETTLE: Duration = Duration::from_millis(60);
 
-/// IO 位定义
-///
-/// 定义 XL9555 各个 IO 引脚的功能分配
-/// IO 引脚分为两组：
-/// - P0 端口（P0.0-P0.7）：主要用于按键输入
-/// - P1 端口（P1.0-P1.7）：主要用于 LCD 控制信号输出
-///
-/// 引脚分配说明：
-/// - LCD_BL_IO: P1.0 - LCD 背光控制（备用）
-/// - SLCD_RST_IO: P1.2 - SPI LCD 复位信号
-/// - SLCD_PWR_IO: P1.3 - SPI LCD 电源/背光控制
-/// - KEY0_IO: P1.7 - 按键 0 输入
-/// - KEY1_IO: P1.6 - 按键 1 输入
-/// - KEY2_IO: P1.5 - 按键 2 输入
-/// - KEY3_IO: P1.4 - 按键 3 输入
-#[allow(unused)]
-pub mod io_bits {
-    pub const AP_INT_IO: u16 = 0x0001; // P0.0
-    pub const QMA_INT_IO: u16 = 0x0002; // P0.1
-    pub const SPK_EN_IO: u16 = 0x0004; // P0.2
-    pub const BEEP_IO: u16 = 0x0008; // P0.3
-    pub const OV_PWDN_IO: u16 = 0x0010; // P0.4
-    pub const OV_RESET_IO: u16 = 0x0020; // P0.5
-    pub const GBC_LED_IO: u16 = 0x0040; // P0.6
-    pub const GBC_KEY_IO: u16 = 0x0080; // P0.7
-    pub const LCD_BL_IO: u16 = 0x0100; // P1.0
-    pub const CT_RST_IO: u16 = 0x0200; // P1.1
-    pub const SLCD_RST_IO: u16 = 0x0400; // P1.2
-    pub const SLCD_PWR_IO: u16 = 0x0800; // P1.3
-    pub const KEY3_IO: u16 = 0x1000; // P1.4
-    pub const KEY2_IO: u16 = 0x2000; // P1.5
-    pub const KEY1_IO: u16 = 0x4000; // P1.6
-    pub const KEY0_IO: u16 = 0x8000; // P1.7
-}
+/// 看门狗校验寄存器的周期
+const WATCHDOG_PERIOD: Duration = Duration::from_secs(5);
 
 /// 初始化 XL9555 芯片
 ///
-/// 配置 I2C 接口并设置 GPIO 引脚方向：
-/// - P0 端口配置为输入模式，用于按键检测
-/// - P1 端口部分配置为输出模式，用于 LCD 控制信号
-///
-pub async fn init() -> Result<(), I2cError> {
-    i2c::with_i2c(|i2c| {
-        // 配置XL9555 IO方向 (0表示输出，1表示输入)
-        // P0全部配置为输入 (按键等)
-        // P1配置为输出，但按键引脚配置为输入
-        // P1.0-P1.3 为输出（LCD控制）
-        // P1.4-P1.7 为输入（按键）
-        // 配置 P0 端口为输入模式
-        // P0 端口连接按键，需要配置为输入模式以检测按键状态
-        i2c.write(XL9555_ADDR, &[registers::CONFIG_PORT_0, 0xFF])?;
-        // 配置 P1 端口方向
-        // P1 端口混合使用，低 4 位用于 LCD 控制（输出），高 4 位用于按键（输入）
-        // 0xF0 表示高 4 位为输入(1)，低 4 位为输出(0)
-        i2c.write(XL9555_ADDR, &[registers::CONFIG_PORT_1, 0xF0])?;
+/// 设置 GPIO 引脚方向并清零输出：
+/// - P0 端口配置为输入模式
+/// - P1 端口低 4 位配置为输出模式，用于 LCD 控制信号，高 4 位为按键输入
+pub async fn init() -> Result<(), Error> {
+    let result = i2c::with_i2c("XL9555 init", |i2c| driver::init(i2c));
+    registry::report(Peripheral::Expander, &result);
+    result?;
+    critical_section::with(|cs| *SHADOW.borrow_ref_mut(cs) = Shadow::INIT);
+    Ok(())
+}
 
-        // 初始化 P0 端口输出状态
-        // 将 P0 端口输出寄存器初始化为 0
-        i2c.write(XL9555_ADDR, &[registers::OUTPUT_PORT_0, 0x00])
-            .ok();
-        // 初始化 P1 端口输出状态
-        // 将 P1 端口输出寄存器初始化为 0
-        i2c.write(XL9555_ADDR, &[registers::OUTPUT_PORT_1, 0x00])
-            .ok();
+/// 通过影子寄存器修改引脚电平，芯片复位过时记录日志
+///
+/// # 参数
+/// * `i2c` - I2C 接口引用
+/// * `pins` - [io_bits] 中的引脚
+/// * `high` - true 表示高电平
+/// * `as_output` - 是否同时把引脚配置为输出
+fn write_pins(
+    i2c: &mut I2c<Blocking>,
+    pins: u16,
+    high: bool,
+    as_output: bool,
+) -> Result<(), I2cError> {
+    let verify = critical_section::with(|cs| {
+        let mut shadow = SHADOW.borrow_ref_mut(cs);
+        if as_output {
+            shadow.set_as_output(i2c, pins, high)
+        } else {
+            shadow.write_output(i2c, pins, high)
+        }
+    })?;
+    report(verify);
+    Ok(())
+}
 
-        Ok(())
-    })
+/// 记录校验结果
+fn report(verify: Verify) {
+    match verify {
+        Verify::Ok => {}
+        Verify::Restored => warn!("XL9555 was reset, outputs restored"),
+        Verify::Failed => warn!("XL9555 registers do not hold the written values"),
+    }
 }
 
 // 控制 SPI LCD 电源状态
@@ -139,24 +112,8 @@ pub async fn init() -> Result<(), I2cError> {
 /// # 参数
 /// * `i2c` - I2C 接口引用
 /// * `state` - 电源状态，true 表示开启（高电平），false 表示关闭（低电平）
-pub fn set_spi_lcd_power_state(i2c: &mut I2c<Blocking>, state: bool) {
-    // 读取当前端口1输出状态
-    let mut port1_data = [0u8];
-    if i2c
-        .write_read(XL9555_ADDR, &[registers::OUTPUT_PORT_1], &mut port1_data)
-        .is_ok()
-    {
-        // 根据状态设置 SPI LCD 电源引脚 (P1.3)
-        let new_port1_data = if state {
-            port1_data[0] | (io_bits::SLCD_PWR_IO >> 8) as u8 // 设置P1.3为高电平
-        } else {
-            port1_data[0] & !((io_bits::SLCD_PWR_IO >> 8) as u8) // 设置P1.3为低电平
-        };
-
-        // 写回端口1输出
-        i2c.write(XL9555_ADDR, &[registers::OUTPUT_PORT_1, new_port1_data])
-            .ok();
-    }
+pub fn set_spi_lcd_power_state(i2c: &mut I2c<Blocking>, state: bool) -> Result<(), I2cError> {
+    write_pins(i2c, io_bits::SLCD_PWR_IO, state, false)
 }
 
 // 控制 SPI LCD 复位状态
@@ -166,31 +123,15 @@ pub fn set_spi_lcd_power_state(i2c: &mut I2c<Blocking>, state: bool) {
 /// # 参数
 /// * `i2c` - I2C 接口引用
 /// * `state` - 复位状态，true 表示复位释放（高电平），false 表示复位（低电平）
-pub fn set_spi_lcd_reset_state(i2c: &mut I2c<Blocking>, state: bool) {
-    // 读取当前端口1输出状态
-    let mut port1_data = [0u8];
-    if i2c
-        .write_read(XL9555_ADDR, &[registers::OUTPUT_PORT_1], &mut port1_data)
-        .is_ok()
-    {
-        // 根据状态设置 SPI LCD 复位引脚 (P1.2)
-        let new_port1_data = if state {
-            port1_data[0] | (io_bits::SLCD_RST_IO >> 8) as u8 // 设置P1.2为高电平
-        } else {
-            port1_data[0] & !((io_bits::SLCD_RST_IO >> 8) as u8) // 设置P1.2为低电平
-        };
-
-        // 写回端口1输出
-        i2c.write(XL9555_ADDR, &[registers::OUTPUT_PORT_1, new_port1_data])
-            .ok();
-    }
+#[cfg(feature = "ui")]
+pub fn set_spi_lcd_reset_state(i2c: &mut I2c<Blocking>, state: bool) -> Result<(), I2cError> {
+    write_pins(i2c, io_bits::SLCD_RST_IO, state, false)
 }
 
 // 添加公共函数用于外部调用
-pub async fn spi_lcd_reset(state: bool) {
-    i2c::with_i2c_mut(|i2c| {
-        set_spi_lcd_reset_state(i2c, state);
-    });
+#[cfg(feature = "ui")]
+pub async fn spi_lcd_reset(state: bool) -> Result<(), Error> {
+    i2c::with_i2c("XL9555 LCD reset", |i2c| set_spi_lcd_reset_state(i2c, state))
 }
 
 /// 公共接口函数：控制 LCD 背光开关
@@ -200,38 +141,125 @@ pub async fn spi_lcd_reset(state: bool) {
 ///
 /// # 参数
 /// * `state` - 背光状态，true 表示开启背光，false 表示关闭背光
-pub async fn set_lcd_backlight(state: bool) {
-    i2c::with_i2c_mut(|i2c| {
-        set_spi_lcd_power_state(i2c, state);
-    });
+///
+/// 写入失败时记录的背光状态保持不变
+pub async fn set_lcd_backlight(state: bool) -> Result<(), Error> {
+    i2c::with_i2c("XL9555 LCD backlight", |i2c| set_spi_lcd_power_state(i2c, state))?;
+    critical_section::with(|cs| *BL_STATE.borrow_ref_mut(cs) = state);
+    Ok(())
+}
+
+/// 公共接口函数：LCD 背光当前是否开启
+pub fn lcd_backlight() -> bool {
+    critical_section::with(|cs| *BL_STATE.borrow_ref(cs))
+}
+
+/// 公共接口函数：KEY0-KEY3 当前是否按下
+///
+/// 状态由 [read_keys] 任务更新，任务未运行时全部为 false
+pub fn key_states() -> [bool; 4] {
+    critical_section::with(|cs| *KEY_STATES.borrow_ref(cs))
+}
+
+// 控制摄像头掉电状态
+///
+/// 操作 I2C 接口控制 XL9555 的 P0.4 引脚（OV_PWDN），高电平时摄像头进入掉电模式。
+///
+/// # 参数
+/// * `i2c` - I2C 接口引用
+/// * `power_down` - true 表示掉电（高电平），false 表示正常工作（低电平）
+pub fn set_camera_power_down_state(
+    i2c: &mut I2c<Blocking>,
+    power_down: bool,
+) -> Result<(), I2cError> {
+    write_pins(i2c, io_bits::OV_PWDN_IO, power_down, true)
+}
+
+/// 公共接口函数：控制摄像头掉电
+///
+/// # 参数
+/// * `power_down` - true 表示摄像头掉电，false 表示摄像头上电
+pub async fn set_camera_power_down(power_down: bool) -> Result<(), Error> {
+    i2c::with_i2c("XL9555 camera power", |i2c| set_camera_power_down_state(i2c, power_down))
+}
+
+// 控制蜂鸣器状态
+///
+/// 操作 I2C 接口控制 XL9555 的 P0.3 引脚（BEEP），蜂鸣器为有源蜂鸣器，低电平时鸣响。
+///
+/// # 参数
+/// * `i2c` - I2C 接口引用
+/// * `on` - true 表示鸣响（低电平），false 表示静音（高电平）
+pub fn set_beep_state(i2c: &mut I2c<Blocking>, on: bool) -> Result<(), I2cError> {
+    write_pins(i2c, io_bits::BEEP_IO, !on, true)
+}
+
+/// 公共接口函数：控制蜂鸣器鸣响
+///
+/// 只改变电平，不会自动关闭；应用代码应通过 [crate::buzzer] 定时鸣响
+///
+/// # 参数
+/// * `on` - true 表示鸣响，false 表示静音
+pub async fn set_beep(on: bool) -> Result<(), Error> {
+    i2c::with_i2c("XL9555 beep", |i2c| set_beep_state(i2c, on))
+}
+
+/// 公共接口函数：控制继电器输出
+///
+/// 继电器接在扩展接口引出的 P0.6（GBC_LED）上，高电平吸合。
+/// P0 端口初始化时为输入，第一次调用时改为输出
+///
+/// # 参数
+/// * `on` - true 表示吸合（高电平），false 表示释放（低电平）
+pub async fn set_relay(on: bool) -> Result<(), Error> {
+    i2c::with_i2c("XL9555 relay", |i2c| {
+        write_pins(i2c, io_bits::GBC_LED_IO, on, true)
+    })
+}
+
+/// 控制扩展接口上的空闲引脚（继电器输出，见 [crate::relay]）
+///
+/// 引脚初始化时为输入，第一次调用时改为输出
+///
+/// # 参数
+/// * `pins` - 引脚掩码，见 [io_bits]
+/// * `high` - true 表示输出高电平
+pub async fn set_spare_output(pins: u16, high: bool) -> Result<(), Error> {
+    i2c::with_i2c("XL9555 spare output", |i2c| {
+        write_pins(i2c, pins, high, true)
+    })
 }
 
 /// 初始化ATK-MD0240模块
 /// 执行硬件复位序列：RST引脚拉低至少10微秒，然后拉高并延时120毫秒等待复位完成
-pub async fn init_atk_md0240() {
+#[cfg(feature = "ui")]
+pub async fn init_atk_md0240() -> Result<(), Error> {
     // 拉低RST引脚至少10微秒
-    spi_lcd_reset(false).await;
+    spi_lcd_reset(false).await?;
     Timer::after_micros(10).await;
 
     // 拉高RST引脚
-    spi_lcd_reset(true).await;
+    spi_lcd_reset(true).await?;
 
     // 延时120毫秒等待复位完成
     Timer::after_millis(120).await;
+    Ok(())
 }
 
 /// 按键输入检测任务
 ///
 /// 该异步任务负责持续检测 XL9555 连接的按键状态
 /// 使用轮询方式每 50 毫秒检测一次按键状态
-/// 实现边缘检测，确保按键按下时只触发一次操作
+/// 实现边缘检测，确保按键按下时只触发一次操作；每次状态变化后的 [KEY_SETTLE] 内忽略抖动
 ///
 /// 按键功能分配：
 /// - KEY0: 未分配特定功能
 /// - KEY1: 切换 LCD 背光状态
-/// - KEY2: 未分配特定功能
+/// - KEY2: 切换状态屏幕的背景颜色（见 [crate::render::PALETTE]）
 /// - KEY3: 未分配特定功能
 ///
+/// 所有按键按下时都会发布 [crate::input::Key] 事件，供界面使用，并短鸣一声按键音
+///
 /// 读取按键输入
 /// 状态跟踪: 添加 KEY_STATES 全局变量记录每个按键的上一次状态
 /// 边缘检测: 只有当按键从释放状态(高电平)变为按下状态(低电平)时才触发事件
@@ -247,34 +275,26 @@ pub async fn init_atk_md0240() {
 ///
 #[embassy_executor::task]
 pub async fn read_keys() {
+    // 轮询周期 50ms，同时统计调度延迟
+    let mut monitor = jitter::Monitor::new("keys", Duration::from_millis(50));
+    // 连续读取失败时只记录第一次
+    let mut failing = false;
+    let mut debouncers = [Debouncer::new(KEY_SETTLE.as_micros()); 4];
+    // KEY2 当前选择的背景颜色
+    #[cfg(feature = "ui")]
+    let mut color = 0;
     loop {
-        i2c::with_i2c(|i2c_ref| {
-            // 读取 P0 端口输入状态
-            // 通过读取输入端口寄存器获取 P0 端口当前的电平状态
-            let mut port0_data = [0u8];
-            // 读取 P1 端口输入状态
-            // 通过读取输入端口寄存器获取 P1 端口当前的电平状态
-            let mut port1_data = [0u8];
-
-            i2c_ref
-                .write_read(XL9555_ADDR, &[registers::INPUT_PORT_0], &mut port0_data)
-                .ok();
-            i2c_ref
-                .write_read(XL9555_ADDR, &[registers::INPUT_PORT_1], &mut port1_data)
-                .ok();
+        let result = i2c::with_i2c("XL9555 read keys", |i2c_ref| {
+            // 读取 P0、P1 端口输入状态，读取失败时跳过本次轮询，
+            // 避免把全 0 的结果误判为所有按键按下
+            let inputs = driver::read_inputs(i2c_ref)?;
 
-            // 组合按键值
-            //将 P1 和 P0 端口的值组合成一个 16 位值用于按键检测
-            // 高 8 位来自 P1 端口，低 8 位来自 P0 端口
-            let key_value: u16 = (port1_data[0] as u16) << 8 | (port0_data[0] as u16);
-
-            // 获取当前按键状态（低电平表示按下）
-            let current_states = [
-                (key_value & io_bits::KEY0_IO) == 0,
-                (key_value & io_bits::KEY1_IO) == 0,
-                (key_value & io_bits::KEY2_IO) == 0,
-                (key_value & io_bits::KEY3_IO) == 0,
-            ];
+            // 获取当前按键状态（低电平表示按下），消抖后再做边缘检测
+            let pressed = driver::pressed_keys(inputs);
+            let current_states: [bool; 4] = core::array::from_fn(|i| {
+                debouncers[i].update(&Monotonic, pressed[i]);
+                debouncers[i].state()
+            });
 
             // 检查按键状态变化
             critical_section::with(|cs| {
@@ -282,20 +302,34 @@ pub async fn read_keys() {
                 for i in 0..4 {
                     if current_states[i] && !key_states[i] {
                         // 按键刚被按下
+                        input::publish(KEYS[i]);
+                        buzzer::chirp(buzzer::KEY_CLICK_MS);
                         match i {
                             0 => info!("KEY0 pressed"),
+                            // 界面独占按键时不切换背光
+                            1 if input::is_captured() => info!("KEY1 pressed"),
                             1 => {
                                 info!("KEY1 pressed - toggling LCD backlight");
-                                // 切换背光状态
+                                // 切换背光状态，写入成功后才更新记录的状态
                                 let mut bl_state = BL_STATE.borrow_ref_mut(cs);
-                                *bl_state = !*bl_state;
-                                set_spi_lcd_power_state(i2c_ref, *bl_state);
-                                info!(
-                                    "LCD backlight is now {}",
-                                    if *bl_state { "ON" } else { "OFF" }
-                                );
+                                match set_spi_lcd_power_state(i2c_ref, !*bl_state) {
+                                    Ok(()) => {
+                                        *bl_state = !*bl_state;
+                                        info!(
+                                            "LCD backlight is now {}",
+                                            if *bl_state { "ON" } else { "OFF" }
+                                        );
+                                    }
+                                    Err(err) => warn!("Failed to toggle LCD backlight: {}", err),
+                                }
+                            }
+                            2 if input::is_captured() => info!("KEY2 pressed"),
+                            #[cfg(feature = "ui")]
+                            2 => {
+                                color = (color + 1) % render::PALETTE.len();
+                                info!("KEY2 pressed - background color {}", color);
+                                render::command(Command::FillColor(render::PALETTE[color]));
                             }
-                            2 => info!("KEY2 pressed"),
                             3 => info!("KEY3 pressed"),
                             _ => {}
                         }
@@ -307,9 +341,42 @@ pub async fn read_keys() {
             });
 
             Ok(())
-        })
-        .unwrap();
+        });
+        match result {
+            Ok(()) => failing = false,
+            Err(err) if !failing => {
+                warn!("Failed to read keys: {}", err);
+                failing = true;
+            }
+            Err(_) => {}
+        }
 
-        Timer::after_millis(50).await;
+        monitor.wait().await;
+    }
+}
+
+/// 输出寄存器看门狗任务
+///
+/// 每 [WATCHDOG_PERIOD] 读取输出和方向寄存器与期望值比较，芯片复位过时重写，
+/// 恢复背光、蜂鸣器和继电器等输出。没有写操作时也能发现复位；
+/// 模块拔出后重新插上时同样按期望值恢复，并更新在位状态（见 [crate::registry]）
+#[embassy_executor::task]
+pub async fn watchdog_task() {
+    let mut ticker = Ticker::every(WATCHDOG_PERIOD);
+    loop {
+        ticker.next().await;
+        let result = i2c::with_i2c("XL9555 watchdog", |i2c| {
+            critical_section::with(|cs| SHADOW.borrow_ref(cs).verify(i2c))
+        });
+        // 拔出期间只记录第一次失败
+        if let Err(err) = &result
+            && registry::state(Peripheral::Expander) != State::Offline
+        {
+            warn!("Failed to check XL9555 registers: {}", err);
+        }
+        registry::report(Peripheral::Expander, &result);
+        if let Ok(verify) = result {
+            report(verify);
+        }
     }
 }
@@ -0,0 +1,223 @@
+//! 折线图
+//!
+//! [Chart] 在固定区域内绘制若干条共用纵轴的曲线，例如控制回路的设定值和实际值。
+//! 纵轴按所有曲线的有效数据自动缩放，数据几乎不变时按 `min_span` 展开，
+//! 不会把噪声放大到整个高度。横轴是采样序号，最新的数据在最右边，
+//! 数据不足 `capacity` 个时曲线从右向左延伸。
+//!
+//! 非有限值（NaN、无穷大）表示该时刻没有数据，曲线在此断开。
+//!
+//! 每次调用 [Chart::draw] 都先用背景色填满区域再画线，直接画到屏幕上时会闪烁，
+//! 需要频繁刷新时用 [crate::strip] 分条渲染。
+
+use embedded_graphics::pixelcolor::Rgb565;
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::{Line, PrimitiveStyle, Rectangle};
+
+/// 一条曲线
+#[derive(Debug, Clone, Copy)]
+pub struct Series<'a> {
+    /// 按时间顺序排列的数据，最后一个是最新的
+    pub values: &'a [f32],
+    pub color: Rgb565,
+}
+
+/// 折线图
+#[derive(Debug, Clone, Copy)]
+pub struct Chart {
+    area: Rectangle,
+    /// 横轴能容纳的采样数
+    capacity: usize,
+    /// 纵轴的最小跨度
+    min_span: f32,
+    background: Rgb565,
+    /// 坐标轴颜色
+    axis: Rgb565,
+}
+
+impl Chart {
+    /// 创建折线图
+    ///
+    /// # 参数
+    /// * `area` - 绘制区域，包括左边和下边的坐标轴
+    /// * `capacity` - 横轴能容纳的采样数，至少为 2
+    /// * `min_span` - 纵轴的最小跨度
+    /// * `background` - 背景色
+    /// * `axis` - 坐标轴颜色
+    pub const fn new(
+        area: Rectangle,
+        capacity: usize,
+        min_span: f32,
+        background: Rgb565,
+        axis: Rgb565,
+    ) -> Self {
+        Chart {
+            area,
+            capacity: if capacity < 2 { 2 } else { capacity },
+            min_span,
+            background,
+            axis,
+        }
+    }
+
+    /// 纵轴范围
+    ///
+    /// # 返回
+    /// 最小值和最大值，所有曲线都没有有效数据时返回 None
+    pub fn range(&self, series: &[Series<'_>]) -> Option<(f32, f32)> {
+        let values = series.iter().flat_map(|s| s.values.iter().copied());
+        let (min, max) = values
+            .filter(|v| v.is_finite())
+            .fold(None, |range, v| match range {
+                None => Some((v, v)),
+                Some((min, max)) => Some((v.min(min), v.max(max))),
+            })?;
+        if max - min >= self.min_span {
+            return Some((min, max));
+        }
+        let center = (min + max) / 2.0;
+        Some((center - self.min_span / 2.0, center + self.min_span / 2.0))
+    }
+
+    /// 绘制坐标轴和曲线
+    ///
+    /// # 参数
+    /// * `target` - 绘制目标
+    /// * `series` - 曲线，后面的画在上层，超过 `capacity` 的旧数据不显示
+    ///
+    /// # 返回
+    /// 纵轴范围，见 [Chart::range]
+    pub fn draw<D>(
+        &self,
+        target: &mut D,
+        series: &[Series<'_>],
+    ) -> Result<Option<(f32, f32)>, D::Error>
+    where
+        D: DrawTarget<Color = Rgb565>,
+    {
+        target.fill_solid(&self.area, self.background)?;
+        let Some(bottom_right) = self.area.bottom_right() else {
+            return Ok(None);
+        };
+        let top_left = self.area.top_left;
+        let axis = PrimitiveStyle::with_stroke(self.axis, 1);
+        Line::new(top_left, Point::new(top_left.x, bottom_right.y))
+            .into_styled(axis)
+            .draw(target)?;
+        Line::new(Point::new(top_left.x, bottom_right.y), bottom_right)
+            .into_styled(axis)
+            .draw(target)?;
+
+        let range = self.range(series);
+        let Some((min, max)) = range else {
+            return Ok(None);
+        };
+        // 坐标轴以内的绘图区
+        let (left, right) = (top_left.x + 1, bottom_right.x);
+        let (top, bottom) = (top_left.y, bottom_right.y - 1);
+        let width = (right - left) as f32;
+        let height = (bottom - top) as f32;
+        let step = width / (self.capacity - 1) as f32;
+        for s in series {
+            let values = &s.values[s.values.len().saturating_sub(self.capacity)..];
+            let style = PrimitiveStyle::with_stroke(s.color, 1);
+            let point = |i: usize, v: f32| {
+                let x = right as f32 - (values.len() - 1 - i) as f32 * step;
+                let y = bottom as f32 - (v - min) / (max - min) * height;
+                Point::new((x + 0.5) as i32, (y + 0.5) as i32)
+            };
+            let mut last = None;
+            for (i, &v) in values.iter().enumerate() {
+                if !v.is_finite() {
+                    last = None;
+                    continue;
+                }
+                let p = point(i, v);
+                match last {
+                    Some(from) => Line::new(from, p).into_styled(style).draw(target)?,
+                    None => Pixel(p, s.color).draw(target)?,
+                }
+                last = Some(p);
+            }
+        }
+        Ok(range)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use drivers::sim;
+
+    const AREA: Rectangle = Rectangle::new(Point::new(10, 20), Size::new(101, 51));
+
+    fn chart() -> Chart {
+        Chart::new(AREA, 11, 1.0, Rgb565::BLACK, Rgb565::WHITE)
+    }
+
+    #[test]
+    fn range_covers_all_series() {
+        let a = [1.0, 2.0, f32::NAN];
+        let b = [5.0, -3.0];
+        let series = [
+            Series {
+                values: &a,
+                color: Rgb565::RED,
+            },
+            Series {
+                values: &b,
+                color: Rgb565::GREEN,
+            },
+        ];
+        assert_eq!(chart().range(&series), Some((-3.0, 5.0)));
+    }
+
+    #[test]
+    fn flat_data_is_centered() {
+        let values = [20.0; 4];
+        let series = [Series {
+            values: &values,
+            color: Rgb565::RED,
+        }];
+        assert_eq!(chart().range(&series), Some((19.5, 20.5)));
+
+        let (mut lcd, panel) = sim::display();
+        chart().draw(&mut lcd, &series).unwrap();
+        // 绘图区 y 20..=69，中点 y = 44.5 取整到 45；最新的点在最右边
+        assert_eq!(panel.borrow().pixel(110, 45), Rgb565::RED);
+        assert_eq!(panel.borrow().pixel(80, 45), Rgb565::RED);
+        // 只有 4 个点，左侧没有数据
+        assert_eq!(panel.borrow().pixel(50, 45), Rgb565::BLACK);
+        // 坐标轴
+        assert_eq!(panel.borrow().pixel(10, 30), Rgb565::WHITE);
+        assert_eq!(panel.borrow().pixel(60, 70), Rgb565::WHITE);
+    }
+
+    #[test]
+    fn gaps_break_the_line() {
+        let values = [0.0, 0.0, f32::NAN, 1.0, 1.0];
+        let series = [Series {
+            values: &values,
+            color: Rgb565::RED,
+        }];
+        let (mut lcd, panel) = sim::display();
+        assert_eq!(chart().draw(&mut lcd, &series).unwrap(), Some((0.0, 1.0)));
+        // 第 4、5 个点在顶部（x 100..=110），第 1、2 个点在底部（x 70..=80）
+        assert_eq!(panel.borrow().pixel(105, 20), Rgb565::RED);
+        assert_eq!(panel.borrow().pixel(75, 69), Rgb565::RED);
+        // 缺失的第 3 个点两侧没有连线
+        assert_eq!(panel.borrow().pixel(90, 44), Rgb565::BLACK);
+    }
+
+    #[test]
+    fn no_data_draws_axes_only() {
+        let series = [Series {
+            values: &[f32::NAN],
+            color: Rgb565::RED,
+        }];
+        let (mut lcd, panel) = sim::display();
+        assert_eq!(chart().draw(&mut lcd, &series).unwrap(), None);
+        assert_eq!(panel.borrow().pixel(60, 44), Rgb565::BLACK);
+        assert_eq!(panel.borrow().pixel(10, 44), Rgb565::WHITE);
+    }
+}
@@ -0,0 +1,306 @@
+//! 帧率控制
+//!
+//! [FrameLimiter] 按目标帧率安排动画帧：帧的时间落在以第一帧为起点、间隔为帧周期的网格上，
+//! 绘制得快时等到下一个格点，一帧超时就跳过已经错过的格点，直接对齐到之后的第一个格点，
+//! 不会为了追帧连续绘制。面板没有引出 TE（撕裂效应）信号，格点只是固定的节拍，
+//! 与控制器的扫描并不同步；但帧间隔稳定，动画不会忽快忽慢。
+//!
+//! 同时统计每秒实际绘制的帧数、帧时间和跳过的帧数（[FrameStats]），用于调试界面性能。
+//! 与 [crate::tween] 一样，时间由调用方以毫秒传入，可以在主机上测试。
+//!
+//! ```
+//! use ui::frame::FrameLimiter;
+//!
+//! let mut frames = FrameLimiter::new(20);
+//! frames.request(1000);
+//! assert_eq!(frames.next_frame(), Some(1000));
+//! frames.begin(1000);
+//! frames.finish(1012, true);
+//! assert_eq!(frames.next_frame(), Some(1050));
+//! ```
+
+/// 最低帧率
+pub const MIN_FPS: u32 = 1;
+
+/// 最高帧率
+pub const MAX_FPS: u32 = 60;
+
+/// 统计窗口（毫秒）
+const WINDOW_MS: u64 = 1000;
+
+/// 最近一个统计窗口的帧率统计
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct FrameStats {
+    /// 每秒绘制的帧数
+    pub fps: u32,
+    /// 平均帧时间（毫秒）
+    pub average_ms: u32,
+    /// 最长帧时间（毫秒）
+    pub max_ms: u32,
+    /// 因超时跳过的帧数
+    pub skipped: u32,
+}
+
+/// 正在累计的统计窗口
+#[derive(Debug, Clone, Copy, Default)]
+struct Window {
+    start_ms: u64,
+    frames: u32,
+    busy_ms: u64,
+    max_ms: u32,
+    skipped: u32,
+}
+
+/// 帧率控制器
+#[derive(Debug, Clone)]
+pub struct FrameLimiter {
+    fps: u32,
+    period_ms: u32,
+    /// 网格起点，None 表示没有动画
+    anchor_ms: Option<u64>,
+    /// 下一帧的时间
+    next_ms: Option<u64>,
+    /// 当前帧的开始时间
+    started_ms: u64,
+    window: Window,
+    stats: FrameStats,
+}
+
+impl FrameLimiter {
+    /// 创建控制器
+    ///
+    /// # 参数
+    /// * `fps` - 目标帧率，超出 [MIN_FPS]-[MAX_FPS] 时取最近的边界
+    pub const fn new(fps: u32) -> Self {
+        let fps = clamp(fps);
+        FrameLimiter {
+            fps,
+            period_ms: 1000 / fps,
+            anchor_ms: None,
+            next_ms: None,
+            started_ms: 0,
+            window: Window {
+                start_ms: 0,
+                frames: 0,
+                busy_ms: 0,
+                max_ms: 0,
+                skipped: 0,
+            },
+            stats: FrameStats {
+                fps: 0,
+                average_ms: 0,
+                max_ms: 0,
+                skipped: 0,
+            },
+        }
+    }
+
+    /// 目标帧率
+    pub fn fps(&self) -> u32 {
+        self.fps
+    }
+
+    /// 帧周期（毫秒）
+    pub fn period_ms(&self) -> u32 {
+        self.period_ms
+    }
+
+    /// 修改目标帧率，从已安排的下一帧开始按新的周期对齐
+    pub fn set_fps(&mut self, fps: u32) {
+        self.fps = clamp(fps);
+        self.period_ms = 1000 / self.fps;
+        self.anchor_ms = self.next_ms;
+    }
+
+    /// 下一帧的时间，None 表示没有动画，不需要绘制
+    pub fn next_frame(&self) -> Option<u64> {
+        self.next_ms
+    }
+
+    /// 请求绘制，空闲时从 `now_ms` 开始一段新的动画，已在播放时不改变节拍
+    pub fn request(&mut self, now_ms: u64) {
+        if self.next_ms.is_none() {
+            self.anchor_ms = Some(now_ms);
+            self.next_ms = Some(now_ms);
+        }
+    }
+
+    /// 停止动画，直到下一次 [FrameLimiter::request]
+    pub fn stop(&mut self) {
+        self.anchor_ms = None;
+        self.next_ms = None;
+    }
+
+    /// 开始绘制一帧
+    pub fn begin(&mut self, now_ms: u64) {
+        self.started_ms = now_ms;
+    }
+
+    /// 一帧绘制完成，只记录帧时间，不影响动画的节拍
+    ///
+    /// 用于动画以外的绘制（例如页面刷新），它们同样占用总线，计入统计
+    pub fn record(&mut self, now_ms: u64) {
+        self.roll(now_ms);
+        let spent = now_ms.saturating_sub(self.started_ms);
+        self.window.frames += 1;
+        self.window.busy_ms += spent;
+        self.window.max_ms = self.window.max_ms.max(spent as u32);
+    }
+
+    /// 一帧动画绘制完成，记录帧时间并安排下一帧
+    ///
+    /// # 参数
+    /// * `now_ms` - 绘制完成的时间
+    /// * `animating` - 是否还需要下一帧，false 时停止
+    pub fn finish(&mut self, now_ms: u64, animating: bool) {
+        self.record(now_ms);
+        let anchor = match self.anchor_ms {
+            Some(anchor) if animating => anchor,
+            _ => {
+                self.stop();
+                return;
+            }
+        };
+        let period = self.period_ms as u64;
+        // 本帧所在的格点和完成时刻之后的第一个格点，中间的格点都已错过
+        let slot = self.started_ms.saturating_sub(anchor) / period;
+        let next = now_ms.saturating_sub(anchor) / period + 1;
+        self.window.skipped += next.saturating_sub(slot + 1) as u32;
+        self.next_ms = Some(anchor + next * period);
+    }
+
+    /// 最近一个完整统计窗口的结果，超过一个窗口没有绘制时各项为 0
+    pub fn stats(&mut self, now_ms: u64) -> FrameStats {
+        self.roll(now_ms);
+        self.stats
+    }
+
+    /// 统计窗口结束时发布结果并开始新的窗口
+    fn roll(&mut self, now_ms: u64) {
+        let elapsed = now_ms.saturating_sub(self.window.start_ms);
+        if elapsed < WINDOW_MS {
+            return;
+        }
+        let window = self.window;
+        let frames = window.frames as u64;
+        self.stats = if elapsed >= 2 * WINDOW_MS {
+            // 整整一个窗口没有结束过，说明中间空闲了，之前的统计已经过时
+            FrameStats::default()
+        } else {
+            FrameStats {
+                fps: (frames * 1000 / elapsed) as u32,
+                average_ms: window.busy_ms.checked_div(frames).unwrap_or(0) as u32,
+                max_ms: window.max_ms,
+                skipped: window.skipped,
+            }
+        };
+        self.window = Window {
+            start_ms: now_ms,
+            ..Window::default()
+        };
+    }
+}
+
+/// 把帧率限制在 [MIN_FPS]-[MAX_FPS] 之间
+const fn clamp(fps: u32) -> u32 {
+    if fps < MIN_FPS {
+        MIN_FPS
+    } else if fps > MAX_FPS {
+        MAX_FPS
+    } else {
+        fps
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 从 `at` 开始绘制一帧，耗时 `spent` 毫秒
+    fn frame(frames: &mut FrameLimiter, at: u64, spent: u64) {
+        frames.begin(at);
+        frames.finish(at + spent, true);
+    }
+
+    #[test]
+    fn frames_land_on_the_grid() {
+        let mut frames = FrameLimiter::new(20);
+        assert_eq!(frames.next_frame(), None);
+        frames.request(100);
+        assert_eq!(frames.next_frame(), Some(100));
+        // 播放中再次请求不改变节拍
+        frames.request(120);
+        assert_eq!(frames.next_frame(), Some(100));
+
+        frame(&mut frames, 100, 10);
+        assert_eq!(frames.next_frame(), Some(150));
+        // 唤醒晚了几毫秒，下一帧仍然对齐到格点
+        frame(&mut frames, 157, 10);
+        assert_eq!(frames.next_frame(), Some(200));
+    }
+
+    #[test]
+    fn slow_frames_skip_missed_slots() {
+        let mut frames = FrameLimiter::new(20);
+        frames.request(0);
+        frame(&mut frames, 0, 120);
+        assert_eq!(frames.next_frame(), Some(150));
+        frame(&mut frames, 150, 10);
+        assert_eq!(frames.next_frame(), Some(200));
+        // 动画以外的绘制只计入统计
+        frames.begin(170);
+        frames.record(190);
+        assert_eq!(frames.next_frame(), Some(200));
+        let stats = frames.stats(1000);
+        assert_eq!((stats.fps, stats.max_ms, stats.skipped), (3, 120, 2));
+    }
+
+    #[test]
+    fn finishing_without_animation_stops() {
+        let mut frames = FrameLimiter::new(30);
+        frames.request(0);
+        frames.begin(0);
+        frames.finish(5, false);
+        assert_eq!(frames.next_frame(), None);
+        // 重新开始时以新的请求时间为起点
+        frames.request(1234);
+        frames.begin(1234);
+        frames.finish(1240, true);
+        assert_eq!(frames.next_frame(), Some(1234 + 33));
+    }
+
+    #[test]
+    fn set_fps_clamps_and_realigns() {
+        let mut frames = FrameLimiter::new(0);
+        assert_eq!(frames.period_ms(), 1000);
+        frames.set_fps(500);
+        assert_eq!(frames.fps(), MAX_FPS);
+        assert_eq!(frames.period_ms(), 16);
+
+        frames.set_fps(10);
+        frames.request(0);
+        frame(&mut frames, 0, 5);
+        assert_eq!(frames.next_frame(), Some(100));
+        frames.set_fps(25);
+        frame(&mut frames, 100, 5);
+        assert_eq!(frames.next_frame(), Some(140));
+    }
+
+    #[test]
+    fn stats_cover_the_last_window() {
+        let mut frames = FrameLimiter::new(20);
+        frames.request(0);
+        for i in 0..20 {
+            frame(&mut frames, i * 50, 4 + i % 3);
+        }
+        let stats = frames.stats(1000);
+        assert_eq!(stats.fps, 20);
+        assert_eq!(stats.average_ms, 4);
+        assert_eq!(stats.max_ms, 6);
+        assert_eq!(stats.skipped, 0);
+        // 窗口内没有新的帧
+        assert_eq!(frames.stats(2100).fps, 0);
+        assert_eq!(frames.stats(5000), FrameStats::default());
+    }
+}
@@ -0,0 +1,435 @@
+//! 整帧帧缓冲区
+//!
+//! [Framebuffer] 在内存中保存整个屏幕的 RGB565 像素（320x240 需 150KB，固件中放在 PSRAM），
+//! 实现 [DrawTarget]，绘制只写内存，同时记录改变过的区域（脏区域）。一帧画完后调用方用
+//! [Framebuffer::take_dirty] 取出脏区域，用 [chunks] 按传输缓冲区的大小拆块，
+//! [Framebuffer::window] 取出每块的像素发送到 LCD，没有改变的部分不再传输。
+//! 逐像素绘制（`draw_iter`）也只是写内存，不会对每个像素单独设置窗口、发起传输。
+//!
+//! 脏区域最多记录 [MAX_DIRTY] 个矩形。新的区域与已有区域相交或相邻时合并为外接矩形；
+//! 已满时与合并后面积增加最少的区域合并。合并可能多传输一些没有改变的像素，但结果总是正确的。
+//!
+//! ```
+//! use embedded_graphics::pixelcolor::Rgb565;
+//! use embedded_graphics::prelude::*;
+//! use embedded_graphics::primitives::{PrimitiveStyle, Rectangle};
+//! use ui::framebuffer::{self, Framebuffer};
+//!
+//! let mut buffer = vec![0u8; 320 * 240 * 2];
+//! let mut fb = Framebuffer::new(&mut buffer, Size::new(320, 240)).unwrap();
+//! Rectangle::new(Point::new(20, 20), Size::new(100, 50))
+//!     .into_styled(PrimitiveStyle::with_fill(Rgb565::WHITE))
+//!     .draw(&mut fb)
+//!     .unwrap();
+//! let dirty = fb.take_dirty();
+//! assert_eq!(dirty.as_slice(), [Rectangle::new(Point::new(20, 20), Size::new(100, 50))]);
+//!
+//! let mut scratch = [0u8; 4096];
+//! for chunk in framebuffer::chunks(dirty[0], scratch.len()) {
+//!     let pixels = fb.window(&chunk, &mut scratch).unwrap();
+//!     // 发送到 LCD
+//!     assert_eq!(pixels.len(), chunk.size.width as usize * chunk.size.height as usize * 2);
+//! }
+//! ```
+
+use core::convert::Infallible;
+use embedded_graphics::pixelcolor::Rgb565;
+use embedded_graphics::pixelcolor::raw::RawU16;
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::Rectangle;
+use heapless::Vec;
+
+/// 最多记录的脏区域数
+pub const MAX_DIRTY: usize = 8;
+
+/// 整帧帧缓冲区
+pub struct Framebuffer<'a> {
+    /// 按行排列的像素，每像素 2 字节（大端），与 LCD 的传输格式相同
+    buf: &'a mut [u8],
+    size: Size,
+    /// 上次 [Framebuffer::take_dirty] 以来改变过的区域，互不相交也不相邻
+    dirty: Vec<Rectangle, MAX_DIRTY>,
+}
+
+impl<'a> Framebuffer<'a> {
+    /// 创建帧缓冲区，初始内容为缓冲区中原有的数据，没有脏区域
+    ///
+    /// # 参数
+    /// * `buf` - 像素缓冲区，至少 `宽度 * 高度 * 2` 字节
+    /// * `size` - 屏幕大小
+    ///
+    /// # 返回
+    /// 缓冲区不够大时返回 None
+    pub fn new(buf: &'a mut [u8], size: Size) -> Option<Self> {
+        let len = size.width as usize * size.height as usize * 2;
+        let buf = buf.get_mut(..len)?;
+        Some(Framebuffer {
+            buf,
+            size,
+            dirty: Vec::new(),
+        })
+    }
+
+    /// 上次取出以来改变过的区域
+    pub fn dirty(&self) -> &[Rectangle] {
+        &self.dirty
+    }
+
+    /// 是否有没有发送的改变
+    pub fn is_dirty(&self) -> bool {
+        !self.dirty.is_empty()
+    }
+
+    /// 把整个屏幕标记为已改变，例如 LCD 的内容被其他途径覆盖或上次发送失败后
+    pub fn invalidate(&mut self) {
+        let all = self.bounding_box();
+        self.mark_dirty(all);
+    }
+
+    /// 把一个区域标记为已改变，超出屏幕的部分被裁剪
+    ///
+    /// # 参数
+    /// * `area` - 改变的区域
+    pub fn mark_dirty(&mut self, area: Rectangle) {
+        let mut area = area.intersection(&self.bounding_box());
+        if area.is_zero_sized() {
+            return;
+        }
+        loop {
+            // 合并后的区域可能又与其他区域相交，重新检查
+            if let Some(i) = self.dirty.iter().position(|d| touches(d, &area)) {
+                area = union(&self.dirty.swap_remove(i), &area);
+                continue;
+            }
+            if !self.dirty.is_full() {
+                break;
+            }
+            let cheapest = (0..self.dirty.len())
+                .min_by_key(|&i| growth(&self.dirty[i], &area))
+                .unwrap_or(0);
+            area = union(&self.dirty.swap_remove(cheapest), &area);
+        }
+        // 上面保证了有空位
+        self.dirty.push(area).ok();
+    }
+
+    /// 取出并清空脏区域
+    pub fn take_dirty(&mut self) -> Vec<Rectangle, MAX_DIRTY> {
+        core::mem::take(&mut self.dirty)
+    }
+
+    /// 取出一个区域的像素，按行排列，每像素 2 字节（大端）
+    ///
+    /// 区域与屏幕等宽时直接返回缓冲区中的数据，否则逐行复制到 `scratch`
+    ///
+    /// # 参数
+    /// * `area` - 区域，必须完整位于屏幕内
+    /// * `scratch` - 复制用的缓冲区，至少 `宽度 * 高度 * 2` 字节
+    ///
+    /// # 返回
+    /// 区域超出屏幕或 `scratch` 不够大时返回 None
+    pub fn window<'s>(&'s self, area: &Rectangle, scratch: &'s mut [u8]) -> Option<&'s [u8]> {
+        if area.is_zero_sized() || area.intersection(&self.bounding_box()) != *area {
+            return None;
+        }
+        let width = self.size.width as usize;
+        let (left, top) = (area.top_left.x as usize, area.top_left.y as usize);
+        let (w, h) = (area.size.width as usize, area.size.height as usize);
+        if w == width {
+            return Some(&self.buf[top * width * 2..(top + h) * width * 2]);
+        }
+        let out = scratch.get_mut(..w * h * 2)?;
+        for (row, dst) in out.chunks_exact_mut(w * 2).enumerate() {
+            let start = ((top + row) * width + left) * 2;
+            dst.copy_from_slice(&self.buf[start..start + w * 2]);
+        }
+        Some(out)
+    }
+
+    /// 写入一个像素，调用方保证坐标在屏幕内
+    fn set(&mut self, point: Point, raw: [u8; 2]) {
+        let index = (point.y as usize * self.size.width as usize + point.x as usize) * 2;
+        self.buf[index..index + 2].copy_from_slice(&raw);
+    }
+}
+
+impl OriginDimensions for Framebuffer<'_> {
+    fn size(&self) -> Size {
+        self.size
+    }
+}
+
+impl DrawTarget for Framebuffer<'_> {
+    type Color = Rgb565;
+    type Error = Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        // 一次调用的所有像素合成一个外接矩形标记，不逐个像素合并脏区域
+        let bounds = self.bounding_box();
+        let mut extent: Option<(Point, Point)> = None;
+        for Pixel(point, color) in pixels {
+            if !bounds.contains(point) {
+                continue;
+            }
+            self.set(point, RawU16::from(color).into_inner().to_be_bytes());
+            extent = Some(match extent {
+                Some((min, max)) => (min.component_min(point), max.component_max(point)),
+                None => (point, point),
+            });
+        }
+        if let Some((min, max)) = extent {
+            self.mark_dirty(Rectangle::with_corners(min, max));
+        }
+        Ok(())
+    }
+
+    fn fill_contiguous<I>(&mut self, area: &Rectangle, colors: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Self::Color>,
+    {
+        let bounds = self.bounding_box();
+        for (point, color) in area.points().zip(colors) {
+            if bounds.contains(point) {
+                self.set(point, RawU16::from(color).into_inner().to_be_bytes());
+            }
+        }
+        self.mark_dirty(*area);
+        Ok(())
+    }
+
+    fn fill_solid(&mut self, area: &Rectangle, color: Self::Color) -> Result<(), Self::Error> {
+        let area = area.intersection(&self.bounding_box());
+        let Some(bottom_right) = area.bottom_right() else {
+            return Ok(());
+        };
+        let raw = RawU16::from(color).into_inner().to_be_bytes();
+        let width = self.size.width as usize;
+        let (left, right) = (area.top_left.x as usize, bottom_right.x as usize);
+        for y in area.rows() {
+            let row = y as usize * width;
+            for pixel in self.buf[(row + left) * 2..(row + right + 1) * 2].chunks_exact_mut(2) {
+                pixel.copy_from_slice(&raw);
+            }
+        }
+        self.mark_dirty(area);
+        Ok(())
+    }
+
+    fn clear(&mut self, color: Self::Color) -> Result<(), Self::Error> {
+        let all = self.bounding_box();
+        self.fill_solid(&all, color)
+    }
+}
+
+/// 把区域按行拆成不超过 `max_bytes` 字节的块，用于按传输缓冲区的大小分块发送
+///
+/// # 参数
+/// * `area` - 区域
+/// * `max_bytes` - 每块的最大字节数，放不下一行时不拆出任何块
+pub fn chunks(area: Rectangle, max_bytes: usize) -> impl Iterator<Item = Rectangle> {
+    let row_bytes = area.size.width as usize * 2;
+    let rows = (max_bytes / row_bytes.max(1)).min(area.size.height as usize) as u32;
+    let height = if rows == 0 { 0 } else { area.size.height };
+    (0..height)
+        .step_by(rows.max(1) as usize)
+        .map(move |offset| {
+            let top_left = area.top_left + Point::new(0, offset as i32);
+            Rectangle::new(
+                top_left,
+                Size::new(area.size.width, rows.min(height - offset)),
+            )
+        })
+}
+
+/// 两个区域是否相交或相邻（包括只有角接触）
+fn touches(a: &Rectangle, b: &Rectangle) -> bool {
+    let (Some(a_end), Some(b_end)) = (a.bottom_right(), b.bottom_right()) else {
+        return false;
+    };
+    a.top_left.x <= b_end.x + 1
+        && b.top_left.x <= a_end.x + 1
+        && a.top_left.y <= b_end.y + 1
+        && b.top_left.y <= a_end.y + 1
+}
+
+/// 两个区域的外接矩形
+fn union(a: &Rectangle, b: &Rectangle) -> Rectangle {
+    match (a.bottom_right(), b.bottom_right()) {
+        (Some(a_end), Some(b_end)) => Rectangle::with_corners(
+            a.top_left.component_min(b.top_left),
+            a_end.component_max(b_end),
+        ),
+        (Some(_), None) => *a,
+        _ => *b,
+    }
+}
+
+/// 把 `b` 合并进 `a` 后面积的增加量
+fn growth(a: &Rectangle, b: &Rectangle) -> u64 {
+    let area = |r: &Rectangle| r.size.width as u64 * r.size.height as u64;
+    area(&union(a, b)) - area(a)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use drivers::sim;
+    use embedded_graphics::mono_font::MonoTextStyle;
+    use embedded_graphics::mono_font::ascii::FONT_10X20;
+    use embedded_graphics::primitives::{Circle, PrimitiveStyle, RoundedRectangle};
+    use embedded_graphics::text::Text;
+
+    const SCREEN: Size = Size::new(320, 240);
+
+    fn rect(x: i32, y: i32, w: u32, h: u32) -> Rectangle {
+        Rectangle::new(Point::new(x, y), Size::new(w, h))
+    }
+
+    /// 背景上叠加圆角面板、圆和文字
+    fn scene<D>(target: &mut D) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Color = Rgb565>,
+    {
+        target.clear(Rgb565::CSS_NAVY)?;
+        RoundedRectangle::with_equal_corners(rect(30, 40, 200, 120), Size::new(12, 12))
+            .into_styled(PrimitiveStyle::with_fill(Rgb565::CSS_DARK_SLATE_GRAY))
+            .draw(target)?;
+        Circle::new(Point::new(250, 150), 60)
+            .into_styled(PrimitiveStyle::with_stroke(Rgb565::YELLOW, 3))
+            .draw(target)?;
+        let style = MonoTextStyle::new(&FONT_10X20, Rgb565::WHITE);
+        Text::new("Framebuffer", Point::new(40, 63), style).draw(target)?;
+        Ok(())
+    }
+
+    /// 把脏区域按 `scratch_len` 字节分块发送到模拟面板
+    fn flush(fb: &mut Framebuffer<'_>, lcd: &mut sim::SimDisplay, scratch_len: usize) -> usize {
+        let mut scratch = vec![0u8; scratch_len];
+        let mut sent = 0;
+        for area in fb.take_dirty() {
+            for chunk in chunks(area, scratch.len()) {
+                let pixels = fb.window(&chunk, &mut scratch).unwrap();
+                let (x, y) = (chunk.top_left.x as u16, chunk.top_left.y as u16);
+                let (w, h) = (chunk.size.width as u16, chunk.size.height as u16);
+                lcd.blit(x, y, w, h, pixels).unwrap();
+                sent += pixels.len();
+            }
+        }
+        sent
+    }
+
+    #[test]
+    fn flushed_frame_matches_direct_drawing() {
+        let (mut direct, expected) = sim::display();
+        scene(&mut direct).unwrap();
+
+        let (mut lcd, panel) = sim::display();
+        let mut buffer = vec![0u8; SCREEN.width as usize * SCREEN.height as usize * 2];
+        let mut fb = Framebuffer::new(&mut buffer, SCREEN).unwrap();
+        scene(&mut fb).unwrap();
+        assert_eq!(fb.dirty(), [rect(0, 0, 320, 240)]);
+        flush(&mut fb, &mut lcd, 320 * 16 * 2);
+        assert!(!fb.is_dirty());
+        assert_eq!(panel.borrow().checksum(), expected.borrow().checksum());
+    }
+
+    #[test]
+    fn only_changed_area_is_sent() {
+        let (mut lcd, panel) = sim::display();
+        let mut buffer = vec![0u8; SCREEN.width as usize * SCREEN.height as usize * 2];
+        let mut fb = Framebuffer::new(&mut buffer, SCREEN).unwrap();
+        fb.clear(Rgb565::BLACK).unwrap();
+        flush(&mut fb, &mut lcd, 4096);
+
+        Pixel(Point::new(100, 50), Rgb565::RED)
+            .draw(&mut fb)
+            .unwrap();
+        Pixel(Point::new(101, 51), Rgb565::RED)
+            .draw(&mut fb)
+            .unwrap();
+        assert_eq!(fb.dirty(), [rect(100, 50, 2, 2)]);
+        assert_eq!(flush(&mut fb, &mut lcd, 4096), 2 * 2 * 2);
+        assert_eq!(panel.borrow().pixel(101, 51), Rgb565::RED);
+        assert_eq!(panel.borrow().pixel(101, 50), Rgb565::BLACK);
+    }
+
+    #[test]
+    fn adjacent_regions_merge_and_distant_ones_do_not() {
+        let mut buffer = vec![0u8; 64 * 64 * 2];
+        let mut fb = Framebuffer::new(&mut buffer, Size::new(64, 64)).unwrap();
+        fb.mark_dirty(rect(0, 0, 10, 20));
+        fb.mark_dirty(rect(10, 0, 10, 20));
+        fb.mark_dirty(rect(40, 40, 4, 4));
+        assert_eq!(fb.dirty(), [rect(0, 0, 20, 20), rect(40, 40, 4, 4)]);
+
+        // 把两个区域连起来后三者合并
+        fb.mark_dirty(rect(19, 19, 22, 22));
+        assert_eq!(fb.dirty(), [rect(0, 0, 44, 44)]);
+    }
+
+    #[test]
+    fn full_dirty_list_merges_cheapest_pair() {
+        let mut buffer = vec![0u8; 200 * 20 * 2];
+        let mut fb = Framebuffer::new(&mut buffer, Size::new(200, 20)).unwrap();
+        for i in 0..MAX_DIRTY as i32 {
+            fb.mark_dirty(rect(i * 20, 0, 2, 2));
+        }
+        assert_eq!(fb.dirty().len(), MAX_DIRTY);
+        fb.mark_dirty(rect(163, 0, 2, 2));
+        assert_eq!(fb.dirty().len(), MAX_DIRTY);
+        assert!(fb.dirty().contains(&rect(140, 0, 25, 2)));
+    }
+
+    #[test]
+    fn marks_are_clipped_to_screen() {
+        let mut buffer = vec![0u8; 32 * 32 * 2];
+        let mut fb = Framebuffer::new(&mut buffer, Size::new(32, 32)).unwrap();
+        fb.mark_dirty(rect(-5, 30, 10, 10));
+        fb.mark_dirty(rect(40, 40, 4, 4));
+        assert_eq!(fb.dirty(), [rect(0, 30, 5, 2)]);
+        Pixel(Point::new(-1, 3), Rgb565::RED).draw(&mut fb).unwrap();
+        assert_eq!(fb.dirty().len(), 1);
+    }
+
+    #[test]
+    fn chunks_fit_the_transfer_buffer() {
+        let full: std::vec::Vec<_> = chunks(rect(0, 0, 320, 240), 320 * 16 * 2).collect();
+        assert_eq!(full.len(), 15);
+        assert_eq!(full[14], rect(0, 224, 320, 16));
+
+        let narrow: std::vec::Vec<_> = chunks(rect(5, 10, 10, 7), 10 * 3 * 2).collect();
+        assert_eq!(
+            narrow,
+            [rect(5, 10, 10, 3), rect(5, 13, 10, 3), rect(5, 16, 10, 1)]
+        );
+
+        assert_eq!(chunks(rect(0, 0, 320, 4), 100).count(), 0);
+    }
+
+    #[test]
+    fn window_copies_rows_of_a_narrow_area() {
+        let mut buffer = vec![0u8; 4 * 3 * 2];
+        let mut fb = Framebuffer::new(&mut buffer, Size::new(4, 3)).unwrap();
+        fb.fill_solid(&rect(1, 1, 2, 2), Rgb565::WHITE).unwrap();
+        let mut scratch = [0u8; 8];
+        assert_eq!(
+            fb.window(&rect(1, 1, 2, 2), &mut scratch),
+            Some(&[0xFF; 8][..])
+        );
+        assert_eq!(
+            fb.window(&rect(0, 2, 4, 1), &mut scratch).unwrap()[..4],
+            [0, 0, 0xFF, 0xFF]
+        );
+        assert_eq!(fb.window(&rect(0, 0, 3, 3), &mut scratch), None);
+        assert_eq!(fb.window(&rect(3, 0, 2, 1), &mut scratch), None);
+    }
+
+    #[test]
+    fn small_buffer_is_rejected() {
+        let mut buffer = [0u8; 100];
+        assert!(Framebuffer::new(&mut buffer, SCREEN).is_none());
+    }
+}
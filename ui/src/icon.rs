@@ -0,0 +1,305 @@
+//! 图标
+//!
+//! 16x16 的单色位图，编译进固件，绘制时指定颜色，状态栏和菜单直接使用，不必在应用代码里画像素。
+//! 信号强度和电池电量图标按等级（0 到 [LEVELS]）生成，其余为固定图形。
+//!
+//! 位图每行一个 `u16`，最高位为最左边的像素。
+
+use embedded_graphics::pixelcolor::Rgb565;
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::Rectangle;
+
+/// 图标边长（像素）
+pub const SIZE: u32 = 16;
+
+/// 信号强度和电池电量的最高等级
+pub const LEVELS: u8 = 4;
+
+/// 单色图标
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Icon {
+    rows: [u16; SIZE as usize],
+}
+
+impl Icon {
+    /// 从位图创建图标
+    ///
+    /// # 参数
+    /// * `rows` - 从上到下每行的像素，最高位为最左边
+    pub const fn from_rows(rows: [u16; SIZE as usize]) -> Self {
+        Icon { rows }
+    }
+
+    /// 像素是否点亮，超出图标范围时为 false
+    pub const fn is_set(&self, x: u32, y: u32) -> bool {
+        x < SIZE && y < SIZE && self.rows[y as usize] & (0x8000 >> x) != 0
+    }
+
+    /// 只绘制点亮的像素，其余位置保持原样
+    ///
+    /// # 参数
+    /// * `target` - 绘制目标
+    /// * `origin` - 图标左上角
+    /// * `color` - 图标颜色
+    pub fn draw<D>(&self, target: &mut D, origin: Point, color: Rgb565) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Color = Rgb565>,
+    {
+        let pixels = (0..SIZE)
+            .flat_map(|y| (0..SIZE).map(move |x| (x, y)))
+            .filter(|&(x, y)| self.is_set(x, y))
+            .map(|(x, y)| Pixel(origin + Point::new(x as i32, y as i32), color));
+        target.draw_iter(pixels)
+    }
+
+    /// 连同背景一起绘制整个图标区域，覆盖之前的内容
+    ///
+    /// # 参数
+    /// * `target` - 绘制目标
+    /// * `origin` - 图标左上角
+    /// * `color` - 图标颜色
+    /// * `background` - 未点亮像素的颜色
+    pub fn draw_opaque<D>(
+        &self,
+        target: &mut D,
+        origin: Point,
+        color: Rgb565,
+        background: Rgb565,
+    ) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Color = Rgb565>,
+    {
+        let area = Rectangle::new(origin, Size::new(SIZE, SIZE));
+        let colors = area.points().map(|point| {
+            let offset = point - origin;
+            if self.is_set(offset.x as u32, offset.y as u32) {
+                color
+            } else {
+                background
+            }
+        });
+        target.fill_contiguous(&area, colors)
+    }
+}
+
+/// 信号强度：四根由低到高的竖条，点亮 `level` 根，未点亮的只画底线
+///
+/// # 参数
+/// * `level` - 0 到 [LEVELS]，超出时按 [LEVELS] 计算
+pub const fn signal(level: u8) -> Icon {
+    let level = if level > LEVELS { LEVELS } else { level };
+    let mut rows = [0u16; SIZE as usize];
+    let mut bar = 0;
+    while bar < LEVELS {
+        // 竖条宽 3 像素，间隔 1 像素，高度依次为 4、7、10、13
+        let columns = 0xE000u16 >> (bar * 4 + 1);
+        let height = if bar < level { 4 + bar as usize * 3 } else { 1 };
+        let mut y = SIZE as usize - height;
+        while y < SIZE as usize {
+            rows[y] |= columns;
+            y += 1;
+        }
+        bar += 1;
+    }
+    Icon { rows }
+}
+
+/// 电池电量：横放的电池外框，内部按 `level` 填充
+///
+/// # 参数
+/// * `level` - 0 到 [LEVELS]，超出时按 [LEVELS] 计算
+pub const fn battery(level: u8) -> Icon {
+    let level = if level > LEVELS { LEVELS } else { level };
+    let mut rows = [0u16; SIZE as usize];
+    // 外框占第 0-13 列、第 4-11 行，正极在第 14-15 列、第 6-9 行
+    rows[4] = 0xFFFC;
+    rows[11] = 0xFFFC;
+    let mut y = 5;
+    while y < 11 {
+        rows[y] = 0x8004;
+        y += 1;
+    }
+    let mut y = 6;
+    while y < 10 {
+        rows[y] |= 0x0003;
+        // 内部第 2-11 列，共 10 列
+        let width = level as u32 * 10 / LEVELS as u32;
+        if width > 0 {
+            rows[y] |= (0xFFC0u16 << (10 - width)) >> 2;
+        }
+        y += 1;
+    }
+    Icon { rows }
+}
+
+/// TF 卡
+pub const SD: Icon = Icon::from_rows([
+    0b0001_1111_1111_0000,
+    0b0001_0101_0100_1000,
+    0b0001_0101_0100_0100,
+    0b0001_0101_0100_0010,
+    0b0001_0000_0000_0010,
+    0b0001_0000_0000_0010,
+    0b0001_0000_0000_0010,
+    0b0001_0000_0000_0010,
+    0b0001_0000_0000_0010,
+    0b0001_0000_0000_0010,
+    0b0001_0000_0000_0010,
+    0b0001_0000_0000_0010,
+    0b0001_0000_0000_0010,
+    0b0001_0000_0000_0010,
+    0b0001_1111_1111_1110,
+    0b0000_0000_0000_0000,
+]);
+
+/// 铃铛，用于提醒和闹钟
+pub const BELL: Icon = Icon::from_rows([
+    0b0000_0001_1000_0000,
+    0b0000_0011_1100_0000,
+    0b0000_0111_1110_0000,
+    0b0000_1111_1111_0000,
+    0b0000_1111_1111_0000,
+    0b0000_1111_1111_0000,
+    0b0000_1111_1111_0000,
+    0b0001_1111_1111_1000,
+    0b0001_1111_1111_1000,
+    0b0011_1111_1111_1100,
+    0b0011_1111_1111_1100,
+    0b0111_1111_1111_1110,
+    0b0111_1111_1111_1110,
+    0b0000_0000_0000_0000,
+    0b0000_0011_1100_0000,
+    0b0000_0001_1000_0000,
+]);
+
+/// 温度计
+pub const THERMOMETER: Icon = Icon::from_rows([
+    0b0000_0001_1000_0000,
+    0b0000_0010_0100_0000,
+    0b0000_0010_0100_0000,
+    0b0000_0010_0100_0000,
+    0b0000_0010_0100_0000,
+    0b0000_0010_0100_0000,
+    0b0000_0011_1100_0000,
+    0b0000_0011_1100_0000,
+    0b0000_0011_1100_0000,
+    0b0000_0011_1100_0000,
+    0b0000_0111_1110_0000,
+    0b0000_1111_1111_0000,
+    0b0000_1111_1111_0000,
+    0b0000_1111_1111_0000,
+    0b0000_0111_1110_0000,
+    0b0000_0011_1100_0000,
+]);
+
+/// 播放
+pub const PLAY: Icon = Icon::from_rows([
+    0b0000_0000_0000_0000,
+    0b0001_1000_0000_0000,
+    0b0001_1110_0000_0000,
+    0b0001_1111_1000_0000,
+    0b0001_1111_1110_0000,
+    0b0001_1111_1111_1000,
+    0b0001_1111_1111_1110,
+    0b0001_1111_1111_1111,
+    0b0001_1111_1111_1110,
+    0b0001_1111_1111_1000,
+    0b0001_1111_1110_0000,
+    0b0001_1111_1000_0000,
+    0b0001_1110_0000_0000,
+    0b0001_1000_0000_0000,
+    0b0000_0000_0000_0000,
+    0b0000_0000_0000_0000,
+]);
+
+/// 暂停
+pub const PAUSE: Icon = Icon::from_rows([
+    0b0000_0000_0000_0000,
+    0b0000_0000_0000_0000,
+    0b0001_1110_0111_1000,
+    0b0001_1110_0111_1000,
+    0b0001_1110_0111_1000,
+    0b0001_1110_0111_1000,
+    0b0001_1110_0111_1000,
+    0b0001_1110_0111_1000,
+    0b0001_1110_0111_1000,
+    0b0001_1110_0111_1000,
+    0b0001_1110_0111_1000,
+    0b0001_1110_0111_1000,
+    0b0001_1110_0111_1000,
+    0b0001_1110_0111_1000,
+    0b0000_0000_0000_0000,
+    0b0000_0000_0000_0000,
+]);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use drivers::sim;
+
+    /// 点亮的像素数
+    fn lit(icon: &Icon) -> usize {
+        (0..SIZE)
+            .flat_map(|y| (0..SIZE).map(move |x| (x, y)))
+            .filter(|&(x, y)| icon.is_set(x, y))
+            .count()
+    }
+
+    #[test]
+    fn signal_lights_bars_up_to_level() {
+        // 第 2 根竖条（第 5-7 列）高 7 像素
+        assert!(!signal(1).is_set(6, 9));
+        assert!(signal(1).is_set(6, 15));
+        assert!(signal(2).is_set(6, 9));
+        assert!(!signal(2).is_set(6, 8));
+        // 最高的竖条占满第 3-15 行
+        assert!(signal(4).is_set(14, 3));
+        assert!(!signal(4).is_set(14, 2));
+        for level in 1..=LEVELS {
+            assert!(lit(&signal(level)) > lit(&signal(level - 1)));
+        }
+        assert_eq!(signal(LEVELS + 3), signal(LEVELS));
+    }
+
+    #[test]
+    fn battery_fills_inside_the_outline() {
+        let empty = battery(0);
+        assert!(empty.is_set(0, 4) && empty.is_set(13, 11) && empty.is_set(15, 7));
+        assert!(!empty.is_set(2, 7));
+        assert!(battery(1).is_set(2, 7));
+        assert!(!battery(1).is_set(4, 7));
+        assert!(battery(LEVELS).is_set(11, 7));
+        // 内框与外框之间留一列空隙
+        assert!(!battery(LEVELS).is_set(12, 7));
+        for level in 1..=LEVELS {
+            assert!(lit(&battery(level)) > lit(&battery(level - 1)));
+        }
+    }
+
+    #[test]
+    fn draw_leaves_unlit_pixels_untouched() {
+        let (mut lcd, panel) = sim::display();
+        lcd.clear(Rgb565::BLUE).unwrap();
+        PAUSE
+            .draw(&mut lcd, Point::new(100, 50), Rgb565::WHITE)
+            .unwrap();
+        let panel = panel.borrow();
+        assert_eq!(panel.pixel(103, 52), Rgb565::WHITE);
+        assert_eq!(panel.pixel(107, 52), Rgb565::BLUE);
+        assert_eq!(panel.pixel(100, 50), Rgb565::BLUE);
+    }
+
+    #[test]
+    fn draw_opaque_covers_the_whole_square() {
+        let (mut lcd, panel) = sim::display();
+        lcd.clear(Rgb565::BLUE).unwrap();
+        PLAY.draw_opaque(&mut lcd, Point::new(0, 0), Rgb565::GREEN, Rgb565::BLACK)
+            .unwrap();
+        let panel = panel.borrow();
+        assert_eq!(panel.pixel(3, 7), Rgb565::GREEN);
+        assert_eq!(panel.pixel(15, 7), Rgb565::GREEN);
+        assert_eq!(panel.pixel(0, 0), Rgb565::BLACK);
+        assert_eq!(panel.pixel(15, 15), Rgb565::BLACK);
+        assert_eq!(panel.pixel(16, 0), Rgb565::BLUE);
+    }
+}
@@ -0,0 +1,555 @@
+//! 屏幕键盘
+//!
+//! 用于输入 WiFi 密码等文本：上方一行显示已输入的内容，下方是字符网格，
+//! 最后一行为功能键（切换字符页、空格、删除、完成）。字符分小写、大写、符号三页，
+//! 覆盖全部可打印 ASCII 字符。
+//!
+//! 输入方式与具体硬件无关，调用方把事件转换为 [Input]：
+//! - 四个按键：方向键移动选中项，[Input::Press] 输入，[Input::Cancel] 返回
+//! - 旋转编码器：[Input::Next] / [Input::Previous] 按行依次移动，按下为 [Input::Press]
+//! - 触摸屏：[Input::Tap] 直接选中并输入触摸位置的键
+//!
+//! [Keyboard::draw] 记住上一次绘制的状态，移动选中项时只重绘变化的两个键。
+//! 颜色取自创建时的 [Theme]，选中的键以 [Theme::highlight] 为底色。
+
+use crate::theme::Theme;
+use embedded_graphics::mono_font::ascii::FONT_10X20;
+use embedded_graphics::mono_font::{MonoTextStyle, MonoTextStyleBuilder};
+use embedded_graphics::pixelcolor::Rgb565;
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::Rectangle;
+use embedded_graphics::text::{Baseline, Text};
+use heapless::String;
+
+/// 键的宽度（像素），一行最多 10 个键，正好占满 320 像素宽的屏幕
+pub const CELL_WIDTH: u32 = 32;
+
+/// 键和输入行的高度（像素）
+pub const CELL_HEIGHT: u32 = 24;
+
+/// 一行最多的键数
+const COLUMNS: usize = 10;
+
+/// 字符行数
+const CHAR_ROWS: usize = 4;
+
+/// 总行数：字符行加一行功能键
+const ROWS: usize = CHAR_ROWS + 1;
+
+/// 键盘总高度（像素），包括输入行
+pub const HEIGHT: u32 = CELL_HEIGHT * (ROWS as u32 + 1);
+
+/// 字符宽度（像素）
+const CHAR_WIDTH: u32 = 10;
+
+/// 字符在键内的上边距（像素）
+const TEXT_TOP: i32 = 2;
+
+/// 各字符页的内容
+const LOWER: [&str; CHAR_ROWS] = ["1234567890", "qwertyuiop", "asdfghjkl.", "zxcvbnm-_@"];
+const UPPER: [&str; CHAR_ROWS] = ["1234567890", "QWERTYUIOP", "ASDFGHJKL.", "ZXCVBNM-_@"];
+const SYMBOL: [&str; CHAR_ROWS] = ["!\"#$%&'()*", "+,-./:;<=>", "?@[\\]^_`{|", "}~"];
+
+/// 功能键
+const SPECIALS: [Special; 4] = [
+    Special::Layer,
+    Special::Space,
+    Special::Delete,
+    Special::Done,
+];
+
+/// 功能键所占的键宽数，合计为 [COLUMNS]
+const SPECIAL_SPANS: [usize; 4] = [2, 4, 2, 2];
+
+/// 输入事件
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Input {
+    /// 同一行内左移，到头后回到行尾
+    Left,
+    /// 同一行内右移，到头后回到行首
+    Right,
+    /// 移到上一行位置相同的键
+    Up,
+    /// 移到下一行位置相同的键
+    Down,
+    /// 移到下一个键，行尾后进入下一行
+    Next,
+    /// 移到上一个键，行首前回到上一行
+    Previous,
+    /// 输入选中的键
+    Press,
+    /// 选中并输入屏幕上该位置的键，不在键上时忽略
+    Tap(Point),
+    /// 放弃输入
+    Cancel,
+}
+
+/// 输入结束的方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    /// 按下了“完成”键
+    Done,
+    /// 放弃输入
+    Cancelled,
+}
+
+/// 字符页
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Layer {
+    Lower,
+    Upper,
+    Symbol,
+}
+
+impl Layer {
+    fn rows(self) -> &'static [&'static str; CHAR_ROWS] {
+        match self {
+            Layer::Lower => &LOWER,
+            Layer::Upper => &UPPER,
+            Layer::Symbol => &SYMBOL,
+        }
+    }
+
+    fn next(self) -> Layer {
+        match self {
+            Layer::Lower => Layer::Upper,
+            Layer::Upper => Layer::Symbol,
+            Layer::Symbol => Layer::Lower,
+        }
+    }
+}
+
+/// 功能键
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Special {
+    /// 切换到下一个字符页
+    Layer,
+    Space,
+    Delete,
+    Done,
+}
+
+/// 键
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Cell {
+    Char(char),
+    Special(Special),
+}
+
+/// 已绘制的状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Drawn {
+    layer: Layer,
+    selected: (usize, usize),
+}
+
+/// 屏幕键盘，最多输入 `N` 字节
+pub struct Keyboard<const N: usize> {
+    origin: Point,
+    text: String<N>,
+    layer: Layer,
+    /// 选中的键：行号和行内序号
+    selected: (usize, usize),
+    /// 上一次绘制的状态，None 表示需要完整重绘
+    drawn: Option<Drawn>,
+    theme: Theme,
+    normal: MonoTextStyle<'static, Rgb565>,
+    highlight: MonoTextStyle<'static, Rgb565>,
+}
+
+impl<const N: usize> Keyboard<N> {
+    /// 创建键盘
+    ///
+    /// # 参数
+    /// * `origin` - 输入行的左上角坐标，键盘宽 320、高 [HEIGHT] 像素
+    /// * `text` - 初始内容，超出 `N` 字节的部分被忽略
+    /// * `theme` - 配色
+    pub fn new(origin: Point, text: &str, theme: Theme) -> Self {
+        let style = |color, background| {
+            MonoTextStyleBuilder::new()
+                .font(&FONT_10X20)
+                .text_color(color)
+                .background_color(background)
+                .build()
+        };
+        let mut keyboard = Keyboard {
+            origin,
+            text: String::new(),
+            layer: Layer::Lower,
+            selected: (0, 0),
+            drawn: None,
+            theme,
+            normal: style(theme.foreground, theme.background),
+            highlight: style(theme.background, theme.highlight),
+        };
+        for c in text.chars() {
+            if keyboard.text.push(c).is_err() {
+                break;
+            }
+        }
+        keyboard
+    }
+
+    /// 已输入的内容
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// 取出已输入的内容
+    pub fn into_text(self) -> String<N> {
+        self.text
+    }
+
+    /// 下次绘制时完整重绘（例如屏幕被其他内容覆盖后）
+    pub fn invalidate(&mut self) {
+        self.drawn = None;
+    }
+
+    /// 处理一个输入事件
+    ///
+    /// 内容已满时输入的字符被忽略。
+    ///
+    /// # 返回
+    /// 输入结束时返回结束方式，否则为 None
+    pub fn handle(&mut self, input: Input) -> Option<Outcome> {
+        let (row, col) = self.selected;
+        match input {
+            Input::Left => self.selected.1 = (col + self.row_len(row) - 1) % self.row_len(row),
+            Input::Right => self.selected.1 = (col + 1) % self.row_len(row),
+            Input::Up => self.move_to_row((row + ROWS - 1) % ROWS),
+            Input::Down => self.move_to_row((row + 1) % ROWS),
+            Input::Next if col + 1 < self.row_len(row) => self.selected.1 = col + 1,
+            Input::Next => self.selected = ((row + 1) % ROWS, 0),
+            Input::Previous if col > 0 => self.selected.1 = col - 1,
+            Input::Previous => {
+                let row = (row + ROWS - 1) % ROWS;
+                self.selected = (row, self.row_len(row) - 1);
+            }
+            Input::Press => return self.press(),
+            Input::Tap(point) => {
+                if let Some(key) = self.key_at(point) {
+                    self.selected = key;
+                    return self.press();
+                }
+            }
+            Input::Cancel => return Some(Outcome::Cancelled),
+        }
+        None
+    }
+
+    /// 输入选中的键
+    fn press(&mut self) -> Option<Outcome> {
+        let (row, col) = self.selected;
+        match self.cell(row, col) {
+            Cell::Char(c) => {
+                self.text.push(c).ok();
+            }
+            Cell::Special(Special::Layer) => {
+                self.layer = self.layer.next();
+                // 各页行长不同，选中项保持在功能键上
+            }
+            Cell::Special(Special::Space) => {
+                self.text.push(' ').ok();
+            }
+            Cell::Special(Special::Delete) => {
+                self.text.pop();
+            }
+            Cell::Special(Special::Done) => return Some(Outcome::Done),
+        }
+        None
+    }
+
+    /// 移到另一行，选中覆盖当前键左边缘的键
+    fn move_to_row(&mut self, row: usize) {
+        let (start, _) = self.span(self.selected.0, self.selected.1);
+        self.selected = (row, self.key_at_column(row, start));
+    }
+
+    /// 一行的键数
+    fn row_len(&self, row: usize) -> usize {
+        match self.layer.rows().get(row) {
+            Some(chars) => chars.len(),
+            None => SPECIALS.len(),
+        }
+    }
+
+    fn cell(&self, row: usize, col: usize) -> Cell {
+        match self.layer.rows().get(row) {
+            Some(chars) => Cell::Char(chars.as_bytes()[col] as char),
+            None => Cell::Special(SPECIALS[col]),
+        }
+    }
+
+    /// 键占据的位置：起始列和列数
+    fn span(&self, row: usize, col: usize) -> (usize, usize) {
+        if row < CHAR_ROWS {
+            return (col, 1);
+        }
+        let start = SPECIAL_SPANS[..col].iter().sum();
+        (start, SPECIAL_SPANS[col])
+    }
+
+    /// 覆盖第 `column` 列的键，超出行尾时为最后一个键
+    fn key_at_column(&self, row: usize, column: usize) -> usize {
+        (0..self.row_len(row))
+            .find(|&col| {
+                let (start, span) = self.span(row, col);
+                column < start + span
+            })
+            .unwrap_or(self.row_len(row) - 1)
+    }
+
+    /// 屏幕上某一点所在的键
+    fn key_at(&self, point: Point) -> Option<(usize, usize)> {
+        let offset = point - self.origin;
+        if offset.x < 0 || offset.y < CELL_HEIGHT as i32 {
+            return None;
+        }
+        let row = (offset.y as u32 / CELL_HEIGHT - 1) as usize;
+        let column = (offset.x as u32 / CELL_WIDTH) as usize;
+        if row >= ROWS || column >= COLUMNS {
+            return None;
+        }
+        let col = self.key_at_column(row, column);
+        let (start, span) = self.span(row, col);
+        (column < start + span).then_some((row, col))
+    }
+
+    /// 功能键的标签，切换键显示下一页的名称
+    fn label(&self, special: Special) -> &'static str {
+        match special {
+            Special::Layer => match self.layer.next() {
+                Layer::Lower => "abc",
+                Layer::Upper => "ABC",
+                Layer::Symbol => "#+=",
+            },
+            Special::Space => "space",
+            Special::Delete => "DEL",
+            Special::Done => "OK",
+        }
+    }
+
+    /// 绘制键盘
+    ///
+    /// 字符页变化或调用 [Keyboard::invalidate] 后完整重绘，
+    /// 否则只重绘输入行和选中状态变化的键。
+    pub fn draw<D>(&mut self, lcd: &mut D) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Color = Rgb565>,
+    {
+        let current = Drawn {
+            layer: self.layer,
+            selected: self.selected,
+        };
+        match self.drawn {
+            Some(drawn) if drawn.layer == self.layer => {
+                if drawn.selected != self.selected {
+                    self.draw_key(lcd, drawn.selected)?;
+                    self.draw_key(lcd, self.selected)?;
+                }
+            }
+            _ => {
+                let grid = Size::new(CELL_WIDTH * COLUMNS as u32, CELL_HEIGHT * ROWS as u32);
+                let top_left = self.origin + Point::new(0, CELL_HEIGHT as i32);
+                lcd.fill_solid(&Rectangle::new(top_left, grid), self.theme.background)?;
+                for row in 0..ROWS {
+                    for col in 0..self.row_len(row) {
+                        self.draw_key(lcd, (row, col))?;
+                    }
+                }
+            }
+        }
+        self.draw_field(lcd)?;
+        self.drawn = Some(current);
+        Ok(())
+    }
+
+    /// 绘制输入行，内容过长时显示末尾部分
+    fn draw_field<D>(&self, lcd: &mut D) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Color = Rgb565>,
+    {
+        let size = Size::new(CELL_WIDTH * COLUMNS as u32, CELL_HEIGHT);
+        lcd.fill_solid(&Rectangle::new(self.origin, size), self.theme.background)?;
+        // 留一个字符的位置给光标
+        let visible = (size.width / CHAR_WIDTH) as usize - 1;
+        let skip = self.text.chars().count().saturating_sub(visible);
+        let tail = self
+            .text
+            .char_indices()
+            .nth(skip)
+            .map_or("", |(i, _)| &self.text[i..]);
+        let position = self.origin + Point::new(0, TEXT_TOP);
+        let next = Text::with_baseline(tail, position, self.normal, Baseline::Top).draw(lcd)?;
+        Text::with_baseline("_", next, self.normal, Baseline::Top).draw(lcd)?;
+        Ok(())
+    }
+
+    /// 绘制一个键，选中的键高亮显示
+    fn draw_key<D>(&self, lcd: &mut D, (row, col): (usize, usize)) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Color = Rgb565>,
+    {
+        let selected = (row, col) == self.selected;
+        let style = if selected {
+            self.highlight
+        } else {
+            self.normal
+        };
+        let background = if selected {
+            self.theme.highlight
+        } else {
+            self.theme.background
+        };
+
+        let (start, span) = self.span(row, col);
+        let top_left = self.origin
+            + Point::new(
+                (start as u32 * CELL_WIDTH) as i32,
+                ((row as u32 + 1) * CELL_HEIGHT) as i32,
+            );
+        let width = span as u32 * CELL_WIDTH;
+        // 键之间留 1 像素间隙
+        let key = Rectangle::new(top_left, Size::new(width - 1, CELL_HEIGHT - 1));
+        lcd.fill_solid(&key, background)?;
+
+        let mut buf = [0; 4];
+        let label = match self.cell(row, col) {
+            Cell::Char(c) => &*c.encode_utf8(&mut buf),
+            Cell::Special(special) => self.label(special),
+        };
+        let x = (width - label.len() as u32 * CHAR_WIDTH) / 2;
+        let position = top_left + Point::new(x as i32, TEXT_TOP);
+        Text::with_baseline(label, position, style, Baseline::Top).draw(lcd)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::theme::{DARK, LIGHT};
+    use drivers::sim;
+
+    const ORIGIN: Point = Point::new(0, 40);
+
+    fn keyboard() -> Keyboard<8> {
+        Keyboard::new(ORIGIN, "", DARK)
+    }
+
+    /// 键中心在屏幕上的位置
+    fn center(row: usize, column: usize) -> Point {
+        ORIGIN
+            + Point::new(
+                (column as u32 * CELL_WIDTH + CELL_WIDTH / 2) as i32,
+                ((row as u32 + 1) * CELL_HEIGHT + CELL_HEIGHT / 2) as i32,
+            )
+    }
+
+    #[test]
+    fn types_selected_characters() {
+        let mut kb = keyboard();
+        assert_eq!(kb.handle(Input::Press), None);
+        kb.handle(Input::Down);
+        kb.handle(Input::Right);
+        kb.handle(Input::Press);
+        assert_eq!(kb.text(), "1w");
+    }
+
+    #[test]
+    fn moves_wrap_within_row_and_grid() {
+        let mut kb = keyboard();
+        kb.handle(Input::Left);
+        assert_eq!(kb.selected, (0, 9));
+        kb.handle(Input::Next);
+        assert_eq!(kb.selected, (1, 0));
+        kb.handle(Input::Previous);
+        kb.handle(Input::Previous);
+        assert_eq!(kb.selected, (0, 8));
+        kb.handle(Input::Up);
+        // 功能键行中覆盖第 8 列的是“完成”键
+        assert_eq!(kb.selected, (4, 3));
+        kb.handle(Input::Up);
+        assert_eq!(kb.selected, (3, 8));
+    }
+
+    #[test]
+    fn special_keys_edit_text() {
+        let mut kb = Keyboard::<8>::new(ORIGIN, "ab", DARK);
+        kb.selected = (4, 2);
+        kb.handle(Input::Press);
+        kb.selected = (4, 1);
+        kb.handle(Input::Press);
+        assert_eq!(kb.text(), "a ");
+        kb.selected = (4, 3);
+        assert_eq!(kb.handle(Input::Press), Some(Outcome::Done));
+        assert_eq!(kb.handle(Input::Cancel), Some(Outcome::Cancelled));
+
+        // 内容已满时忽略输入的字符
+        let mut kb = Keyboard::<2>::new(ORIGIN, "abc", DARK);
+        assert_eq!(kb.text(), "ab");
+        kb.handle(Input::Press);
+        assert_eq!(kb.text(), "ab");
+    }
+
+    #[test]
+    fn layer_key_switches_pages() {
+        let mut kb = keyboard();
+        kb.selected = (4, 0);
+        kb.handle(Input::Press);
+        kb.handle(Input::Up);
+        kb.handle(Input::Press);
+        assert_eq!(kb.text(), "Z");
+
+        kb.handle(Input::Down);
+        kb.handle(Input::Press);
+        // 符号页最后一行只有两个键
+        kb.handle(Input::Up);
+        kb.handle(Input::Right);
+        kb.handle(Input::Press);
+        assert_eq!(kb.text(), "Z~");
+    }
+
+    #[test]
+    fn tap_presses_key_under_point() {
+        let mut kb = keyboard();
+        kb.handle(Input::Tap(center(2, 3)));
+        // “空格”键占第 2-5 列
+        kb.handle(Input::Tap(center(4, 5)));
+        assert_eq!(kb.text(), "f ");
+        // 输入行和行尾之后的空位不是键
+        kb.handle(Input::Tap(ORIGIN + Point::new(5, 5)));
+        kb.selected = (4, 0);
+        kb.handle(Input::Press);
+        kb.handle(Input::Press);
+        kb.handle(Input::Tap(center(3, 5)));
+        assert_eq!(kb.text(), "f ");
+        assert_eq!(kb.selected, (4, 0));
+    }
+
+    #[test]
+    fn draw_highlights_selected_key() {
+        let (mut lcd, panel) = sim::display();
+        let mut kb = Keyboard::<8>::new(ORIGIN, "", LIGHT);
+        kb.draw(&mut lcd).unwrap();
+        let corner = |row: usize, column: usize| {
+            let p = center(row, column) - Point::new(CELL_WIDTH as i32 / 2 - 1, 0);
+            panel.borrow().pixel(p.x as u16, p.y as u16)
+        };
+        assert_eq!(corner(0, 0), LIGHT.highlight);
+        assert_eq!(corner(0, 1), LIGHT.background);
+
+        // 未变化的键不重绘，标记保留
+        let mark = center(2, 0) - Point::new(CELL_WIDTH as i32 / 2 - 1, 0);
+        Pixel(mark, Rgb565::RED).draw(&mut lcd).unwrap();
+        kb.handle(Input::Right);
+        kb.draw(&mut lcd).unwrap();
+        assert_eq!(corner(0, 0), LIGHT.background);
+        assert_eq!(corner(0, 1), LIGHT.highlight);
+        assert_eq!(corner(2, 0), Rgb565::RED);
+
+        kb.invalidate();
+        kb.draw(&mut lcd).unwrap();
+        assert_eq!(corner(2, 0), LIGHT.background);
+    }
+}
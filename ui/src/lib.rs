@@ -0,0 +1,25 @@
+//! 界面组件
+//!
+//! 只依赖 embedded-graphics 的 [DrawTarget](embedded_graphics::draw_target::DrawTarget)，
+//! 与具体的 LCD 总线无关：固件中绘制到板载 LCD，主机上绘制到
+//! `drivers::sim` 的模拟面板（drivers 的 `sim` 特性），在桌面上开发和做快照测试：
+//!
+//! ```text
+//! cargo +stable test -p ui --target x86_64-unknown-linux-gnu
+//! ```
+//!
+//! 不接设备查看画面时，可以把模拟面板的 `to_ppm()` 保存为图片。
+
+#![cfg_attr(not(test), no_std)]
+
+pub mod chart;
+pub mod frame;
+pub mod framebuffer;
+pub mod icon;
+pub mod keyboard;
+pub mod screens;
+pub mod segment;
+pub mod strip;
+pub mod text;
+pub mod theme;
+pub mod tween;
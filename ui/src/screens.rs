@@ -0,0 +1,388 @@
+//! 多页面屏幕管理
+//!
+//! 每个页面实现 [Screen]：成为当前页面时调用 [Screen::on_enter]，收到输入时调用
+//! [Screen::on_event]，刷新时调用 [Screen::render]。[Navigator] 维护页面栈：
+//!
+//! - 栈底是一组并列的标签页（例如仪表盘、设置、文件），[Event::SwipeLeft] 和
+//!   [Event::SwipeRight] 在标签页之间循环切换；
+//! - 页面返回 [Response::Push] 打开子页面，返回 [Response::Pop] 回到上一页；
+//! - 页面不处理的 [Event::Back] 和 [Event::SwipeRight] 关闭子页面，
+//!   在标签页上按返回回到第一个标签页。
+//!
+//! 页面对象由应用持有，导航器只保存页面标识，通过 [Pages] 取得页面，不需要堆内存。
+//! 按键和触摸手势由应用转换为 [Event]。
+
+use embedded_graphics::pixelcolor::Rgb565;
+use embedded_graphics::prelude::*;
+use heapless::Vec;
+
+/// 页面输入事件
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    /// 上一项
+    Up,
+    /// 下一项
+    Down,
+    /// 确认
+    Select,
+    /// 返回
+    Back,
+    /// 向左滑动：下一个标签页
+    SwipeLeft,
+    /// 向右滑动：上一个标签页，或关闭子页面
+    SwipeRight,
+}
+
+/// 页面对事件的处理结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Response<Id> {
+    /// 已处理，页面自己负责局部刷新
+    Handled,
+    /// 已处理，需要重绘整个页面
+    Redraw,
+    /// 打开子页面
+    Push(Id),
+    /// 关闭当前页面，回到上一页
+    Pop,
+    /// 未处理，由导航器按默认方式处理
+    Ignored,
+}
+
+/// 页面
+pub trait Screen<Id, D: DrawTarget<Color = Rgb565>> {
+    /// 成为当前页面时调用，之后会完整重绘
+    fn on_enter(&mut self) {}
+
+    /// 处理输入事件
+    fn on_event(&mut self, _event: Event) -> Response<Id> {
+        Response::Ignored
+    }
+
+    /// 绘制页面
+    ///
+    /// # 参数
+    /// * `target` - 绘制目标
+    /// * `full` - 为 true 时清除整个页面后重绘，否则只刷新变化的内容
+    fn render(&mut self, target: &mut D, full: bool) -> Result<(), D::Error>;
+}
+
+/// 按标识取得页面
+pub trait Pages<Id, D: DrawTarget<Color = Rgb565>> {
+    fn page(&mut self, id: Id) -> &mut dyn Screen<Id, D>;
+}
+
+/// 导航错误
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum NavError {
+    /// 子页面层数已达上限
+    StackFull,
+}
+
+/// 页面导航器
+///
+/// `DEPTH` 为标签页之上最多打开的子页面层数。
+pub struct Navigator<Id: Copy + PartialEq + 'static, const DEPTH: usize> {
+    tabs: &'static [Id],
+    tab: usize,
+    stack: Vec<Id, DEPTH>,
+    /// 下一次绘制是否需要完整重绘
+    full: bool,
+}
+
+impl<Id: Copy + PartialEq + 'static, const DEPTH: usize> Navigator<Id, DEPTH> {
+    /// 创建导航器，当前页面为第一个标签页
+    ///
+    /// 创建后应调用 [Navigator::start] 通知第一个页面。
+    ///
+    /// # 参数
+    /// * `tabs` - 标签页，不能为空
+    pub fn new(tabs: &'static [Id]) -> Self {
+        assert!(!tabs.is_empty(), "navigator needs at least one tab");
+        Navigator {
+            tabs,
+            tab: 0,
+            stack: Vec::new(),
+            full: true,
+        }
+    }
+
+    /// 当前页面
+    pub fn current(&self) -> Id {
+        self.stack.last().copied().unwrap_or(self.tabs[self.tab])
+    }
+
+    /// 已打开的子页面层数，0 表示当前为标签页
+    pub fn depth(&self) -> usize {
+        self.stack.len()
+    }
+
+    /// 通知当前页面已进入
+    pub fn start<D, P>(&mut self, pages: &mut P)
+    where
+        D: DrawTarget<Color = Rgb565>,
+        P: Pages<Id, D>,
+    {
+        self.entered(pages);
+    }
+
+    /// 打开子页面
+    pub fn push<D, P>(&mut self, pages: &mut P, id: Id) -> Result<(), NavError>
+    where
+        D: DrawTarget<Color = Rgb565>,
+        P: Pages<Id, D>,
+    {
+        self.stack.push(id).map_err(|_| NavError::StackFull)?;
+        self.entered(pages);
+        Ok(())
+    }
+
+    /// 关闭当前子页面
+    ///
+    /// # 返回
+    /// 当前为标签页、没有可关闭的子页面时返回 false
+    pub fn pop<D, P>(&mut self, pages: &mut P) -> bool
+    where
+        D: DrawTarget<Color = Rgb565>,
+        P: Pages<Id, D>,
+    {
+        if self.stack.pop().is_none() {
+            return false;
+        }
+        self.entered(pages);
+        true
+    }
+
+    /// 关闭所有子页面并切换到指定的标签页
+    ///
+    /// # 参数
+    /// * `index` - 标签页下标，超出范围时回绕
+    pub fn show_tab<D, P>(&mut self, pages: &mut P, index: usize)
+    where
+        D: DrawTarget<Color = Rgb565>,
+        P: Pages<Id, D>,
+    {
+        let index = index % self.tabs.len();
+        if self.stack.is_empty() && index == self.tab {
+            return;
+        }
+        self.stack.clear();
+        self.tab = index;
+        self.entered(pages);
+    }
+
+    /// 把事件交给当前页面，页面未处理时按默认方式导航
+    pub fn handle<D, P>(&mut self, pages: &mut P, event: Event) -> Result<(), NavError>
+    where
+        D: DrawTarget<Color = Rgb565>,
+        P: Pages<Id, D>,
+    {
+        match pages.page(self.current()).on_event(event) {
+            Response::Handled => {}
+            Response::Redraw => self.full = true,
+            Response::Push(id) => self.push(pages, id)?,
+            Response::Pop => {
+                self.pop(pages);
+            }
+            Response::Ignored => match event {
+                Event::Back | Event::SwipeRight if !self.stack.is_empty() => {
+                    self.pop(pages);
+                }
+                Event::Back => self.show_tab(pages, 0),
+                Event::SwipeLeft => self.show_tab(pages, self.tab + 1),
+                Event::SwipeRight => self.show_tab(pages, self.tab + self.tabs.len() - 1),
+                Event::Up | Event::Down | Event::Select => {}
+            },
+        }
+        Ok(())
+    }
+
+    /// 下一次绘制时完整重绘，用于屏幕被其他内容覆盖之后
+    pub fn invalidate(&mut self) {
+        self.full = true;
+    }
+
+    /// 绘制当前页面，切换页面或调用 [Navigator::invalidate] 后为完整重绘
+    pub fn render<D, P>(&mut self, pages: &mut P, target: &mut D) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Color = Rgb565>,
+        P: Pages<Id, D>,
+    {
+        let full = self.full;
+        pages.page(self.current()).render(target, full)?;
+        self.full = false;
+        Ok(())
+    }
+
+    fn entered<D, P>(&mut self, pages: &mut P)
+    where
+        D: DrawTarget<Color = Rgb565>,
+        P: Pages<Id, D>,
+    {
+        pages.page(self.current()).on_enter();
+        self.full = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::convert::Infallible;
+    use drivers::sim::{self, SimDisplay};
+    use embedded_graphics::primitives::Rectangle;
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    enum Page {
+        Home,
+        Settings,
+        Files,
+        Detail,
+    }
+
+    const TABS: [Page; 3] = [Page::Home, Page::Settings, Page::Files];
+
+    /// 记录调用次数的页面，完整重绘时用自己的颜色填充左上角
+    #[derive(Default)]
+    struct TestPage {
+        color: Rgb565,
+        entered: usize,
+        full_renders: usize,
+        partial_renders: usize,
+        /// 下一次事件的处理结果
+        response: Option<Response<Page>>,
+    }
+
+    impl Screen<Page, SimDisplay> for TestPage {
+        fn on_enter(&mut self) {
+            self.entered += 1;
+        }
+
+        fn on_event(&mut self, _event: Event) -> Response<Page> {
+            self.response.take().unwrap_or(Response::Ignored)
+        }
+
+        fn render(&mut self, target: &mut SimDisplay, full: bool) -> Result<(), Infallible> {
+            if full {
+                self.full_renders += 1;
+                let area = Rectangle::new(Point::zero(), Size::new(4, 4));
+                target.fill_solid(&area, self.color)?;
+            } else {
+                self.partial_renders += 1;
+            }
+            Ok(())
+        }
+    }
+
+    struct TestPages([TestPage; 4]);
+
+    impl TestPages {
+        fn new() -> Self {
+            let colors = [Rgb565::RED, Rgb565::GREEN, Rgb565::BLUE, Rgb565::YELLOW];
+            TestPages(colors.map(|color| TestPage {
+                color,
+                ..TestPage::default()
+            }))
+        }
+    }
+
+    impl Pages<Page, SimDisplay> for TestPages {
+        fn page(&mut self, id: Page) -> &mut dyn Screen<Page, SimDisplay> {
+            &mut self.0[id as usize]
+        }
+    }
+
+    fn setup() -> (Navigator<Page, 2>, TestPages) {
+        let mut nav = Navigator::new(&TABS);
+        let mut pages = TestPages::new();
+        nav.start(&mut pages);
+        (nav, pages)
+    }
+
+    #[test]
+    fn swipes_cycle_through_tabs() {
+        let (mut nav, mut pages) = setup();
+        assert_eq!(nav.current(), Page::Home);
+        assert_eq!(pages.0[0].entered, 1);
+
+        nav.handle(&mut pages, Event::SwipeLeft).unwrap();
+        assert_eq!(nav.current(), Page::Settings);
+        nav.handle(&mut pages, Event::SwipeRight).unwrap();
+        nav.handle(&mut pages, Event::SwipeRight).unwrap();
+        assert_eq!(nav.current(), Page::Files);
+        nav.handle(&mut pages, Event::SwipeLeft).unwrap();
+        assert_eq!(nav.current(), Page::Home);
+        assert_eq!(pages.0[0].entered, 3);
+        assert_eq!(pages.0[2].entered, 1);
+
+        // 标签页上按返回回到第一个标签页，已在第一个时不重新进入
+        nav.show_tab(&mut pages, 2);
+        nav.handle(&mut pages, Event::Back).unwrap();
+        assert_eq!(nav.current(), Page::Home);
+        nav.handle(&mut pages, Event::Back).unwrap();
+        assert_eq!(pages.0[0].entered, 4);
+    }
+
+    #[test]
+    fn responses_push_and_pop_pages() {
+        let (mut nav, mut pages) = setup();
+        pages.0[0].response = Some(Response::Push(Page::Detail));
+        nav.handle(&mut pages, Event::Select).unwrap();
+        assert_eq!(nav.current(), Page::Detail);
+        assert_eq!(nav.depth(), 1);
+
+        // 子页面上的滑动关闭子页面，而不是切换标签页
+        nav.handle(&mut pages, Event::SwipeRight).unwrap();
+        assert_eq!(nav.current(), Page::Home);
+        assert_eq!(pages.0[0].entered, 2);
+
+        nav.handle(&mut pages, Event::SwipeLeft).unwrap();
+        pages.0[1].response = Some(Response::Push(Page::Detail));
+        nav.handle(&mut pages, Event::Select).unwrap();
+        pages.0[3].response = Some(Response::Pop);
+        nav.handle(&mut pages, Event::Select).unwrap();
+        assert_eq!(nav.current(), Page::Settings);
+        assert!(!nav.pop(&mut pages));
+    }
+
+    #[test]
+    fn push_beyond_depth_fails() {
+        let (mut nav, mut pages) = setup();
+        nav.push(&mut pages, Page::Detail).unwrap();
+        nav.push(&mut pages, Page::Files).unwrap();
+        assert_eq!(nav.push(&mut pages, Page::Detail), Err(NavError::StackFull));
+        assert_eq!(nav.current(), Page::Files);
+        assert_eq!(nav.depth(), 2);
+
+        // 切换标签页时关闭所有子页面
+        nav.show_tab(&mut pages, 1);
+        assert_eq!(nav.depth(), 0);
+        assert_eq!(nav.current(), Page::Settings);
+    }
+
+    #[test]
+    fn render_is_full_only_after_changes() {
+        let (mut nav, mut pages) = setup();
+        let (mut lcd, panel) = sim::display();
+        nav.render(&mut pages, &mut lcd).unwrap();
+        nav.render(&mut pages, &mut lcd).unwrap();
+        assert_eq!(pages.0[0].full_renders, 1);
+        assert_eq!(pages.0[0].partial_renders, 1);
+        assert_eq!(panel.borrow().pixel(0, 0), Rgb565::RED);
+
+        pages.0[0].response = Some(Response::Handled);
+        nav.handle(&mut pages, Event::Down).unwrap();
+        nav.render(&mut pages, &mut lcd).unwrap();
+        assert_eq!(pages.0[0].full_renders, 1);
+
+        pages.0[0].response = Some(Response::Redraw);
+        nav.handle(&mut pages, Event::Down).unwrap();
+        nav.render(&mut pages, &mut lcd).unwrap();
+        assert_eq!(pages.0[0].full_renders, 2);
+
+        nav.invalidate();
+        nav.handle(&mut pages, Event::SwipeLeft).unwrap();
+        nav.render(&mut pages, &mut lcd).unwrap();
+        assert_eq!(pages.0[1].full_renders, 1);
+        assert_eq!(panel.borrow().pixel(0, 0), Rgb565::GREEN);
+    }
+}
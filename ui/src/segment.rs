@@ -0,0 +1,301 @@
+//! 七段数码管样式的大号数字
+//!
+//! 内置字体最大只有 10x20，倒计时、秒表、时钟等需要远处可读的数字时，
+//! 用矩形拼出七段数码管样式的字符。支持 `0`-`9`、`-`、空格，以及窄字符 `:` 和 `.`。
+//!
+//! [SegmentDisplay] 记住上一次显示的内容，只重绘变化的字符，
+//! 每个字符的熄灭段用背景色填充，不需要先清屏，刷新时不闪烁。
+
+use drivers::st7789;
+use embedded_graphics::pixelcolor::Rgb565;
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::Rectangle;
+use heapless::String;
+
+/// 一次最多显示的字符数
+const MAX_CHARS: usize = 16;
+
+/// 各段在位图中的位置：a 上、b 右上、c 右下、d 下、e 左下、f 左上、g 中
+const SEG_A: u8 = 0x01;
+const SEG_B: u8 = 0x02;
+const SEG_C: u8 = 0x04;
+const SEG_D: u8 = 0x08;
+const SEG_E: u8 = 0x10;
+const SEG_F: u8 = 0x20;
+const SEG_G: u8 = 0x40;
+
+/// 字符对应的段位图，不支持的字符显示为空白
+const fn segments(c: char) -> u8 {
+    match c {
+        '0' => SEG_A | SEG_B | SEG_C | SEG_D | SEG_E | SEG_F,
+        '1' => SEG_B | SEG_C,
+        '2' => SEG_A | SEG_B | SEG_D | SEG_E | SEG_G,
+        '3' => SEG_A | SEG_B | SEG_C | SEG_D | SEG_G,
+        '4' => SEG_B | SEG_C | SEG_F | SEG_G,
+        '5' => SEG_A | SEG_C | SEG_D | SEG_F | SEG_G,
+        '6' => SEG_A | SEG_C | SEG_D | SEG_E | SEG_F | SEG_G,
+        '7' => SEG_A | SEG_B | SEG_C,
+        '8' => SEG_A | SEG_B | SEG_C | SEG_D | SEG_E | SEG_F | SEG_G,
+        '9' => SEG_A | SEG_B | SEG_C | SEG_D | SEG_F | SEG_G,
+        '-' => SEG_G,
+        _ => 0,
+    }
+}
+
+/// 是否为窄字符（`:` 和 `.`）
+const fn is_narrow(c: char) -> bool {
+    matches!(c, ':' | '.')
+}
+
+/// 大号数字显示区域
+pub struct SegmentDisplay {
+    origin: Point,
+    width: u32,
+    height: u32,
+    /// 笔画粗细
+    thickness: u32,
+    color: Rgb565,
+    background: Rgb565,
+    /// 上一次显示的内容，None 表示需要完整重绘
+    shown: Option<String<MAX_CHARS>>,
+}
+
+impl SegmentDisplay {
+    /// 创建显示区域
+    ///
+    /// # 参数
+    /// * `origin` - 左上角坐标
+    /// * `width`, `height` - 单个数字的宽度和高度，笔画粗细为宽度的 1/5
+    /// * `color` - 数字颜色
+    pub fn new(origin: Point, width: u32, height: u32, color: Rgb565) -> Self {
+        SegmentDisplay {
+            origin,
+            width,
+            height,
+            thickness: (width / 5).max(1),
+            color,
+            background: Rgb565::BLACK,
+            shown: None,
+        }
+    }
+
+    /// 水平移动显示区域，使 `text` 在屏幕上居中
+    pub fn centered(mut self, text: &str) -> Self {
+        self.origin.x = (st7789::WIDTH as i32 - self.text_width(text) as i32).max(0) / 2;
+        self
+    }
+
+    /// 设置背景色（默认为黑色），熄灭的段和字符间隙用背景色填充
+    pub fn with_background(mut self, background: Rgb565) -> Self {
+        self.background = background;
+        self
+    }
+
+    /// 数字之间的间距
+    fn gap(&self) -> u32 {
+        self.thickness + self.thickness / 2
+    }
+
+    fn advance(&self, c: char) -> u32 {
+        let glyph = if is_narrow(c) { self.thickness } else { self.width };
+        glyph + self.gap()
+    }
+
+    /// 显示 `text` 所需的宽度
+    pub fn text_width(&self, text: &str) -> u32 {
+        let total: u32 = text.chars().map(|c| self.advance(c)).sum();
+        total.saturating_sub(self.gap())
+    }
+
+    /// 修改数字颜色，下次显示时完整重绘
+    pub fn set_color(&mut self, color: Rgb565) {
+        if self.color != color {
+            self.color = color;
+            self.shown = None;
+        }
+    }
+
+    /// 下次显示时完整重绘（例如屏幕被其他内容覆盖后）
+    pub fn invalidate(&mut self) {
+        self.shown = None;
+    }
+
+    /// 显示文本，只重绘与上一次不同的字符
+    ///
+    /// # 参数
+    /// * `lcd` - LCD 或其他绘制目标
+    /// * `text` - 显示内容，超过 16 个字符的部分被忽略
+    pub fn show<D>(&mut self, lcd: &mut D, text: &str) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Color = Rgb565>,
+    {
+        let text = text.get(..MAX_CHARS.min(text.len())).unwrap_or(text);
+
+        // 字符数或窄字符位置变化时布局改变，清除原有区域后完整重绘
+        let same_layout = self.shown.as_ref().is_some_and(|shown| {
+            shown.len() == text.len()
+                && shown.chars().zip(text.chars()).all(|(a, b)| is_narrow(a) == is_narrow(b))
+        });
+        if !same_layout && let Some(shown) = self.shown.take() {
+            let width = self.text_width(&shown);
+            self.fill(lcd, 0, 0, width, self.height, self.background)?;
+        }
+
+        let mut x = 0;
+        for (i, c) in text.chars().enumerate() {
+            let unchanged = same_layout
+                && self.shown.as_ref().and_then(|shown| shown.chars().nth(i)) == Some(c);
+            if !unchanged {
+                self.draw_char(lcd, x, c)?;
+            }
+            x += self.advance(c);
+        }
+
+        self.shown = String::try_from(text).ok();
+        Ok(())
+    }
+
+    /// 在相对横坐标 `x` 处绘制一个字符
+    fn draw_char<D>(&self, lcd: &mut D, x: u32, c: char) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Color = Rgb565>,
+    {
+        let (w, h, t) = (self.width, self.height, self.thickness);
+        let on = |lit: bool| if lit { self.color } else { self.background };
+
+        if is_narrow(c) {
+            self.fill(lcd, x, 0, t, h, self.background)?;
+            if c == ':' {
+                self.fill(lcd, x, h / 3 - t / 2, t, t, self.color)?;
+                self.fill(lcd, x, h * 2 / 3 - t / 2, t, t, self.color)?;
+            } else {
+                self.fill(lcd, x, h - t, t, t, self.color)?;
+            }
+            return Ok(());
+        }
+
+        // 竖段长度：总高度减去三条横段后平分
+        let half = h.saturating_sub(3 * t) / 2;
+        let bits = segments(c);
+        let lit = |seg: u8| on(bits & seg != 0);
+
+        // 四个角和竖段之间的空隙保持背景色
+        self.fill(lcd, x, 0, t, t, self.background)?;
+        self.fill(lcd, x + w - t, 0, t, t, self.background)?;
+        self.fill(lcd, x, t + half, t, t, self.background)?;
+        self.fill(lcd, x + w - t, t + half, t, t, self.background)?;
+        self.fill(lcd, x, 2 * (t + half), t, t, self.background)?;
+        self.fill(lcd, x + w - t, 2 * (t + half), t, t, self.background)?;
+
+        self.fill(lcd, x + t, 0, w - 2 * t, t, lit(SEG_A))?;
+        self.fill(lcd, x + w - t, t, t, half, lit(SEG_B))?;
+        self.fill(lcd, x + w - t, 2 * t + half, t, half, lit(SEG_C))?;
+        self.fill(lcd, x + t, 2 * (t + half), w - 2 * t, t, lit(SEG_D))?;
+        self.fill(lcd, x, 2 * t + half, t, half, lit(SEG_E))?;
+        self.fill(lcd, x, t, t, half, lit(SEG_F))?;
+        self.fill(lcd, x + t, t + half, w - 2 * t, t, lit(SEG_G))?;
+        // 横段和竖段围出的内部区域
+        self.fill(lcd, x + t, t, w - 2 * t, half, self.background)?;
+        self.fill(lcd, x + t, 2 * t + half, w - 2 * t, half, self.background)?;
+
+        // 与下一个字符之间的间隙
+        self.fill(lcd, x + w, 0, self.gap(), h, self.background)
+    }
+
+    /// 填充相对于显示区域左上角的矩形
+    fn fill<D>(
+        &self,
+        lcd: &mut D,
+        x: u32,
+        y: u32,
+        w: u32,
+        h: u32,
+        color: Rgb565,
+    ) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Color = Rgb565>,
+    {
+        let top_left = self.origin + Point::new(x as i32, y as i32);
+        lcd.fill_solid(&Rectangle::new(top_left, Size::new(w, h)), color)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use drivers::sim::{self, PanelRef};
+
+    /// 40x70 的数字：笔画 8，间距 12，竖段长 23
+    fn digits() -> SegmentDisplay {
+        SegmentDisplay::new(Point::new(10, 20), 40, 70, Rgb565::GREEN)
+    }
+
+    /// 读取相对于显示区域左上角的像素
+    fn pixel(panel: &PanelRef, x: u16, y: u16) -> Rgb565 {
+        panel.borrow().pixel(10 + x, 20 + y)
+    }
+
+    #[test]
+    fn eight_lights_every_segment() {
+        let (mut lcd, panel) = sim::display();
+        digits().show(&mut lcd, "8").unwrap();
+        // a、b、c、d、e、f 各段中点
+        let centers = [(20, 4), (36, 19), (36, 50), (20, 66), (4, 50), (4, 19)];
+        for (x, y) in centers {
+            assert_eq!(pixel(&panel, x, y), Rgb565::GREEN, "({x}, {y})");
+        }
+        // g 段
+        assert_eq!(pixel(&panel, 20, 35), Rgb565::GREEN);
+        // 段围出的内部区域
+        assert_eq!(pixel(&panel, 20, 19), Rgb565::BLACK);
+    }
+
+    #[test]
+    fn one_leaves_left_segments_dark() {
+        let (mut lcd, panel) = sim::display();
+        digits().show(&mut lcd, "1").unwrap();
+        assert_eq!(pixel(&panel, 36, 19), Rgb565::GREEN);
+        assert_eq!(pixel(&panel, 36, 50), Rgb565::GREEN);
+        assert_eq!(pixel(&panel, 20, 4), Rgb565::BLACK);
+        assert_eq!(pixel(&panel, 4, 19), Rgb565::BLACK);
+    }
+
+    #[test]
+    fn only_changed_characters_are_redrawn() {
+        let (mut lcd, panel) = sim::display();
+        let mut display = digits();
+        display.show(&mut lcd, "12").unwrap();
+        // 在第一个数字的熄灭段上做标记，没有重绘时标记保留
+        Pixel(Point::new(10 + 4, 20 + 19), Rgb565::RED).draw(&mut lcd).unwrap();
+        display.show(&mut lcd, "13").unwrap();
+        assert_eq!(pixel(&panel, 4, 19), Rgb565::RED);
+        // 第二个数字从 2 变成 3，左下段熄灭、右下段点亮
+        assert_eq!(pixel(&panel, 52 + 4, 50), Rgb565::BLACK);
+        assert_eq!(pixel(&panel, 52 + 36, 50), Rgb565::GREEN);
+
+        display.invalidate();
+        display.show(&mut lcd, "13").unwrap();
+        assert_eq!(pixel(&panel, 4, 19), Rgb565::BLACK);
+    }
+
+    #[test]
+    fn shorter_text_clears_previous_area() {
+        let (mut lcd, panel) = sim::display();
+        let mut display = digits();
+        display.show(&mut lcd, "12:34").unwrap();
+        display.show(&mut lcd, "5").unwrap();
+
+        // 与直接在空白屏幕上显示 "5" 的画面相同
+        let (mut fresh_lcd, fresh) = sim::display();
+        digits().show(&mut fresh_lcd, "5").unwrap();
+        assert_eq!(panel.borrow().checksum(), fresh.borrow().checksum());
+    }
+
+    #[test]
+    fn centered_uses_screen_width() {
+        let display = digits().centered("00:00");
+        let width = display.text_width("00:00");
+        assert_eq!(display.origin.x, (st7789::WIDTH as i32 - width as i32) / 2);
+        assert_eq!(display.origin.y, 20);
+    }
+}
@@ -0,0 +1,276 @@
+//! 分条渲染
+//!
+//! 没有 PSRAM 时内部 SRAM 放不下整帧帧缓冲区（320x240 RGB565 需 150KB）。[render] 把屏幕
+//! 分成若干水平条带，每次只在一小块缓冲区中绘制一条（例如 16 行，10KB），绘制完成后交给
+//! 调用方整块发送到 LCD（固件中用驱动的 `blit`，经 SPI DMA 传输），再绘制下一条。
+//!
+//! 画面由调用方的绘制函数给出，对每一条都完整执行一遍，落在条带以外的像素直接丢弃。
+//! 多层叠加的画面（背景、面板、文字）先在缓冲区里合成，每个像素只写入 LCD 一次，
+//! 重绘时不会看到底层闪过。代价是绘制函数要执行「屏幕高度 / 条带行数」次，
+//! 适合由图元和文字组成的画面。
+//!
+//! ```
+//! use core::convert::Infallible;
+//! use embedded_graphics::pixelcolor::Rgb565;
+//! use embedded_graphics::prelude::*;
+//! use embedded_graphics::primitives::{PrimitiveStyle, Rectangle};
+//! use ui::strip;
+//!
+//! let mut buffer = [0u8; 320 * 16 * 2];
+//! let mut strips = 0;
+//! strip::render(
+//!     &mut buffer,
+//!     Size::new(320, 240),
+//!     |target| {
+//!         target.clear(Rgb565::BLUE)?;
+//!         Rectangle::new(Point::new(20, 20), Size::new(100, 50))
+//!             .into_styled(PrimitiveStyle::with_fill(Rgb565::WHITE))
+//!             .draw(target)
+//!     },
+//!     |_area, _pixels| {
+//!         strips += 1;
+//!         Ok::<(), Infallible>(())
+//!     },
+//! )
+//! .unwrap();
+//! assert_eq!(strips, 15);
+//! ```
+
+use core::convert::Infallible;
+use embedded_graphics::pixelcolor::Rgb565;
+use embedded_graphics::pixelcolor::raw::RawU16;
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::Rectangle;
+
+/// 一条条带的绘制目标
+///
+/// 坐标与整个屏幕一致，[OriginDimensions::size] 返回整个屏幕的大小，
+/// 画面代码不需要知道当前绘制的是哪一条
+pub struct Strip<'a> {
+    /// 按行排列的像素，每像素 2 字节（大端）
+    buf: &'a mut [u8],
+    screen: Size,
+    /// 当前条带在屏幕上的区域
+    area: Rectangle,
+}
+
+impl Strip<'_> {
+    /// 当前条带在屏幕上的区域
+    pub fn area(&self) -> Rectangle {
+        self.area
+    }
+
+    /// 写入一个像素，调用方保证坐标在条带内
+    fn set(&mut self, point: Point, color: Rgb565) {
+        let x = point.x as usize;
+        let y = (point.y - self.area.top_left.y) as usize;
+        let index = (y * self.screen.width as usize + x) * 2;
+        let raw = RawU16::from(color).into_inner().to_be_bytes();
+        self.buf[index..index + 2].copy_from_slice(&raw);
+    }
+}
+
+impl OriginDimensions for Strip<'_> {
+    fn size(&self) -> Size {
+        self.screen
+    }
+}
+
+impl DrawTarget for Strip<'_> {
+    type Color = Rgb565;
+    type Error = Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for Pixel(point, color) in pixels {
+            if self.area.contains(point) {
+                self.set(point, color);
+            }
+        }
+        Ok(())
+    }
+
+    fn fill_solid(&mut self, area: &Rectangle, color: Self::Color) -> Result<(), Self::Error> {
+        let area = area.intersection(&self.area);
+        let Some(bottom_right) = area.bottom_right() else {
+            return Ok(());
+        };
+        let raw = RawU16::from(color).into_inner().to_be_bytes();
+        let width = self.screen.width as usize;
+        let (left, right) = (area.top_left.x as usize, bottom_right.x as usize);
+        for y in area.rows() {
+            let row = (y - self.area.top_left.y) as usize * width;
+            for pixel in self.buf[(row + left) * 2..(row + right + 1) * 2].chunks_exact_mut(2) {
+                pixel.copy_from_slice(&raw);
+            }
+        }
+        Ok(())
+    }
+
+    fn clear(&mut self, color: Self::Color) -> Result<(), Self::Error> {
+        let area = self.area;
+        self.fill_solid(&area, color)
+    }
+}
+
+/// 按条带绘制整个屏幕
+///
+/// 条带的行数由缓冲区大小决定，最后一条可能不足；缓冲区放不下一行时不绘制
+///
+/// # 参数
+/// * `buf` - 条带缓冲区，大小为 `屏幕宽度 * 行数 * 2` 字节
+/// * `screen` - 屏幕大小
+/// * `scene` - 画面的绘制函数，每一条调用一次，调用前条带已清为黑色
+/// * `flush` - 发送绘制完成的一条，参数为条带在屏幕上的区域和按行排列的像素（RGB565，大端）
+///
+/// # 返回
+/// `flush` 返回的第一个错误，出错后不再绘制剩余的条带
+pub fn render<S, F, E>(buf: &mut [u8], screen: Size, mut scene: S, mut flush: F) -> Result<(), E>
+where
+    S: FnMut(&mut Strip<'_>) -> Result<(), Infallible>,
+    F: FnMut(&Rectangle, &[u8]) -> Result<(), E>,
+{
+    let row_bytes = screen.width as usize * 2;
+    let rows = (buf.len() / row_bytes.max(1)).min(screen.height as usize) as u32;
+    if rows == 0 || screen.width == 0 {
+        return Ok(());
+    }
+    for top in (0..screen.height).step_by(rows as usize) {
+        let size = Size::new(screen.width, rows.min(screen.height - top));
+        let area = Rectangle::new(Point::new(0, top as i32), size);
+        let len = row_bytes * size.height as usize;
+        // 清为黑色，画面没有覆盖的像素不会留下上一条的内容
+        buf[..len].fill(0);
+        let mut strip = Strip {
+            buf: &mut buf[..len],
+            screen,
+            area,
+        };
+        // 绘制到缓冲区不会失败
+        scene(&mut strip).ok();
+        flush(&area, &buf[..len])?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use drivers::sim;
+    use embedded_graphics::mono_font::MonoTextStyle;
+    use embedded_graphics::mono_font::ascii::FONT_10X20;
+    use embedded_graphics::primitives::{Circle, PrimitiveStyle, RoundedRectangle};
+    use embedded_graphics::text::Text;
+
+    const SCREEN: Size = Size::new(320, 240);
+
+    /// 渐变背景上叠加圆角面板、圆和跨越条带边界的文字
+    fn scene<D>(target: &mut D) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Color = Rgb565>,
+    {
+        for y in 0..SCREEN.height as i32 {
+            let band = Rectangle::new(Point::new(0, y), Size::new(SCREEN.width, 1));
+            target.fill_solid(&band, Rgb565::new(0, (y / 4) as u8, 31 - (y / 8) as u8))?;
+        }
+        RoundedRectangle::with_equal_corners(
+            Rectangle::new(Point::new(30, 40), Size::new(200, 120)),
+            Size::new(12, 12),
+        )
+        .into_styled(PrimitiveStyle::with_fill(Rgb565::CSS_DARK_SLATE_GRAY))
+        .draw(target)?;
+        Circle::new(Point::new(250, 150), 60)
+            .into_styled(PrimitiveStyle::with_stroke(Rgb565::YELLOW, 3))
+            .draw(target)?;
+        let style = MonoTextStyle::new(&FONT_10X20, Rgb565::WHITE);
+        Text::new("Strips 16px", Point::new(40, 63), style).draw(target)?;
+        Ok(())
+    }
+
+    #[test]
+    fn strips_match_direct_drawing() {
+        let (mut direct, expected) = sim::display();
+        scene(&mut direct).unwrap();
+
+        // 7 行一条，240 行除不尽，最后一条只有 2 行
+        for rows in [16, 7] {
+            let (mut lcd, panel) = sim::display();
+            let mut buffer = vec![0u8; SCREEN.width as usize * rows * 2];
+            let mut flushed = 0;
+            render(
+                &mut buffer,
+                SCREEN,
+                |strip| scene(strip),
+                |area, pixels| {
+                    flushed += area.size.height;
+                    let (x, y) = (area.top_left.x as u16, area.top_left.y as u16);
+                    let (w, h) = (area.size.width as u16, area.size.height as u16);
+                    lcd.blit(x, y, w, h, pixels)
+                },
+            )
+            .unwrap();
+            assert_eq!(flushed, SCREEN.height);
+            assert_eq!(panel.borrow().checksum(), expected.borrow().checksum());
+        }
+    }
+
+    #[test]
+    fn strip_clips_to_its_rows() {
+        let mut buffer = [0u8; 8 * 2 * 2];
+        let mut strips = Vec::new();
+        render(
+            &mut buffer,
+            Size::new(8, 6),
+            |strip| {
+                assert_eq!(strip.size(), Size::new(8, 6));
+                Rectangle::new(Point::new(2, 1), Size::new(3, 3))
+                    .into_styled(PrimitiveStyle::with_fill(Rgb565::WHITE))
+                    .draw(strip)?;
+                Pixel(Point::new(-1, 0), Rgb565::RED).draw(strip)
+            },
+            |area, pixels| {
+                let lit = pixels.chunks(2).filter(|p| p == &[0xFF, 0xFF]).count();
+                strips.push((area.top_left.y, area.size.height, lit));
+                Ok::<(), Infallible>(())
+            },
+        )
+        .unwrap();
+        assert_eq!(strips, [(0, 2, 3), (2, 2, 6), (4, 2, 0)]);
+    }
+
+    #[test]
+    fn flush_error_stops_rendering() {
+        let mut buffer = [0u8; 320 * 2 * 4];
+        let mut calls = 0;
+        let result = render(
+            &mut buffer,
+            SCREEN,
+            |strip| strip.clear(Rgb565::BLACK),
+            |_, _| {
+                calls += 1;
+                if calls == 3 { Err("bus") } else { Ok(()) }
+            },
+        );
+        assert_eq!(result, Err("bus"));
+        assert_eq!(calls, 3);
+    }
+
+    #[test]
+    fn small_buffer_draws_nothing() {
+        let mut buffer = [0u8; 100];
+        let mut drawn = false;
+        render(
+            &mut buffer,
+            SCREEN,
+            |_| {
+                drawn = true;
+                Ok(())
+            },
+            |_, _| Ok::<(), Infallible>(()),
+        )
+        .unwrap();
+        assert!(!drawn);
+    }
+}
@@ -0,0 +1,386 @@
+//! 文本排版
+//!
+//! 等宽字体下文本的宽度只取决于字符数，[TextBox] 据此在一个矩形区域内排版：
+//! 按单词边界换行、左/中/右对齐，放不下的部分截断并以 [ELLIPSIS] 结尾，
+//! 长的 WiFi 名称或提示信息不会画出区域或被写进相邻的内容。
+//!
+//! 长度按 Unicode 字符（`char`）计算，不会在多字节字符中间截断；
+//! 空白处和中日韩文字之后都可以换行，没有空格的中文也能折行。
+//! 字体中没有的字符由 embedded-graphics 画成替代字形，仍占一个字符宽度。
+//!
+//! ```
+//! use embedded_graphics::mono_font::MonoTextStyle;
+//! use embedded_graphics::mono_font::ascii::FONT_6X10;
+//! use embedded_graphics::pixelcolor::Rgb565;
+//! use embedded_graphics::prelude::*;
+//! use embedded_graphics::primitives::Rectangle;
+//! use ui::text::{self, Align, TextBox};
+//!
+//! let lines: Vec<&str> = text::wrap("connect to office wifi", 10).collect();
+//! assert_eq!(lines, ["connect to", "office", "wifi"]);
+//! assert_eq!(text::fit("HomeNetwork-5G", 10, false), ("HomeNet", true));
+//!
+//! let (mut lcd, _panel) = drivers::sim::display();
+//! let area = Rectangle::new(Point::new(0, 0), Size::new(60, 20));
+//! let style = MonoTextStyle::new(&FONT_6X10, Rgb565::WHITE);
+//! let label = TextBox::new(area, style, Align::Center);
+//! assert_eq!(label.draw(&mut lcd, "connect to office wifi").unwrap(), 2);
+//! ```
+
+use embedded_graphics::mono_font::{MonoFont, MonoTextStyle};
+use embedded_graphics::pixelcolor::Rgb565;
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::Rectangle;
+use embedded_graphics::text::{Baseline, Text};
+
+/// 截断时追加的省略号，ASCII 字体中没有「…」，用三个点代替
+pub const ELLIPSIS: &str = "...";
+
+/// 水平对齐方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Align {
+    Left,
+    Center,
+    Right,
+}
+
+/// 文本的宽度（像素）
+pub fn width(text: &str, font: &MonoFont<'_>) -> u32 {
+    let count = text.chars().count() as u32;
+    let advance = font.character_size.width + font.character_spacing;
+    (count * advance).saturating_sub(font.character_spacing)
+}
+
+/// 宽度为 `width` 像素的区域一行能放下的字符数
+pub fn columns(width: u32, font: &MonoFont<'_>) -> usize {
+    let advance = font.character_size.width + font.character_spacing;
+    ((width + font.character_spacing) / advance.max(1)) as usize
+}
+
+/// 把一行文本截断到 `columns` 个字符以内
+///
+/// 需要截断时去掉末尾的空白，留出 [ELLIPSIS] 的位置；宽度连省略号都放不下时直接截断
+///
+/// # 参数
+/// * `text` - 一行文本
+/// * `columns` - 最多的字符数，包括省略号
+/// * `more` - 后面还有没显示的内容（例如换行后放不下的行），即使本行放得下也要加省略号
+///
+/// # 返回
+/// 要显示的部分，以及是否需要在后面加省略号
+pub fn fit(text: &str, columns: usize, more: bool) -> (&str, bool) {
+    let count = text.chars().count();
+    if count <= columns && !more {
+        return (text, false);
+    }
+    let dots = ELLIPSIS.len();
+    if columns <= dots {
+        return (prefix(text, columns), false);
+    }
+    (prefix(text, columns - dots).trim_end(), true)
+}
+
+/// 前 `count` 个字符
+fn prefix(text: &str, count: usize) -> &str {
+    match text.char_indices().nth(count) {
+        Some((end, _)) => &text[..end],
+        None => text,
+    }
+}
+
+/// 按 `columns` 个字符的行宽折行，见 [wrap]
+#[derive(Debug, Clone)]
+pub struct Wrap<'a> {
+    rest: Option<&'a str>,
+    columns: usize,
+}
+
+/// 按单词边界把文本折成不超过 `columns` 个字符的行
+///
+/// `\n` 强制换行；一个单词比整行还长时在行宽处断开。换行处的空白被去掉，
+/// 返回的每一行都不以空白结尾
+pub fn wrap(text: &str, columns: usize) -> Wrap<'_> {
+    Wrap {
+        rest: (!text.is_empty()).then_some(text),
+        columns: columns.max(1),
+    }
+}
+
+impl<'a> Iterator for Wrap<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<&'a str> {
+        let text = self.rest?;
+        let (line, after) = match text.find('\n') {
+            Some(end) => (&text[..end], Some(&text[end + 1..])),
+            None => (text, None),
+        };
+        let line = line.trim_end();
+        match break_point(line, self.columns) {
+            None => {
+                self.rest = after.filter(|rest| !rest.is_empty());
+                Some(line)
+            }
+            Some(end) => {
+                // 断开处之后一定还有非空白字符，跳过空白不会越过 `\n`
+                let rest = text[end..].trim_start_matches(|c: char| c != '\n' && c.is_whitespace());
+                self.rest = Some(rest);
+                Some(line[..end].trim_end())
+            }
+        }
+    }
+}
+
+/// 一行放不下时的断开位置（字节下标），放得下时为 None
+fn break_point(line: &str, columns: usize) -> Option<usize> {
+    let mut last = None;
+    let mut word = false;
+    for (count, (index, c)) in line.char_indices().enumerate() {
+        if count == columns {
+            return Some(if c.is_whitespace() {
+                index
+            } else {
+                last.unwrap_or(index)
+            });
+        }
+        if c.is_whitespace() {
+            if word {
+                last = Some(index);
+            }
+        } else {
+            word = true;
+            if is_wide(c) {
+                last = Some(index + c.len_utf8());
+            }
+        }
+    }
+    None
+}
+
+/// 之后可以换行的中日韩文字和全角符号
+fn is_wide(c: char) -> bool {
+    matches!(c, '\u{2E80}'..='\u{9FFF}' | '\u{AC00}'..='\u{D7AF}' | '\u{FF00}'..='\u{FFEF}')
+}
+
+/// 矩形区域内的文本
+///
+/// 行高为字体的字符高度，从区域顶端开始排列；区域的宽高决定每行的字符数和最多的行数。
+/// 样式带背景色时，文字以外的部分也用背景色填满，重绘较短的文本不会残留上一次的内容
+#[derive(Debug, Clone, Copy)]
+pub struct TextBox<'a> {
+    area: Rectangle,
+    style: MonoTextStyle<'a, Rgb565>,
+    align: Align,
+}
+
+impl<'a> TextBox<'a> {
+    /// 创建文本区域
+    ///
+    /// # 参数
+    /// * `area` - 文本所在的矩形
+    /// * `style` - 文字样式
+    /// * `align` - 每行的水平对齐方式
+    pub fn new(area: Rectangle, style: MonoTextStyle<'a, Rgb565>, align: Align) -> Self {
+        TextBox { area, style, align }
+    }
+
+    /// 每行的字符数
+    pub fn columns(&self) -> usize {
+        columns(self.area.size.width, self.style.font)
+    }
+
+    /// 最多的行数
+    pub fn rows(&self) -> usize {
+        (self.area.size.height / self.style.font.character_size.height.max(1)) as usize
+    }
+
+    /// 折行后绘制，放不下的行省略，最后一行以省略号结尾
+    ///
+    /// # 返回
+    /// 绘制的行数
+    pub fn draw<D>(&self, target: &mut D, text: &str) -> Result<usize, D::Error>
+    where
+        D: DrawTarget<Color = Rgb565>,
+    {
+        let rows = self.rows();
+        let mut lines = wrap(text, self.columns()).peekable();
+        let mut row = 0;
+        while row < rows {
+            let Some(line) = lines.next() else {
+                break;
+            };
+            let more = row + 1 == rows && lines.peek().is_some();
+            self.draw_row(target, row, line, more)?;
+            row += 1;
+        }
+        self.clear_rows(target, row)?;
+        Ok(row)
+    }
+
+    /// 只在第一行绘制，不折行，放不下时以省略号结尾
+    ///
+    /// # 返回
+    /// 是否被截断
+    pub fn draw_line<D>(&self, target: &mut D, text: &str) -> Result<bool, D::Error>
+    where
+        D: DrawTarget<Color = Rgb565>,
+    {
+        if self.rows() == 0 {
+            return Ok(!text.is_empty());
+        }
+        let line = text.split('\n').next().unwrap_or_default();
+        let more = !text[line.len()..].trim().is_empty();
+        let truncated = self.draw_row(target, 0, line, more)?;
+        self.clear_rows(target, 1)?;
+        Ok(truncated)
+    }
+
+    /// 绘制第 `row` 行，返回是否加了省略号
+    fn draw_row<D>(
+        &self,
+        target: &mut D,
+        row: usize,
+        line: &str,
+        more: bool,
+    ) -> Result<bool, D::Error>
+    where
+        D: DrawTarget<Color = Rgb565>,
+    {
+        let font = self.style.font;
+        let (shown, ellipsis) = fit(line, self.columns(), more);
+        let mut used = width(shown, font);
+        if ellipsis {
+            if !shown.is_empty() {
+                used += font.character_spacing;
+            }
+            used += width(ELLIPSIS, font);
+        }
+        let free = self.area.size.width.saturating_sub(used) as i32;
+        let offset = match self.align {
+            Align::Left => 0,
+            Align::Center => free / 2,
+            Align::Right => free,
+        };
+        let height = font.character_size.height;
+        let top = self.area.top_left + Point::new(0, (row as u32 * height) as i32);
+
+        if let Some(background) = self.style.background_color {
+            let left = Rectangle::new(top, Size::new(offset as u32, height));
+            let right = Rectangle::new(
+                top + Point::new(offset + used as i32, 0),
+                Size::new((free - offset) as u32, height),
+            );
+            target.fill_solid(&left, background)?;
+            target.fill_solid(&right, background)?;
+        }
+        let position = top + Point::new(offset, 0);
+        let next = Text::with_baseline(shown, position, self.style, Baseline::Top).draw(target)?;
+        if ellipsis {
+            Text::with_baseline(ELLIPSIS, next, self.style, Baseline::Top).draw(target)?;
+        }
+        Ok(ellipsis)
+    }
+
+    /// 用背景色填满第 `row` 行以下的部分，样式没有背景色时不处理
+    fn clear_rows<D>(&self, target: &mut D, row: usize) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Color = Rgb565>,
+    {
+        let Some(background) = self.style.background_color else {
+            return Ok(());
+        };
+        let used = (row as u32 * self.style.font.character_size.height).min(self.area.size.height);
+        let rest = Rectangle::new(
+            self.area.top_left + Point::new(0, used as i32),
+            Size::new(self.area.size.width, self.area.size.height - used),
+        );
+        target.fill_solid(&rest, background)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use drivers::sim;
+    use embedded_graphics::mono_font::MonoTextStyleBuilder;
+    use embedded_graphics::mono_font::ascii::FONT_10X20;
+
+    fn lines(text: &str, columns: usize) -> Vec<&str> {
+        wrap(text, columns).collect()
+    }
+
+    #[test]
+    fn wraps_at_word_boundaries() {
+        assert_eq!(lines("the quick brown fox", 9), ["the quick", "brown fox"]);
+        assert_eq!(lines("the quick  brown", 10), ["the quick", "brown"]);
+        // 比行宽还长的单词直接断开
+        assert_eq!(lines("abcdefghij kl", 4), ["abcd", "efgh", "ij", "kl"]);
+        // 强制换行，空行保留，末尾的换行和空白不产生空行
+        assert_eq!(lines("one\n\ntwo \n", 8), ["one", "", "two"]);
+        assert_eq!(lines("  indented text", 9), ["  indente", "d text"]);
+        assert!(lines("", 8).is_empty());
+    }
+
+    #[test]
+    fn wraps_unicode_by_characters() {
+        // 中文没有空格，每个字之后都可以换行，按字符而不是字节计数
+        assert_eq!(lines("无线网络已连接", 3), ["无线网", "络已连", "接"]);
+        assert_eq!(lines("WiFi 已连接到网络", 6), ["WiFi 已", "连接到网络"]);
+        assert_eq!(lines("Café au lait", 6), ["Café", "au", "lait"]);
+    }
+
+    #[test]
+    fn fit_adds_ellipsis() {
+        assert_eq!(fit("short", 8, false), ("short", false));
+        assert_eq!(fit("MyHomeNetwork", 8, false), ("MyHom", true));
+        // 截断处的空白不保留
+        assert_eq!(fit("My Home Network", 6, false), ("My", true));
+        assert_eq!(fit("Guest Wi", 8, false), ("Guest Wi", false));
+        assert_eq!(fit("Guest", 8, true), ("Guest", true));
+        assert_eq!(fit("网络名称很长很长", 6, false), ("网络名", true));
+        // 宽度放不下省略号时直接截断
+        assert_eq!(fit("abcdef", 3, false), ("abc", false));
+    }
+
+    #[test]
+    fn measures_monospace_text() {
+        assert_eq!(width("", &FONT_10X20), 0);
+        assert_eq!(width("abc", &FONT_10X20), 30);
+        assert_eq!(width("网络", &FONT_10X20), 20);
+        assert_eq!(columns(320, &FONT_10X20), 32);
+        assert_eq!(columns(39, &FONT_10X20), 3);
+    }
+
+    #[test]
+    fn text_box_aligns_and_truncates() {
+        let style = MonoTextStyleBuilder::new()
+            .font(&FONT_10X20)
+            .text_color(Rgb565::WHITE)
+            .background_color(Rgb565::BLUE)
+            .build();
+        let area = Rectangle::new(Point::new(10, 10), Size::new(100, 45));
+        let (mut lcd, panel) = sim::display();
+        lcd.fill_screen(Rgb565::BLACK).unwrap();
+
+        let right = TextBox::new(area, style, Align::Right);
+        assert_eq!(right.rows(), 2);
+        assert!(!right.draw_line(&mut lcd, "abc").unwrap());
+        {
+            let panel = panel.borrow();
+            // 右对齐：文字占最右边 30 像素，左边和下面的空行都填成背景色
+            assert_eq!(panel.pixel(10, 10), Rgb565::BLUE);
+            assert_eq!(panel.pixel(10, 54), Rgb565::BLUE);
+            assert_eq!(panel.pixel(9, 10), Rgb565::BLACK);
+            assert_eq!(panel.pixel(110, 10), Rgb565::BLACK);
+        }
+
+        let left = TextBox::new(area, style, Align::Left);
+        let text = "a long network name that does not fit";
+        assert!(left.draw_line(&mut lcd, text).unwrap());
+        assert_eq!(left.draw(&mut lcd, text).unwrap(), 2);
+        assert_eq!(left.draw(&mut lcd, "one\ntwo\nthree").unwrap(), 2);
+        assert_eq!(left.draw(&mut lcd, "").unwrap(), 0);
+        // 整个区域都是背景色
+        assert_eq!(panel.borrow().pixel(60, 20), Rgb565::BLUE);
+    }
+}
@@ -0,0 +1,62 @@
+//! 界面配色
+//!
+//! 组件和屏幕不直接使用固定颜色，而是从 [Theme] 取背景、文字和强调色，
+//! 切换配色时重绘即可。内置深色、浅色和高对比度三套配色，强调色可以单独替换。
+
+use embedded_graphics::pixelcolor::Rgb565;
+use embedded_graphics::prelude::*;
+
+/// 配色
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Theme {
+    /// 背景
+    pub background: Rgb565,
+    /// 正文
+    pub foreground: Rgb565,
+    /// 次要文字、边框
+    pub muted: Rgb565,
+    /// 标题和需要突出的数值
+    pub accent: Rgb565,
+    /// 选中项，选中项上的文字使用背景色
+    pub highlight: Rgb565,
+}
+
+/// 深色配色，默认使用
+pub const DARK: Theme = Theme {
+    background: Rgb565::BLACK,
+    foreground: Rgb565::WHITE,
+    muted: Rgb565::CSS_GRAY,
+    accent: Rgb565::CYAN,
+    highlight: Rgb565::YELLOW,
+};
+
+/// 浅色配色，适合明亮的环境
+pub const LIGHT: Theme = Theme {
+    background: Rgb565::WHITE,
+    foreground: Rgb565::BLACK,
+    muted: Rgb565::CSS_DIM_GRAY,
+    accent: Rgb565::CSS_TEAL,
+    highlight: Rgb565::CSS_DARK_ORANGE,
+};
+
+/// 高对比度配色：黑底白字，强调色和选中项都用黄色
+pub const HIGH_CONTRAST: Theme = Theme {
+    background: Rgb565::BLACK,
+    foreground: Rgb565::WHITE,
+    muted: Rgb565::WHITE,
+    accent: Rgb565::YELLOW,
+    highlight: Rgb565::YELLOW,
+};
+
+impl Theme {
+    /// 替换强调色
+    pub const fn with_accent(self, accent: Rgb565) -> Theme {
+        Theme { accent, ..self }
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        DARK
+    }
+}
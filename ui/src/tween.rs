@@ -0,0 +1,226 @@
+//! 补间动画
+//!
+//! [Tween] 在给定时长内把一个整数从起点过渡到终点（位置、宽度、颜色混合比例等），
+//! 过渡曲线由 [Easing] 决定。数值只由传入的当前时间计算，与刷新频率无关：
+//! 绘制一帧耗时过长时跳过的帧不会拖慢动画，只是画面更新得少一些。
+//!
+//! 时间用毫秒表示，由调用方提供（固件中为 `embassy_time::Instant::as_millis()`），
+//! 因此可以在主机上测试。进度用千分比（0-1000）表示，只使用整数运算。
+//!
+//! ```
+//! use ui::tween::{Easing, Tween};
+//!
+//! let mut slide = Tween::new(-24, 0, 200, Easing::EaseOutCubic);
+//! slide.start(1000);
+//! assert_eq!(slide.value(1000), -24);
+//! assert_eq!(slide.value(1200), 0);
+//! assert!(slide.is_finished(1200));
+//! ```
+
+use embedded_graphics::pixelcolor::Rgb565;
+use embedded_graphics::prelude::*;
+
+/// 进度的满值（千分比）
+pub const FULL: u32 = 1000;
+
+/// 过渡曲线
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Easing {
+    /// 匀速
+    Linear,
+    /// 由慢到快
+    EaseInQuad,
+    /// 由快到慢
+    EaseOutQuad,
+    /// 两端慢、中间快
+    EaseInOutQuad,
+    /// 由快到慢，减速比 [Easing::EaseOutQuad] 更明显，适合滑入
+    EaseOutCubic,
+}
+
+impl Easing {
+    /// 按曲线换算进度
+    ///
+    /// # 参数
+    /// * `t` - 时间进度（千分比），超过 [FULL] 时按 [FULL] 计算
+    ///
+    /// # 返回
+    /// 数值进度（千分比），两端分别为 0 和 [FULL]
+    pub fn apply(self, t: u32) -> u32 {
+        let t = t.min(FULL);
+        let inverse = FULL - t;
+        match self {
+            Easing::Linear => t,
+            Easing::EaseInQuad => t * t / FULL,
+            Easing::EaseOutQuad => FULL - inverse * inverse / FULL,
+            Easing::EaseInOutQuad if t < FULL / 2 => 2 * t * t / FULL,
+            Easing::EaseInOutQuad => FULL - 2 * inverse * inverse / FULL,
+            Easing::EaseOutCubic => FULL - inverse * inverse / FULL * inverse / FULL,
+        }
+    }
+}
+
+/// 一段补间动画
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tween {
+    from: i32,
+    to: i32,
+    duration_ms: u32,
+    easing: Easing,
+    /// 开始时间，None 表示尚未开始，停在起点
+    started_ms: Option<u64>,
+}
+
+impl Tween {
+    /// 创建动画，调用 [Tween::start] 后开始
+    ///
+    /// # 参数
+    /// * `from`, `to` - 起点和终点
+    /// * `duration_ms` - 时长（毫秒），0 表示开始后立即到达终点
+    /// * `easing` - 过渡曲线
+    pub const fn new(from: i32, to: i32, duration_ms: u32, easing: Easing) -> Self {
+        Tween {
+            from,
+            to,
+            duration_ms,
+            easing,
+            started_ms: None,
+        }
+    }
+
+    /// 停在 `value` 的动画，已经结束
+    pub const fn at(value: i32) -> Self {
+        Tween {
+            from: value,
+            to: value,
+            duration_ms: 0,
+            easing: Easing::Linear,
+            started_ms: Some(0),
+        }
+    }
+
+    /// 从 `now_ms` 开始播放
+    pub fn start(&mut self, now_ms: u64) {
+        self.started_ms = Some(now_ms);
+    }
+
+    /// 终点
+    pub fn target(&self) -> i32 {
+        self.to
+    }
+
+    /// 时间进度（千分比）
+    fn progress(&self, now_ms: u64) -> u32 {
+        match self.started_ms {
+            None => 0,
+            Some(_) if self.duration_ms == 0 => FULL,
+            Some(started) => {
+                let elapsed = now_ms.saturating_sub(started).min(self.duration_ms as u64);
+                (elapsed * FULL as u64 / self.duration_ms as u64) as u32
+            }
+        }
+    }
+
+    /// `now_ms` 时的数值
+    pub fn value(&self, now_ms: u64) -> i32 {
+        let eased = self.easing.apply(self.progress(now_ms)) as i64;
+        let span = self.to as i64 - self.from as i64;
+        (self.from as i64 + span * eased / FULL as i64) as i32
+    }
+
+    /// `now_ms` 时是否已到达终点
+    pub fn is_finished(&self, now_ms: u64) -> bool {
+        self.progress(now_ms) >= FULL
+    }
+
+    /// 从当前数值出发，改为过渡到新的终点，时长和曲线不变
+    ///
+    /// 用于进度条等终点不断变化的场合，不会出现跳变。
+    pub fn retarget(&mut self, to: i32, now_ms: u64) {
+        self.from = self.value(now_ms);
+        self.to = to;
+        self.started_ms = Some(now_ms);
+    }
+}
+
+/// 按比例混合两种颜色，用于淡入淡出
+///
+/// # 参数
+/// * `from`, `to` - 起止颜色
+/// * `amount` - `to` 所占的比例（千分比）
+pub fn mix(from: Rgb565, to: Rgb565, amount: u32) -> Rgb565 {
+    let amount = amount.min(FULL);
+    let channel = |a: u8, b: u8| ((a as u32 * (FULL - amount) + b as u32 * amount) / FULL) as u8;
+    Rgb565::new(
+        channel(from.r(), to.r()),
+        channel(from.g(), to.g()),
+        channel(from.b(), to.b()),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALL: [Easing; 5] = [
+        Easing::Linear,
+        Easing::EaseInQuad,
+        Easing::EaseOutQuad,
+        Easing::EaseInOutQuad,
+        Easing::EaseOutCubic,
+    ];
+
+    #[test]
+    fn easing_is_monotonic_between_endpoints() {
+        for easing in ALL {
+            assert_eq!(easing.apply(0), 0, "{easing:?}");
+            assert_eq!(easing.apply(FULL), FULL, "{easing:?}");
+            assert_eq!(easing.apply(FULL + 1), FULL, "{easing:?}");
+            for t in 1..=FULL {
+                assert!(easing.apply(t) >= easing.apply(t - 1), "{easing:?} at {t}");
+            }
+        }
+        assert!(Easing::EaseInQuad.apply(500) < 500);
+        assert!(Easing::EaseOutCubic.apply(500) > Easing::EaseOutQuad.apply(500));
+    }
+
+    #[test]
+    fn value_follows_time_not_frames() {
+        let mut tween = Tween::new(100, 300, 400, Easing::Linear);
+        assert_eq!(tween.value(5000), 100);
+        assert!(!tween.is_finished(5000));
+
+        tween.start(1000);
+        assert_eq!(tween.value(900), 100);
+        assert_eq!(tween.value(1100), 150);
+        assert_eq!(tween.value(1200), 200);
+        assert_eq!(tween.value(9999), 300);
+        assert!(tween.is_finished(1400));
+
+        let mut instant = Tween::new(0, -8, 0, Easing::EaseOutQuad);
+        instant.start(0);
+        assert_eq!(instant.value(0), -8);
+        assert_eq!(Tween::at(7).value(0), 7);
+    }
+
+    #[test]
+    fn retarget_continues_from_current_value() {
+        let mut bar = Tween::new(0, 100, 100, Easing::Linear);
+        bar.start(0);
+        bar.retarget(20, 50);
+        assert_eq!(bar.value(50), 50);
+        assert_eq!(bar.value(100), 35);
+        assert_eq!(bar.value(150), 20);
+        assert_eq!(bar.target(), 20);
+    }
+
+    #[test]
+    fn mix_blends_channels() {
+        assert_eq!(mix(Rgb565::BLACK, Rgb565::WHITE, 0), Rgb565::BLACK);
+        assert_eq!(mix(Rgb565::BLACK, Rgb565::WHITE, FULL), Rgb565::WHITE);
+        assert_eq!(
+            mix(Rgb565::BLACK, Rgb565::WHITE, 500),
+            Rgb565::new(15, 31, 15)
+        );
+    }
+}